@@ -0,0 +1,214 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Anchoring of the root hashes of a directory in external timestamping and notarization systems.
+//!
+//! An [Anchor] submits the root hash of an epoch to a system outside of the directory's control,
+//! and returns a receipt with which that system attests to having seen the root hash. Once a
+//! receipt is stored, the directory cannot later deny having committed to the root hash, nor
+//! claim to have committed to it at a later time, even if its signing keys are compromised.
+//!
+//! The current epoch is anchored with `Directory::anchor`, which stores the receipt as an
+//! [AnchorReceipt]. The receipts of an epoch are served alongside its root hash as an
+//! [AnchoredRoot] (e.g. by `Directory::lookup_anchored`), which a client checks with
+//! [AnchoredRoot::verify] before checking its proofs against the root hash.
+//!
+//! Two anchors are provided: [OpenTimestampsAnchor], which produces OpenTimestamps proofs, and
+//! [NotaryAnchor], which delegates to a function for any other notarization service.
+
+use crate::errors::{AkdError, DirectoryError};
+use crate::helper_structs::EpochHash;
+use crate::storage::types::AnchorReceipt;
+use crate::Digest;
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+
+/// An external system in which the root hashes of a directory are anchored
+#[async_trait]
+pub trait Anchor: Send + Sync {
+    /// The name under which the receipts of this anchor are stored, which must be unique
+    /// among the anchors of a directory
+    fn name(&self) -> &str;
+
+    /// Submits the root hash of an epoch to the external system, returning its receipt
+    async fn anchor(&self, epoch: u64, root_hash: Digest) -> Result<Vec<u8>, AkdError>;
+
+    /// Checks that a receipt produced by this anchor attests to the root hash of its epoch,
+    /// as far as can be done without contacting the external system. By default, receipts
+    /// are opaque and are not checked.
+    fn verify(&self, _receipt: &AnchorReceipt) -> Result<(), AkdError> {
+        Ok(())
+    }
+}
+
+/// The root hash of an epoch, together with the receipts of the anchors in which it has
+/// been anchored
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnchoredRoot {
+    /// The epoch and its root hash
+    pub epoch_hash: EpochHash,
+    /// The receipts of the epoch, ordered by the name of their anchor
+    pub receipts: Vec<AnchorReceipt>,
+}
+
+impl AnchoredRoot {
+    /// Returns the receipt of the named anchor, if the root hash has been anchored in it
+    pub fn receipt(&self, anchor: &str) -> Option<&AnchorReceipt> {
+        self.receipts
+            .iter()
+            .find(|receipt| receipt.anchor == anchor)
+    }
+
+    /// Checks that each receipt is for the epoch and root hash of this root, and that the
+    /// receipt of each of the provided anchors is present and passes [Anchor::verify]
+    pub fn verify(&self, anchors: &[&dyn Anchor]) -> Result<(), AkdError> {
+        let EpochHash(epoch, root_hash) = &self.epoch_hash;
+        if let Some(receipt) = self
+            .receipts
+            .iter()
+            .find(|receipt| receipt.epoch != *epoch || receipt.root_hash != *root_hash)
+        {
+            return Err(anchoring_error(format!(
+                "The receipt of anchor {} is for epoch {}, rather than the root hash of epoch {epoch}",
+                receipt.anchor, receipt.epoch
+            )));
+        }
+        for anchor in anchors {
+            let receipt = self.receipt(anchor.name()).ok_or_else(|| {
+                anchoring_error(format!(
+                    "Epoch {epoch} has not been anchored in {}",
+                    anchor.name()
+                ))
+            })?;
+            anchor.verify(receipt)?;
+        }
+        Ok(())
+    }
+}
+
+/// A client for an OpenTimestamps calendar server. The transport is left to the integrator,
+/// so that the library does not depend on an HTTP client.
+#[async_trait]
+pub trait CalendarClient: Send + Sync {
+    /// Submits a digest to the calendar (with a `POST` of the digest to its `/digest`
+    /// endpoint), returning the serialized timestamp in the body of the response
+    async fn submit_digest(&self, digest: &Digest) -> Result<Vec<u8>, AkdError>;
+}
+
+/// The magic bytes which begin an OpenTimestamps detached timestamp file
+const OTS_HEADER_MAGIC: &[u8] =
+    b"\x00OpenTimestamps\x00\x00Proof\x00\xbf\x89\xe2\xe8\x84\xe8\x92\x94";
+/// The major version of the detached timestamp file format
+const OTS_MAJOR_VERSION: u8 = 1;
+/// The tag of the SHA-256 operation, with which the timestamped digest is declared
+const OTS_OP_SHA256: u8 = 0x08;
+
+/// An anchor which timestamps root hashes with an OpenTimestamps calendar.
+///
+/// The root hash is submitted to the calendar directly as the timestamped digest, and each
+/// receipt is a detached timestamp file (`.ots`) for it. A calendar initially returns a
+/// pending attestation, which the standard OpenTimestamps tools can later upgrade to a
+/// Bitcoin attestation and verify with the root hash as the digest (e.g. with
+/// `ots verify -d <root hash in hex> <receipt>`).
+pub struct OpenTimestampsAnchor<C: CalendarClient> {
+    calendar: C,
+}
+
+impl<C: CalendarClient> OpenTimestampsAnchor<C> {
+    /// The name under which the receipts of this anchor are stored
+    pub const NAME: &'static str = "opentimestamps";
+
+    /// Creates an anchor which submits root hashes to the provided calendar
+    pub fn new(calendar: C) -> Self {
+        Self { calendar }
+    }
+}
+
+#[async_trait]
+impl<C: CalendarClient> Anchor for OpenTimestampsAnchor<C> {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    async fn anchor(&self, _epoch: u64, root_hash: Digest) -> Result<Vec<u8>, AkdError> {
+        let timestamp = self.calendar.submit_digest(&root_hash).await?;
+        if timestamp.is_empty() {
+            return Err(anchoring_error(
+                "The calendar returned an empty timestamp".to_string(),
+            ));
+        }
+        let mut receipt = OTS_HEADER_MAGIC.to_vec();
+        receipt.push(OTS_MAJOR_VERSION);
+        receipt.push(OTS_OP_SHA256);
+        receipt.extend_from_slice(&root_hash);
+        receipt.extend_from_slice(&timestamp);
+        Ok(receipt)
+    }
+
+    /// Checks that the receipt is a detached timestamp file for the root hash. The
+    /// attestations of the timestamp are not checked.
+    fn verify(&self, receipt: &AnchorReceipt) -> Result<(), AkdError> {
+        let timestamp = receipt
+            .receipt
+            .strip_prefix(OTS_HEADER_MAGIC)
+            .and_then(|rest| rest.strip_prefix(&[OTS_MAJOR_VERSION, OTS_OP_SHA256]))
+            .ok_or_else(|| {
+                anchoring_error("The receipt is not an OpenTimestamps proof".to_string())
+            })?;
+        match timestamp.strip_prefix(&receipt.root_hash[..]) {
+            Some(attestations) if !attestations.is_empty() => Ok(()),
+            Some(_) => Err(anchoring_error(
+                "The OpenTimestamps proof has no attestations".to_string(),
+            )),
+            None => Err(anchoring_error(format!(
+                "The OpenTimestamps proof is not for the root hash of epoch {}",
+                receipt.epoch
+            ))),
+        }
+    }
+}
+
+/// The function with which a [NotaryAnchor] notarizes the root hash of an epoch
+pub type NotarizeFn =
+    dyn Fn(u64, Digest) -> BoxFuture<'static, Result<Vec<u8>, AkdError>> + Send + Sync;
+
+/// An anchor which notarizes root hashes with a function provided by the integrator, such as
+/// a call to a company-internal notarization service. Its receipts are opaque to the library.
+pub struct NotaryAnchor {
+    name: String,
+    notarize: Box<NotarizeFn>,
+}
+
+impl NotaryAnchor {
+    /// Creates an anchor with the provided name, which notarizes each root hash by calling
+    /// `notarize` with the epoch and the root hash
+    pub fn new<F>(name: impl Into<String>, notarize: F) -> Self
+    where
+        F: Fn(u64, Digest) -> BoxFuture<'static, Result<Vec<u8>, AkdError>> + Send + Sync + 'static,
+    {
+        Self {
+            name: name.into(),
+            notarize: Box::new(notarize),
+        }
+    }
+}
+
+#[async_trait]
+impl Anchor for NotaryAnchor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn anchor(&self, epoch: u64, root_hash: Digest) -> Result<Vec<u8>, AkdError> {
+        (self.notarize)(epoch, root_hash).await
+    }
+}
+
+fn anchoring_error(message: String) -> AkdError {
+    AkdError::Directory(DirectoryError::Anchoring(message))
+}
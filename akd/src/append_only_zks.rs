@@ -7,8 +7,15 @@
 
 //! An implementation of an append-only zero knowledge set
 
+use crate::audit_lite::{
+    bucket_of, check_prefix_bits, AuditLiteProof, AuditLiteSnapshot, ChangedSubtree,
+};
 use crate::hash::EMPTY_DIGEST;
 use crate::helper_structs::LookupInfo;
+use crate::instrumentation;
+use crate::profiling::{Operation, Phase, PhaseTimer};
+use crate::proof_chunks::AppendOnlyProofChunk;
+use crate::spot_check::{target_direction, SpotCheckSample};
 use crate::storage::manager::StorageManager;
 use crate::storage::types::StorageType;
 use crate::tree_node::{
@@ -17,19 +24,21 @@ use crate::tree_node::{
 };
 use crate::Configuration;
 use crate::{
-    errors::{AkdError, DirectoryError, ParallelismError, TreeNodeError},
+    errors::{AkdError, DirectoryError, ParallelismError, StorageError, TreeNodeError},
     storage::{Database, Storable},
-    AppendOnlyProof, AzksElement, AzksValue, Digest, Direction, MembershipProof, NodeLabel,
-    NonMembershipProof, PrefixOrdering, SiblingProof, SingleAppendOnlyProof, SizeOf, ARITY,
+    AppendOnlyProof, AzksElement, AzksValue, Digest, Direction, EpochInsertions, MembershipProof,
+    MultiEpochAppendOnlyProof, NodeLabel, NonMembershipProof, PrefixOrdering, SiblingProof,
+    SingleAppendOnlyProof, SizeOf, ARITY,
 };
 use async_recursion::async_recursion;
-use log::info;
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 #[cfg(feature = "greedy_lookup_preload")]
 use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::marker::Sync;
 use std::ops::Deref;
+use tracing::info;
 
 /// The default azks key
 pub const DEFAULT_AZKS_KEY: u8 = 1u8;
@@ -73,8 +82,8 @@ fn get_parallel_levels() -> Option<u8> {
         let parallel_levels = (available_parallelism as f32).log2().ceil() as u8;
 
         info!(
-            "Insert will be performed in parallel (available parallelism: {}, parallel levels: {})",
-            available_parallelism, parallel_levels
+            available_parallelism,
+            parallel_levels, "Insert will be performed in parallel"
         );
         Some(parallel_levels)
     }
@@ -265,14 +274,12 @@ impl Storable for Azks {
     }
 
     fn get_full_binary_key_id(key: &u8) -> Vec<u8> {
-        vec![StorageType::Azks as u8, *key]
+        crate::storage::keys::encode_integer_key(StorageType::Azks, *key as u64)
     }
 
     fn key_from_full_binary(bin: &[u8]) -> Result<u8, String> {
-        if bin.is_empty() || bin[0] != StorageType::Azks as u8 {
-            return Err("Not an AZKS key".to_string());
-        }
-        Ok(DEFAULT_AZKS_KEY)
+        let key = crate::storage::keys::decode_integer_key(bin, StorageType::Azks)?;
+        u8::try_from(key).map_err(|_| format!("Azks key {key} out of range"))
     }
 }
 
@@ -300,14 +307,33 @@ impl Azks {
         storage: &StorageManager<S>,
         nodes: Vec<AzksElement>,
         insert_mode: InsertMode,
+    ) -> Result<(), AkdError> {
+        let mut timer = PhaseTimer::new(Operation::Publish);
+        self.batch_insert_nodes_timed::<TC, _>(storage, nodes, insert_mode, &mut timer)
+            .await
+    }
+
+    /// Insert a batch of new leaves, recording the preload and tree mutation phases
+    /// of the insertion with the provided timer
+    pub(crate) async fn batch_insert_nodes_timed<TC: Configuration, S: Database + 'static>(
+        &mut self,
+        storage: &StorageManager<S>,
+        nodes: Vec<AzksElement>,
+        insert_mode: InsertMode,
+        timer: &mut PhaseTimer,
     ) -> Result<(), AkdError> {
         let azks_element_set = AzksElementSet::from(nodes);
 
         // preload the nodes that we will visit during the insertion
-        let (_, time_s) = tic_toc(self.preload_nodes(storage, &azks_element_set)).await;
+        timer.begin(Phase::Preload);
+        let (preloaded, time_s) = tic_toc(self.preload_nodes(storage, &azks_element_set)).await;
         if let Some(time) = time_s {
-            info!("Preload of tree took {} s", time,);
+            info!(duration_s = time, "Preload of tree completed");
         }
+        if let Ok((_, load_bytes)) = preloaded {
+            timer.add_bytes(Phase::Preload, || load_bytes);
+        }
+        timer.begin(Phase::TreeMutation);
 
         // increment the current epoch
         self.increment_epoch();
@@ -324,11 +350,16 @@ impl Azks {
             )
             .await?;
             root_node.write_to_storage(storage, is_new).await?;
+            // The mutated nodes are staged in the transaction, if one is active
+            timer.add_bytes(Phase::TreeMutation, || storage.transaction_size_of());
 
             // update the number of nodes
             self.num_nodes += num_inserted;
 
-            info!("Batch insert completed ({} new nodes)", num_inserted);
+            info!(
+                epoch = self.latest_epoch,
+                num_inserted, "Batch insert completed"
+            );
         }
 
         Ok(())
@@ -433,7 +464,9 @@ impl Azks {
             if parallel_levels.is_some() {
                 // spawn a task and return the handle if there are still levels
                 // to be processed in parallel
-                Some(tokio::task::spawn(left_future))
+                Some(tokio::task::spawn(instrumentation::in_current_span(
+                    left_future,
+                )))
             } else {
                 // else handle the left child in the current task
                 let (mut left_node, left_is_new, left_num_inserted) = left_future.await?;
@@ -499,11 +532,7 @@ impl Azks {
                     .await
                 {
                     Some(crate::storage::types::DbRecord::TreeNode(tnpv)) => {
-                        if let Ok(node) = tnpv.determine_node_to_get(self.latest_epoch) {
-                            Some(node)
-                        } else {
-                            None
-                        }
+                        tnpv.determine_node_to_get(self.latest_epoch).ok()
                     }
                     _ => None,
                 }
@@ -514,11 +543,7 @@ impl Azks {
                     .await
                 {
                     Some(crate::storage::types::DbRecord::TreeNode(tnpv)) => {
-                        if let Ok(node) = tnpv.determine_node_to_get(self.latest_epoch) {
-                            Some(node)
-                        } else {
-                            None
-                        }
+                        tnpv.determine_node_to_get(self.latest_epoch).ok()
                     }
                     _ => None,
                 }
@@ -555,7 +580,7 @@ impl Azks {
             }
             // load the rest of the nodes in the path, as soon as a child node can't be resolved. In the worst-case
             // this is loading every possible node on the path (i.e. uninitialized cache)
-            for len in cnode.label.label_len..256 {
+            for len in cnode.label.label_len..label.label_len {
                 results.insert(label.get_prefix(len));
             }
         }
@@ -609,10 +634,10 @@ impl Azks {
             TreeNode::batch_get_from_storage(storage, &children, self.latest_epoch).await?;
         count += children.len() as u64;
 
-        log::info!(
-            "Greedy lookup proof preloading loaded {} of {} nodes",
-            count,
-            requested_count
+        info!(
+            num_loaded = count,
+            num_requested = requested_count,
+            "Greedy lookup proof preloading completed"
         );
 
         Ok(count)
@@ -636,20 +661,23 @@ impl Azks {
         // Load nodes.
         self.preload_nodes(storage, &AzksElementSet::from(lookup_nodes))
             .await
+            .map(|(load_count, _)| load_count)
     }
 
-    /// Preloads given nodes using breadth-first search.
+    /// Preloads given nodes using breadth-first search, returning the number of nodes
+    /// loaded and their total size in bytes.
     pub(crate) async fn preload_nodes<S: Database>(
         &self,
         storage: &StorageManager<S>,
         azks_element_set: &AzksElementSet,
-    ) -> Result<u64, AkdError> {
+    ) -> Result<(u64, usize), AkdError> {
         if !storage.has_cache() {
             info!("No cache found, skipping preload");
-            return Ok(0);
+            return Ok((0, 0));
         }
 
         let mut load_count: u64 = 0;
+        let mut load_bytes: usize = 0;
         let mut current_nodes = vec![NodeKey(NodeLabel::root())];
 
         while !current_nodes.is_empty() {
@@ -657,6 +685,7 @@ impl Azks {
                 TreeNode::batch_get_from_storage(storage, &current_nodes, self.get_latest_epoch())
                     .await?;
             load_count += nodes.len() as u64;
+            load_bytes += nodes.iter().map(|node| node.size_of()).sum::<usize>();
 
             // Now that states are loaded in the cache, we can read and access them.
             // Note, we perform directional loads to avoid accessing remote storage
@@ -673,9 +702,13 @@ impl Azks {
                 .collect();
         }
 
-        info!("Preload of tree ({} nodes) completed", load_count);
+        info!(
+            num_loaded = load_count,
+            num_bytes = load_bytes,
+            "Preload of tree completed"
+        );
 
-        Ok(load_count)
+        Ok((load_count, load_bytes))
     }
 
     /// Returns the Merkle membership proof for the trie as it stood at epoch
@@ -691,6 +724,58 @@ impl Azks {
         Ok(proof)
     }
 
+    /// Returns the leaf sampled by a spot check target, which is found by walking down from
+    /// the root along the bits of the target, along with the proof of its membership. Returns
+    /// [None] if the tree is empty. See [crate::spot_check].
+    pub async fn get_spot_check_sample<TC: Configuration, S: Database>(
+        &self,
+        storage: &StorageManager<S>,
+        target: &Digest,
+    ) -> Result<Option<SpotCheckSample>, AkdError> {
+        let mut sibling_proofs = Vec::new();
+        let latest_epoch = self.get_latest_epoch();
+
+        let mut curr_node =
+            TreeNode::get_from_storage(storage, &NodeKey(NodeLabel::root()), latest_epoch).await?;
+        while curr_node.node_type != TreeNodeType::Leaf {
+            let [left, right] = curr_node.get_child_nodes(storage, latest_epoch).await?;
+            // Only the root can have a single child, in which case the walk continues to it
+            // whatever the bit of the target
+            let (direction, child, sibling) = match (
+                target_direction(target, curr_node.label.label_len),
+                left,
+                right,
+            ) {
+                (_, None, None) => return Ok(None),
+                (_, Some(left), None) => (Direction::Left, left, None),
+                (_, None, Some(right)) => (Direction::Right, right, None),
+                (Direction::Left, Some(left), right) => (Direction::Left, left, right),
+                (Direction::Right, left, Some(right)) => (Direction::Right, right, left),
+            };
+            sibling_proofs.push(SiblingProof {
+                label: curr_node.label,
+                siblings: [AzksElement {
+                    label: node_to_label::<TC>(&sibling),
+                    value: node_to_azks_value::<TC>(&sibling, NodeHashingMode::WithLeafEpoch),
+                }],
+                direction,
+            });
+            curr_node = child;
+        }
+
+        Ok(Some(SpotCheckSample {
+            commitment: curr_node.hash,
+            leaf_epoch: curr_node.last_epoch,
+            proof: MembershipProof {
+                label: curr_node.label,
+                hash_val: AzksValue(
+                    TC::hash_leaf_with_commitment(curr_node.hash, curr_node.last_epoch).0,
+                ),
+                sibling_proofs,
+            },
+        }))
+    }
+
     /// In a compressed trie, the proof consists of the longest prefix
     /// of the label that is included in the trie, as well as its children, to show that
     /// none of the children is equal to the given label.
@@ -713,29 +798,13 @@ impl Azks {
         };
 
         let mut longest_prefix_children = [empty_azks_element; ARITY];
-        for (i, dir) in [Direction::Left, Direction::Right].iter().enumerate() {
-            match lcp_node
-                .get_child_node(storage, *dir, self.latest_epoch)
-                .await?
-            {
-                None => {
-                    longest_prefix_children[i] = empty_azks_element;
-                }
-                Some(child) => {
-                    let unwrapped_child: TreeNode = TreeNode::get_from_storage(
-                        storage,
-                        &NodeKey(child.label),
-                        self.get_latest_epoch(),
-                    )
-                    .await?;
-                    longest_prefix_children[i] = AzksElement {
-                        label: unwrapped_child.label,
-                        value: node_to_azks_value::<TC>(
-                            &Some(unwrapped_child),
-                            NodeHashingMode::WithLeafEpoch,
-                        ),
-                    };
-                }
+        let children = lcp_node.get_child_nodes(storage, self.latest_epoch).await?;
+        for (i, child) in children.into_iter().enumerate() {
+            if let Some(child) = child {
+                longest_prefix_children[i] = AzksElement {
+                    label: child.label,
+                    value: node_to_azks_value::<TC>(&Some(child), NodeHashingMode::WithLeafEpoch),
+                };
             }
         }
 
@@ -761,6 +830,20 @@ impl Azks {
         storage: &StorageManager<S>,
         start_epoch: u64,
         end_epoch: u64,
+    ) -> Result<AppendOnlyProof, AkdError> {
+        let mut timer = PhaseTimer::new(Operation::Audit);
+        self.get_append_only_proof_timed::<TC, _>(storage, start_epoch, end_epoch, &mut timer)
+            .await
+    }
+
+    /// Builds an append-only proof (see [Azks::get_append_only_proof]), recording the
+    /// preload and proof assembly phases of each epoch with the provided timer
+    pub(crate) async fn get_append_only_proof_timed<TC: Configuration, S: Database + 'static>(
+        &self,
+        storage: &StorageManager<S>,
+        start_epoch: u64,
+        end_epoch: u64,
+        timer: &mut PhaseTimer,
     ) -> Result<AppendOnlyProof, AkdError> {
         let latest_epoch = self.get_latest_epoch();
         if latest_epoch < end_epoch || end_epoch <= start_epoch {
@@ -780,6 +863,7 @@ impl Azks {
             TreeNode::get_from_storage(storage, &NodeKey(NodeLabel::root()), latest_epoch).await?;
 
         for ep in start_epoch..end_epoch {
+            timer.begin(Phase::Preload);
             let (fallable_load_count, time_s) = tic_toc(self.gather_audit_proof_nodes::<_>(
                 vec![node.clone()],
                 storage,
@@ -790,17 +874,21 @@ impl Azks {
             let load_count = fallable_load_count?;
             if let Some(time) = time_s {
                 info!(
-                    "Preload of nodes for audit ({} objects loaded), took {} s",
-                    load_count, time,
+                    epoch = ep,
+                    num_loaded = load_count,
+                    duration_s = time,
+                    "Preload of nodes for audit completed"
                 );
             } else {
                 info!(
-                    "Preload of nodes for audit ({} objects loaded) completed.",
-                    load_count
+                    epoch = ep,
+                    num_loaded = load_count,
+                    "Preload of nodes for audit completed"
                 );
             }
-            storage.log_metrics(log::Level::Info).await;
+            storage.log_metrics(tracing::Level::INFO).await;
 
+            timer.begin(Phase::ProofAssembly);
             let (unchanged, leaves) = Self::get_append_only_proof_helper::<TC, _>(
                 latest_epoch,
                 storage,
@@ -811,9 +899,13 @@ impl Azks {
                 get_parallel_levels(),
             )
             .await?;
-            info!("Generated audit proof for {} -> {}", ep, ep + 1);
+            info!(
+                start_epoch = ep,
+                end_epoch = ep + 1,
+                "Generated audit proof"
+            );
             proofs.push(SingleAppendOnlyProof {
-                inserted: leaves,
+                inserted: leaves.into_iter().map(|(_, leaf)| leaf).collect(),
                 unchanged_nodes: unchanged,
             });
             epochs.push(ep);
@@ -822,6 +914,350 @@ impl Azks {
         Ok(AppendOnlyProof { proofs, epochs })
     }
 
+    /// Builds a single [MultiEpochAppendOnlyProof] covering all of the epochs from `start_epoch`
+    /// to `end_epoch`, rather than one [SingleAppendOnlyProof] per epoch as in
+    /// [Azks::get_append_only_proof]. The nodes which remain unchanged since `start_epoch` are
+    /// only included once, and the leaves inserted after `start_epoch` and up until `end_epoch`
+    /// are chunked by the epoch in which they were inserted.
+    ///
+    /// **RESTRICTIONS**: The same restrictions on `start_epoch` and `end_epoch` apply as for
+    /// [Azks::get_append_only_proof].
+    pub async fn get_multi_epoch_append_only_proof<TC: Configuration, S: Database + 'static>(
+        &self,
+        storage: &StorageManager<S>,
+        start_epoch: u64,
+        end_epoch: u64,
+    ) -> Result<MultiEpochAppendOnlyProof, AkdError> {
+        let mut timer = PhaseTimer::new(Operation::Audit);
+        self.get_multi_epoch_append_only_proof_timed::<TC, _>(
+            storage,
+            start_epoch,
+            end_epoch,
+            &mut timer,
+        )
+        .await
+    }
+
+    /// Builds a multi-epoch append-only proof (see [Azks::get_multi_epoch_append_only_proof]),
+    /// recording the preload and proof assembly phases with the provided timer
+    pub(crate) async fn get_multi_epoch_append_only_proof_timed<
+        TC: Configuration,
+        S: Database + 'static,
+    >(
+        &self,
+        storage: &StorageManager<S>,
+        start_epoch: u64,
+        end_epoch: u64,
+        timer: &mut PhaseTimer,
+    ) -> Result<MultiEpochAppendOnlyProof, AkdError> {
+        let latest_epoch = self.get_latest_epoch();
+        if latest_epoch < end_epoch || end_epoch <= start_epoch {
+            return Err(AkdError::Directory(DirectoryError::InvalidEpoch(format!(
+                "Start epoch must be less than end epoch, and end epoch must be at most the latest epoch. \
+                Start epoch: {start_epoch}, end epoch: {end_epoch}, latest_epoch: {latest_epoch}."
+            ))));
+        }
+
+        let node =
+            TreeNode::get_from_storage(storage, &NodeKey(NodeLabel::root()), latest_epoch).await?;
+
+        timer.begin(Phase::Preload);
+        let load_count = self
+            .gather_audit_proof_nodes::<_>(vec![node.clone()], storage, start_epoch, end_epoch)
+            .await?;
+        info!(
+            start_epoch,
+            end_epoch,
+            num_loaded = load_count,
+            "Preload of nodes for multi-epoch audit completed"
+        );
+        storage.log_metrics(tracing::Level::INFO).await;
+
+        timer.begin(Phase::ProofAssembly);
+        let (unchanged_nodes, leaves) = Self::get_append_only_proof_helper::<TC, _>(
+            latest_epoch,
+            storage,
+            node,
+            start_epoch,
+            end_epoch,
+            0,
+            get_parallel_levels(),
+        )
+        .await?;
+
+        let mut chunks = BTreeMap::<u64, Vec<AzksElement>>::new();
+        for (epoch, leaf) in leaves {
+            chunks.entry(epoch).or_default().push(leaf);
+        }
+        let insertions = chunks
+            .into_iter()
+            .map(|(epoch, inserted)| EpochInsertions { epoch, inserted })
+            .collect();
+        info!(start_epoch, end_epoch, "Generated audit proof");
+
+        Ok(MultiEpochAppendOnlyProof {
+            start_epoch,
+            end_epoch,
+            unchanged_nodes,
+            insertions,
+        })
+    }
+
+    /// Builds the head chunk of the append-only proof from `epoch` to the epoch after it (see
+    /// [crate::proof_chunks]), which holds the elements of the proof found above `split_depth`
+    /// levels below the root, and lists the roots of the subtrees at that depth whose elements
+    /// are built by [Azks::get_append_only_proof_subtree]
+    pub(crate) async fn get_append_only_proof_head<TC: Configuration, S: Database + 'static>(
+        &self,
+        storage: &StorageManager<S>,
+        epoch: u64,
+        split_depth: u8,
+    ) -> Result<AppendOnlyProofChunk, AkdError> {
+        let latest_epoch = self.get_latest_epoch();
+        if latest_epoch <= epoch {
+            return Err(AkdError::Directory(DirectoryError::InvalidEpoch(format!(
+                "The proof from epoch {epoch} requires a later epoch, but the latest epoch is {latest_epoch}"
+            ))));
+        }
+
+        let mut head = AppendOnlyProofChunk {
+            epoch,
+            subtree: None,
+            subtrees: vec![],
+            inserted: vec![],
+            unchanged_nodes: vec![],
+        };
+        let root =
+            TreeNode::get_from_storage(storage, &NodeKey(NodeLabel::root()), latest_epoch).await?;
+        if root.get_latest_epoch() <= epoch {
+            // the root is unchanged since the epoch, as in get_append_only_proof_helper
+            return Ok(head);
+        }
+
+        // The nodes are classified as in get_append_only_proof_helper, level by level
+        let mut level = vec![root];
+        for depth in 0..=split_depth {
+            let mut next_level = vec![];
+            for node in level {
+                if node.get_latest_epoch() <= epoch {
+                    head.unchanged_nodes.push(AzksElement {
+                        label: node.label,
+                        value: node_to_azks_value::<TC>(
+                            &Some(node),
+                            NodeHashingMode::WithLeafEpoch,
+                        ),
+                    });
+                } else if node.min_descendant_epoch > epoch + 1 {
+                    continue;
+                } else if node.node_type == TreeNodeType::Leaf {
+                    head.inserted.push(AzksElement {
+                        label: node.label,
+                        value: node.hash,
+                    });
+                } else if depth == split_depth {
+                    head.subtrees.push(node.label);
+                } else {
+                    let [left_node, right_node] =
+                        node.get_child_nodes(storage, latest_epoch).await?;
+                    for (child_label, child_node) in
+                        [(node.left_child, left_node), (node.right_child, right_node)]
+                    {
+                        match (child_label, child_node) {
+                            (Some(child_label), None) => {
+                                return Err(AkdError::Storage(StorageError::NotFound(format!(
+                                    "TreeNodeWithPreviousValue {:?}",
+                                    NodeKey(child_label)
+                                ))))
+                            }
+                            (_, Some(child_node)) => next_level.push(child_node),
+                            (None, None) => {}
+                        }
+                    }
+                }
+            }
+            level = next_level;
+        }
+        Ok(head)
+    }
+
+    /// Builds the chunk of the append-only proof from `epoch` to the epoch after it which
+    /// holds the elements of the subtree rooted at the given node (see
+    /// [Azks::get_append_only_proof_head])
+    pub(crate) async fn get_append_only_proof_subtree<TC: Configuration, S: Database + 'static>(
+        &self,
+        storage: &StorageManager<S>,
+        epoch: u64,
+        subtree: NodeLabel,
+    ) -> Result<AppendOnlyProofChunk, AkdError> {
+        let latest_epoch = self.get_latest_epoch();
+        let node = TreeNode::get_from_storage(storage, &NodeKey(subtree), latest_epoch).await?;
+        let (unchanged_nodes, leaves) = Self::get_append_only_proof_helper::<TC, _>(
+            latest_epoch,
+            storage,
+            node,
+            epoch,
+            epoch + 1,
+            0,
+            None,
+        )
+        .await?;
+        Ok(AppendOnlyProofChunk {
+            epoch,
+            subtree: Some(subtree),
+            subtrees: vec![],
+            inserted: leaves.into_iter().map(|(_, leaf)| leaf).collect(),
+            unchanged_nodes,
+        })
+    }
+
+    /// Returns the [AuditLiteSnapshot] of the tree at the latest epoch (see [crate::audit_lite])
+    pub(crate) async fn get_audit_lite_snapshot<TC: Configuration, S: Database>(
+        &self,
+        storage: &StorageManager<S>,
+        prefix_bits: u8,
+    ) -> Result<AuditLiteSnapshot, AkdError> {
+        check_prefix_bits(prefix_bits)?;
+        let epoch = self.get_latest_epoch();
+        let subtrees = self
+            .get_subtree_roots(storage, epoch, prefix_bits)
+            .await?
+            .into_iter()
+            .map(|node| AzksElement {
+                label: node.label,
+                value: node_to_azks_value::<TC>(&Some(node), NodeHashingMode::WithLeafEpoch),
+            })
+            .collect();
+        Ok(AuditLiteSnapshot {
+            epoch,
+            prefix_bits,
+            subtrees,
+        })
+    }
+
+    /// Returns the [AuditLiteProof] of the changes to the top-level subtrees of the tree from the
+    /// epoch before the latest epoch to the latest epoch (see [crate::audit_lite]). This relies on
+    /// the previous values of the nodes, so it must be generated before the next publish.
+    pub(crate) async fn get_audit_lite_proof<TC: Configuration, S: Database>(
+        &self,
+        storage: &StorageManager<S>,
+        prefix_bits: u8,
+    ) -> Result<AuditLiteProof, AkdError> {
+        check_prefix_bits(prefix_bits)?;
+        let end_epoch = self.get_latest_epoch();
+        if end_epoch == 0 {
+            return Err(AkdError::Directory(DirectoryError::InvalidEpoch(
+                "No epoch has been published yet".to_string(),
+            )));
+        }
+        let start_epoch = end_epoch - 1;
+        let element = |node: &TreeNode| AzksElement {
+            label: node.label,
+            value: node_to_azks_value::<TC>(&Some(node.clone()), NodeHashingMode::WithLeafEpoch),
+        };
+
+        let previous = self
+            .get_subtree_roots(storage, start_epoch, prefix_bits)
+            .await?
+            .iter()
+            .map(|node| (bucket_of(&node.label, prefix_bits), element(node)))
+            .collect::<BTreeMap<_, _>>();
+        let mut changed = vec![];
+        let mut num_inserted = 0;
+        for node in self
+            .get_subtree_roots(storage, end_epoch, prefix_bits)
+            .await?
+        {
+            let current = element(&node);
+            let previous = previous.get(&bucket_of(&node.label, prefix_bits)).copied();
+            if previous == Some(current) {
+                continue;
+            }
+            let inserted = self.count_leaves_inserted(storage, &node).await?;
+            num_inserted += inserted;
+            changed.push(ChangedSubtree {
+                previous,
+                current,
+                num_inserted: inserted,
+            });
+        }
+        Ok(AuditLiteProof {
+            start_epoch,
+            end_epoch,
+            prefix_bits,
+            changed,
+            num_inserted,
+        })
+    }
+
+    /// Returns the roots of the top-level subtrees of the tree as of `epoch`, whose labels have
+    /// at least `prefix_bits` bits while their parents' labels do not, ordered by bucket. The epoch
+    /// must either be the latest epoch or precede only the most recent change of each node.
+    async fn get_subtree_roots<S: Database>(
+        &self,
+        storage: &StorageManager<S>,
+        epoch: u64,
+        prefix_bits: u8,
+    ) -> Result<Vec<TreeNode>, AkdError> {
+        let mut roots = vec![];
+        let mut level =
+            vec![TreeNode::get_from_storage(storage, &NodeKey(NodeLabel::root()), epoch).await?];
+        while !level.is_empty() {
+            let mut children = vec![];
+            for node in level {
+                if node.label.label_len >= u32::from(prefix_bits) {
+                    roots.push(node);
+                } else {
+                    children.extend(
+                        [node.left_child, node.right_child]
+                            .into_iter()
+                            .flatten()
+                            .map(NodeKey),
+                    );
+                }
+            }
+            if children.is_empty() {
+                break;
+            }
+            level = TreeNode::batch_get_from_storage(storage, &children, epoch).await?;
+        }
+        roots.sort_by_key(|node| node.label.label_val);
+        Ok(roots)
+    }
+
+    /// Counts the leaves inserted in the latest epoch into the subtree rooted at the given node
+    async fn count_leaves_inserted<S: Database>(
+        &self,
+        storage: &StorageManager<S>,
+        node: &TreeNode,
+    ) -> Result<u64, AkdError> {
+        let latest_epoch = self.get_latest_epoch();
+        let mut count = 0;
+        let mut level = vec![node.clone()];
+        while !level.is_empty() {
+            let mut children = vec![];
+            for node in level {
+                if node.last_epoch != latest_epoch {
+                    continue;
+                }
+                if node.node_type == TreeNodeType::Leaf {
+                    count += 1;
+                } else {
+                    children.extend(
+                        [node.left_child, node.right_child]
+                            .into_iter()
+                            .flatten()
+                            .map(NodeKey),
+                    );
+                }
+            }
+            if children.is_empty() {
+                break;
+            }
+            level = TreeNode::batch_get_from_storage(storage, &children, latest_epoch).await?;
+        }
+        Ok(count)
+    }
+
     fn determine_retrieval_nodes(
         node: &TreeNode,
         start_epoch: u64,
@@ -890,7 +1326,7 @@ impl Azks {
         parallel_levels: Option<u8>,
     ) -> Result<AppendOnlyHelper, AkdError> {
         let mut unchanged = Vec::<AzksElement>::new();
-        let mut leaves = Vec::<AzksElement>::new();
+        let mut leaves = Vec::<(u64, AzksElement)>::new();
 
         if node.get_latest_epoch() <= start_epoch {
             if node.node_type == TreeNodeType::Root {
@@ -910,46 +1346,73 @@ impl Azks {
         }
 
         if node.node_type == TreeNodeType::Leaf {
-            leaves.push(AzksElement {
-                label: node.label,
-                value: node.hash,
-            });
+            leaves.push((
+                node.last_epoch,
+                AzksElement {
+                    label: node.label,
+                    value: node.hash,
+                },
+            ));
         } else {
-            let maybe_task: Option<
-                tokio::task::JoinHandle<Result<(Vec<AzksElement>, Vec<AzksElement>), AkdError>>,
-            > = if let Some(left_child) = node.left_child {
-                #[cfg(feature = "parallel_insert")]
-                {
-                    if parallel_levels.map(|p| p as u64 > level).unwrap_or(false) {
-                        // we can parallelise further!
-                        let storage_clone = storage.clone();
-                        let tsk: tokio::task::JoinHandle<Result<_, AkdError>> =
-                            tokio::spawn(async move {
-                                let my_storage = storage_clone;
-                                let child_node = TreeNode::get_from_storage(
-                                    &my_storage,
-                                    &NodeKey(left_child),
-                                    latest_epoch,
-                                )
-                                .await?;
+            // Both children are retrieved together, and must both exist if they are set
+            let [left_node, right_node] = node.get_child_nodes(storage, latest_epoch).await?;
+            for (child_label, child_node) in [
+                (node.left_child, &left_node),
+                (node.right_child, &right_node),
+            ] {
+                if let (Some(child_label), None) = (child_label, child_node) {
+                    return Err(AkdError::Storage(StorageError::NotFound(format!(
+                        "TreeNodeWithPreviousValue {:?}",
+                        NodeKey(child_label)
+                    ))));
+                }
+            }
+
+            let maybe_task: Option<tokio::task::JoinHandle<Result<AppendOnlyHelper, AkdError>>> =
+                if let Some(child_node) = left_node {
+                    #[cfg(feature = "parallel_insert")]
+                    {
+                        if parallel_levels.map(|p| p as u64 > level).unwrap_or(false) {
+                            // we can parallelise further!
+                            let storage_clone = storage.clone();
+                            let tsk: tokio::task::JoinHandle<Result<_, AkdError>> =
+                                tokio::spawn(instrumentation::in_current_span(async move {
+                                    let my_storage = storage_clone;
+                                    Self::get_append_only_proof_helper::<TC, _>(
+                                        latest_epoch,
+                                        &my_storage,
+                                        child_node,
+                                        start_epoch,
+                                        end_epoch,
+                                        level + 1,
+                                        parallel_levels,
+                                    )
+                                    .await
+                                }));
+
+                            Some(tsk)
+                        } else {
+                            // Enough parallelism already, STOP IT! Don't make me get the belt!
+                            let (mut inner_unchanged, mut inner_leaf) =
                                 Self::get_append_only_proof_helper::<TC, _>(
                                     latest_epoch,
-                                    &my_storage,
+                                    storage,
                                     child_node,
                                     start_epoch,
                                     end_epoch,
                                     level + 1,
                                     parallel_levels,
                                 )
-                                .await
-                            });
-
-                        Some(tsk)
-                    } else {
-                        // Enough parallelism already, STOP IT! Don't make me get the belt!
-                        let child_node =
-                            TreeNode::get_from_storage(storage, &NodeKey(left_child), latest_epoch)
                                 .await?;
+                            unchanged.append(&mut inner_unchanged);
+                            leaves.append(&mut inner_leaf);
+                            None
+                        }
+                    }
+
+                    #[cfg(not(feature = "parallel_insert"))]
+                    {
+                        // NO Parallelism, BAD! parallelism. Get your nose out of the garbage!
                         let (mut inner_unchanged, mut inner_leaf) =
                             Self::get_append_only_proof_helper::<TC, _>(
                                 latest_epoch,
@@ -965,37 +1428,11 @@ impl Azks {
                         leaves.append(&mut inner_leaf);
                         None
                     }
-                }
-
-                #[cfg(not(feature = "parallel_insert"))]
-                {
-                    // NO Parallelism, BAD! parallelism. Get your nose out of the garbage!
-                    let child_node =
-                        TreeNode::get_from_storage(storage, &NodeKey(left_child), latest_epoch)
-                            .await?;
-                    let (mut inner_unchanged, mut inner_leaf) =
-                        Self::get_append_only_proof_helper::<TC, _>(
-                            latest_epoch,
-                            storage,
-                            child_node,
-                            start_epoch,
-                            end_epoch,
-                            level + 1,
-                            parallel_levels,
-                        )
-                        .await?;
-                    unchanged.append(&mut inner_unchanged);
-                    leaves.append(&mut inner_leaf);
+                } else {
                     None
-                }
-            } else {
-                None
-            };
+                };
 
-            if let Some(right_child) = node.right_child {
-                let child_node =
-                    TreeNode::get_from_storage(storage, &NodeKey(right_child), latest_epoch)
-                        .await?;
+            if let Some(child_node) = right_node {
                 let (mut inner_unchanged, mut inner_leaf) =
                     Self::get_append_only_proof_helper::<TC, _>(
                         latest_epoch,
@@ -1062,20 +1499,10 @@ impl Azks {
         self.latest_epoch = epoch;
     }
 
-    /// Gets the sibling node of the passed node's child in the "opposite" of the passed direction.
-    async fn get_child_azks_element_in_dir<TC: Configuration, S: Database>(
-        &self,
-        storage: &StorageManager<S>,
-        curr_node: &TreeNode,
-        dir: Direction,
-        latest_epoch: u64,
-    ) -> Result<AzksElement, AkdError> {
-        // Find the sibling in the "other" direction
-        let sibling = curr_node.get_child_node(storage, dir, latest_epoch).await?;
-        Ok(AzksElement {
-            label: node_to_label::<TC>(&sibling),
-            value: node_to_azks_value::<TC>(&sibling, NodeHashingMode::WithLeafEpoch),
-        })
+    /// Skips the epochs before `next_epoch`, which becomes the epoch of the next batch
+    /// insertion. The skipped epochs hold the same tree as the latest epoch.
+    pub(crate) fn skip_to_epoch(&mut self, next_epoch: u64) {
+        self.latest_epoch = self.latest_epoch.max(next_epoch.saturating_sub(1));
     }
 
     /// This function returns the node label for the node whose label is the longest common
@@ -1095,54 +1522,46 @@ impl Azks {
 
         let mut prefix_ordering = curr_node.label.get_prefix_ordering(label);
         let mut equal = label == curr_node.label;
-        let mut prev_node = curr_node.clone();
+        let mut prev_node = None;
         while !equal && prefix_ordering != PrefixOrdering::Invalid {
             let direction = Direction::try_from(prefix_ordering).map_err(|_| {
                 AkdError::TreeNode(TreeNodeError::NoDirection(curr_node.label, None))
             })?;
-            let child = curr_node
-                .get_child_node(storage, direction, latest_epoch)
-                .await?;
-            if child.is_none() {
+            // Both children are retrieved together, since the sibling of the child on the
+            // path to the label is needed for the proof
+            let [left, right] = curr_node.get_child_nodes(storage, latest_epoch).await?;
+            let (child, sibling) = match direction {
+                Direction::Left => (left, right),
+                Direction::Right => (right, left),
+            };
+            let Some(child) = child else {
                 // Special case, if the root node has a direction with no child there
                 break;
-            }
+            };
 
             // Find the sibling node. Note that for ARITY = 2, this does not need to be
             // an array, as it can just be a single node.
-            let child_azks_element = self
-                .get_child_azks_element_in_dir::<TC, _>(
-                    storage,
-                    &curr_node,
-                    direction.other(),
-                    latest_epoch,
-                )
-                .await?;
+            let child_azks_element = AzksElement {
+                label: node_to_label::<TC>(&sibling),
+                value: node_to_azks_value::<TC>(&sibling, NodeHashingMode::WithLeafEpoch),
+            };
             sibling_proofs.push(SiblingProof {
                 label: curr_node.label,
                 siblings: [child_azks_element],
                 direction,
             });
 
-            prev_node = curr_node.clone();
-            match curr_node
-                .get_child_node(storage, direction, latest_epoch)
-                .await?
-            {
-                Some(n) => curr_node = n,
-                None => {
-                    return Err(AkdError::TreeNode(TreeNodeError::NoChildAtEpoch(
-                        latest_epoch,
-                        direction,
-                    )));
-                }
-            }
+            // Move down to the child, keeping the current node around in case
+            // the child turns out not to be on the path to the label
+            prev_node = Some(std::mem::replace(&mut curr_node, child));
             prefix_ordering = curr_node.label.get_prefix_ordering(label);
             equal = label == curr_node.label;
         }
 
         if !equal {
-            curr_node = prev_node;
+            if let Some(prev_node) = prev_node {
+                curr_node = prev_node;
+            }
             sibling_proofs.pop();
         }
         let hash_val = if curr_node.node_type == TreeNodeType::Leaf {
@@ -1162,7 +1581,8 @@ impl Azks {
     }
 }
 
-type AppendOnlyHelper = (Vec<AzksElement>, Vec<AzksElement>);
+/// The unchanged nodes, and the inserted leaves along with their epoch of insertion
+type AppendOnlyHelper = (Vec<AzksElement>, Vec<(u64, AzksElement)>);
 
 #[cfg(test)]
 mod tests {
@@ -1208,6 +1628,7 @@ mod tests {
                 username: crate::AkdLabel::random(&mut rng),
                 value: crate::AkdValue::random(&mut rng),
                 version: 1,
+                retention_class: None,
             },
         };
 
@@ -1549,7 +1970,7 @@ mod tests {
             },
         ]);
         let expected_preload_count = 3u64;
-        let actual_preload_count = azks
+        let (actual_preload_count, _) = azks
             .preload_nodes(&storage_manager, &azks_element_set)
             .await
             .expect("Failed to preload nodes");
@@ -1640,8 +2061,8 @@ mod tests {
         Ok(())
     }
 
-    test_config!(test_get_child_azks_element);
-    async fn test_get_child_azks_element<TC: Configuration>() -> Result<(), AkdError> {
+    test_config!(test_get_child_nodes);
+    async fn test_get_child_nodes<TC: Configuration>() -> Result<(), AkdError> {
         let num_nodes = 5;
         let mut rng = StdRng::seed_from_u64(42);
 
@@ -1669,40 +2090,27 @@ mod tests {
             // Recursively traverse the tree and check that the sibling of each node is correct
             let root_node = TreeNode::get_from_storage(&db, &NodeKey(NodeLabel::root()), 1).await?;
             let mut nodes: Vec<TreeNode> = vec![root_node];
-            while !nodes.is_empty() {
-                let current_node = nodes.pop().unwrap();
-
+            while let Some(current_node) = nodes.pop() {
                 let left_child = current_node.get_child_node(&db, Direction::Left, 1).await?;
                 let right_child = current_node
                     .get_child_node(&db, Direction::Right, 1)
                     .await?;
+                // The children retrieved together are the same as those retrieved individually
+                let [left_sibling, right_sibling] = current_node.get_child_nodes(&db, 1).await?;
+                assert_eq!(
+                    left_child.as_ref().map(|node| node.label),
+                    left_sibling.map(|node| node.label)
+                );
+                assert_eq!(
+                    right_child.as_ref().map(|node| node.label),
+                    right_sibling.map(|node| node.label)
+                );
 
                 if let Some(left_child) = left_child {
-                    let sibling_label = azks
-                        .get_child_azks_element_in_dir::<TC, _>(
-                            &db,
-                            &current_node,
-                            Direction::Left,
-                            1,
-                        )
-                        .await?
-                        .label;
-                    assert_eq!(left_child.label, sibling_label);
                     nodes.push(left_child);
                 }
 
                 if let Some(right_child) = right_child {
-                    println!("right_child.label: {:?}", right_child.label);
-                    let sibling_label = azks
-                        .get_child_azks_element_in_dir::<TC, _>(
-                            &db,
-                            &current_node,
-                            Direction::Right,
-                            1,
-                        )
-                        .await?
-                        .label;
-                    assert_eq!(right_child.label, sibling_label);
                     nodes.push(right_child);
                 }
             }
@@ -0,0 +1,255 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Lightweight audits, for auditors which cannot afford to download a full append-only proof
+//! for every epoch, but can check each epoch continuously and request the full proof of an
+//! epoch (e.g. with `Directory::audit`) only when something looks wrong.
+//!
+//! The label space is divided into `2^prefix_bits` buckets by the first `prefix_bits` bits of
+//! the labels. The top-level subtree of a bucket is rooted at the highest node whose label has
+//! at least `prefix_bits` bits, and the digests of the top-level subtrees determine the root
+//! hash. An auditor starts from an [AuditLiteSnapshot] of the digests of every top-level
+//! subtree, and checks it against the root hash of its epoch with [AuditLiteState::new]. For
+//! each later epoch, the directory's `Directory::audit_lite` returns an [AuditLiteProof] with
+//! the digests of only the subtrees which changed, along with the number of leaves inserted
+//! into each, and the auditor checks it against the next root hash with
+//! [AuditLiteState::apply].
+//!
+//! A lightweight audit checks that the subtrees which were not reported as changed are indeed
+//! unchanged, and that each changed subtree only grew, in that its new root is an ancestor of
+//! (or is) its previous root. Unlike a full append-only proof, it cannot check that no leaf
+//! was removed or modified within a changed subtree, nor that the counts of inserted leaves
+//! are correct.
+
+use crate::append_only_zks::{Azks, InsertMode};
+use crate::errors::{AkdError, AuditorError};
+use crate::storage::manager::StorageManager;
+use crate::storage::memory::AsyncInMemoryDatabase;
+use crate::{AzksElement, Configuration, Digest, NodeLabel};
+
+use std::collections::BTreeMap;
+
+/// The maximum number of bits of the prefixes which divide the label space into buckets
+pub const MAX_PREFIX_BITS: u8 = 16;
+
+/// The digests of every top-level subtree of the tree at an epoch
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct AuditLiteSnapshot {
+    /// The epoch of the snapshot
+    pub epoch: u64,
+    /// The number of bits of the prefixes which divide the label space into buckets
+    pub prefix_bits: u8,
+    /// The roots of the top-level subtrees, ordered by bucket
+    pub subtrees: Vec<AzksElement>,
+}
+
+/// A top-level subtree which changed between the epochs of an [AuditLiteProof]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct ChangedSubtree {
+    /// The root of the subtree at the start epoch, or [None] if its bucket was empty
+    pub previous: Option<AzksElement>,
+    /// The root of the subtree at the end epoch
+    pub current: AzksElement,
+    /// The number of leaves inserted into the subtree
+    pub num_inserted: u64,
+}
+
+/// The top-level subtrees which changed from one epoch to the next
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct AuditLiteProof {
+    /// The epoch from which the subtrees changed. Any epochs between it and the end epoch
+    /// hold the same tree as it.
+    pub start_epoch: u64,
+    /// The epoch to which the subtrees changed
+    pub end_epoch: u64,
+    /// The number of bits of the prefixes which divide the label space into buckets
+    pub prefix_bits: u8,
+    /// The changed subtrees, ordered by bucket
+    pub changed: Vec<ChangedSubtree>,
+    /// The total number of leaves inserted
+    pub num_inserted: u64,
+}
+
+/// Checks that the number of prefix bits is supported
+pub(crate) fn check_prefix_bits(prefix_bits: u8) -> Result<(), AkdError> {
+    if prefix_bits == 0 || prefix_bits > MAX_PREFIX_BITS {
+        return Err(audit_error(format!(
+            "The number of prefix bits must be between 1 and {MAX_PREFIX_BITS}, but is {prefix_bits}"
+        )));
+    }
+    Ok(())
+}
+
+/// The bucket of a label, given by the first `prefix_bits` bits of the label, which must be
+/// at most [MAX_PREFIX_BITS]
+pub fn bucket_of(label: &NodeLabel, prefix_bits: u8) -> u64 {
+    let mut prefix = [0u8; 8];
+    prefix.copy_from_slice(&label.label_val[..8]);
+    u64::from_be_bytes(prefix) >> (64 - u32::from(prefix_bits))
+}
+
+fn audit_error(message: String) -> AkdError {
+    AkdError::AuditErr(AuditorError::VerifyAuditProof(message))
+}
+
+/// The state of a lightweight auditor: the digests of the top-level subtrees at the epoch
+/// which it last checked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditLiteState {
+    epoch: u64,
+    prefix_bits: u8,
+    subtrees: BTreeMap<u64, AzksElement>,
+}
+
+impl AuditLiteState {
+    /// Starts a lightweight audit from a snapshot, which is checked against the root hash of
+    /// its epoch
+    pub async fn new<TC: Configuration>(
+        snapshot: AuditLiteSnapshot,
+        root_hash: Digest,
+    ) -> Result<Self, AkdError> {
+        check_prefix_bits(snapshot.prefix_bits)?;
+        let mut subtrees = BTreeMap::new();
+        for subtree in snapshot.subtrees {
+            check_subtree_root(&subtree, snapshot.prefix_bits)?;
+            let bucket = bucket_of(&subtree.label, snapshot.prefix_bits);
+            if subtrees.insert(bucket, subtree).is_some() {
+                return Err(audit_error(format!(
+                    "The snapshot has more than one subtree in bucket {bucket}"
+                )));
+            }
+        }
+        let state = Self {
+            epoch: snapshot.epoch,
+            prefix_bits: snapshot.prefix_bits,
+            subtrees,
+        };
+        state.check_root_hash::<TC>(root_hash).await?;
+        Ok(state)
+    }
+
+    /// The epoch which the auditor last checked
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Checks the changes to the top-level subtrees from the auditor's epoch (or a later epoch
+    /// which holds the same tree) to the end epoch of the proof, against the root hash of the
+    /// end epoch. The state is advanced to the end epoch if the check succeeds, and is left
+    /// unchanged otherwise.
+    pub async fn apply<TC: Configuration>(
+        &mut self,
+        proof: &AuditLiteProof,
+        end_root_hash: Digest,
+    ) -> Result<(), AkdError> {
+        if proof.prefix_bits != self.prefix_bits {
+            return Err(audit_error(format!(
+                "The proof has {} prefix bits, but the audit has {}",
+                proof.prefix_bits, self.prefix_bits
+            )));
+        }
+        if proof.start_epoch < self.epoch || proof.end_epoch <= proof.start_epoch {
+            return Err(audit_error(format!(
+                "The proof from epoch {} to epoch {} does not follow epoch {}",
+                proof.start_epoch, proof.end_epoch, self.epoch
+            )));
+        }
+
+        let mut subtrees = self.subtrees.clone();
+        let mut previous_bucket = None;
+        let mut num_inserted = 0u64;
+        for change in proof.changed.iter() {
+            check_subtree_root(&change.current, self.prefix_bits)?;
+            let bucket = bucket_of(&change.current.label, self.prefix_bits);
+            if previous_bucket.is_some_and(|previous| previous >= bucket) {
+                return Err(audit_error(format!(
+                    "The change to bucket {bucket} is out of order"
+                )));
+            }
+            previous_bucket = Some(bucket);
+
+            if change.previous.as_ref() != self.subtrees.get(&bucket) {
+                return Err(audit_error(format!(
+                    "The previous root of the subtree in bucket {bucket} does not match the audited root"
+                )));
+            }
+            if let Some(previous) = change.previous {
+                // The new root of a subtree which only grew is an ancestor of its old root
+                if !change.current.label.is_prefix_of(&previous.label) || change.current == previous
+                {
+                    return Err(audit_error(format!(
+                        "The subtree in bucket {bucket} did not grow from its previous root"
+                    )));
+                }
+            }
+            if change.num_inserted == 0 {
+                return Err(audit_error(format!(
+                    "The subtree in bucket {bucket} changed without any leaves being inserted"
+                )));
+            }
+            num_inserted = num_inserted.saturating_add(change.num_inserted);
+            subtrees.insert(bucket, change.current);
+        }
+        if num_inserted != proof.num_inserted {
+            return Err(audit_error(format!(
+                "The subtrees have {num_inserted} inserted leaves, but the proof reports {}",
+                proof.num_inserted
+            )));
+        }
+
+        let state = Self {
+            epoch: proof.end_epoch,
+            prefix_bits: self.prefix_bits,
+            subtrees,
+        };
+        state.check_root_hash::<TC>(end_root_hash).await?;
+        *self = state;
+        Ok(())
+    }
+
+    /// Checks that the top-level subtrees form the tree with the given root hash
+    async fn check_root_hash<TC: Configuration>(&self, root_hash: Digest) -> Result<(), AkdError> {
+        let manager = StorageManager::new_no_cache(AsyncInMemoryDatabase::new());
+        let mut azks = Azks::new::<TC, _>(&manager).await?;
+        azks.batch_insert_nodes::<TC, _>(
+            &manager,
+            self.subtrees.values().copied().collect(),
+            InsertMode::Auditor,
+        )
+        .await?;
+        if azks.get_root_hash::<TC, _>(&manager).await? != root_hash {
+            return Err(audit_error(format!(
+                "The subtrees do not match the root hash of epoch {}",
+                self.epoch
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Checks that a subtree root is long enough to lie within a single bucket
+fn check_subtree_root(root: &AzksElement, prefix_bits: u8) -> Result<(), AkdError> {
+    if root.label.label_len < u32::from(prefix_bits) {
+        return Err(audit_error(format!(
+            "The subtree root {:?} is shorter than the {prefix_bits} prefix bits",
+            root.label
+        )));
+    }
+    Ok(())
+}
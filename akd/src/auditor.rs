@@ -14,7 +14,7 @@ use crate::{
     append_only_zks::InsertMode,
     errors::{AkdError, AuditorError, AzksError},
     storage::{manager::StorageManager, memory::AsyncInMemoryDatabase},
-    AppendOnlyProof, Azks, Digest, SingleAppendOnlyProof,
+    AppendOnlyProof, Azks, Digest, MultiEpochAppendOnlyProof, SingleAppendOnlyProof,
 };
 
 /// Verifies an audit proof, given start and end hashes for a merkle patricia tree.
@@ -86,3 +86,60 @@ pub async fn verify_consecutive_append_only<TC: Configuration>(
     }
     Ok(())
 }
+
+/// Verifies a [MultiEpochAppendOnlyProof], given the root hashes at the start and end
+/// epochs of the proof. The tree is rebuilt from the unchanged nodes and compared against
+/// the start hash, then each chunk of inserted leaves is applied in order of epoch and the
+/// final tree is compared against the end hash.
+pub async fn audit_verify_multi_epoch<TC: Configuration>(
+    start_hash: Digest,
+    end_hash: Digest,
+    proof: &MultiEpochAppendOnlyProof,
+) -> Result<(), AkdError> {
+    if proof.start_epoch >= proof.end_epoch {
+        return Err(AkdError::AuditErr(AuditorError::VerifyAuditProof(format!(
+            "The start epoch {} of the proof must be less than its end epoch {}",
+            proof.start_epoch, proof.end_epoch
+        ))));
+    }
+    let mut previous_epoch = proof.start_epoch;
+    for chunk in proof.insertions.iter() {
+        if chunk.epoch <= previous_epoch || chunk.epoch > proof.end_epoch {
+            return Err(AkdError::AuditErr(AuditorError::VerifyAuditProof(format!(
+                "The proof contains leaves inserted in epoch {}, which is out of order or \
+                outside of the epochs ({}, {}] covered by the proof",
+                chunk.epoch, proof.start_epoch, proof.end_epoch
+            ))));
+        }
+        previous_epoch = chunk.epoch;
+    }
+
+    let db = AsyncInMemoryDatabase::new();
+    let manager = StorageManager::new_no_cache(db);
+
+    let mut azks = Azks::new::<TC, _>(&manager).await?;
+    azks.batch_insert_nodes::<TC, _>(&manager, proof.unchanged_nodes.clone(), InsertMode::Auditor)
+        .await?;
+    let computed_start_root_hash: Digest = azks.get_root_hash::<TC, _>(&manager).await?;
+    let mut verified = computed_start_root_hash == start_hash;
+    for chunk in proof.insertions.iter() {
+        azks.latest_epoch = chunk.epoch - 1;
+        let updated_inserted = chunk
+            .inserted
+            .iter()
+            .map(|x| {
+                let mut y = *x;
+                y.value = AzksValue(TC::hash_leaf_with_commitment(x.value, chunk.epoch).0);
+                y
+            })
+            .collect();
+        azks.batch_insert_nodes::<TC, _>(&manager, updated_inserted, InsertMode::Auditor)
+            .await?;
+    }
+    let computed_end_root_hash: Digest = azks.get_root_hash::<TC, _>(&manager).await?;
+    verified = verified && (computed_end_root_hash == end_hash);
+    if !verified {
+        return Err(AkdError::AzksErr(AzksError::VerifyAppendOnlyProof));
+    }
+    Ok(())
+}
@@ -0,0 +1,125 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Coalescing of concurrent lookups of the same label.
+//!
+//! When many clients look up the same label at once (e.g. right after it has been updated),
+//! each of their lookups traverses the tree to generate the same proof. With
+//! `Directory::set_lookup_coalescing`, concurrent lookups of a label at the same epoch are
+//! coalesced into a single flight: the first lookup generates the proof, and the others wait
+//! for it and are answered with a clone of the same proof. A proof is only shared between
+//! lookups which are served at the same epoch and root hash, so that a lookup is never
+//! answered with a proof of another epoch.
+//!
+//! An error is not shared: if the lookup which generates the proof fails (or is cancelled),
+//! one of the waiting lookups generates the proof in its place. Only lookups which overlap in
+//! time are coalesced, and the proofs can also be reused across lookups which do not with
+//! the proof cache (see [crate::proof_cache]). The coalesced lookups are counted by
+//! [LookupCoalescingMetrics], and by the `akd_coalesced_lookups_total` metric (see
+//! [crate::instrumentation]).
+
+use crate::errors::AkdError;
+use crate::instrumentation;
+use crate::proof_cache::ProofCacheKey;
+use crate::LookupProof;
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+/// Counts of the lookups which have passed through the coalescing layer of a directory
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct LookupCoalescingMetrics {
+    /// The number of lookups which were served while coalescing was enabled
+    pub lookups: u64,
+    /// The number of those lookups which were answered with the proof generated by another,
+    /// concurrent lookup
+    pub coalesced: u64,
+}
+
+/// The lookups which are currently generating a proof, keyed by the label and the epoch and
+/// root hash at which the proof is generated
+#[derive(Default)]
+pub(crate) struct LookupFlights {
+    enabled: AtomicBool,
+    in_flight: Mutex<HashMap<ProofCacheKey, Arc<OnceCell<LookupProof>>>>,
+    lookups: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+impl LookupFlights {
+    /// Whether concurrent lookups are coalesced
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Enables or disables the coalescing of concurrent lookups
+    pub(crate) fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns the counts of the coalesced lookups
+    pub(crate) fn metrics(&self) -> LookupCoalescingMetrics {
+        LookupCoalescingMetrics {
+            lookups: self.lookups.load(Ordering::Relaxed),
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Answers a lookup with the proof generated by the flight of its key, joining the flight
+    /// if one is underway, or starting one with `generate` otherwise
+    pub(crate) async fn run<F, Fut>(
+        &self,
+        key: ProofCacheKey,
+        generate: F,
+    ) -> Result<LookupProof, AkdError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<LookupProof, AkdError>>,
+    {
+        let flight = self
+            .lock()
+            .entry(key.clone())
+            .or_insert_with(|| Arc::new(OnceCell::new()))
+            .clone();
+        let mut generated = false;
+        let result = flight
+            .get_or_try_init(|| {
+                generated = true;
+                generate()
+            })
+            .await
+            .cloned();
+
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+        if generated {
+            // The flight is over once it has a result, so that later lookups start a new one
+            let mut in_flight = self.lock();
+            if in_flight
+                .get(&key)
+                .is_some_and(|current| Arc::ptr_eq(current, &flight))
+            {
+                in_flight.remove(&key);
+            }
+        } else {
+            self.coalesced.fetch_add(1, Ordering::Relaxed);
+            instrumentation::record_coalesced_lookup();
+        }
+        result
+    }
+
+    fn lock(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<ProofCacheKey, Arc<OnceCell<LookupProof>>>> {
+        // The map is never left in an inconsistent state, so a poisoned lock is recovered
+        self.in_flight
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
@@ -0,0 +1,556 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A conformance suite of directory tests, which crates implementing their own
+//! [Configuration] or [Database] can run against their implementations, to catch
+//! incompatibilities with the directory before they reach production.
+//!
+//! Each test takes a newly-created, empty database. The whole suite can be run with
+//! [run_conformance_suite], or declared as individual `#[tokio::test]` functions (along
+//! with the storage-layer tests of [run_test_cases_for_storage_impl]) with the
+//! [conformance_suite](crate::conformance_suite) macro, which requires the `tokio` crate
+//! with its `macros` feature:
+//!
+//! ```ignore
+//! akd::conformance_suite!(
+//!     my_config_conformance,
+//!     MyConfiguration,
+//!     || MyDatabase::new()
+//! );
+//! ```
+//!
+//! [run_test_cases_for_storage_impl]: crate::storage::tests::run_test_cases_for_storage_impl
+
+use crate::auditor::{audit_verify, verify_consecutive_append_only};
+use crate::client::{key_history_verify, lookup_verify, nonexistence_verify};
+use crate::directory::{Directory, ReadOnlyDirectory};
+use crate::ecvrf::HardCodedAkdVRF;
+use crate::errors::{AkdError, DirectoryError};
+use crate::storage::manager::StorageManager;
+use crate::storage::Database;
+use crate::{
+    AkdLabel, AkdValue, AppendOnlyProof, Configuration, HistoryParams, HistoryVerificationParams,
+    VerifyResult,
+};
+
+/// Runs every test of the conformance suite for a configuration and storage implementation,
+/// where `new_db` creates a new, empty database for each test
+pub async fn run_conformance_suite<TC: Configuration, S: Database + 'static>(
+    new_db: impl Fn() -> S,
+) -> Result<(), AkdError> {
+    test_empty_tree_root_hash::<TC, S>(new_db()).await?;
+    test_simple_publish::<TC, S>(new_db()).await?;
+    test_simple_lookup::<TC, S>(new_db()).await?;
+    test_small_key_history::<TC, S>(new_db()).await?;
+    test_lookup_absent::<TC, S>(new_db()).await?;
+    test_simple_audit::<TC, S>(new_db()).await?;
+    test_directory_read_only_mode::<TC, S>(new_db()).await?;
+    test_tombstoned_key_history::<TC, S>(new_db()).await?;
+    test_publish_duplicate_entries::<TC, S>(new_db()).await?;
+    Ok(())
+}
+
+/// Declares a module named `$name` with a `#[tokio::test]` for each test of the conformance
+/// suite, for the configuration `$config` and the storage implementation created by the
+/// closure `$new_db`, along with a test of the storage layer
+#[macro_export]
+macro_rules! conformance_suite {
+    ( $name:ident, $config:ty, $new_db:expr ) => {
+        mod $name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            $crate::conformance_suite!(
+                @tests $config, $new_db;
+                test_empty_tree_root_hash,
+                test_simple_publish,
+                test_simple_lookup,
+                test_small_key_history,
+                test_lookup_absent,
+                test_simple_audit,
+                test_directory_read_only_mode,
+                test_tombstoned_key_history,
+                test_publish_duplicate_entries
+            );
+
+            #[tokio::test]
+            async fn test_storage() {
+                $crate::storage::tests::run_test_cases_for_storage_impl(($new_db)()).await;
+            }
+        }
+    };
+    ( @tests $config:ty, $new_db:expr; $( $test:ident ),* ) => {
+        $(
+            #[tokio::test]
+            async fn $test() -> Result<(), $crate::errors::AkdError> {
+                $crate::conformance::$test::<$config, _>(($new_db)()).await
+            }
+        )*
+    };
+}
+
+/// A simple test to ensure that the empty tree hashes to the correct value
+pub async fn test_empty_tree_root_hash<TC: Configuration, S: Database + 'static>(
+    db: S,
+) -> Result<(), AkdError> {
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd: Directory<_, S, HardCodedAkdVRF> = Directory::<TC, _, _>::new(storage, vrf).await?;
+
+    let hash = akd.get_epoch_hash().await?.1;
+
+    // Ensuring that the root hash of an empty tree is equal to the following constant
+    assert_eq!(
+        TC::compute_root_hash_from_val(&TC::empty_root_value()),
+        hash
+    );
+
+    Ok(())
+}
+
+/// A simple publish test to make sure a publish doesn't throw an error.
+pub async fn test_simple_publish<TC: Configuration, S: Database + 'static>(
+    db: S,
+) -> Result<(), AkdError> {
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+    // Make sure you can publish and that something so simple
+    // won't throw errors.
+    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world"))])
+        .await?;
+    Ok(())
+}
+
+/// A simple lookup test, for a tree with two elements:
+/// ensure that calculation of a lookup proof doesn't throw an error and
+/// that the output of akd.lookup verifies on the client.
+pub async fn test_simple_lookup<TC: Configuration, S: Database + 'static>(
+    db: S,
+) -> Result<(), AkdError> {
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+    // Add two labels and corresponding values to the akd
+    akd.publish(vec![
+        (AkdLabel::from("hello"), AkdValue::from("world")),
+        (AkdLabel::from("hello2"), AkdValue::from("world2")),
+    ])
+    .await?;
+    // Get the lookup proof
+    let (lookup_proof, root_hash) = akd.lookup(AkdLabel::from("hello")).await?;
+    // Get the VRF public key
+    let vrf_pk = akd.get_public_key().await?;
+    // Verify the lookup proof
+    lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash.hash(),
+        root_hash.epoch(),
+        AkdLabel::from("hello"),
+        lookup_proof,
+    )?;
+    Ok(())
+}
+
+/// This test also covers #144: That key history doesn't fail on very small trees,
+/// i.e. trees with a potentially empty child for the root node.
+/// Other that it is just a simple check to see that a valid key history proof passes.
+pub async fn test_small_key_history<TC: Configuration, S: Database + 'static>(
+    db: S,
+) -> Result<(), AkdError> {
+    // This test has an akd with a single label: "hello"
+    // The value of this label is updated two times.
+    // Then the test verifies the key history.
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+    // Publish the first value for the label "hello"
+    // Epoch here will be 1
+    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world"))])
+        .await?;
+    // Publish the second value for the label "hello"
+    // Epoch here will be 2
+    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world2"))])
+        .await?;
+
+    // Get the key_history_proof for the label "hello"
+    let (key_history_proof, root_hash) = akd
+        .key_history(&AkdLabel::from("hello"), HistoryParams::default())
+        .await?;
+    // Get the VRF public key
+    let vrf_pk = akd.get_public_key().await?;
+    // Verify the key history proof
+    let result = key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash.hash(),
+        root_hash.epoch(),
+        AkdLabel::from("hello"),
+        key_history_proof,
+        HistoryVerificationParams::default(),
+    )?;
+
+    assert_eq!(
+        result,
+        vec![
+            VerifyResult {
+                epoch: 2,
+                version: 2,
+                value: AkdValue::from("world2"),
+            },
+            VerifyResult {
+                epoch: 1,
+                version: 1,
+                value: AkdValue::from("world"),
+            },
+        ]
+    );
+
+    Ok(())
+}
+
+/// Checks that a non-existence proof can be generated and verified for a label which
+/// was never registered, and that it cannot be generated or forged for a registered one
+pub async fn test_lookup_absent<TC: Configuration, S: Database + 'static>(
+    db: S,
+) -> Result<(), AkdError> {
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+    let vrf_pk = akd.get_public_key().await?;
+
+    // Nothing can be proven before the first epoch is published
+    assert!(matches!(
+        akd.lookup_absent(AkdLabel::from("hello")).await,
+        Err(AkdError::Directory(DirectoryError::InvalidEpoch(_)))
+    ));
+
+    akd.publish(vec![
+        (AkdLabel::from("hello"), AkdValue::from("world")),
+        (AkdLabel::from("hello2"), AkdValue::from("world2")),
+    ])
+    .await?;
+
+    let (proof, root_hash) = akd.lookup_absent(AkdLabel::from("unregistered")).await?;
+    nonexistence_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash.hash(),
+        AkdLabel::from("unregistered"),
+        proof.clone(),
+    )?;
+
+    // The proof does not verify for a different label
+    assert!(nonexistence_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash.hash(),
+        AkdLabel::from("hello"),
+        proof,
+    )
+    .is_err());
+
+    // A registered label cannot be proven absent
+    assert!(matches!(
+        akd.lookup_absent(AkdLabel::from("hello")).await,
+        Err(AkdError::Directory(DirectoryError::LabelRegistered(_)))
+    ));
+
+    Ok(())
+}
+
+/// This test ensures valid audit proofs pass for various epochs and
+/// that invalid audit proofs fail.
+pub async fn test_simple_audit<TC: Configuration, S: Database + 'static>(
+    db: S,
+) -> Result<(), AkdError> {
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+
+    akd.publish(vec![
+        (AkdLabel::from("hello"), AkdValue::from("world")),
+        (AkdLabel::from("hello2"), AkdValue::from("world2")),
+    ])
+    .await?;
+
+    // Get the root hash after the first server publish
+    let root_hash_1 = akd.get_epoch_hash().await?.1;
+
+    akd.publish(vec![
+        (AkdLabel::from("hello"), AkdValue::from("world_2")),
+        (AkdLabel::from("hello2"), AkdValue::from("world2_2")),
+    ])
+    .await?;
+
+    // Get the root hash after the second server publish
+    let root_hash_2 = akd.get_epoch_hash().await?.1;
+
+    akd.publish(vec![
+        (AkdLabel::from("hello"), AkdValue::from("world3")),
+        (AkdLabel::from("hello2"), AkdValue::from("world4")),
+    ])
+    .await?;
+
+    // Get the root hash after the third server publish
+    let root_hash_3 = akd.get_epoch_hash().await?.1;
+
+    akd.publish(vec![
+        (AkdLabel::from("hello3"), AkdValue::from("world")),
+        (AkdLabel::from("hello4"), AkdValue::from("world2")),
+    ])
+    .await?;
+
+    // Get the root hash after the fourth server publish
+    let root_hash_4 = akd.get_epoch_hash().await?.1;
+
+    akd.publish(vec![(
+        AkdLabel::from("hello"),
+        AkdValue::from("world_updated"),
+    )])
+    .await?;
+
+    // Get the root hash after the fifth server publish
+    let root_hash_5 = akd.get_epoch_hash().await?.1;
+
+    akd.publish(vec![
+        (AkdLabel::from("hello3"), AkdValue::from("world6")),
+        (AkdLabel::from("hello4"), AkdValue::from("world12")),
+    ])
+    .await?;
+
+    // Get the root hash after the 6th server publish
+    let root_hash_6 = akd.get_epoch_hash().await?.1;
+
+    // This is to ensure that an audit of two consecutive, although relatively old epochs is calculated correctly.
+    let audit_proof_1 = akd.audit(1, 2).await?;
+    audit_verify::<TC>(vec![root_hash_1, root_hash_2], audit_proof_1).await?;
+
+    // This is to ensure that an audit of 3 consecutive epochs although not the most recent is calculated correctly.
+    let audit_proof_2 = akd.audit(1, 3).await?;
+    audit_verify::<TC>(vec![root_hash_1, root_hash_2, root_hash_3], audit_proof_2).await?;
+
+    // This is to ensure that an audit of 4 consecutive epochs is calculated correctly.
+    let audit_proof_3 = akd.audit(1, 4).await?;
+    audit_verify::<TC>(
+        vec![root_hash_1, root_hash_2, root_hash_3, root_hash_4],
+        audit_proof_3,
+    )
+    .await?;
+
+    // This is to ensure that an audit of 5 consecutive epochs is calculated correctly.
+    let audit_proof_4 = akd.audit(1, 5).await?;
+    audit_verify::<TC>(
+        vec![
+            root_hash_1,
+            root_hash_2,
+            root_hash_3,
+            root_hash_4,
+            root_hash_5,
+        ],
+        audit_proof_4,
+    )
+    .await?;
+
+    // Test correct audit of two consecutive epochs but not starting at epoch 1.
+    let audit_proof_5 = akd.audit(2, 3).await?;
+    audit_verify::<TC>(vec![root_hash_2, root_hash_3], audit_proof_5).await?;
+
+    // Test correct audit of 3 consecutive epochs but not starting at epoch 1.
+    let audit_proof_6 = akd.audit(2, 4).await?;
+    audit_verify::<TC>(vec![root_hash_2, root_hash_3, root_hash_4], audit_proof_6).await?;
+
+    // Test correct audit of 3 consecutive epochs ending at epoch 6 -- the last epoch
+    let audit_proof_7 = akd.audit(4, 6).await?;
+    audit_verify::<TC>(vec![root_hash_4, root_hash_5, root_hash_6], audit_proof_7).await?;
+
+    // The audit_verify function should throw an AuditorError when the proof has a different
+    // number of epochs than needed for hashes
+    let audit_proof_8 = akd.audit(4, 6).await?;
+    let invalid_audit_verification = audit_verify::<TC>(
+        vec![
+            root_hash_1,
+            root_hash_2,
+            root_hash_3,
+            root_hash_4,
+            root_hash_5,
+        ],
+        audit_proof_8,
+    )
+    .await;
+    assert!(matches!(
+        invalid_audit_verification,
+        Err(AkdError::AuditErr(_))
+    ));
+
+    // The audit_verify function should throw an AuditorError when the proof does not have the same
+    // number of epochs as proofs
+    let audit_proof_9 = akd.audit(1, 5).await?;
+    let audit_proof_10 = akd.audit(4, 6).await?;
+    let invalid_audit_proof = AppendOnlyProof {
+        proofs: audit_proof_10.proofs,
+        epochs: audit_proof_9.epochs,
+    };
+    let invalid_audit_verification = audit_verify::<TC>(
+        vec![
+            root_hash_1,
+            root_hash_2,
+            root_hash_3,
+            root_hash_4,
+            root_hash_5,
+        ],
+        invalid_audit_proof,
+    )
+    .await;
+    assert!(matches!(
+        invalid_audit_verification,
+        Err(AkdError::AuditErr(_))
+    ));
+
+    // The verify_consecutive_append_only function should throw an AzksErr error when the computed
+    // end root hash is not equal to the end hash
+    let audit_proof_11 = akd.audit(1, 2).await?;
+    let verification = verify_consecutive_append_only::<TC>(
+        &audit_proof_11.proofs[0],
+        root_hash_1,
+        root_hash_3, // incorrect end hash - should be root_hash_2
+        audit_proof_11.epochs[0] + 1,
+    )
+    .await;
+    assert!(matches!(verification, Err(AkdError::AzksErr(_))));
+
+    // The audit should be of more than 1 epoch
+    let invalid_audit = akd.audit(3, 3).await;
+    assert!(invalid_audit.is_err());
+
+    // The audit epochs must be increasing
+    let invalid_audit = akd.audit(3, 2).await;
+    assert!(invalid_audit.is_err());
+
+    // The audit should throw an error when queried for an epoch which hasn't yet taken place!
+    let invalid_audit = akd.audit(6, 7).await;
+    assert!(invalid_audit.is_err());
+
+    Ok(())
+}
+
+/// The read-only mode of a directory is meant to simply read from memory.
+/// This test makes sure it throws errors appropriately, i.e. when trying to
+/// write to a read-only directory and when trying to read a directory when none
+/// exists in storage.
+pub async fn test_directory_read_only_mode<TC: Configuration, S: Database + 'static>(
+    db: S,
+) -> Result<(), AkdError> {
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    // There is no AZKS object in the storage layer, directory construction should fail
+    let akd = ReadOnlyDirectory::<TC, _, _>::new(storage, vrf).await;
+    assert!(akd.is_err());
+
+    Ok(())
+}
+
+/// Checks that the values which are tombstoned in storage are only accepted by clients
+/// which allow missing values when verifying a key history
+pub async fn test_tombstoned_key_history<TC: Configuration, S: Database + 'static>(
+    db: S,
+) -> Result<(), AkdError> {
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    // epoch 0
+    let akd = Directory::<TC, _, _>::new(storage.clone(), vrf).await?;
+
+    // epoch 1
+    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world"))])
+        .await?;
+
+    // epoch 2
+    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world2"))])
+        .await?;
+
+    // epoch 3
+    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world3"))])
+        .await?;
+
+    // epoch 4
+    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world4"))])
+        .await?;
+
+    // epoch 5
+    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world5"))])
+        .await?;
+
+    // Epochs 1-5, we're going to tombstone 1 & 2
+
+    // Get the VRF public key
+    let vrf_pk = akd.get_public_key().await?;
+
+    // tombstone epochs 1 & 2
+    storage
+        .tombstone_value_states(&AkdLabel::from("hello"), 2)
+        .await?;
+
+    // Now get a history proof for this key
+    let (history_proof, root_hash) = akd
+        .key_history(&AkdLabel::from("hello"), HistoryParams::default())
+        .await?;
+    assert_eq!(5, history_proof.update_proofs.len());
+
+    // If we request a proof with tombstones but without saying we're OK with tombstones, throw an err
+    let tombstones = key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash.hash(),
+        root_hash.epoch(),
+        AkdLabel::from("hello"),
+        history_proof.clone(),
+        HistoryVerificationParams::default(),
+    );
+    assert!(tombstones.is_err());
+
+    // We should be able to verify tombstones assuming the client is accepting
+    // of tombstoned states
+    let results = key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash.hash(),
+        root_hash.epoch(),
+        AkdLabel::from("hello"),
+        history_proof,
+        HistoryVerificationParams::AllowMissingValues,
+    )?;
+    assert!(!TC::is_tombstone(&results[0].value));
+    assert!(!TC::is_tombstone(&results[1].value));
+    assert!(!TC::is_tombstone(&results[2].value));
+    assert!(TC::is_tombstone(&results[3].value));
+    assert!(TC::is_tombstone(&results[4].value));
+
+    Ok(())
+}
+
+/// Test for attempting to publish duplicate entries as updates to the directory
+pub async fn test_publish_duplicate_entries<TC: Configuration, S: Database + 'static>(
+    db: S,
+) -> Result<(), AkdError> {
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf.clone()).await?;
+
+    // Create a set of updates
+    let mut updates = vec![];
+    for i in 0..10 {
+        updates.push((
+            AkdLabel(format!("hello1{i}").as_bytes().to_vec()),
+            AkdValue(format!("hello1{i}").as_bytes().to_vec()),
+        ));
+    }
+
+    // Now add a duplicate entry
+    updates.push(updates[0].clone());
+
+    // Attempt to publish -- this should throw an error because of the duplicate entry
+    let Err(AkdError::Directory(DirectoryError::Publish(_))) = akd.publish(updates).await else {
+        panic!("Expected a directory publish error");
+    };
+
+    Ok(())
+}
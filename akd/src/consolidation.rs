@@ -0,0 +1,157 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Consolidation of frequently published mini-epochs into canonical audit epochs.
+//!
+//! A directory which publishes often, so that new keys become available with low latency,
+//! produces many small epochs, which are referred to here as mini-epochs. Every epoch of the
+//! directory is a mini-epoch: lookup and history proofs are served at the latest mini-epoch,
+//! and are verified against its root hash as usual. `Directory::consolidate` periodically
+//! groups the mini-epochs published since the previous consolidation into an [AuditEpoch],
+//! which records the first and last root hashes of the group, and which is the granularity at
+//! which auditors and gossiping clients track the directory.
+//!
+//! The two granularities are verified as follows:
+//!
+//! - An audit epoch is verified with [verify_audit_epoch], against a single
+//!   [MultiEpochAppendOnlyProof] (see `Directory::audit_epoch_proof`) which shows that its
+//!   last root hash extends its first, and against the previous audit epoch, whose last root
+//!   hash must be its first. The audit epochs therefore form a chain from the empty tree.
+//! - A mini-epoch is verified with [verify_mini_epoch], against a [MiniEpochProof] (see
+//!   `Directory::mini_epoch_proof`) which shows that the last root hash of the audit epoch
+//!   covering the mini-epoch extends the root hash of the mini-epoch. A client which verified
+//!   a proof against the root hash of a mini-epoch can thereby check, once the mini-epoch has
+//!   been consolidated, that the root hash it relied on is part of the audited history.
+//!
+//! The mini-epochs of a directory can still be audited individually, with `Directory::audit`.
+//! Audit epochs are final, so a directory cannot be rolled back to an epoch before the end of
+//! its latest audit epoch.
+
+pub use crate::storage::types::AuditEpoch;
+
+use crate::auditor::audit_verify_multi_epoch;
+use crate::errors::{AkdError, AuditorError};
+use crate::{Configuration, EpochHash, MultiEpochAppendOnlyProof};
+
+/// A proof that the root hash of a mini-epoch is extended by the last root hash of the audit
+/// epoch which covers it
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct MiniEpochProof {
+    /// The mini-epoch
+    pub epoch: u64,
+    /// The index of the audit epoch which covers the mini-epoch
+    pub audit_epoch: u64,
+    /// The append-only proof from the mini-epoch to the end of the audit epoch, or [None] if
+    /// the mini-epoch is the end of the audit epoch
+    pub proof: Option<MultiEpochAppendOnlyProof>,
+}
+
+/// Verifies an audit epoch, given the previous audit epoch (or [None] for the first audit
+/// epoch, which must start from the empty tree at epoch 0) and the append-only proof between
+/// its first and last root hashes
+pub async fn verify_audit_epoch<TC: Configuration>(
+    audit_epoch: &AuditEpoch,
+    previous: Option<&AuditEpoch>,
+    proof: &MultiEpochAppendOnlyProof,
+) -> Result<(), AkdError> {
+    let (expected_index, expected_epoch, expected_root_hash) = match previous {
+        Some(previous) => (
+            previous.index + 1,
+            previous.end_epoch,
+            previous.end_root_hash,
+        ),
+        None => (
+            1,
+            0,
+            TC::compute_root_hash_from_val(&TC::empty_root_value()),
+        ),
+    };
+    if audit_epoch.index != expected_index
+        || audit_epoch.start_epoch != expected_epoch
+        || audit_epoch.start_root_hash != expected_root_hash
+    {
+        return Err(verification_error(format!(
+            "Audit epoch {} starting at epoch {} does not follow audit epoch {} ending at epoch {}",
+            audit_epoch.index,
+            audit_epoch.start_epoch,
+            expected_index - 1,
+            expected_epoch
+        )));
+    }
+    if proof.start_epoch != audit_epoch.start_epoch || proof.end_epoch != audit_epoch.end_epoch {
+        return Err(verification_error(format!(
+            "The proof covers epochs ({}, {}], rather than the epochs ({}, {}] of audit epoch {}",
+            proof.start_epoch,
+            proof.end_epoch,
+            audit_epoch.start_epoch,
+            audit_epoch.end_epoch,
+            audit_epoch.index
+        )));
+    }
+    audit_verify_multi_epoch::<TC>(
+        audit_epoch.start_root_hash,
+        audit_epoch.end_root_hash,
+        proof,
+    )
+    .await
+}
+
+/// Verifies that the root hash of a mini-epoch is extended by the last root hash of the
+/// audit epoch which covers it. The audit epoch should itself have been verified, with
+/// [verify_audit_epoch].
+pub async fn verify_mini_epoch<TC: Configuration>(
+    mini_epoch: EpochHash,
+    audit_epoch: &AuditEpoch,
+    proof: &MiniEpochProof,
+) -> Result<(), AkdError> {
+    let EpochHash(epoch, root_hash) = mini_epoch;
+    if proof.epoch != epoch
+        || proof.audit_epoch != audit_epoch.index
+        || epoch <= audit_epoch.start_epoch
+        || epoch > audit_epoch.end_epoch
+    {
+        return Err(verification_error(format!(
+            "The proof of mini-epoch {} in audit epoch {} does not apply to mini-epoch {epoch} in \
+            audit epoch {}, which covers the epochs ({}, {}]",
+            proof.epoch,
+            proof.audit_epoch,
+            audit_epoch.index,
+            audit_epoch.start_epoch,
+            audit_epoch.end_epoch
+        )));
+    }
+    match &proof.proof {
+        None if epoch == audit_epoch.end_epoch => {
+            if root_hash != audit_epoch.end_root_hash {
+                return Err(verification_error(format!(
+                    "The root hash of mini-epoch {epoch} is not the last root hash of audit epoch {}",
+                    audit_epoch.index
+                )));
+            }
+            Ok(())
+        }
+        Some(append_only)
+            if append_only.start_epoch == epoch
+                && append_only.end_epoch == audit_epoch.end_epoch =>
+        {
+            audit_verify_multi_epoch::<TC>(root_hash, audit_epoch.end_root_hash, append_only).await
+        }
+        _ => Err(verification_error(format!(
+            "The append-only proof of mini-epoch {epoch} does not cover the epochs up to the end \
+            of audit epoch {}",
+            audit_epoch.index
+        ))),
+    }
+}
+
+fn verification_error(message: String) -> AkdError {
+    AkdError::AuditErr(AuditorError::VerifyAuditProof(message))
+}
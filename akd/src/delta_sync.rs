@@ -0,0 +1,215 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Delta sync, for clients which regularly check the values of a fixed set of labels.
+//!
+//! A client which last synced at some epoch, and verified the root hash of that epoch, asks
+//! the directory for everything it needs to catch up to the current epoch with
+//! `Directory::delta_sync`. The [DeltaSyncResponse] combines the append-only proof from the
+//! client's epoch to the current one, which shows that the current root hash extends the
+//! one the client already trusts, with a [LabelDelta] for each label. The delta of a label
+//! holds the updates published since the client's epoch, along with the latest update at or
+//! before it, which proves that no other update was published since (as with
+//! `HistoryParams::RecentEpochs`). The delta of a label which has not been published proves
+//! that it is absent. The client checks the response with [delta_sync_verify], and then
+//! keeps the current epoch and root hash for its next sync.
+
+use crate::auditor::audit_verify_multi_epoch;
+use crate::canonical::CanonicalEncode;
+use crate::client::{key_history_verify_recent_epochs, nonexistence_verify};
+use crate::errors::{AkdError, AuditorError};
+use crate::{
+    AkdLabel, Configuration, Digest, EpochHash, HistoryProof, HistoryVerificationParams,
+    MultiEpochAppendOnlyProof, NonExistenceProof, VerifyResult,
+};
+
+/// The changes to a single label since the epoch of a delta sync
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub enum LabelDelta {
+    /// The history of the updates since the epoch of the sync, along with the latest update at
+    /// or before it
+    History(HistoryProof),
+    /// The proof that the label has not been published
+    Absent(NonExistenceProof),
+}
+
+impl CanonicalEncode for LabelDelta {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        match self {
+            LabelDelta::History(proof) => {
+                out.push(0);
+                proof.canonical_encode(out);
+            }
+            LabelDelta::Absent(proof) => {
+                out.push(1);
+                proof.canonical_encode(out);
+            }
+        }
+    }
+}
+
+/// Everything a client needs to catch up on a set of labels from the epoch it last synced at
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct DeltaSyncResponse {
+    /// The epoch which the client last synced at
+    pub since_epoch: u64,
+    /// The current epoch
+    pub epoch: u64,
+    /// The root hash of the current epoch
+    pub root_hash: Digest,
+    /// The append-only proof from the epoch of the last sync to the current epoch, or [None]
+    /// if no epoch has been published since
+    pub consistency_proof: Option<MultiEpochAppendOnlyProof>,
+    /// The deltas of the labels, in the order they were requested in
+    pub deltas: Vec<(AkdLabel, LabelDelta)>,
+}
+
+impl CanonicalEncode for DeltaSyncResponse {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.since_epoch.canonical_encode(out);
+        EpochHash(self.epoch, self.root_hash).canonical_encode(out);
+        self.consistency_proof.canonical_encode(out);
+        (self.deltas.len() as u64).canonical_encode(out);
+        for (label, delta) in self.deltas.iter() {
+            label.canonical_encode(out);
+            delta.canonical_encode(out);
+        }
+    }
+}
+
+/// The verified changes to a single label since the epoch of a delta sync
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelSyncResult {
+    /// The label
+    pub label: AkdLabel,
+    /// The updates published after the epoch of the sync, ordered from the most recent one
+    pub updates: Vec<VerifyResult>,
+    /// The latest update, or [None] if the label has not been published
+    pub latest: Option<VerifyResult>,
+}
+
+/// The verified result of a delta sync
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeltaSyncResult {
+    /// The epoch and root hash which the client syncs to, and should keep for its next sync
+    pub epoch_hash: EpochHash,
+    /// The changes to the labels, in the order they were requested in
+    pub labels: Vec<LabelSyncResult>,
+}
+
+impl DeltaSyncResult {
+    /// The labels which were updated since the epoch of the sync
+    pub fn changed(&self) -> impl Iterator<Item = &LabelSyncResult> {
+        self.labels.iter().filter(|label| !label.updates.is_empty())
+    }
+}
+
+/// Verifies a [DeltaSyncResponse] for the given labels, given the epoch and root hash which the
+/// client last synced at (or epoch 0 and the root hash of the empty tree for its first sync).
+/// The response must hold a delta for exactly the requested labels, in the same order, and the
+/// history of each published label must contain every update since the epoch of the last sync.
+pub async fn delta_sync_verify<TC: Configuration>(
+    vrf_public_key: &[u8],
+    since: EpochHash,
+    labels: &[AkdLabel],
+    response: DeltaSyncResponse,
+    params: HistoryVerificationParams,
+) -> Result<DeltaSyncResult, AkdError> {
+    let EpochHash(since_epoch, since_root_hash) = since;
+    if response.since_epoch != since_epoch || response.epoch < since_epoch {
+        return Err(verification_error(format!(
+            "The response syncs from epoch {} to epoch {}, rather than from epoch {since_epoch}",
+            response.since_epoch, response.epoch
+        )));
+    }
+    match &response.consistency_proof {
+        None if response.epoch == since_epoch => {
+            if response.root_hash != since_root_hash {
+                return Err(verification_error(format!(
+                    "The root hash of epoch {since_epoch} does not match the one of the last sync"
+                )));
+            }
+        }
+        Some(proof) if proof.start_epoch == since_epoch && proof.end_epoch == response.epoch => {
+            audit_verify_multi_epoch::<TC>(since_root_hash, response.root_hash, proof).await?;
+        }
+        _ => {
+            return Err(verification_error(format!(
+                "The consistency proof does not cover the epochs ({since_epoch}, {}]",
+                response.epoch
+            )));
+        }
+    }
+
+    if response.deltas.len() != labels.len() {
+        return Err(verification_error(format!(
+            "The response holds {} deltas for {} requested labels",
+            response.deltas.len(),
+            labels.len()
+        )));
+    }
+    let mut results = Vec::with_capacity(labels.len());
+    for (label, (delta_label, delta)) in labels.iter().zip(response.deltas) {
+        if TC::normalize_label(label) != TC::normalize_label(&delta_label) {
+            return Err(verification_error(format!(
+                "The response holds a delta for {delta_label:?} rather than {label:?}"
+            )));
+        }
+        let result = match delta {
+            LabelDelta::History(proof) => {
+                let mut updates = key_history_verify_recent_epochs::<TC>(
+                    vrf_public_key,
+                    response.root_hash,
+                    response.epoch,
+                    label.clone(),
+                    proof,
+                    response.epoch - since_epoch,
+                    params,
+                )?;
+                let latest = updates.first().cloned();
+                updates.retain(|update| update.epoch > since_epoch);
+                LabelSyncResult {
+                    label: label.clone(),
+                    updates,
+                    latest,
+                }
+            }
+            LabelDelta::Absent(proof) => {
+                nonexistence_verify::<TC>(
+                    vrf_public_key,
+                    response.root_hash,
+                    label.clone(),
+                    proof,
+                )?;
+                LabelSyncResult {
+                    label: label.clone(),
+                    updates: vec![],
+                    latest: None,
+                }
+            }
+        };
+        results.push(result);
+    }
+
+    Ok(DeltaSyncResult {
+        epoch_hash: EpochHash(response.epoch, response.root_hash),
+        labels: results,
+    })
+}
+
+fn verification_error(message: String) -> AkdError {
+    AkdError::AuditErr(AuditorError::VerifyAuditProof(message))
+}
@@ -7,25 +7,97 @@
 
 //! Implementation of an auditable key directory
 
-use crate::append_only_zks::{Azks, InsertMode};
+use crate::anchoring::{Anchor, AnchoredRoot};
+use crate::append_only_zks::{Azks, AzksElementSet, InsertMode};
+use crate::audit_lite::{AuditLiteProof, AuditLiteSnapshot};
+use crate::coalescing::{LookupCoalescingMetrics, LookupFlights};
+use crate::consolidation::MiniEpochProof;
+use crate::delta_sync::{DeltaSyncResponse, LabelDelta};
+use crate::divergence::{self, Divergence, DivergenceReport};
 use crate::ecvrf::{VRFKeyStorage, VRFPublicKey};
-use crate::errors::{AkdError, DirectoryError, StorageError};
+use crate::errors::{AkdError, DirectoryError, ParallelismError, StorageError};
+use crate::health::{ComponentHealth, HealthCheckOptions, HealthReport};
 use crate::helper_structs::LookupInfo;
-use crate::storage::manager::StorageManager;
-use crate::storage::types::{DbRecord, ValueState, ValueStateRetrievalFlag};
-use crate::storage::Database;
+use crate::hooks::DirectoryHooks;
+use crate::hot_labels::HotLabels;
+use crate::instrumentation;
+use crate::maintenance::{
+    self, MaintenanceConfig, MaintenanceHandle, MaintenanceReport, MaintenanceTask, RetentionPolicy,
+};
+use crate::prefetch::{AccessTracker, PrefetchConfig};
+#[cfg(feature = "profiling")]
+use crate::profiling::TimingReport;
+use crate::profiling::{Operation, Phase, PhaseTimer};
+use crate::proof_cache::{ProofCache, ProofCacheKey};
+use crate::proof_chunks::{
+    assemble_chunks, AppendOnlyProofChunk, ChunkedAuditOptions, ProofChunkStore,
+};
+use crate::publish_queue::PublishQueue;
+use crate::replication::{self, FollowerHandle};
+use crate::scheduling::{self, EpochSource};
+use crate::storage::manager::{
+    PublishLeaseGuard, PublishLeaseOptions, StorageManager, StorageRole,
+};
+use crate::storage::memory::AsyncInMemoryDatabase;
+use crate::storage::types::{
+    AnchorReceipt, AuditEpoch, DbRecord, OperationKind, OperationRecord, ValueState,
+    ValueStateRetrievalFlag,
+};
+use crate::storage::{Database, Storable, StorageUtil};
 use crate::{
-    AkdLabel, AkdValue, AppendOnlyProof, AzksElement, Digest, EpochHash, HistoryProof, LookupProof,
-    NonMembershipProof, UpdateProof,
+    history_aggregate_label, removed_value_commitment, AggregatedHistoryProof, AkdLabel, AkdValue,
+    AppendOnlyProof, AzksElement, AzksValue, Digest, DirectoryExport, EpochHash, ExportEntry,
+    HistoryAggregate, HistoryProof, LatestVersionProof, LinkedHistoryProof, LinkedHistorySegment,
+    LookupProof, MultiEpochAppendOnlyProof, NonExistenceProof, NonMembershipProof, RedactionRecord,
+    RetentionClass, SelectiveHistoryProof, SelectiveUpdateProof, SignedLookupResponse,
+    SignedRollbackRecord, SingleAppendOnlyProof, SizeOf, TombstoneMetadata, UpdateDisclosure,
+    UpdateProof,
 };
 
-use crate::VersionFreshness;
+use crate::tree_node::{NodeHashingMode, NodeKey, TreeNode, TreeNodeWithPreviousValue};
+use crate::visualization::{TreeVisualization, TreeVisualizationOptions};
+use crate::{ByteConstraints, Direction, NodeLabel, VersionFreshness};
 use akd_core::configuration::Configuration;
-use log::{error, info};
-use std::collections::{HashMap, HashSet};
+use akd_core::signing::{
+    configuration_fingerprint, epoch_signature_message, rollback_signature_message, EpochSigner,
+};
+use akd_core::spot_check::{spot_check_targets, SpotCheckProof, MAX_SPOT_CHECK_SAMPLES};
+use akd_core::verify::VerificationError;
+use futures::{StreamExt, TryStreamExt};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::marker::PhantomData;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
+use tracing::{debug, error, info, warn};
+
+/// The number of label-value pairs which are processed together in each stage
+/// of the publish pipeline
+const PUBLISH_PIPELINE_CHUNK_SIZE: usize = 1000;
+
+/// The maximum number of chunks which can be buffered between two stages of the
+/// publish pipeline
+const PUBLISH_PIPELINE_DEPTH: usize = 4;
+
+/// The VRF and non-membership proofs for the versions until the next marker version,
+/// followed by those for the future marker versions
+type FutureVersionProofs = (
+    Vec<Vec<u8>>,
+    Vec<NonMembershipProof>,
+    Vec<Vec<u8>>,
+    Vec<NonMembershipProof>,
+);
+
+/// Canonicalizes a label with [Configuration::normalize_label], without copying the label
+/// if it is already normalized
+fn normalize_label<TC: Configuration>(label: AkdLabel) -> AkdLabel {
+    let normalized = match TC::normalize_label(&label) {
+        Cow::Owned(normalized) => Some(normalized),
+        Cow::Borrowed(_) => None,
+    };
+    normalized.unwrap_or(label)
+}
 
 /// The representation of a auditable key directory
 pub struct Directory<TC, S: Database, V> {
@@ -38,6 +110,32 @@ pub struct Directory<TC, S: Database, V> {
     /// (in this case we do utilize the write() lock which can only occur 1
     /// at a time and gates further read() locks being acquired during write()).
     cache_lock: Arc<RwLock<()>>,
+    /// The time of the last successful publish by this instance (or any of its clones)
+    last_publish: Arc<std::sync::Mutex<Option<Instant>>>,
+    /// The operator which is recorded in the operations log for mutating operations
+    operator: Arc<std::sync::RwLock<Operator>>,
+    /// The sequence number of the next entry in the operations log, once it is known
+    next_operation: Arc<tokio::sync::Mutex<Option<u64>>>,
+    /// The hooks which are notified of the events of this instance and its clones
+    hooks: Arc<std::sync::RwLock<Vec<Arc<dyn DirectoryHooks>>>>,
+    /// The hot labels of this instance and its clones, and their precomputed lookup proofs
+    hot_labels: Arc<std::sync::RwLock<HotLabels>>,
+    /// Notifies the background task which precomputes the lookup proofs of the hot labels of
+    /// the epochs published by this instance and its clones, once there are hot labels
+    hot_label_worker: Arc<std::sync::Mutex<Option<tokio::sync::watch::Sender<()>>>>,
+    /// The cached lookup proofs of this instance and its clones
+    proof_cache: Arc<std::sync::RwLock<ProofCache>>,
+    /// The lookups of this instance and its clones which are generating a proof, when
+    /// concurrent lookups are coalesced
+    lookup_flights: Arc<LookupFlights>,
+    /// The recent lookups of this instance and its clones, when prefetching is enabled
+    prefetcher: Arc<std::sync::Mutex<Option<AccessTracker>>>,
+    /// The source of the epochs of the publishes of this instance and its clones, if they
+    /// are scheduled externally
+    epoch_source: Arc<std::sync::RwLock<Option<Arc<dyn EpochSource>>>>,
+    /// The most recent timing report for each profiled operation
+    #[cfg(feature = "profiling")]
+    timing_reports: Arc<std::sync::Mutex<HashMap<Operation, TimingReport>>>,
     tc: PhantomData<TC>,
 }
 
@@ -48,6 +146,18 @@ impl<TC, S: Database, V: VRFKeyStorage> Clone for Directory<TC, S, V> {
             storage: self.storage.clone(),
             vrf: self.vrf.clone(),
             cache_lock: self.cache_lock.clone(),
+            last_publish: self.last_publish.clone(),
+            operator: self.operator.clone(),
+            next_operation: self.next_operation.clone(),
+            hooks: self.hooks.clone(),
+            hot_labels: self.hot_labels.clone(),
+            hot_label_worker: self.hot_label_worker.clone(),
+            proof_cache: self.proof_cache.clone(),
+            lookup_flights: self.lookup_flights.clone(),
+            prefetcher: self.prefetcher.clone(),
+            epoch_source: self.epoch_source.clone(),
+            #[cfg(feature = "profiling")]
+            timing_reports: self.timing_reports.clone(),
             tc: PhantomData,
         }
     }
@@ -59,14 +169,36 @@ where
     S: Database + 'static,
     V: VRFKeyStorage,
 {
+    /// Returns a [DirectoryBuilder], which creates a directory from the storage manager and
+    /// VRF given to it, along with any further options
+    pub fn builder() -> DirectoryBuilder<TC, S, V> {
+        DirectoryBuilder::default()
+    }
+
     /// Creates a new (stateless) instance of a auditable key directory.
     /// Takes as input a pointer to the storage being used for this instance.
-    /// The state is stored in the storage.
+    /// The state is stored in the storage. If the storage manager has a write-ahead
+    /// log, any commits which it recorded but which did not complete are replayed first.
     pub async fn new(storage: StorageManager<S>, vrf: V) -> Result<Self, AkdError> {
+        // complete any commit which was interrupted before the directory was last stopped
+        let num_recovered = storage.recover_write_ahead_log().await?;
+        if num_recovered > 0 {
+            info!(
+                num_recovered,
+                "Recovered incomplete commits from the write-ahead log"
+            );
+        }
         let azks = Directory::<TC, S, V>::get_azks_from_storage(&storage, false).await;
 
         if let Err(AkdError::Storage(StorageError::NotFound(e))) = azks {
-            info!("No aZKS was found in storage: {e}. Creating a new aZKS!");
+            if storage.role() == StorageRole::Follower {
+                return Err(AkdError::Directory(DirectoryError::ReadOnlyDirectory(
+                    format!(
+                    "A follower cannot start before its leader has created the AZKS, error: {e}"
+                ),
+                )));
+            }
+            info!(error = %e, "No aZKS was found in storage, creating a new aZKS");
             // generate + store a new azks only if one is not found
             let new_azks = Azks::new::<TC, _>(&storage).await?;
             storage.set(DbRecord::Azks(new_azks)).await?;
@@ -79,7 +211,19 @@ where
         Ok(Directory {
             storage,
             cache_lock: Arc::new(RwLock::new(())),
+            last_publish: Arc::new(std::sync::Mutex::new(None)),
+            operator: Arc::new(std::sync::RwLock::new(Operator::default())),
+            next_operation: Arc::new(tokio::sync::Mutex::new(None)),
+            hooks: Arc::new(std::sync::RwLock::new(vec![])),
+            hot_labels: Arc::new(std::sync::RwLock::new(HotLabels::default())),
+            hot_label_worker: Arc::new(std::sync::Mutex::new(None)),
+            proof_cache: Arc::new(std::sync::RwLock::new(ProofCache::default())),
+            lookup_flights: Arc::new(LookupFlights::default()),
+            prefetcher: Arc::new(std::sync::Mutex::new(None)),
+            epoch_source: Arc::new(std::sync::RwLock::new(None)),
             vrf,
+            #[cfg(feature = "profiling")]
+            timing_reports: Arc::new(std::sync::Mutex::new(HashMap::new())),
             tc: PhantomData,
         })
     }
@@ -88,11 +232,109 @@ where
     ///
     /// Note that the vector of label-value pairs should not contain any entries with duplicate labels. This
     /// condition is explicitly checked, and an error will be returned if this is the case.
+    ///
+    /// Updates whose values are already the current values of their labels are skipped, rather than
+    /// published as new versions. See [Directory::publish_with_limits] for a publish which reports them.
+    ///
+    /// If an [EpochSource] has been set (see [Directory::set_epoch_source]), the publish commits
+    /// the epoch which it provides, as with [Directory::publish_at_epoch].
     pub async fn publish(&self, updates: Vec<(AkdLabel, AkdValue)>) -> Result<EpochHash, AkdError> {
-        // The guard will be dropped at the end of the publish
-        let _guard = self.cache_lock.read().await;
+        let mut timer = PhaseTimer::new(Operation::Publish);
+        let (epoch_hash, _) = self
+            .publish_timed(updates, None, None, &mut timer)
+            .await
+            .inspect_err(|error| self.notify_hooks(|hook| hook.on_publish_failed(error)))?;
+        self.record_timing(timer);
+        Ok(epoch_hash)
+    }
+
+    /// Updates the directory to include the input label-value pairs, as with
+    /// [Directory::publish], and returns a [TimingReport] breaking the publish down into its
+    /// phases: preloading the previous versions and tree nodes, evaluating the VRF, mutating
+    /// the tree, hashing, writing, and committing. Each phase reports its duration and the
+    /// number of bytes it read from or wrote to storage.
+    #[cfg(feature = "profiling")]
+    pub async fn publish_with_report(
+        &self,
+        updates: Vec<(AkdLabel, AkdValue)>,
+    ) -> Result<(EpochHash, TimingReport), AkdError> {
+        let mut timer = PhaseTimer::new(Operation::Publish);
+        let (epoch_hash, _) = self
+            .publish_timed(updates, None, None, &mut timer)
+            .await
+            .inspect_err(|error| self.notify_hooks(|hook| hook.on_publish_failed(error)))?;
+        Ok((epoch_hash, self.record_timing(timer)))
+    }
+
+    /// Updates the directory to include the input label-value pairs, as with [Directory::publish],
+    /// committing them in the given epoch rather than the one following the current epoch. The epoch
+    /// must be greater than the current epoch, and at most [scheduling::MAX_EPOCH_GAP] epochs ahead of
+    /// it; the epochs in between hold the same tree as the current epoch. The epoch is committed even
+    /// if there are no updates to publish. See [crate::scheduling].
+    pub async fn publish_at_epoch(
+        &self,
+        updates: Vec<(AkdLabel, AkdValue)>,
+        epoch: u64,
+    ) -> Result<EpochHash, AkdError> {
+        let mut timer = PhaseTimer::new(Operation::Publish);
+        let (epoch_hash, _) = self
+            .publish_timed(updates, Some(epoch), None, &mut timer)
+            .await
+            .inspect_err(|error| self.notify_hooks(|hook| hook.on_publish_failed(error)))?;
+        self.record_timing(timer);
+        Ok(epoch_hash)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "publish", skip_all, fields(num_updates = updates.len())))]
+    async fn publish_timed(
+        &self,
+        updates: Vec<(AkdLabel, AkdValue)>,
+        epoch: Option<u64>,
+        link_label: Option<&AkdLabel>,
+        timer: &mut PhaseTimer,
+    ) -> Result<(EpochHash, Vec<AkdLabel>), AkdError> {
+        if self.role() == StorageRole::Follower {
+            return Err(AkdError::Directory(DirectoryError::ReadOnlyDirectory(
+                "A follower cannot publish, since only the leader writes to the storage"
+                    .to_string(),
+            )));
+        }
+        let lease = self.storage.acquire_publish_lease().await?;
+        let result = self
+            .publish_leased(updates, epoch, link_label, timer, lease.as_ref())
+            .await;
+        if let Some(lease) = lease {
+            if let Err(err) = lease.release().await {
+                warn!(error = %err, "Failed to release the publish lease");
+            }
+        }
+        result
+    }
+
+    /// Publishes while holding the publish lease, if the storage manager requires one. The
+    /// lease is renewed in the same conditional write as the commit, so that a publisher whose
+    /// lease has been taken over does not commit. The epoch of the publish is either given, provided by the epoch
+    /// source, or the one following the current epoch. Only the update of `link_label` may
+    /// publish a link record (see [Directory::rename_label]). Returns the labels of the updates
+    /// which were skipped, as they would not have changed the values of their labels.
+    async fn publish_leased(
+        &self,
+        updates: Vec<(AkdLabel, AkdValue)>,
+        epoch: Option<u64>,
+        link_label: Option<&AkdLabel>,
+        timer: &mut PhaseTimer,
+        lease: Option<&PublishLeaseGuard<S>>,
+    ) -> Result<(EpochHash, Vec<AkdLabel>), AkdError> {
+        // The guard is upgraded to a write guard for the commit (see below)
+        let guard = self.cache_lock.read().await;
+        let start = Instant::now();
 
-        // Check for duplicate labels and return an error if any are encountered
+        // Check for duplicate labels and return an error if any are encountered. Labels which
+        // only differ before they are normalized are duplicates.
+        let updates = updates
+            .into_iter()
+            .map(|(label, value)| (normalize_label::<TC>(label), value))
+            .collect::<Vec<_>>();
         let distinct_set: HashSet<AkdLabel> =
             updates.iter().map(|(label, _)| label.clone()).collect();
         if distinct_set.len() != updates.len() {
@@ -100,50 +342,356 @@ where
                 "Cannot publish with a set of entries that contain duplicate labels".to_string(),
             )));
         }
+        // Values which carry tombstone metadata would be mistaken for tombstones by clients,
+        // and likewise for redaction records
+        if let Some((label, _)) = updates.iter().find(|(_, value)| {
+            TC::tombstone_metadata(value).is_some() || RedactionRecord::from_value(value).is_some()
+        }) {
+            return Err(AkdError::Directory(DirectoryError::Publish(format!(
+                "The value of label {} cannot be published, as it is encoded as a tombstone",
+                instrumentation::label_hash_prefix::<TC>(label)
+            ))));
+        }
+        // Links are only published by renames, since clients follow them to the linked label
+        if let Some((label, _)) = updates
+            .iter()
+            .find(|(label, value)| value.link_target().is_some() && Some(label) != link_label)
+        {
+            return Err(AkdError::Directory(DirectoryError::Publish(format!(
+                "The value of label {} cannot be published, as it is encoded as a link",
+                instrumentation::label_hash_prefix::<TC>(label)
+            ))));
+        }
 
         let mut update_set = Vec::<AzksElement>::new();
         let mut user_data_update_set = Vec::<ValueState>::new();
+        let mut skipped = Vec::<AkdLabel>::new();
 
+        timer.begin(Phase::Preload);
         let mut current_azks = self.retrieve_azks().await?;
         let current_epoch = current_azks.get_latest_epoch();
-        let next_epoch = current_epoch + 1;
+        let epoch_source = self
+            .epoch_source
+            .read()
+            .ok()
+            .and_then(|source| source.clone());
+        let scheduled_epoch = match (epoch, epoch_source) {
+            (Some(epoch), _) => Some(epoch),
+            (None, Some(source)) => Some(source.next_epoch(current_epoch).await?),
+            (None, None) => None,
+        };
+        let next_epoch = match scheduled_epoch {
+            Some(epoch) => {
+                scheduling::check_next_epoch(current_epoch, epoch)?;
+                epoch
+            }
+            None => current_epoch + 1,
+        };
 
-        let mut keys: Vec<AkdLabel> = updates
-            .iter()
-            .map(|(akd_label, _val)| akd_label.clone())
-            .collect();
+        let commitment_key = self.derive_commitment_key().await?;
 
-        // sort the keys, as inserting in primary-key order is more efficient for MySQL
-        keys.sort();
+        // sort the updates by label, as inserting in primary-key order is more efficient for MySQL
+        let mut updates = updates;
+        updates.sort_by(|(a, _), (b, _)| a.cmp(b));
 
-        // we're only using the maximum "version" of the user's state at the last epoch
-        // they were seen in the directory. Therefore we've minimized the call to only
-        // return a hashmap of AkdLabel => u64 and not retrieving the other data which is not
-        // read (i.e. the actual _data_ payload).
-        let all_user_versions_retrieved = self
-            .storage
-            .get_user_state_versions(&keys, ValueStateRetrievalFlag::LeqEpoch(current_epoch))
+        // The publish is pipelined in chunks: a background task retrieves the previous
+        // user versions for each chunk from storage, while the current task computes the
+        // VRF labels and commitments for the chunks which have already been retrieved, and
+        // another background task preloads into the cache the tree nodes along the paths of
+        // the chunks which have already been hashed. The channels are bounded so that no
+        // stage runs too far ahead of the next one. The tree is then mutated and the epoch
+        // committed as a single transaction once every chunk has been hashed, since the
+        // commit has to be atomic.
+        let (chunk_tx, mut chunk_rx) = tokio::sync::mpsc::channel(PUBLISH_PIPELINE_DEPTH);
+        let storage = self.storage.clone();
+        let retrieval_handle = tokio::task::spawn(instrumentation::in_current_span(async move {
+            let mut updates_iter = updates.into_iter();
+            loop {
+                let chunk: Vec<(AkdLabel, AkdValue)> = updates_iter
+                    .by_ref()
+                    .take(PUBLISH_PIPELINE_CHUNK_SIZE)
+                    .collect();
+                if chunk.is_empty() {
+                    break;
+                }
+                let keys: Vec<AkdLabel> = chunk
+                    .iter()
+                    .map(|(akd_label, _val)| akd_label.clone())
+                    .collect();
+
+                // we're only using the maximum "version" of the user's state at the last epoch
+                // they were seen in the directory. Therefore we've minimized the call to only
+                // return a hashmap of AkdLabel => u64 and not retrieving the other data which is not
+                // read (i.e. the actual _data_ payload).
+                let user_versions = storage
+                    .get_user_state_versions(
+                        &keys,
+                        ValueStateRetrievalFlag::LeqEpoch(current_epoch),
+                    )
+                    .await?;
+
+                if chunk_tx.send((chunk, user_versions)).await.is_err() {
+                    // The receiving end has been dropped, which means that hashing
+                    // failed and the publish is being aborted
+                    break;
+                }
+            }
+            Ok::<(), AkdError>(())
+        }));
+
+        // Without a cache, there is nowhere to preload the tree nodes into
+        let (element_tx, preload_handle) = if self.storage.has_cache() {
+            let (element_tx, mut element_rx) =
+                tokio::sync::mpsc::channel::<Vec<AzksElement>>(PUBLISH_PIPELINE_DEPTH);
+            let azks = current_azks.clone();
+            let storage = self.storage.clone();
+            let preload_handle = tokio::task::spawn(instrumentation::in_current_span(async move {
+                let mut load_bytes = 0;
+                while let Some(elements) = element_rx.recv().await {
+                    let (_, chunk_bytes) = azks
+                        .preload_nodes(&storage, &AzksElementSet::from(elements))
+                        .await?;
+                    load_bytes += chunk_bytes;
+                }
+                Ok::<usize, AkdError>(load_bytes)
+            }));
+            (Some(element_tx), Some(preload_handle))
+        } else {
+            (None, None)
+        };
+
+        let mut num_requested = 0;
+        let mut num_retrieved = 0;
+        loop {
+            // Waiting for the retrieval of the next chunk is part of the preload
+            timer.begin(Phase::Preload);
+            let Some((chunk, user_versions)) = chunk_rx.recv().await else {
+                break;
+            };
+            timer.add_bytes(Phase::Preload, || {
+                user_versions
+                    .iter()
+                    .map(|(label, (_, value))| {
+                        label.0.len() + std::mem::size_of::<u64>() + value.0.len()
+                    })
+                    .sum()
+            });
+            num_requested += chunk.len();
+            num_retrieved += user_versions.len();
+            let num_hashed = update_set.len();
+            self.compute_chunk_updates(
+                chunk,
+                &user_versions,
+                &commitment_key,
+                next_epoch,
+                &mut update_set,
+                &mut user_data_update_set,
+                &mut skipped,
+                timer,
+            )
             .await?;
+            if let Some(element_tx) = &element_tx {
+                // A failed preload only means that the insertion loads the nodes itself, so
+                // its error is reported below rather than aborting the hashing
+                let _ = element_tx.send(update_set[num_hashed..].to_vec()).await;
+            }
+        }
+        retrieval_handle
+            .await
+            .map_err(|e| AkdError::Parallelism(ParallelismError::JoinErr(e.to_string())))??;
+        drop(element_tx);
+        if let Some(preload_handle) = preload_handle {
+            timer.begin(Phase::Preload);
+            match preload_handle.await {
+                Ok(Ok(load_bytes)) => timer.add_bytes(Phase::Preload, || load_bytes),
+                Ok(Err(err)) => warn!(error = %err, "Failed to preload the tree for the publish"),
+                Err(err) => warn!(error = %err, "The preload of the tree for the publish panicked"),
+            }
+        }
 
         info!(
-            "Retrieved {} previous user versions of {} requested",
-            all_user_versions_retrieved.len(),
-            keys.len()
+            epoch = current_epoch,
+            num_retrieved, num_requested, "Retrieved previous user versions"
         );
 
-        let vrf_computations = updates
-            .iter()
+        self.check_node_label_collisions(&update_set, current_epoch)
+            .await?;
+
+        // A scheduled epoch is committed even without updates, so as to keep to its timeline
+        if update_set.is_empty() && scheduled_epoch.is_none() {
+            info!(
+                epoch = current_epoch,
+                "After filtering for duplicated user information, there is no publish which is necessary (0 updates)"
+            );
+            // The AZKS has not been updated/mutated at this point, so we can just return the root hash from before
+            let root_hash = current_azks.get_root_hash::<TC, _>(&self.storage).await?;
+            return Ok((EpochHash(current_epoch, root_hash), skipped));
+        }
+
+        if !self.storage.begin_transaction() {
+            error!("Transaction is already active");
+            return Err(AkdError::Storage(StorageError::Transaction(
+                "Transaction is already active".to_string(),
+            )));
+        }
+        info!(
+            epoch = next_epoch,
+            batch_size = update_set.len(),
+            "Starting inserting new leaves"
+        );
+
+        current_azks.skip_to_epoch(next_epoch);
+        if let Err(err) = current_azks
+            .batch_insert_nodes_timed::<TC, _>(
+                &self.storage,
+                update_set,
+                InsertMode::Directory,
+                timer,
+            )
+            .await
+        {
+            // If we fail to do the batch-leaf insert, we should rollback the transaction so we can try again cleanly.
+            // Only fails if transaction is not currently active.
+            let _ = self.storage.rollback_transaction();
+            // bubble up the err
+            return Err(err);
+        }
+
+        // Until the new head of the tree is written, concurrent proof generations only observe
+        // the transaction's nodes as of the current epoch. The commit itself is exclusive, so
+        // that no proof generation can observe the head of one epoch with the nodes of another,
+        // or span two commits (after which the nodes no longer hold their values for its epoch).
+        drop(guard);
+        let commit_guard = self.cache_lock.write().await;
+
+        // batch all the inserts into a single write to storage (in this case it insert's into the transaction log)
+        timer.begin(Phase::Write);
+        let num_updates = user_data_update_set.len();
+        let mut updates = vec![DbRecord::Azks(current_azks.clone())];
+        for update in user_data_update_set.into_iter() {
+            updates.push(DbRecord::ValueState(update));
+        }
+        let mut next_operation = self.next_operation.lock().await;
+        let operation = match self
+            .operation_record(
+                &mut next_operation,
+                OperationKind::Publish,
+                next_epoch,
+                format!("Published {num_updates} updates"),
+            )
+            .await
+        {
+            Ok(operation) => operation,
+            Err(err) => {
+                // Roll back, so that the transaction does not block later publishes (and the
+                // cleaning of the cache)
+                let _ = self.storage.rollback_transaction();
+                return Err(err);
+            }
+        };
+        let operation_sequence = operation.sequence;
+        updates.push(DbRecord::OperationRecord(operation));
+        timer.add_bytes(Phase::Write, || {
+            updates.iter().map(|record| record.size_of()).sum()
+        });
+        if let Err(err) = self.storage.batch_set(updates).await {
+            let _ = self.storage.rollback_transaction();
+            return Err(AkdError::Storage(err));
+        }
+
+        // Commit the transaction
+        timer.begin(Phase::Commit);
+        timer.add_bytes(Phase::Commit, || self.storage.transaction_size_of());
+        info!("Committing transaction");
+        match self
+            .storage
+            .commit_transaction_after(current_epoch, lease)
+            .await
+        {
+            Ok(num_records) => {
+                *next_operation = Some(operation_sequence + 1);
+                info!(epoch = next_epoch, num_records, "Transaction committed");
+            }
+            Err(err) => {
+                error!(error = %err, "Failed to commit transaction, rolling back");
+                let _ = self.storage.rollback_transaction();
+                return Err(AkdError::Storage(err));
+            }
+        };
+        drop(commit_guard);
+
+        timer.begin(Phase::Hash);
+        let root_hash = current_azks
+            .get_root_hash_safe::<TC, _>(&self.storage, next_epoch)
+            .await?;
+
+        instrumentation::record_publish(num_updates, next_epoch, start);
+        if let Ok(mut last_publish) = self.last_publish.lock() {
+            *last_publish = Some(Instant::now());
+        }
+        let epoch_hash = EpochHash(next_epoch, root_hash);
+        self.notify_hooks(|hook| hook.on_epoch_published(&epoch_hash, num_updates));
+        // A publish which commits no epoch has returned before this, and leaves the precomputed
+        // proofs as they are. The proofs of the hot labels are precomputed in the background,
+        // rather than before the publish returns, and the cached proofs of the earlier epoch are
+        // discarded once the first proof of the new epoch is cached (see [crate::proof_cache]).
+        self.notify_hot_label_worker();
+        Ok((epoch_hash, skipped))
+    }
+
+    /// Checks that the node labels of a publish collide neither with each other nor with the
+    /// leaves of the tree, which is only possible when the configuration truncates node labels
+    /// (see [Configuration::node_label_len])
+    async fn check_node_label_collisions(
+        &self,
+        update_set: &[AzksElement],
+        current_epoch: u64,
+    ) -> Result<(), AkdError> {
+        if TC::node_label_len() >= 256 {
+            return Ok(());
+        }
+        let mut labels = HashSet::with_capacity(update_set.len());
+        for element in update_set {
+            if !labels.insert(element.label) {
+                return Err(node_label_collision(&element.label));
+            }
+        }
+        // The leaves are the only nodes whose labels have the full length
+        let keys = labels.into_iter().map(NodeKey).collect::<Vec<_>>();
+        let existing =
+            TreeNode::batch_get_from_storage(&self.storage, &keys, current_epoch).await?;
+        match existing.first() {
+            Some(node) => Err(node_label_collision(&node.label)),
+            None => Ok(()),
+        }
+    }
+
+    /// Computes the VRF labels and commitments for a chunk of publish updates, given the
+    /// previous versions of the chunk's labels retrieved from storage. The resulting tree
+    /// elements and value states are appended to the provided output vectors, along with the
+    /// labels of the updates which are skipped as they would not change their values.
+    #[allow(clippy::too_many_arguments)]
+    async fn compute_chunk_updates(
+        &self,
+        chunk: Vec<(AkdLabel, AkdValue)>,
+        user_versions: &HashMap<AkdLabel, (u64, AkdValue)>,
+        commitment_key: &Digest,
+        next_epoch: u64,
+        update_set: &mut Vec<AzksElement>,
+        user_data_update_set: &mut Vec<ValueState>,
+        skipped: &mut Vec<AkdLabel>,
+        timer: &mut PhaseTimer,
+    ) -> Result<(), AkdError> {
+        timer.begin(Phase::VrfEvaluation);
+        let vrf_computations = chunk
+            .into_iter()
             .flat_map(
-                |(akd_label, akd_value)| match all_user_versions_retrieved.get(akd_label) {
-                    None => vec![(
-                        akd_label.clone(),
-                        VersionFreshness::Fresh,
-                        1u64,
-                        akd_value.clone(),
-                    )],
+                |(akd_label, akd_value)| match user_versions.get(&akd_label) {
+                    None => vec![(akd_label, VersionFreshness::Fresh, 1u64, akd_value)],
                     Some((latest_version, existing_akd_value)) => {
-                        if existing_akd_value == akd_value {
+                        if existing_akd_value == &akd_value {
                             // Skip this because the user is trying to re-publish the same value
+                            skipped.push(akd_label);
                             return vec![];
                         }
                         vec![
@@ -154,10 +702,10 @@ where
                                 akd_value.clone(),
                             ),
                             (
-                                akd_label.clone(),
+                                akd_label,
                                 VersionFreshness::Fresh,
                                 *latest_version + 1,
-                                akd_value.clone(),
+                                akd_value,
                             ),
                         ]
                     }
@@ -172,13 +720,12 @@ where
             .into_iter()
             .collect::<HashMap<_, _>>();
 
-        let commitment_key = self.derive_commitment_key().await?;
-
+        timer.begin(Phase::Hash);
         for ((akd_label, freshness, version, akd_value), node_label) in vrf_map {
             let azks_value = match freshness {
                 VersionFreshness::Stale => TC::stale_azks_value(),
                 VersionFreshness::Fresh => {
-                    TC::compute_fresh_azks_value(&commitment_key, &node_label, version, &akd_value)
+                    TC::compute_fresh_azks_value(commitment_key, &node_label, version, &akd_value)
                 }
             };
             update_set.push(AzksElement {
@@ -193,57 +740,239 @@ where
             }
         }
 
-        if update_set.is_empty() {
-            info!("After filtering for duplicated user information, there is no publish which is necessary (0 updates)");
-            // The AZKS has not been updated/mutated at this point, so we can just return the root hash from before
-            let root_hash = current_azks.get_root_hash::<TC, _>(&self.storage).await?;
-            return Ok(EpochHash(current_epoch, root_hash));
-        }
+        Ok(())
+    }
 
-        if !self.storage.begin_transaction() {
-            error!("Transaction is already active");
-            return Err(AkdError::Storage(StorageError::Transaction(
-                "Transaction is already active".to_string(),
+    /// Moves the history of a label to a new label, such as following a change of phone number.
+    /// A link record to `new_label` (see [AkdValue::link_to]) is published as the final value of
+    /// `old_label`, in the same epoch as `value` is published as the first value of `new_label`.
+    /// Clients can then follow the history of the old label to the new one with
+    /// [Directory::linked_key_history].
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn rename_label(
+        &self,
+        old_label: AkdLabel,
+        new_label: AkdLabel,
+        value: AkdValue,
+    ) -> Result<EpochHash, AkdError> {
+        let old_label = normalize_label::<TC>(old_label);
+        let new_label = normalize_label::<TC>(new_label);
+        if old_label == new_label {
+            return Err(AkdError::Directory(DirectoryError::Publish(
+                "Cannot rename a label to itself".to_string(),
+            )));
+        }
+        let versions = self
+            .storage
+            .get_user_state_versions(
+                &[old_label.clone(), new_label.clone()],
+                ValueStateRetrievalFlag::MaxEpoch,
+            )
+            .await?;
+        match versions.get(&old_label) {
+            None => {
+                return Err(AkdError::Directory(DirectoryError::Publish(format!(
+                    "Cannot rename the unregistered label {}",
+                    instrumentation::label_hash_prefix::<TC>(&old_label)
+                ))))
+            }
+            Some((_, value)) if value.link_target().is_some() => {
+                return Err(AkdError::Directory(DirectoryError::Publish(format!(
+                    "The label {} has already been renamed",
+                    instrumentation::label_hash_prefix::<TC>(&old_label)
+                ))))
+            }
+            Some(_) => {}
+        }
+        if versions.contains_key(&new_label) {
+            return Err(AkdError::Directory(DirectoryError::LabelRegistered(
+                instrumentation::label_hash_prefix::<TC>(&new_label),
             )));
         }
-        info!("Starting inserting new leaves");
 
-        if let Err(err) = current_azks
-            .batch_insert_nodes::<TC, _>(&self.storage, update_set, InsertMode::Directory)
+        info!(
+            old_label = %instrumentation::label_hash_prefix::<TC>(&old_label),
+            new_label = %instrumentation::label_hash_prefix::<TC>(&new_label),
+            "Renaming label"
+        );
+        let link = AkdValue::link_to(&new_label);
+        let mut timer = PhaseTimer::new(Operation::Publish);
+        let (epoch_hash, _) = self
+            .publish_timed(
+                vec![(old_label.clone(), link), (new_label, value)],
+                None,
+                Some(&old_label),
+                &mut timer,
+            )
             .await
-        {
-            // If we fail to do the batch-leaf insert, we should rollback the transaction so we can try again cleanly.
-            // Only fails if transaction is not currently active.
-            let _ = self.storage.rollback_transaction();
-            // bubble up the err
-            return Err(err);
+            .inspect_err(|error| self.notify_hooks(|hook| hook.on_publish_failed(error)))?;
+        self.record_timing(timer);
+        Ok(epoch_hash)
+    }
+
+    /// Updates the directory to include the input label-value pairs, as with [Directory::publish],
+    /// while enforcing the provided [PublishLimits] on the labels being updated. Depending on the
+    /// [LimitEnforcement], a publish containing updates which exceed the limits is either rejected
+    /// entirely, or published without those updates. The updates which exceeded the limits are
+    /// reported in the returned [PublishResult].
+    ///
+    /// Updates whose values are already the current values of their labels do not create new
+    /// versions (as with [Directory::publish]), and are reported as skipped, so that retried
+    /// submissions do not inflate the histories of their labels. If every update is skipped or
+    /// dropped, no epoch is published and the current epoch is returned, unless an
+    /// [EpochSource] is configured, in which case the epoch it schedules is published without
+    /// updates (as with [Directory::publish]).
+    pub async fn publish_with_limits(
+        &self,
+        updates: Vec<(AkdLabel, AkdValue)>,
+        limits: &PublishLimits,
+    ) -> Result<PublishResult, AkdError> {
+        let updates = updates
+            .into_iter()
+            .map(|(label, value)| (normalize_label::<TC>(label), value))
+            .collect::<Vec<_>>();
+        let violations = self.check_publish_limits(&updates, limits).await?;
+        if !violations.is_empty() && limits.enforcement == LimitEnforcement::Reject {
+            return Err(AkdError::Directory(DirectoryError::Publish(format!(
+                "{} updates exceed the publish limits: {:?}",
+                violations.len(),
+                violations
+            ))));
         }
 
-        // batch all the inserts into a single write to storage (in this case it insert's into the transaction log)
-        let mut updates = vec![DbRecord::Azks(current_azks.clone())];
-        for update in user_data_update_set.into_iter() {
-            updates.push(DbRecord::ValueState(update));
+        let violating_labels = violations
+            .iter()
+            .map(|violation| violation.label())
+            .collect::<HashSet<_>>();
+        let updates = updates
+            .into_iter()
+            .filter(|(label, _)| !violating_labels.contains(label))
+            .collect::<Vec<_>>();
+        if !violations.is_empty() {
+            warn!(
+                num_violations = violations.len(),
+                "Dropping updates which exceed the publish limits"
+            );
+            for violation in &violations {
+                debug!(
+                    label = %instrumentation::label_hash_prefix::<TC>(violation.label()),
+                    limit = violation.limit(),
+                    "Dropping update which exceeds the publish limits"
+                );
+            }
         }
-        self.storage.batch_set(updates).await?;
 
-        // Commit the transaction
-        info!("Committing transaction");
-        match self.storage.commit_transaction().await {
-            Ok(num_records) => {
-                info!("Transaction committed ({} records)", num_records);
+        // Updates which would not change the value of their label are skipped by the publish,
+        // and so are reported rather than counted as published. They are determined by the
+        // publish itself, from the state of the directory which its commit is conditional on.
+        let mut timer = PhaseTimer::new(Operation::Publish);
+        let (epoch_hash, skipped) = self
+            .publish_timed(updates, None, None, &mut timer)
+            .await
+            .inspect_err(|error| self.notify_hooks(|hook| hook.on_publish_failed(error)))?;
+        self.record_timing(timer);
+        if !skipped.is_empty() {
+            debug!(
+                num_skipped = skipped.len(),
+                "Skipped updates which would not change the values of their labels"
+            );
+        }
+        Ok(PublishResult {
+            epoch_hash,
+            violations,
+            skipped,
+        })
+    }
+
+    /// Returns the updates which would exceed the provided limits if they were published in the next epoch
+    async fn check_publish_limits(
+        &self,
+        updates: &[(AkdLabel, AkdValue)],
+        limits: &PublishLimits,
+    ) -> Result<Vec<LimitViolation>, AkdError> {
+        // The lengths and bytes of the updates are checked first, as they do not depend on
+        // the state of the directory
+        let mut violations = vec![];
+        let mut checked_updates = vec![];
+        for (label, value) in updates {
+            if let Some(violation) = check_update_contents(label, value, limits) {
+                violations.push(violation);
+            } else {
+                checked_updates.push((label.clone(), value));
             }
-            Err(err) => {
-                error!("Failed to commit transaction, rolling back");
-                let _ = self.storage.rollback_transaction();
-                return Err(AkdError::Storage(err));
+        }
+        if limits.max_versions.is_none() && limits.rate_limit.is_none() {
+            return Ok(violations);
+        }
+        let next_epoch = self.retrieve_azks().await?.get_latest_epoch() + 1;
+        let labels = checked_updates
+            .iter()
+            .map(|(label, _)| label.clone())
+            .collect::<Vec<_>>();
+        let current_versions = self
+            .storage
+            .get_user_state_versions(&labels, ValueStateRetrievalFlag::MaxEpoch)
+            .await?;
+        // The versions of each label as of the epoch just before the rate-limiting window
+        let window_start_versions = match limits.rate_limit {
+            Some(rate_limit) if next_epoch > rate_limit.window_epochs => {
+                self.storage
+                    .get_user_state_versions(
+                        &labels,
+                        ValueStateRetrievalFlag::LeqEpoch(next_epoch - rate_limit.window_epochs),
+                    )
+                    .await?
             }
+            _ => HashMap::new(),
         };
 
-        let root_hash = current_azks
-            .get_root_hash_safe::<TC, _>(&self.storage, next_epoch)
-            .await?;
-
-        Ok(EpochHash(next_epoch, root_hash))
+        for (label, value) in checked_updates {
+            // An update which does not change the value of its label is skipped by the
+            // publish, and so creates no version to count against the limits
+            let (current_version, current_value) = match current_versions.get(&label) {
+                Some((version, current_value)) => (*version, Some(current_value)),
+                None => (0, None),
+            };
+            if current_value == Some(value) {
+                continue;
+            }
+            if let Some(max_versions) = limits.max_versions {
+                if current_version >= max_versions {
+                    violations.push(LimitViolation::MaxVersions {
+                        label,
+                        max_versions,
+                    });
+                    continue;
+                }
+            }
+            if let Some(rate_limit) = limits.rate_limit {
+                let window_start_version = window_start_versions
+                    .get(&label)
+                    .map_or(0, |(version, _)| *version);
+                if current_version - window_start_version >= rate_limit.max_updates {
+                    violations.push(LimitViolation::RateLimit { label, rate_limit });
+                }
+            }
+        }
+        Ok(violations)
+    }
+
+    /// Publishes all of the updates pending in the queue as a new epoch. If the publish fails,
+    /// the updates are returned to the queue so that they can be retried, unless newer updates
+    /// to the same labels have been submitted in the meantime.
+    pub async fn publish_queued(&self, queue: &PublishQueue) -> Result<EpochHash, AkdError> {
+        let updates = queue.drain();
+        let num_updates = updates.len();
+        match self.publish(updates.clone()).await {
+            Ok(epoch_hash) => {
+                queue.record_published(num_updates);
+                Ok(epoch_hash)
+            }
+            Err(err) => {
+                queue.restore(updates);
+                Err(err)
+            }
+        }
     }
 
     /// Provides proof for correctness of latest version
@@ -252,31 +981,474 @@ where
     ///
     /// Returns [Ok((LookupProof, EpochHash))] upon successful generation for the latest version
     /// of the target label's state. [Err(_)] otherwise
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     pub async fn lookup(&self, akd_label: AkdLabel) -> Result<(LookupProof, EpochHash), AkdError> {
         // The guard will be dropped at the end of the proof generation
         let _guard = self.cache_lock.read().await;
+        let start = Instant::now();
+        let akd_label = normalize_label::<TC>(akd_label);
+        self.record_lookups(std::slice::from_ref(&akd_label));
+
+        let mut timer = PhaseTimer::new(Operation::Lookup);
+        timer.begin(Phase::Preload);
+        let current_azks = self.retrieve_azks().await?;
+        let current_epoch = current_azks.get_latest_epoch();
+        let root_hash = EpochHash(
+            current_epoch,
+            current_azks.get_root_hash::<TC, _>(&self.storage).await?,
+        );
+        let precomputed = self
+            .hot_labels
+            .read()
+            .ok()
+            .and_then(|hot_labels| hot_labels.get(&akd_label, &root_hash));
+        if let Some(proof) = precomputed {
+            instrumentation::record_hot_lookup();
+            return Ok((proof, root_hash));
+        }
+        let cache_key = ProofCacheKey {
+            epoch_hash: root_hash.clone(),
+            label: akd_label.clone(),
+        };
+        if let Some(proof) = self.cached_proof(&cache_key) {
+            return Ok((proof, root_hash));
+        }
+
+        let generate = async {
+            let lookup_info = self.get_lookup_info(akd_label, current_epoch).await?;
+            let proof = self
+                .lookup_with_info(&current_azks, lookup_info, false, &mut timer)
+                .await?;
+            self.record_timing(timer);
+            instrumentation::record_proof("lookup", start, &proof);
+            Ok(proof)
+        };
+        let proof = if self.lookup_flights.is_enabled() {
+            self.lookup_flights
+                .run(cache_key.clone(), || generate)
+                .await?
+        } else {
+            generate.await?
+        };
+        self.cache_proof(cache_key, &proof);
+        Ok((proof, root_hash))
+    }
+
+    /// Enables or disables the coalescing of concurrent lookups of the same label at the same
+    /// epoch, so that the proof is generated by one of them and shared with the others.
+    /// Coalescing is disabled by default. See [crate::coalescing].
+    pub fn set_lookup_coalescing(&self, enabled: bool) {
+        self.lookup_flights.set_enabled(enabled);
+    }
+
+    /// The counts of the lookups which were coalesced (see [Directory::set_lookup_coalescing])
+    pub fn lookup_coalescing_metrics(&self) -> LookupCoalescingMetrics {
+        self.lookup_flights.metrics()
+    }
+
+    /// Sets the maximum number of lookup proofs which are cached, keyed by the epoch and root
+    /// hash at which they were generated, so that repeated lookups of a label within an epoch
+    /// are answered without traversing the tree. A capacity of zero (the default) disables the
+    /// cache and discards any cached proofs. See [crate::proof_cache].
+    pub fn set_proof_cache_capacity(&self, capacity: usize) {
+        if let Ok(mut proof_cache) = self.proof_cache.write() {
+            proof_cache.set_capacity(capacity);
+        }
+    }
+
+    /// The number of lookup proofs which are currently cached (see
+    /// [Directory::set_proof_cache_capacity])
+    pub fn cached_proofs(&self) -> usize {
+        self.proof_cache
+            .read()
+            .map(|proof_cache| proof_cache.len())
+            .unwrap_or_default()
+    }
+
+    /// The cached lookup proof for a key, if the proof cache is enabled and holds one
+    fn cached_proof(&self, key: &ProofCacheKey) -> Option<LookupProof> {
+        let proof_cache = self.proof_cache.read().ok()?;
+        if proof_cache.capacity() == 0 {
+            return None;
+        }
+        let proof = proof_cache.get(key);
+        instrumentation::record_proof_cache_access(proof.is_some());
+        proof
+    }
+
+    /// Stores a generated lookup proof in the proof cache, if it is enabled
+    fn cache_proof(&self, key: ProofCacheKey, proof: &LookupProof) {
+        if let Ok(mut proof_cache) = self.proof_cache.write() {
+            if proof_cache.capacity() > 0 {
+                let num_discarded = proof_cache.insert(key, proof.clone());
+                if num_discarded > 0 {
+                    instrumentation::record_proof_cache_invalidation(num_discarded);
+                }
+            }
+        }
+    }
+
+    /// Enables prefetching with the given config, or disables it with [None], which is the
+    /// default. While enabled, the labels which are looked up are recorded, and
+    /// [Directory::prefetch] loads the nodes of those which are likely to be looked up next
+    /// into the cache. Changing the config discards the recorded lookups. See
+    /// [crate::prefetch].
+    pub fn set_prefetching(&self, config: Option<PrefetchConfig>) {
+        *self.lock_prefetcher() = config.map(AccessTracker::new);
+    }
+
+    /// Loads the nodes which the lookups of the labels that are likely to be looked up next
+    /// need into the cache, as predicted from the lookups recorded since prefetching was
+    /// enabled (see [Directory::set_prefetching]). This is meant to be run between requests,
+    /// e.g. as the [MaintenanceTask::Prefetch] task. Returns the number of labels whose nodes
+    /// were loaded, which is zero if prefetching is disabled or the storage has no cache.
+    pub async fn prefetch(&self) -> Result<usize, AkdError> {
+        if !self.storage.has_cache() {
+            return Ok(0);
+        }
+        let candidates = match self.lock_prefetcher().as_mut() {
+            Some(tracker) => tracker.take_candidates(),
+            None => return Ok(0),
+        };
+        if candidates.is_empty() {
+            return Ok(0);
+        }
+
+        let _guard = self.cache_lock.read().await;
+        let current_azks = self.retrieve_azks().await?;
+        let current_epoch = current_azks.get_latest_epoch();
+        let mut lookup_infos = Vec::with_capacity(candidates.len());
+        for akd_label in candidates {
+            match self.get_lookup_info(akd_label, current_epoch).await {
+                Ok(lookup_info) => lookup_infos.push(lookup_info),
+                Err(AkdError::Storage(StorageError::NotFound(_))) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        current_azks
+            .preload_lookup_nodes(&self.storage, &lookup_infos)
+            .await?;
+        instrumentation::record_prefetch(lookup_infos.len());
+        Ok(lookup_infos.len())
+    }
+
+    /// Records lookups of the given (normalized) labels, if prefetching is enabled
+    fn record_lookups(&self, akd_labels: &[AkdLabel]) {
+        if let Some(tracker) = self.lock_prefetcher().as_mut() {
+            tracker.record(akd_labels);
+        }
+    }
+
+    fn lock_prefetcher(&self) -> std::sync::MutexGuard<'_, Option<AccessTracker>> {
+        // The tracker is never left in an inconsistent state, so a poisoned lock is recovered
+        self.prefetcher
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Designates the labels whose lookup proofs are precomputed whenever the directory moves
+    /// to a new epoch, replacing the previous hot labels, and precomputes their proofs at the
+    /// current epoch. Returns the number of hot labels with a precomputed proof, which excludes
+    /// those which have not been published yet. See [crate::hot_labels].
+    pub async fn set_hot_labels(&self, labels: Vec<AkdLabel>) -> Result<usize, AkdError>
+    where
+        V: 'static,
+    {
+        let labels = labels.into_iter().map(normalize_label::<TC>).collect();
+        if let Ok(mut hot_labels) = self.hot_labels.write() {
+            hot_labels.set_labels(labels);
+        }
+        self.start_hot_label_worker();
+        self.precompute_hot_lookups().await
+    }
+
+    /// Starts the background task which precomputes the lookup proofs of the hot labels
+    /// whenever this instance or one of its clones publishes an epoch, unless it is running.
+    /// Epochs which are published while the proofs of an earlier epoch are being precomputed
+    /// are coalesced, and the proofs are precomputed at the latest of them.
+    fn start_hot_label_worker(&self)
+    where
+        V: 'static,
+    {
+        // The sender is never left in an inconsistent state, so a poisoned lock is recovered
+        let mut worker = self
+            .hot_label_worker
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if worker.is_some() {
+            return;
+        }
+        let (sender, mut receiver) = tokio::sync::watch::channel(());
+        // The task does not hold the sender, so that it stops once the directory and all of
+        // its clones have been dropped
+        let directory = Directory {
+            hot_label_worker: Arc::new(std::sync::Mutex::new(None)),
+            ..self.clone()
+        };
+        tokio::task::spawn(instrumentation::in_current_span(async move {
+            while receiver.changed().await.is_ok() {
+                directory.precompute_hot_lookups_logged().await;
+            }
+        }));
+        *worker = Some(sender);
+    }
+
+    /// Notifies the background task which precomputes the lookup proofs of the hot labels of
+    /// a new epoch, if there are hot labels
+    fn notify_hot_label_worker(&self) {
+        let worker = self
+            .hot_label_worker
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(sender) = worker.as_ref() {
+            sender.send_replace(());
+        }
+    }
+
+    /// The hot labels whose lookup proofs are precomputed (see [Directory::set_hot_labels])
+    pub fn hot_labels(&self) -> Vec<AkdLabel> {
+        self.hot_labels
+            .read()
+            .map(|hot_labels| hot_labels.labels())
+            .unwrap_or_default()
+    }
+
+    /// Precomputes the lookup proofs of the hot labels at the current epoch, and returns the
+    /// number of proofs which were precomputed
+    async fn precompute_hot_lookups(&self) -> Result<usize, AkdError> {
+        let labels = self.hot_labels();
+        if labels.is_empty() {
+            return Ok(0);
+        }
+        // The proofs are stored before the guard is released, so that they cannot replace
+        // those of a later epoch
+        let _guard = self.cache_lock.read().await;
+        let current_azks = self.retrieve_azks().await?;
+        let current_epoch = current_azks.get_latest_epoch();
+        let root_hash = EpochHash(
+            current_epoch,
+            current_azks.get_root_hash::<TC, _>(&self.storage).await?,
+        );
+        let mut proofs = HashMap::with_capacity(labels.len());
+        for akd_label in labels {
+            let lookup_info = match self.get_lookup_info(akd_label.clone(), current_epoch).await {
+                Ok(lookup_info) => lookup_info,
+                Err(AkdError::Storage(StorageError::NotFound(_))) => continue,
+                Err(err) => return Err(err),
+            };
+            let mut timer = PhaseTimer::new(Operation::Lookup);
+            let proof = self
+                .lookup_with_info(&current_azks, lookup_info, false, &mut timer)
+                .await?;
+            proofs.insert(akd_label, proof);
+        }
+        let num_proofs = proofs.len();
+        if let Ok(mut hot_labels) = self.hot_labels.write() {
+            hot_labels.store(root_hash, proofs);
+        }
+        Ok(num_proofs)
+    }
+
+    /// Discards the cached lookup proofs of earlier epochs, and precomputes the lookup proofs
+    /// of the hot labels, after the directory has refreshed to a new epoch. A failure is only
+    /// logged, since the cached proofs of earlier epochs are never served, and lookups of the
+    /// hot labels then fall back to generating their proofs.
+    async fn on_epoch_changed(&self) {
+        match self.get_epoch_hash().await {
+            Ok(epoch_hash) => self.advance_proof_cache(epoch_hash),
+            Err(err) => warn!(error = %err, "Failed to invalidate the proof cache"),
+        }
+        self.precompute_hot_lookups_logged().await;
+    }
+
+    /// Discards the cached lookup proofs of epochs other than the given one
+    fn advance_proof_cache(&self, epoch_hash: EpochHash) {
+        if let Ok(mut proof_cache) = self.proof_cache.write() {
+            let num_proofs = proof_cache.advance(epoch_hash);
+            instrumentation::record_proof_cache_invalidation(num_proofs);
+        }
+    }
+
+    async fn precompute_hot_lookups_logged(&self) {
+        if let Err(err) = self.precompute_hot_lookups().await {
+            warn!(error = %err, "Failed to precompute the lookup proofs of the hot labels");
+        }
+    }
+
+    /// Waits until the lookup proofs of the hot labels have been precomputed at the current
+    /// epoch, in the background after a publish
+    #[cfg(test)]
+    pub(crate) async fn wait_for_hot_lookups(&self) -> Result<(), AkdError> {
+        let epoch_hash = self.get_epoch_hash().await?;
+        while !self
+            .hot_labels
+            .read()
+            .map(|hot_labels| hot_labels.is_precomputed_at(&epoch_hash))
+            .unwrap_or(false)
+        {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        Ok(())
+    }
+
+    /// Provides proof for correctness of the latest version of the target label, together
+    /// with the proofs that no newer version exists as of the current epoch. This gives the
+    /// client the same guarantee about the latest version as a [Directory::key_history]
+    /// query, without the proofs for all of the label's previous versions. The proof can
+    /// be verified with [crate::client::lookup_latest_verify].
+    ///
+    /// * `akd_label`: The target label to generate a proof for
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn lookup_latest(
+        &self,
+        akd_label: AkdLabel,
+    ) -> Result<(LatestVersionProof, EpochHash), AkdError> {
+        // The guard will be dropped at the end of the proof generation
+        let _guard = self.cache_lock.read().await;
+        let start = Instant::now();
+        let akd_label = normalize_label::<TC>(akd_label);
 
+        let mut timer = PhaseTimer::new(Operation::Lookup);
+        timer.begin(Phase::Preload);
         let current_azks = self.retrieve_azks().await?;
         let current_epoch = current_azks.get_latest_epoch();
-        let lookup_info = self.get_lookup_info(akd_label, current_epoch).await?;
+        let lookup_info = self
+            .get_lookup_info(akd_label.clone(), current_epoch)
+            .await?;
 
         let root_hash = EpochHash(
             current_epoch,
             current_azks.get_root_hash::<TC, _>(&self.storage).await?,
         );
-        let proof = self
-            .lookup_with_info(&current_azks, lookup_info, false)
+        let lookup_proof = self
+            .lookup_with_info(&current_azks, lookup_info, false, &mut timer)
+            .await?;
+        let (
+            until_marker_vrf_proofs,
+            non_existence_until_marker_proofs,
+            future_marker_vrf_proofs,
+            non_existence_of_future_marker_proofs,
+        ) = self
+            .get_future_version_proofs(
+                &current_azks,
+                &akd_label,
+                lookup_proof.version,
+                current_epoch,
+            )
+            .await?;
+        self.record_timing(timer);
+        let proof = LatestVersionProof {
+            lookup_proof,
+            until_marker_vrf_proofs,
+            non_existence_until_marker_proofs,
+            future_marker_vrf_proofs,
+            non_existence_of_future_marker_proofs,
+        };
+        instrumentation::record_proof("lookup_latest", start, &proof);
+        Ok((proof, root_hash))
+    }
+
+    /// Provides proof that a label has never been registered in the directory, as of the
+    /// current epoch. The proof can be verified with [crate::client::nonexistence_verify].
+    ///
+    /// * `akd_label`: The target label to generate a non-existence proof for
+    ///
+    /// Returns an error if the label has been registered as of the current epoch, or if no
+    /// epoch has been published yet.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn lookup_absent(
+        &self,
+        akd_label: AkdLabel,
+    ) -> Result<(NonExistenceProof, EpochHash), AkdError> {
+        // The guard will be dropped at the end of the proof generation
+        let _guard = self.cache_lock.read().await;
+        let start = Instant::now();
+        let akd_label = normalize_label::<TC>(akd_label);
+
+        let current_azks = self.retrieve_azks().await?;
+        let current_epoch = current_azks.get_latest_epoch();
+        if current_epoch == 0 {
+            // Non-membership cannot be proven against the empty tree
+            return Err(AkdError::Directory(DirectoryError::InvalidEpoch(
+                "0: no epoch has been published yet".to_string(),
+            )));
+        }
+        if self
+            .storage
+            .get_user_state(&akd_label, ValueStateRetrievalFlag::LeqEpoch(current_epoch))
+            .await
+            .is_ok()
+        {
+            let label = match std::str::from_utf8(&akd_label) {
+                Ok(name) => name.to_string(),
+                _ => format!("{akd_label:?}"),
+            };
+            return Err(AkdError::Directory(DirectoryError::LabelRegistered(
+                format!("{label} at epoch {current_epoch}"),
+            )));
+        }
+
+        let node_label = self
+            .vrf
+            .get_node_label::<TC>(&akd_label, VersionFreshness::Fresh, 1)
             .await?;
+        let non_membership_proof = current_azks
+            .get_non_membership_proof::<TC, _>(&self.storage, node_label)
+            .await?;
+        let vrf_proof = self
+            .vrf
+            .get_label_proof::<TC>(&akd_label, VersionFreshness::Fresh, 1)
+            .await?
+            .to_bytes()
+            .to_vec();
+
+        let root_hash = EpochHash(
+            current_epoch,
+            current_azks.get_root_hash::<TC, _>(&self.storage).await?,
+        );
+        let proof = NonExistenceProof {
+            vrf_proof,
+            non_membership_proof,
+        };
+        instrumentation::record_proof("lookup_absent", start, &proof);
         Ok((proof, root_hash))
     }
 
+    /// Provides a lookup proof for the latest version of the target label, bundled together
+    /// with the epoch and root hash it verifies against, the signer's signature over that
+    /// root hash, and the fingerprint of this directory's configuration. If the signer is a
+    /// delegate of the root key (see [akd_core::signing::DelegatedSigner]), its delegation
+    /// certificate is included. The response can be verified with
+    /// [crate::client::signed_lookup_verify].
+    ///
+    /// * `akd_label`: The target label to generate a lookup proof for
+    /// * `signer`: The signer for the directory's epoch root hashes
+    pub async fn signed_lookup(
+        &self,
+        akd_label: AkdLabel,
+        signer: &impl EpochSigner,
+    ) -> Result<SignedLookupResponse, AkdError> {
+        let (proof, EpochHash(epoch, root_hash)) = self.lookup(akd_label).await?;
+        let epoch_signature =
+            signer.sign_epoch_message(&epoch_signature_message::<TC>(epoch, &root_hash));
+        Ok(SignedLookupResponse {
+            proof,
+            epoch,
+            root_hash,
+            epoch_signature,
+            configuration_fingerprint: configuration_fingerprint::<TC>(),
+            delegation: signer.delegation_certificate(),
+        })
+    }
+
     /// Generate a lookup proof with the provided target information
     ///
     /// * `current_azks`: The current [Azks] element
     /// * `lookup_info`: The information to target in the lookup request. Includes all
-    /// necessary information to build the proof
+    ///   necessary information to build the proof
     /// * `skip_preload`: Denotes if we should not preload as part of this optimization. Enabled
-    /// from bulk lookup proof generation, as it has its own preloading operation
+    ///   from bulk lookup proof generation, as it has its own preloading operation
+    /// * `timer`: Records the preload and proof assembly phases of the lookup
     ///
     /// Returns [Ok(LookupProof)] if the proof generation succeeded, [Err(_)] otherwise
     async fn lookup_with_info(
@@ -284,8 +1456,10 @@ where
         current_azks: &Azks,
         lookup_info: LookupInfo,
         skip_preload: bool,
+        timer: &mut PhaseTimer,
     ) -> Result<LookupProof, AkdError> {
         if !skip_preload {
+            timer.begin(Phase::Preload);
             // Preload nodes needed for lookup.
             #[cfg(feature = "greedy_lookup_preload")]
             {
@@ -300,6 +1474,7 @@ where
                     .await?;
             }
         }
+        timer.begin(Phase::ProofAssembly);
         let label = &lookup_info.value_state.username;
         let current_version = lookup_info.value_state.version;
         let commitment_key = self.derive_commitment_key().await?;
@@ -308,7 +1483,10 @@ where
             .vrf
             .get_label_proof::<TC>(label, VersionFreshness::Fresh, current_version)
             .await?;
-        let commitment_label = self.vrf.get_node_label_from_vrf_proof(existence_vrf).await;
+        let commitment_label = self
+            .vrf
+            .get_node_label_from_vrf_proof::<TC>(existence_vrf)
+            .await;
         let lookup_proof = LookupProof {
             epoch: lookup_info.value_state.epoch,
             value: plaintext_value.clone(),
@@ -349,19 +1527,28 @@ where
 
     // TODO(eoz): Call proof generations async
     /// Allows efficient batch lookups by preloading necessary nodes for the lookups.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(num_labels = akd_labels.len())))]
     pub async fn batch_lookup(
         &self,
         akd_labels: &[AkdLabel],
     ) -> Result<(Vec<LookupProof>, EpochHash), AkdError> {
         // The guard will be dropped at the end of the proof generation
         let _guard = self.cache_lock.read().await;
+        let start = Instant::now();
 
+        let mut timer = PhaseTimer::new(Operation::BatchLookup);
+        timer.begin(Phase::Preload);
         let current_azks = self.retrieve_azks().await?;
         let current_epoch = current_azks.get_latest_epoch();
+        let akd_labels = akd_labels
+            .iter()
+            .map(|akd_label| normalize_label::<TC>(akd_label.clone()))
+            .collect::<Vec<_>>();
+        self.record_lookups(&akd_labels);
 
         // Take a union of the labels we will need proofs of for each lookup.
         let mut lookup_infos = Vec::new();
-        for akd_label in akd_labels {
+        for akd_label in akd_labels.iter() {
             // Save lookup info for later use.
             let lookup_info = self
                 .get_lookup_info(akd_label.clone(), current_epoch)
@@ -384,8 +1571,13 @@ where
 
         let mut lookup_proofs = Vec::new();
         for info in lookup_infos.into_iter() {
-            lookup_proofs.push(self.lookup_with_info(&current_azks, info, true).await?);
+            lookup_proofs.push(
+                self.lookup_with_info(&current_azks, info, true, &mut timer)
+                    .await?,
+            );
         }
+        self.record_timing(timer);
+        instrumentation::record_batch_proofs("batch_lookup", start, &lookup_proofs);
 
         Ok((lookup_proofs, root_hash))
     }
@@ -395,7 +1587,7 @@ where
         // Need to account for the case where the latest state is
         // added but the database is in the middle of an update
         let version = latest_st.version;
-        let marker_version = 1 << get_marker_version(version);
+        let marker_version = TC::marker_version(version);
         let existent_label = self
             .vrf
             .get_node_label::<TC>(akd_label, VersionFreshness::Fresh, version)
@@ -447,20 +1639,35 @@ where
     /// this function returns all the values ever associated with it,
     /// and the epoch at which each value was first committed to the server state.
     /// It also returns the proof of the latest version being served at all times.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     pub async fn key_history(
         &self,
         akd_label: &AkdLabel,
         params: HistoryParams,
+    ) -> Result<(HistoryProof, EpochHash), AkdError> {
+        let start = Instant::now();
+        let (proof, root_hash) = self.generate_history_proof(akd_label, params).await?;
+        instrumentation::record_history_proof("key_history", params, start, &proof);
+        Ok((proof, root_hash))
+    }
+
+    /// Generates the history proof for [Directory::key_history], which is also the basis of
+    /// the linked and selective history proofs
+    async fn generate_history_proof(
+        &self,
+        akd_label: &AkdLabel,
+        params: HistoryParams,
     ) -> Result<(HistoryProof, EpochHash), AkdError> {
         // The guard will be dropped at the end of the proof generation
         let _guard = self.cache_lock.read().await;
+        let akd_label = &*TC::normalize_label(akd_label);
 
         let current_azks = self.retrieve_azks().await?;
         let current_epoch = current_azks.get_latest_epoch();
         let mut user_data = self.storage.get_user_data(akd_label).await?.states;
 
         // reverse sort from highest epoch to lowest
-        user_data.sort_by(|a, b| b.epoch.cmp(&a.epoch));
+        user_data.sort_by_key(|v| std::cmp::Reverse(v.epoch));
 
         // apply filters specified by HistoryParams struct
         user_data = match params {
@@ -474,7 +1681,16 @@ where
                     .filter(|val| val.epoch >= epoch)
                     .collect::<Vec<_>>();
                 // Ordering should be maintained after filtering, but let's re-sort just in case
-                user_data.sort_by(|a, b| b.epoch.cmp(&a.epoch));
+                user_data.sort_by_key(|v| std::cmp::Reverse(v.epoch));
+                user_data
+            }
+            HistoryParams::RecentEpochs(num_epochs) => {
+                // Keep every update within the window, along with the most recent update
+                // at or before the start of the window which proves that the window is complete
+                let window_start = current_epoch.saturating_sub(num_epochs);
+                if let Some(boundary) = user_data.iter().position(|val| val.epoch <= window_start) {
+                    user_data.truncate(boundary + 1);
+                }
                 user_data
             }
         };
@@ -517,104 +1733,498 @@ where
                 };
             }
         }
-        let next_marker = get_marker_version(last_version) + 1;
-        let final_marker = get_marker_version(current_epoch);
-
-        let mut until_marker_vrf_proofs = Vec::<Vec<u8>>::new();
-        let mut non_existence_until_marker_proofs = Vec::<NonMembershipProof>::new();
-
-        for ver in last_version + 1..(1 << next_marker) {
-            let label_for_ver = self
-                .vrf
-                .get_node_label::<TC>(akd_label, VersionFreshness::Fresh, ver)
-                .await?;
-            let non_existence_of_ver = current_azks
-                .get_non_membership_proof::<TC, _>(&self.storage, label_for_ver)
-                .await?;
-            non_existence_until_marker_proofs.push(non_existence_of_ver);
-            until_marker_vrf_proofs.push(
-                self.vrf
-                    .get_label_proof::<TC>(akd_label, VersionFreshness::Fresh, ver)
-                    .await?
-                    .to_bytes()
-                    .to_vec(),
-            );
-        }
-
-        let mut future_marker_vrf_proofs = Vec::<Vec<u8>>::new();
-        let mut non_existence_of_future_marker_proofs = Vec::<NonMembershipProof>::new();
-
-        for marker_power in next_marker..final_marker + 1 {
-            let ver = 1 << marker_power;
-            let label_for_ver = self
-                .vrf
-                .get_node_label::<TC>(akd_label, VersionFreshness::Fresh, ver)
-                .await?;
-            let non_existence_of_ver = current_azks
-                .get_non_membership_proof::<TC, _>(&self.storage, label_for_ver)
-                .await?;
-            non_existence_of_future_marker_proofs.push(non_existence_of_ver);
-            future_marker_vrf_proofs.push(
-                self.vrf
-                    .get_label_proof::<TC>(akd_label, VersionFreshness::Fresh, ver)
-                    .await?
-                    .to_bytes()
-                    .to_vec(),
-            );
-        }
+        let (
+            until_marker_vrf_proofs,
+            non_existence_until_marker_proofs,
+            future_marker_vrf_proofs,
+            non_existence_of_future_marker_proofs,
+        ) = self
+            .get_future_version_proofs(&current_azks, akd_label, last_version, current_epoch)
+            .await?;
 
         let root_hash = EpochHash(
             current_epoch,
             current_azks.get_root_hash::<TC, _>(&self.storage).await?,
         );
 
-        Ok((
-            HistoryProof {
-                update_proofs,
-                until_marker_vrf_proofs,
-                non_existence_until_marker_proofs,
-                future_marker_vrf_proofs,
-                non_existence_of_future_marker_proofs,
-            },
-            root_hash,
-        ))
+        let proof = HistoryProof {
+            update_proofs,
+            until_marker_vrf_proofs,
+            non_existence_until_marker_proofs,
+            future_marker_vrf_proofs,
+            non_existence_of_future_marker_proofs,
+        };
+        Ok((proof, root_hash))
     }
 
-    /// Poll for changes in the epoch number of the AZKS struct
-    /// stored in the storage layer. If an epoch change is detected,
-    /// the object cache (if present) is flushed immediately so
-    /// that new objects are retrieved from the storage layer against
-    /// the "latest" epoch. There is a "special" flow in the storage layer
-    /// to do a storage-layer retrieval which ignores the cache
-    pub async fn poll_for_azks_changes(
+    /// Generates the histories of a label and of each label its history has been moved to
+    /// with [Directory::rename_label], following the links from `akd_label` until a label
+    /// which has not been renamed is reached. The proof is verified with
+    /// [crate::client::linked_key_history_verify].
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn linked_key_history(
         &self,
-        period: tokio::time::Duration,
-        change_detected: Option<tokio::sync::mpsc::Sender<()>>,
-    ) -> Result<(), AkdError> {
-        // Retrieve the same AZKS that all the other calls see (i.e. the version that could be cached
-        // at this point). We'll compare this via an uncached call when a change is notified
-        let mut last = Directory::<TC, S, V>::get_azks_from_storage(&self.storage, false).await?;
-
+        akd_label: &AkdLabel,
+        params: HistoryParams,
+    ) -> Result<(LinkedHistoryProof, EpochHash), AkdError> {
+        let start = Instant::now();
+        // The histories are generated separately, so a publish which completes in between
+        // them requires the histories to be generated again at the new epoch
         loop {
-            // loop forever polling for changes
-            tokio::time::sleep(period).await;
-
-            let latest = Directory::<TC, S, V>::get_azks_from_storage(&self.storage, true).await?;
-            if latest.latest_epoch > last.latest_epoch {
+            let mut segments = vec![];
+            let mut epoch_hashes = HashSet::new();
+            let mut label = normalize_label::<TC>(akd_label.clone());
+            loop {
+                // A label can only be renamed to a new label, so the links only form a cycle
+                // if link records were published directly
+                if segments
+                    .iter()
+                    .any(|segment: &LinkedHistorySegment| segment.label == label)
                 {
-                    // acquire a singleton lock prior to flushing the cache to assert that no
-                    // cache accesses are underway (i.e. publish/proof generations/etc)
-                    let _guard = self.cache_lock.write().await;
-                    // flush the cache in its entirety
-                    self.storage.flush_cache().await;
-                    // re-fetch the azks to load it into cache so when we release the cache lock
-                    // others will see the new AZKS loaded up and ready
-                    last =
-                        Directory::<TC, S, V>::get_azks_from_storage(&self.storage, false).await?;
+                    return Err(AkdError::Directory(DirectoryError::Publish(format!(
+                        "The links from label {} form a cycle at label {}",
+                        instrumentation::label_hash_prefix::<TC>(akd_label),
+                        instrumentation::label_hash_prefix::<TC>(&label)
+                    ))));
+                }
+                let (proof, epoch_hash) = self.generate_history_proof(&label, params).await?;
+                epoch_hashes.insert(epoch_hash);
+                let next_label = proof
+                    .update_proofs
+                    .first()
+                    .and_then(|update_proof| update_proof.value.link_target());
+                segments.push(LinkedHistorySegment { label, proof });
+                match next_label {
+                    Some(next_label) => label = next_label,
+                    None => break,
+                }
+            }
+            if epoch_hashes.len() == 1 {
+                let epoch_hash = epoch_hashes.into_iter().next().expect("one epoch hash");
+                let proof = LinkedHistoryProof { segments };
+                instrumentation::record_history_proof("linked_key_history", params, start, &proof);
+                return Ok((proof, epoch_hash));
+            }
+        }
+    }
 
-                    // notify change occurred
-                    if let Some(channel) = &change_detected {
-                        channel.send(()).await.map_err(|send_err| {
+    /// Generates everything a client which last synced at `since_epoch` needs to catch up on
+    /// the given labels (see [crate::delta_sync]): the append-only proof from `since_epoch` to
+    /// the current epoch, along with the updates of each label since `since_epoch`, or the
+    /// proof that it is absent. The response is verified with
+    /// [crate::delta_sync::delta_sync_verify].
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(since_epoch = since_epoch)))]
+    pub async fn delta_sync(
+        &self,
+        since_epoch: u64,
+        akd_labels: &[AkdLabel],
+    ) -> Result<DeltaSyncResponse, AkdError> {
+        let start = Instant::now();
+        // The proofs are generated separately, so a publish which completes in between them
+        // requires the proofs to be generated again at the new epoch
+        'sync: loop {
+            let EpochHash(epoch, root_hash) = self.get_epoch_hash().await?;
+            if since_epoch > epoch {
+                return Err(AkdError::Directory(DirectoryError::InvalidEpoch(format!(
+                    "Sync epoch {since_epoch} is greater than the current epoch {epoch}"
+                ))));
+            }
+            let mut deltas = Vec::with_capacity(akd_labels.len());
+            for akd_label in akd_labels {
+                let (delta, epoch_hash) = match self
+                    .generate_history_proof(
+                        akd_label,
+                        HistoryParams::RecentEpochs(epoch - since_epoch),
+                    )
+                    .await
+                {
+                    Ok((proof, epoch_hash)) => (LabelDelta::History(proof), epoch_hash),
+                    Err(AkdError::Storage(StorageError::NotFound(_))) => {
+                        let (proof, epoch_hash) = self.lookup_absent(akd_label.clone()).await?;
+                        (LabelDelta::Absent(proof), epoch_hash)
+                    }
+                    Err(err) => return Err(err),
+                };
+                if epoch_hash.0 != epoch {
+                    continue 'sync;
+                }
+                deltas.push((akd_label.clone(), delta));
+            }
+            let consistency_proof = if since_epoch < epoch {
+                Some(self.audit_multi_epoch(since_epoch, epoch).await?)
+            } else {
+                None
+            };
+            let response = DeltaSyncResponse {
+                since_epoch,
+                epoch,
+                root_hash,
+                consistency_proof,
+                deltas,
+            };
+            instrumentation::record_proof("delta_sync", start, &response);
+            return Ok(response);
+        }
+    }
+
+    /// Publishes the aggregate of the versions of a label up to `up_to_version` (see
+    /// [crate::aggregated_history]), so that [Directory::aggregated_key_history] summarizes them
+    /// with a single proof. The aggregate extends any earlier aggregate of the label, and must
+    /// leave the latest version of the label out. Tombstoned versions can only be aggregated if
+    /// their tombstones record their commitments (see [crate::removed_value_commitment]).
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn aggregate_history(
+        &self,
+        akd_label: AkdLabel,
+        up_to_version: u64,
+    ) -> Result<EpochHash, AkdError> {
+        let akd_label = normalize_label::<TC>(akd_label);
+        let aggregate_label = history_aggregate_label(&akd_label);
+        let mut aggregate = match self
+            .storage
+            .get_user_state_versions(
+                std::slice::from_ref(&aggregate_label),
+                ValueStateRetrievalFlag::MaxEpoch,
+            )
+            .await?
+            .remove(&aggregate_label)
+        {
+            None => HistoryAggregate::default(),
+            Some((_, value)) => HistoryAggregate::from_value(&value).ok_or_else(|| {
+                AkdError::Directory(DirectoryError::Publish(format!(
+                    "The value of label {} is not a history aggregate",
+                    instrumentation::label_hash_prefix::<TC>(&aggregate_label)
+                )))
+            })?,
+        };
+
+        let mut states = self.storage.get_user_data(&akd_label).await?.states;
+        let latest_version = states
+            .iter()
+            .map(|state| state.version)
+            .max()
+            .unwrap_or_default();
+        if up_to_version <= aggregate.last_version || up_to_version >= latest_version {
+            return Err(AkdError::Directory(DirectoryError::Publish(format!(
+                "Cannot aggregate the versions of label {} up to version {up_to_version}, as \
+                versions up to {} are already aggregated, and the latest version {latest_version} \
+                cannot be",
+                instrumentation::label_hash_prefix::<TC>(&akd_label),
+                aggregate.last_version
+            ))));
+        }
+        states.retain(|state| {
+            state.version > aggregate.last_version && state.version <= up_to_version
+        });
+        states.sort_by_key(|state| state.version);
+
+        let commitment_key = self.derive_commitment_key().await?;
+        for state in states {
+            let commitment = if TC::is_tombstone(&state.value) {
+                removed_value_commitment::<TC>(&state.value).ok_or_else(|| {
+                    AkdError::Directory(DirectoryError::Publish(format!(
+                        "Version {} of label {} has been tombstoned without recording its \
+                        commitment, so it cannot be aggregated",
+                        state.version,
+                        instrumentation::label_hash_prefix::<TC>(&akd_label)
+                    )))
+                })?
+            } else {
+                let label = self
+                    .vrf
+                    .get_node_label::<TC>(&akd_label, VersionFreshness::Fresh, state.version)
+                    .await?;
+                TC::compute_fresh_azks_value(&commitment_key, &label, state.version, &state.value)
+            };
+            aggregate = aggregate.append::<TC>(state.version, state.epoch, &commitment)?;
+        }
+
+        info!(
+            label = %instrumentation::label_hash_prefix::<TC>(&akd_label),
+            last_version = aggregate.last_version,
+            "Aggregating history"
+        );
+        self.publish(vec![(aggregate_label, aggregate.to_value())])
+            .await
+    }
+
+    /// Generates the history of a label as in [Directory::key_history], where the versions
+    /// aggregated with [Directory::aggregate_history] are summarized by a lookup proof of their
+    /// aggregate, so that the proof stays bounded for labels with very many versions. The proof
+    /// is verified with [crate::client::aggregated_key_history_verify].
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn aggregated_key_history(
+        &self,
+        akd_label: &AkdLabel,
+    ) -> Result<(AggregatedHistoryProof, EpochHash), AkdError> {
+        let start = Instant::now();
+        let akd_label = normalize_label::<TC>(akd_label.clone());
+        let aggregate_label = history_aggregate_label(&akd_label);
+        // The aggregate and the history are generated separately, so a publish which completes
+        // in between them requires both to be generated again at the new epoch
+        loop {
+            let (aggregate, params) = match self.lookup(aggregate_label.clone()).await {
+                Ok((proof, epoch_hash)) => {
+                    let aggregate =
+                        HistoryAggregate::from_value(&proof.value).ok_or_else(|| {
+                            AkdError::Directory(DirectoryError::Publish(format!(
+                                "The value of label {} is not a history aggregate",
+                                instrumentation::label_hash_prefix::<TC>(&aggregate_label)
+                            )))
+                        })?;
+                    (
+                        Some((proof, epoch_hash)),
+                        HistoryParams::SinceEpochInsecure(aggregate.last_epoch + 1),
+                    )
+                }
+                Err(AkdError::Storage(StorageError::NotFound(_))) => {
+                    (None, HistoryParams::Complete)
+                }
+                Err(err) => return Err(err),
+            };
+            let (recent, epoch_hash) = self.generate_history_proof(&akd_label, params).await?;
+            if let Some((_, aggregate_epoch_hash)) = &aggregate {
+                if *aggregate_epoch_hash != epoch_hash {
+                    continue;
+                }
+            }
+            let proof = AggregatedHistoryProof {
+                aggregate: aggregate.map(|(proof, _)| proof),
+                recent,
+            };
+            instrumentation::record_history_proof("aggregated_key_history", params, start, &proof);
+            return Ok((proof, epoch_hash));
+        }
+    }
+
+    /// Generates a [SelectiveHistoryProof] for a label, which is a history proof as in
+    /// [Directory::key_history] that only reveals the values of the versions published
+    /// between `disclosed_start_epoch` and `disclosed_end_epoch` (inclusive). All other
+    /// versions are proven with the commitments to their values, which hide the values.
+    ///
+    /// Tombstoned versions can only be included outside of the disclosed epochs if their
+    /// tombstones record their commitments (see [crate::removed_value_commitment]), or if the
+    /// history parameters exclude them.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn selective_key_history(
+        &self,
+        akd_label: &AkdLabel,
+        params: HistoryParams,
+        disclosed_start_epoch: u64,
+        disclosed_end_epoch: u64,
+    ) -> Result<(SelectiveHistoryProof, EpochHash), AkdError> {
+        if disclosed_start_epoch > disclosed_end_epoch {
+            return Err(AkdError::Directory(DirectoryError::InvalidEpoch(format!(
+                "Disclosed start epoch {disclosed_start_epoch} is after the disclosed end epoch {disclosed_end_epoch}"
+            ))));
+        }
+
+        let (proof, root_hash) = self.generate_history_proof(akd_label, params).await?;
+        let commitment_key = self.derive_commitment_key().await?;
+
+        let mut update_proofs = Vec::with_capacity(proof.update_proofs.len());
+        for update_proof in proof.update_proofs {
+            let disclosure = if (disclosed_start_epoch..=disclosed_end_epoch)
+                .contains(&update_proof.epoch)
+            {
+                UpdateDisclosure::Revealed {
+                    value: update_proof.value,
+                    commitment_nonce: update_proof.commitment_nonce,
+                }
+            } else {
+                let commitment = if TC::is_tombstone(&update_proof.value) {
+                    removed_value_commitment::<TC>(&update_proof.value).ok_or_else(|| {
+                        AkdError::Directory(DirectoryError::InvalidEpoch(format!(
+                            "{}: the update has been tombstoned without recording its commitment, so it cannot be hidden",
+                            update_proof.epoch
+                        )))
+                    })?
+                } else {
+                    let label = self
+                        .vrf
+                        .get_node_label::<TC>(
+                            akd_label,
+                            VersionFreshness::Fresh,
+                            update_proof.version,
+                        )
+                        .await?;
+                    TC::compute_fresh_azks_value(
+                        &commitment_key,
+                        &label,
+                        update_proof.version,
+                        &update_proof.value,
+                    )
+                };
+                UpdateDisclosure::Hidden { commitment }
+            };
+            update_proofs.push(SelectiveUpdateProof {
+                epoch: update_proof.epoch,
+                version: update_proof.version,
+                disclosure,
+                existence_vrf_proof: update_proof.existence_vrf_proof,
+                existence_proof: update_proof.existence_proof,
+                previous_version_vrf_proof: update_proof.previous_version_vrf_proof,
+                previous_version_proof: update_proof.previous_version_proof,
+            });
+        }
+
+        Ok((
+            SelectiveHistoryProof {
+                disclosed_start_epoch,
+                disclosed_end_epoch,
+                update_proofs,
+                until_marker_vrf_proofs: proof.until_marker_vrf_proofs,
+                non_existence_until_marker_proofs: proof.non_existence_until_marker_proofs,
+                future_marker_vrf_proofs: proof.future_marker_vrf_proofs,
+                non_existence_of_future_marker_proofs: proof.non_existence_of_future_marker_proofs,
+            },
+            root_hash,
+        ))
+    }
+
+    /// Generates the proofs that no version of a label after `last_version` exists as of
+    /// `current_epoch`: the non-existence of every version up until the next marker version,
+    /// and of every marker version after that up to the current epoch
+    async fn get_future_version_proofs(
+        &self,
+        current_azks: &Azks,
+        akd_label: &AkdLabel,
+        last_version: u64,
+        current_epoch: u64,
+    ) -> Result<FutureVersionProofs, AkdError> {
+        let (until_marker_versions, future_marker_versions) =
+            akd_core::utils::future_versions::<TC>(last_version, current_epoch);
+
+        let mut until_marker_vrf_proofs = Vec::<Vec<u8>>::new();
+        let mut non_existence_until_marker_proofs = Vec::<NonMembershipProof>::new();
+
+        for ver in until_marker_versions {
+            let label_for_ver = self
+                .vrf
+                .get_node_label::<TC>(akd_label, VersionFreshness::Fresh, ver)
+                .await?;
+            let non_existence_of_ver = current_azks
+                .get_non_membership_proof::<TC, _>(&self.storage, label_for_ver)
+                .await?;
+            non_existence_until_marker_proofs.push(non_existence_of_ver);
+            until_marker_vrf_proofs.push(
+                self.vrf
+                    .get_label_proof::<TC>(akd_label, VersionFreshness::Fresh, ver)
+                    .await?
+                    .to_bytes()
+                    .to_vec(),
+            );
+        }
+
+        let mut future_marker_vrf_proofs = Vec::<Vec<u8>>::new();
+        let mut non_existence_of_future_marker_proofs = Vec::<NonMembershipProof>::new();
+
+        for ver in future_marker_versions {
+            let label_for_ver = self
+                .vrf
+                .get_node_label::<TC>(akd_label, VersionFreshness::Fresh, ver)
+                .await?;
+            let non_existence_of_ver = current_azks
+                .get_non_membership_proof::<TC, _>(&self.storage, label_for_ver)
+                .await?;
+            non_existence_of_future_marker_proofs.push(non_existence_of_ver);
+            future_marker_vrf_proofs.push(
+                self.vrf
+                    .get_label_proof::<TC>(akd_label, VersionFreshness::Fresh, ver)
+                    .await?
+                    .to_bytes()
+                    .to_vec(),
+            );
+        }
+
+        Ok((
+            until_marker_vrf_proofs,
+            non_existence_until_marker_proofs,
+            future_marker_vrf_proofs,
+            non_existence_of_future_marker_proofs,
+        ))
+    }
+
+    /// The role of the directory among the replicas which share its storage, which is the
+    /// role of its storage manager (see [StorageRole])
+    pub fn role(&self) -> StorageRole {
+        self.storage.role()
+    }
+
+    /// Brings the directory up to date with the latest epoch published to its storage, and
+    /// returns that epoch. If the storage holds a newer epoch than the cached one (e.g. a
+    /// follower whose leader has published), the object cache is flushed so that proofs are
+    /// generated against the new epoch. See [Directory::spawn_follower] to refresh in the
+    /// background.
+    pub async fn refresh(&self) -> Result<u64, AkdError> {
+        let cached = Directory::<TC, S, V>::get_azks_from_storage(&self.storage, false).await?;
+        let latest = Directory::<TC, S, V>::get_azks_from_storage(&self.storage, true).await?;
+        if latest.latest_epoch <= cached.latest_epoch {
+            return Ok(cached.latest_epoch);
+        }
+        let epoch = {
+            // no proof generations may be underway while the cache is flushed
+            let _guard = self.cache_lock.write().await;
+            self.storage.flush_cache().await;
+            // load the new AZKS into the cache before proofs are generated against it
+            Directory::<TC, S, V>::get_azks_from_storage(&self.storage, false)
+                .await?
+                .latest_epoch
+        };
+        self.on_epoch_changed().await;
+        Ok(epoch)
+    }
+
+    /// Refreshes the directory in the background with [Directory::refresh], every
+    /// `poll_interval` or as soon as the returned handle is notified of a new epoch, so that
+    /// a follower serves proofs against the epochs which its leader publishes
+    pub async fn spawn_follower(&self, poll_interval: Duration) -> Result<FollowerHandle, AkdError>
+    where
+        V: 'static,
+    {
+        let epoch = self.refresh().await?;
+        let directory = self.clone();
+        Ok(replication::spawn(epoch, poll_interval, move || {
+            let directory = directory.clone();
+            async move { directory.refresh().await }
+        }))
+    }
+
+    /// Poll for changes in the epoch number of the AZKS struct
+    /// stored in the storage layer. If an epoch change is detected,
+    /// the object cache (if present) is flushed immediately so
+    /// that new objects are retrieved from the storage layer against
+    /// the "latest" epoch. There is a "special" flow in the storage layer
+    /// to do a storage-layer retrieval which ignores the cache
+    pub async fn poll_for_azks_changes(
+        &self,
+        period: tokio::time::Duration,
+        change_detected: Option<tokio::sync::mpsc::Sender<()>>,
+    ) -> Result<(), AkdError> {
+        // Retrieve the same AZKS that all the other calls see (i.e. the version that could be cached
+        // at this point). We'll compare this via an uncached call when a change is notified
+        let mut last = Directory::<TC, S, V>::get_azks_from_storage(&self.storage, false).await?;
+
+        loop {
+            // loop forever polling for changes
+            tokio::time::sleep(period).await;
+
+            let latest = Directory::<TC, S, V>::get_azks_from_storage(&self.storage, true).await?;
+            if latest.latest_epoch > last.latest_epoch {
+                {
+                    // acquire a singleton lock prior to flushing the cache to assert that no
+                    // cache accesses are underway (i.e. publish/proof generations/etc)
+                    let _guard = self.cache_lock.write().await;
+                    // flush the cache in its entirety
+                    self.storage.flush_cache().await;
+                    // re-fetch the azks to load it into cache so when we release the cache lock
+                    // others will see the new AZKS loaded up and ready
+                    last =
+                        Directory::<TC, S, V>::get_azks_from_storage(&self.storage, false).await?;
+
+                    // notify change occurred
+                    if let Some(channel) = &change_detected {
+                        channel.send(()).await.map_err(|send_err| {
                             AkdError::Storage(StorageError::Connection(format!(
                                 "Tokio MPSC sender failed to publish notification with error {send_err:?}"
                             )))
@@ -622,6 +2232,7 @@ where
                     }
                     // drop the guard
                 }
+                self.on_epoch_changed().await;
             }
         }
 
@@ -631,6 +2242,7 @@ where
 
     /// Returns an [AppendOnlyProof] for the leaves inserted into the underlying tree between
     /// the epochs `audit_start_ep` and `audit_end_ep`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(start_epoch = audit_start_ep, end_epoch = audit_end_ep)))]
     pub async fn audit(
         &self,
         audit_start_ep: u64,
@@ -651,125 +2263,2002 @@ where
                 "End epoch {audit_end_ep} is greater than the current epoch {current_epoch}"
             ))))
         } else {
+            let start = Instant::now();
+            let mut timer = PhaseTimer::new(Operation::Audit);
+            self.storage.disable_cache_cleaning();
+            let result = current_azks
+                .get_append_only_proof_timed::<TC, _>(
+                    &self.storage,
+                    audit_start_ep,
+                    audit_end_ep,
+                    &mut timer,
+                )
+                .await;
+            self.storage.enable_cache_cleaning();
+            if let Ok(proof) = &result {
+                self.record_timing(timer);
+                instrumentation::record_proof("audit", start, proof);
+            }
+            result
+        }
+    }
+
+    /// Returns a single [MultiEpochAppendOnlyProof] for the leaves inserted into the underlying
+    /// tree between the epochs `audit_start_ep` and `audit_end_ep`, which allows an auditor to
+    /// catch up on many epochs with one verification pass (see [crate::auditor::audit_verify_multi_epoch]).
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(start_epoch = audit_start_ep, end_epoch = audit_end_ep)))]
+    pub async fn audit_multi_epoch(
+        &self,
+        audit_start_ep: u64,
+        audit_end_ep: u64,
+    ) -> Result<MultiEpochAppendOnlyProof, AkdError> {
+        // The guard will be dropped at the end of the proof generation
+        let _guard = self.cache_lock.read().await;
+
+        let current_azks = self.retrieve_azks().await?;
+        let current_epoch = current_azks.get_latest_epoch();
+
+        if audit_start_ep >= audit_end_ep {
+            Err(AkdError::Directory(DirectoryError::InvalidEpoch(format!(
+                "Start epoch {audit_start_ep} is greater than or equal the end epoch {audit_end_ep}"
+            ))))
+        } else if current_epoch < audit_end_ep {
+            Err(AkdError::Directory(DirectoryError::InvalidEpoch(format!(
+                "End epoch {audit_end_ep} is greater than the current epoch {current_epoch}"
+            ))))
+        } else {
+            let start = Instant::now();
+            let mut timer = PhaseTimer::new(Operation::Audit);
             self.storage.disable_cache_cleaning();
             let result = current_azks
-                .get_append_only_proof::<TC, _>(&self.storage, audit_start_ep, audit_end_ep)
+                .get_multi_epoch_append_only_proof_timed::<TC, _>(
+                    &self.storage,
+                    audit_start_ep,
+                    audit_end_ep,
+                    &mut timer,
+                )
                 .await;
             self.storage.enable_cache_cleaning();
+            if let Ok(proof) = &result {
+                self.record_timing(timer);
+                instrumentation::record_proof("audit_multi_epoch", start, proof);
+            }
             result
         }
-    }
+    }
+
+    /// Returns the [AppendOnlyProof] of the epochs from `audit_start_ep` to `audit_end_ep`, as with
+    /// [Directory::audit], building the proof of each epoch in chunks which are saved to the store as
+    /// they complete (see [crate::proof_chunks]). Up to [ChunkedAuditOptions::max_concurrent_chunks]
+    /// chunks are built in parallel, and a publish may commit between any two chunks. The chunks saved
+    /// by an earlier call which was interrupted are reused, and the chunks are discarded from the store
+    /// once the whole proof has been assembled.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(start_epoch = audit_start_ep, end_epoch = audit_end_ep)))]
+    pub async fn audit_chunked(
+        &self,
+        audit_start_ep: u64,
+        audit_end_ep: u64,
+        store: &dyn ProofChunkStore,
+        options: &ChunkedAuditOptions,
+    ) -> Result<AppendOnlyProof, AkdError>
+    where
+        V: 'static,
+    {
+        let current_epoch = self.retrieve_azks().await?.get_latest_epoch();
+        if audit_start_ep >= audit_end_ep {
+            return Err(AkdError::Directory(DirectoryError::InvalidEpoch(format!(
+                "Start epoch {audit_start_ep} is greater than or equal the end epoch {audit_end_ep}"
+            ))));
+        } else if current_epoch < audit_end_ep {
+            return Err(AkdError::Directory(DirectoryError::InvalidEpoch(format!(
+                "End epoch {audit_end_ep} is greater than the current epoch {current_epoch}"
+            ))));
+        }
+
+        let start = Instant::now();
+        self.storage.disable_cache_cleaning();
+        let mut proofs = Vec::new();
+        let mut result = Ok(());
+        for epoch in audit_start_ep..audit_end_ep {
+            match self.audit_epoch_chunked(epoch, store, options).await {
+                Ok(proof) => proofs.push(proof),
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+        }
+        self.storage.enable_cache_cleaning();
+        result?;
+
+        for epoch in audit_start_ep..audit_end_ep {
+            store.clear(epoch).await?;
+        }
+        let proof = AppendOnlyProof {
+            proofs,
+            epochs: (audit_start_ep..audit_end_ep).collect(),
+        };
+        instrumentation::record_proof("audit_chunked", start, &proof);
+        Ok(proof)
+    }
+
+    /// Returns the digests of every top-level subtree of the current epoch, from which a
+    /// lightweight auditor starts (see [crate::audit_lite]), along with the epoch's root hash
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn audit_lite_snapshot(
+        &self,
+        prefix_bits: u8,
+    ) -> Result<(AuditLiteSnapshot, EpochHash), AkdError> {
+        let _guard = self.cache_lock.read().await;
+
+        let current_azks = self.retrieve_azks().await?;
+        let snapshot = current_azks
+            .get_audit_lite_snapshot::<TC, _>(&self.storage, prefix_bits)
+            .await?;
+        let root_hash = current_azks.get_root_hash::<TC, _>(&self.storage).await?;
+        let epoch_hash = EpochHash(snapshot.epoch, root_hash);
+        Ok((snapshot, epoch_hash))
+    }
+
+    /// Returns the digests of the top-level subtrees which changed in the current epoch, which a
+    /// lightweight auditor checks against the epoch's root hash (see [crate::audit_lite]). As
+    /// this relies on the previous values of the tree nodes, the proof of an epoch can only be
+    /// generated until the next epoch is published.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn audit_lite(
+        &self,
+        prefix_bits: u8,
+    ) -> Result<(AuditLiteProof, EpochHash), AkdError> {
+        let _guard = self.cache_lock.read().await;
+
+        let current_azks = self.retrieve_azks().await?;
+        let proof = current_azks
+            .get_audit_lite_proof::<TC, _>(&self.storage, prefix_bits)
+            .await?;
+        let root_hash = current_azks.get_root_hash::<TC, _>(&self.storage).await?;
+        let epoch_hash = EpochHash(proof.end_epoch, root_hash);
+        Ok((proof, epoch_hash))
+    }
+
+    /// Builds the chunks of the append-only proof from `epoch` which are missing from the store,
+    /// and assembles the proof
+    async fn audit_epoch_chunked(
+        &self,
+        epoch: u64,
+        store: &dyn ProofChunkStore,
+        options: &ChunkedAuditOptions,
+    ) -> Result<SingleAppendOnlyProof, AkdError>
+    where
+        V: 'static,
+    {
+        let mut chunks = store.chunks(epoch).await?;
+        let head = match chunks.iter().position(AppendOnlyProofChunk::is_head) {
+            Some(index) => chunks.swap_remove(index),
+            None => {
+                let head = {
+                    let _guard = self.cache_lock.read().await;
+                    self.retrieve_azks()
+                        .await?
+                        .get_append_only_proof_head::<TC, _>(
+                            &self.storage,
+                            epoch,
+                            options.split_depth,
+                        )
+                        .await?
+                };
+                store.save(head.clone()).await?;
+                head
+            }
+        };
+
+        let completed = chunks
+            .iter()
+            .filter_map(|chunk| chunk.subtree)
+            .collect::<HashSet<_>>();
+        let missing = head
+            .subtrees
+            .iter()
+            .filter(|subtree| !completed.contains(subtree))
+            .copied()
+            .collect::<Vec<_>>();
+        info!(
+            epoch,
+            num_chunks = head.subtrees.len(),
+            num_missing = missing.len(),
+            "Building the chunks of an append-only proof"
+        );
+
+        let mut built = futures::stream::iter(missing.into_iter().map(|subtree| {
+            let directory = self.clone();
+            tokio::task::spawn(instrumentation::in_current_span(async move {
+                // A publish cannot commit while a chunk is being built, but may between chunks
+                let _guard = directory.cache_lock.read().await;
+                directory
+                    .retrieve_azks()
+                    .await?
+                    .get_append_only_proof_subtree::<TC, _>(&directory.storage, epoch, subtree)
+                    .await
+            }))
+        }))
+        .buffer_unordered(options.max_concurrent_chunks.max(1));
+        while let Some(joined) = built.next().await {
+            let chunk = joined
+                .map_err(|e| AkdError::Parallelism(ParallelismError::JoinErr(e.to_string())))??;
+            store.save(chunk.clone()).await?;
+            chunks.push(chunk);
+        }
+
+        assemble_chunks(&head, &chunks)
+    }
+
+    /// Spot checks the current epoch with `sample_size` leaves, sampled pseudorandomly from
+    /// the epoch's root hash. The proof is verified with
+    /// [spot_check_verify](crate::spot_check::spot_check_verify) against the returned
+    /// [EpochHash]. See [crate::spot_check].
+    pub async fn spot_check(
+        &self,
+        sample_size: u64,
+    ) -> Result<(SpotCheckProof, EpochHash), AkdError> {
+        if sample_size > MAX_SPOT_CHECK_SAMPLES {
+            return Err(AkdError::Directory(DirectoryError::Verification(
+                VerificationError::SpotCheck(format!(
+                    "The sample size {sample_size} exceeds the maximum of {MAX_SPOT_CHECK_SAMPLES}"
+                )),
+            )));
+        }
+        // The guard will be dropped at the end of the proof generation
+        let _guard = self.cache_lock.read().await;
+        let start = Instant::now();
+
+        let current_azks = self.retrieve_azks().await?;
+        let current_epoch = current_azks.get_latest_epoch();
+        let root_hash = current_azks.get_root_hash::<TC, _>(&self.storage).await?;
+
+        let mut samples = Vec::new();
+        for target in spot_check_targets::<TC>(root_hash, current_epoch, sample_size) {
+            match current_azks
+                .get_spot_check_sample::<TC, _>(&self.storage, &target)
+                .await?
+            {
+                Some(sample) => samples.push(sample),
+                None => break,
+            }
+        }
+        let proof = SpotCheckProof {
+            epoch: current_epoch,
+            samples,
+        };
+        instrumentation::record_proof("spot_check", start, &proof);
+        Ok((proof, EpochHash(current_epoch, root_hash)))
+    }
+
+    /// Consolidates the mini-epochs published since the previous audit epoch into a new
+    /// [AuditEpoch], which ends at the current epoch. Returns [None] if no epochs have been
+    /// published since the previous audit epoch. See [crate::consolidation].
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn consolidate(&self) -> Result<Option<AuditEpoch>, AkdError> {
+        if self.role() == StorageRole::Follower {
+            return Err(AkdError::Directory(DirectoryError::ReadOnlyDirectory(
+                "A follower cannot consolidate, since only the leader writes to the storage"
+                    .to_string(),
+            )));
+        }
+        // The guard keeps a publish from committing while the head of the directory is read
+        let _guard = self.cache_lock.read().await;
+
+        let EpochHash(end_epoch, end_root_hash) = self.get_epoch_hash().await?;
+        let (index, start_epoch, start_root_hash) = match self.latest_audit_epoch().await? {
+            Some(previous) if previous.end_epoch >= end_epoch => return Ok(None),
+            Some(previous) => (
+                previous.index + 1,
+                previous.end_epoch,
+                previous.end_root_hash,
+            ),
+            None if end_epoch == 0 => return Ok(None),
+            None => (
+                1,
+                0,
+                TC::compute_root_hash_from_val(&TC::empty_root_value()),
+            ),
+        };
+        let timestamp_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+        let audit_epoch = AuditEpoch {
+            index,
+            start_epoch,
+            start_root_hash,
+            end_epoch,
+            end_root_hash,
+            timestamp_ms,
+        };
+        self.storage
+            .set(DbRecord::AuditEpoch(audit_epoch.clone()))
+            .await?;
+        info!(
+            index,
+            start_epoch, end_epoch, "Consolidated mini-epochs into an audit epoch"
+        );
+        Ok(Some(audit_epoch))
+    }
+
+    /// Retrieves the audit epoch with the provided index, failing with a
+    /// [StorageError::NotFound] error if it has not been consolidated
+    pub async fn audit_epoch(&self, index: u64) -> Result<AuditEpoch, AkdError> {
+        match self.storage.get::<AuditEpoch>(&index).await? {
+            DbRecord::AuditEpoch(audit_epoch) => Ok(audit_epoch),
+            _ => Err(AkdError::Storage(StorageError::NotFound(format!(
+                "Audit epoch {index} not found"
+            )))),
+        }
+    }
+
+    /// Retrieves the latest audit epoch, or [None] if no epochs have been consolidated
+    pub async fn latest_audit_epoch(&self) -> Result<Option<AuditEpoch>, AkdError> {
+        // Audit epochs are numbered from 1
+        match self.sequence_length::<AuditEpoch>(1).await? {
+            0 => Ok(None),
+            count => self.audit_epoch(count).await.map(Some),
+        }
+    }
+
+    /// Returns the [MultiEpochAppendOnlyProof] of the audit epoch with the provided index,
+    /// which is verified with [crate::consolidation::verify_audit_epoch]
+    pub async fn audit_epoch_proof(
+        &self,
+        index: u64,
+    ) -> Result<MultiEpochAppendOnlyProof, AkdError> {
+        let audit_epoch = self.audit_epoch(index).await?;
+        self.audit_multi_epoch(audit_epoch.start_epoch, audit_epoch.end_epoch)
+            .await
+    }
+
+    /// Returns the [MiniEpochProof] of a mini-epoch which has been consolidated, which is
+    /// verified with [crate::consolidation::verify_mini_epoch]
+    pub async fn mini_epoch_proof(&self, epoch: u64) -> Result<MiniEpochProof, AkdError> {
+        let latest = self.latest_audit_epoch().await?;
+        let Some(latest) = latest.filter(|latest| epoch > 0 && epoch <= latest.end_epoch) else {
+            return Err(AkdError::Directory(DirectoryError::InvalidEpoch(format!(
+                "Epoch {epoch} has not been consolidated into an audit epoch"
+            ))));
+        };
+
+        // The audit epochs cover consecutive ranges of epochs, so the audit epoch covering
+        // the mini-epoch is the first one which ends at or after it
+        let (mut low, mut high) = (1, latest.index);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if self.audit_epoch(middle).await?.end_epoch >= epoch {
+                high = middle;
+            } else {
+                low = middle + 1;
+            }
+        }
+        let audit_epoch = self.audit_epoch(low).await?;
+        let proof = if epoch < audit_epoch.end_epoch {
+            Some(self.audit_multi_epoch(epoch, audit_epoch.end_epoch).await?)
+        } else {
+            None
+        };
+        Ok(MiniEpochProof {
+            epoch,
+            audit_epoch: audit_epoch.index,
+            proof,
+        })
+    }
+
+    /// Retrieves the [Azks]
+    pub(crate) async fn retrieve_azks(&self) -> Result<Azks, crate::errors::AkdError> {
+        Directory::<TC, S, V>::get_azks_from_storage(&self.storage, false).await
+    }
+
+    async fn get_azks_from_storage(
+        storage: &StorageManager<S>,
+        ignore_cache: bool,
+    ) -> Result<Azks, crate::errors::AkdError> {
+        let got = if ignore_cache {
+            storage
+                .get_direct::<Azks>(&crate::append_only_zks::DEFAULT_AZKS_KEY)
+                .await?
+        } else {
+            storage
+                .get::<Azks>(&crate::append_only_zks::DEFAULT_AZKS_KEY)
+                .await?
+        };
+        match got {
+            DbRecord::Azks(azks) => Ok(azks),
+            _ => {
+                error!("No AZKS can be found. You should re-initialize the directory to create a new one");
+                Err(AkdError::Storage(StorageError::NotFound(
+                    "AZKS not found".to_string(),
+                )))
+            }
+        }
+    }
+
+    /// Returns the timing report of the most recently completed operation of the
+    /// provided type, or [None] if no such operation has completed yet
+    #[cfg(feature = "profiling")]
+    pub fn last_timing_report(&self, operation: Operation) -> Option<TimingReport> {
+        self.timing_reports
+            .lock()
+            .ok()
+            .and_then(|reports| reports.get(&operation).cloned())
+    }
+
+    #[cfg(feature = "profiling")]
+    fn record_timing(&self, timer: PhaseTimer) -> TimingReport {
+        let report = timer.finish();
+        info!(operation = ?report.operation, "{report}");
+        if let Ok(mut reports) = self.timing_reports.lock() {
+            reports.insert(report.operation, report.clone());
+        }
+        report
+    }
+
+    #[cfg(not(feature = "profiling"))]
+    fn record_timing(&self, _timer: PhaseTimer) {}
+
+    // HELPERS //
+
+    /// Use this function to retrieve the [VRFPublicKey] for this AKD.
+    pub async fn get_public_key(&self) -> Result<VRFPublicKey, AkdError> {
+        Ok(self.vrf.get_vrf_public_key().await?)
+    }
+
+    async fn create_single_update_proof(
+        &self,
+        akd_label: &AkdLabel,
+        user_state: &ValueState,
+    ) -> Result<UpdateProof, AkdError> {
+        let epoch = user_state.epoch;
+        let value = &user_state.value;
+        let version = user_state.version;
+
+        let label_at_ep = self
+            .vrf
+            .get_node_label::<TC>(akd_label, VersionFreshness::Fresh, version)
+            .await?;
+
+        let current_azks = self.retrieve_azks().await?;
+        let existence_vrf = self
+            .vrf
+            .get_label_proof::<TC>(akd_label, VersionFreshness::Fresh, version)
+            .await?;
+        let existence_vrf_proof = existence_vrf.to_bytes().to_vec();
+        let existence_label = self
+            .vrf
+            .get_node_label_from_vrf_proof::<TC>(existence_vrf)
+            .await;
+        let existence_proof = current_azks
+            .get_membership_proof::<TC, _>(&self.storage, label_at_ep)
+            .await?;
+        let mut previous_version_proof = Option::None;
+        let mut previous_version_vrf_proof = Option::None;
+        if version > 1 {
+            let prev_label_at_ep = self
+                .vrf
+                .get_node_label::<TC>(akd_label, VersionFreshness::Stale, version - 1)
+                .await?;
+            previous_version_proof = Option::Some(
+                current_azks
+                    .get_membership_proof::<TC, _>(&self.storage, prev_label_at_ep)
+                    .await?,
+            );
+            previous_version_vrf_proof = Option::Some(
+                self.vrf
+                    .get_label_proof::<TC>(akd_label, VersionFreshness::Stale, version - 1)
+                    .await?
+                    .to_bytes()
+                    .to_vec(),
+            );
+        }
+
+        let commitment_key = self.derive_commitment_key().await?;
+        let commitment_nonce =
+            TC::get_commitment_nonce(&commitment_key, &existence_label, version, value).to_vec();
+
+        Ok(UpdateProof {
+            epoch,
+            version,
+            value: value.clone(),
+            existence_vrf_proof,
+            existence_proof,
+            previous_version_vrf_proof,
+            previous_version_proof,
+            commitment_nonce,
+        })
+    }
+
+    /// Exports the nodes of the tree at the latest epoch for visualization, optionally limited
+    /// to the top levels of the tree or to the neighborhood of a label. See
+    /// [crate::visualization] for how the export can be rendered.
+    pub async fn visualize_tree(
+        &self,
+        options: &TreeVisualizationOptions,
+    ) -> Result<TreeVisualization, AkdError> {
+        let _guard = self.cache_lock.read().await;
+        let epoch = self.retrieve_azks().await?.get_latest_epoch();
+        let target = match &options.neighborhood {
+            Some(akd_label) => {
+                let akd_label = &*TC::normalize_label(akd_label);
+                let version = match self
+                    .storage
+                    .get_user_state(akd_label, ValueStateRetrievalFlag::LeqEpoch(epoch))
+                    .await
+                {
+                    Ok(state) => state.version,
+                    Err(StorageError::NotFound(_)) => 1,
+                    Err(err) => return Err(AkdError::Storage(err)),
+                };
+                Some(
+                    self.vrf
+                        .get_node_label::<TC>(akd_label, VersionFreshness::Fresh, version)
+                        .await?,
+                )
+            }
+            None => None,
+        };
+        TreeVisualization::collect(&self.storage, epoch, options.max_depth, target).await
+    }
+
+    /// Gets the root hash at the current epoch.
+    pub async fn get_epoch_hash(&self) -> Result<EpochHash, AkdError> {
+        let current_azks = self.retrieve_azks().await?;
+        let latest_epoch = current_azks.get_latest_epoch();
+        let root_hash = current_azks.get_root_hash::<TC, _>(&self.storage).await?;
+        Ok(EpochHash(latest_epoch, root_hash))
+    }
+
+    /// Anchors the root hash of the current epoch in an external system, and stores the
+    /// receipt of the anchor. Anchoring an epoch again with the same anchor replaces its
+    /// receipt. See [crate::anchoring].
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn anchor(&self, anchor: &dyn Anchor) -> Result<AnchorReceipt, AkdError> {
+        let EpochHash(epoch, root_hash) = self.get_epoch_hash().await?;
+        let receipt = anchor.anchor(epoch, root_hash).await?;
+        let timestamp_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+        let receipt = AnchorReceipt {
+            epoch,
+            anchor: anchor.name().to_string(),
+            root_hash,
+            timestamp_ms,
+            receipt,
+        };
+        anchor.verify(&receipt)?;
+        self.storage
+            .set(DbRecord::AnchorReceipt(receipt.clone()))
+            .await?;
+        info!(epoch, anchor = anchor.name(), "Anchored the root hash");
+        Ok(receipt)
+    }
+
+    /// Sets the operator which is recorded in the operations log for the mutating operations
+    /// (publishes, prunes, rollbacks, and rebuilds) subsequently performed through this
+    /// instance or any of its clones
+    pub fn set_operator(&self, operator: Operator) {
+        if let Ok(mut current) = self.operator.write() {
+            *current = operator;
+        }
+    }
+
+    /// Sets the source of the epochs committed by the publishes subsequently performed through
+    /// this instance or any of its clones, in place of incrementing the epoch on each publish.
+    /// See [crate::scheduling].
+    pub fn set_epoch_source(&self, source: impl EpochSource + 'static) {
+        if let Ok(mut current) = self.epoch_source.write() {
+            *current = Some(Arc::new(source));
+        }
+    }
+
+    /// Registers a hook which is notified of the events of this instance and of all of its
+    /// clones, after any hooks which have already been registered. See [crate::hooks] for
+    /// the events which are reported.
+    pub fn register_hook(&self, hook: impl DirectoryHooks + 'static) {
+        if let Ok(mut hooks) = self.hooks.write() {
+            hooks.push(Arc::new(hook));
+        }
+    }
+
+    /// Calls each of the registered hooks. The hooks are called without holding the lock, so
+    /// that a hook may register further hooks.
+    fn notify_hooks(&self, notify: impl Fn(&dyn DirectoryHooks)) {
+        let hooks = match self.hooks.read() {
+            Ok(hooks) => hooks.clone(),
+            Err(_) => return,
+        };
+        for hook in hooks.iter() {
+            notify(hook.as_ref());
+        }
+    }
+
+    /// Reports an inconsistency in the stored state to the registered hooks, and returns the
+    /// error with which the operation which found it fails
+    fn integrity_violation(&self, detail: String) -> AkdError {
+        error!(detail = %detail, "Integrity violation detected");
+        self.notify_hooks(|hook| hook.on_integrity_violation(&detail));
+        AkdError::Directory(DirectoryError::Integrity(detail))
+    }
+
+    /// Returns the number of entries in the operations log
+    pub async fn operation_count(&self) -> Result<u64, AkdError> {
+        self.sequence_length::<OperationRecord>(0).await
+    }
+
+    /// Returns the number of records of a type which are keyed by consecutive numbers from
+    /// `first`, such as the entries of the operations log
+    async fn sequence_length<St: Storable<StorageKey = u64>>(
+        &self,
+        first: u64,
+    ) -> Result<u64, AkdError> {
+        // The sequence has no gaps, so its length is the offset of the first missing record,
+        // which is found by an exponential search followed by a binary search
+        if !self.record_exists::<St>(first).await? {
+            return Ok(0);
+        }
+        let (mut present, mut missing) = (0u64, 1u64);
+        while self.record_exists::<St>(first + missing).await? {
+            present = missing;
+            missing *= 2;
+        }
+        while missing - present > 1 {
+            let middle = present + (missing - present) / 2;
+            if self.record_exists::<St>(first + middle).await? {
+                present = middle;
+            } else {
+                missing = middle;
+            }
+        }
+        Ok(missing)
+    }
+
+    /// Returns up to `limit` entries of the operations log, in order, starting from the entry
+    /// with sequence number `start`
+    pub async fn operations(
+        &self,
+        start: u64,
+        limit: usize,
+    ) -> Result<Vec<OperationRecord>, AkdError> {
+        let ids = (start..start.saturating_add(limit as u64)).collect::<Vec<_>>();
+        let mut operations = self
+            .storage
+            .batch_get::<OperationRecord>(&ids)
+            .await?
+            .into_iter()
+            .filter_map(|record| match record {
+                DbRecord::OperationRecord(operation) => Some(operation),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        operations.sort_by_key(|operation| operation.sequence);
+        Ok(operations)
+    }
+
+    async fn record_exists<St: Storable<StorageKey = u64>>(
+        &self,
+        key: u64,
+    ) -> Result<bool, AkdError> {
+        match self.storage.get::<St>(&key).await {
+            Ok(_) => Ok(true),
+            Err(StorageError::NotFound(_)) => Ok(false),
+            Err(err) => Err(AkdError::Storage(err)),
+        }
+    }
+
+    /// Builds the next entry of the operations log, for an operation by the current operator.
+    /// The caller holds the lock on the next sequence number until the entry is written, and
+    /// then advances it.
+    async fn operation_record(
+        &self,
+        next_operation: &mut Option<u64>,
+        kind: OperationKind,
+        epoch: u64,
+        detail: String,
+    ) -> Result<OperationRecord, AkdError> {
+        let sequence = match *next_operation {
+            Some(sequence) => sequence,
+            None => {
+                let sequence = self.operation_count().await?;
+                *next_operation = Some(sequence);
+                sequence
+            }
+        };
+        let operator = self
+            .operator
+            .read()
+            .map(|operator| operator.clone())
+            .unwrap_or_default();
+        let timestamp_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+        Ok(OperationRecord {
+            sequence,
+            kind,
+            epoch,
+            timestamp_ms,
+            identity: operator.identity,
+            metadata: operator.metadata,
+            detail,
+        })
+    }
+
+    /// Checks the health of each of the components which the directory depends on: that its
+    /// state can be read from storage, that its VRF key is available, that its cache is being
+    /// cleaned, that it has published recently, and that its audits are keeping up. The
+    /// thresholds for the last two checks are provided by the options.
+    ///
+    /// The age of the last publish is only known for publishes made through this instance
+    /// (or its clones), so a directory which has not published since it was created reports
+    /// its last publish as degraded whenever a maximum age is set.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn health(&self, options: &HealthCheckOptions) -> HealthReport {
+        let (storage, epoch) = match self.retrieve_azks().await {
+            Ok(azks) => {
+                let epoch = azks.get_latest_epoch();
+                (
+                    ComponentHealth::healthy(format!("at epoch {epoch}")),
+                    Some(epoch),
+                )
+            }
+            Err(err) => (
+                ComponentHealth::unhealthy(format!("failed to read the directory state: {err}")),
+                None,
+            ),
+        };
+
+        let vrf = match self.vrf.get_vrf_public_key().await {
+            Ok(_) => ComponentHealth::healthy("key available"),
+            Err(err) => ComponentHealth::unhealthy(format!("key unavailable: {err}")),
+        };
+
+        let cache_items = self.storage.cache_len();
+        let cache = match (cache_items, self.storage.is_cache_cleaning_enabled()) {
+            (Some(items), Some(false)) if !self.storage.is_transaction_active() => {
+                ComponentHealth::degraded(format!(
+                    "{items} items, and cleaning is disabled outside of a transaction"
+                ))
+            }
+            (Some(items), _) => ComponentHealth::healthy(format!("{items} items")),
+            (None, _) => ComponentHealth::healthy("disabled"),
+        };
+
+        let last_publish_age = self
+            .last_publish
+            .lock()
+            .ok()
+            .and_then(|last_publish| *last_publish)
+            .map(|last_publish| last_publish.elapsed());
+        let last_publish = match (last_publish_age, options.max_publish_age) {
+            (Some(age), Some(max_age)) if age > max_age => ComponentHealth::degraded(format!(
+                "last publish was {age:?} ago, which exceeds {max_age:?}"
+            )),
+            (None, Some(_)) => ComponentHealth::degraded("no publish by this instance"),
+            (Some(age), _) => ComponentHealth::healthy(format!("last publish was {age:?} ago")),
+            (None, None) => ComponentHealth::healthy("not checked"),
+        };
+
+        let audit_lag = options
+            .audited_epoch
+            .zip(epoch)
+            .map(|(audited_epoch, epoch)| epoch.saturating_sub(audited_epoch));
+        let audit = match (options.audited_epoch, audit_lag) {
+            (None, _) => ComponentHealth::healthy("not checked"),
+            (Some(_), None) => ComponentHealth::unhealthy("the current epoch is unknown"),
+            (Some(_), Some(lag)) if lag > options.max_audit_lag => {
+                ComponentHealth::degraded(format!(
+                    "audits trail by {lag} epochs, which exceeds {}",
+                    options.max_audit_lag
+                ))
+            }
+            (Some(_), Some(lag)) => {
+                ComponentHealth::healthy(format!("audits trail by {lag} epochs"))
+            }
+        };
+
+        let status = [&storage, &vrf, &cache, &last_publish, &audit]
+            .iter()
+            .map(|component| component.status)
+            .max()
+            .expect("at least one component");
+        HealthReport {
+            status,
+            epoch,
+            storage,
+            vrf,
+            cache,
+            last_publish,
+            audit,
+            last_publish_age,
+            audit_lag,
+            cache_items,
+        }
+    }
+
+    // We simply hash the VRF private key to derive the commitment key
+    async fn derive_commitment_key(&self) -> Result<Digest, AkdError> {
+        let raw_key = self.vrf.retrieve().await?;
+        let commitment_key = TC::hash(&raw_key);
+        Ok(commitment_key)
+    }
+}
+
+impl<TC, S, V> Directory<TC, S, V>
+where
+    TC: Configuration,
+    S: StorageUtil + 'static,
+    V: VRFKeyStorage,
+{
+    /// Gets the root hash at the current epoch, together with the receipts of the anchors
+    /// in which it has been anchored
+    pub async fn get_anchored_root(&self) -> Result<AnchoredRoot, AkdError> {
+        let epoch_hash = self.get_epoch_hash().await?;
+        let receipts = self.storage.get_anchor_receipts(epoch_hash.epoch()).await?;
+        Ok(AnchoredRoot {
+            epoch_hash,
+            receipts,
+        })
+    }
+
+    /// Provides a lookup proof for the latest version of the target label, as with
+    /// [Directory::lookup], together with the receipts of the anchors in which the root hash
+    /// it verifies against has been anchored. The client checks the receipts with
+    /// [AnchoredRoot::verify] before verifying the proof against the root hash.
+    ///
+    /// * `akd_label`: The target label to generate a lookup proof for
+    pub async fn lookup_anchored(
+        &self,
+        akd_label: AkdLabel,
+    ) -> Result<(LookupProof, AnchoredRoot), AkdError> {
+        let (proof, epoch_hash) = self.lookup(akd_label).await?;
+        let receipts = self.storage.get_anchor_receipts(epoch_hash.epoch()).await?;
+        Ok((
+            proof,
+            AnchoredRoot {
+                epoch_hash,
+                receipts,
+            },
+        ))
+    }
+
+    /// Reconstructs every tree node of the directory from its stored value states, as a recovery
+    /// path of last resort when interior nodes of the tree have been corrupted. The tree is
+    /// rebuilt by replaying the insertions of each epoch in order, and the rebuilt nodes are
+    /// only written to storage if the resulting root hash matches the root hash currently
+    /// recorded for the latest epoch. Returns the (unchanged) root hash of the latest epoch.
+    ///
+    /// The rebuild requires every value state to be present, so it fails if any values
+    /// have been tombstoned.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn rebuild_from_values(&self) -> Result<EpochHash, AkdError> {
+        // Block all other operations on the directory while its tree nodes are replaced
+        let _guard = self.cache_lock.write().await;
+
+        let current_azks = self.retrieve_azks().await?;
+        let current_epoch = current_azks.get_latest_epoch();
+        let recorded_root_hash = current_azks.get_root_hash::<TC, _>(&self.storage).await?;
+
+        let (rebuilt_azks, rebuild_storage) = self.replay_value_states(current_epoch).await?;
+        let rebuilt_root_hash = rebuilt_azks
+            .get_root_hash::<TC, _>(&rebuild_storage)
+            .await?;
+        if rebuilt_root_hash != recorded_root_hash {
+            return Err(AkdError::Directory(DirectoryError::Rebuild(format!(
+                "The rebuilt root hash {} does not match the recorded root hash {} at epoch {current_epoch}",
+                hex::encode(rebuilt_root_hash),
+                hex::encode(recorded_root_hash)
+            ))));
+        }
+
+        let mut records = rebuild_storage
+            .get_all_direct::<TreeNodeWithPreviousValue>()
+            .await?;
+        records.push(DbRecord::Azks(rebuilt_azks));
+        info!(
+            epoch = current_epoch,
+            batch_size = records.len(),
+            "Writing rebuilt records"
+        );
+        let mut next_operation = self.next_operation.lock().await;
+        let operation = self
+            .operation_record(
+                &mut next_operation,
+                OperationKind::Rebuild,
+                current_epoch,
+                format!("Rewrote {} records", records.len()),
+            )
+            .await?;
+        let operation_sequence = operation.sequence;
+        records.push(DbRecord::OperationRecord(operation));
+        self.storage.batch_set(records).await?;
+        *next_operation = Some(operation_sequence + 1);
+
+        Ok(EpochHash(current_epoch, recorded_root_hash))
+    }
+
+    /// Rolls the directory back to an earlier epoch, for disaster recovery when a bad publish
+    /// must be unwound before clients have observed it. All value states published after
+    /// `epoch` are deleted, the tree is rebuilt from the remaining value states (as in
+    /// [Directory::rebuild_from_values]), and `epoch` is reinstated as the head of the
+    /// directory. Returns a record of the rollback signed by the provided signer, which
+    /// should be published so that auditors can account for the rollback.
+    ///
+    /// Only the leader may roll back, and it holds the publish lease (if the storage manager
+    /// requires one) for the duration of the rollback. The rollback is not atomic, and no
+    /// other operations may be run against the storage layer while it is in progress.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(epoch = epoch)))]
+    pub async fn rollback_to(
+        &self,
+        epoch: u64,
+        signer: &impl EpochSigner,
+    ) -> Result<SignedRollbackRecord, AkdError> {
+        if self.role() == StorageRole::Follower {
+            return Err(AkdError::Directory(DirectoryError::ReadOnlyDirectory(
+                "A follower cannot roll back, since only the leader writes to the storage"
+                    .to_string(),
+            )));
+        }
+        let lease = self.storage.acquire_publish_lease().await?;
+        let result = self.rollback_leased(epoch, signer, lease.as_ref()).await;
+        if let Some(lease) = lease {
+            if let Err(err) = lease.release().await {
+                warn!(error = %err, "Failed to release the publish lease");
+            }
+        }
+        result
+    }
+
+    /// Rolls back while holding the publish lease, if the storage manager requires one. The
+    /// lease is renewed before any record is deleted, so that a holder whose lease has been
+    /// taken over does not modify the storage.
+    async fn rollback_leased(
+        &self,
+        epoch: u64,
+        signer: &impl EpochSigner,
+        lease: Option<&PublishLeaseGuard<S>>,
+    ) -> Result<SignedRollbackRecord, AkdError> {
+        // Block all other operations on the directory while its state is replaced
+        let _guard = self.cache_lock.write().await;
+
+        let current_azks = self.retrieve_azks().await?;
+        let current_epoch = current_azks.get_latest_epoch();
+        if epoch >= current_epoch {
+            return Err(AkdError::Directory(DirectoryError::InvalidEpoch(format!(
+                "Cannot roll back to epoch {epoch}, which is not before the current epoch {current_epoch}"
+            ))));
+        }
+        if let Some(audit_epoch) = self.latest_audit_epoch().await? {
+            if epoch < audit_epoch.end_epoch {
+                return Err(AkdError::Directory(DirectoryError::InvalidEpoch(format!(
+                    "Cannot roll back to epoch {epoch}, which is before the end of audit epoch {} at epoch {}",
+                    audit_epoch.index, audit_epoch.end_epoch
+                ))));
+            }
+        }
+        let current_root_hash = current_azks.get_root_hash::<TC, _>(&self.storage).await?;
+
+        // Only the nodes on the paths of the leaves inserted after `epoch` have changed since
+        // then, which are the prefixes of the labels of the removed value states (and of the
+        // stale labels of their previous versions), along with the children of those nodes,
+        // whose parent may have been inserted after `epoch`
+        let mut removed_value_state_keys = Vec::new();
+        let mut touched_labels = Vec::new();
+        let mut stale_computations = Vec::new();
+        let mut states = self.storage.iter_user_states();
+        while let Some(state) = states.try_next().await? {
+            if state.epoch > epoch {
+                removed_value_state_keys.push(state.get_id());
+                touched_labels.push(state.label);
+                if state.version > 1 {
+                    stale_computations.push((
+                        state.username,
+                        VersionFreshness::Stale,
+                        state.version - 1,
+                        state.value,
+                    ));
+                }
+            }
+        }
+        touched_labels.extend(
+            self.vrf
+                .get_node_labels::<TC>(&stale_computations)
+                .await?
+                .into_iter()
+                .map(|(_, label)| label),
+        );
+        let path_node_keys = touched_labels
+            .iter()
+            .flat_map(|label| (0..=label.label_len).map(|len| NodeKey(label.get_prefix(len))))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let mut current_nodes = self
+            .storage
+            .batch_get::<TreeNodeWithPreviousValue>(&path_node_keys)
+            .await?
+            .into_iter()
+            .filter_map(|record| match record {
+                DbRecord::TreeNode(node) => Some(node),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let path_node_keys = path_node_keys.into_iter().collect::<HashSet<_>>();
+        let child_node_keys = current_nodes
+            .iter()
+            .flat_map(|node| [node.latest_node.left_child, node.latest_node.right_child])
+            .flatten()
+            .map(NodeKey)
+            .filter(|key| !path_node_keys.contains(key))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        current_nodes.extend(
+            self.storage
+                .batch_get::<TreeNodeWithPreviousValue>(&child_node_keys)
+                .await?
+                .into_iter()
+                .filter_map(|record| match record {
+                    DbRecord::TreeNode(node) => Some(node),
+                    _ => None,
+                }),
+        );
+        let touched_node_keys = current_nodes
+            .iter()
+            .map(|node| node.get_id())
+            .collect::<Vec<_>>();
+
+        let (rebuilt_azks, rebuild_storage) = self.replay_value_states(epoch).await?;
+        let rebuilt_root_hash = rebuilt_azks
+            .get_root_hash::<TC, _>(&rebuild_storage)
+            .await?;
+        let rebuilt_records = rebuild_storage
+            .batch_get::<TreeNodeWithPreviousValue>(&touched_node_keys)
+            .await?;
+
+        let rebuilt_node_keys = rebuilt_records
+            .iter()
+            .filter_map(|record| match record {
+                DbRecord::TreeNode(node) => Some(node.get_id()),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        let removed_node_keys = touched_node_keys
+            .into_iter()
+            .filter(|key| !rebuilt_node_keys.contains(key))
+            .collect::<Vec<_>>();
+
+        if let Some(lease) = lease {
+            lease.renew().await?;
+        }
+        warn!(
+            from_epoch = current_epoch,
+            to_epoch = epoch,
+            num_value_states = removed_value_state_keys.len(),
+            num_nodes = removed_node_keys.len(),
+            "Rolling back the directory"
+        );
+        self.storage
+            .batch_delete_direct::<ValueState>(&removed_value_state_keys)
+            .await?;
+        self.storage
+            .batch_delete_direct::<TreeNodeWithPreviousValue>(&removed_node_keys)
+            .await?;
+        let mut next_operation = self.next_operation.lock().await;
+        let operation = self
+            .operation_record(
+                &mut next_operation,
+                OperationKind::Rollback,
+                epoch,
+                format!(
+                    "Rolled back from epoch {current_epoch}, removing {} value states and {} tree nodes",
+                    removed_value_state_keys.len(),
+                    removed_node_keys.len()
+                ),
+            )
+            .await?;
+        let operation_sequence = operation.sequence;
+        let mut records = rebuilt_records;
+        records.push(DbRecord::Azks(rebuilt_azks));
+        records.push(DbRecord::OperationRecord(operation));
+        self.storage.batch_set(records).await?;
+        *next_operation = Some(operation_sequence + 1);
+        // The head of the directory moves back to an earlier epoch, which the cached proofs
+        // would otherwise be considered newer than
+        if let Ok(mut proof_cache) = self.proof_cache.write() {
+            let num_proofs = proof_cache.clear();
+            instrumentation::record_proof_cache_invalidation(num_proofs);
+        }
+
+        let signature = signer.sign_epoch_message(&rollback_signature_message::<TC>(
+            current_epoch,
+            &current_root_hash,
+            epoch,
+            &rebuilt_root_hash,
+        ));
+        Ok(SignedRollbackRecord {
+            from_epoch: current_epoch,
+            from_root_hash: current_root_hash,
+            to_epoch: epoch,
+            to_root_hash: rebuilt_root_hash,
+            signature,
+        })
+    }
+
+    /// Redacts a version of a label, replacing its value in storage with a [RedactionRecord]
+    /// which states the epoch of the redaction and its reason, along with the commitment to the
+    /// redacted value. Clients which verify the label's key history with
+    /// [HistoryVerificationParams::AllowMissingValues](akd_core::verify::HistoryVerificationParams::AllowMissingValues)
+    /// then see the redaction record in place of the value. The redaction is logged in the
+    /// operations log.
+    ///
+    /// The latest version of a label cannot be redacted, since lookups must reveal its value:
+    /// a replacement value should be published first.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(version = version)))]
+    pub async fn redact(
+        &self,
+        akd_label: &AkdLabel,
+        version: u64,
+        reason: &str,
+    ) -> Result<RedactionRecord, AkdError> {
+        if self.role() == StorageRole::Follower {
+            return Err(AkdError::Directory(DirectoryError::ReadOnlyDirectory(
+                "A follower cannot redact values, since only the leader writes to the storage"
+                    .to_string(),
+            )));
+        }
+        let akd_label = normalize_label::<TC>(akd_label.clone());
+        // The guard keeps a publish from committing while the value is redacted
+        let _guard = self.cache_lock.read().await;
+        let current_epoch = self.retrieve_azks().await?.get_latest_epoch();
+
+        let latest = self
+            .storage
+            .get_user_state(&akd_label, ValueStateRetrievalFlag::MaxEpoch)
+            .await?;
+        if version >= latest.version {
+            return Err(AkdError::Directory(DirectoryError::Redaction(format!(
+                "Version {version} of label {} is not older than the latest version {}",
+                instrumentation::label_hash_prefix::<TC>(&akd_label),
+                latest.version
+            ))));
+        }
+        let state = self
+            .storage
+            .get_user_state(
+                &akd_label,
+                ValueStateRetrievalFlag::SpecificVersion(version),
+            )
+            .await?;
+        if TC::is_tombstone(&state.value) {
+            return Err(AkdError::Directory(DirectoryError::Redaction(format!(
+                "Version {version} of label {} has already been tombstoned",
+                instrumentation::label_hash_prefix::<TC>(&akd_label)
+            ))));
+        }
+
+        let commitment_key = self.derive_commitment_key().await?;
+        let record = RedactionRecord {
+            redacted_at: current_epoch,
+            commitment: TC::compute_fresh_azks_value(
+                &commitment_key,
+                &state.label,
+                state.version,
+                &state.value,
+            ),
+            reason: reason.to_string(),
+        };
+        info!(version, epoch = current_epoch, "Redacting a value");
+        let mut next_operation = self.next_operation.lock().await;
+        let operation = self
+            .operation_record(
+                &mut next_operation,
+                OperationKind::Redaction,
+                current_epoch,
+                format!(
+                    "Redacted version {version} of label {}: {reason}",
+                    instrumentation::label_hash_prefix::<TC>(&akd_label)
+                ),
+            )
+            .await?;
+        let operation_sequence = operation.sequence;
+        self.storage
+            .batch_set(vec![
+                DbRecord::ValueState(ValueState {
+                    value: record.to_value(),
+                    ..state
+                }),
+                DbRecord::OperationRecord(operation),
+            ])
+            .await?;
+        *next_operation = Some(operation_sequence + 1);
+        Ok(record)
+    }
+
+    /// Compares the stored state of the directory against its operations log, to find the
+    /// inconsistencies left behind by two publishers writing to the same storage (see
+    /// [crate::divergence]). The head of the directory must be at the epoch of the last logged
+    /// operation, each publish must be logged at the epoch following the previous operation, no
+    /// records may have been written after the head, and the root hash must match that of the
+    /// tree rebuilt from the stored values (unless values have been tombstoned). Any
+    /// inconsistencies are also reported to the registered hooks.
+    ///
+    /// This reads every record in storage, so it is meant to be run on startup (see
+    /// [Directory::new_checked]) or before a recovery, rather than while serving.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn detect_divergence(&self) -> Result<DivergenceReport, AkdError> {
+        let _guard = self.cache_lock.read().await;
+        let azks = Directory::<TC, S, V>::get_azks_from_storage(&self.storage, true).await?;
+        let head_epoch = azks.get_latest_epoch();
+
+        let operations = self
+            .storage
+            .get_all_direct::<OperationRecord>()
+            .await?
+            .into_iter()
+            .filter_map(|record| match record {
+                DbRecord::OperationRecord(operation) => Some(operation),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let num_operations = operations.len() as u64;
+        let mut divergences = divergence::check_operations_log(head_epoch, operations);
+
+        let mut states = vec![];
+        let mut tombstoned = false;
+        let mut stream = self.storage.iter_user_states();
+        while let Some(state) = stream.try_next().await? {
+            tombstoned |= TC::is_tombstone(&state.value);
+            states.push(state);
+        }
+        let state_divergences = divergence::check_value_states(head_epoch, states);
+        let duplicated = state_divergences
+            .iter()
+            .any(|divergence| matches!(divergence, Divergence::DuplicateVersion { .. }));
+        divergences.extend(state_divergences);
+
+        let mut orphaned_nodes = self
+            .storage
+            .get_all_direct::<TreeNodeWithPreviousValue>()
+            .await?
+            .into_iter()
+            .filter_map(|record| match record {
+                DbRecord::TreeNode(node) if node.latest_node.last_epoch > head_epoch => {
+                    Some((node.get_id(), node.latest_node.last_epoch))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if !orphaned_nodes.is_empty() {
+            orphaned_nodes.sort_by_key(|(_, epoch)| *epoch);
+            divergences.push(Divergence::OrphanedTreeNodes {
+                nodes: orphaned_nodes,
+            });
+        }
+
+        // A rebuild is only meaningful when every version of every label is stored once
+        let root_hash_checked = !tombstoned && !duplicated;
+        if root_hash_checked {
+            let stored = azks.get_root_hash::<TC, _>(&self.storage).await?;
+            let (rebuilt_azks, rebuild_storage) = self.replay_value_states(head_epoch).await?;
+            let rebuilt = rebuilt_azks
+                .get_root_hash::<TC, _>(&rebuild_storage)
+                .await?;
+            if rebuilt != stored {
+                divergences.push(Divergence::RootHashMismatch {
+                    epoch: head_epoch,
+                    stored,
+                    rebuilt,
+                });
+            }
+        }
+
+        let report = DivergenceReport {
+            head_epoch,
+            num_operations,
+            root_hash_checked,
+            divergences,
+        };
+        if !report.is_consistent() {
+            let detail = report.to_string();
+            error!(detail = %detail, "Divergence detected in storage");
+            self.notify_hooks(|hook| hook.on_integrity_violation(&detail));
+        }
+        Ok(report)
+    }
+
+    /// Creates a directory as with [Directory::new], and checks its storage for the divergence
+    /// left behind by two publishers writing to it (see [Directory::detect_divergence]). The
+    /// directory is returned along with the report, so that the operator can decide how to
+    /// recover before publishing.
+    pub async fn new_checked(
+        storage: StorageManager<S>,
+        vrf: V,
+    ) -> Result<(Self, DivergenceReport), AkdError> {
+        let directory = Self::new(storage, vrf).await?;
+        let report = directory.detect_divergence().await?;
+        Ok((directory, report))
+    }
+
+    /// Exports the current version of every label as of `epoch`, together with the root hash of
+    /// the directory at that epoch, as a snapshot which can be consumed without access to the
+    /// storage layer. Each entry carries the commitment to its value rather than the value
+    /// itself, and can be checked against the membership proof for its version in the label's
+    /// history proof.
+    ///
+    /// The commitments for the latest epoch are read directly from the tree. For an earlier
+    /// epoch, the tree at that epoch is first reconstructed from the value states published up
+    /// to it (as in [Directory::rebuild_from_values]), which fails if any of those values have
+    /// been tombstoned.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(epoch = epoch)))]
+    pub async fn export(&self, epoch: u64) -> Result<DirectoryExport, AkdError> {
+        let _guard = self.cache_lock.read().await;
+
+        let current_azks = self.retrieve_azks().await?;
+        let current_epoch = current_azks.get_latest_epoch();
+        if epoch > current_epoch {
+            return Err(AkdError::Directory(DirectoryError::InvalidEpoch(format!(
+                "Cannot export epoch {epoch}, which is after the current epoch {current_epoch}"
+            ))));
+        }
+
+        // Find the latest version and the retention class of each label as of the epoch
+        let mut current_states = HashMap::<AkdLabel, ValueState>::new();
+        let mut retention_classes = HashMap::new();
+        let mut stored_states = self.storage.iter_user_states();
+        while let Some(state) = stored_states.try_next().await? {
+            if state.epoch > epoch {
+                continue;
+            }
+            track_retention_class(&mut retention_classes, &state);
+            match current_states.get(&state.username) {
+                Some(existing) if existing.version >= state.version => {}
+                _ => {
+                    current_states.insert(state.username.clone(), state);
+                }
+            }
+        }
+        let states = current_states.into_values().collect::<Vec<_>>();
+
+        let (root_hash, commitments) = if epoch == current_epoch {
+            let root_hash = current_azks.get_root_hash::<TC, _>(&self.storage).await?;
+            let commitments = Self::leaf_commitments(&self.storage, &states, epoch).await?;
+            (root_hash, commitments)
+        } else {
+            let (rebuilt_azks, rebuild_storage) = self.replay_value_states(epoch).await?;
+            let root_hash = rebuilt_azks
+                .get_root_hash::<TC, _>(&rebuild_storage)
+                .await?;
+            let commitments = Self::leaf_commitments(&rebuild_storage, &states, epoch).await?;
+            (root_hash, commitments)
+        };
+
+        let mut entries = states
+            .into_iter()
+            .map(|state| {
+                let commitment = commitments.get(&state.label).copied().ok_or_else(|| {
+                    self.integrity_violation(format!(
+                        "The tree leaf for version {} of label {} is missing",
+                        state.version,
+                        instrumentation::label_hash_prefix::<TC>(&state.username)
+                    ))
+                })?;
+                let retention_class = retention_classes
+                    .get(&state.username)
+                    .map(|(_, retention_class)| *retention_class)
+                    .unwrap_or_default();
+                Ok(ExportEntry {
+                    label: state.username,
+                    version: state.version,
+                    epoch: state.epoch,
+                    node_label: state.label,
+                    commitment,
+                    retention_class,
+                })
+            })
+            .collect::<Result<Vec<_>, AkdError>>()?;
+        entries.sort_by(|a, b| a.label.cmp(&b.label));
+
+        Ok(DirectoryExport {
+            epoch,
+            root_hash,
+            entries,
+        })
+    }
+
+    /// Reads the values of the tree leaves for the provided value states, keyed by leaf label
+    async fn leaf_commitments<S2: Database>(
+        storage: &StorageManager<S2>,
+        states: &[ValueState],
+        epoch: u64,
+    ) -> Result<HashMap<NodeLabel, AzksValue>, AkdError> {
+        let keys = states
+            .iter()
+            .map(|state| NodeKey(state.label))
+            .collect::<Vec<_>>();
+        Ok(TreeNode::batch_get_from_storage(storage, &keys, epoch)
+            .await?
+            .into_iter()
+            .map(|node| (node.label, node.hash))
+            .collect())
+    }
+
+    /// Assigns a retention class to a label, which decides how long the values of its older
+    /// versions are kept when values are pruned (see [RetentionClass]). The class is recorded on
+    /// the latest value state of the label, and is kept by the versions published after it until
+    /// another class is assigned, so the label must have been published.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn set_retention_class(
+        &self,
+        akd_label: &AkdLabel,
+        retention_class: RetentionClass,
+    ) -> Result<(), AkdError> {
+        if self.role() == StorageRole::Follower {
+            return Err(AkdError::Directory(DirectoryError::ReadOnlyDirectory(
+                "A follower cannot assign retention classes, since only the leader writes to the storage"
+                    .to_string(),
+            )));
+        }
+        let akd_label = normalize_label::<TC>(akd_label.clone());
+        // The guard keeps a publish from committing while the class is assigned
+        let _guard = self.cache_lock.read().await;
+        let current_epoch = self.retrieve_azks().await?.get_latest_epoch();
+
+        let latest = self
+            .storage
+            .get_user_state(&akd_label, ValueStateRetrievalFlag::MaxEpoch)
+            .await?;
+        info!(
+            ?retention_class,
+            epoch = current_epoch,
+            "Assigning a retention class"
+        );
+        let mut next_operation = self.next_operation.lock().await;
+        let operation = self
+            .operation_record(
+                &mut next_operation,
+                OperationKind::RetentionChange,
+                current_epoch,
+                format!(
+                    "Assigned retention class {retention_class:?} to label {}",
+                    instrumentation::label_hash_prefix::<TC>(&akd_label)
+                ),
+            )
+            .await?;
+        let operation_sequence = operation.sequence;
+        self.storage
+            .batch_set(vec![
+                DbRecord::ValueState(ValueState {
+                    retention_class: Some(retention_class),
+                    ..latest
+                }),
+                DbRecord::OperationRecord(operation),
+            ])
+            .await?;
+        *next_operation = Some(operation_sequence + 1);
+        Ok(())
+    }
+
+    /// Returns the retention class of a label, which is [RetentionClass::Standard] unless
+    /// another class has been assigned with [Directory::set_retention_class]
+    pub async fn retention_class(&self, akd_label: &AkdLabel) -> Result<RetentionClass, AkdError> {
+        let akd_label = normalize_label::<TC>(akd_label.clone());
+        let mut retention_classes = HashMap::new();
+        for state in self.storage.get_user_data(&akd_label).await?.states.iter() {
+            track_retention_class(&mut retention_classes, state);
+        }
+        Ok(retention_classes
+            .remove(&akd_label)
+            .map(|(_, retention_class)| retention_class)
+            .unwrap_or_default())
+    }
+
+    /// Tombstones the values of all versions which were published more than `retain_epochs`
+    /// epochs before the latest epoch, except for the latest version of each label, as with
+    /// [MaintenanceTask::Pruning]. Returns the number of values tombstoned.
+    pub async fn tombstone_older_than(&self, retain_epochs: u64) -> Result<usize, AkdError> {
+        self.apply_retention_policy(&RetentionPolicy::new(retain_epochs))
+            .await
+    }
+
+    /// Tombstones the values of all versions which were published before the epochs retained
+    /// for their label by the policy, except for the latest version of each label. Returns the
+    /// number of values tombstoned.
+    ///
+    /// The tombstones are written with [Configuration::tombstone_value], so the histories of the
+    /// labels still verify with
+    /// [AllowMissingValues](crate::HistoryVerificationParams::AllowMissingValues), and the
+    /// commitments carried by their metadata (if any) still allow the versions to be hidden by
+    /// [Directory::selective_key_history] or aggregated by [Directory::aggregate_history].
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn apply_retention_policy(
+        &self,
+        policy: &RetentionPolicy,
+    ) -> Result<usize, AkdError> {
+        if self.role() == StorageRole::Follower {
+            return Err(AkdError::Directory(DirectoryError::ReadOnlyDirectory(
+                "A follower cannot tombstone values, since only the leader writes to the storage"
+                    .to_string(),
+            )));
+        }
+        self.prune_values(policy).await
+    }
+
+    /// Runs a single [MaintenanceTask] against the directory, returning a report of its outcome.
+    /// An [MaintenanceTask::IntegrityCheck] which finds a node whose hash does not match the
+    /// hashes of its children fails with a [DirectoryError::Integrity] error.
+    ///
+    /// Note that values which have been tombstoned by [MaintenanceTask::Pruning] can no longer
+    /// be replayed by [Directory::rebuild_from_values] or [Directory::rollback_to].
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(task = ?task)))]
+    pub async fn run_maintenance_task(
+        &self,
+        task: MaintenanceTask,
+    ) -> Result<MaintenanceReport, AkdError> {
+        let tic = Instant::now();
+        let records = match task {
+            MaintenanceTask::Compaction => self.storage.evict_expired_cache_items(),
+            MaintenanceTask::CacheWarmup { depth } => self.warm_cache(depth).await?,
+            MaintenanceTask::Pruning { retain_epochs } => {
+                self.prune_values(&RetentionPolicy::new(retain_epochs))
+                    .await?
+            }
+            MaintenanceTask::IntegrityCheck { sample_size } => {
+                self.check_integrity(sample_size).await?
+            }
+            MaintenanceTask::Consolidation => self.consolidate().await?.map_or(0, |audit_epoch| {
+                (audit_epoch.end_epoch - audit_epoch.start_epoch) as usize
+            }),
+            MaintenanceTask::Prefetch => self.prefetch().await?,
+        };
+        Ok(MaintenanceReport {
+            task,
+            records,
+            duration: tic.elapsed(),
+        })
+    }
+
+    /// Starts running the scheduled maintenance tasks of the config in the background, until
+    /// the returned [MaintenanceHandle] is shut down or dropped. Failed tasks are logged, and
+    /// retried on their next interval.
+    ///
+    /// This must be called from within a tokio runtime.
+    pub fn spawn_maintenance(&self, config: MaintenanceConfig) -> MaintenanceHandle
+    where
+        V: 'static,
+    {
+        let directory = self.clone();
+        maintenance::spawn(config, move |task| {
+            let directory = directory.clone();
+            async move { directory.run_maintenance_task(task).await }
+        })
+    }
+
+    /// Loads the nodes in the top `depth` levels of the tree into the cache,
+    /// returning the number of nodes loaded
+    async fn warm_cache(&self, depth: u8) -> Result<usize, AkdError> {
+        if !self.storage.has_cache() {
+            return Ok(0);
+        }
+        let _guard = self.cache_lock.read().await;
+        let epoch = self.retrieve_azks().await?.get_latest_epoch();
+
+        let mut num_loaded = 0;
+        let mut keys = vec![NodeKey(NodeLabel::root())];
+        for _ in 0..=depth {
+            if keys.is_empty() {
+                break;
+            }
+            let nodes = TreeNode::batch_get_from_storage(&self.storage, &keys, epoch).await?;
+            num_loaded += nodes.len();
+            keys = nodes
+                .iter()
+                .flat_map(|node| [node.left_child, node.right_child])
+                .flatten()
+                .map(NodeKey)
+                .collect();
+        }
+        Ok(num_loaded)
+    }
+
+    /// Tombstones the values of all versions which were published more than the number of
+    /// epochs retained by the policy for their label before the latest epoch, except for the
+    /// latest version of each label. The retention class of a label takes precedence over the
+    /// policy, unless it is [RetentionClass::Standard]. Returns the number of values tombstoned.
+    async fn prune_values(&self, policy: &RetentionPolicy) -> Result<usize, AkdError> {
+        let latest_epoch = self.retrieve_azks().await?.get_latest_epoch();
+        let cutoff = |retain_epochs: Option<u64>| {
+            retain_epochs.and_then(|retain_epochs| latest_epoch.checked_sub(retain_epochs))
+        };
+        let default_cutoff = cutoff(Some(policy.retain_epochs));
+        let cutoffs = policy
+            .overrides
+            .iter()
+            .map(|(label, retain_epochs)| {
+                (normalize_label::<TC>(label.clone()), cutoff(*retain_epochs))
+            })
+            .collect::<HashMap<_, _>>();
+
+        // The states are streamed twice, first to find the latest epoch and the retention
+        // class of each label, and then to find the states to tombstone, so that only the
+        // tombstones are held in memory
+        let mut latest_epochs = HashMap::<AkdLabel, u64>::new();
+        let mut retention_classes = HashMap::new();
+        let mut states = self.storage.iter_user_states();
+        while let Some(state) = states.try_next().await? {
+            track_retention_class(&mut retention_classes, &state);
+            let latest = latest_epochs.entry(state.username).or_default();
+            *latest = (*latest).max(state.epoch);
+        }
+
+        let commitment_key = self.derive_commitment_key().await?;
+        let mut tombstones = Vec::new();
+        let mut states = self.storage.iter_user_states();
+        while let Some(state) = states.try_next().await? {
+            let retention_class = retention_classes
+                .get(&state.username)
+                .map(|(_, retention_class)| *retention_class)
+                .unwrap_or_default();
+            let cutoff_epoch = match retention_class {
+                RetentionClass::Standard => cutoffs
+                    .get(&state.username)
+                    .copied()
+                    .unwrap_or(default_cutoff),
+                RetentionClass::Ephemeral => Some(latest_epoch),
+                RetentionClass::LegalHold => None,
+            };
+            if cutoff_epoch.is_none_or(|cutoff_epoch| state.epoch > cutoff_epoch)
+                || state.epoch >= latest_epochs[&state.username]
+                || TC::is_tombstone(&state.value)
+            {
+                continue;
+            }
+            let metadata = TombstoneMetadata {
+                tombstoned_at: latest_epoch,
+                commitment: TC::compute_fresh_azks_value(
+                    &commitment_key,
+                    &state.label,
+                    state.version,
+                    &state.value,
+                ),
+            };
+            tombstones.push(DbRecord::ValueState(ValueState {
+                value: TC::tombstone_value(&metadata),
+                ..state
+            }));
+        }
+        let num_tombstoned = tombstones.len();
+        let cutoff_epoch = default_cutoff.unwrap_or_default();
+        if num_tombstoned > 0 {
+            info!(
+                batch_size = num_tombstoned,
+                cutoff_epoch,
+                num_overrides = cutoffs.len(),
+                "Pruning values published before the cutoff epoch"
+            );
+            let mut next_operation = self.next_operation.lock().await;
+            let operation = self
+                .operation_record(
+                    &mut next_operation,
+                    OperationKind::Prune,
+                    latest_epoch,
+                    format!(
+                        "Tombstoned {num_tombstoned} values published by epoch {cutoff_epoch}, with {} per-label overrides",
+                        cutoffs.len()
+                    ),
+                )
+                .await?;
+            let operation_sequence = operation.sequence;
+            let mut records = tombstones;
+            records.push(DbRecord::OperationRecord(operation));
+            self.storage.batch_set(records).await?;
+            *next_operation = Some(operation_sequence + 1);
+        }
+        self.notify_hooks(|hook| hook.on_prune_completed(cutoff_epoch, num_tombstoned));
+        Ok(num_tombstoned)
+    }
+
+    /// Walks `sample_size` random paths from the root of the tree down to a leaf, checking
+    /// that the hash of each interior node matches the hashes of its children. Returns the
+    /// number of nodes checked.
+    async fn check_integrity(&self, sample_size: usize) -> Result<usize, AkdError> {
+        let _guard = self.cache_lock.read().await;
+        let epoch = self.retrieve_azks().await?.get_latest_epoch();
+        let root =
+            TreeNode::get_from_storage(&self.storage, &NodeKey(NodeLabel::root()), epoch).await?;
+
+        let mut num_checked = 0;
+        for _ in 0..sample_size {
+            let mut node = root.clone();
+            while node.left_child.is_some() || node.right_child.is_some() {
+                let mut recomputed = node.clone();
+                recomputed
+                    .update_hash::<TC, _>(&self.storage, NodeHashingMode::WithLeafEpoch)
+                    .await?;
+                num_checked += 1;
+                if recomputed.hash != node.hash {
+                    return Err(self.integrity_violation(format!(
+                        "The hash of node {:?} at epoch {} does not match the hashes of its children",
+                        node.label, node.last_epoch
+                    )));
+                }
+
+                let direction = match (node.left_child, node.right_child) {
+                    (Some(_), Some(_)) if maintenance::random_u64().is_multiple_of(2) => {
+                        Direction::Left
+                    }
+                    (Some(_), None) => Direction::Left,
+                    _ => Direction::Right,
+                };
+                node = match node
+                    .get_child_node(&self.storage, direction, node.last_epoch)
+                    .await?
+                {
+                    Some(child) => child,
+                    None => {
+                        return Err(self.integrity_violation(format!(
+                            "The {direction:?} child of node {:?} at epoch {} is missing",
+                            node.label, node.last_epoch
+                        )))
+                    }
+                };
+            }
+        }
+        Ok(num_checked)
+    }
+
+    /// Rebuilds the tree in a separate in-memory storage layer, by replaying the insertions
+    /// of the stored value states for each epoch up to and including `through_epoch` in order
+    async fn replay_value_states(
+        &self,
+        through_epoch: u64,
+    ) -> Result<(Azks, StorageManager<AsyncInMemoryDatabase>), AkdError> {
+        // Group the value states by the epoch in which they were published
+        let mut states_by_epoch = BTreeMap::<u64, Vec<ValueState>>::new();
+        let mut states = self.storage.iter_user_states();
+        while let Some(state) = states.try_next().await? {
+            if state.epoch <= through_epoch {
+                states_by_epoch.entry(state.epoch).or_default().push(state);
+            }
+        }
+
+        let commitment_key = self.derive_commitment_key().await?;
+        let rebuild_storage = StorageManager::new_no_cache(AsyncInMemoryDatabase::new());
+        let mut rebuilt_azks = Azks::new::<TC, _>(&rebuild_storage).await?;
+        for (epoch, states) in states_by_epoch {
+            let stale_computations = states
+                .iter()
+                .filter(|state| state.version > 1)
+                .map(|state| {
+                    (
+                        state.username.clone(),
+                        VersionFreshness::Stale,
+                        state.version - 1,
+                        state.value.clone(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let mut insertions = self
+                .vrf
+                .get_node_labels::<TC>(&stale_computations)
+                .await?
+                .into_iter()
+                .map(|(_, label)| AzksElement {
+                    label,
+                    value: TC::stale_azks_value(),
+                })
+                .collect::<Vec<_>>();
+
+            for state in states {
+                if TC::is_tombstone(&state.value) {
+                    return Err(AkdError::Directory(DirectoryError::Rebuild(format!(
+                        "The value of version {} of label {} has been tombstoned",
+                        state.version,
+                        instrumentation::label_hash_prefix::<TC>(&state.username)
+                    ))));
+                }
+                insertions.push(AzksElement {
+                    label: state.label,
+                    value: TC::compute_fresh_azks_value(
+                        &commitment_key,
+                        &state.label,
+                        state.version,
+                        &state.value,
+                    ),
+                });
+            }
+
+            rebuilt_azks.latest_epoch = epoch - 1;
+            rebuilt_azks
+                .batch_insert_nodes::<TC, _>(&rebuild_storage, insertions, InsertMode::Directory)
+                .await?;
+        }
+        rebuilt_azks.latest_epoch = through_epoch;
 
-    /// Retrieves the [Azks]
-    pub(crate) async fn retrieve_azks(&self) -> Result<Azks, crate::errors::AkdError> {
-        Directory::<TC, S, V>::get_azks_from_storage(&self.storage, false).await
+        Ok((rebuilt_azks, rebuild_storage))
     }
+}
 
-    async fn get_azks_from_storage(
-        storage: &StorageManager<S>,
-        ignore_cache: bool,
-    ) -> Result<Azks, crate::errors::AkdError> {
-        let got = if ignore_cache {
-            storage
-                .get_direct::<Azks>(&crate::append_only_zks::DEFAULT_AZKS_KEY)
-                .await?
-        } else {
-            storage
-                .get::<Azks>(&crate::append_only_zks::DEFAULT_AZKS_KEY)
-                .await?
-        };
-        match got {
-            DbRecord::Azks(azks) => Ok(azks),
-            _ => {
-                error!("No AZKS can be found. You should re-initialize the directory to create a new one");
-                Err(AkdError::Storage(StorageError::NotFound(
-                    "AZKS not found".to_string(),
-                )))
-            }
+/// A builder for a [Directory], created with [Directory::builder]. The storage manager and
+/// VRF are required, and the other options are applied to the directory once it has been
+/// created, as with the corresponding methods of [Directory], so that new options can be
+/// added without changing the callers which do not use them.
+///
+/// ```
+/// use akd::directory::{Directory, Operator};
+/// use akd::ecvrf::HardCodedAkdVRF;
+/// use akd::storage::memory::AsyncInMemoryDatabase;
+/// use akd::storage::StorageManager;
+///
+/// type Config = akd::WhatsAppV1Configuration;
+///
+/// # tokio_test::block_on(async {
+/// let akd = Directory::<Config, _, _>::builder()
+///     .storage(StorageManager::new_no_cache(AsyncInMemoryDatabase::new()))
+///     .vrf(HardCodedAkdVRF {})
+///     .operator(Operator {
+///         identity: "publisher".to_string(),
+///         metadata: String::new(),
+///     })
+///     .build()
+///     .await
+///     .expect("Could not create a new directory");
+/// # });
+/// ```
+pub struct DirectoryBuilder<TC, S: Database, V> {
+    storage: Option<StorageManager<S>>,
+    vrf: Option<V>,
+    read_only: bool,
+    publish_lease: Option<PublishLeaseOptions>,
+    operator: Option<Operator>,
+    hooks: Vec<Arc<dyn DirectoryHooks>>,
+    hot_labels: Vec<AkdLabel>,
+    proof_cache_capacity: usize,
+    coalesce_lookups: bool,
+    prefetching: Option<PrefetchConfig>,
+    epoch_source: Option<Arc<dyn EpochSource>>,
+    tc: PhantomData<TC>,
+}
+
+impl<TC, S: Database, V> Default for DirectoryBuilder<TC, S, V> {
+    fn default() -> Self {
+        Self {
+            storage: None,
+            vrf: None,
+            read_only: false,
+            publish_lease: None,
+            operator: None,
+            hooks: vec![],
+            hot_labels: vec![],
+            proof_cache_capacity: 0,
+            coalesce_lookups: false,
+            prefetching: None,
+            epoch_source: None,
+            tc: PhantomData,
         }
     }
+}
 
-    /// HELPERS ///
+impl<TC, S, V> DirectoryBuilder<TC, S, V>
+where
+    TC: Configuration,
+    S: Database + 'static,
+    V: VRFKeyStorage,
+{
+    /// Sets the storage manager of the directory, which is required
+    pub fn storage(mut self, storage: StorageManager<S>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
 
-    /// Use this function to retrieve the [VRFPublicKey] for this AKD.
-    pub async fn get_public_key(&self) -> Result<VRFPublicKey, AkdError> {
-        Ok(self.vrf.get_vrf_public_key().await?)
+    /// Sets the VRF of the directory, which is required
+    pub fn vrf(mut self, vrf: V) -> Self {
+        self.vrf = Some(vrf);
+        self
     }
 
-    async fn create_single_update_proof(
-        &self,
-        akd_label: &AkdLabel,
-        user_state: &ValueState,
-    ) -> Result<UpdateProof, AkdError> {
-        let epoch = user_state.epoch;
-        let value = &user_state.value;
-        let version = user_state.version;
+    /// Whether the directory is read-only, in which case it is a follower of the directory
+    /// which writes to the storage (see [StorageRole::Follower]), and cannot be created
+    /// before that directory has created the tree. Defaults to false.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
 
-        let label_at_ep = self
-            .vrf
-            .get_node_label::<TC>(akd_label, VersionFreshness::Fresh, version)
-            .await?;
+    /// Requires each publish to hold the database's publish lease, as with
+    /// [StorageManager::with_publish_lease]
+    pub fn publish_lease(mut self, options: PublishLeaseOptions) -> Self {
+        self.publish_lease = Some(options);
+        self
+    }
 
-        let current_azks = self.retrieve_azks().await?;
-        let existence_vrf = self
-            .vrf
-            .get_label_proof::<TC>(akd_label, VersionFreshness::Fresh, version)
-            .await?;
-        let existence_vrf_proof = existence_vrf.to_bytes().to_vec();
-        let existence_label = self.vrf.get_node_label_from_vrf_proof(existence_vrf).await;
-        let existence_proof = current_azks
-            .get_membership_proof::<TC, _>(&self.storage, label_at_ep)
-            .await?;
-        let mut previous_version_proof = Option::None;
-        let mut previous_version_vrf_proof = Option::None;
-        if version > 1 {
-            let prev_label_at_ep = self
-                .vrf
-                .get_node_label::<TC>(akd_label, VersionFreshness::Stale, version - 1)
-                .await?;
-            previous_version_proof = Option::Some(
-                current_azks
-                    .get_membership_proof::<TC, _>(&self.storage, prev_label_at_ep)
-                    .await?,
-            );
-            previous_version_vrf_proof = Option::Some(
-                self.vrf
-                    .get_label_proof::<TC>(akd_label, VersionFreshness::Stale, version - 1)
-                    .await?
-                    .to_bytes()
-                    .to_vec(),
-            );
-        }
+    /// Sets the operator recorded in the operations log, as with [Directory::set_operator]
+    pub fn operator(mut self, operator: Operator) -> Self {
+        self.operator = Some(operator);
+        self
+    }
 
-        let commitment_key = self.derive_commitment_key().await?;
-        let commitment_nonce =
-            TC::get_commitment_nonce(&commitment_key, &existence_label, version, value).to_vec();
+    /// Registers a hook, as with [Directory::register_hook]. Hooks are registered in the
+    /// order in which they are given.
+    pub fn hook(mut self, hook: impl DirectoryHooks + 'static) -> Self {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
 
-        Ok(UpdateProof {
-            epoch,
-            version,
-            value: value.clone(),
-            existence_vrf_proof,
-            existence_proof,
-            previous_version_vrf_proof,
-            previous_version_proof,
-            commitment_nonce,
-        })
+    /// Designates the hot labels of the directory, as with [Directory::set_hot_labels]
+    pub fn hot_labels(mut self, labels: Vec<AkdLabel>) -> Self {
+        self.hot_labels = labels;
+        self
     }
 
-    /// Gets the root hash at the current epoch.
-    pub async fn get_epoch_hash(&self) -> Result<EpochHash, AkdError> {
-        let current_azks = self.retrieve_azks().await?;
-        let latest_epoch = current_azks.get_latest_epoch();
-        let root_hash = current_azks.get_root_hash::<TC, _>(&self.storage).await?;
-        Ok(EpochHash(latest_epoch, root_hash))
+    /// Sets the maximum number of cached lookup proofs, as with
+    /// [Directory::set_proof_cache_capacity]
+    pub fn proof_cache_capacity(mut self, capacity: usize) -> Self {
+        self.proof_cache_capacity = capacity;
+        self
     }
 
-    // We simply hash the VRF private key to derive the commitment key
-    async fn derive_commitment_key(&self) -> Result<Digest, AkdError> {
-        let raw_key = self.vrf.retrieve().await?;
-        let commitment_key = TC::hash(&raw_key);
-        Ok(commitment_key)
+    /// Whether concurrent lookups of the same label are coalesced, as with
+    /// [Directory::set_lookup_coalescing]. Defaults to false.
+    pub fn coalesce_lookups(mut self, enabled: bool) -> Self {
+        self.coalesce_lookups = enabled;
+        self
+    }
+
+    /// Enables prefetching with the given config, as with [Directory::set_prefetching]
+    pub fn prefetching(mut self, config: PrefetchConfig) -> Self {
+        self.prefetching = Some(config);
+        self
+    }
+
+    /// Sets the source of the epochs of the directory's publishes, as with
+    /// [Directory::set_epoch_source]
+    pub fn epoch_source(mut self, source: impl EpochSource + 'static) -> Self {
+        self.epoch_source = Some(Arc::new(source));
+        self
+    }
+
+    /// Creates the directory, as with [Directory::new], and applies the options to it
+    pub async fn build(self) -> Result<Directory<TC, S, V>, AkdError>
+    where
+        V: 'static,
+    {
+        let missing = |option: &str| {
+            AkdError::Directory(DirectoryError::Builder(format!(
+                "The {option} of the directory is required"
+            )))
+        };
+        let mut storage = self.storage.ok_or_else(|| missing("storage manager"))?;
+        let vrf = self.vrf.ok_or_else(|| missing("VRF"))?;
+        if self.read_only {
+            storage = storage.with_role(StorageRole::Follower);
+        }
+        if let Some(options) = self.publish_lease {
+            storage = storage.with_publish_lease(options);
+        }
+
+        let directory = Directory::new(storage, vrf).await?;
+        if let Some(operator) = self.operator {
+            directory.set_operator(operator);
+        }
+        if let Ok(mut hooks) = directory.hooks.write() {
+            hooks.extend(self.hooks);
+        }
+        if let Some(source) = self.epoch_source {
+            if let Ok(mut current) = directory.epoch_source.write() {
+                *current = Some(source);
+            }
+        }
+        directory.set_proof_cache_capacity(self.proof_cache_capacity);
+        directory.set_lookup_coalescing(self.coalesce_lookups);
+        directory.set_prefetching(self.prefetching);
+        if !self.hot_labels.is_empty() {
+            directory.set_hot_labels(self.hot_labels).await?;
+        }
+        Ok(directory)
     }
 }
 
@@ -791,13 +4280,14 @@ where
     /// does not exist in the storage, or we're unable to retrieve it from storage, then
     /// a [DirectoryError] will be returned.
     pub async fn new(storage: StorageManager<S>, vrf: V) -> Result<Self, AkdError> {
+        let storage = storage.with_role(StorageRole::Follower);
         let azks = Directory::<TC, S, V>::get_azks_from_storage(&storage, false).await;
 
         if azks.is_err() {
             return Err(AkdError::Directory(DirectoryError::ReadOnlyDirectory(
                 format!(
                     "Cannot start directory in read-only mode when AZKS is missing, error: {:?}",
-                    azks.err().take()
+                    azks.err()
                 ),
             )));
         }
@@ -805,7 +4295,19 @@ where
         Ok(Self(Directory {
             storage,
             cache_lock: Arc::new(RwLock::new(())),
+            last_publish: Arc::new(std::sync::Mutex::new(None)),
+            operator: Arc::new(std::sync::RwLock::new(Operator::default())),
+            next_operation: Arc::new(tokio::sync::Mutex::new(None)),
+            hooks: Arc::new(std::sync::RwLock::new(vec![])),
+            hot_labels: Arc::new(std::sync::RwLock::new(HotLabels::default())),
+            hot_label_worker: Arc::new(std::sync::Mutex::new(None)),
+            proof_cache: Arc::new(std::sync::RwLock::new(ProofCache::default())),
+            lookup_flights: Arc::new(LookupFlights::default()),
+            prefetcher: Arc::new(std::sync::Mutex::new(None)),
+            epoch_source: Arc::new(std::sync::RwLock::new(None)),
             vrf,
+            #[cfg(feature = "profiling")]
+            timing_reports: Arc::new(std::sync::Mutex::new(HashMap::new())),
             tc: PhantomData,
         }))
     }
@@ -815,6 +4317,60 @@ where
         self.0.lookup(uname).await
     }
 
+    /// Read-only access to [Directory::set_hot_labels](Directory::set_hot_labels). Precomputing
+    /// the proofs of the hot labels does not write to the storage.
+    pub async fn set_hot_labels(&self, labels: Vec<AkdLabel>) -> Result<usize, AkdError>
+    where
+        V: 'static,
+    {
+        self.0.set_hot_labels(labels).await
+    }
+
+    /// Read-only access to [Directory::hot_labels](Directory::hot_labels).
+    pub fn hot_labels(&self) -> Vec<AkdLabel> {
+        self.0.hot_labels()
+    }
+
+    /// Read-only access to [Directory::set_proof_cache_capacity](Directory::set_proof_cache_capacity).
+    /// Caching proofs does not write to the storage.
+    pub fn set_proof_cache_capacity(&self, capacity: usize) {
+        self.0.set_proof_cache_capacity(capacity)
+    }
+
+    /// Read-only access to [Directory::cached_proofs](Directory::cached_proofs).
+    pub fn cached_proofs(&self) -> usize {
+        self.0.cached_proofs()
+    }
+
+    /// Read-only access to [Directory::set_lookup_coalescing](Directory::set_lookup_coalescing).
+    pub fn set_lookup_coalescing(&self, enabled: bool) {
+        self.0.set_lookup_coalescing(enabled)
+    }
+
+    /// Read-only access to [Directory::lookup_coalescing_metrics](Directory::lookup_coalescing_metrics).
+    pub fn lookup_coalescing_metrics(&self) -> LookupCoalescingMetrics {
+        self.0.lookup_coalescing_metrics()
+    }
+
+    /// Read-only access to [Directory::set_prefetching](Directory::set_prefetching).
+    pub fn set_prefetching(&self, config: Option<PrefetchConfig>) {
+        self.0.set_prefetching(config)
+    }
+
+    /// Read-only access to [Directory::prefetch](Directory::prefetch).
+    pub async fn prefetch(&self) -> Result<usize, AkdError> {
+        self.0.prefetch().await
+    }
+
+    /// Read-only access to [Directory::signed_lookup](Directory::signed_lookup).
+    pub async fn signed_lookup(
+        &self,
+        uname: AkdLabel,
+        signer: &impl EpochSigner,
+    ) -> Result<SignedLookupResponse, AkdError> {
+        self.0.signed_lookup(uname, signer).await
+    }
+
     /// Read-only access to [Directory::batch_lookup](Directory::batch_lookup).
     pub async fn batch_lookup(
         &self,
@@ -823,6 +4379,22 @@ where
         self.0.batch_lookup(unames).await
     }
 
+    /// Read-only access to [Directory::lookup_latest](Directory::lookup_latest).
+    pub async fn lookup_latest(
+        &self,
+        uname: AkdLabel,
+    ) -> Result<(LatestVersionProof, EpochHash), AkdError> {
+        self.0.lookup_latest(uname).await
+    }
+
+    /// Read-only access to [Directory::lookup_absent](Directory::lookup_absent).
+    pub async fn lookup_absent(
+        &self,
+        uname: AkdLabel,
+    ) -> Result<(NonExistenceProof, EpochHash), AkdError> {
+        self.0.lookup_absent(uname).await
+    }
+
     /// Read-only access to [Directory::key_history](Directory::key_history).
     pub async fn key_history(
         &self,
@@ -832,6 +4404,45 @@ where
         self.0.key_history(uname, params).await
     }
 
+    /// Read-only access to [Directory::linked_key_history](Directory::linked_key_history).
+    pub async fn linked_key_history(
+        &self,
+        uname: &AkdLabel,
+        params: HistoryParams,
+    ) -> Result<(LinkedHistoryProof, EpochHash), AkdError> {
+        self.0.linked_key_history(uname, params).await
+    }
+
+    /// Read-only access to [Directory::delta_sync](Directory::delta_sync).
+    pub async fn delta_sync(
+        &self,
+        since_epoch: u64,
+        unames: &[AkdLabel],
+    ) -> Result<DeltaSyncResponse, AkdError> {
+        self.0.delta_sync(since_epoch, unames).await
+    }
+
+    /// Read-only access to [Directory::aggregated_key_history](Directory::aggregated_key_history).
+    pub async fn aggregated_key_history(
+        &self,
+        uname: &AkdLabel,
+    ) -> Result<(AggregatedHistoryProof, EpochHash), AkdError> {
+        self.0.aggregated_key_history(uname).await
+    }
+
+    /// Read-only access to [Directory::selective_key_history](Directory::selective_key_history).
+    pub async fn selective_key_history(
+        &self,
+        uname: &AkdLabel,
+        params: HistoryParams,
+        disclosed_start_epoch: u64,
+        disclosed_end_epoch: u64,
+    ) -> Result<(SelectiveHistoryProof, EpochHash), AkdError> {
+        self.0
+            .selective_key_history(uname, params, disclosed_start_epoch, disclosed_end_epoch)
+            .await
+    }
+
     /// Read-only access to [Directory::poll_for_azks_changes](Directory::poll_for_azks_changes).
     pub async fn poll_for_azks_changes(
         &self,
@@ -850,17 +4461,115 @@ where
         self.0.audit(audit_start_ep, audit_end_ep).await
     }
 
+    /// Read-only access to [Directory::audit_multi_epoch](Directory::audit_multi_epoch).
+    pub async fn audit_multi_epoch(
+        &self,
+        audit_start_ep: u64,
+        audit_end_ep: u64,
+    ) -> Result<MultiEpochAppendOnlyProof, AkdError> {
+        self.0.audit_multi_epoch(audit_start_ep, audit_end_ep).await
+    }
+
+    /// Read-only access to [Directory::spot_check](Directory::spot_check).
+    pub async fn spot_check(
+        &self,
+        sample_size: u64,
+    ) -> Result<(SpotCheckProof, EpochHash), AkdError> {
+        self.0.spot_check(sample_size).await
+    }
+
+    /// Read-only access to [Directory::audit_epoch](Directory::audit_epoch).
+    pub async fn audit_epoch(&self, index: u64) -> Result<AuditEpoch, AkdError> {
+        self.0.audit_epoch(index).await
+    }
+
+    /// Read-only access to [Directory::latest_audit_epoch](Directory::latest_audit_epoch).
+    pub async fn latest_audit_epoch(&self) -> Result<Option<AuditEpoch>, AkdError> {
+        self.0.latest_audit_epoch().await
+    }
+
+    /// Read-only access to [Directory::audit_epoch_proof](Directory::audit_epoch_proof).
+    pub async fn audit_epoch_proof(
+        &self,
+        index: u64,
+    ) -> Result<MultiEpochAppendOnlyProof, AkdError> {
+        self.0.audit_epoch_proof(index).await
+    }
+
+    /// Read-only access to [Directory::mini_epoch_proof](Directory::mini_epoch_proof).
+    pub async fn mini_epoch_proof(&self, epoch: u64) -> Result<MiniEpochProof, AkdError> {
+        self.0.mini_epoch_proof(epoch).await
+    }
+
+    /// Read-only access to [Directory::health]. A read-only directory never publishes,
+    /// so its last publish should not be checked.
+    pub async fn health(&self, options: &HealthCheckOptions) -> HealthReport {
+        self.0.health(options).await
+    }
+
+    /// Read-only access to [Directory::visualize_tree].
+    pub async fn visualize_tree(
+        &self,
+        options: &TreeVisualizationOptions,
+    ) -> Result<TreeVisualization, AkdError> {
+        self.0.visualize_tree(options).await
+    }
+
     /// Read-only access to [Directory::get_epoch_hash].
     pub async fn get_epoch_hash(&self) -> Result<EpochHash, AkdError> {
         self.0.get_epoch_hash().await
     }
 
+    /// Read-only access to [Directory::operation_count].
+    pub async fn operation_count(&self) -> Result<u64, AkdError> {
+        self.0.operation_count().await
+    }
+
+    /// Read-only access to [Directory::operations].
+    pub async fn operations(
+        &self,
+        start: u64,
+        limit: usize,
+    ) -> Result<Vec<OperationRecord>, AkdError> {
+        self.0.operations(start, limit).await
+    }
+
     /// Read-only access to [Directory::get_public_key](Directory::get_public_key).
     pub async fn get_public_key(&self) -> Result<VRFPublicKey, AkdError> {
         self.0.get_public_key().await
     }
 }
 
+impl<TC, S, V> ReadOnlyDirectory<TC, S, V>
+where
+    TC: Configuration,
+    S: StorageUtil + 'static,
+    V: VRFKeyStorage,
+{
+    /// Read-only access to [Directory::get_anchored_root].
+    pub async fn get_anchored_root(&self) -> Result<AnchoredRoot, AkdError> {
+        self.0.get_anchored_root().await
+    }
+
+    /// Read-only access to [Directory::lookup_anchored].
+    pub async fn lookup_anchored(
+        &self,
+        uname: AkdLabel,
+    ) -> Result<(LookupProof, AnchoredRoot), AkdError> {
+        self.0.lookup_anchored(uname).await
+    }
+}
+
+/// The operator of a directory, whose identity and metadata are recorded in the operations
+/// log for each mutating operation (see [Directory::set_operator])
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Operator {
+    /// The identity of the operator, such as a user or service name
+    pub identity: String,
+    /// Metadata about the operations, such as a change ticket or the reason for the operations
+    pub metadata: String,
+}
+
 /// The parameters that dictate how much of the history proof to return to the consumer
 /// (either a complete history, or some limited form).
 #[derive(Copy, Clone)]
@@ -873,6 +4582,11 @@ pub enum HistoryParams {
     /// Returns all updates since a specified epoch (inclusive). This is not secure, and
     /// should not be used in a production environment.
     SinceEpochInsecure(u64),
+    /// Returns all updates made in the last E epochs, along with the most recent update
+    /// made before them. Unlike [HistoryParams::MostRecentInsecure], the returned proof can
+    /// be verified to contain every update within the window with
+    /// [crate::client::key_history_verify_recent_epochs].
+    RecentEpochs(u64),
 }
 
 impl Default for HistoryParams {
@@ -882,13 +4596,208 @@ impl Default for HistoryParams {
     }
 }
 
-/// Helpers
+/// The maximum rate at which a single label may be updated
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RateLimit {
+    /// The maximum number of updates to a label within any window of epochs
+    pub max_updates: u64,
+    /// The number of consecutive epochs in a window
+    pub window_epochs: u64,
+}
+
+/// How a publish handles updates which exceed its [PublishLimits]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum LimitEnforcement {
+    /// The publish fails if any of its updates exceed the limits
+    #[default]
+    Reject,
+    /// The updates which exceed the limits are dropped, and the remaining updates are published
+    Truncate,
+}
+
+/// The bytes which may appear in the labels of a publish
+pub use akd_core::BytePolicy;
+
+/// Limits on the updates to the labels of a directory, which protect the tree from abusive
+/// churn on single labels, and from pathological labels and values which bloat its storage
+/// and proofs
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct PublishLimits {
+    /// The maximum number of versions which may be published for a label
+    pub max_versions: Option<u64>,
+    /// The maximum rate at which a label may be updated
+    pub rate_limit: Option<RateLimit>,
+    /// The maximum length of a label, in bytes
+    pub max_label_bytes: Option<usize>,
+    /// The maximum length of a value, in bytes
+    pub max_value_bytes: Option<usize>,
+    /// The bytes which may appear in a label
+    pub label_bytes: BytePolicy,
+    /// How updates which exceed the limits are handled
+    pub enforcement: LimitEnforcement,
+}
+
+impl PublishLimits {
+    /// The constraints on labels of the limits, so that a server can reject labels with
+    /// [AkdLabel::new] as they are received, rather than when they are published
+    pub fn label_constraints(&self) -> ByteConstraints {
+        ByteConstraints {
+            allow_empty: true,
+            max_len: self.max_label_bytes,
+            bytes: self.label_bytes,
+        }
+    }
+
+    /// The constraints on values of the limits (see [PublishLimits::label_constraints])
+    pub fn value_constraints(&self) -> ByteConstraints {
+        ByteConstraints {
+            allow_empty: true,
+            max_len: self.max_value_bytes,
+            bytes: BytePolicy::Any,
+        }
+    }
+}
+
+/// An update which exceeded the [PublishLimits] of a publish
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum LimitViolation {
+    /// The label already has the maximum number of versions
+    MaxVersions {
+        /// The label of the update
+        label: AkdLabel,
+        /// The maximum number of versions for a label
+        max_versions: u64,
+    },
+    /// The label has already been updated the maximum number of times within the current window
+    RateLimit {
+        /// The label of the update
+        label: AkdLabel,
+        /// The rate limit which was exceeded
+        rate_limit: RateLimit,
+    },
+    /// The label is longer than the maximum length of a label
+    LabelTooLong {
+        /// The label of the update
+        label: AkdLabel,
+        /// The maximum length of a label, in bytes
+        max_label_bytes: usize,
+    },
+    /// The value is longer than the maximum length of a value
+    ValueTooLong {
+        /// The label of the update
+        label: AkdLabel,
+        /// The length of the value, in bytes
+        value_bytes: usize,
+        /// The maximum length of a value, in bytes
+        max_value_bytes: usize,
+    },
+    /// The label contains a byte which is not allowed by the [BytePolicy] of the limits
+    DisallowedLabelByte {
+        /// The label of the update
+        label: AkdLabel,
+        /// The index of the first disallowed byte in the label
+        index: usize,
+        /// The value of the first disallowed byte
+        byte: u8,
+    },
+}
+
+impl LimitViolation {
+    /// The label of the update which exceeded the limits
+    pub fn label(&self) -> &AkdLabel {
+        match self {
+            Self::MaxVersions { label, .. }
+            | Self::RateLimit { label, .. }
+            | Self::LabelTooLong { label, .. }
+            | Self::ValueTooLong { label, .. }
+            | Self::DisallowedLabelByte { label, .. } => label,
+        }
+    }
+
+    /// The name of the limit which was exceeded
+    pub fn limit(&self) -> &'static str {
+        match self {
+            Self::MaxVersions { .. } => "max_versions",
+            Self::RateLimit { .. } => "rate_limit",
+            Self::LabelTooLong { .. } => "max_label_bytes",
+            Self::ValueTooLong { .. } => "max_value_bytes",
+            Self::DisallowedLabelByte { .. } => "label_bytes",
+        }
+    }
+}
+
+/// Returns the violation of the length and byte limits by an update, if any
+fn check_update_contents(
+    label: &AkdLabel,
+    value: &AkdValue,
+    limits: &PublishLimits,
+) -> Option<LimitViolation> {
+    if let Some(max_label_bytes) = limits.max_label_bytes {
+        if label.len() > max_label_bytes {
+            return Some(LimitViolation::LabelTooLong {
+                label: label.clone(),
+                max_label_bytes,
+            });
+        }
+    }
+    if let Some((index, byte)) = limits.label_bytes.first_disallowed(label) {
+        return Some(LimitViolation::DisallowedLabelByte {
+            label: label.clone(),
+            index,
+            byte,
+        });
+    }
+    if let Some(max_value_bytes) = limits.max_value_bytes {
+        if value.len() > max_value_bytes {
+            return Some(LimitViolation::ValueTooLong {
+                label: label.clone(),
+                value_bytes: value.len(),
+                max_value_bytes,
+            });
+        }
+    }
+    None
+}
+
+/// Records the retention class assigned at a value state, given the value states of the labels
+/// in any order, so that the class assigned at the latest value state of each label which
+/// assigns one is kept along with its version
+fn track_retention_class(
+    retention_classes: &mut HashMap<AkdLabel, (u64, RetentionClass)>,
+    state: &ValueState,
+) {
+    let Some(retention_class) = state.retention_class else {
+        return;
+    };
+    match retention_classes.get(&state.username) {
+        Some((version, _)) if *version > state.version => {}
+        _ => {
+            retention_classes.insert(state.username.clone(), (state.version, retention_class));
+        }
+    }
+}
+
+fn node_label_collision(label: &NodeLabel) -> AkdError {
+    AkdError::Directory(DirectoryError::Publish(format!(
+        "The node label {label} of an update collides with another node label, \
+        as node labels are truncated to {} bits",
+        label.label_len
+    )))
+}
 
-pub(crate) fn get_marker_version(version: u64) -> u64 {
-    (64 - version.leading_zeros() - 1).into()
+/// The outcome of [Directory::publish_with_limits]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PublishResult {
+    /// The epoch and root hash of the directory after the publish
+    pub epoch_hash: EpochHash,
+    /// The updates which exceeded the limits, and so were not published
+    pub violations: Vec<LimitViolation>,
+    /// The labels of the updates whose values were already the current values of the labels,
+    /// which were skipped rather than published as new versions
+    pub skipped: Vec<AkdLabel>,
 }
 
-/// Helpers for testing
+// Helpers for testing
 
 /// This enum is meant to insert corruptions into a malicious publish function.
 #[derive(Debug, Clone)]
@@ -949,9 +4858,10 @@ impl<TC: Configuration, S: Database + 'static, V: VRFKeyStorage> Directory<TC, S
             .await?;
 
         info!(
-            "Retrieved {} previous user versions of {} requested",
-            all_user_versions_retrieved.len(),
-            keys.len()
+            epoch = current_epoch,
+            num_retrieved = all_user_versions_retrieved.len(),
+            num_requested = keys.len(),
+            "Retrieved previous user versions"
         );
 
         let commitment_key = self.derive_commitment_key().await?;
@@ -1033,19 +4943,26 @@ impl<TC: Configuration, S: Database + 'static, V: VRFKeyStorage> Directory<TC, S
         let azks_element_set: Vec<AzksElement> = update_set.to_vec();
 
         if azks_element_set.is_empty() {
-            info!("After filtering for duplicated user information, there is no publish which is necessary (0 updates)");
+            info!(
+                epoch = current_epoch,
+                "After filtering for duplicated user information, there is no publish which is necessary (0 updates)"
+            );
             // The AZKS has not been updated/mutated at this point, so we can just return the root hash from before
             let root_hash = current_azks.get_root_hash::<TC, _>(&self.storage).await?;
             return Ok(EpochHash(current_epoch, root_hash));
         }
 
-        if let false = self.storage.begin_transaction() {
+        if !self.storage.begin_transaction() {
             error!("Transaction is already active");
             return Err(AkdError::Storage(StorageError::Transaction(
                 "Transaction is already active".to_string(),
             )));
         }
-        info!("Starting database insertion");
+        info!(
+            epoch = next_epoch,
+            batch_size = azks_element_set.len(),
+            "Starting database insertion"
+        );
 
         current_azks
             .batch_insert_nodes::<TC, _>(&self.storage, azks_element_set, InsertMode::Directory)
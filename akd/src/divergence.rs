@@ -0,0 +1,305 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Detection of the divergence left behind when two publishers write to the same storage.
+//!
+//! Conditional commits and the publish lease (see `StorageManager::with_publish_lease`) keep
+//! two publishers from committing the same epoch, but a directory whose storage does not
+//! support them, or which was written by a publisher running without them, may have been
+//! written by two publishers at once. `Directory::detect_divergence` (or
+//! `Directory::new_checked`, on startup) compares the head of the directory, its stored
+//! records, and the root hash recomputed from its values against the operations log, and
+//! lists every inconsistency as a [Divergence] in a [DivergenceReport], along with the
+//! exact records involved, so that an operator can decide how to recover (for instance with
+//! `Directory::rollback_to` to the last consistent epoch).
+
+use crate::storage::types::{OperationKind, OperationRecord, ValueState};
+use crate::tree_node::NodeKey;
+use crate::{AkdLabel, Digest};
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// An inconsistency between the stored state of a directory and its operations log
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Divergence {
+    /// The head of the directory is not at the epoch of the last entry of the operations log
+    HeadMismatch {
+        /// The epoch of the head of the directory
+        head_epoch: u64,
+        /// The epoch of the directory after the last logged operation
+        logged_epoch: u64,
+    },
+    /// Entries are missing from the operations log, which has entries after them
+    MissingOperations {
+        /// The sequence numbers of the missing entries
+        sequences: Vec<u64>,
+    },
+    /// A publish was logged at an epoch other than the one following the previous operation,
+    /// as happens when two publishers each log a publish of the same epoch
+    ConflictingPublish {
+        /// The epoch which the publish was expected to produce
+        expected_epoch: u64,
+        /// The logged publish
+        operation: OperationRecord,
+        /// The previously logged operation
+        previous: OperationRecord,
+    },
+    /// Value states were written for an epoch after the head of the directory, by a publish
+    /// which did not commit
+    OrphanedValueStates {
+        /// The orphaned value states
+        states: Vec<ValueState>,
+    },
+    /// Tree nodes were last modified at an epoch after the head of the directory, by a
+    /// publish which did not commit
+    OrphanedTreeNodes {
+        /// The keys of the orphaned nodes, with the epoch at which each was last modified
+        nodes: Vec<(NodeKey, u64)>,
+    },
+    /// Several value states were written for the same version of a label
+    DuplicateVersion {
+        /// The label
+        label: AkdLabel,
+        /// The duplicated version
+        version: u64,
+        /// The value states with that version
+        states: Vec<ValueState>,
+    },
+    /// The root hash of the stored tree does not match the root hash of the tree rebuilt from
+    /// the stored values
+    RootHashMismatch {
+        /// The epoch of the head of the directory
+        epoch: u64,
+        /// The root hash of the stored tree
+        stored: Digest,
+        /// The root hash of the tree rebuilt from the stored values
+        rebuilt: Digest,
+    },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HeadMismatch {
+                head_epoch,
+                logged_epoch,
+            } => write!(
+                f,
+                "The head of the directory is at epoch {head_epoch}, but the operations log ends at epoch {logged_epoch}"
+            ),
+            Self::MissingOperations { sequences } => write!(
+                f,
+                "The operations log is missing the entries with sequence numbers {sequences:?}"
+            ),
+            Self::ConflictingPublish {
+                expected_epoch,
+                operation,
+                previous,
+            } => write!(
+                f,
+                "Operation {} by {:?} published epoch {}, but epoch {expected_epoch} follows operation {} by {:?}",
+                operation.sequence,
+                operation.identity,
+                operation.epoch,
+                previous.sequence,
+                previous.identity
+            ),
+            Self::OrphanedValueStates { states } => write!(
+                f,
+                "{} value states were written after the head of the directory",
+                states.len()
+            ),
+            Self::OrphanedTreeNodes { nodes } => write!(
+                f,
+                "{} tree nodes were modified after the head of the directory",
+                nodes.len()
+            ),
+            Self::DuplicateVersion {
+                label,
+                version,
+                states,
+            } => write!(
+                f,
+                "Version {version} of label {label:?} was written {} times",
+                states.len()
+            ),
+            Self::RootHashMismatch {
+                epoch,
+                stored,
+                rebuilt,
+            } => write!(
+                f,
+                "The stored root hash {} at epoch {epoch} does not match the root hash {} rebuilt from the stored values",
+                hex::encode(stored),
+                hex::encode(rebuilt)
+            ),
+        }
+    }
+}
+
+/// The inconsistencies found between the stored state of a directory and its operations log
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DivergenceReport {
+    /// The epoch of the head of the directory
+    pub head_epoch: u64,
+    /// The number of entries in the operations log
+    pub num_operations: u64,
+    /// Whether the root hash was checked against a rebuild of the tree from the stored values,
+    /// which is not possible once values have been tombstoned
+    pub root_hash_checked: bool,
+    /// The inconsistencies which were found
+    pub divergences: Vec<Divergence>,
+}
+
+impl DivergenceReport {
+    /// Returns whether no inconsistencies were found
+    pub fn is_consistent(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+impl fmt::Display for DivergenceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} divergences at epoch {} ({} logged operations)",
+            self.divergences.len(),
+            self.head_epoch,
+            self.num_operations
+        )?;
+        for divergence in self.divergences.iter() {
+            write!(f, "\n- {divergence}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the head of a directory with its operations log, given in any order
+pub(crate) fn check_operations_log(
+    head_epoch: u64,
+    mut operations: Vec<OperationRecord>,
+) -> Vec<Divergence> {
+    operations.sort_by_key(|operation| operation.sequence);
+    let mut divergences = vec![];
+
+    let first = operations.first().map_or(0, |first| first.sequence);
+    let missing = (0..first)
+        .chain(
+            operations
+                .windows(2)
+                .flat_map(|pair| pair[0].sequence + 1..pair[1].sequence),
+        )
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        divergences.push(Divergence::MissingOperations { sequences: missing });
+    }
+
+    for pair in operations.windows(2) {
+        let (previous, operation) = (&pair[0], &pair[1]);
+        let expected_epoch = previous.epoch + 1;
+        if operation.kind == OperationKind::Publish && operation.epoch != expected_epoch {
+            divergences.push(Divergence::ConflictingPublish {
+                expected_epoch,
+                operation: operation.clone(),
+                previous: previous.clone(),
+            });
+        }
+    }
+
+    if let Some(last) = operations.last() {
+        if last.epoch != head_epoch {
+            divergences.push(Divergence::HeadMismatch {
+                head_epoch,
+                logged_epoch: last.epoch,
+            });
+        }
+    }
+    divergences
+}
+
+/// Finds the value states written after the head of a directory, and the versions of a label
+/// which were written more than once
+pub(crate) fn check_value_states(head_epoch: u64, states: Vec<ValueState>) -> Vec<Divergence> {
+    let mut orphaned = vec![];
+    let mut versions = BTreeMap::<(AkdLabel, u64), Vec<ValueState>>::new();
+    for state in states {
+        if state.epoch > head_epoch {
+            orphaned.push(state);
+        } else {
+            versions
+                .entry((state.username.clone(), state.version))
+                .or_default()
+                .push(state);
+        }
+    }
+
+    let mut divergences = vec![];
+    if !orphaned.is_empty() {
+        orphaned.sort_by(|a, b| (a.epoch, &a.username).cmp(&(b.epoch, &b.username)));
+        divergences.push(Divergence::OrphanedValueStates { states: orphaned });
+    }
+    for ((label, version), mut states) in versions {
+        if states.len() > 1 {
+            states.sort_by_key(|state| state.epoch);
+            divergences.push(Divergence::DuplicateVersion {
+                label,
+                version,
+                states,
+            });
+        }
+    }
+    divergences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn operation(sequence: u64, kind: OperationKind, epoch: u64) -> OperationRecord {
+        OperationRecord {
+            sequence,
+            kind,
+            epoch,
+            timestamp_ms: 0,
+            identity: String::new(),
+            metadata: String::new(),
+            detail: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_check_operations_log() {
+        // prunes and rebuilds keep the epoch, and rollbacks lower it
+        let log = vec![
+            operation(0, OperationKind::Publish, 1),
+            operation(1, OperationKind::Prune, 1),
+            operation(2, OperationKind::Publish, 2),
+            operation(3, OperationKind::Rollback, 1),
+            operation(4, OperationKind::Publish, 2),
+        ];
+        assert!(check_operations_log(2, log.clone()).is_empty());
+        assert!(check_operations_log(0, vec![]).is_empty());
+
+        let mut gapped = log.clone();
+        gapped.remove(3);
+        gapped.remove(0);
+        assert_eq!(
+            Divergence::MissingOperations {
+                sequences: vec![0, 3]
+            },
+            check_operations_log(2, gapped)[0]
+        );
+        assert_eq!(
+            vec![Divergence::HeadMismatch {
+                head_epoch: 3,
+                logged_epoch: 2
+            }],
+            check_operations_log(3, log)
+        );
+    }
+}
@@ -6,330 +6,340 @@
 // of this source tree. You may select, at your option, one of the above-listed licenses.
 
 //! Errors for various data structure operations.
-use core::fmt;
+//!
+//! Every error returned by the directory is an [AkdError], which wraps the error of the
+//! component it came from. Rather than matching on the variants (or the messages) of these
+//! errors, a serving layer can classify any of them with [AkdError::kind], which maps it to
+//! an [ErrorKind] that corresponds to an HTTP or gRPC status, and decide whether to retry the
+//! operation with [AkdError::is_retryable].
 
 use crate::node_label::NodeLabel;
 use crate::Direction;
+use akd_core::ecvrf::VrfError;
+use akd_core::verify::VerificationError;
+
+/// A classification of errors which is independent of the component they came from, with the
+/// HTTP and gRPC statuses each kind corresponds to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The requested data does not exist (HTTP 404, gRPC `NOT_FOUND`)
+    NotFound,
+    /// The request was malformed, or refers to an invalid epoch, version or option (HTTP 400,
+    /// gRPC `INVALID_ARGUMENT`)
+    InvalidArgument,
+    /// The request would create something which already exists (HTTP 409, gRPC
+    /// `ALREADY_EXISTS`)
+    AlreadyExists,
+    /// The operation raced with another, e.g. a concurrent publish, and may succeed if it is
+    /// retried (HTTP 409, gRPC `ABORTED`)
+    Conflict,
+    /// The operation is not permitted in the current state of the directory or of this
+    /// instance, e.g. a write to a follower (HTTP 412, gRPC `FAILED_PRECONDITION`)
+    FailedPrecondition,
+    /// A proof failed to verify (HTTP 422, gRPC `FAILED_PRECONDITION`)
+    Verification,
+    /// The storage layer could not be reached, and the operation may succeed if it is retried
+    /// (HTTP 503, gRPC `UNAVAILABLE`)
+    Unavailable,
+    /// The stored state of the directory is corrupt or inconsistent (HTTP 500, gRPC
+    /// `DATA_LOSS`)
+    Integrity,
+    /// Any other error (HTTP 500, gRPC `INTERNAL`)
+    Internal,
+}
+
+impl ErrorKind {
+    /// Whether an operation which failed with an error of this kind may succeed if it is
+    /// retried unchanged, which is the case for conflicts and unavailable storage
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Conflict | Self::Unavailable)
+    }
+}
 
 /// Symbolizes a AkdError, thrown by the akd.
 #[cfg_attr(test, derive(PartialEq, Eq))]
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum AkdError {
     /// Error propagation
-    TreeNode(TreeNodeError),
+    #[error("AKD Tree Node Error: {0}\n")]
+    TreeNode(#[from] TreeNodeError),
     /// Error propagation
-    Directory(DirectoryError),
+    #[error("AKD Directory Error: {0}\n")]
+    Directory(#[from] DirectoryError),
     /// Error propagation
-    AzksErr(AzksError),
+    #[error("AKD AZKS Error: {0}\n")]
+    AzksErr(#[from] AzksError),
     /// Vrf related error
-    Vrf(akd_core::ecvrf::VrfError),
+    #[error("AKD VRF Error: {0}\n")]
+    Vrf(#[from] VrfError),
     /// Storage layer error thrown
-    Storage(StorageError),
+    #[error("AKD Storage Error: {0}\n")]
+    Storage(#[from] StorageError),
     /// Audit verification error thrown
-    AuditErr(AuditorError),
+    #[error("AKD Auditor Error {0}\n")]
+    AuditErr(#[from] AuditorError),
     /// Parallelism/concurrency related errors
-    Parallelism(ParallelismError),
+    #[error("AKD Parallelism Error: {0}\n")]
+    Parallelism(#[from] ParallelismError),
     /// Test error
+    #[error("{0}\n")]
     TestErr(String),
 }
 
-impl std::error::Error for AkdError {}
-
-impl From<TreeNodeError> for AkdError {
-    fn from(error: TreeNodeError) -> Self {
-        Self::TreeNode(error)
-    }
-}
-
-impl From<StorageError> for AkdError {
-    fn from(error: StorageError) -> Self {
-        Self::Storage(error)
-    }
-}
-
-impl From<DirectoryError> for AkdError {
-    fn from(error: DirectoryError) -> Self {
-        Self::Directory(error)
-    }
-}
-
-impl From<akd_core::ecvrf::VrfError> for AkdError {
-    fn from(error: akd_core::ecvrf::VrfError) -> Self {
-        Self::Vrf(error)
-    }
-}
-
-impl From<AzksError> for AkdError {
-    fn from(error: AzksError) -> Self {
-        Self::AzksErr(error)
-    }
-}
-
-impl From<AuditorError> for AkdError {
-    fn from(error: AuditorError) -> Self {
-        Self::AuditErr(error)
+impl AkdError {
+    /// The kind of the error, which determines how it should be reported to callers
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::TreeNode(_) | Self::Parallelism(_) | Self::TestErr(_) => ErrorKind::Internal,
+            Self::Directory(err) => err.kind(),
+            Self::AzksErr(err) => err.kind(),
+            Self::Vrf(VrfError::Verification(_)) => ErrorKind::Verification,
+            Self::Vrf(_) => ErrorKind::Internal,
+            Self::Storage(err) => err.kind(),
+            Self::AuditErr(_) => ErrorKind::Verification,
+        }
     }
-}
 
-impl From<ParallelismError> for AkdError {
-    fn from(error: ParallelismError) -> Self {
-        Self::Parallelism(error)
+    /// Whether the operation which failed with this error may succeed if it is retried
+    /// unchanged (see [ErrorKind::is_retryable])
+    pub fn is_retryable(&self) -> bool {
+        self.kind().is_retryable()
     }
 }
 
-impl From<akd_core::verify::VerificationError> for AkdError {
-    fn from(err: akd_core::verify::VerificationError) -> Self {
+impl From<VerificationError> for AkdError {
+    fn from(err: VerificationError) -> Self {
         Self::Directory(err.into())
     }
 }
 
-impl std::fmt::Display for AkdError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        match self {
-            AkdError::TreeNode(err) => {
-                writeln!(f, "AKD Tree Node Error: {err}")
-            }
-            AkdError::Directory(err) => {
-                writeln!(f, "AKD Directory Error: {err}")
-            }
-            AkdError::AzksErr(err) => {
-                writeln!(f, "AKD AZKS Error: {err}")
-            }
-            AkdError::Vrf(err) => {
-                writeln!(f, "AKD VRF Error: {err}")
-            }
-            AkdError::Storage(err) => {
-                writeln!(f, "AKD Storage Error: {err}")
-            }
-            AkdError::AuditErr(err) => {
-                writeln!(f, "AKD Auditor Error {err}")
-            }
-            AkdError::Parallelism(err) => {
-                writeln!(f, "AKD Parallelism Error: {err}")
-            }
-            AkdError::TestErr(err) => {
-                writeln!(f, "{err}")
-            }
-        }
-    }
-}
-
 /// Errors thrown by TreeNodes
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, thiserror::Error)]
 pub enum TreeNodeError {
     /// At the moment the only supported dirs are 0, 1
+    #[error("AKD is based on a binary tree. No child with a given direction: {0:?}")]
     InvalidDirection(Direction),
     /// No direction provided for the node.
     /// Second parameter is the label of the child attempted to be set
     /// -- if there is one, otherwise it is None.
+    #[error(
+        "no direction provided for the node {0:?}{}",
+        .1.as_ref().map(|child| format!(" and child {child:?}")).unwrap_or_default()
+    )]
     NoDirection(NodeLabel, Option<NodeLabel>),
     /// The node didn't have a child in the given epoch
+    #[error("no node in direction {1:?} at epoch {0}")]
     NoChildAtEpoch(u64, Direction),
     /// The next epoch of this node's parent was invalid
+    #[error("Next epoch of parent is invalid, epoch = {0}")]
     ParentNextEpochInvalid(u64),
     /// The hash of a parent was attempted to be updated, without setting the calling node as a child.
+    #[error("Hash update in parent only allowed after node is inserted")]
     HashUpdateOrderInconsistent,
     /// The node did not exist at epoch
+    #[error("This node, labelled {0:?}, did not exist at epoch {1:?}.")]
     NonexistentAtEpoch(NodeLabel, u64),
     /// The state of a node did not exist at a given epoch
+    #[error("This node, labelled {0:?}, did not exist at epoch {1:?}.")]
     NoStateAtEpoch(NodeLabel, u64),
     /// Failed to deserialize a digest
+    #[error("Encountered a serialization error {0}")]
     DigestDeserializationFailed(String),
 }
 
-impl std::error::Error for TreeNodeError {}
-
-impl fmt::Display for TreeNodeError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::InvalidDirection(dir) => {
-                write!(
-                    f,
-                    "AKD is based on a binary tree. No child with a given direction: {dir:?}"
-                )
-            }
-            Self::NoDirection(node_label, child_label) => {
-                let mut to_print = format!("no direction provided for the node {node_label:?}");
-                // Add child info if given.
-                if let Some(child_label) = child_label {
-                    let child_str = format!(" and child {child_label:?}");
-                    to_print.push_str(&child_str);
-                }
-                write!(f, "{to_print}")
-            }
-            Self::NoChildAtEpoch(epoch, direction) => {
-                write!(f, "no node in direction {direction:?} at epoch {epoch}")
-            }
-            Self::ParentNextEpochInvalid(epoch) => {
-                write!(f, "Next epoch of parent is invalid, epoch = {epoch}")
-            }
-            Self::HashUpdateOrderInconsistent => {
-                write!(
-                    f,
-                    "Hash update in parent only allowed after node is inserted"
-                )
-            }
-            Self::NonexistentAtEpoch(label, epoch) => {
-                write!(
-                    f,
-                    "This node, labelled {label:?}, did not exist at epoch {epoch:?}."
-                )
-            }
-            Self::NoStateAtEpoch(label, epoch) => {
-                write!(
-                    f,
-                    "This node, labelled {label:?}, did not exist at epoch {epoch:?}."
-                )
-            }
-            Self::DigestDeserializationFailed(inner_error) => {
-                write!(f, "Encountered a serialization error {inner_error}")
-            }
-        }
-    }
-}
-
 /// An error thrown by the Azks data structure.
 #[cfg_attr(test, derive(PartialEq, Eq))]
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum AzksError {
     /// Membership proof did not verify
+    #[error("{0}")]
     VerifyMembershipProof(String),
     /// Append-only proof did not verify
+    #[error("Append only proof did not verify!")]
     VerifyAppendOnlyProof,
     /// Thrown when a place where an epoch is needed wasn't provided one.
+    #[error("An epoch was required but not supplied")]
     NoEpochGiven,
 }
 
-impl std::error::Error for AzksError {}
-
-impl fmt::Display for AzksError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl AzksError {
+    /// The kind of the error (see [AkdError::kind])
+    pub fn kind(&self) -> ErrorKind {
         match self {
-            Self::VerifyMembershipProof(error_string) => {
-                write!(f, "{error_string}")
-            }
-            Self::VerifyAppendOnlyProof => {
-                write!(f, "Append only proof did not verify!")
-            }
-            Self::NoEpochGiven => {
-                write!(f, "An epoch was required but not supplied")
-            }
+            Self::VerifyMembershipProof(_) | Self::VerifyAppendOnlyProof => ErrorKind::Verification,
+            Self::NoEpochGiven => ErrorKind::InvalidArgument,
         }
     }
 }
 
 /// The errors thrown by various algorithms in [crate::directory::Directory]
 #[cfg_attr(test, derive(PartialEq, Eq))]
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum DirectoryError {
     /// A verification error occurred
-    Verification(akd_core::verify::VerificationError),
+    #[error("Verification failure {0}")]
+    Verification(#[from] VerificationError),
     /// Tried to perform an operation on an invalid epoch or epoch range
+    #[error("Invalid epoch {0}")]
     InvalidEpoch(String),
     /// AZKS not found in read-only directory mode
+    #[error("Directory in read-only mode: {0}")]
     ReadOnlyDirectory(String),
     /// Publish
+    #[error("Directory publish error: {0}")]
     Publish(String),
+    /// A label was expected to be absent from the directory, but has been registered
+    #[error("Label {0} has been registered")]
+    LabelRegistered(String),
+    /// The tree could not be rebuilt from the stored value states
+    #[error("Directory rebuild error: {0}")]
+    Rebuild(String),
+    /// The stored state of the directory failed an integrity check
+    #[error("Directory integrity error: {0}")]
+    Integrity(String),
+    /// A root hash could not be anchored, or its anchor receipt failed to verify
+    #[error("Directory anchoring error: {0}")]
+    Anchoring(String),
+    /// The shards of a sharded directory are misconfigured or inconsistent
+    #[error("Directory sharding error: {0}")]
+    Sharding(String),
+    /// A value could not be redacted
+    #[error("Directory redaction error: {0}")]
+    Redaction(String),
+    /// A directory could not be built from the options given to its builder
+    #[error("Directory builder error: {0}")]
+    Builder(String),
 }
 
-impl std::error::Error for DirectoryError {}
-
-impl fmt::Display for DirectoryError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl DirectoryError {
+    /// The kind of the error (see [AkdError::kind])
+    pub fn kind(&self) -> ErrorKind {
         match self {
-            Self::Verification(err) => {
-                write!(f, "Verification failure {err}")
-            }
-            Self::InvalidEpoch(err_string) => {
-                write!(f, "Invalid epoch {err_string}")
+            Self::Verification(_) => ErrorKind::Verification,
+            Self::InvalidEpoch(_) | Self::Publish(_) | Self::Redaction(_) | Self::Builder(_) => {
+                ErrorKind::InvalidArgument
             }
-            Self::ReadOnlyDirectory(inner_message) => {
-                write!(f, "Directory in read-only mode: {inner_message}")
-            }
-            Self::Publish(inner_message) => {
-                write!(f, "Directory publish error: {inner_message}")
+            Self::LabelRegistered(_) => ErrorKind::AlreadyExists,
+            Self::ReadOnlyDirectory(_) | Self::Rebuild(_) | Self::Sharding(_) => {
+                ErrorKind::FailedPrecondition
             }
+            Self::Integrity(_) => ErrorKind::Integrity,
+            Self::Anchoring(_) => ErrorKind::Internal,
         }
     }
 }
 
-impl From<akd_core::verify::VerificationError> for DirectoryError {
-    fn from(err: akd_core::verify::VerificationError) -> Self {
-        Self::Verification(err)
-    }
-}
-
 /// Represents a storage-layer error
 #[cfg_attr(any(test, feature = "public_tests"), derive(PartialEq, Eq))]
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum StorageError {
     /// Data wasn't found in the storage layer
+    #[error("Data not found: {0}")]
     NotFound(String),
     /// A transaction error
+    #[error("Transaction: {0}")]
     Transaction(String),
     /// Some kind of storage connection error occurred
+    #[error("Storage connection: {0}")]
     Connection(String),
+    /// A conditional write failed, since the stored record was modified concurrently
+    #[error("Conflicting write: {0}")]
+    Conflict(String),
+    /// A write was attempted through a storage manager which is a follower
+    #[error("Read-only storage: {0}")]
+    ReadOnly(String),
+    /// The publish lease is held by another publisher
+    #[error("Publish lease unavailable: {0}")]
+    LeaseHeld(String),
     /// Some other storage-layer error occurred
+    #[error("Other storage error: {0}")]
     Other(String),
 }
 
-impl std::error::Error for StorageError {}
-
-impl fmt::Display for StorageError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl StorageError {
+    /// The kind of the error (see [AkdError::kind]). Only connection errors are treated as
+    /// transient, and a held publish lease as a conflict with another publisher. Transaction
+    /// errors come from the misuse of a transaction (e.g. a commit without an active
+    /// transaction, or whose records do not end with the head of the tree), which a retry
+    /// does not fix.
+    pub fn kind(&self) -> ErrorKind {
         match self {
-            StorageError::Connection(inner) => {
-                write!(f, "Storage connection: {inner}")
-            }
-            StorageError::Transaction(inner) => {
-                write!(f, "Transaction: {inner}")
-            }
-            StorageError::NotFound(inner) => {
-                write!(f, "Data not found: {inner}")
-            }
-            StorageError::Other(inner) => {
-                write!(f, "Other storage error: {inner}")
-            }
+            Self::NotFound(_) => ErrorKind::NotFound,
+            Self::Connection(_) => ErrorKind::Unavailable,
+            Self::Conflict(_) | Self::LeaseHeld(_) => ErrorKind::Conflict,
+            Self::Transaction(_) | Self::ReadOnly(_) => ErrorKind::FailedPrecondition,
+            Self::Other(_) => ErrorKind::Internal,
         }
     }
 }
 
 /// The errors thrown by various algorithms in [crate::directory::Directory]
 #[cfg_attr(test, derive(PartialEq, Eq))]
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum AuditorError {
     /// A general auditor error
+    #[error("Failed to verify audit {0}")]
     VerifyAuditProof(String),
 }
 
-impl std::error::Error for AuditorError {}
-
-impl fmt::Display for AuditorError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::VerifyAuditProof(err_string) => {
-                write!(f, "Failed to verify audit {err_string}")
-            }
-        }
-    }
-}
-
 /// The errors thrown by parallel code
 #[cfg_attr(test, derive(PartialEq, Eq))]
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum ParallelismError {
     /// A tokio task join error
+    #[error("Failed to join tokio task {0}")]
     JoinErr(String),
 }
 
-impl std::error::Error for ParallelismError {}
-
-impl fmt::Display for ParallelismError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::JoinErr(err_string) => {
-                write!(f, "Failed to join tokio task {err_string}")
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_kinds() {
+        let not_found = AkdError::Storage(StorageError::NotFound("key".to_string()));
+        assert_eq!(ErrorKind::NotFound, not_found.kind());
+        assert!(!not_found.is_retryable());
+
+        for retryable in [
+            StorageError::Connection("down".to_string()),
+            StorageError::Conflict("epoch 2".to_string()),
+            StorageError::LeaseHeld("leader".to_string()),
+        ] {
+            assert!(AkdError::from(retryable).is_retryable());
         }
+        let misuse = AkdError::from(StorageError::Transaction(
+            "Transaction not currently active".to_string(),
+        ));
+        assert_eq!(ErrorKind::FailedPrecondition, misuse.kind());
+        assert!(!misuse.is_retryable());
+
+        let verification: AkdError = VerificationError::LookupProof("bad proof".to_string()).into();
+        assert_eq!(ErrorKind::Verification, verification.kind());
+        assert_eq!(
+            ErrorKind::FailedPrecondition,
+            AkdError::from(DirectoryError::ReadOnlyDirectory("follower".to_string())).kind()
+        );
+        assert_eq!(
+            ErrorKind::Integrity,
+            AkdError::from(DirectoryError::Integrity("hash".to_string())).kind()
+        );
+    }
+
+    #[test]
+    fn test_error_sources() {
+        use std::error::Error;
+
+        let err = AkdError::from(StorageError::NotFound("key".to_string()));
+        assert_eq!("AKD Storage Error: Data not found: key\n", err.to_string());
+        assert_eq!(
+            "Data not found: key",
+            err.source().map(|source| source.to_string()).unwrap()
+        );
+
+        let err = TreeNodeError::NoDirection(NodeLabel::root(), Some(NodeLabel::root()));
+        assert!(err
+            .to_string()
+            .ends_with(&format!(" and child {:?}", NodeLabel::root())));
     }
 }
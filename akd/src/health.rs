@@ -0,0 +1,136 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Health checks for a directory, suitable for load-balancer health endpoints and dashboards.
+//!
+//! `Directory::health` checks each of the components which a directory depends on, and
+//! aggregates their results into a [HealthReport]. A component which cannot be used at
+//! all is [HealthStatus::Unhealthy], while a component which is usable but outside of the
+//! thresholds of the [HealthCheckOptions] is [HealthStatus::Degraded].
+
+use std::time::Duration;
+
+/// The health of a directory, or of one of its components, ordered from best to worst
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HealthStatus {
+    /// The component is working as expected
+    Healthy,
+    /// The component is working, but is outside of its expected thresholds
+    Degraded,
+    /// The component is not working
+    Unhealthy,
+}
+
+impl std::fmt::Display for HealthStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self {
+            HealthStatus::Healthy => "healthy",
+            HealthStatus::Degraded => "degraded",
+            HealthStatus::Unhealthy => "unhealthy",
+        };
+        write!(f, "{status}")
+    }
+}
+
+/// The health of a single component of a directory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ComponentHealth {
+    /// The status of the component
+    pub status: HealthStatus,
+    /// A human-readable description of the status
+    pub detail: String,
+}
+
+impl ComponentHealth {
+    pub(crate) fn healthy(detail: impl Into<String>) -> Self {
+        Self {
+            status: HealthStatus::Healthy,
+            detail: detail.into(),
+        }
+    }
+
+    pub(crate) fn degraded(detail: impl Into<String>) -> Self {
+        Self {
+            status: HealthStatus::Degraded,
+            detail: detail.into(),
+        }
+    }
+
+    pub(crate) fn unhealthy(detail: impl Into<String>) -> Self {
+        Self {
+            status: HealthStatus::Unhealthy,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// The thresholds and external state used by a health check
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HealthCheckOptions {
+    /// The maximum time since the last publish by this directory instance before it
+    /// is considered degraded. If not set, the age of the last publish is not checked.
+    pub max_publish_age: Option<Duration>,
+    /// The latest epoch which has been audited, as reported by the auditor. If not set,
+    /// the audit lag is not checked.
+    pub audited_epoch: Option<u64>,
+    /// The maximum number of epochs by which the audited epoch may trail the current
+    /// epoch before the directory is considered degraded
+    pub max_audit_lag: u64,
+}
+
+/// The result of a health check of a directory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HealthReport {
+    /// The overall status, which is the worst status of any component
+    pub status: HealthStatus,
+    /// The current epoch of the directory, if storage could be read
+    pub epoch: Option<u64>,
+    /// Whether the directory's state can be read from storage
+    pub storage: ComponentHealth,
+    /// Whether the VRF key is available
+    pub vrf: ComponentHealth,
+    /// The state of the storage cache
+    pub cache: ComponentHealth,
+    /// The time since the last publish by this directory instance
+    pub last_publish: ComponentHealth,
+    /// How far the audited epoch trails the current epoch
+    pub audit: ComponentHealth,
+    /// The time since the last successful publish by this directory instance, if any
+    pub last_publish_age: Option<Duration>,
+    /// The number of epochs by which the audited epoch trails the current epoch
+    pub audit_lag: Option<u64>,
+    /// The number of items in the cache, if the directory has a cache
+    pub cache_items: Option<usize>,
+}
+
+impl HealthReport {
+    /// Returns whether the directory can serve requests, i.e. whether no component is unhealthy
+    pub fn is_serving(&self) -> bool {
+        self.status != HealthStatus::Unhealthy
+    }
+
+    /// The name and health of each component
+    pub fn components(&self) -> [(&'static str, &ComponentHealth); 5] {
+        [
+            ("storage", &self.storage),
+            ("vrf", &self.vrf),
+            ("cache", &self.cache),
+            ("last_publish", &self.last_publish),
+            ("audit", &self.audit),
+        ]
+    }
+}
+
+impl std::fmt::Display for HealthReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Directory is {}", self.status)?;
+        for (name, component) in self.components() {
+            write!(f, "\n  {name}: {} ({})", component.status, component.detail)?;
+        }
+        Ok(())
+    }
+}
@@ -8,7 +8,9 @@
 //! Helper structs that are used for various data structures,
 //! to make it easier to pass arguments around.
 
+use crate::canonical::{encode_epoch_metadata, CanonicalEncode};
 use crate::Digest;
+#[cfg(feature = "server")]
 use crate::{storage::types::ValueState, NodeLabel};
 
 /// Root hash of the tree and its associated epoch
@@ -26,6 +28,13 @@ impl EpochHash {
     }
 }
 
+impl CanonicalEncode for EpochHash {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        encode_epoch_metadata(self.0, &self.1, out);
+    }
+}
+
+#[cfg(feature = "server")]
 #[derive(Clone, Debug)]
 /// Info needed for a lookup of a user for an epoch
 pub struct LookupInfo {
@@ -0,0 +1,39 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Callbacks for the events of a directory.
+//!
+//! An implementation of [DirectoryHooks] which is registered with `Directory::register_hook`
+//! is notified of the events of the directory and of all of its clones, so that an integrator
+//! can send notifications, invalidate caches, or raise alerts without polling the directory.
+//! The hooks are called synchronously by the task which performs the operation, in the order
+//! in which they were registered, so a hook which needs to do slow or asynchronous work should
+//! hand the event off to another task.
+
+use crate::errors::AkdError;
+use crate::helper_structs::EpochHash;
+
+/// The events of a directory which can be observed. Each method has an empty default
+/// implementation, so a hook only needs to implement the events it is interested in.
+pub trait DirectoryHooks: Send + Sync {
+    /// Called after a publish has committed a new epoch containing `num_updates` updates.
+    /// A publish which does not change the directory does not produce a new epoch, and
+    /// is not reported, unless its epoch is scheduled (see [crate::scheduling]).
+    fn on_epoch_published(&self, _epoch_hash: &EpochHash, _num_updates: usize) {}
+
+    /// Called when a publish fails, with the error which is returned to the caller
+    fn on_publish_failed(&self, _error: &AkdError) {}
+
+    /// Called after the values published by `cutoff_epoch` have been pruned, with the
+    /// number of values which were tombstoned
+    fn on_prune_completed(&self, _cutoff_epoch: u64, _num_tombstoned: usize) {}
+
+    /// Called when the directory finds that its stored state is inconsistent, with a
+    /// description of the inconsistency. The operation which found it then fails with a
+    /// `DirectoryError::Integrity` error.
+    fn on_integrity_violation(&self, _detail: &str) {}
+}
@@ -0,0 +1,72 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Precomputation of the lookup proofs of designated hot labels.
+//!
+//! A label which is looked up by many clients at once (e.g. that of a celebrity, or of a
+//! service account which every client contacts) would otherwise have its lookup proof
+//! generated once per request, each time traversing the tree. With
+//! `Directory::set_hot_labels`, the lookup proofs of a set of hot labels are instead generated
+//! eagerly whenever the directory moves to a new epoch: in a background task after each publish
+//! which commits an epoch, so that the publish does not wait for them, and when a follower
+//! refreshes to an epoch published by its leader. `Directory::lookup` then answers a lookup of
+//! a hot label with a clone of its precomputed proof, without any tree traversal. Until the
+//! proofs of a new epoch have been precomputed, the hot labels are looked up as usual.
+//!
+//! A precomputed proof is only served while the directory is at the epoch and root hash it was
+//! generated for, so that a lookup never returns a proof for an older epoch. Hot labels which
+//! have not been published yet have no precomputed proof, and are looked up as usual.
+
+use crate::{AkdLabel, EpochHash, LookupProof};
+
+use std::collections::{BTreeSet, HashMap};
+
+/// The hot labels of a directory, and their lookup proofs at the latest epoch for which they
+/// were precomputed
+#[derive(Default)]
+pub(crate) struct HotLabels {
+    labels: BTreeSet<AkdLabel>,
+    epoch_hash: Option<EpochHash>,
+    proofs: HashMap<AkdLabel, LookupProof>,
+}
+
+impl HotLabels {
+    /// Replaces the hot labels, discarding the proofs of the labels which are no longer hot
+    pub(crate) fn set_labels(&mut self, labels: BTreeSet<AkdLabel>) {
+        self.proofs.retain(|label, _| labels.contains(label));
+        self.labels = labels;
+    }
+
+    /// The hot labels, in order
+    pub(crate) fn labels(&self) -> Vec<AkdLabel> {
+        self.labels.iter().cloned().collect()
+    }
+
+    /// Replaces the precomputed proofs with those generated at `epoch_hash`
+    pub(crate) fn store(&mut self, epoch_hash: EpochHash, proofs: HashMap<AkdLabel, LookupProof>) {
+        self.epoch_hash = Some(epoch_hash);
+        self.proofs = proofs
+            .into_iter()
+            .filter(|(label, _)| self.labels.contains(label))
+            .collect();
+    }
+
+    /// Whether the proofs were last precomputed at `epoch_hash`
+    #[cfg(test)]
+    pub(crate) fn is_precomputed_at(&self, epoch_hash: &EpochHash) -> bool {
+        self.epoch_hash.as_ref() == Some(epoch_hash)
+    }
+
+    /// The precomputed proof of a label, if the label is hot and its proof was generated at
+    /// `epoch_hash`
+    pub(crate) fn get(&self, label: &AkdLabel, epoch_hash: &EpochHash) -> Option<LookupProof> {
+        if self.epoch_hash.as_ref() != Some(epoch_hash) {
+            return None;
+        }
+        self.proofs.get(label).cloned()
+    }
+}
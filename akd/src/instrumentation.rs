@@ -0,0 +1,230 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Operational metrics and tracing for a directory.
+//!
+//! When the `metrics` feature is enabled, the directory and its storage manager emit
+//! counters, gauges, and histograms through the [`metrics`](https://docs.rs/metrics) facade,
+//! which can be exported to Prometheus (or any other backend) by installing a recorder such
+//! as `metrics-exporter-prometheus` in the application. Without the feature, nothing is
+//! recorded. The names of the emitted metrics are listed below; durations are recorded in
+//! seconds and sizes in bytes.
+//!
+//! When the `tracing` feature is enabled, the operations of the directory and of its storage
+//! manager are recorded as [`tracing`](https://docs.rs/tracing) spans. Each span is a child of
+//! the span which is current when the operation is called, including across the background
+//! tasks spawned by the operation, so an application which installs a `tracing-opentelemetry`
+//! layer and enters a span carrying the OpenTelemetry context of an incoming request will see
+//! the directory operation, its storage accesses, and any spans emitted by the database
+//! implementation within that request's distributed trace.
+//!
+//! Independently of these features, the library logs through `tracing` events with structured
+//! fields, such as the epoch and the size of a batch. Labels are never logged directly, but are
+//! identified by a prefix of their hash. With the `log` feature (enabled by default), these
+//! events are also emitted as `log` records when no `tracing` subscriber has been installed.
+
+use crate::{AkdLabel, Configuration, HistoryParams};
+use std::future::Future;
+use std::time::Instant;
+
+/// Counter of successful publishes which produced a new epoch
+pub const PUBLISH_TOTAL: &str = "akd_publish_total";
+/// Counter of the label updates included in published epochs
+pub const PUBLISH_UPDATES_TOTAL: &str = "akd_publish_updates_total";
+/// Histogram of the time taken to publish an epoch
+pub const EPOCH_DURATION_SECONDS: &str = "akd_epoch_duration_seconds";
+/// Gauge of the latest published epoch
+pub const EPOCH: &str = "akd_epoch";
+/// Histogram of the time taken to generate a proof, labeled by `operation` and, for history
+/// proofs, by `params`
+pub const PROOF_GENERATION_SECONDS: &str = "akd_proof_generation_seconds";
+/// Histogram of the canonically encoded size of a generated proof, labeled by `operation` and,
+/// for history proofs, by the `params` with which the history was requested. The proofs of a
+/// batch lookup are recorded individually.
+pub const PROOF_SIZE_BYTES: &str = "akd_proof_size_bytes";
+/// Histogram of the time taken by an operation against the database, labeled by `operation`
+pub const STORAGE_OPERATION_SECONDS: &str = "akd_storage_operation_seconds";
+/// Counter of the cache lookups which found the requested record
+pub const CACHE_HITS_TOTAL: &str = "akd_cache_hits_total";
+/// Counter of the cache lookups which did not find the requested record
+pub const CACHE_MISSES_TOTAL: &str = "akd_cache_misses_total";
+/// Counter of the lookups which were answered with a precomputed proof of a hot label
+pub const HOT_LOOKUPS_TOTAL: &str = "akd_hot_lookups_total";
+/// Counter of the lookups which were answered with a cached proof
+pub const PROOF_CACHE_HITS_TOTAL: &str = "akd_proof_cache_hits_total";
+/// Counter of the lookups which found no cached proof, while the proof cache was enabled
+pub const PROOF_CACHE_MISSES_TOTAL: &str = "akd_proof_cache_misses_total";
+/// Counter of the cached proofs which were discarded because the directory moved to a new epoch
+pub const PROOF_CACHE_INVALIDATIONS_TOTAL: &str = "akd_proof_cache_invalidations_total";
+/// Counter of the lookups which were answered with the proof generated by a concurrent lookup
+/// of the same label
+pub const COALESCED_LOOKUPS_TOTAL: &str = "akd_coalesced_lookups_total";
+/// Counter of the labels whose nodes were loaded into the cache by a prefetch
+pub const PREFETCHED_LABELS_TOTAL: &str = "akd_prefetched_labels_total";
+
+/// The number of bytes of the hash of a label which identify the label in log events
+const LABEL_HASH_PREFIX_BYTES: usize = 4;
+
+/// Identifies a label in log events and error messages by a hex-encoded prefix of its hash, so
+/// that the events for a label can be correlated without recording the label itself
+pub(crate) fn label_hash_prefix<TC: Configuration>(label: &AkdLabel) -> String {
+    hex::encode(&TC::hash(label)[..LABEL_HASH_PREFIX_BYTES])
+}
+
+/// Records a publish of `num_updates` updates which produced `epoch`
+pub(crate) fn record_publish(_num_updates: usize, _epoch: u64, _start: Instant) {
+    #[cfg(feature = "metrics")]
+    {
+        metrics::counter!(PUBLISH_TOTAL).increment(1);
+        metrics::counter!(PUBLISH_UPDATES_TOTAL).increment(_num_updates as u64);
+        metrics::histogram!(EPOCH_DURATION_SECONDS).record(_start.elapsed().as_secs_f64());
+        metrics::gauge!(EPOCH).set(_epoch as f64);
+    }
+}
+
+/// Records the generation of a proof by the named operation. The size of the proof is only
+/// computed when metrics are enabled.
+pub(crate) fn record_proof<P: crate::canonical::CanonicalEncode>(
+    _operation: &'static str,
+    _start: Instant,
+    _proof: &P,
+) {
+    #[cfg(feature = "metrics")]
+    {
+        let mut encoded = vec![];
+        _proof.canonical_encode(&mut encoded);
+        metrics::histogram!(PROOF_GENERATION_SECONDS, "operation" => _operation)
+            .record(_start.elapsed().as_secs_f64());
+        metrics::histogram!(PROOF_SIZE_BYTES, "operation" => _operation)
+            .record(encoded.len() as f64);
+    }
+}
+
+/// Records the generation of a history proof by the named operation, labeled by the kind of
+/// [HistoryParams] it was generated with, since the size of a history proof depends on them
+pub(crate) fn record_history_proof<P: crate::canonical::CanonicalEncode>(
+    _operation: &'static str,
+    _params: HistoryParams,
+    _start: Instant,
+    _proof: &P,
+) {
+    #[cfg(feature = "metrics")]
+    {
+        let params = match _params {
+            HistoryParams::Complete => "complete",
+            HistoryParams::MostRecentInsecure(_) => "most_recent",
+            HistoryParams::SinceEpochInsecure(_) => "since_epoch",
+            HistoryParams::RecentEpochs(_) => "recent_epochs",
+        };
+        let mut encoded = vec![];
+        _proof.canonical_encode(&mut encoded);
+        metrics::histogram!(PROOF_GENERATION_SECONDS, "operation" => _operation, "params" => params)
+            .record(_start.elapsed().as_secs_f64());
+        metrics::histogram!(PROOF_SIZE_BYTES, "operation" => _operation, "params" => params)
+            .record(encoded.len() as f64);
+    }
+}
+
+/// Records the generation of a batch of proofs by the named operation, whose sizes are
+/// recorded individually so that they can be compared with those of single proofs
+pub(crate) fn record_batch_proofs<P: crate::canonical::CanonicalEncode>(
+    _operation: &'static str,
+    _start: Instant,
+    _proofs: &[P],
+) {
+    #[cfg(feature = "metrics")]
+    {
+        metrics::histogram!(PROOF_GENERATION_SECONDS, "operation" => _operation)
+            .record(_start.elapsed().as_secs_f64());
+        let size = metrics::histogram!(PROOF_SIZE_BYTES, "operation" => _operation);
+        for proof in _proofs {
+            let mut encoded = vec![];
+            proof.canonical_encode(&mut encoded);
+            size.record(encoded.len() as f64);
+        }
+    }
+}
+
+/// Records the duration of an operation against the database
+pub(crate) fn record_storage_operation(_operation: &'static str, _start: Instant) {
+    #[cfg(feature = "metrics")]
+    metrics::histogram!(STORAGE_OPERATION_SECONDS, "operation" => _operation)
+        .record(_start.elapsed().as_secs_f64());
+}
+
+/// Records a lookup in the cache
+pub(crate) fn record_cache_access(_hit: bool) {
+    #[cfg(feature = "metrics")]
+    if _hit {
+        metrics::counter!(CACHE_HITS_TOTAL).increment(1);
+    } else {
+        metrics::counter!(CACHE_MISSES_TOTAL).increment(1);
+    }
+}
+
+/// Records a lookup which was answered with a precomputed proof of a hot label
+pub(crate) fn record_hot_lookup() {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(HOT_LOOKUPS_TOTAL).increment(1);
+}
+
+/// Records a lookup in the proof cache
+pub(crate) fn record_proof_cache_access(_hit: bool) {
+    #[cfg(feature = "metrics")]
+    if _hit {
+        metrics::counter!(PROOF_CACHE_HITS_TOTAL).increment(1);
+    } else {
+        metrics::counter!(PROOF_CACHE_MISSES_TOTAL).increment(1);
+    }
+}
+
+/// Records a lookup which was answered with the proof generated by a concurrent lookup
+pub(crate) fn record_coalesced_lookup() {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(COALESCED_LOOKUPS_TOTAL).increment(1);
+}
+
+/// Records the labels whose nodes were loaded into the cache by a prefetch
+pub(crate) fn record_prefetch(_num_labels: usize) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(PREFETCHED_LABELS_TOTAL).increment(_num_labels as u64);
+}
+
+/// Records the cached proofs which were discarded because the directory moved to a new epoch
+pub(crate) fn record_proof_cache_invalidation(_num_proofs: usize) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(PROOF_CACHE_INVALIDATIONS_TOTAL).increment(_num_proofs as u64);
+}
+
+/// Emits a `tracing` event at a level which is only known at runtime
+#[cfg(feature = "runtime_metrics")]
+macro_rules! dyn_event {
+    ($level:expr, $($args:tt)+) => {
+        match $level {
+            tracing::Level::TRACE => tracing::trace!($($args)+),
+            tracing::Level::DEBUG => tracing::debug!($($args)+),
+            tracing::Level::INFO => tracing::info!($($args)+),
+            tracing::Level::WARN => tracing::warn!($($args)+),
+            _ => tracing::error!($($args)+),
+        }
+    };
+}
+#[cfg(feature = "runtime_metrics")]
+pub(crate) use dyn_event;
+
+/// Attaches the current tracing span to a future which is about to be spawned onto another
+/// task, so that the spans emitted by the future remain part of the caller's trace
+pub(crate) fn in_current_span<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    #[cfg(feature = "tracing")]
+    {
+        tracing::Instrument::in_current_span(future)
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        future
+    }
+}
@@ -30,12 +30,12 @@
 //! This library supports the following operations for the directory it maintains:
 //! - [Publishing](#publishing): Allows the directory server to insert and update new entries into the directory.
 //! - [Lookup Proofs](#lookup-proofs): Handles point queries to the directory, providing proofs of validity based on the server's
-//! public key and a root hash for an epoch.
+//!   public key and a root hash for an epoch.
 //! - [History Proofs](#history-proofs): For a given index in the directory, provides proofs for the history of updates to this
-//! entry, matched against the server's public key and a root hash for an epoch.
+//!   entry, matched against the server's public key and a root hash for an epoch.
 //! - [Append-Only Proofs](#append-only-proofs): For a pair of epochs, provides a proof to an auditor that the database has evolved
-//! consistently and in an append-only manner. These append-only proofs use a verifiable random function (VRF)
-//! to avoid leaking any information about the labels and their corresponding values.
+//!   consistently and in an append-only manner. These append-only proofs use a verifiable random function (VRF)
+//!   to avoid leaking any information about the labels and their corresponding values.
 //!
 //!
 //! ### Asynchronicity
@@ -69,8 +69,46 @@
 //! # });
 //! ```
 //!
+//! A directory can also be created with [`Directory::builder`], which takes the storage manager and VRF along with
+//! further options (such as whether the directory is read-only, its operator, and its hooks) by name, as a
+//! [`DirectoryBuilder`].
+//!
 //! For more information on setting configurations, see the [Configurations](#configurations) section.
 //!
+//! The types and functions which most servers and clients need can be imported together from the [prelude].
+//!
+//! With the `dyn_traits` feature, the storage and VRF of a directory can instead be chosen at runtime, as an
+//! `Arc<dyn DynDatabase>` (see `storage::dynamic`) and an `Arc<dyn DynVRFKeyStorage>`.
+//!
+//! Databases which do not write the records of a commit atomically can be given a
+//! [`storage::wal::WriteAheadLog`] with [`storage::StorageManager::with_write_ahead_log`], so that a
+//! publish which is interrupted part way through writing its records is completed when the directory
+//! is next created. A [`storage::wal::FileWriteAheadLog`] keeps the log in a file, so that the publish is
+//! also completed after the process restarts. Each commit writes its records with [`storage::Database::batch_set_if_versions`],
+//! conditional on the head of the tree still being at the previous epoch, so if two publishers build the same epoch, the
+//! second to commit fails with a [`errors::StorageError::Conflict`] rather than overwriting the tree of the first.
+//! To keep a second publisher from building an epoch at all, a storage manager can require each publish to hold
+//! the database's publish lease with [`storage::StorageManager::with_publish_lease`]. The lease is renewed in the
+//! background while it is held, and carries a fencing token which is checked in the same conditional write as the
+//! commit, so a publisher whose lease has expired and been taken over fails with a [`errors::StorageError::LeaseHeld`]
+//! without writing any of its records. If two publishers have nonetheless
+//! written to the same storage, [`Directory::detect_divergence`] (or [`Directory::new_checked`], on startup)
+//! compares the stored state with the operations log, and lists the conflicting records in a
+//! [divergence::DivergenceReport].
+//!
+//! With [`storage::StorageManager::with_write_behind`], the writes made outside of a publish are queued and written
+//! in the background, and each publish waits for the queue to be flushed before it commits its epoch.
+//! [`storage::StorageManager::shutdown`] flushes the queue and the cache, stops the background flusher, and closes
+//! the database, after which the storage manager refuses any further writes.
+//!
+//! The epoch of each publish is, by default, the one following the current epoch. Systems which share a timeline
+//! of epochs can instead have the epochs scheduled by a [`scheduling::EpochSource`] (such as a wall-clock schedule,
+//! [`scheduling::WallClockEpochs`]), set with [`Directory::set_epoch_source`], or provide the epoch of each publish
+//! with [`Directory::publish_at_epoch`]. Epochs may then be skipped, and hold the tree of the epoch before them.
+//!
+//! Records are keyed in storage by their full binary keys, whose framing is described in [`storage::keys`].
+//! The keys are compact and reversible: [`storage::keys::parse_key`] recovers the key of any stored record.
+//!
 //! ## Publishing
 //! To add label-value pairs (of type [`AkdLabel`] and [`AkdValue`]) to the directory, we can call [`Directory::publish`]
 //! with a list of the pairs. In the following example, we derive the labels and values from strings. After publishing,
@@ -108,6 +146,19 @@
 //! This function can be called repeatedly to add entries to the directory, with each invocation
 //! producing a new epoch and root hash for the directory.
 //!
+//! Updates which arrive over the course of an epoch can instead be collected in a
+//! [`publish_queue::PublishQueue`], which keeps only the latest update submitted for each label,
+//! and then published together with [`Directory::publish_queued`]. To protect the tree from abusive churn
+//! on single labels, [`Directory::publish_with_limits`] enforces a maximum number of versions and a maximum
+//! update rate for each label, as described by a [`PublishLimits`]. The limits can also bound the lengths of labels
+//! and values, and restrict the bytes of labels to a [`directory::BytePolicy`], with each rejected update reported
+//! along with the reason it was rejected. The same constraints can be checked as labels and values are received, by
+//! creating them with [`AkdLabel::new`] and [`AkdValue::new`] from the [`PublishLimits::label_constraints`] and
+//! [`PublishLimits::value_constraints`]. The [`Debug`](std::fmt::Debug) output of labels and values only reveals their
+//! lengths, so that user identifiers do not leak into logs.
+//! Updates which would not change the value of their label are skipped and reported as such, so that
+//! retried publishes do not inflate the version histories of their labels.
+//!
 //! ## Lookup Proofs
 //! We can call [`Directory::lookup`] to generate a [`LookupProof`] that proves the correctness
 //! of a client lookup for an existing entry.
@@ -197,6 +248,19 @@
 //! # });
 //! ```
 //!
+//! A lookup can also be verified with [`client::lookup_verify_result`], whose [`LookupResult`] carries the label,
+//! the root hash and epoch it was verified against, and the proof along with the value, and offers conveniences such as
+//! [`LookupResult::epochs_since_update`] and [`LookupResult::assert_value_equals`].
+//!
+//! A lookup proof shows that its version has not been marked as stale, but does not by itself show that
+//! no newer version was published. Clients which need that guarantee without a full history query can use
+//! [`Directory::lookup_latest`], whose proof additionally includes the non-existence of all newer versions
+//! up to the current epoch, and verify it with [`client::lookup_latest_verify`].
+//!
+//! A server can also prove that a label has never been registered, for example to show a client that
+//! a username is available. [`Directory::lookup_absent`] produces a proof that the first version of
+//! the label is absent from the tree, which is verified with [`client::nonexistence_verify`].
+//!
 //! ## History Proofs
 //! As mentioned above, security is defined by consistent views of the value for a key at any epoch.
 //! To this end, a server running an AKD needs to provide a way to check the history of a key. Note that in this case,
@@ -401,6 +465,32 @@
 //! # });
 //! ```
 //!
+//! An auditor which needs to catch up on many epochs at once can instead request a single
+//! [`MultiEpochAppendOnlyProof`] with [`Directory::audit_multi_epoch`], and verify it against only
+//! the start and end root hashes using [`auditor::audit_verify_multi_epoch`].
+//!
+//! For very large epochs, [`Directory::audit_chunked`] builds the same [`AppendOnlyProof`] in chunks, one per
+//! subtree, which are built in parallel without holding off publishes in between, and are saved to a
+//! [`proof_chunks::ProofChunkStore`] so that an interrupted generation resumes from the last completed chunks.
+//! See [proof_chunks] for details.
+//!
+//! An auditor which cannot afford a full proof for every epoch can instead run a lightweight audit,
+//! starting from the digests of the top-level subtrees returned by [`Directory::audit_lite_snapshot`],
+//! and checking the digests of the subtrees which changed in each epoch, returned by
+//! [`Directory::audit_lite`], with [`audit_lite::AuditLiteState::apply`]. See [audit_lite] for details.
+//!
+//! A directory which publishes small epochs frequently, so that new keys are available quickly, can
+//! periodically group them into audit epochs with [`Directory::consolidate`] (or the
+//! [`maintenance::MaintenanceTask::Consolidation`] task). Auditors then verify one proof per audit epoch
+//! with [`consolidation::verify_audit_epoch`], and clients check that the root hash of the epoch they
+//! looked up in is covered by an audit epoch with [`consolidation::verify_mini_epoch`]. See
+//! [consolidation] for details.
+//!
+//! A lightweight auditor which cannot afford to verify every append-only proof can instead spot check
+//! the current epoch with [`Directory::spot_check`], which proves the membership of leaves sampled
+//! pseudorandomly from the epoch's root hash, so that the server cannot predict which leaves will be
+//! checked. The proof is verified with [`spot_check::spot_check_verify`]. See [spot_check] for details.
+//!
 //! # Advanced Usage
 //!
 //! ## Configurations
@@ -425,6 +515,58 @@
 //! ```
 //! An application can set their own specific domain label to a custom string achieve domain separation from other applications.
 //!
+//! A configuration can also canonicalize labels before they are used, with [Configuration::normalize_label], so that
+//! different encodings of the same identity (e.g. differing in case) refer to the same entry. A [LabelNormalizer] can be
+//! added to an existing configuration with [NormalizedConfiguration].
+//!
+//! The marker versions of labels, which lookup and history proofs rely on to prove that no later version exists, are
+//! also chosen by the configuration ([Configuration::marker_version]) and default to the powers of two. Sparser
+//! markers (e.g. with [ExponentialMarkers]) shrink the proofs of non-existence of future marker versions at the cost of
+//! more proofs for the versions before the next marker. A [MarkerStrategy] can be added to an existing configuration
+//! with [MarkerConfiguration], and clients must verify proofs with the same strategy as the server.
+//!
+//! The node labels of the tree are the full 256-bit VRF outputs of labels by default. A configuration can instead use
+//! a prefix of them ([Configuration::node_label_len]), for instance with [TruncatedLabelConfiguration], which bounds
+//! the depth of the tree at the cost of a higher probability of two labels colliding. The directory refuses to publish
+//! an update whose node label collides with another, and clients must verify proofs with the same truncation.
+//!
+//! The values which are removed from storage by pruning are replaced by tombstones, whose encoding is also chosen by
+//! the configuration ([Configuration::tombstone_value]). By default, a tombstone is the empty [TOMBSTONE] value. With
+//! [TombstoneMetadataConfiguration], tombstones instead carry [TombstoneMetadata]: the epoch at which the value was
+//! tombstoned, and the commitment to the removed value, which clients check against the tree when verifying a key
+//! history with [HistoryVerificationParams::AllowMissingValues].
+//!
+//! A value which must be removed for legal or compliance reasons can instead be redacted with [Directory::redact],
+//! which replaces it with a [RedactionRecord] stating the epoch and reason of the redaction along with the commitment
+//! to the redacted value. Redaction records are checked by clients like tombstone metadata, so that a key history shows
+//! when and why a value was redacted rather than an indistinguishable tombstone. Redactions are logged in the operations
+//! log.
+//!
+//! The values of a label can also be erased on request, in stages: [storage::StorageManager::soft_delete_label] flags
+//! them for erasure without altering them, [storage::StorageManager::restore_label] reverses this, and
+//! [storage::StorageManager::purge_soft_deletions] tombstones the values of the labels whose retention window has passed.
+//!
+//! Values are committed to in the tree with the hash-based commitments of [HashCommitments] by default
+//! ([Configuration::commit_value]). A different [CommitmentScheme] can be added to an existing configuration with
+//! [CommitmentConfiguration], such as the Pedersen commitments of `PedersenCommitments` (with the `pedersen` feature),
+//! which are additively homomorphic and so allow for zero-knowledge proofs about committed values to be built on top.
+//! Clients verify values by recomputing their commitments, so they must use the same scheme as the server.
+//!
+//! With the `poseidon` feature, `PoseidonConfiguration` goes further, and also hashes the leaves and parents of the
+//! tree with the `Poseidon` permutation over the scalar field of the Ristretto group, which is cheap to express in
+//! arithmetic circuits over that field. This lays the groundwork for succinct proofs of audits, while existing
+//! configurations are unaffected.
+//!
+//! A server which needs to choose its configuration at runtime, rather than at compile time, can instead use a
+//! [runtime_directory::RuntimeDirectory], which wraps a [Directory] of the configuration selected by a
+//! [runtime_directory::ConfigurationKind] and forwards each operation to it.
+//!
+//! A directory which outgrows a single tree can split its labels across several trees with a
+//! [sharded_directory::ShardedDirectory], which combines the root hashes of its shards into a single top-level root
+//! hash. Its proofs carry the proof of the label's shard along with the proof that the shard's root is committed to by
+//! the top-level root hash, and are verified with [client::sharded_lookup_verify] and
+//! [client::sharded_key_history_verify].
+//!
 //! ## History Parameters
 //!
 //! The [HistoryParams] enum can be used to limit the number of updates for a given entry that the server provides
@@ -432,19 +574,105 @@
 //! - [HistoryParams::Complete]: Includes a complete history of all updates to an entry. This is the default option.
 //! - [HistoryParams::MostRecentInsecure]: Includes (at most) the most recent input number of updates for an entry.
 //! - [HistoryParams::SinceEpochInsecure]: Includes all updates to an entry since a given epoch.
+//! - [HistoryParams::RecentEpochs]: Includes all updates to an entry made in the last input number of epochs,
+//!   along with the most recent update made before them.
 //!
 //! Note that the "insecure" options are not recommended for use in production, as they do not provide a
 //! complete history of updates, and lack inclusion proofs for earlier entries. These options should only be
 //! used for testing purposes.
 //!
+//! Clients which only need a bounded history should instead use [HistoryParams::RecentEpochs], and verify
+//! the proof with [client::key_history_verify_recent_epochs], which checks that no update within the window
+//! was omitted.
+//!
+//! A history can also be shared without revealing every value in it: [`Directory::selective_key_history`]
+//! reveals only the values of the versions published within a range of epochs, and proves that all other
+//! versions exist through the commitments to their values. Such proofs are verified with
+//! [client::selective_key_history_verify].
+//!
+//! When a user's label changes (e.g. following a change of phone number), [`Directory::rename_label`] moves
+//! their history to the new label by publishing a link record as the final value of the old label. The
+//! histories of the old and new labels are then proven together by [`Directory::linked_key_history`], and
+//! verified with [client::linked_key_history_verify], so that clients see one continuous history.
+//!
+//! For labels with very many versions, [`Directory::aggregate_history`] publishes a digest of their older
+//! versions, and [`Directory::aggregated_key_history`] proves the history as a lookup of that digest along
+//! with the versions after it, which is verified with [client::aggregated_key_history_verify]. See
+//! [aggregated_history] for details.
+//!
+//! Clients which regularly check the same set of labels can catch up on all of them at once with
+//! [`Directory::delta_sync`], which returns the append-only proof from the epoch of the client's last sync
+//! along with only the updates made to each label since, and is verified with
+//! [delta_sync::delta_sync_verify]. See [delta_sync] for details.
+//!
+//! ## Maintenance
+//!
+//! Routine upkeep of a directory, such as evicting expired cache items, warming the cache, pruning old
+//! values, and spot-checking the integrity of the stored tree, is described by a [maintenance::MaintenanceTask].
+//! A task can be run once with [`Directory::run_maintenance_task`], or tasks can be scheduled to run in the
+//! background on jittered intervals with [`Directory::spawn_maintenance`]. Old values can also be tombstoned
+//! in a single batch with [`Directory::tombstone_older_than`], or with [`Directory::apply_retention_policy`]
+//! given a [maintenance::RetentionPolicy] which overrides the retention period of individual labels. A label
+//! can also be assigned a [RetentionClass] with [`Directory::set_retention_class`], which takes precedence over
+//! the policy: the older values of an ephemeral label are always tombstoned, while those of a label under a
+//! legal hold never are. The class of each label is reported in a [`Directory::export`].
+//!
+//! To scale out the serving of proofs, a single leader publishes while followers share its storage. A follower's
+//! storage manager has the [storage::StorageRole::Follower] role, set with
+//! [`storage::StorageManager::with_role`], which refuses writes. A follower picks up new epochs with
+//! [`Directory::refresh`], or in the background with [`Directory::spawn_follower`] (see [replication]).
+//!
+//! Labels which are looked up by many clients at once can be designated as hot with
+//! [`Directory::set_hot_labels`]. Their lookup proofs are precomputed whenever the directory moves to a new epoch
+//! (in the background, after a publish), and [`Directory::lookup`] answers them without traversing the tree (see
+//! [hot_labels]). More generally, the
+//! proofs generated by [`Directory::lookup`] can be cached with [`Directory::set_proof_cache_capacity`], keyed by the
+//! epoch and root hash they were generated at, and discarded once the directory has moved to a new epoch (see
+//! [proof_cache]). Concurrent lookups of the same label can also be coalesced with
+//! [`Directory::set_lookup_coalescing`], so that the proof is generated once and shared between them (see
+//! [coalescing]). On remote storage, [`Directory::set_prefetching`] records the recent lookups, and
+//! [`Directory::prefetch`] loads the nodes of the labels which are likely to be looked up next (e.g. the rest of
+//! a set of contacts which are usually looked up together) into the cache between requests (see [prefetch]).
+//!
+//! A snapshot of the directory at an epoch can be taken with [`Directory::export`], which lists the current
+//! version of every label along with the commitment to its value and the root hash of the epoch, for systems
+//! which need to consume the contents of the directory without access to its storage.
+//!
+//! The health of a directory's storage, VRF key, cache, publishing, and auditing can be checked with
+//! [`Directory::health`], which aggregates them into a [health::HealthReport] for health endpoints.
+//!
+//! Rather than polling the directory, an integrator can register a [hooks::DirectoryHooks] with
+//! [`Directory::register_hook`] to be notified when an epoch is published, a publish fails, values are
+//! pruned, or an integrity violation is detected.
+//!
+//! For long-term non-repudiation, the root hash of the current epoch can be anchored in an external timestamping or
+//! notarization system with [`Directory::anchor`], which stores the receipt of an [anchoring::Anchor] such as
+//! [anchoring::OpenTimestampsAnchor]. [`Directory::lookup_anchored`] serves a lookup proof together with the receipts
+//! of its epoch, which clients check with [anchoring::AnchoredRoot::verify].
+//!
+//! For debugging and teaching, the tree can be exported with [`Directory::visualize_tree`] as a
+//! [visualization::TreeVisualization], which renders as a graphviz DOT graph or as JSON.
+//!
 //!
 //! ## Compilation Features
 //!
 //! This crate supports multiple compilation features:
 //!
+//! Verification-only builds:
+//! - `server`: Enables the directory, its storage layer, and the auditor, along with their dependencies (such as `tokio`).
+//!   Enabled by default. Clients which only verify proofs (e.g. on mobile or wasm) can disable the default features, and
+//!   compile only the verification surface of [client], the proof types, and the errors:
+//!   ```toml
+//!   [dependencies]
+//!   akd = { version = "0.12", default-features = false, features = ["whatsapp_v1"] }
+//!   ```
+//!   The features below which concern the directory or its storage (e.g. the performance optimizations) enable `server`.
+//!
 //! Configurations:
 //! - `whatsapp_v1`: Enables usage of `WhatsAppV1Configuration`
 //! - `experimental`: Enables usage of `ExperimentalConfiguration`
+//! - `pedersen`: Enables Pedersen commitments to values, with `PedersenCommitments`
+//! - `poseidon`: Enables Poseidon hashing and commitments, with `PoseidonConfiguration` and `PoseidonCommitments`
 //!
 //! Performance optimizations:
 //! - `parallel_vrf`: Enables the VRF computations to be run in parallel
@@ -458,59 +686,130 @@
 //!
 //! Utilities:
 //! - `public_auditing`: Enables the publishing of audit proofs
+//! - `gossip`: Enables the export of epoch root hashes as signed checkpoints, which can be exchanged with the gossip and
+//!   witnessing networks of transparency logs (see `akd_core::gossip`)
+//! - `json`: Enables the JSON encoding of proofs in `proto::json`, with base64url bytes and the field names of the protobuf
+//!   specification, for embedding proofs in web API responses
 //! - `serde_serialization`: Will enable `serde` serialization support on all public structs used in storage & transmission operations. This is helpful
-//! in the event you wish to directly serialize the structures to transmit between library <-> storage layer or library <-> clients. If you're
-//! also utilizing VRFs (see (2.) below) it will additionally enable the _serde_ feature in the ed25519-dalek crate.
+//!   in the event you wish to directly serialize the structures to transmit between library <-> storage layer or library <-> clients. If you're
+//!   also utilizing VRFs (see (2.) below) it will additionally enable the _serde_ feature in the ed25519-dalek crate.
+//! - `bincode_codec`, `msgpack_codec`, `protobuf_codec`: Enable the corresponding codecs of [storage::codec], which serialize
+//!   storage records to bytes for a [storage::StorageManager] configured with a record codec
 //! - `runtime_metrics`: Collects metrics on the accesses to the storage layer
+//! - `profiling`: Records per-phase timing reports for publish, lookup, batch lookup, and audit operations, and enables `Directory::publish_with_report`
+//! - `metrics`: Emits counters, gauges, and histograms through the `metrics` facade (see [instrumentation])
+//! - `tracing`: Records directory and storage operations as `tracing` spans, which carry OpenTelemetry context (see [instrumentation])
+//! - `log`: Emits the library's `tracing` events, which carry structured fields such as the epoch and batch size, as `log` records
+//!   when no `tracing` subscriber has been installed. Enabled by default, for applications which only install a `log` logger
 //! - `public_tests`: Will expose some internal sanity testing functionality, which is often helpful so you don't have to write all your own
-//! unit test cases when implementing a storage layer yourself. This helps guarantee the sanity of a given storage implementation. Should be
-//! used only in unit testing scenarios by altering your Cargo.toml as such:
+//!   unit test cases when implementing a storage layer yourself. This helps guarantee the sanity of a given storage implementation. The
+//!   directory tests of the `conformance` module can likewise be run against custom configurations and storage layers. Should be
+//!   used only in unit testing scenarios by altering your Cargo.toml as such:
 //!
 
 #![warn(missing_docs)]
 #![allow(clippy::multiple_crate_versions)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
-#[cfg(feature = "rand")]
-extern crate rand;
-
 // Due to the amount of types an implementing storage layer needs to access,
 // it's quite unreasonable to expose them all at the crate root, and a storage
 // implementer will simply need to import the necessary inner types which are
 // a dependency of ths [`Storage`] trait anyways
 
+#[cfg(feature = "server")]
+pub mod anchoring;
+#[cfg(feature = "server")]
 pub mod append_only_zks;
+#[cfg(feature = "server")]
+pub mod audit_lite;
+#[cfg(feature = "server")]
 pub mod auditor;
 pub mod client;
+#[cfg(feature = "server")]
+pub mod coalescing;
+#[cfg(feature = "server")]
+pub mod consolidation;
+#[cfg(feature = "server")]
+pub mod delta_sync;
+#[cfg(feature = "server")]
 pub mod directory;
+#[cfg(feature = "server")]
+pub mod divergence;
 pub mod errors;
+#[cfg(feature = "server")]
+pub mod health;
 pub mod helper_structs;
+#[cfg(feature = "server")]
+pub mod hooks;
+#[cfg(feature = "server")]
+pub mod hot_labels;
+#[cfg(feature = "server")]
+pub mod instrumentation;
+#[cfg(feature = "server")]
+pub mod maintenance;
+#[cfg(feature = "server")]
+pub mod prefetch;
+pub mod prelude;
+#[cfg(feature = "server")]
+pub mod profiling;
+#[cfg(feature = "server")]
+pub mod proof_cache;
+#[cfg(feature = "server")]
+pub mod proof_chunks;
+#[cfg(feature = "server")]
+pub mod publish_queue;
+#[cfg(feature = "server")]
+pub mod replication;
+#[cfg(all(feature = "server", feature = "experimental"))]
+pub mod runtime_directory;
+#[cfg(feature = "server")]
+pub mod scheduling;
+#[cfg(feature = "server")]
+pub mod sharded_directory;
+#[cfg(feature = "server")]
 pub mod storage;
+#[cfg(feature = "server")]
 pub mod tree_node;
+#[cfg(feature = "server")]
+pub mod visualization;
 
 #[cfg(feature = "public_auditing")]
 pub mod local_auditing;
 
+#[cfg(feature = "gossip")]
+pub use akd_core::gossip;
+#[cfg(feature = "public_auditing")]
+pub use akd_core::proto;
 pub use akd_core::{
-    configuration, configuration::*, ecvrf, hash, hash::Digest, proto, types::*, verify, ARITY,
+    audit_path, canonical, configuration, configuration::*, coniks, ecvrf, hash, hash::Digest,
+    sharding, signing, spot_check, types::*, verify, ARITY,
 };
 
 #[macro_use]
 mod utils;
 
 // ========== Type re-exports which are commonly used ========== //
-pub use append_only_zks::Azks;
+// (see also the [prelude], which gathers them with the verification functions)
 pub use client::HistoryVerificationParams;
-pub use directory::{Directory, HistoryParams};
+pub use errors::{AkdError, ErrorKind};
 pub use helper_structs::EpochHash;
+#[cfg(feature = "server")]
+pub use {
+    append_only_zks::Azks,
+    directory::{Directory, DirectoryBuilder, HistoryParams, PublishLimits, ReadOnlyDirectory},
+    storage::StorageManager,
+};
 
 // ========== Constants and type aliases ========== //
 #[cfg(any(test, feature = "public_tests"))]
+pub mod conformance;
+#[cfg(any(test, feature = "public_tests"))]
 pub mod test_utils;
 #[cfg(test)]
 mod tests;
 
-/// The length of a leaf node's label (in bits)
+/// The length of a leaf node's label (in bits), unless the configuration truncates node labels
+/// (see [Configuration::node_label_len])
 pub const LEAF_LEN: u32 = 256;
 
 /// The label used for a root node
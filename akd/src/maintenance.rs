@@ -0,0 +1,222 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Scheduling of background maintenance for a directory.
+//!
+//! A [MaintenanceTask] can be run once with `Directory::run_maintenance_task`, or a set of
+//! tasks can be run repeatedly in the background with `Directory::spawn_maintenance`. Each
+//! [ScheduledTask] is run on its own interval, delayed by a random jitter so that the tasks
+//! of several directory instances sharing the same storage do not run in lockstep, and the
+//! [MaintenanceConfig] limits how many tasks may be running at the same time.
+//!
+//! The values of old versions can also be tombstoned in bulk with a [RetentionPolicy], which
+//! unlike [MaintenanceTask::Pruning] can retain the values of some labels for longer (see
+//! `Directory::apply_retention_policy`).
+
+use crate::errors::AkdError;
+use crate::AkdLabel;
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// A maintenance task which can be run against a directory
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MaintenanceTask {
+    /// Evicts all of the expired items from the cache, rather than waiting for them to
+    /// be evicted by the next operation which accesses the cache
+    Compaction,
+    /// Loads the nodes in the top `depth` levels of the tree into the cache, since these
+    /// nodes are accessed by every proof
+    CacheWarmup {
+        /// The number of levels below the root to load
+        depth: u8,
+    },
+    /// Tombstones the values of all versions which were published more than
+    /// `retain_epochs` epochs ago, except for the latest version of each label
+    Pruning {
+        /// The number of most recent epochs whose values are retained
+        retain_epochs: u64,
+    },
+    /// Walks `sample_size` random paths from the root of the tree to a leaf, checking that
+    /// the hash of each node along the path matches the hashes of its children
+    IntegrityCheck {
+        /// The number of paths to check
+        sample_size: usize,
+    },
+    /// Consolidates the epochs published since the previous audit epoch into a new audit
+    /// epoch, if any have been published (see [crate::consolidation])
+    Consolidation,
+    /// Loads the nodes of the labels which are likely to be looked up next into the cache,
+    /// as predicted from the recent lookups, if prefetching is enabled (see [crate::prefetch])
+    Prefetch,
+}
+
+/// How long the values of a directory's labels are retained before they are tombstoned. The
+/// value of the latest version of a label is always retained.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RetentionPolicy {
+    /// The number of most recent epochs whose values are retained, for labels without an override
+    pub retain_epochs: u64,
+    /// The labels whose values are retained for a different number of epochs, or indefinitely
+    /// when [None] (e.g. labels under a legal hold)
+    pub overrides: HashMap<AkdLabel, Option<u64>>,
+}
+
+impl RetentionPolicy {
+    /// Creates a policy which retains the values of the most recent `retain_epochs` epochs for
+    /// every label
+    pub fn new(retain_epochs: u64) -> Self {
+        Self {
+            retain_epochs,
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Retains the values of a label for a different number of epochs, or indefinitely when
+    /// `retain_epochs` is [None]
+    pub fn with_override(mut self, label: AkdLabel, retain_epochs: Option<u64>) -> Self {
+        self.overrides.insert(label, retain_epochs);
+        self
+    }
+}
+
+/// A [MaintenanceTask] which is run repeatedly in the background
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ScheduledTask {
+    /// The task to run
+    pub task: MaintenanceTask,
+    /// The time between the end of one run of the task and the start of the next
+    pub interval: Duration,
+    /// The maximum random delay which is added to each interval
+    pub jitter: Duration,
+}
+
+/// The configuration of the background maintenance of a directory
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MaintenanceConfig {
+    /// The tasks to run
+    pub tasks: Vec<ScheduledTask>,
+    /// The maximum number of tasks which may be running at the same time
+    pub max_concurrent_tasks: usize,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            tasks: vec![],
+            max_concurrent_tasks: 1,
+        }
+    }
+}
+
+/// The outcome of a single run of a [MaintenanceTask]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MaintenanceReport {
+    /// The task which was run
+    pub task: MaintenanceTask,
+    /// The number of records which were evicted, loaded, tombstoned, or checked by the
+    /// task, or the number of epochs which it consolidated
+    pub records: usize,
+    /// The time taken to run the task
+    pub duration: Duration,
+}
+
+impl std::fmt::Display for MaintenanceReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} processed {} records in {:?}",
+            self.task, self.records, self.duration
+        )
+    }
+}
+
+/// A handle to the background maintenance of a directory. The maintenance
+/// is stopped when the handle is shut down or dropped.
+pub struct MaintenanceHandle {
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl MaintenanceHandle {
+    /// Stops all of the scheduled tasks. A task which is currently running is
+    /// interrupted at its next suspension point.
+    pub fn shutdown(self) {
+        // The tasks are aborted when the handle is dropped
+    }
+
+    /// Returns whether all of the scheduled tasks have stopped
+    pub fn is_finished(&self) -> bool {
+        self.tasks.iter().all(|task| task.is_finished())
+    }
+}
+
+impl Drop for MaintenanceHandle {
+    fn drop(&mut self) {
+        for task in self.tasks.iter() {
+            task.abort();
+        }
+    }
+}
+
+/// Spawns a background task for each of the scheduled tasks in the config, which runs
+/// the task with the provided function on its interval
+pub(crate) fn spawn<F, Fut>(config: MaintenanceConfig, run: F) -> MaintenanceHandle
+where
+    F: Fn(MaintenanceTask) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = Result<MaintenanceReport, AkdError>> + Send,
+{
+    let permits = Arc::new(Semaphore::new(config.max_concurrent_tasks.max(1)));
+    let tasks = config
+        .tasks
+        .into_iter()
+        .map(|scheduled| {
+            let permits = permits.clone();
+            let run = run.clone();
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(scheduled.interval + random_duration(scheduled.jitter))
+                        .await;
+                    let Ok(_permit) = permits.acquire().await else {
+                        break;
+                    };
+                    match run(scheduled.task).await {
+                        Ok(report) => info!(
+                            task = ?report.task,
+                            records = report.records,
+                            duration_ms = report.duration.as_millis() as u64,
+                            "Maintenance task completed"
+                        ),
+                        Err(err) => {
+                            warn!(task = ?scheduled.task, error = %err, "Maintenance task failed")
+                        }
+                    }
+                }
+            })
+        })
+        .collect();
+    MaintenanceHandle { tasks }
+}
+
+/// Returns a random number, which is not suitable for cryptographic use
+pub(crate) fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+/// Returns a random duration between zero and the provided maximum
+fn random_duration(max: Duration) -> Duration {
+    match max.as_nanos() {
+        0 => Duration::ZERO,
+        nanos => Duration::from_nanos((random_u64() as u128 % nanos) as u64),
+    }
+}
@@ -0,0 +1,213 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Prefetching of the tree nodes of the labels which are likely to be looked up next.
+//!
+//! On remote storage, the tail latency of lookups is dominated by those which have to load the
+//! nodes along their paths from the database. Lookups are not independent, though: a client
+//! which comes online typically looks up its whole set of contacts at once, and some labels are
+//! looked up much more often than others. With `Directory::set_prefetching`, the directory
+//! records its recent lookups, and `Directory::prefetch` (or the
+//! [MaintenanceTask::Prefetch](crate::maintenance::MaintenanceTask::Prefetch) task, scheduled to
+//! run between requests) loads the nodes of the labels which are likely to be looked up next
+//! into the cache, ahead of the lookups themselves:
+//!
+//! 1. The companions of the labels looked up since the last prefetch, which are the labels that
+//!    were looked up within [PrefetchConfig::co_lookup_window] of them (or in the same batch
+//!    lookup) earlier in the recorded history, ranked by how often they were.
+//! 2. The labels which are looked up most often in the recorded history.
+//!
+//! Prefetching only loads records into the cache of the storage manager, so it has no effect
+//! on a directory without a cache, and never changes the proofs which are served.
+
+use crate::AkdLabel;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+/// The options of the prefetching of a directory
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PrefetchConfig {
+    /// The number of most recent lookups from which the access patterns are learned
+    pub history_len: usize,
+    /// Lookups which are at most this far apart are considered to be part of the same set of
+    /// lookups (e.g. of a client looking up its contacts)
+    pub co_lookup_window: Duration,
+    /// The maximum number of labels which are prefetched at once
+    pub max_labels: usize,
+}
+
+impl Default for PrefetchConfig {
+    fn default() -> Self {
+        Self {
+            history_len: 4096,
+            co_lookup_window: Duration::from_millis(100),
+            max_labels: 64,
+        }
+    }
+}
+
+/// The recent lookups of a directory, from which the labels to prefetch are predicted
+pub(crate) struct AccessTracker {
+    config: PrefetchConfig,
+    /// The most recent lookups, oldest first
+    history: VecDeque<(Instant, AkdLabel)>,
+    /// The number of lookups recorded since the last prefetch
+    num_new: usize,
+}
+
+impl AccessTracker {
+    pub(crate) fn new(config: PrefetchConfig) -> Self {
+        Self {
+            config,
+            history: VecDeque::with_capacity(config.history_len),
+            num_new: 0,
+        }
+    }
+
+    /// Records lookups of the given (normalized) labels, made together at the current time
+    pub(crate) fn record(&mut self, labels: &[AkdLabel]) {
+        self.record_at(Instant::now(), labels);
+    }
+
+    /// Records lookups of the given labels, made together at the given time
+    pub(crate) fn record_at(&mut self, at: Instant, labels: &[AkdLabel]) {
+        for label in labels {
+            self.history.push_back((at, label.clone()));
+        }
+        while self.history.len() > self.config.history_len {
+            self.history.pop_front();
+        }
+        self.num_new = (self.num_new + labels.len()).min(self.history.len());
+    }
+
+    /// Returns the labels to prefetch, most likely to be looked up first: the companions of the
+    /// labels looked up since the last prefetch, followed by the most frequently looked up labels
+    pub(crate) fn take_candidates(&mut self) -> Vec<AkdLabel> {
+        let mut candidates = vec![];
+        let mut seen = HashSet::new();
+        let new_lookups = self.history.len() - self.num_new;
+        let triggers = self
+            .history
+            .iter()
+            .skip(new_lookups)
+            .rev()
+            .map(|(_, label)| label)
+            .filter(|label| seen.insert(*label))
+            .take(self.config.max_labels)
+            .collect::<Vec<_>>();
+        let mut selected = HashSet::new();
+        for trigger in triggers {
+            if candidates.len() >= self.config.max_labels {
+                break;
+            }
+            for companion in self.companions(trigger) {
+                if selected.insert(companion.clone()) {
+                    candidates.push(companion);
+                }
+            }
+        }
+        for label in self.most_frequent() {
+            if selected.insert(label.clone()) {
+                candidates.push(label);
+            }
+        }
+        candidates.truncate(self.config.max_labels);
+        self.num_new = 0;
+        candidates
+    }
+
+    /// The labels which were looked up within the co-lookup window of a lookup of `label`,
+    /// ranked by the number of times they were
+    fn companions(&self, label: &AkdLabel) -> Vec<AkdLabel> {
+        let window = self.config.co_lookup_window;
+        let mut counts = HashMap::<&AkdLabel, usize>::new();
+        let occurrences = self
+            .history
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, other))| other == label);
+        for (index, (at, _)) in occurrences {
+            // The history is in the order of the lookups, so only the lookups next to an
+            // occurrence can be within its window
+            let before = self
+                .history
+                .range(..index)
+                .rev()
+                .take_while(|(other_at, _)| at.duration_since(*other_at) <= window);
+            let after = self
+                .history
+                .range(index + 1..)
+                .take_while(|(other_at, _)| other_at.duration_since(*at) <= window);
+            for (_, companion) in before.chain(after).filter(|(_, other)| other != label) {
+                *counts.entry(companion).or_default() += 1;
+            }
+        }
+        ranked(counts)
+    }
+
+    /// The labels in the history, ranked by the number of times they were looked up
+    fn most_frequent(&self) -> Vec<AkdLabel> {
+        let mut counts = HashMap::<&AkdLabel, usize>::new();
+        for (_, label) in self.history.iter() {
+            *counts.entry(label).or_default() += 1;
+        }
+        ranked(counts)
+    }
+}
+
+/// Ranks labels by decreasing count, and then by label, so that the ranking is deterministic
+fn ranked(counts: HashMap<&AkdLabel, usize>) -> Vec<AkdLabel> {
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_by(|(label, count), (other_label, other_count)| {
+        other_count.cmp(count).then_with(|| label.cmp(other_label))
+    });
+    counts.into_iter().map(|(label, _)| label.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates() {
+        let mut tracker = AccessTracker::new(PrefetchConfig {
+            history_len: 16,
+            co_lookup_window: Duration::from_millis(100),
+            max_labels: 4,
+        });
+        let label = |name: &str| AkdLabel::from(name);
+        let start = Instant::now();
+        let at = |millis: u64| start + Duration::from_millis(millis);
+
+        // "bob", "carol", and "dave" are looked up together with "alice", while "zed" is looked
+        // up on its own
+        tracker.record_at(at(0), &[label("alice")]);
+        tracker.record_at(at(10), &[label("bob"), label("carol")]);
+        tracker.record_at(at(500), &[label("zed")]);
+        tracker.record_at(at(1000), &[label("alice")]);
+        tracker.record_at(at(1050), &[label("carol")]);
+        tracker.record_at(at(1100), &[label("dave")]);
+        assert_eq!(
+            vec![label("alice"), label("carol"), label("bob"), label("dave")],
+            tracker.take_candidates()
+        );
+
+        // once "alice" is looked up again, her companions are prefetched first
+        tracker.record_at(at(2000), &[label("alice")]);
+        assert_eq!(
+            vec![label("carol"), label("bob"), label("dave"), label("alice")],
+            tracker.take_candidates()
+        );
+
+        // only the most recent lookups are kept
+        for millis in 0..16 {
+            tracker.record_at(at(3000 + 1000 * millis), &[label("zed")]);
+        }
+        assert_eq!(vec![label("zed")], tracker.take_candidates());
+    }
+}
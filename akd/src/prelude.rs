@@ -0,0 +1,76 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! The types and functions which most servers and clients of a directory need, so that they
+//! can be brought into scope with a single import:
+//!
+//! ```
+//! use akd::prelude::*;
+//!
+//! type Config = WhatsAppV1Configuration;
+//!
+//! # tokio_test::block_on(async {
+//! let db = AsyncInMemoryDatabase::new();
+//! let akd = Directory::<Config, _, _>::new(StorageManager::new_no_cache(db), HardCodedAkdVRF {})
+//!     .await
+//!     .expect("Could not create a new directory");
+//! let EpochHash(epoch, root_hash) = akd
+//!     .publish(vec![(AkdLabel::from("hello"), AkdValue::from("world"))])
+//!     .await
+//!     .expect("Error with publishing");
+//! let (proof, _) = akd.lookup(AkdLabel::from("hello")).await.expect("Could not look up");
+//!
+//! let public_key = akd.get_public_key().await.expect("Could not fetch the public key");
+//! let result = lookup_verify::<Config>(
+//!     public_key.as_bytes(),
+//!     root_hash,
+//!     epoch,
+//!     AkdLabel::from("hello"),
+//!     proof,
+//! )
+//! .expect("The lookup proof did not verify");
+//! assert_eq!(AkdValue::from("world"), result.value);
+//! # });
+//! ```
+//!
+//! The server-side types are those needed to create a `Directory` over a storage layer and a
+//! VRF, publish to it, and generate proofs (with the `server` feature). The client-side types are the proofs themselves,
+//! the functions which verify them, and their results. More specialized functionality is
+//! found in the modules of the crate.
+
+// Configurations
+#[cfg(feature = "experimental")]
+pub use crate::configuration::ExperimentalConfiguration;
+#[cfg(feature = "whatsapp_v1")]
+pub use crate::configuration::WhatsAppV1Configuration;
+pub use crate::configuration::{Configuration, DomainLabel, ExampleLabel};
+
+// Servers
+pub use crate::ecvrf::{HardCodedAkdVRF, VRFKeyStorage, VRFPublicKey};
+pub use crate::errors::{AkdError, ErrorKind};
+#[cfg(feature = "server")]
+pub use crate::{
+    directory::{Directory, DirectoryBuilder, HistoryParams, PublishLimits, ReadOnlyDirectory},
+    storage::memory::AsyncInMemoryDatabase,
+    storage::{Database, StorageManager},
+};
+
+// Data and proofs
+pub use crate::hash::Digest;
+pub use crate::helper_structs::EpochHash;
+pub use crate::{
+    AkdLabel, AkdValue, AppendOnlyProof, HistoryProof, LookupProof, LookupResult,
+    MultiEpochAppendOnlyProof, VerifyResult,
+};
+
+// Clients and auditors
+#[cfg(feature = "server")]
+pub use crate::auditor::{audit_verify, audit_verify_multi_epoch};
+pub use crate::client::{
+    key_history_verify, lookup_verify, lookup_verify_result, HistoryVerificationParams,
+    VerificationError,
+};
@@ -0,0 +1,241 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Phase timing breakdowns for directory operations.
+//!
+//! When the `profiling` feature is enabled, publish, lookup, batch lookup, and audit
+//! operations record how long they spent in each of their named [Phase]s, and the most recent
+//! [TimingReport] for each [Operation] can be retrieved with `Directory::last_timing_report`.
+//! Each report is also logged at the info level. The report of a publish can instead be returned
+//! directly with `Directory::publish_with_report`, and also records how many bytes each
+//! phase read from or wrote to storage. Without the feature, no timing is recorded.
+
+use std::time::Duration;
+#[cfg(feature = "profiling")]
+use std::time::Instant;
+
+/// A directory operation which is instrumented with phase timings
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub enum Operation {
+    /// [crate::Directory::publish]
+    Publish,
+    /// [crate::Directory::lookup]
+    Lookup,
+    /// [crate::Directory::batch_lookup]
+    BatchLookup,
+    /// [crate::Directory::audit]
+    Audit,
+}
+
+/// A named phase of a directory operation
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub enum Phase {
+    /// Loading the tree nodes and user states which the operation will access from storage
+    Preload,
+    /// Evaluating the VRF to compute the tree labels of the updated versions
+    VrfEvaluation,
+    /// Inserting the updated versions into the tree, and recomputing the node hashes
+    TreeMutation,
+    /// Computing commitments and root hashes
+    Hash,
+    /// Writing the results of the operation to storage
+    Write,
+    /// Committing the writes of the operation to the database
+    Commit,
+    /// Assembling the proofs returned by the operation
+    ProofAssembly,
+}
+
+/// The total time spent in a single phase of an operation
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct PhaseTiming {
+    /// The phase
+    pub phase: Phase,
+    /// The time spent in the phase, summed over all of the times the phase was entered
+    pub duration: Duration,
+    /// The number of bytes which the phase read from or wrote to storage, where measured
+    pub bytes: u64,
+}
+
+/// A machine-readable breakdown of how long an operation spent in each of its phases
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct TimingReport {
+    /// The operation which was timed
+    pub operation: Operation,
+    /// The phases of the operation, in the order in which they were first entered
+    pub phases: Vec<PhaseTiming>,
+    /// The total duration of the operation, including any time not attributed to a phase
+    pub total: Duration,
+}
+
+impl TimingReport {
+    /// Returns the total time spent in the provided phase, which is zero if
+    /// the operation never entered the phase
+    pub fn phase_duration(&self, phase: Phase) -> Duration {
+        self.phases
+            .iter()
+            .find(|timing| timing.phase == phase)
+            .map_or(Duration::ZERO, |timing| timing.duration)
+    }
+
+    /// Returns the number of bytes which the provided phase read from or wrote to
+    /// storage, which is zero if the operation never entered the phase
+    pub fn phase_bytes(&self, phase: Phase) -> u64 {
+        self.phases
+            .iter()
+            .find(|timing| timing.phase == phase)
+            .map_or(0, |timing| timing.bytes)
+    }
+}
+
+impl std::fmt::Display for TimingReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} took {:?}", self.operation, self.total)?;
+        for timing in self.phases.iter() {
+            write!(f, ", {:?} = {:?}", timing.phase, timing.duration)?;
+            if timing.bytes > 0 {
+                write!(f, " ({} bytes)", timing.bytes)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Records the phases of a single operation. All of the methods are no-ops
+/// unless the `profiling` feature is enabled.
+pub(crate) struct PhaseTimer {
+    #[cfg(feature = "profiling")]
+    operation: Operation,
+    #[cfg(feature = "profiling")]
+    start: Instant,
+    #[cfg(feature = "profiling")]
+    current: Option<(Phase, Instant)>,
+    #[cfg(feature = "profiling")]
+    phases: Vec<PhaseTiming>,
+}
+
+impl PhaseTimer {
+    pub(crate) fn new(operation: Operation) -> Self {
+        #[cfg(feature = "profiling")]
+        {
+            Self {
+                operation,
+                start: Instant::now(),
+                current: None,
+                phases: vec![],
+            }
+        }
+        #[cfg(not(feature = "profiling"))]
+        {
+            let _ = operation;
+            Self {}
+        }
+    }
+
+    /// Ends the current phase (if any) and starts timing the provided one
+    pub(crate) fn begin(&mut self, phase: Phase) {
+        self.end();
+        #[cfg(feature = "profiling")]
+        {
+            self.current = Some((phase, Instant::now()));
+        }
+        #[cfg(not(feature = "profiling"))]
+        let _ = phase;
+    }
+
+    /// Ends the current phase, if any
+    pub(crate) fn end(&mut self) {
+        #[cfg(feature = "profiling")]
+        if let Some((phase, tic)) = self.current.take() {
+            let duration = tic.elapsed();
+            self.timing(phase).duration += duration;
+        }
+    }
+
+    /// Attributes a number of bytes read from or written to storage to the provided phase.
+    /// The number is only computed if the `profiling` feature is enabled.
+    pub(crate) fn add_bytes(&mut self, phase: Phase, bytes: impl FnOnce() -> usize) {
+        #[cfg(feature = "profiling")]
+        {
+            self.timing(phase).bytes += bytes() as u64;
+        }
+        #[cfg(not(feature = "profiling"))]
+        let _ = (phase, bytes);
+    }
+
+    #[cfg(feature = "profiling")]
+    fn timing(&mut self, phase: Phase) -> &mut PhaseTiming {
+        let index = match self.phases.iter().position(|timing| timing.phase == phase) {
+            Some(index) => index,
+            None => {
+                self.phases.push(PhaseTiming {
+                    phase,
+                    duration: Duration::ZERO,
+                    bytes: 0,
+                });
+                self.phases.len() - 1
+            }
+        };
+        &mut self.phases[index]
+    }
+
+    /// Ends the current phase and produces the report for the operation
+    #[cfg(feature = "profiling")]
+    pub(crate) fn finish(mut self) -> TimingReport {
+        self.end();
+        TimingReport {
+            operation: self.operation,
+            phases: self.phases,
+            total: self.start.elapsed(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "profiling"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_timer_accumulates_repeated_phases() {
+        let mut timer = PhaseTimer::new(Operation::Audit);
+        timer.begin(Phase::Preload);
+        std::thread::sleep(Duration::from_millis(2));
+        timer.begin(Phase::ProofAssembly);
+        timer.begin(Phase::Preload);
+        std::thread::sleep(Duration::from_millis(2));
+        timer.add_bytes(Phase::Write, || 10);
+        timer.add_bytes(Phase::Write, || 5);
+        let report = timer.finish();
+
+        assert_eq!(Operation::Audit, report.operation);
+        assert_eq!(
+            vec![Phase::Preload, Phase::ProofAssembly, Phase::Write],
+            report.phases.iter().map(|t| t.phase).collect::<Vec<_>>()
+        );
+        assert!(report.phase_duration(Phase::Preload) >= Duration::from_millis(4));
+        assert_eq!(Duration::ZERO, report.phase_duration(Phase::Write));
+        assert_eq!(15, report.phase_bytes(Phase::Write));
+        assert_eq!(0, report.phase_bytes(Phase::Preload));
+        assert!(report.total >= report.phase_duration(Phase::Preload));
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Caching of the lookup proofs generated by a directory.
+//!
+//! Between two epochs, the lookup proof of a label does not change, yet each lookup of it
+//! traverses the tree again. With `Directory::set_proof_cache_capacity`, the proofs generated by
+//! `Directory::lookup` are cached, and later lookups of the same label at the same epoch are
+//! answered with a clone of the cached proof. Unlike the hot labels (see [crate::hot_labels]),
+//! which are designated in advance, the proof of any label which is looked up is cached.
+//!
+//! A cached proof is keyed by its label along with the epoch and root hash at which it was
+//! generated, and a lookup only uses a proof whose key matches the epoch and root hash which the
+//! lookup is served at. A proof of an earlier epoch is therefore never served, even by a lookup
+//! which races with a publish. A publish leaves the cache as it is, so that it does not wait
+//! for the cache to be invalidated: the proofs of earlier epochs are discarded once the first
+//! proof of the new epoch is stored, after which a proof which was generated at an earlier epoch
+//! is no longer stored. A follower discards them as soon as it refreshes to an epoch published
+//! by its leader, and a rollback discards every cached proof.
+
+use crate::{AkdLabel, EpochHash, LookupProof};
+
+use std::collections::{HashMap, VecDeque};
+
+/// The key of a cached lookup proof, which is versioned by the epoch and root hash at which
+/// the proof was generated
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct ProofCacheKey {
+    pub(crate) epoch_hash: EpochHash,
+    pub(crate) label: AkdLabel,
+}
+
+/// The cached lookup proofs of a directory, which hold at most `capacity` proofs
+#[derive(Default)]
+pub(crate) struct ProofCache {
+    capacity: usize,
+    /// The latest epoch which the cache has moved to
+    epoch_hash: Option<EpochHash>,
+    proofs: HashMap<ProofCacheKey, LookupProof>,
+    /// The keys of the cached proofs, in the order in which they were stored
+    order: VecDeque<ProofCacheKey>,
+}
+
+impl ProofCache {
+    /// The maximum number of cached proofs, where zero disables the cache
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Sets the maximum number of cached proofs, evicting the oldest proofs beyond it
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    /// The number of cached proofs
+    pub(crate) fn len(&self) -> usize {
+        self.proofs.len()
+    }
+
+    /// The cached proof for a key, if any
+    pub(crate) fn get(&self, key: &ProofCacheKey) -> Option<LookupProof> {
+        self.proofs.get(key).cloned()
+    }
+
+    /// Stores a proof, unless the cache is disabled or has moved past the epoch of its key. A
+    /// proof of a later epoch moves the cache to that epoch. Returns the number of proofs which
+    /// were discarded by moving to a later epoch.
+    pub(crate) fn insert(&mut self, key: ProofCacheKey, proof: LookupProof) -> usize {
+        if self.capacity == 0 {
+            return 0;
+        }
+        let num_discarded = match &self.epoch_hash {
+            Some(epoch_hash) if *epoch_hash == key.epoch_hash => 0,
+            Some(epoch_hash) if epoch_hash.epoch() >= key.epoch_hash.epoch() => return 0,
+            _ => self.advance(key.epoch_hash.clone()),
+        };
+        if self.proofs.insert(key.clone(), proof).is_none() {
+            self.order.push_back(key);
+        }
+        self.evict();
+        num_discarded
+    }
+
+    /// Moves the cache to a new epoch, discarding the proofs of every other epoch, and returns
+    /// the number of discarded proofs. Moving to an earlier epoch than the current one has no
+    /// effect.
+    pub(crate) fn advance(&mut self, epoch_hash: EpochHash) -> usize {
+        if let Some(current) = &self.epoch_hash {
+            if *current == epoch_hash || current.epoch() > epoch_hash.epoch() {
+                return 0;
+            }
+        }
+        let num_proofs = self.proofs.len();
+        self.proofs.retain(|key, _| key.epoch_hash == epoch_hash);
+        self.order.retain(|key| key.epoch_hash == epoch_hash);
+        self.epoch_hash = Some(epoch_hash);
+        num_proofs - self.proofs.len()
+    }
+
+    /// Discards every cached proof, and forgets the epoch which the cache has moved to
+    pub(crate) fn clear(&mut self) -> usize {
+        let num_proofs = self.proofs.len();
+        self.proofs.clear();
+        self.order.clear();
+        self.epoch_hash = None;
+        num_proofs
+    }
+
+    fn evict(&mut self) {
+        while self.proofs.len() > self.capacity {
+            match self.order.pop_front() {
+                Some(key) => {
+                    self.proofs.remove(&key);
+                }
+                None => break,
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Parallel, chunked, and resumable generation of append-only proofs.
+//!
+//! `Directory::audit` builds the append-only proof of each epoch in a single pass, while
+//! holding off any publish from committing. `Directory::audit_chunked` instead splits the
+//! proof of each epoch into [AppendOnlyProofChunk]s: a head chunk, which holds the elements
+//! of the proof found in the top levels of the tree and lists the subtrees below them, and
+//! one chunk for each of those subtrees. The subtree chunks are built in parallel, and each
+//! only holds off publishes while it is being built, so that a long audit does not extend
+//! the publish critical path.
+//!
+//! Each completed chunk is saved to a [ProofChunkStore]. If the generation is interrupted
+//! (e.g. by a crash), calling `Directory::audit_chunked` again with the same store resumes
+//! from the saved chunks, and only builds the chunks which are missing. The chunks of an
+//! epoch are assembled into a [SingleAppendOnlyProof] once all of them are complete, and
+//! the result is the same [AppendOnlyProof](crate::AppendOnlyProof) that an auditor verifies with
+//! [audit_verify](crate::auditor::audit_verify).
+
+use crate::errors::{AkdError, AuditorError, StorageError};
+use crate::{AzksElement, NodeLabel, SingleAppendOnlyProof};
+
+use async_trait::async_trait;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// A part of the append-only proof from an epoch to the epoch after it
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct AppendOnlyProofChunk {
+    /// The epoch from which the proof starts
+    pub epoch: u64,
+    /// The root of the subtree whose elements the chunk holds, or [None] for the head chunk,
+    /// which holds the elements above the subtrees
+    pub subtree: Option<NodeLabel>,
+    /// For the head chunk, the roots of the subtrees of the other chunks of the proof
+    pub subtrees: Vec<NodeLabel>,
+    /// The leaves inserted in the epoch after `epoch`
+    pub inserted: Vec<AzksElement>,
+    /// The nodes which are unchanged since `epoch`
+    pub unchanged_nodes: Vec<AzksElement>,
+}
+
+impl AppendOnlyProofChunk {
+    /// Whether this is the head chunk of its proof
+    pub fn is_head(&self) -> bool {
+        self.subtree.is_none()
+    }
+}
+
+/// Assembles the chunks of the append-only proof of an epoch, which fails if any of the
+/// subtrees listed by the head chunk is missing
+pub fn assemble_chunks(
+    head: &AppendOnlyProofChunk,
+    chunks: &[AppendOnlyProofChunk],
+) -> Result<SingleAppendOnlyProof, AkdError> {
+    let by_subtree = chunks
+        .iter()
+        .filter(|chunk| chunk.epoch == head.epoch)
+        .filter_map(|chunk| chunk.subtree.map(|subtree| (subtree, chunk)))
+        .collect::<HashMap<_, _>>();
+    let mut proof = SingleAppendOnlyProof {
+        inserted: head.inserted.clone(),
+        unchanged_nodes: head.unchanged_nodes.clone(),
+    };
+    for subtree in head.subtrees.iter() {
+        let chunk = by_subtree.get(subtree).ok_or_else(|| {
+            AkdError::AuditErr(AuditorError::VerifyAuditProof(format!(
+                "The chunk of subtree {subtree:?} of the proof from epoch {} is missing",
+                head.epoch
+            )))
+        })?;
+        proof.inserted.extend_from_slice(&chunk.inserted);
+        proof
+            .unchanged_nodes
+            .extend_from_slice(&chunk.unchanged_nodes);
+    }
+    Ok(proof)
+}
+
+/// The options of a chunked audit
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ChunkedAuditOptions {
+    /// The depth in the tree below which the proof of each epoch is split into subtrees, so
+    /// that there are at most `2^split_depth` subtree chunks per epoch
+    pub split_depth: u8,
+    /// The maximum number of chunks which are built at the same time
+    pub max_concurrent_chunks: usize,
+}
+
+impl Default for ChunkedAuditOptions {
+    fn default() -> Self {
+        Self {
+            split_depth: 4,
+            max_concurrent_chunks: 4,
+        }
+    }
+}
+
+/// A durable store of the completed chunks of append-only proofs, from which an interrupted
+/// chunked audit resumes
+#[async_trait]
+pub trait ProofChunkStore: Send + Sync {
+    /// Durably records a completed chunk. This must not return until the chunk would survive
+    /// a crash.
+    async fn save(&self, chunk: AppendOnlyProofChunk) -> Result<(), StorageError>;
+
+    /// Retrieves the completed chunks of the proof from an epoch, in any order
+    async fn chunks(&self, epoch: u64) -> Result<Vec<AppendOnlyProofChunk>, StorageError>;
+
+    /// Discards the chunks of the proof from an epoch, once the proof has been assembled
+    async fn clear(&self, epoch: u64) -> Result<(), StorageError>;
+}
+
+/// An in-memory [ProofChunkStore]. As it does not survive the process, this is only
+/// useful for testing, or when the store's lifetime is managed by the caller.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryProofChunkStore {
+    chunks: Arc<Mutex<BTreeMap<u64, Vec<AppendOnlyProofChunk>>>>,
+}
+
+impl InMemoryProofChunkStore {
+    /// Creates a new, empty, in-memory proof chunk store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ProofChunkStore for InMemoryProofChunkStore {
+    async fn save(&self, chunk: AppendOnlyProofChunk) -> Result<(), StorageError> {
+        let mut chunks = self.chunks.lock().map_err(|err| {
+            StorageError::Other(format!("Proof chunk store lock poisoned: {err}"))
+        })?;
+        let epoch_chunks = chunks.entry(chunk.epoch).or_default();
+        epoch_chunks.retain(|saved| saved.subtree != chunk.subtree);
+        epoch_chunks.push(chunk);
+        Ok(())
+    }
+
+    async fn chunks(&self, epoch: u64) -> Result<Vec<AppendOnlyProofChunk>, StorageError> {
+        let chunks = self.chunks.lock().map_err(|err| {
+            StorageError::Other(format!("Proof chunk store lock poisoned: {err}"))
+        })?;
+        Ok(chunks.get(&epoch).cloned().unwrap_or_default())
+    }
+
+    async fn clear(&self, epoch: u64) -> Result<(), StorageError> {
+        let mut chunks = self.chunks.lock().map_err(|err| {
+            StorageError::Other(format!("Proof chunk store lock poisoned: {err}"))
+        })?;
+        chunks.remove(&epoch);
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! An ingestion queue which collects the updates to publish in the next epoch.
+//!
+//! Since a publish may not contain more than one update for the same label, and upstream
+//! systems frequently re-submit the same key, the [PublishQueue] coalesces all of the updates
+//! submitted for a label within one epoch window into the most recently submitted one. The
+//! queued updates are published with `Directory::publish_queued`.
+
+use crate::{AkdLabel, AkdValue};
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Counts of the updates which have passed through a [PublishQueue]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PublishQueueMetrics {
+    /// The number of updates submitted to the queue
+    pub submitted: u64,
+    /// The number of submitted updates which were dropped, because a later
+    /// update to the same label was submitted within the same epoch window
+    pub dropped_duplicates: u64,
+    /// The number of updates which were published
+    pub published: u64,
+}
+
+#[derive(Default)]
+struct PendingUpdates {
+    /// The labels of the pending updates, in the order in which they were first submitted
+    order: Vec<AkdLabel>,
+    values: HashMap<AkdLabel, AkdValue>,
+}
+
+/// A queue of updates to publish in the next epoch, which keeps only the
+/// latest update submitted for each label
+#[derive(Default)]
+pub struct PublishQueue {
+    pending: Mutex<PendingUpdates>,
+    submitted: AtomicU64,
+    dropped_duplicates: AtomicU64,
+    published: AtomicU64,
+}
+
+impl PublishQueue {
+    /// Creates a new, empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Submits an update to be published in the next epoch, replacing any
+    /// update to the same label which is already pending
+    pub fn submit(&self, label: AkdLabel, value: AkdValue) {
+        self.submitted.fetch_add(1, Ordering::Relaxed);
+        let mut pending = self.lock();
+        if pending.values.insert(label.clone(), value).is_some() {
+            self.dropped_duplicates.fetch_add(1, Ordering::Relaxed);
+        } else {
+            pending.order.push(label);
+        }
+    }
+
+    /// Submits a batch of updates, as if each was submitted in order with [PublishQueue::submit]
+    pub fn submit_batch(&self, updates: Vec<(AkdLabel, AkdValue)>) {
+        for (label, value) in updates {
+            self.submit(label, value);
+        }
+    }
+
+    /// The number of labels with a pending update
+    pub fn len(&self) -> usize {
+        self.lock().order.len()
+    }
+
+    /// Returns whether there are no pending updates
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the counts of the updates which have passed through the queue
+    pub fn metrics(&self) -> PublishQueueMetrics {
+        PublishQueueMetrics {
+            submitted: self.submitted.load(Ordering::Relaxed),
+            dropped_duplicates: self.dropped_duplicates.load(Ordering::Relaxed),
+            published: self.published.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Removes and returns all of the pending updates, in the order in which
+    /// their labels were first submitted
+    pub(crate) fn drain(&self) -> Vec<(AkdLabel, AkdValue)> {
+        let mut pending = std::mem::take(&mut *self.lock());
+        pending
+            .order
+            .into_iter()
+            .map(|label| {
+                let value = pending
+                    .values
+                    .remove(&label)
+                    .expect("pending label has a value");
+                (label, value)
+            })
+            .collect()
+    }
+
+    /// Returns drained updates which could not be published to the queue. An update
+    /// is discarded if a newer update to its label was submitted in the meantime.
+    pub(crate) fn restore(&self, updates: Vec<(AkdLabel, AkdValue)>) {
+        let mut pending = self.lock();
+        let mut restored = vec![];
+        for (label, value) in updates {
+            match pending.values.entry(label.clone()) {
+                Entry::Occupied(_) => {
+                    self.dropped_duplicates.fetch_add(1, Ordering::Relaxed);
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(value);
+                    restored.push(label);
+                }
+            }
+        }
+        // The restored updates were submitted before any of the ones now pending
+        restored.append(&mut pending.order);
+        pending.order = restored;
+    }
+
+    /// Records that the drained updates were published
+    pub(crate) fn record_published(&self, num_updates: usize) {
+        self.published
+            .fetch_add(num_updates as u64, Ordering::Relaxed);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PendingUpdates> {
+        // The pending updates are always left in a consistent state, so they can
+        // still be used if another thread panicked while holding the lock
+        self.pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restore_keeps_newer_submissions() {
+        let queue = PublishQueue::new();
+        queue.submit_batch(vec![
+            (AkdLabel::from("a"), AkdValue::from("a1")),
+            (AkdLabel::from("b"), AkdValue::from("b1")),
+        ]);
+        let drained = queue.drain();
+        assert!(queue.is_empty());
+
+        // "b" was resubmitted while the drained updates were being published
+        queue.submit(AkdLabel::from("c"), AkdValue::from("c1"));
+        queue.submit(AkdLabel::from("b"), AkdValue::from("b2"));
+        queue.restore(drained);
+
+        assert_eq!(
+            vec![
+                (AkdLabel::from("a"), AkdValue::from("a1")),
+                (AkdLabel::from("c"), AkdValue::from("c1")),
+                (AkdLabel::from("b"), AkdValue::from("b2")),
+            ],
+            queue.drain()
+        );
+        assert_eq!(
+            PublishQueueMetrics {
+                submitted: 4,
+                dropped_duplicates: 1,
+                published: 0,
+            },
+            queue.metrics()
+        );
+    }
+}
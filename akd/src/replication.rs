@@ -0,0 +1,108 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Replication of a directory across a serving tier.
+//!
+//! A single leader publishes to the storage, and any number of followers share the storage
+//! to serve proofs. A follower is a [crate::Directory] whose storage manager has the
+//! [StorageRole::Follower] role (see `StorageManager::with_role`), which refuses every
+//! write, so that a misconfigured follower cannot fork the directory.
+//!
+//! A follower learns of the epochs published by the leader with `Directory::refresh`, which
+//! flushes its cache when the storage holds a newer epoch. `Directory::spawn_follower` refreshes
+//! in the background on an interval, and can be woken immediately with
+//! [FollowerHandle::notify_epoch], for instance from a [crate::hooks::DirectoryHooks] of the
+//! leader which broadcasts its publishes.
+
+pub use crate::storage::StorageRole;
+
+use crate::errors::AkdError;
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Notify};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// A handle to a follower which refreshes its directory in the background. The follower is
+/// stopped when the handle is shut down or dropped.
+pub struct FollowerHandle {
+    epoch: watch::Receiver<u64>,
+    wakeup: Arc<Notify>,
+    task: JoinHandle<()>,
+}
+
+impl FollowerHandle {
+    /// The latest epoch which the follower has refreshed to
+    pub fn epoch(&self) -> u64 {
+        *self.epoch.borrow()
+    }
+
+    /// Returns a receiver which is notified whenever the follower refreshes to a new epoch
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.epoch.clone()
+    }
+
+    /// Wakes the follower to refresh immediately, rather than at the end of its interval,
+    /// e.g. when the leader announces that it has published a new epoch
+    pub fn notify_epoch(&self) {
+        self.wakeup.notify_one();
+    }
+
+    /// Stops the follower. A refresh which is currently running is interrupted at its
+    /// next suspension point.
+    pub fn shutdown(self) {
+        // The task is aborted when the handle is dropped
+    }
+
+    /// Returns whether the follower has stopped
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+impl Drop for FollowerHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Spawns a background task which refreshes a directory with the provided function every
+/// `poll_interval`, or as soon as it is woken by the handle
+pub(crate) fn spawn<F, Fut>(
+    initial_epoch: u64,
+    poll_interval: Duration,
+    refresh: F,
+) -> FollowerHandle
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<u64, AkdError>> + Send,
+{
+    let (sender, epoch) = watch::channel(initial_epoch);
+    let wakeup = Arc::new(Notify::new());
+    let notified = wakeup.clone();
+    let task = tokio::spawn(async move {
+        loop {
+            // a timeout only means that the interval has passed without a wakeup
+            let _ = tokio::time::timeout(poll_interval, notified.notified()).await;
+            match refresh().await {
+                Ok(latest) if latest > *sender.borrow() => {
+                    info!(epoch = latest, "Follower refreshed to a new epoch");
+                    sender.send_replace(latest);
+                }
+                Ok(_) => {}
+                Err(err) => warn!(error = %err, "Follower failed to refresh"),
+            }
+        }
+    });
+    FollowerHandle {
+        epoch,
+        wakeup,
+        task,
+    }
+}
@@ -0,0 +1,407 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A directory whose [Configuration](crate::Configuration) is selected at runtime.
+//!
+//! A [Directory] is generic over its configuration, so a binary which serves several
+//! configurations must otherwise be written generically over all of them. A
+//! [RuntimeDirectory] instead wraps a directory of one of the built-in configurations, chosen
+//! by a [ConfigurationKind] (e.g. read from a config file), and dispatches each operation to
+//! it. The proofs produced by a directory do not depend on its configuration, so the
+//! operations of a [RuntimeDirectory] return the same types as those of a [Directory].
+//!
+//! The dispatch is a single `match` per operation, and the wrapped directory can always be
+//! matched on directly for the operations which are not forwarded. Clients which verify
+//! proofs for a single configuration should keep using the generic functions of
+//! [crate::client], for which each configuration is monomorphized.
+//!
+//! ```
+//! use akd::ecvrf::HardCodedAkdVRF;
+//! use akd::runtime_directory::{ConfigurationKind, RuntimeDirectory};
+//! use akd::storage::memory::AsyncInMemoryDatabase;
+//! use akd::storage::StorageManager;
+//! use akd::{AkdLabel, AkdValue};
+//!
+//! # tokio_test::block_on(async {
+//! let kind = ConfigurationKind::from_name("experimental").unwrap();
+//! let storage = StorageManager::new_no_cache(AsyncInMemoryDatabase::new());
+//! let akd = RuntimeDirectory::<_, _>::new(kind, storage, HardCodedAkdVRF {})
+//!     .await
+//!     .unwrap();
+//! akd.publish(vec![(AkdLabel::from("label"), AkdValue::from("value"))])
+//!     .await
+//!     .unwrap();
+//! let (proof, epoch_hash) = akd.lookup(AkdLabel::from("label")).await.unwrap();
+//! # });
+//! ```
+
+use crate::directory::{Directory, Operator, PublishResult};
+use crate::ecvrf::{VRFKeyStorage, VRFPublicKey};
+use crate::errors::AkdError;
+use crate::health::{HealthCheckOptions, HealthReport};
+use crate::hooks::DirectoryHooks;
+use crate::maintenance::{
+    MaintenanceConfig, MaintenanceHandle, MaintenanceReport, MaintenanceTask,
+};
+use crate::publish_queue::PublishQueue;
+use crate::storage::manager::StorageManager;
+use crate::storage::types::OperationRecord;
+use crate::storage::{Database, StorageUtil};
+use crate::visualization::{TreeVisualization, TreeVisualizationOptions};
+#[cfg(feature = "whatsapp_v1")]
+use crate::WhatsAppV1Configuration;
+use crate::{
+    AkdLabel, AkdValue, AppendOnlyProof, DirectoryExport, DomainLabel, EpochHash, HistoryParams,
+    HistoryProof, LatestVersionProof, LinkedHistoryProof, LookupProof, MultiEpochAppendOnlyProof,
+    NonExistenceProof, PublishLimits, SelectiveHistoryProof, SignedLookupResponse,
+    SignedRollbackRecord,
+};
+use crate::{Digest, ExampleLabel, ExperimentalConfiguration};
+use akd_core::signing::{configuration_fingerprint, EpochSigner};
+
+// Note(new_config): Update this when adding a new configuration
+
+/// The built-in configurations, which can be selected at runtime
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ConfigurationKind {
+    /// `WhatsAppV1Configuration`
+    #[cfg(feature = "whatsapp_v1")]
+    WhatsAppV1,
+    /// `ExperimentalConfiguration`, with the domain label of the [RuntimeDirectory]
+    Experimental,
+}
+
+impl ConfigurationKind {
+    /// All of the configurations which are enabled by the crate's features
+    pub const ALL: &'static [ConfigurationKind] = &[
+        #[cfg(feature = "whatsapp_v1")]
+        ConfigurationKind::WhatsAppV1,
+        ConfigurationKind::Experimental,
+    ];
+
+    /// The name of the configuration, as used in the feature which enables it
+    pub fn name(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "whatsapp_v1")]
+            ConfigurationKind::WhatsAppV1 => "whatsapp_v1",
+            ConfigurationKind::Experimental => "experimental",
+        }
+    }
+
+    /// The configuration with the given name, if it is enabled
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|kind| kind.name() == name)
+    }
+
+    /// The fingerprint of the configuration (see [configuration_fingerprint]), where the
+    /// experimental configuration uses the domain label `L`
+    pub fn fingerprint<L: DomainLabel>(&self) -> Digest {
+        match self {
+            #[cfg(feature = "whatsapp_v1")]
+            ConfigurationKind::WhatsAppV1 => configuration_fingerprint::<WhatsAppV1Configuration>(),
+            ConfigurationKind::Experimental => {
+                configuration_fingerprint::<ExperimentalConfiguration<L>>()
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigurationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A [Directory] of one of the built-in configurations, where the experimental configuration
+/// uses the domain label `L`
+pub enum RuntimeDirectory<S: Database, V, L = ExampleLabel> {
+    /// A directory of `WhatsAppV1Configuration`
+    #[cfg(feature = "whatsapp_v1")]
+    WhatsAppV1(Directory<WhatsAppV1Configuration, S, V>),
+    /// A directory of `ExperimentalConfiguration`
+    Experimental(Directory<ExperimentalConfiguration<L>, S, V>),
+}
+
+/// Calls the same expression on the directory of whichever configuration is wrapped
+macro_rules! dispatch {
+    ($self:expr, $directory:ident => $call:expr) => {
+        match $self {
+            #[cfg(feature = "whatsapp_v1")]
+            RuntimeDirectory::WhatsAppV1($directory) => $call,
+            RuntimeDirectory::Experimental($directory) => $call,
+        }
+    };
+}
+
+// Manual implementation of Clone, see: https://github.com/rust-lang/rust/issues/41481
+impl<S: Database, V: VRFKeyStorage, L> Clone for RuntimeDirectory<S, V, L> {
+    fn clone(&self) -> Self {
+        match self {
+            #[cfg(feature = "whatsapp_v1")]
+            RuntimeDirectory::WhatsAppV1(directory) => {
+                RuntimeDirectory::WhatsAppV1(directory.clone())
+            }
+            RuntimeDirectory::Experimental(directory) => {
+                RuntimeDirectory::Experimental(directory.clone())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "whatsapp_v1")]
+impl<S: Database, V, L> From<Directory<WhatsAppV1Configuration, S, V>>
+    for RuntimeDirectory<S, V, L>
+{
+    fn from(directory: Directory<WhatsAppV1Configuration, S, V>) -> Self {
+        RuntimeDirectory::WhatsAppV1(directory)
+    }
+}
+
+impl<S: Database, V, L> From<Directory<ExperimentalConfiguration<L>, S, V>>
+    for RuntimeDirectory<S, V, L>
+{
+    fn from(directory: Directory<ExperimentalConfiguration<L>, S, V>) -> Self {
+        RuntimeDirectory::Experimental(directory)
+    }
+}
+
+impl<S, V, L> RuntimeDirectory<S, V, L>
+where
+    S: Database + 'static,
+    V: VRFKeyStorage,
+    L: DomainLabel,
+{
+    /// Creates a directory of the given configuration, as with [Directory::new]
+    pub async fn new(
+        kind: ConfigurationKind,
+        storage: StorageManager<S>,
+        vrf: V,
+    ) -> Result<Self, AkdError> {
+        Ok(match kind {
+            #[cfg(feature = "whatsapp_v1")]
+            ConfigurationKind::WhatsAppV1 => {
+                RuntimeDirectory::WhatsAppV1(Directory::new(storage, vrf).await?)
+            }
+            ConfigurationKind::Experimental => {
+                RuntimeDirectory::Experimental(Directory::new(storage, vrf).await?)
+            }
+        })
+    }
+
+    /// The configuration of the directory
+    pub fn configuration(&self) -> ConfigurationKind {
+        match self {
+            #[cfg(feature = "whatsapp_v1")]
+            RuntimeDirectory::WhatsAppV1(_) => ConfigurationKind::WhatsAppV1,
+            RuntimeDirectory::Experimental(_) => ConfigurationKind::Experimental,
+        }
+    }
+
+    /// Runtime-dispatched access to [Directory::publish]
+    pub async fn publish(&self, updates: Vec<(AkdLabel, AkdValue)>) -> Result<EpochHash, AkdError> {
+        dispatch!(self, directory => directory.publish(updates).await)
+    }
+
+    /// Runtime-dispatched access to [Directory::publish_with_limits]
+    pub async fn publish_with_limits(
+        &self,
+        updates: Vec<(AkdLabel, AkdValue)>,
+        limits: &PublishLimits,
+    ) -> Result<PublishResult, AkdError> {
+        dispatch!(self, directory => directory.publish_with_limits(updates, limits).await)
+    }
+
+    /// Runtime-dispatched access to [Directory::publish_queued]
+    pub async fn publish_queued(&self, queue: &PublishQueue) -> Result<EpochHash, AkdError> {
+        dispatch!(self, directory => directory.publish_queued(queue).await)
+    }
+
+    /// Runtime-dispatched access to [Directory::lookup]
+    pub async fn lookup(&self, akd_label: AkdLabel) -> Result<(LookupProof, EpochHash), AkdError> {
+        dispatch!(self, directory => directory.lookup(akd_label).await)
+    }
+
+    /// Runtime-dispatched access to [Directory::lookup_latest]
+    pub async fn lookup_latest(
+        &self,
+        akd_label: AkdLabel,
+    ) -> Result<(LatestVersionProof, EpochHash), AkdError> {
+        dispatch!(self, directory => directory.lookup_latest(akd_label).await)
+    }
+
+    /// Runtime-dispatched access to [Directory::lookup_absent]
+    pub async fn lookup_absent(
+        &self,
+        akd_label: AkdLabel,
+    ) -> Result<(NonExistenceProof, EpochHash), AkdError> {
+        dispatch!(self, directory => directory.lookup_absent(akd_label).await)
+    }
+
+    /// Runtime-dispatched access to [Directory::signed_lookup]
+    pub async fn signed_lookup(
+        &self,
+        akd_label: AkdLabel,
+        signer: &impl EpochSigner,
+    ) -> Result<SignedLookupResponse, AkdError> {
+        dispatch!(self, directory => directory.signed_lookup(akd_label, signer).await)
+    }
+
+    /// Runtime-dispatched access to [Directory::batch_lookup]
+    pub async fn batch_lookup(
+        &self,
+        akd_labels: &[AkdLabel],
+    ) -> Result<(Vec<LookupProof>, EpochHash), AkdError> {
+        dispatch!(self, directory => directory.batch_lookup(akd_labels).await)
+    }
+
+    /// Runtime-dispatched access to [Directory::key_history]
+    pub async fn key_history(
+        &self,
+        akd_label: &AkdLabel,
+        params: HistoryParams,
+    ) -> Result<(HistoryProof, EpochHash), AkdError> {
+        dispatch!(self, directory => directory.key_history(akd_label, params).await)
+    }
+
+    /// Runtime-dispatched access to [Directory::linked_key_history]
+    pub async fn linked_key_history(
+        &self,
+        akd_label: &AkdLabel,
+        params: HistoryParams,
+    ) -> Result<(LinkedHistoryProof, EpochHash), AkdError> {
+        dispatch!(self, directory => directory.linked_key_history(akd_label, params).await)
+    }
+
+    /// Runtime-dispatched access to [Directory::selective_key_history]
+    pub async fn selective_key_history(
+        &self,
+        akd_label: &AkdLabel,
+        params: HistoryParams,
+        disclosed_start_epoch: u64,
+        disclosed_end_epoch: u64,
+    ) -> Result<(SelectiveHistoryProof, EpochHash), AkdError> {
+        dispatch!(self, directory => directory
+            .selective_key_history(akd_label, params, disclosed_start_epoch, disclosed_end_epoch)
+            .await)
+    }
+
+    /// Runtime-dispatched access to [Directory::poll_for_azks_changes]
+    pub async fn poll_for_azks_changes(
+        &self,
+        period: tokio::time::Duration,
+        change_detected: Option<tokio::sync::mpsc::Sender<()>>,
+    ) -> Result<(), AkdError> {
+        dispatch!(self, directory => directory.poll_for_azks_changes(period, change_detected).await)
+    }
+
+    /// Runtime-dispatched access to [Directory::audit]
+    pub async fn audit(
+        &self,
+        audit_start_ep: u64,
+        audit_end_ep: u64,
+    ) -> Result<AppendOnlyProof, AkdError> {
+        dispatch!(self, directory => directory.audit(audit_start_ep, audit_end_ep).await)
+    }
+
+    /// Runtime-dispatched access to [Directory::audit_multi_epoch]
+    pub async fn audit_multi_epoch(
+        &self,
+        audit_start_ep: u64,
+        audit_end_ep: u64,
+    ) -> Result<MultiEpochAppendOnlyProof, AkdError> {
+        dispatch!(self, directory => directory.audit_multi_epoch(audit_start_ep, audit_end_ep).await)
+    }
+
+    /// Runtime-dispatched access to [Directory::get_public_key]
+    pub async fn get_public_key(&self) -> Result<VRFPublicKey, AkdError> {
+        dispatch!(self, directory => directory.get_public_key().await)
+    }
+
+    /// Runtime-dispatched access to [Directory::get_epoch_hash]
+    pub async fn get_epoch_hash(&self) -> Result<EpochHash, AkdError> {
+        dispatch!(self, directory => directory.get_epoch_hash().await)
+    }
+
+    /// Runtime-dispatched access to [Directory::visualize_tree]
+    pub async fn visualize_tree(
+        &self,
+        options: &TreeVisualizationOptions,
+    ) -> Result<TreeVisualization, AkdError> {
+        dispatch!(self, directory => directory.visualize_tree(options).await)
+    }
+
+    /// Runtime-dispatched access to [Directory::set_operator]
+    pub fn set_operator(&self, operator: Operator) {
+        dispatch!(self, directory => directory.set_operator(operator))
+    }
+
+    /// Runtime-dispatched access to [Directory::register_hook]
+    pub fn register_hook(&self, hook: impl DirectoryHooks + 'static) {
+        dispatch!(self, directory => directory.register_hook(hook))
+    }
+
+    /// Runtime-dispatched access to [Directory::operation_count]
+    pub async fn operation_count(&self) -> Result<u64, AkdError> {
+        dispatch!(self, directory => directory.operation_count().await)
+    }
+
+    /// Runtime-dispatched access to [Directory::operations]
+    pub async fn operations(
+        &self,
+        start: u64,
+        limit: usize,
+    ) -> Result<Vec<OperationRecord>, AkdError> {
+        dispatch!(self, directory => directory.operations(start, limit).await)
+    }
+
+    /// Runtime-dispatched access to [Directory::health]
+    pub async fn health(&self, options: &HealthCheckOptions) -> HealthReport {
+        dispatch!(self, directory => directory.health(options).await)
+    }
+}
+
+impl<S, V, L> RuntimeDirectory<S, V, L>
+where
+    S: StorageUtil + 'static,
+    V: VRFKeyStorage,
+    L: DomainLabel,
+{
+    /// Runtime-dispatched access to [Directory::rebuild_from_values]
+    pub async fn rebuild_from_values(&self) -> Result<EpochHash, AkdError> {
+        dispatch!(self, directory => directory.rebuild_from_values().await)
+    }
+
+    /// Runtime-dispatched access to [Directory::rollback_to]
+    pub async fn rollback_to(
+        &self,
+        epoch: u64,
+        signer: &impl EpochSigner,
+    ) -> Result<SignedRollbackRecord, AkdError> {
+        dispatch!(self, directory => directory.rollback_to(epoch, signer).await)
+    }
+
+    /// Runtime-dispatched access to [Directory::export]
+    pub async fn export(&self, epoch: u64) -> Result<DirectoryExport, AkdError> {
+        dispatch!(self, directory => directory.export(epoch).await)
+    }
+
+    /// Runtime-dispatched access to [Directory::run_maintenance_task]
+    pub async fn run_maintenance_task(
+        &self,
+        task: MaintenanceTask,
+    ) -> Result<MaintenanceReport, AkdError> {
+        dispatch!(self, directory => directory.run_maintenance_task(task).await)
+    }
+
+    /// Runtime-dispatched access to [Directory::spawn_maintenance]
+    pub fn spawn_maintenance(&self, config: MaintenanceConfig) -> MaintenanceHandle
+    where
+        V: 'static,
+    {
+        dispatch!(self, directory => directory.spawn_maintenance(config))
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Scheduling of the epochs of a directory by a source outside of the directory.
+//!
+//! By default, each publish which changes the directory commits the epoch following the
+//! current one. Several systems which must agree on a shared timeline of epochs (e.g. a set
+//! of directories whose epochs are audited together) can instead have the epoch of each
+//! publish chosen by an [EpochSource], which is set with `Directory::set_epoch_source`, or
+//! provide the epoch of a publish themselves with `Directory::publish_at_epoch`.
+//!
+//! The epoch chosen for a publish must be greater than the current epoch of the directory,
+//! and at most [MAX_EPOCH_GAP] epochs ahead of it. The epochs which are skipped hold the
+//! same tree as the current epoch, so they can be looked up and audited like any other. A
+//! scheduled publish commits its epoch even if it has no updates, so that the directory
+//! keeps to the timeline.
+//!
+//! [WallClockEpochs] schedules one epoch per fixed period of wall-clock time. A source
+//! backed by a coordination service implements [EpochSource] directly.
+
+use crate::errors::{AkdError, DirectoryError};
+
+use async_trait::async_trait;
+use std::time::{Duration, SystemTime};
+
+/// The maximum number of epochs by which a single publish may advance a directory, which
+/// guards against a faulty source skipping the directory far into the future
+pub const MAX_EPOCH_GAP: u64 = 1 << 16;
+
+/// A source of the epochs committed by the publishes of a directory
+#[async_trait]
+pub trait EpochSource: Send + Sync {
+    /// Returns the epoch which the next publish commits, given the current epoch of the
+    /// directory. An error aborts the publish, e.g. when the source has not yet scheduled
+    /// an epoch after the current one.
+    async fn next_epoch(&self, current_epoch: u64) -> Result<u64, AkdError>;
+}
+
+/// Checks that a publish may advance a directory from its current epoch to the next epoch
+pub(crate) fn check_next_epoch(current_epoch: u64, next_epoch: u64) -> Result<(), AkdError> {
+    if next_epoch <= current_epoch {
+        return Err(AkdError::Directory(DirectoryError::InvalidEpoch(format!(
+            "Cannot publish epoch {next_epoch}, as the directory is already at epoch {current_epoch}"
+        ))));
+    }
+    if next_epoch - current_epoch > MAX_EPOCH_GAP {
+        return Err(AkdError::Directory(DirectoryError::InvalidEpoch(format!(
+            "Cannot publish epoch {next_epoch}, as it is more than {MAX_EPOCH_GAP} epochs after the current epoch {current_epoch}"
+        ))));
+    }
+    Ok(())
+}
+
+/// Schedules one epoch per period of wall-clock time, starting from an origin shared by
+/// every system on the timeline. Epoch `n` is scheduled in the period starting at
+/// `origin + (n - 1) * period`, and at most one epoch is published in each period.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct WallClockEpochs {
+    origin: SystemTime,
+    period: Duration,
+}
+
+impl WallClockEpochs {
+    /// Creates a schedule whose first epoch begins at the origin, which fails if the period
+    /// is zero
+    pub fn new(origin: SystemTime, period: Duration) -> Result<Self, AkdError> {
+        if period.is_zero() {
+            return Err(AkdError::Directory(DirectoryError::InvalidEpoch(
+                "The period of a wall-clock schedule cannot be zero".to_string(),
+            )));
+        }
+        Ok(Self { origin, period })
+    }
+
+    /// The epoch scheduled at the given time, or zero before the origin
+    pub fn epoch_at(&self, time: SystemTime) -> u64 {
+        match time.duration_since(self.origin) {
+            Ok(elapsed) => {
+                let periods = elapsed.as_nanos() / self.period.as_nanos();
+                u64::try_from(periods).map_or(u64::MAX, |periods| periods.saturating_add(1))
+            }
+            Err(_) => 0,
+        }
+    }
+
+    /// The time at which the given epoch begins, which is the origin for epoch zero, or
+    /// [None] if the time cannot be represented
+    pub fn start_of(&self, epoch: u64) -> Option<SystemTime> {
+        let periods = u32::try_from(epoch.saturating_sub(1)).ok()?;
+        self.origin.checked_add(self.period.checked_mul(periods)?)
+    }
+
+    /// The time remaining until the epoch after the current one begins, which is zero if it
+    /// already has. A publisher sleeps for this long before its next publish.
+    pub fn until_next(&self, current_epoch: u64) -> Duration {
+        self.start_of(current_epoch.saturating_add(1))
+            .map_or(Duration::MAX, |start| {
+                start.duration_since(SystemTime::now()).unwrap_or_default()
+            })
+    }
+}
+
+#[async_trait]
+impl EpochSource for WallClockEpochs {
+    async fn next_epoch(&self, current_epoch: u64) -> Result<u64, AkdError> {
+        let epoch = self.epoch_at(SystemTime::now());
+        if epoch <= current_epoch {
+            return Err(AkdError::Directory(DirectoryError::InvalidEpoch(format!(
+                "Epoch {current_epoch} has already been published, and epoch {} is scheduled in {:?}",
+                current_epoch + 1,
+                self.until_next(current_epoch)
+            ))));
+        }
+        Ok(epoch)
+    }
+}
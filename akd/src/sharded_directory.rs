@@ -0,0 +1,198 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A directory whose label space is split across several trees, for directories beyond the
+//! practical size of a single tree.
+//!
+//! A [ShardedDirectory] holds one [Directory] per shard, each with its own storage, and
+//! assigns each label to a shard with [shard_index]. A publish is split by shard, and each
+//! shard with updates publishes its own epoch, so the epochs of the shards advance
+//! independently. The latest roots of the shards are combined into a single top-level root
+//! hash (see [akd_core::sharding]), which is the commitment that clients hold and gossip.
+//!
+//! The proofs of a sharded directory are the proofs of the label's shard, along with the
+//! proof that the root of the shard is committed to by the top-level root hash, and are
+//! verified with [crate::client::sharded_lookup_verify] and
+//! [crate::client::sharded_key_history_verify]. Each shard is audited on its own, with the
+//! audit proofs of its [Directory] (see [ShardedDirectory::shard]).
+
+use crate::directory::Directory;
+use crate::ecvrf::{VRFKeyStorage, VRFPublicKey};
+use crate::errors::{AkdError, DirectoryError};
+use crate::storage::manager::StorageManager;
+use crate::storage::Database;
+use crate::{AkdLabel, AkdValue, Configuration, Digest, EpochHash, HistoryParams};
+
+pub use akd_core::sharding::{
+    shard_inclusion_proof, shard_index, top_level_root_hash, ShardInclusionProof, ShardRoot,
+    ShardedHistoryProof, ShardedLookupProof, MAX_SHARDS,
+};
+
+use futures::future::try_join_all;
+use tokio::sync::RwLock;
+
+/// The latest roots of the shards of a sharded directory, and the top-level root hash which
+/// combines them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShardedRoot {
+    /// The latest root of each shard, in order of their indices
+    pub shard_roots: Vec<ShardRoot>,
+    /// The top-level root hash
+    pub root_hash: Digest,
+}
+
+/// A directory whose labels are split across several shards
+pub struct ShardedDirectory<TC, S: Database, V> {
+    shards: Vec<Directory<TC, S, V>>,
+    /// Held for writing while publishing, so that the roots of the shards which a proof is
+    /// served with are those of a single top-level root
+    publish_lock: RwLock<()>,
+}
+
+impl<TC, S, V> ShardedDirectory<TC, S, V>
+where
+    TC: Configuration,
+    S: Database + 'static,
+    V: VRFKeyStorage,
+{
+    /// Creates a sharded directory with one shard for each of the provided storage managers,
+    /// whose number must be a power of two of at most [MAX_SHARDS]. The shards share the VRF.
+    pub async fn new(storages: Vec<StorageManager<S>>, vrf: V) -> Result<Self, AkdError> {
+        let num_shards = u32::try_from(storages.len()).unwrap_or(u32::MAX);
+        akd_core::sharding::check_num_shards(num_shards)
+            .map_err(|err| sharding_error(err.to_string()))?;
+        let shards = try_join_all(
+            storages
+                .into_iter()
+                .map(|storage| Directory::new(storage, vrf.clone())),
+        )
+        .await?;
+        Ok(Self {
+            shards,
+            publish_lock: RwLock::new(()),
+        })
+    }
+
+    /// The number of shards
+    pub fn num_shards(&self) -> u32 {
+        self.shards.len() as u32
+    }
+
+    /// The directory of a shard, for the operations which are not served by the sharded
+    /// directory, such as auditing the shard
+    pub fn shard(&self, shard: u32) -> Option<&Directory<TC, S, V>> {
+        self.shards.get(shard as usize)
+    }
+
+    /// The directory of the shard which a label belongs to
+    fn shard_of(&self, akd_label: &AkdLabel) -> (u32, &Directory<TC, S, V>) {
+        let shard = shard_index::<TC>(akd_label, self.num_shards());
+        (shard, &self.shards[shard as usize])
+    }
+
+    /// Updates the directory to include the input label-value pairs, by publishing the
+    /// updates of each shard to it, and returns the new roots. Shards without updates
+    /// keep their epoch.
+    ///
+    /// The shards are published concurrently, and a failed publish of a shard does not
+    /// undo those of the other shards, whose updates remain published.
+    pub async fn publish(
+        &self,
+        updates: Vec<(AkdLabel, AkdValue)>,
+    ) -> Result<ShardedRoot, AkdError> {
+        let _guard = self.publish_lock.write().await;
+        let mut shard_updates = vec![vec![]; self.shards.len()];
+        for (akd_label, akd_value) in updates {
+            let (shard, _) = self.shard_of(&akd_label);
+            shard_updates[shard as usize].push((akd_label, akd_value));
+        }
+        try_join_all(
+            self.shards
+                .iter()
+                .zip(shard_updates)
+                .filter(|(_, updates)| !updates.is_empty())
+                .map(|(directory, updates)| directory.publish(updates)),
+        )
+        .await?;
+        self.current_root().await
+    }
+
+    /// Returns the latest roots of the shards, and the top-level root hash
+    pub async fn get_root(&self) -> Result<ShardedRoot, AkdError> {
+        let _guard = self.publish_lock.read().await;
+        self.current_root().await
+    }
+
+    async fn current_root(&self) -> Result<ShardedRoot, AkdError> {
+        let shard_roots = try_join_all(self.shards.iter().map(|directory| async move {
+            let EpochHash(epoch, root_hash) = directory.get_epoch_hash().await?;
+            Ok::<_, AkdError>(ShardRoot { epoch, root_hash })
+        }))
+        .await?;
+        let root_hash = top_level_root_hash::<TC>(&shard_roots)
+            .map_err(|err| sharding_error(err.to_string()))?;
+        Ok(ShardedRoot {
+            shard_roots,
+            root_hash,
+        })
+    }
+
+    /// Returns the proof that the root of a shard is committed to by the top-level root
+    /// hash, along with the top-level root hash, after checking that the proof of the shard
+    /// was generated at the latest root of the shard
+    async fn inclusion_proof(
+        &self,
+        shard: u32,
+        EpochHash(epoch, root_hash): EpochHash,
+    ) -> Result<(ShardInclusionProof, Digest), AkdError> {
+        let root = self.current_root().await?;
+        if root.shard_roots[shard as usize] != (ShardRoot { epoch, root_hash }) {
+            return Err(sharding_error(format!(
+                "The proof of shard {shard} is not for its latest epoch"
+            )));
+        }
+        let inclusion = shard_inclusion_proof::<TC>(&root.shard_roots, shard)
+            .map_err(|err| sharding_error(err.to_string()))?;
+        Ok((inclusion, root.root_hash))
+    }
+
+    /// Generates a lookup proof of a label, returning it along with the top-level root hash
+    /// which it is verified against
+    pub async fn lookup(
+        &self,
+        akd_label: AkdLabel,
+    ) -> Result<(ShardedLookupProof, Digest), AkdError> {
+        let _guard = self.publish_lock.read().await;
+        let (shard, directory) = self.shard_of(&akd_label);
+        let (proof, epoch_hash) = directory.lookup(akd_label).await?;
+        let (inclusion, root_hash) = self.inclusion_proof(shard, epoch_hash).await?;
+        Ok((ShardedLookupProof { inclusion, proof }, root_hash))
+    }
+
+    /// Generates a history proof of a label, returning it along with the top-level root hash
+    /// which it is verified against
+    pub async fn key_history(
+        &self,
+        akd_label: &AkdLabel,
+        params: HistoryParams,
+    ) -> Result<(ShardedHistoryProof, Digest), AkdError> {
+        let _guard = self.publish_lock.read().await;
+        let (shard, directory) = self.shard_of(akd_label);
+        let (proof, epoch_hash) = directory.key_history(akd_label, params).await?;
+        let (inclusion, root_hash) = self.inclusion_proof(shard, epoch_hash).await?;
+        Ok((ShardedHistoryProof { inclusion, proof }, root_hash))
+    }
+
+    /// Retrieves the public key of the VRF shared by the shards
+    pub async fn get_public_key(&self) -> Result<VRFPublicKey, AkdError> {
+        self.shards[0].get_public_key().await
+    }
+}
+
+fn sharding_error(message: String) -> AkdError {
+    AkdError::Directory(DirectoryError::Sharding(message))
+}
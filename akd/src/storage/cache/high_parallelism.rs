@@ -8,17 +8,18 @@
 //! This module implements a higher-parallelism, async temporary cache for database
 //! objects
 
+use super::stats::{CacheCounters, CacheStats};
 use super::{CachedItem, DEFAULT_CACHE_CLEAN_FREQUENCY_MS, DEFAULT_ITEM_LIFETIME_MS};
+#[cfg(feature = "runtime_metrics")]
+use crate::instrumentation::dyn_event;
+use crate::storage::types::StorageType;
 use crate::storage::DbRecord;
 use crate::storage::Storable;
 use akd_core::SizeOf;
 use dashmap::DashMap;
-#[cfg(not(feature = "runtime_metrics"))]
-use log::debug;
-use log::info;
-#[cfg(feature = "runtime_metrics")]
-use log::{debug, error, warn};
+use tracing::{debug, info};
 
+use std::collections::HashMap;
 #[cfg(feature = "runtime_metrics")]
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -26,17 +27,122 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 
+/// The configuration of a partition of the cache, which holds the records of a single
+/// [StorageType] apart from the rest of the cache, so that the records of other types
+/// cannot evict them. See [TimedCache::with_partition].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CachePartition {
+    /// The lifetime of the records in the partition, or the lifetime of the cache if `None`
+    pub item_lifetime: Option<Duration>,
+    /// The size above which the partition sheds its oldest records, or no limit if `None`
+    pub memory_limit_bytes: Option<usize>,
+}
+
+/// A map of cached records, with the lifetime and memory limit which apply to them
+struct Partition {
+    map: DashMap<Vec<u8>, CachedItem>,
+    item_lifetime: Duration,
+    memory_limit_bytes: Option<usize>,
+}
+
+impl Partition {
+    fn new(item_lifetime: Duration, memory_limit_bytes: Option<usize>) -> Self {
+        Self {
+            map: DashMap::new(),
+            item_lifetime,
+            memory_limit_bytes,
+        }
+    }
+
+    fn clean(&self, now: Instant, counters: &CacheCounters) {
+        if let Some(memory_limit_bytes) = self.memory_limit_bytes {
+            let mut retained_size = 0;
+            let mut num_retained = 0u32;
+            let mut num_removed = 0u32;
+            self.map.retain(|k, v| {
+                if v.expiration >= now {
+                    retained_size += k.len() + v.size_of();
+                    num_retained += 1;
+                    true
+                } else {
+                    num_removed += 1;
+                    false
+                }
+            });
+
+            counters.record_expired_evictions(num_removed as usize);
+            info!(num_removed, "Removed expired elements from the cache");
+            debug!(retained_size, "Retained cache size in bytes");
+
+            if retained_size > memory_limit_bytes {
+                info!(
+                    retained_size,
+                    memory_limit_bytes,
+                    "Retained cache size has exceeded the predefined limit, cleaning old entries"
+                );
+                // calculate the percentage we'd need to trim off to get to 100% utilization and take another 5%
+                let percent_clean =
+                    0.05 + 1.0 - (memory_limit_bytes as f64) / (retained_size as f64);
+                // convert that to the number of items to delete based on the size of the dictionary
+                let num_clean = ((num_retained as f64) * percent_clean).ceil() as usize;
+                // sort the dict based on the oldest entries
+                let mut keys_and_expiration = self
+                    .map
+                    .iter()
+                    .map(|kv| (kv.key().clone(), kv.value().expiration))
+                    .collect::<Vec<_>>();
+                keys_and_expiration.sort_by_key(|(_, a)| *a);
+                // take `num_clean` old entries and remove them
+                let mut num_evicted = 0;
+                for key in keys_and_expiration
+                    .into_iter()
+                    .take(num_clean)
+                    .map(|(k, _)| k)
+                {
+                    if self.map.remove(&key).is_some() {
+                        num_evicted += 1;
+                    }
+                }
+                counters.record_memory_pressure_evictions(num_evicted);
+
+                debug!(num_cleaned = num_clean, "END cache memory pressure clean")
+            }
+        } else {
+            // memory pressure analysis is disabled, simply utilize timed cache cleaning
+            self.evict_expired(now, counters);
+        }
+    }
+
+    fn evict_expired(&self, now: Instant, counters: &CacheCounters) -> usize {
+        let num_items = self.map.len();
+        self.map.retain(|_, v| v.expiration >= now);
+        let num_evicted = num_items.saturating_sub(self.map.len());
+        counters.record_expired_evictions(num_evicted);
+        num_evicted
+    }
+
+    fn insert(&self, key: Vec<u8>, record: &DbRecord) {
+        let item = CachedItem {
+            expiration: Instant::now() + self.item_lifetime,
+            data: record.clone(),
+        };
+        self.map.insert(key, item);
+    }
+}
+
 /// Implements a basic cache with timing information which automatically flushes
 /// expired entries and removes them
 #[derive(Clone)]
 pub struct TimedCache {
     azks: Arc<RwLock<Option<DbRecord>>>,
-    map: Arc<DashMap<Vec<u8>, CachedItem>>,
+    /// The records of the types which have no partition of their own
+    shared: Arc<Partition>,
+    partitions: HashMap<StorageType, Arc<Partition>>,
     last_clean: Arc<RwLock<Instant>>,
     can_clean: Arc<AtomicBool>,
     item_lifetime: Duration,
-    memory_limit_bytes: Option<usize>,
     clean_frequency: Duration,
+    counters: Arc<CacheCounters>,
 
     #[cfg(feature = "runtime_metrics")]
     hit_count: Arc<AtomicU64>,
@@ -44,20 +150,13 @@ pub struct TimedCache {
 
 impl TimedCache {
     /// Log cache access metrics along with size information
-    pub fn log_metrics(&self, _level: log::Level) {
+    pub fn log_metrics(&self, _level: tracing::Level) {
         #[cfg(feature = "runtime_metrics")]
         {
             let hit_count = self.hit_count.swap(0, Ordering::Relaxed);
-            let cache_size = self.map.len();
-
-            let msg = format!("Cache hit since last: {hit_count}, cached size: {cache_size} items");
-            match _level {
-                log::Level::Trace => println!("{msg}"),
-                log::Level::Debug => debug!("{}", msg),
-                log::Level::Info => info!("{}", msg),
-                log::Level::Warn => warn!("{}", msg),
-                _ => error!("{}", msg),
-            }
+            let cache_size = self.len();
+
+            dyn_event!(_level, hit_count, cache_size, "Cache metrics");
         }
     }
 }
@@ -77,52 +176,8 @@ impl TimedCache {
             let mut last_clean_write = self.last_clean.write().await;
 
             let now = Instant::now();
-            if let Some(memory_limit_bytes) = self.memory_limit_bytes {
-                let mut retained_size = 0;
-                let mut num_retained = 0u32;
-                let mut num_removed = 0u32;
-                self.map.retain(|k, v| {
-                    if v.expiration >= now {
-                        retained_size += k.len() + v.size_of();
-                        num_retained += 1;
-                        true
-                    } else {
-                        num_removed += 1;
-                        false
-                    }
-                });
-
-                info!("Removed {} expired elements from the cache", num_removed);
-                debug!("Retained cache size is {} bytes", retained_size);
-
-                if retained_size > memory_limit_bytes {
-                    info!("Retained cache size has exceeded the predefined limit, cleaning old entries");
-                    // calculate the percentage we'd need to trim off to get to 100% utilization and take another 5%
-                    let percent_clean =
-                        0.05 + 1.0 - (memory_limit_bytes as f64) / (retained_size as f64);
-                    // convert that to the number of items to delete based on the size of the dictionary
-                    let num_clean = ((num_retained as f64) * percent_clean).ceil() as usize;
-                    // sort the dict based on the oldest entries
-                    let mut keys_and_expiration = self
-                        .map
-                        .iter()
-                        .map(|kv| (kv.key().clone(), kv.value().expiration))
-                        .collect::<Vec<_>>();
-                    keys_and_expiration.sort_by(|(_, a), (_, b)| a.cmp(b));
-                    // take `num_clean` old entries and remove them
-                    for key in keys_and_expiration
-                        .into_iter()
-                        .take(num_clean)
-                        .map(|(k, _)| k)
-                    {
-                        self.map.remove(&key);
-                    }
-
-                    debug!("END cache memory pressure clean")
-                }
-            } else {
-                // memory pressure analysis is disabled, simply utilize timed cache cleaning
-                self.map.retain(|_, v| v.expiration >= now);
+            for partition in self.all_partitions() {
+                partition.clean(now, &self.counters);
             }
 
             // update last clean time
@@ -130,6 +185,15 @@ impl TimedCache {
         }
     }
 
+    /// The partition which holds the records of a type
+    fn partition(&self, storage_type: StorageType) -> &Partition {
+        self.partitions.get(&storage_type).unwrap_or(&self.shared)
+    }
+
+    fn all_partitions(&self) -> impl Iterator<Item = &Partition> {
+        std::iter::once(self.shared.as_ref()).chain(self.partitions.values().map(|p| p.as_ref()))
+    }
+
     /// Create a new timed cache instance. You can supply an optional item lifetime parameter
     /// or take the default (30s) and an optional memory-pressure limit, where the cache will be
     /// cleaned if too much memory is being utilized
@@ -148,18 +212,38 @@ impl TimedCache {
         };
         Self {
             azks: Arc::new(RwLock::new(None)),
-            map: Arc::new(DashMap::new()),
+            shared: Arc::new(Partition::new(lifetime, o_memory_limit_bytes)),
+            partitions: HashMap::new(),
             last_clean: Arc::new(RwLock::new(Instant::now())),
             can_clean: Arc::new(AtomicBool::new(true)),
             item_lifetime: lifetime,
-            memory_limit_bytes: o_memory_limit_bytes,
             clean_frequency,
+            counters: Arc::new(CacheCounters::default()),
 
             #[cfg(feature = "runtime_metrics")]
             hit_count: Arc::new(AtomicU64::new(0u64)),
         }
     }
 
+    /// Holds the records of a type in a partition of their own, with its own lifetime and
+    /// memory limit, rather than in the part of the cache shared by the other types. Any
+    /// records of the type which are already cached are dropped. The AZKS is always cached
+    /// apart from the other records, and never expires, so it cannot be partitioned.
+    pub fn with_partition(mut self, storage_type: StorageType, partition: CachePartition) -> Self {
+        if storage_type == StorageType::Azks {
+            return self;
+        }
+        self.shared
+            .map
+            .retain(|_, v| v.data.storage_type() != storage_type);
+        let partition = Partition::new(
+            partition.item_lifetime.unwrap_or(self.item_lifetime),
+            partition.memory_limit_bytes,
+        );
+        self.partitions.insert(storage_type, Arc::new(partition));
+        self
+    }
+
     /// Perform a hit-test of the cache for a given key. If successful, Some(record) will be returned
     pub async fn hit_test<St: Storable>(&self, key: &St::StorageKey) -> Option<DbRecord> {
         self.clean().await;
@@ -177,13 +261,16 @@ impl TimedCache {
 
             #[cfg(feature = "runtime_metrics")]
             self.hit_count.fetch_add(1, Ordering::Relaxed);
+            crate::instrumentation::record_cache_access(record.is_some());
+            self.counters
+                .record_access(St::data_type(), record.is_some());
 
             // AZKS objects cannot expire, they need to be manually flushed, so we don't need
             // to check the expiration as below
             return record;
         }
 
-        if let Some(result) = self.map.get(&full_key) {
+        if let Some(result) = self.partition(St::data_type()).map.get(&full_key) {
             #[cfg(feature = "runtime_metrics")]
             self.hit_count.fetch_add(1, Ordering::Relaxed);
 
@@ -192,10 +279,14 @@ impl TimedCache {
             // of an in-memory transaction and should ignore expiration
             // of cache items until this flag is disabled again
             if ignore_clean || result.expiration > Instant::now() {
+                crate::instrumentation::record_cache_access(true);
+                self.counters.record_access(St::data_type(), true);
                 return Some(result.data.clone());
             }
         }
 
+        crate::instrumentation::record_cache_access(false);
+        self.counters.record_access(St::data_type(), false);
         None
     }
 
@@ -210,11 +301,7 @@ impl TimedCache {
             let mut guard = self.azks.write().await;
             *guard = Some(DbRecord::Azks(azks_ref.clone()));
         } else {
-            let item = CachedItem {
-                expiration: Instant::now() + self.item_lifetime,
-                data: record.clone(),
-            };
-            self.map.insert(key, item);
+            self.partition(record.storage_type()).insert(key, record);
         }
     }
 
@@ -228,18 +315,16 @@ impl TimedCache {
                 *azks_guard = Some(DbRecord::Azks(azks_ref.clone()));
             } else {
                 let key = record.get_full_binary_id();
-                let item = CachedItem {
-                    expiration: Instant::now() + self.item_lifetime,
-                    data: record.clone(),
-                };
-                self.map.insert(key, item);
+                self.partition(record.storage_type()).insert(key, record);
             }
         }
     }
 
     /// Flush the cache
     pub async fn flush(&self) {
-        self.map.clear();
+        for partition in self.all_partitions() {
+            partition.map.clear();
+        }
         *(self.azks.write().await) = None;
     }
 
@@ -251,13 +336,83 @@ impl TimedCache {
         if let Some(record) = self.azks.read().await.clone() {
             items.push(record);
         }
-        for kv in self.map.iter() {
-            items.push(kv.value().data.clone());
+        for partition in self.all_partitions() {
+            for kv in partition.map.iter() {
+                items.push(kv.value().data.clone());
+            }
         }
 
         items
     }
 
+    /// Evict all of the expired items from the cache, returning the number of items
+    /// evicted. Nothing is evicted while cache-cleaning is disabled.
+    pub fn evict_expired(&self) -> usize {
+        if !self.can_clean.load(Ordering::Relaxed) {
+            return 0;
+        }
+        let now = Instant::now();
+        self.all_partitions()
+            .map(|partition| partition.evict_expired(now, &self.counters))
+            .sum()
+    }
+
+    /// Takes a snapshot of the contents of the cache, and of the accesses to and evictions
+    /// from it since it was created
+    pub async fn stats(&self) -> CacheStats {
+        let azks = self
+            .azks
+            .read()
+            .await
+            .as_ref()
+            .map(|record| (record.storage_type(), record.size_of()));
+        let now = Instant::now();
+        let mut ages = Vec::with_capacity(self.len());
+        let mut contents = Vec::with_capacity(self.len() + 1);
+        contents.extend(azks);
+        for partition in self.all_partitions() {
+            for kv in partition.map.iter() {
+                // Records expire a fixed lifetime after they are cached
+                let cached = kv.value().expiration.checked_sub(partition.item_lifetime);
+                ages.push(cached.map_or(Duration::ZERO, |cached| {
+                    now.saturating_duration_since(cached)
+                }));
+                contents.push((
+                    kv.value().data.storage_type(),
+                    kv.key().len() + kv.value().size_of(),
+                ));
+            }
+        }
+        let partition_limits = self
+            .partitions
+            .iter()
+            .map(|(storage_type, partition)| (*storage_type, partition.memory_limit_bytes));
+        self.counters.stats(
+            contents.into_iter(),
+            self.shared.memory_limit_bytes,
+            partition_limits,
+            ages,
+        )
+    }
+
+    /// The number of items in the cache
+    pub fn len(&self) -> usize {
+        self.all_partitions()
+            .map(|partition| partition.map.len())
+            .sum()
+    }
+
+    /// Returns whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.all_partitions()
+            .all(|partition| partition.map.is_empty())
+    }
+
+    /// Returns whether cache-cleaning is enabled
+    pub fn is_clean_enabled(&self) -> bool {
+        self.can_clean.load(Ordering::Relaxed)
+    }
+
     /// Disable cache-cleaning (i.e. during a transaction)
     pub fn disable_clean(&self) {
         debug!("Disabling cache cleaning");
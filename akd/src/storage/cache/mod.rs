@@ -35,7 +35,9 @@ impl akd_core::SizeOf for CachedItem {
 // -------- sub modules -------- //
 
 pub mod high_parallelism;
+pub mod stats;
 
 // -------- cache exports -------- //
 
-pub use high_parallelism::TimedCache;
+pub use high_parallelism::{CachePartition, TimedCache};
+pub use stats::{CacheAgeDistribution, CacheStats, CacheTypeStats};
@@ -0,0 +1,216 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Point-in-time statistics of a [super::TimedCache], for serving layers to expose on their
+//! own dashboards and to size the cache by
+
+use crate::storage::types::StorageType;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// The types of record which can be cached, in the order in which they are reported
+const STORAGE_TYPES: [StorageType; 8] = [
+    StorageType::Azks,
+    StorageType::TreeNode,
+    StorageType::ValueState,
+    StorageType::OperationRecord,
+    StorageType::SoftDeletion,
+    StorageType::AnchorReceipt,
+    StorageType::PublishLease,
+    StorageType::AuditEpoch,
+];
+
+fn type_index(storage_type: StorageType) -> usize {
+    match storage_type {
+        StorageType::Azks => 0,
+        StorageType::TreeNode => 1,
+        StorageType::ValueState => 2,
+        StorageType::OperationRecord => 3,
+        StorageType::SoftDeletion => 4,
+        StorageType::AnchorReceipt => 5,
+        StorageType::PublishLease => 6,
+        StorageType::AuditEpoch => 7,
+    }
+}
+
+/// A snapshot of the contents of a cache, and of its accesses since it was created
+#[derive(Clone, Debug, PartialEq)]
+pub struct CacheStats {
+    /// The number of records in the cache
+    pub entries: usize,
+    /// The approximate size of the records in the cache, in bytes
+    pub bytes: usize,
+    /// The size above which the cache sheds its oldest records, if it has a limit. This
+    /// does not apply to the types of record which have their own partitions.
+    pub memory_limit_bytes: Option<usize>,
+    /// The contents of and accesses to the cache for each type of record
+    pub by_type: Vec<CacheTypeStats>,
+    /// The number of records which have been evicted because they expired
+    pub expired_evictions: u64,
+    /// The number of records which have been evicted because the cache exceeded its limit
+    pub memory_pressure_evictions: u64,
+    /// The distribution of the ages of the records in the cache, if it holds any records
+    /// which can expire
+    pub ages: Option<CacheAgeDistribution>,
+}
+
+impl CacheStats {
+    /// The statistics of one type of record
+    pub fn for_type(&self, storage_type: StorageType) -> Option<&CacheTypeStats> {
+        self.by_type
+            .iter()
+            .find(|stats| stats.storage_type == storage_type)
+    }
+
+    /// The total number of lookups which found their record in the cache
+    pub fn hits(&self) -> u64 {
+        self.by_type.iter().map(|stats| stats.hits).sum()
+    }
+
+    /// The total number of lookups which did not find their record in the cache
+    pub fn misses(&self) -> u64 {
+        self.by_type.iter().map(|stats| stats.misses).sum()
+    }
+
+    /// The fraction of lookups which found their record in the cache, if there have been any
+    pub fn hit_rate(&self) -> Option<f64> {
+        hit_rate(self.hits(), self.misses())
+    }
+}
+
+/// The contents of and accesses to a cache for one type of record
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CacheTypeStats {
+    /// The type of record
+    pub storage_type: StorageType,
+    /// The number of records of the type in the cache
+    pub entries: usize,
+    /// The approximate size of the records of the type in the cache, in bytes
+    pub bytes: usize,
+    /// The number of lookups of the type which found their record in the cache
+    pub hits: u64,
+    /// The number of lookups of the type which did not find their record in the cache,
+    /// including those which found an expired record
+    pub misses: u64,
+    /// Whether the records of the type are held in a partition of their own
+    pub partitioned: bool,
+    /// The size above which the partition of the type sheds its oldest records, if the
+    /// type has its own partition with a limit
+    pub memory_limit_bytes: Option<usize>,
+}
+
+impl CacheTypeStats {
+    /// The fraction of lookups of the type which found their record in the cache, if
+    /// there have been any
+    pub fn hit_rate(&self) -> Option<f64> {
+        hit_rate(self.hits, self.misses)
+    }
+}
+
+/// The distribution of the time since the records in a cache were cached
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CacheAgeDistribution {
+    /// The age of the most recently cached record
+    pub newest: Duration,
+    /// The median age of the records
+    pub median: Duration,
+    /// The age which 90% of the records are younger than
+    pub p90: Duration,
+    /// The age of the least recently cached record
+    pub oldest: Duration,
+}
+
+impl CacheAgeDistribution {
+    pub(crate) fn from_ages(mut ages: Vec<Duration>) -> Option<Self> {
+        if ages.is_empty() {
+            return None;
+        }
+        ages.sort();
+        let percentile = |p: usize| ages[(ages.len() - 1) * p / 100];
+        Some(Self {
+            newest: ages[0],
+            median: percentile(50),
+            p90: percentile(90),
+            oldest: ages[ages.len() - 1],
+        })
+    }
+}
+
+fn hit_rate(hits: u64, misses: u64) -> Option<f64> {
+    match hits + misses {
+        0 => None,
+        total => Some(hits as f64 / total as f64),
+    }
+}
+
+/// The counters of the accesses to and evictions from a cache
+#[derive(Default)]
+pub(crate) struct CacheCounters {
+    hits: [AtomicU64; STORAGE_TYPES.len()],
+    misses: [AtomicU64; STORAGE_TYPES.len()],
+    expired_evictions: AtomicU64,
+    memory_pressure_evictions: AtomicU64,
+}
+
+impl CacheCounters {
+    pub(crate) fn record_access(&self, storage_type: StorageType, hit: bool) {
+        let counters = if hit { &self.hits } else { &self.misses };
+        counters[type_index(storage_type)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_expired_evictions(&self, count: usize) {
+        self.expired_evictions
+            .fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_memory_pressure_evictions(&self, count: usize) {
+        self.memory_pressure_evictions
+            .fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    /// Builds the statistics of a cache from the number and size of its records of each type
+    pub(crate) fn stats(
+        &self,
+        contents: impl Iterator<Item = (StorageType, usize)>,
+        memory_limit_bytes: Option<usize>,
+        partition_limits: impl Iterator<Item = (StorageType, Option<usize>)>,
+        ages: Vec<Duration>,
+    ) -> CacheStats {
+        let mut by_type = STORAGE_TYPES
+            .iter()
+            .enumerate()
+            .map(|(index, storage_type)| CacheTypeStats {
+                storage_type: *storage_type,
+                entries: 0,
+                bytes: 0,
+                hits: self.hits[index].load(Ordering::Relaxed),
+                misses: self.misses[index].load(Ordering::Relaxed),
+                partitioned: false,
+                memory_limit_bytes: None,
+            })
+            .collect::<Vec<_>>();
+        for (storage_type, limit) in partition_limits {
+            let stats = &mut by_type[type_index(storage_type)];
+            stats.partitioned = true;
+            stats.memory_limit_bytes = limit;
+        }
+        for (storage_type, bytes) in contents {
+            let stats = &mut by_type[type_index(storage_type)];
+            stats.entries += 1;
+            stats.bytes += bytes;
+        }
+        CacheStats {
+            entries: by_type.iter().map(|stats| stats.entries).sum(),
+            bytes: by_type.iter().map(|stats| stats.bytes).sum(),
+            memory_limit_bytes,
+            by_type,
+            expired_evictions: self.expired_evictions.load(Ordering::Relaxed),
+            memory_pressure_evictions: self.memory_pressure_evictions.load(Ordering::Relaxed),
+            ages: CacheAgeDistribution::from_ages(ages),
+        }
+    }
+}
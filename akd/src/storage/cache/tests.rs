@@ -31,6 +31,7 @@ async fn test_cache_put_and_expires() {
         },
         value: AkdValue::from("some value"),
         username: AkdLabel::from("user"),
+        retention_class: None,
     });
     let key = ValueStateKey(AkdLabel::from("user").0.to_vec(), 1);
     cache.put(&value_state).await;
@@ -57,6 +58,7 @@ async fn test_cache_overwrite() {
         },
         value: AkdValue::from("some value"),
         username: AkdLabel::from("user"),
+        retention_class: None,
     };
     let key = ValueStateKey(AkdLabel::from("user").0.to_vec(), 1);
 
@@ -69,6 +71,7 @@ async fn test_cache_overwrite() {
         },
         value: AkdValue::from("some value"),
         username: AkdLabel::from("user"),
+        retention_class: None,
     };
     cache.put(&DbRecord::ValueState(value_state)).await;
     cache
@@ -96,6 +99,7 @@ async fn test_cache_memory_pressure() {
         },
         value: AkdValue::from("some value"),
         username: AkdLabel::from("user"),
+        retention_class: None,
     });
     let key = ValueStateKey(AkdLabel::from("user").0.to_vec(), 1);
     cache.put(&value_state).await;
@@ -107,6 +111,7 @@ async fn test_cache_memory_pressure() {
     // we should exceed 10 bytes of storage utilization so the cache should clean the item.
     let got = cache.hit_test::<ValueState>(&key).await;
     assert_eq!(None, got);
+    assert_eq!(1, cache.stats().await.memory_pressure_evictions);
 }
 
 #[tokio::test]
@@ -127,6 +132,7 @@ async fn test_many_memory_pressure() {
             },
             value: AkdValue::from("test"),
             username: AkdLabel::from("user"),
+            retention_class: None,
         })
         .map(DbRecord::ValueState)
         .collect::<Vec<_>>();
@@ -140,3 +146,157 @@ async fn test_many_memory_pressure() {
     let all = cache.get_all().await;
     assert!(all.len() < 99);
 }
+
+#[tokio::test]
+async fn test_cache_stats() {
+    use crate::append_only_zks::{Azks, DEFAULT_AZKS_KEY};
+    use crate::storage::types::StorageType;
+
+    let cache = TimedCache::new(
+        Some(Duration::from_millis(50)),
+        None,
+        Some(Duration::from_secs(60)),
+    );
+    let value_states = (1..=2)
+        .map(|version| {
+            DbRecord::ValueState(ValueState {
+                epoch: version,
+                version,
+                label: NodeLabel {
+                    label_len: 1,
+                    label_val: [0u8; 32],
+                },
+                value: AkdValue::from("some value"),
+                username: AkdLabel::from("user"),
+                retention_class: None,
+            })
+        })
+        .collect::<Vec<_>>();
+    cache.batch_put(&value_states).await;
+    cache
+        .put(&DbRecord::Azks(Azks {
+            latest_epoch: 2,
+            num_nodes: 3,
+        }))
+        .await;
+
+    let user = AkdLabel::from("user").0.to_vec();
+    assert!(cache
+        .hit_test::<ValueState>(&ValueStateKey(user.clone(), 1))
+        .await
+        .is_some());
+    assert!(cache
+        .hit_test::<ValueState>(&ValueStateKey(user.clone(), 3))
+        .await
+        .is_none());
+    assert!(cache.hit_test::<Azks>(&DEFAULT_AZKS_KEY).await.is_some());
+
+    let stats = cache.stats().await;
+    assert_eq!(3, stats.entries);
+    let value_state_stats = stats.for_type(StorageType::ValueState).unwrap();
+    assert_eq!(
+        (2, 1, 1),
+        (
+            value_state_stats.entries,
+            value_state_stats.hits,
+            value_state_stats.misses
+        )
+    );
+    assert_eq!(Some(0.5), value_state_stats.hit_rate());
+    assert_eq!(1, stats.for_type(StorageType::Azks).unwrap().entries);
+    assert_eq!((2, 1), (stats.hits(), stats.misses()));
+    assert_eq!(
+        stats.bytes,
+        stats.by_type.iter().map(|stats| stats.bytes).sum::<usize>()
+    );
+    assert!(stats.bytes > 0);
+    let ages = stats.ages.unwrap();
+    assert!(ages.newest <= ages.median && ages.median <= ages.p90 && ages.p90 <= ages.oldest);
+
+    // The value states expire, while the AZKS is only ever replaced
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    assert_eq!(2, cache.evict_expired());
+    let stats = cache.stats().await;
+    assert_eq!(1, stats.entries);
+    assert_eq!(2, stats.expired_evictions);
+    assert_eq!(0, stats.memory_pressure_evictions);
+    assert_eq!(None, stats.ages);
+}
+
+#[tokio::test]
+async fn test_cache_partitions() {
+    use crate::storage::types::StorageType;
+    use crate::tree_node::{NodeKey, TreeNode, TreeNodeType, TreeNodeWithPreviousValue};
+    use crate::AzksValue;
+
+    // The shared part of the cache holds ~5 KB, while tree nodes have their own unlimited
+    // partition with a longer lifetime
+    let cache = TimedCache::new(
+        Some(Duration::from_millis(1000)),
+        Some(1024 * 5),
+        Some(Duration::from_millis(50)),
+    )
+    .with_partition(
+        StorageType::TreeNode,
+        CachePartition {
+            item_lifetime: Some(Duration::from_secs(60)),
+            memory_limit_bytes: None,
+        },
+    );
+
+    let label = NodeLabel {
+        label_len: 1,
+        label_val: [0u8; 32],
+    };
+    let node = TreeNode {
+        label,
+        last_epoch: 1,
+        min_descendant_epoch: 1,
+        parent: NodeLabel::root(),
+        node_type: TreeNodeType::Leaf,
+        left_child: None,
+        right_child: None,
+        hash: AzksValue(crate::hash::EMPTY_DIGEST),
+    };
+    cache
+        .put(&DbRecord::TreeNode(TreeNodeWithPreviousValue {
+            label,
+            latest_node: node,
+            previous_node: None,
+        }))
+        .await;
+
+    // A burst of value states exceeds the limit of the shared part of the cache
+    let value_states = (1..100)
+        .map(|i| ValueState {
+            epoch: i as u64,
+            version: i as u64,
+            label,
+            value: AkdValue::from("test"),
+            username: AkdLabel::from("user"),
+            retention_class: None,
+        })
+        .map(DbRecord::ValueState)
+        .collect::<Vec<_>>();
+    cache.batch_put(&value_states).await;
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    // The tree node is not evicted to make room for the value states
+    assert!(cache
+        .hit_test::<TreeNodeWithPreviousValue>(&NodeKey(label))
+        .await
+        .is_some());
+    let stats = cache.stats().await;
+    assert!(stats.memory_pressure_evictions > 0);
+    assert!(stats.for_type(StorageType::ValueState).unwrap().entries < 99);
+    let tree_node_stats = stats.for_type(StorageType::TreeNode).unwrap();
+    assert_eq!(
+        (1, true, None),
+        (
+            tree_node_stats.entries,
+            tree_node_stats.partitioned,
+            tree_node_stats.memory_limit_bytes
+        )
+    );
+    assert!(!stats.for_type(StorageType::ValueState).unwrap().partitioned);
+}
@@ -0,0 +1,698 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Codecs which serialize [DbRecord]s to and from bytes, for storage backends and data
+//! pipelines which handle records as opaque blobs.
+//!
+//! A codec is chosen when constructing a [StorageManager](crate::storage::StorageManager)
+//! (see [StorageManager::with_record_codec](crate::storage::StorageManager::with_record_codec)),
+//! and is then used by [StorageManager::encode_record](crate::storage::StorageManager::encode_record)
+//! and [StorageManager::decode_record](crate::storage::StorageManager::decode_record). The
+//! following codecs are provided, each behind its own feature:
+//!
+//! * `BincodeCodec` (`bincode_codec`): the serde representation of the records, encoded with bincode
+//! * `MessagePackCodec` (`msgpack_codec`): the serde representation of the records, encoded with MessagePack
+//! * `ProtobufCodec` (`protobuf_codec`): a protobuf encoding of the records, whose schema is documented on the type
+//!
+//! To migrate stored data from one codec to another, a [DualReadCodec] writes with the new
+//! codec, while still reading records which were written with the old one.
+
+use crate::errors::StorageError;
+use crate::storage::types::DbRecord;
+
+use std::sync::Arc;
+
+/// Serializes [DbRecord]s to and from bytes
+pub trait RecordCodec: Send + Sync {
+    /// A short name for the codec, used in error messages
+    fn name(&self) -> &'static str;
+
+    /// Serializes a record
+    fn encode(&self, record: &DbRecord) -> Result<Vec<u8>, StorageError>;
+
+    /// Deserializes a record which was serialized by this codec
+    fn decode(&self, bytes: &[u8]) -> Result<DbRecord, StorageError>;
+}
+
+/// A codec which encodes records with a primary codec, and decodes records with the primary
+/// codec falling back to a secondary codec. This allows the records stored in a database to be
+/// migrated from the secondary codec to the primary one gradually, as they are rewritten.
+#[derive(Clone)]
+pub struct DualReadCodec {
+    primary: Arc<dyn RecordCodec>,
+    fallback: Arc<dyn RecordCodec>,
+}
+
+impl DualReadCodec {
+    /// Creates a codec which writes with `primary`, and reads with `primary` then `fallback`
+    pub fn new(primary: impl RecordCodec + 'static, fallback: impl RecordCodec + 'static) -> Self {
+        Self {
+            primary: Arc::new(primary),
+            fallback: Arc::new(fallback),
+        }
+    }
+}
+
+impl RecordCodec for DualReadCodec {
+    fn name(&self) -> &'static str {
+        self.primary.name()
+    }
+
+    fn encode(&self, record: &DbRecord) -> Result<Vec<u8>, StorageError> {
+        self.primary.encode(record)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<DbRecord, StorageError> {
+        match self.primary.decode(bytes) {
+            Ok(record) => Ok(record),
+            Err(primary_err) => self.fallback.decode(bytes).map_err(|fallback_err| {
+                StorageError::Other(format!(
+                    "Failed to decode record with either codec: {primary_err}; {fallback_err}"
+                ))
+            }),
+        }
+    }
+}
+
+/// A [RecordCodec] which encodes the serde representation of records with bincode
+#[cfg(feature = "bincode_codec")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BincodeCodec;
+
+#[cfg(feature = "bincode_codec")]
+impl RecordCodec for BincodeCodec {
+    fn name(&self) -> &'static str {
+        "bincode"
+    }
+
+    fn encode(&self, record: &DbRecord) -> Result<Vec<u8>, StorageError> {
+        bincode::serialize(record)
+            .map_err(|err| StorageError::Other(format!("Failed to encode record (bincode): {err}")))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<DbRecord, StorageError> {
+        bincode::deserialize(bytes)
+            .map_err(|err| StorageError::Other(format!("Failed to decode record (bincode): {err}")))
+    }
+}
+
+/// A [RecordCodec] which encodes the serde representation of records with MessagePack
+#[cfg(feature = "msgpack_codec")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MessagePackCodec;
+
+#[cfg(feature = "msgpack_codec")]
+impl RecordCodec for MessagePackCodec {
+    fn name(&self) -> &'static str {
+        "msgpack"
+    }
+
+    fn encode(&self, record: &DbRecord) -> Result<Vec<u8>, StorageError> {
+        rmp_serde::to_vec_named(record)
+            .map_err(|err| StorageError::Other(format!("Failed to encode record (msgpack): {err}")))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<DbRecord, StorageError> {
+        rmp_serde::from_slice(bytes)
+            .map_err(|err| StorageError::Other(format!("Failed to decode record (msgpack): {err}")))
+    }
+}
+
+#[cfg(feature = "protobuf_codec")]
+pub use protobuf_codec::ProtobufCodec;
+
+#[cfg(feature = "protobuf_codec")]
+mod protobuf_codec {
+    use super::RecordCodec;
+    use crate::errors::StorageError;
+    use crate::storage::types::{
+        AnchorReceipt, AuditEpoch, DbRecord, OperationRecord, PublishLease, SoftDeletion,
+        ValueState,
+    };
+    use crate::tree_node::{TreeNode, TreeNodeType, TreeNodeWithPreviousValue};
+    use crate::{AkdLabel, AkdValue, Azks, AzksValue, NodeLabel, RetentionClass};
+
+    use protobuf::rt::WireType;
+    use protobuf::{CodedInputStream, CodedOutputStream};
+
+    /// A [RecordCodec] which encodes records as protobuf messages, according to the schema
+    ///
+    /// ```protobuf
+    /// message DbRecord {
+    ///   oneof record {
+    ///     Azks azks = 1;
+    ///     TreeNodeWithPreviousValue tree_node = 2;
+    ///     ValueState value_state = 3;
+    ///     OperationRecord operation_record = 4;
+    ///     SoftDeletion soft_deletion = 5;
+    ///     AnchorReceipt anchor_receipt = 6;
+    ///     PublishLease publish_lease = 7;
+    ///     AuditEpoch audit_epoch = 8;
+    ///   }
+    /// }
+    /// message NodeLabel { bytes label_val = 1; uint32 label_len = 2; }
+    /// message Azks { uint64 latest_epoch = 1; uint64 num_nodes = 2; }
+    /// message TreeNode {
+    ///   NodeLabel label = 1;
+    ///   uint64 last_epoch = 2;
+    ///   uint64 min_descendant_epoch = 3;
+    ///   NodeLabel parent = 4;
+    ///   uint32 node_type = 5;
+    ///   NodeLabel left_child = 6;
+    ///   NodeLabel right_child = 7;
+    ///   bytes hash = 8;
+    /// }
+    /// message TreeNodeWithPreviousValue {
+    ///   NodeLabel label = 1;
+    ///   TreeNode latest_node = 2;
+    ///   TreeNode previous_node = 3;
+    /// }
+    /// message ValueState {
+    ///   bytes value = 1;
+    ///   uint64 version = 2;
+    ///   NodeLabel label = 3;
+    ///   uint64 epoch = 4;
+    ///   bytes username = 5;
+    ///   optional uint32 retention_class = 6;
+    /// }
+    /// message OperationRecord {
+    ///   uint64 sequence = 1;
+    ///   uint32 kind = 2;
+    ///   uint64 epoch = 3;
+    ///   uint64 timestamp_ms = 4;
+    ///   string identity = 5;
+    ///   string metadata = 6;
+    ///   string detail = 7;
+    /// }
+    /// message SoftDeletion { bytes username = 1; uint64 epoch = 2; }
+    /// message AnchorReceipt {
+    ///   uint64 epoch = 1;
+    ///   string anchor = 2;
+    ///   bytes root_hash = 3;
+    ///   uint64 timestamp_ms = 4;
+    ///   bytes receipt = 5;
+    /// }
+    /// message PublishLease { string holder = 1; uint64 token = 2; uint64 expires_at_ms = 3; }
+    /// message AuditEpoch {
+    ///   uint64 index = 1;
+    ///   uint64 start_epoch = 2;
+    ///   bytes start_root_hash = 3;
+    ///   uint64 end_epoch = 4;
+    ///   bytes end_root_hash = 5;
+    ///   uint64 timestamp_ms = 6;
+    /// }
+    /// ```
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct ProtobufCodec;
+
+    impl RecordCodec for ProtobufCodec {
+        fn name(&self) -> &'static str {
+            "protobuf"
+        }
+
+        fn encode(&self, record: &DbRecord) -> Result<Vec<u8>, StorageError> {
+            let (field, message) = match record {
+                DbRecord::Azks(azks) => (1, encode_azks(azks)),
+                DbRecord::TreeNode(node) => (2, encode_tree_node_with_previous_value(node)),
+                DbRecord::ValueState(state) => (3, encode_value_state(state)),
+                DbRecord::OperationRecord(record) => (4, encode_operation_record(record)),
+                DbRecord::SoftDeletion(deletion) => (5, encode_soft_deletion(deletion)),
+                DbRecord::AnchorReceipt(receipt) => (6, encode_anchor_receipt(receipt)),
+                DbRecord::PublishLease(lease) => (7, encode_publish_lease(lease)),
+                DbRecord::AuditEpoch(audit_epoch) => (8, encode_audit_epoch(audit_epoch)),
+            };
+            encode_message(|out| out.write_bytes(field, &message?)).map_err(to_encode_error)
+        }
+
+        fn decode(&self, bytes: &[u8]) -> Result<DbRecord, StorageError> {
+            let mut record = None;
+            decode_message(bytes, |field, input| {
+                record = match field {
+                    1 => Some(DbRecord::Azks(decode_azks(&input.read_bytes()?)?)),
+                    2 => Some(DbRecord::TreeNode(decode_tree_node_with_previous_value(
+                        &input.read_bytes()?,
+                    )?)),
+                    3 => Some(DbRecord::ValueState(decode_value_state(
+                        &input.read_bytes()?,
+                    )?)),
+                    4 => Some(DbRecord::OperationRecord(decode_operation_record(
+                        &input.read_bytes()?,
+                    )?)),
+                    5 => Some(DbRecord::SoftDeletion(decode_soft_deletion(
+                        &input.read_bytes()?,
+                    )?)),
+                    6 => Some(DbRecord::AnchorReceipt(decode_anchor_receipt(
+                        &input.read_bytes()?,
+                    )?)),
+                    7 => Some(DbRecord::PublishLease(decode_publish_lease(
+                        &input.read_bytes()?,
+                    )?)),
+                    8 => Some(DbRecord::AuditEpoch(decode_audit_epoch(
+                        &input.read_bytes()?,
+                    )?)),
+                    _ => return Ok(false),
+                };
+                Ok(true)
+            })
+            .map_err(to_decode_error)?;
+            record.ok_or_else(|| to_decode_error(missing("record")))
+        }
+    }
+
+    fn to_encode_error(err: protobuf::Error) -> StorageError {
+        StorageError::Other(format!("Failed to encode record (protobuf): {err}"))
+    }
+
+    fn to_decode_error(err: protobuf::Error) -> StorageError {
+        StorageError::Other(format!("Failed to decode record (protobuf): {err}"))
+    }
+
+    fn missing(field: &str) -> protobuf::Error {
+        invalid(format!("Required field {field} missing"))
+    }
+
+    fn invalid(message: String) -> protobuf::Error {
+        protobuf::Error::from(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            message,
+        ))
+    }
+
+    fn encode_message(
+        write: impl FnOnce(&mut CodedOutputStream) -> protobuf::Result<()>,
+    ) -> protobuf::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        {
+            let mut out = CodedOutputStream::vec(&mut bytes);
+            write(&mut out)?;
+            out.flush()?;
+        }
+        Ok(bytes)
+    }
+
+    /// Reads the fields of a message, passing each to `read_field`, which returns whether it
+    /// consumed the field. Unknown fields are skipped.
+    fn decode_message(
+        bytes: &[u8],
+        mut read_field: impl FnMut(u32, &mut CodedInputStream) -> protobuf::Result<bool>,
+    ) -> protobuf::Result<()> {
+        let mut input = CodedInputStream::from_bytes(bytes);
+        while let Some(tag) = input.read_raw_tag_or_eof()? {
+            if !read_field(tag >> 3, &mut input)? {
+                let wire_type = WireType::new(tag & 0x7)
+                    .ok_or_else(|| invalid(format!("Unknown wire type in tag {tag}")))?;
+                input.skip_field(wire_type)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn encode_node_label(label: &NodeLabel) -> protobuf::Result<Vec<u8>> {
+        encode_message(|out| {
+            out.write_bytes(1, &label.label_val)?;
+            out.write_uint32(2, label.label_len)
+        })
+    }
+
+    fn decode_node_label(bytes: &[u8]) -> protobuf::Result<NodeLabel> {
+        let mut label_val = None;
+        let mut label_len = 0;
+        decode_message(bytes, |field, input| {
+            match field {
+                1 => {
+                    let val: [u8; 32] = input.read_bytes()?.try_into().map_err(|_| {
+                        invalid("A node label's value must be 32 bytes".to_string())
+                    })?;
+                    label_val = Some(val);
+                }
+                2 => label_len = input.read_uint32()?,
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })?;
+        Ok(NodeLabel::new(
+            label_val.ok_or_else(|| missing("label_val"))?,
+            label_len,
+        ))
+    }
+
+    fn encode_azks(azks: &Azks) -> protobuf::Result<Vec<u8>> {
+        encode_message(|out| {
+            out.write_uint64(1, azks.latest_epoch)?;
+            out.write_uint64(2, azks.num_nodes)
+        })
+    }
+
+    fn decode_azks(bytes: &[u8]) -> protobuf::Result<Azks> {
+        let mut azks = DbRecord::build_azks(0, 0);
+        decode_message(bytes, |field, input| {
+            match field {
+                1 => azks.latest_epoch = input.read_uint64()?,
+                2 => azks.num_nodes = input.read_uint64()?,
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })?;
+        Ok(azks)
+    }
+
+    fn encode_tree_node(node: &TreeNode) -> protobuf::Result<Vec<u8>> {
+        encode_message(|out| {
+            out.write_bytes(1, &encode_node_label(&node.label)?)?;
+            out.write_uint64(2, node.last_epoch)?;
+            out.write_uint64(3, node.min_descendant_epoch)?;
+            out.write_bytes(4, &encode_node_label(&node.parent)?)?;
+            out.write_uint32(5, node.node_type as u32)?;
+            if let Some(left_child) = &node.left_child {
+                out.write_bytes(6, &encode_node_label(left_child)?)?;
+            }
+            if let Some(right_child) = &node.right_child {
+                out.write_bytes(7, &encode_node_label(right_child)?)?;
+            }
+            out.write_bytes(8, &node.hash.0)
+        })
+    }
+
+    fn decode_tree_node(bytes: &[u8]) -> protobuf::Result<TreeNode> {
+        let mut label = None;
+        let mut last_epoch = 0;
+        let mut min_descendant_epoch = 0;
+        let mut parent = None;
+        let mut node_type = None;
+        let mut left_child = None;
+        let mut right_child = None;
+        let mut hash = None;
+        decode_message(bytes, |field, input| {
+            match field {
+                1 => label = Some(decode_node_label(&input.read_bytes()?)?),
+                2 => last_epoch = input.read_uint64()?,
+                3 => min_descendant_epoch = input.read_uint64()?,
+                4 => parent = Some(decode_node_label(&input.read_bytes()?)?),
+                5 => {
+                    node_type = Some(match input.read_uint32()? {
+                        1 => TreeNodeType::Leaf,
+                        2 => TreeNodeType::Root,
+                        3 => TreeNodeType::Interior,
+                        other => return Err(invalid(format!("Unknown node type {other}"))),
+                    })
+                }
+                6 => left_child = Some(decode_node_label(&input.read_bytes()?)?),
+                7 => right_child = Some(decode_node_label(&input.read_bytes()?)?),
+                8 => {
+                    let digest: crate::Digest = input
+                        .read_bytes()?
+                        .try_into()
+                        .map_err(|_| invalid("A node's hash must be 32 bytes".to_string()))?;
+                    hash = Some(AzksValue(digest));
+                }
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })?;
+        Ok(TreeNode {
+            label: label.ok_or_else(|| missing("label"))?,
+            last_epoch,
+            min_descendant_epoch,
+            parent: parent.ok_or_else(|| missing("parent"))?,
+            node_type: node_type.ok_or_else(|| missing("node_type"))?,
+            left_child,
+            right_child,
+            hash: hash.ok_or_else(|| missing("hash"))?,
+        })
+    }
+
+    fn encode_tree_node_with_previous_value(
+        node: &TreeNodeWithPreviousValue,
+    ) -> protobuf::Result<Vec<u8>> {
+        encode_message(|out| {
+            out.write_bytes(1, &encode_node_label(&node.label)?)?;
+            out.write_bytes(2, &encode_tree_node(&node.latest_node)?)?;
+            if let Some(previous_node) = &node.previous_node {
+                out.write_bytes(3, &encode_tree_node(previous_node)?)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn decode_tree_node_with_previous_value(
+        bytes: &[u8],
+    ) -> protobuf::Result<TreeNodeWithPreviousValue> {
+        let mut label = None;
+        let mut latest_node = None;
+        let mut previous_node = None;
+        decode_message(bytes, |field, input| {
+            match field {
+                1 => label = Some(decode_node_label(&input.read_bytes()?)?),
+                2 => latest_node = Some(decode_tree_node(&input.read_bytes()?)?),
+                3 => previous_node = Some(decode_tree_node(&input.read_bytes()?)?),
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })?;
+        Ok(TreeNodeWithPreviousValue {
+            label: label.ok_or_else(|| missing("label"))?,
+            latest_node: latest_node.ok_or_else(|| missing("latest_node"))?,
+            previous_node,
+        })
+    }
+
+    fn encode_value_state(state: &ValueState) -> protobuf::Result<Vec<u8>> {
+        encode_message(|out| {
+            out.write_bytes(1, &state.value.0)?;
+            out.write_uint64(2, state.version)?;
+            out.write_bytes(3, &encode_node_label(&state.label)?)?;
+            out.write_uint64(4, state.epoch)?;
+            out.write_bytes(5, &state.username.0)?;
+            if let Some(retention_class) = state.retention_class {
+                out.write_uint32(6, retention_class as u32)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn decode_value_state(bytes: &[u8]) -> protobuf::Result<ValueState> {
+        let mut value = Vec::new();
+        let mut version = 0;
+        let mut label = None;
+        let mut epoch = 0;
+        let mut username = Vec::new();
+        let mut retention_class = None;
+        decode_message(bytes, |field, input| {
+            match field {
+                1 => value = input.read_bytes()?,
+                2 => version = input.read_uint64()?,
+                3 => label = Some(decode_node_label(&input.read_bytes()?)?),
+                4 => epoch = input.read_uint64()?,
+                5 => username = input.read_bytes()?,
+                6 => retention_class = Some(input.read_uint32()?),
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })?;
+        let retention_class = retention_class
+            .map(|class| {
+                u8::try_from(class)
+                    .ok()
+                    .and_then(RetentionClass::from_u8)
+                    .ok_or_else(|| invalid(format!("Unknown retention class {class}")))
+            })
+            .transpose()?;
+        Ok(ValueState {
+            value: AkdValue(value),
+            version,
+            label: label.ok_or_else(|| missing("label"))?,
+            epoch,
+            username: AkdLabel(username),
+            retention_class,
+        })
+    }
+
+    fn encode_operation_record(record: &OperationRecord) -> protobuf::Result<Vec<u8>> {
+        encode_message(|out| {
+            out.write_uint64(1, record.sequence)?;
+            out.write_uint32(2, record.kind as u32)?;
+            out.write_uint64(3, record.epoch)?;
+            out.write_uint64(4, record.timestamp_ms)?;
+            out.write_string(5, &record.identity)?;
+            out.write_string(6, &record.metadata)?;
+            out.write_string(7, &record.detail)
+        })
+    }
+
+    fn decode_operation_record(bytes: &[u8]) -> protobuf::Result<OperationRecord> {
+        let mut sequence = 0;
+        let mut kind = None;
+        let mut epoch = 0;
+        let mut timestamp_ms = 0;
+        let mut identity = String::new();
+        let mut metadata = String::new();
+        let mut detail = String::new();
+        decode_message(bytes, |field, input| {
+            match field {
+                1 => sequence = input.read_uint64()?,
+                2 => kind = Some(input.read_uint32()?),
+                3 => epoch = input.read_uint64()?,
+                4 => timestamp_ms = input.read_uint64()?,
+                5 => identity = input.read_string()?,
+                6 => metadata = input.read_string()?,
+                7 => detail = input.read_string()?,
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })?;
+        let kind = u8::try_from(kind.ok_or_else(|| missing("kind"))?)
+            .map_err(|_| invalid("Operation kind out of range".to_string()))?;
+        DbRecord::build_operation_record(
+            sequence,
+            kind,
+            epoch,
+            timestamp_ms,
+            identity,
+            metadata,
+            detail,
+        )
+        .map_err(invalid)
+    }
+
+    fn encode_soft_deletion(deletion: &SoftDeletion) -> protobuf::Result<Vec<u8>> {
+        encode_message(|out| {
+            out.write_bytes(1, &deletion.username.0)?;
+            out.write_uint64(2, deletion.epoch)
+        })
+    }
+
+    fn decode_soft_deletion(bytes: &[u8]) -> protobuf::Result<SoftDeletion> {
+        let mut username = Vec::new();
+        let mut epoch = 0;
+        decode_message(bytes, |field, input| {
+            match field {
+                1 => username = input.read_bytes()?,
+                2 => epoch = input.read_uint64()?,
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })?;
+        Ok(SoftDeletion {
+            username: AkdLabel(username),
+            epoch,
+        })
+    }
+
+    fn encode_anchor_receipt(receipt: &AnchorReceipt) -> protobuf::Result<Vec<u8>> {
+        encode_message(|out| {
+            out.write_uint64(1, receipt.epoch)?;
+            out.write_string(2, &receipt.anchor)?;
+            out.write_bytes(3, &receipt.root_hash)?;
+            out.write_uint64(4, receipt.timestamp_ms)?;
+            out.write_bytes(5, &receipt.receipt)
+        })
+    }
+
+    fn decode_anchor_receipt(bytes: &[u8]) -> protobuf::Result<AnchorReceipt> {
+        let mut epoch = 0;
+        let mut anchor = String::new();
+        let mut root_hash = None;
+        let mut timestamp_ms = 0;
+        let mut receipt = Vec::new();
+        decode_message(bytes, |field, input| {
+            match field {
+                1 => epoch = input.read_uint64()?,
+                2 => anchor = input.read_string()?,
+                3 => {
+                    let digest: crate::Digest = input.read_bytes()?.try_into().map_err(|_| {
+                        invalid("An anchored root hash must be 32 bytes".to_string())
+                    })?;
+                    root_hash = Some(digest);
+                }
+                4 => timestamp_ms = input.read_uint64()?,
+                5 => receipt = input.read_bytes()?,
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })?;
+        Ok(AnchorReceipt {
+            epoch,
+            anchor,
+            root_hash: root_hash.ok_or_else(|| missing("root_hash"))?,
+            timestamp_ms,
+            receipt,
+        })
+    }
+
+    fn encode_publish_lease(lease: &PublishLease) -> protobuf::Result<Vec<u8>> {
+        encode_message(|out| {
+            out.write_string(1, &lease.holder)?;
+            out.write_uint64(2, lease.token)?;
+            out.write_uint64(3, lease.expires_at_ms)
+        })
+    }
+
+    fn decode_publish_lease(bytes: &[u8]) -> protobuf::Result<PublishLease> {
+        let mut lease = PublishLease {
+            holder: String::new(),
+            token: 0,
+            expires_at_ms: 0,
+        };
+        decode_message(bytes, |field, input| {
+            match field {
+                1 => lease.holder = input.read_string()?,
+                2 => lease.token = input.read_uint64()?,
+                3 => lease.expires_at_ms = input.read_uint64()?,
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })?;
+        Ok(lease)
+    }
+
+    fn encode_audit_epoch(audit_epoch: &AuditEpoch) -> protobuf::Result<Vec<u8>> {
+        encode_message(|out| {
+            out.write_uint64(1, audit_epoch.index)?;
+            out.write_uint64(2, audit_epoch.start_epoch)?;
+            out.write_bytes(3, &audit_epoch.start_root_hash)?;
+            out.write_uint64(4, audit_epoch.end_epoch)?;
+            out.write_bytes(5, &audit_epoch.end_root_hash)?;
+            out.write_uint64(6, audit_epoch.timestamp_ms)
+        })
+    }
+
+    fn decode_audit_epoch(bytes: &[u8]) -> protobuf::Result<AuditEpoch> {
+        let mut index = 0;
+        let mut start_epoch = 0;
+        let mut start_root_hash = None;
+        let mut end_epoch = 0;
+        let mut end_root_hash = None;
+        let mut timestamp_ms = 0;
+        decode_message(bytes, |field, input| {
+            match field {
+                1 => index = input.read_uint64()?,
+                2 => start_epoch = input.read_uint64()?,
+                3 => start_root_hash = Some(read_digest(input)?),
+                4 => end_epoch = input.read_uint64()?,
+                5 => end_root_hash = Some(read_digest(input)?),
+                6 => timestamp_ms = input.read_uint64()?,
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })?;
+        Ok(AuditEpoch {
+            index,
+            start_epoch,
+            start_root_hash: start_root_hash.ok_or_else(|| missing("start_root_hash"))?,
+            end_epoch,
+            end_root_hash: end_root_hash.ok_or_else(|| missing("end_root_hash"))?,
+            timestamp_ms,
+        })
+    }
+
+    fn read_digest(input: &mut CodedInputStream) -> protobuf::Result<crate::Digest> {
+        input
+            .read_bytes()?
+            .try_into()
+            .map_err(|_| invalid("The root hash of an audit epoch must be 32 bytes".to_string()))
+    }
+}
@@ -0,0 +1,304 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! An object-safe form of the [Database] trait, so that the storage backend of a directory
+//! can be chosen at runtime (e.g. from a configuration file) rather than by a generic
+//! parameter.
+//!
+//! The retrieval methods of [Database] are generic over the [Storable] type being retrieved,
+//! and so cannot be called through a trait object. [DynDatabase] instead retrieves records by
+//! their full binary keys (see [Storable::get_full_binary_key_id]), which carry the type of
+//! the record. Every [Database] implements [DynDatabase], and an `Arc<dyn DynDatabase>`
+//! implements [Database], so it can back a [StorageManager](crate::storage::StorageManager):
+//!
+//! ```
+//! use akd::storage::dynamic::DynDatabase;
+//! use akd::storage::memory::AsyncInMemoryDatabase;
+//! use akd::storage::StorageManager;
+//! use std::sync::Arc;
+//!
+//! let db: Arc<dyn DynDatabase> = Arc::new(AsyncInMemoryDatabase::new());
+//! let storage_manager = StorageManager::new_no_cache(db);
+//! ```
+//!
+//! The VRF key storage of a directory can be chosen at runtime in the same way, with an
+//! `Arc<dyn DynVRFKeyStorage>` (see [DynVRFKeyStorage](crate::ecvrf::DynVRFKeyStorage)).
+//! The administrative operations which require [StorageUtil](crate::storage::StorageUtil)
+//! are not available over a [DynDatabase].
+
+use crate::errors::StorageError;
+use crate::storage::keys::{parse_key, ParsedKey};
+use crate::storage::types::{
+    AnchorReceipt, AuditEpoch, DbRecord, KeyData, OperationRecord, PublishLease, SoftDeletion,
+    ValueState, ValueStateRetrievalFlag,
+};
+use crate::storage::{Database, DbSetState, Storable};
+use crate::tree_node::TreeNodeWithPreviousValue;
+use crate::{AkdLabel, AkdValue, Azks};
+
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// An object-safe form of [Database], whose records are retrieved by their full binary keys
+#[async_trait]
+pub trait DynDatabase: Send + Sync {
+    /// Set a record in the database
+    async fn set(&self, record: DbRecord) -> Result<(), StorageError>;
+
+    /// Set multiple records in the database with a minimal set of operations
+    async fn batch_set(
+        &self,
+        records: Vec<DbRecord>,
+        state: DbSetState,
+    ) -> Result<(), StorageError>;
+
+    /// Set a record in the database only if the stored record with the same id has the
+    /// expected version stamp (see [Database::set_if_version])
+    async fn set_if_version(
+        &self,
+        record: DbRecord,
+        expected_version: Option<u64>,
+    ) -> Result<(), StorageError>;
+
+    /// Set multiple records in the database, along with records which are only written if the
+    /// stored records with the same ids have the expected version stamps (see
+    /// [Database::batch_set_if_versions])
+    async fn batch_set_if_versions(
+        &self,
+        records: Vec<DbRecord>,
+        conditional: Vec<(DbRecord, Option<u64>)>,
+        state: DbSetState,
+    ) -> Result<(), StorageError>;
+
+    /// Retrieve a stored record from the database by its full binary key
+    async fn get_by_binary_key(&self, key: &[u8]) -> Result<DbRecord, StorageError>;
+
+    /// Retrieve a batch of records from the database by their full binary keys, which may be
+    /// of different types. Keys which are not found are omitted from the result, and the order
+    /// of the records returned is unspecified.
+    async fn batch_get_by_binary_keys(
+        &self,
+        keys: &[Vec<u8>],
+    ) -> Result<Vec<DbRecord>, StorageError>;
+
+    /// Retrieve the user data for a given user
+    async fn get_user_data(&self, username: &AkdLabel) -> Result<KeyData, StorageError>;
+
+    /// Retrieve a specific state for a given user
+    async fn get_user_state(
+        &self,
+        username: &AkdLabel,
+        flag: ValueStateRetrievalFlag,
+    ) -> Result<ValueState, StorageError>;
+
+    /// Retrieve the user -> state version mapping in bulk
+    async fn get_user_state_versions(
+        &self,
+        usernames: &[AkdLabel],
+        flag: ValueStateRetrievalFlag,
+    ) -> Result<HashMap<AkdLabel, (u64, AkdValue)>, StorageError>;
+
+    /// Closes the connections of the database (see [Database::close])
+    async fn close(&self) -> Result<(), StorageError>;
+}
+
+fn parse_binary_key(key: &[u8]) -> Result<ParsedKey, StorageError> {
+    parse_key(key).map_err(|err| StorageError::Other(format!("Invalid storage key: {err}")))
+}
+
+/// Retrieves the records of a single type into `records`, skipping the call to the database
+/// when there are no ids of the type
+async fn batch_get_into<St: Storable, D: Database>(
+    db: &D,
+    ids: Vec<St::StorageKey>,
+    records: &mut Vec<DbRecord>,
+) -> Result<(), StorageError> {
+    if !ids.is_empty() {
+        records.extend(db.batch_get::<St>(&ids).await?);
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl<D: Database> DynDatabase for D {
+    async fn set(&self, record: DbRecord) -> Result<(), StorageError> {
+        Database::set(self, record).await
+    }
+
+    async fn batch_set(
+        &self,
+        records: Vec<DbRecord>,
+        state: DbSetState,
+    ) -> Result<(), StorageError> {
+        Database::batch_set(self, records, state).await
+    }
+
+    async fn set_if_version(
+        &self,
+        record: DbRecord,
+        expected_version: Option<u64>,
+    ) -> Result<(), StorageError> {
+        Database::set_if_version(self, record, expected_version).await
+    }
+
+    async fn batch_set_if_versions(
+        &self,
+        records: Vec<DbRecord>,
+        conditional: Vec<(DbRecord, Option<u64>)>,
+        state: DbSetState,
+    ) -> Result<(), StorageError> {
+        Database::batch_set_if_versions(self, records, conditional, state).await
+    }
+
+    async fn get_by_binary_key(&self, key: &[u8]) -> Result<DbRecord, StorageError> {
+        match parse_binary_key(key)? {
+            ParsedKey::Azks(key) => self.get::<Azks>(&key).await,
+            ParsedKey::TreeNode(key) => self.get::<TreeNodeWithPreviousValue>(&key).await,
+            ParsedKey::ValueState(key) => self.get::<ValueState>(&key).await,
+            ParsedKey::OperationRecord(key) => self.get::<OperationRecord>(&key).await,
+            ParsedKey::SoftDeletion(key) => self.get::<SoftDeletion>(&key).await,
+            ParsedKey::AnchorReceipt(key) => self.get::<AnchorReceipt>(&key).await,
+            ParsedKey::PublishLease(key) => self.get::<PublishLease>(&key).await,
+            ParsedKey::AuditEpoch(key) => self.get::<AuditEpoch>(&key).await,
+        }
+    }
+
+    async fn batch_get_by_binary_keys(
+        &self,
+        keys: &[Vec<u8>],
+    ) -> Result<Vec<DbRecord>, StorageError> {
+        let mut azks = Vec::new();
+        let mut nodes = Vec::new();
+        let mut states = Vec::new();
+        let mut operations = Vec::new();
+        let mut deletions = Vec::new();
+        let mut receipts = Vec::new();
+        let mut leases = Vec::new();
+        let mut audit_epochs = Vec::new();
+        for key in keys {
+            match parse_binary_key(key)? {
+                ParsedKey::Azks(key) => azks.push(key),
+                ParsedKey::TreeNode(key) => nodes.push(key),
+                ParsedKey::ValueState(key) => states.push(key),
+                ParsedKey::OperationRecord(key) => operations.push(key),
+                ParsedKey::SoftDeletion(key) => deletions.push(key),
+                ParsedKey::AnchorReceipt(key) => receipts.push(key),
+                ParsedKey::PublishLease(key) => leases.push(key),
+                ParsedKey::AuditEpoch(key) => audit_epochs.push(key),
+            }
+        }
+
+        let mut records = Vec::with_capacity(keys.len());
+        batch_get_into::<Azks, _>(self, azks, &mut records).await?;
+        batch_get_into::<TreeNodeWithPreviousValue, _>(self, nodes, &mut records).await?;
+        batch_get_into::<ValueState, _>(self, states, &mut records).await?;
+        batch_get_into::<OperationRecord, _>(self, operations, &mut records).await?;
+        batch_get_into::<SoftDeletion, _>(self, deletions, &mut records).await?;
+        batch_get_into::<AnchorReceipt, _>(self, receipts, &mut records).await?;
+        batch_get_into::<PublishLease, _>(self, leases, &mut records).await?;
+        batch_get_into::<AuditEpoch, _>(self, audit_epochs, &mut records).await?;
+        Ok(records)
+    }
+
+    async fn get_user_data(&self, username: &AkdLabel) -> Result<KeyData, StorageError> {
+        Database::get_user_data(self, username).await
+    }
+
+    async fn get_user_state(
+        &self,
+        username: &AkdLabel,
+        flag: ValueStateRetrievalFlag,
+    ) -> Result<ValueState, StorageError> {
+        Database::get_user_state(self, username, flag).await
+    }
+
+    async fn get_user_state_versions(
+        &self,
+        usernames: &[AkdLabel],
+        flag: ValueStateRetrievalFlag,
+    ) -> Result<HashMap<AkdLabel, (u64, AkdValue)>, StorageError> {
+        Database::get_user_state_versions(self, usernames, flag).await
+    }
+
+    async fn close(&self) -> Result<(), StorageError> {
+        Database::close(self).await
+    }
+}
+
+#[async_trait]
+impl Database for Arc<dyn DynDatabase> {
+    async fn set(&self, record: DbRecord) -> Result<(), StorageError> {
+        DynDatabase::set(self.as_ref(), record).await
+    }
+
+    async fn batch_set(
+        &self,
+        records: Vec<DbRecord>,
+        state: DbSetState,
+    ) -> Result<(), StorageError> {
+        DynDatabase::batch_set(self.as_ref(), records, state).await
+    }
+
+    async fn set_if_version(
+        &self,
+        record: DbRecord,
+        expected_version: Option<u64>,
+    ) -> Result<(), StorageError> {
+        DynDatabase::set_if_version(self.as_ref(), record, expected_version).await
+    }
+
+    async fn batch_set_if_versions(
+        &self,
+        records: Vec<DbRecord>,
+        conditional: Vec<(DbRecord, Option<u64>)>,
+        state: DbSetState,
+    ) -> Result<(), StorageError> {
+        DynDatabase::batch_set_if_versions(self.as_ref(), records, conditional, state).await
+    }
+
+    async fn get<St: Storable>(&self, id: &St::StorageKey) -> Result<DbRecord, StorageError> {
+        self.as_ref()
+            .get_by_binary_key(&St::get_full_binary_key_id(id))
+            .await
+    }
+
+    async fn batch_get<St: Storable>(
+        &self,
+        ids: &[St::StorageKey],
+    ) -> Result<Vec<DbRecord>, StorageError> {
+        let keys = ids
+            .iter()
+            .map(St::get_full_binary_key_id)
+            .collect::<Vec<_>>();
+        self.as_ref().batch_get_by_binary_keys(&keys).await
+    }
+
+    async fn get_user_data(&self, username: &AkdLabel) -> Result<KeyData, StorageError> {
+        DynDatabase::get_user_data(self.as_ref(), username).await
+    }
+
+    async fn get_user_state(
+        &self,
+        username: &AkdLabel,
+        flag: ValueStateRetrievalFlag,
+    ) -> Result<ValueState, StorageError> {
+        DynDatabase::get_user_state(self.as_ref(), username, flag).await
+    }
+
+    async fn get_user_state_versions(
+        &self,
+        usernames: &[AkdLabel],
+        flag: ValueStateRetrievalFlag,
+    ) -> Result<HashMap<AkdLabel, (u64, AkdValue)>, StorageError> {
+        DynDatabase::get_user_state_versions(self.as_ref(), usernames, flag).await
+    }
+
+    async fn close(&self) -> Result<(), StorageError> {
+        DynDatabase::close(self.as_ref()).await
+    }
+}
@@ -0,0 +1,481 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! The framing of the full binary keys of [Storable](crate::storage::Storable) records.
+//!
+//! Every key starts with the [StorageType] of its record, followed by the fields of the
+//! record's key, each encoded with one of two primitives:
+//!
+//! * an integer is encoded as a single byte holding the number of bytes which follow (0 to 8),
+//!   followed by the minimal big-endian bytes of the integer. Smaller integers have shorter
+//!   encodings, so the byte-wise order of the encodings is the numeric order of the integers.
+//! * a byte string is encoded as its length (as an integer), followed by its bytes. No
+//!   encoding is the prefix of another, so the keys which share a leading byte string (such
+//!   as the value states of one label) form a contiguous range of the key space.
+//!
+//! The encodings are canonical: each key has exactly one encoding, and decoding rejects any
+//! bytes which are not the encoding of a key, so that a key can be parsed back out of a
+//! database (with [parse_key]) for range scans and debugging tools.
+
+use crate::storage::types::{AnchorReceiptKey, StorageType, ValueStateKey};
+use crate::tree_node::NodeKey;
+use crate::{AkdLabel, NodeLabel};
+
+use std::fmt;
+
+/// The largest number of bytes in the encoding of an integer
+const MAX_VARINT_BYTES: usize = std::mem::size_of::<u64>();
+
+/// Appends the encoding of an integer to a key
+pub fn encode_varint(out: &mut Vec<u8>, value: u64) {
+    let bytes = value.to_be_bytes();
+    let skip = (value.leading_zeros() / 8) as usize;
+    out.push((MAX_VARINT_BYTES - skip) as u8);
+    out.extend_from_slice(&bytes[skip..]);
+}
+
+/// Appends the encoding of a byte string to a key
+pub fn encode_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    encode_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// Begins the full binary key of a record of the given type
+pub fn key_header(storage_type: StorageType) -> Vec<u8> {
+    vec![storage_type as u8]
+}
+
+/// Reads the fields of a full binary key, in the order in which they were written
+#[derive(Debug)]
+pub struct KeyReader<'a> {
+    remaining: &'a [u8],
+}
+
+impl<'a> KeyReader<'a> {
+    /// Starts reading a key, which must be the key of a record of the given type
+    pub fn new(bin: &'a [u8], storage_type: StorageType) -> Result<Self, String> {
+        match bin.split_first() {
+            Some((&header, remaining)) if header == storage_type as u8 => Ok(Self { remaining }),
+            Some(_) => Err(format!("Not a {storage_type:?} key")),
+            None => Err("Not enough bytes to form a proper key".to_string()),
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.remaining.len() < len {
+            return Err("Not enough bytes to form a proper key".to_string());
+        }
+        let (taken, remaining) = self.remaining.split_at(len);
+        self.remaining = remaining;
+        Ok(taken)
+    }
+
+    /// Reads an integer
+    pub fn read_varint(&mut self) -> Result<u64, String> {
+        let len = self.take(1)?[0] as usize;
+        if len > MAX_VARINT_BYTES {
+            return Err(format!("Integer of {len} bytes in key"));
+        }
+        let bytes = self.take(len)?;
+        if bytes.first() == Some(&0) {
+            return Err("Non-minimal integer in key".to_string());
+        }
+        Ok(bytes
+            .iter()
+            .fold(0u64, |value, byte| (value << 8) | *byte as u64))
+    }
+
+    /// Reads a byte string
+    pub fn read_bytes(&mut self) -> Result<&'a [u8], String> {
+        let len = self.read_varint()?;
+        let len = usize::try_from(len).map_err(|_| format!("Byte string of {len} bytes in key"))?;
+        self.take(len)
+    }
+
+    /// Reads all of the bytes which remain in the key
+    pub fn read_remaining(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.remaining)
+    }
+
+    /// Finishes reading the key, which must have no bytes remaining
+    pub fn finish(self) -> Result<(), String> {
+        if self.remaining.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} unexpected trailing bytes in key",
+                self.remaining.len()
+            ))
+        }
+    }
+}
+
+/// Encodes the key of a tree node, as its label length followed by its label value, without
+/// the trailing zero bytes of the value (which is padded back to its full size when decoded)
+pub(crate) fn encode_node_key(key: &NodeKey) -> Vec<u8> {
+    let mut result = key_header(StorageType::TreeNode);
+    encode_varint(&mut result, key.0.label_len as u64);
+    let val = &key.0.label_val;
+    let used = val.iter().rposition(|byte| *byte != 0).map_or(0, |i| i + 1);
+    result.extend_from_slice(&val[..used]);
+    result
+}
+
+pub(crate) fn decode_node_key(bin: &[u8]) -> Result<NodeKey, String> {
+    let mut reader = KeyReader::new(bin, StorageType::TreeNode)?;
+    let len = reader.read_varint()?;
+    let len = u32::try_from(len).map_err(|_| format!("Label length {len} in key"))?;
+    let used = reader.read_remaining();
+    let mut val = [0u8; 32];
+    if used.len() > val.len() {
+        return Err(format!("Label value of {} bytes in key", used.len()));
+    }
+    if used.last() == Some(&0) {
+        return Err("Trailing zero bytes in label value of key".to_string());
+    }
+    val[..used.len()].copy_from_slice(used);
+    Ok(NodeKey(NodeLabel::new(val, len)))
+}
+
+/// Encodes the key of a value state, as its label followed by its epoch, so that the value
+/// states of a label are contiguous and ordered by epoch
+pub(crate) fn encode_value_state_key(key: &ValueStateKey) -> Vec<u8> {
+    let mut result = key_header(StorageType::ValueState);
+    encode_bytes(&mut result, &key.0);
+    encode_varint(&mut result, key.1);
+    result
+}
+
+pub(crate) fn decode_value_state_key(bin: &[u8]) -> Result<ValueStateKey, String> {
+    let mut reader = KeyReader::new(bin, StorageType::ValueState)?;
+    let username = reader.read_bytes()?.to_vec();
+    let epoch = reader.read_varint()?;
+    reader.finish()?;
+    Ok(ValueStateKey(username, epoch))
+}
+
+/// Encodes the key of an anchor receipt, as its epoch followed by the name of its anchor, so
+/// that the receipts of an epoch are contiguous
+pub(crate) fn encode_anchor_receipt_key(key: &AnchorReceiptKey) -> Vec<u8> {
+    let mut result = key_header(StorageType::AnchorReceipt);
+    encode_varint(&mut result, key.0);
+    encode_bytes(&mut result, key.1.as_bytes());
+    result
+}
+
+pub(crate) fn decode_anchor_receipt_key(bin: &[u8]) -> Result<AnchorReceiptKey, String> {
+    let mut reader = KeyReader::new(bin, StorageType::AnchorReceipt)?;
+    let epoch = reader.read_varint()?;
+    let anchor = String::from_utf8(reader.read_bytes()?.to_vec())
+        .map_err(|_| "Anchor name in key is not UTF-8".to_string())?;
+    reader.finish()?;
+    Ok(AnchorReceiptKey(epoch, anchor))
+}
+
+pub(crate) fn encode_integer_key(storage_type: StorageType, key: u64) -> Vec<u8> {
+    let mut result = key_header(storage_type);
+    encode_varint(&mut result, key);
+    result
+}
+
+pub(crate) fn decode_integer_key(bin: &[u8], storage_type: StorageType) -> Result<u64, String> {
+    let mut reader = KeyReader::new(bin, storage_type)?;
+    let key = reader.read_varint()?;
+    reader.finish()?;
+    Ok(key)
+}
+
+pub(crate) fn encode_label_key(storage_type: StorageType, label: &AkdLabel) -> Vec<u8> {
+    let mut result = key_header(storage_type);
+    encode_bytes(&mut result, label);
+    result
+}
+
+pub(crate) fn decode_label_key(bin: &[u8], storage_type: StorageType) -> Result<AkdLabel, String> {
+    let mut reader = KeyReader::new(bin, storage_type)?;
+    let label = AkdLabel(reader.read_bytes()?.to_vec());
+    reader.finish()?;
+    Ok(label)
+}
+
+/// The key of a record of any type, parsed from its full binary key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParsedKey {
+    /// The key of an Azks record
+    Azks(u8),
+    /// The key of a tree node
+    TreeNode(NodeKey),
+    /// The key of a value state
+    ValueState(ValueStateKey),
+    /// The key of an operation record
+    OperationRecord(u64),
+    /// The key of a soft deletion
+    SoftDeletion(AkdLabel),
+    /// The key of an anchor receipt
+    AnchorReceipt(AnchorReceiptKey),
+    /// The key of the publish lease
+    PublishLease(u8),
+    /// The key of an audit epoch: its index
+    AuditEpoch(u64),
+}
+
+impl ParsedKey {
+    /// The type of the record which the key belongs to
+    pub fn storage_type(&self) -> StorageType {
+        match self {
+            ParsedKey::Azks(_) => StorageType::Azks,
+            ParsedKey::TreeNode(_) => StorageType::TreeNode,
+            ParsedKey::ValueState(_) => StorageType::ValueState,
+            ParsedKey::OperationRecord(_) => StorageType::OperationRecord,
+            ParsedKey::SoftDeletion(_) => StorageType::SoftDeletion,
+            ParsedKey::AnchorReceipt(_) => StorageType::AnchorReceipt,
+            ParsedKey::PublishLease(_) => StorageType::PublishLease,
+            ParsedKey::AuditEpoch(_) => StorageType::AuditEpoch,
+        }
+    }
+
+    /// Encodes the key back into its full binary key
+    pub fn to_binary(&self) -> Vec<u8> {
+        match self {
+            ParsedKey::Azks(key) => encode_integer_key(StorageType::Azks, *key as u64),
+            ParsedKey::TreeNode(key) => encode_node_key(key),
+            ParsedKey::ValueState(key) => encode_value_state_key(key),
+            ParsedKey::OperationRecord(key) => {
+                encode_integer_key(StorageType::OperationRecord, *key)
+            }
+            ParsedKey::SoftDeletion(key) => encode_label_key(StorageType::SoftDeletion, key),
+            ParsedKey::AnchorReceipt(key) => encode_anchor_receipt_key(key),
+            ParsedKey::PublishLease(key) => {
+                encode_integer_key(StorageType::PublishLease, *key as u64)
+            }
+            ParsedKey::AuditEpoch(index) => encode_integer_key(StorageType::AuditEpoch, *index),
+        }
+    }
+}
+
+impl fmt::Display for ParsedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParsedKey::Azks(key) => write!(f, "Azks({key})"),
+            ParsedKey::TreeNode(NodeKey(label)) => write!(
+                f,
+                "TreeNode(len={}, val={})",
+                label.label_len,
+                hex::encode(label.label_val)
+            ),
+            ParsedKey::ValueState(ValueStateKey(username, epoch)) => write!(
+                f,
+                "ValueState(label={}, epoch={epoch})",
+                hex::encode(username)
+            ),
+            ParsedKey::OperationRecord(sequence) => write!(f, "OperationRecord({sequence})"),
+            ParsedKey::SoftDeletion(username) => {
+                write!(f, "SoftDeletion(label={})", hex::encode(&username.0))
+            }
+            ParsedKey::AnchorReceipt(AnchorReceiptKey(epoch, anchor)) => {
+                write!(f, "AnchorReceipt(epoch={epoch}, anchor={anchor})")
+            }
+            ParsedKey::PublishLease(key) => write!(f, "PublishLease({key})"),
+            ParsedKey::AuditEpoch(index) => write!(f, "AuditEpoch({index})"),
+        }
+    }
+}
+
+/// Parses the full binary key of a record of any type, dispatching on its leading
+/// [StorageType] byte
+pub fn parse_key(bin: &[u8]) -> Result<ParsedKey, String> {
+    const AZKS: u8 = StorageType::Azks as u8;
+    const TREE_NODE: u8 = StorageType::TreeNode as u8;
+    const VALUE_STATE: u8 = StorageType::ValueState as u8;
+    const OPERATION_RECORD: u8 = StorageType::OperationRecord as u8;
+    const SOFT_DELETION: u8 = StorageType::SoftDeletion as u8;
+    const ANCHOR_RECEIPT: u8 = StorageType::AnchorReceipt as u8;
+    const PUBLISH_LEASE: u8 = StorageType::PublishLease as u8;
+    const AUDIT_EPOCH: u8 = StorageType::AuditEpoch as u8;
+
+    match bin.first() {
+        Some(&AZKS) => {
+            let key = decode_integer_key(bin, StorageType::Azks)?;
+            let key = u8::try_from(key).map_err(|_| format!("Azks key {key} out of range"))?;
+            Ok(ParsedKey::Azks(key))
+        }
+        Some(&TREE_NODE) => decode_node_key(bin).map(ParsedKey::TreeNode),
+        Some(&VALUE_STATE) => decode_value_state_key(bin).map(ParsedKey::ValueState),
+        Some(&OPERATION_RECORD) => {
+            decode_integer_key(bin, StorageType::OperationRecord).map(ParsedKey::OperationRecord)
+        }
+        Some(&SOFT_DELETION) => {
+            decode_label_key(bin, StorageType::SoftDeletion).map(ParsedKey::SoftDeletion)
+        }
+        Some(&ANCHOR_RECEIPT) => decode_anchor_receipt_key(bin).map(ParsedKey::AnchorReceipt),
+        Some(&PUBLISH_LEASE) => {
+            let key = decode_integer_key(bin, StorageType::PublishLease)?;
+            let key =
+                u8::try_from(key).map_err(|_| format!("PublishLease key {key} out of range"))?;
+            Ok(ParsedKey::PublishLease(key))
+        }
+        Some(&AUDIT_EPOCH) => {
+            decode_integer_key(bin, StorageType::AuditEpoch).map(ParsedKey::AuditEpoch)
+        }
+        Some(other) => Err(format!("Unknown storage type {other} in key")),
+        None => Err("Not enough bytes to form a proper key".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::types::{AnchorReceipt, OperationRecord, SoftDeletion, ValueState};
+    use crate::storage::Storable;
+    use crate::tree_node::TreeNodeWithPreviousValue;
+    use crate::Azks;
+    use proptest::prelude::*;
+
+    fn any_node_key() -> impl Strategy<Value = NodeKey> {
+        (any::<[u8; 32]>(), 0u32..=256, 0usize..=32).prop_map(|(mut val, len, zeroed)| {
+            // exercise labels whose values have trailing zero bytes
+            for byte in val.iter_mut().skip(32 - zeroed) {
+                *byte = 0;
+            }
+            NodeKey(NodeLabel::new(val, len))
+        })
+    }
+
+    #[test]
+    fn test_varint_boundaries() {
+        for (value, expected) in [
+            (0u64, vec![0u8]),
+            (1, vec![1, 1]),
+            (255, vec![1, 255]),
+            (256, vec![2, 1, 0]),
+            (u64::MAX, vec![8, 255, 255, 255, 255, 255, 255, 255, 255]),
+        ] {
+            let mut bin = key_header(StorageType::OperationRecord);
+            encode_varint(&mut bin, value);
+            assert_eq!(&bin[1..], &expected[..]);
+            assert_eq!(
+                Ok(value),
+                decode_integer_key(&bin, StorageType::OperationRecord)
+            );
+        }
+
+        // non-minimal and oversized integers are rejected
+        for bin in [vec![5u8, 1, 0, 1], vec![5, 9, 1, 1, 1, 1, 1, 1, 1, 1, 1]] {
+            assert!(decode_integer_key(&bin, StorageType::OperationRecord).is_err());
+        }
+    }
+
+    #[test]
+    fn test_value_states_of_a_label_are_contiguous() {
+        let short = ValueState::get_full_binary_key_id(&ValueStateKey(vec![1, 2], u64::MAX));
+        let long = ValueState::get_full_binary_key_id(&ValueStateKey(vec![1, 2, 0], 0));
+        let prefix = {
+            let mut prefix = key_header(StorageType::ValueState);
+            encode_bytes(&mut prefix, &[1, 2]);
+            prefix
+        };
+        assert!(short.starts_with(&prefix));
+        assert!(!long.starts_with(&prefix));
+    }
+
+    proptest! {
+        #[test]
+        fn test_varint_preserves_order(a in any::<u64>(), b in any::<u64>()) {
+            let (mut bin_a, mut bin_b) = (vec![], vec![]);
+            encode_varint(&mut bin_a, a);
+            encode_varint(&mut bin_b, b);
+            prop_assert_eq!(a.cmp(&b), bin_a.cmp(&bin_b));
+        }
+
+        #[test]
+        fn test_value_state_key_roundtrip(
+            username in proptest::collection::vec(any::<u8>(), 0..64),
+            epoch in any::<u64>(),
+            other_epoch in any::<u64>(),
+        ) {
+            let key = ValueStateKey(username.clone(), epoch);
+            let bin = ValueState::get_full_binary_key_id(&key);
+            prop_assert_eq!(ValueState::key_from_full_binary(&bin), Ok(key.clone()));
+            prop_assert_eq!(parse_key(&bin), Ok(ParsedKey::ValueState(key)));
+
+            // the value states of a label are ordered by epoch
+            let other = ValueState::get_full_binary_key_id(&ValueStateKey(username, other_epoch));
+            prop_assert_eq!(epoch.cmp(&other_epoch), bin.cmp(&other));
+        }
+
+        #[test]
+        fn test_anchor_receipt_key_roundtrip(
+            epoch in any::<u64>(),
+            other_epoch in any::<u64>(),
+            anchor in "[a-z0-9:/.-]{0,32}",
+        ) {
+            let key = AnchorReceiptKey(epoch, anchor.clone());
+            let bin = AnchorReceipt::get_full_binary_key_id(&key);
+            prop_assert_eq!(AnchorReceipt::key_from_full_binary(&bin), Ok(key.clone()));
+            prop_assert_eq!(parse_key(&bin), Ok(ParsedKey::AnchorReceipt(key)));
+
+            // the receipts are ordered by epoch
+            let other = AnchorReceipt::get_full_binary_key_id(&AnchorReceiptKey(other_epoch, anchor));
+            if epoch != other_epoch {
+                prop_assert_eq!(epoch.cmp(&other_epoch), bin.cmp(&other));
+            }
+        }
+
+        #[test]
+        fn test_node_key_roundtrip(key in any_node_key()) {
+            let bin = TreeNodeWithPreviousValue::get_full_binary_key_id(&key);
+            prop_assert!(bin.len() <= 1 + 1 + 4 + 32);
+            prop_assert_eq!(TreeNodeWithPreviousValue::key_from_full_binary(&bin), Ok(key.clone()));
+            prop_assert_eq!(parse_key(&bin), Ok(ParsedKey::TreeNode(key)));
+        }
+
+        #[test]
+        fn test_integer_and_label_key_roundtrip(
+            sequence in any::<u64>(),
+            username in proptest::collection::vec(any::<u8>(), 0..64),
+        ) {
+            let bin = OperationRecord::get_full_binary_key_id(&sequence);
+            prop_assert_eq!(OperationRecord::key_from_full_binary(&bin), Ok(sequence));
+            prop_assert_eq!(parse_key(&bin), Ok(ParsedKey::OperationRecord(sequence)));
+
+            let label = AkdLabel(username);
+            let bin = SoftDeletion::get_full_binary_key_id(&label);
+            prop_assert_eq!(SoftDeletion::key_from_full_binary(&bin), Ok(label.clone()));
+            prop_assert_eq!(parse_key(&bin), Ok(ParsedKey::SoftDeletion(label)));
+
+            let bin = Azks::get_full_binary_key_id(&crate::append_only_zks::DEFAULT_AZKS_KEY);
+            prop_assert_eq!(
+                parse_key(&bin),
+                Ok(ParsedKey::Azks(crate::append_only_zks::DEFAULT_AZKS_KEY))
+            );
+        }
+
+        #[test]
+        fn test_parse_arbitrary_bytes(
+            header in 0u8..8,
+            body in proptest::collection::vec(any::<u8>(), 0..48),
+        ) {
+            // any bytes which parse as a key are that key's one and only encoding
+            let mut bin = vec![header];
+            bin.extend_from_slice(&body);
+            if let Ok(key) = parse_key(&bin) {
+                prop_assert_eq!(key.to_binary(), bin);
+            }
+        }
+
+        #[test]
+        fn test_keys_reject_other_types(key in any_node_key(), sequence in any::<u64>()) {
+            let node_bin = TreeNodeWithPreviousValue::get_full_binary_key_id(&key);
+            prop_assert!(OperationRecord::key_from_full_binary(&node_bin).is_err());
+            prop_assert!(ValueState::key_from_full_binary(&node_bin).is_err());
+
+            let sequence_bin = OperationRecord::get_full_binary_key_id(&sequence);
+            prop_assert!(TreeNodeWithPreviousValue::key_from_full_binary(&sequence_bin).is_err());
+            prop_assert!(SoftDeletion::key_from_full_binary(&sequence_bin).is_err());
+        }
+    }
+}
@@ -0,0 +1,201 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! The lease on publishing to a database, which keeps two publishers sharing a database
+//! from committing epochs at the same time
+
+use crate::errors::StorageError;
+use crate::storage::types::{DbRecord, PublishLease, DEFAULT_PUBLISH_LEASE_KEY};
+use crate::storage::Database;
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+/// The default duration of a publish lease
+pub const DEFAULT_LEASE_DURATION: Duration = Duration::from_secs(30);
+
+/// The options of the publish lease of a storage manager
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublishLeaseOptions {
+    /// The identity of the publisher, which must be unique among the publishers sharing the
+    /// database
+    pub holder: String,
+    /// How long the lease lasts without being renewed. The lease is renewed in the background
+    /// every third of this duration while it is held, and a publish must commit within it.
+    pub duration: Duration,
+}
+
+impl PublishLeaseOptions {
+    /// Creates the options of a lease held by the given publisher, with the
+    /// [DEFAULT_LEASE_DURATION]
+    pub fn new(holder: impl Into<String>) -> Self {
+        Self {
+            holder: holder.into(),
+            duration: DEFAULT_LEASE_DURATION,
+        }
+    }
+}
+
+/// A held publish lease, which is renewed in the background until it is released or dropped.
+/// A dropped lease is not released, and so blocks other publishers until it expires.
+pub struct PublishLeaseGuard<Db: Database> {
+    db: Arc<Db>,
+    options: PublishLeaseOptions,
+    token: u64,
+    heartbeat: JoinHandle<()>,
+}
+
+impl<Db: Database + 'static> PublishLeaseGuard<Db> {
+    /// Acquires the lease, if it is not held by another publisher. The fencing token of the
+    /// lease is incremented, so that the previous holder fails to renew it.
+    pub(crate) async fn acquire(
+        db: Arc<Db>,
+        options: PublishLeaseOptions,
+    ) -> Result<Self, StorageError> {
+        let now = now_ms();
+        let stored = match db.get::<PublishLease>(&DEFAULT_PUBLISH_LEASE_KEY).await {
+            Ok(DbRecord::PublishLease(lease)) => Some(lease),
+            Ok(_) => {
+                return Err(StorageError::Other(
+                    "The publish lease is not stored as a lease".to_string(),
+                ))
+            }
+            Err(StorageError::NotFound(_)) => None,
+            Err(err) => return Err(err),
+        };
+        if let Some(stored) = stored.as_ref() {
+            if stored.expires_at_ms > now && stored.holder != options.holder {
+                return Err(StorageError::LeaseHeld(format!(
+                    "The publish lease is held by {} for another {} ms",
+                    stored.holder,
+                    stored.expires_at_ms - now
+                )));
+            }
+        }
+        let expected = stored.as_ref().map(|stored| stored.token);
+        let token = expected.map_or(1, |token| token + 1);
+        let lease = lease_record(&options, token, now);
+        db.set_if_version(lease, expected)
+            .await
+            .map_err(|err| match err {
+                StorageError::Conflict(_) => StorageError::LeaseHeld(
+                    "The publish lease was acquired concurrently by another publisher".to_string(),
+                ),
+                err => err,
+            })?;
+
+        let heartbeat = tokio::spawn(heartbeat(db.clone(), options.clone(), token));
+        Ok(Self {
+            db,
+            options,
+            token,
+            heartbeat,
+        })
+    }
+
+    /// Renews the lease, failing with a [StorageError::LeaseHeld] if it has been taken over
+    /// by another publisher since it was acquired
+    pub async fn renew(&self) -> Result<(), StorageError> {
+        renew(self.db.as_ref(), &self.options, self.token).await
+    }
+
+    /// Releases the lease, so that another publisher may acquire it immediately
+    pub async fn release(self) -> Result<(), StorageError> {
+        self.heartbeat.abort();
+        let released = DbRecord::PublishLease(PublishLease {
+            holder: self.options.holder.clone(),
+            token: self.token,
+            expires_at_ms: 0,
+        });
+        match self.db.set_if_version(released, Some(self.token)).await {
+            // a lease which was taken over is no longer ours to release
+            Ok(()) | Err(StorageError::Conflict(_)) => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl<Db: Database> PublishLeaseGuard<Db> {
+    /// The fencing token of the lease
+    pub fn token(&self) -> u64 {
+        self.token
+    }
+
+    /// The renewal of the lease, paired with the version it expects of the stored lease, for a
+    /// conditional write (see [Database::batch_set_if_versions]) which is fenced off once the
+    /// lease has been taken over
+    pub(crate) fn renewal(&self) -> (DbRecord, Option<u64>) {
+        (
+            lease_record(&self.options, self.token, now_ms()),
+            Some(self.token),
+        )
+    }
+
+    /// Whether the stored lease still carries the fencing token of this lease
+    pub(crate) async fn is_current(&self) -> Result<bool, StorageError> {
+        match self
+            .db
+            .get::<PublishLease>(&DEFAULT_PUBLISH_LEASE_KEY)
+            .await
+        {
+            Ok(DbRecord::PublishLease(lease)) => Ok(lease.token == self.token),
+            Ok(_) | Err(StorageError::NotFound(_)) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl<Db: Database> Drop for PublishLeaseGuard<Db> {
+    fn drop(&mut self) {
+        self.heartbeat.abort();
+    }
+}
+
+fn lease_record(options: &PublishLeaseOptions, token: u64, now: u64) -> DbRecord {
+    DbRecord::PublishLease(PublishLease {
+        holder: options.holder.clone(),
+        token,
+        expires_at_ms: now.saturating_add(options.duration.as_millis() as u64),
+    })
+}
+
+async fn renew<Db: Database>(
+    db: &Db,
+    options: &PublishLeaseOptions,
+    token: u64,
+) -> Result<(), StorageError> {
+    db.set_if_version(lease_record(options, token, now_ms()), Some(token))
+        .await
+        .map_err(|err| match err {
+            StorageError::Conflict(_) => StorageError::LeaseHeld(format!(
+                "The publish lease with token {token} was taken over by another publisher"
+            )),
+            err => err,
+        })
+}
+
+async fn heartbeat<Db: Database>(db: Arc<Db>, options: PublishLeaseOptions, token: u64) {
+    let interval = (options.duration / 3).max(Duration::from_millis(1));
+    loop {
+        tokio::time::sleep(interval).await;
+        if let Err(err) = renew(db.as_ref(), &options, token).await {
+            warn!(error = %err, token, "Failed to renew the publish lease");
+            if matches!(err, StorageError::LeaseHeld(_)) {
+                break;
+            }
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
@@ -9,28 +9,36 @@
 //! to manage interactions with the data layer to optimize things like caching and
 //! transaction management
 
-use crate::storage::cache::TimedCache;
+use crate::append_only_zks::DEFAULT_AZKS_KEY;
+use crate::storage::cache::{CachePartition, CacheStats, TimedCache};
+use crate::storage::codec::RecordCodec;
 use crate::storage::transaction::Transaction;
+use crate::storage::types::AnchorReceipt;
 use crate::storage::types::DbRecord;
 use crate::storage::types::KeyData;
+use crate::storage::types::SoftDeletion;
+use crate::storage::types::StorageType;
 use crate::storage::types::ValueState;
+use crate::storage::wal::{WalEntry, WriteAheadLog};
 use crate::storage::Database;
 use crate::storage::DbSetState;
 use crate::storage::Storable;
 use crate::storage::StorageError;
+use crate::storage::StorageUtil;
 use crate::AkdLabel;
 use crate::AkdValue;
+use crate::Azks;
 
-use log::debug;
 #[cfg(feature = "runtime_metrics")]
-use log::{error, info, warn};
+use crate::instrumentation::dyn_event;
+use akd_core::SizeOf;
+use futures::stream::{BoxStream, StreamExt};
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::sync::atomic::AtomicU64;
-#[cfg(feature = "runtime_metrics")]
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
+use tracing::{debug, info, warn};
 
 use super::types::ValueStateRetrievalFlag;
 
@@ -49,9 +57,38 @@ const METRIC_GET_USER_STATE_VERSIONS: Metric = 9;
 
 const NUM_METRICS: usize = 10;
 
+mod lease;
+pub use lease::{PublishLeaseGuard, PublishLeaseOptions, DEFAULT_LEASE_DURATION};
+
+mod slow_operations;
+use slow_operations::OperationTimer;
+pub use slow_operations::{SlowOperationThresholds, StorageOperation};
+
+mod usage;
+pub use usage::{StorageTypeUsage, StorageUsage, DEFAULT_USAGE_EPOCHS};
+use usage::{UsageDelta, UsageTracker};
+
+mod write_behind;
+use write_behind::WriteBehind;
+pub use write_behind::{
+    WriteBehindOptions, DEFAULT_WRITE_BEHIND_BATCH_SIZE, DEFAULT_WRITE_BEHIND_QUEUE_CAPACITY,
+};
+
 #[cfg(test)]
 mod tests;
 
+/// The role of a storage manager among the replicas of a directory which share a database
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StorageRole {
+    /// The replica which publishes, and so is the only one to write to the database
+    #[default]
+    Leader,
+    /// A replica which serves proofs from the database written by the leader. Its writes
+    /// are refused with a [StorageError::ReadOnly], and its cache must be flushed when the
+    /// leader publishes a new epoch (see `Directory::refresh`).
+    Follower,
+}
+
 /// Represents the manager of the storage mediums, including caching
 /// and transactional operations (creating the transaction, committing it, etc)
 pub struct StorageManager<Db: Database> {
@@ -61,6 +98,14 @@ pub struct StorageManager<Db: Database> {
     db: Arc<Db>,
 
     metrics: [Arc<AtomicU64>; NUM_METRICS],
+    slow_operation_thresholds: Arc<SlowOperationThresholds>,
+    wal: Option<Arc<dyn WriteAheadLog>>,
+    codec: Option<Arc<dyn RecordCodec>>,
+    usage: Arc<UsageTracker>,
+    write_behind: Option<Arc<WriteBehind>>,
+    role: StorageRole,
+    lease: Option<PublishLeaseOptions>,
+    shut_down: Arc<AtomicBool>,
 }
 
 impl<Db: Database> Clone for StorageManager<Db> {
@@ -70,6 +115,14 @@ impl<Db: Database> Clone for StorageManager<Db> {
             transaction: self.transaction.clone(),
             db: self.db.clone(),
             metrics: self.metrics.clone(),
+            slow_operation_thresholds: self.slow_operation_thresholds.clone(),
+            wal: self.wal.clone(),
+            codec: self.codec.clone(),
+            usage: self.usage.clone(),
+            write_behind: self.write_behind.clone(),
+            role: self.role,
+            lease: self.lease.clone(),
+            shut_down: self.shut_down.clone(),
         }
     }
 }
@@ -85,6 +138,14 @@ impl<Db: Database> StorageManager<Db> {
             transaction: Transaction::new(),
             db: Arc::new(db),
             metrics: [0; NUM_METRICS].map(|_| Arc::new(AtomicU64::new(0))),
+            slow_operation_thresholds: Arc::new(SlowOperationThresholds::default()),
+            wal: None,
+            codec: None,
+            usage: Arc::new(UsageTracker::new(DEFAULT_USAGE_EPOCHS)),
+            write_behind: None,
+            role: StorageRole::Leader,
+            lease: None,
+            shut_down: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -104,7 +165,260 @@ impl<Db: Database> StorageManager<Db> {
             transaction: Transaction::new(),
             db: Arc::new(db),
             metrics: [0; NUM_METRICS].map(|_| Arc::new(AtomicU64::new(0))),
+            slow_operation_thresholds: Arc::new(SlowOperationThresholds::default()),
+            wal: None,
+            codec: None,
+            usage: Arc::new(UsageTracker::new(DEFAULT_USAGE_EPOCHS)),
+            write_behind: None,
+            role: StorageRole::Leader,
+            lease: None,
+            shut_down: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Sets the role of the storage manager among the replicas which share its database.
+    /// Storage managers are leaders by default.
+    pub fn with_role(mut self, role: StorageRole) -> Self {
+        self.role = role;
+        self
+    }
+
+    /// Returns the role of the storage manager
+    pub fn role(&self) -> StorageRole {
+        self.role
+    }
+
+    /// Requires each publish through the storage manager to hold the database's publish lease
+    /// (see [PublishLeaseGuard]), so that a second publisher which is accidentally run against
+    /// the same database fails to publish instead of racing the first.
+    pub fn with_publish_lease(mut self, options: PublishLeaseOptions) -> Self {
+        self.lease = Some(options);
+        self
+    }
+
+    /// Returns the options of the storage manager's publish lease, if publishing requires one
+    pub fn publish_lease_options(&self) -> Option<&PublishLeaseOptions> {
+        self.lease.as_ref()
+    }
+
+    /// Acquires the publish lease of the database, if the storage manager is configured with
+    /// one, failing with a [StorageError::LeaseHeld] if another publisher holds it
+    pub async fn acquire_publish_lease(&self) -> Result<Option<PublishLeaseGuard<Db>>, StorageError>
+    where
+        Db: 'static,
+    {
+        let Some(options) = self.lease.clone() else {
+            return Ok(None);
+        };
+        self.check_writable()?;
+        PublishLeaseGuard::acquire(self.db.clone(), options)
+            .await
+            .map(Some)
+    }
+
+    /// Refuses a write to the database if the storage manager is a follower, or has been shut
+    /// down
+    fn check_writable(&self) -> Result<(), StorageError> {
+        if self.is_shut_down() {
+            return Err(StorageError::Connection(
+                "The storage manager has been shut down".to_string(),
+            ));
+        }
+        match self.role {
+            StorageRole::Leader => Ok(()),
+            StorageRole::Follower => Err(StorageError::ReadOnly(
+                "A follower cannot write to the database".to_string(),
+            )),
+        }
+    }
+
+    /// Holds the cached records of a type in a partition of the cache with its own lifetime and
+    /// memory limit, so that bursts of other types of record cannot evict them (see
+    /// [TimedCache::with_partition]). This has no effect if the storage manager has no cache.
+    pub fn with_cache_partition(
+        mut self,
+        storage_type: StorageType,
+        partition: CachePartition,
+    ) -> Self {
+        self.cache = self
+            .cache
+            .map(|cache| cache.with_partition(storage_type, partition));
+        self
+    }
+
+    /// Logs the operations against the database which take longer than the given thresholds
+    pub fn with_slow_operation_thresholds(mut self, thresholds: SlowOperationThresholds) -> Self {
+        self.slow_operation_thresholds = Arc::new(thresholds);
+        self
+    }
+
+    /// Records the writes of each committed transaction in the given [WriteAheadLog] before
+    /// they are written to the database, so that a commit which is interrupted part way through
+    /// can be completed by [StorageManager::recover_write_ahead_log]. This is only necessary for
+    /// databases which do not write a [DbSetState::TransactionCommit] batch atomically.
+    pub fn with_write_ahead_log(mut self, wal: impl WriteAheadLog + 'static) -> Self {
+        self.wal = Some(Arc::new(wal));
+        self
+    }
+
+    /// Returns whether the storage manager has a write-ahead log
+    pub fn has_write_ahead_log(&self) -> bool {
+        self.wal.is_some()
+    }
+
+    /// Writes the records which are set outside of a transaction in the background: they are
+    /// acknowledged once they are queued, and are read back from the queue until they are
+    /// written. Reads which are not by key (such as [StorageManager::get_user_state]) first
+    /// wait for the queue to be flushed, as does each [StorageManager::commit_transaction], so
+    /// the writes made before an epoch are durable once the epoch is committed. Requires a
+    /// tokio runtime, on which the flusher is spawned by the first write.
+    pub fn with_write_behind(mut self, options: WriteBehindOptions) -> Self
+    where
+        Db: 'static,
+    {
+        self.write_behind = Some(Arc::new(WriteBehind::new(self.db.clone(), options)));
+        self
+    }
+
+    /// Returns whether the storage manager writes in the background
+    pub fn has_write_behind(&self) -> bool {
+        self.write_behind.is_some()
+    }
+
+    /// The number of records which were written in the background, but have not been written
+    /// to the database yet
+    pub fn pending_writes(&self) -> usize {
+        self.write_behind
+            .as_ref()
+            .map_or(0, |write_behind| write_behind.pending())
+    }
+
+    /// Waits until the records which were written in the background so far have been written
+    /// to the database, failing if the flusher failed to write any of them. This returns
+    /// immediately if the storage manager does not write in the background.
+    pub async fn flush_and_wait(&self) -> Result<(), StorageError> {
+        match &self.write_behind {
+            Some(write_behind) => write_behind.flush_and_wait().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Keeps the [StorageUsage] of the given number of most recent epochs, rather than the
+    /// [DEFAULT_USAGE_EPOCHS]. With 0, only the overall usage of each type of record is kept.
+    pub fn with_usage_epochs(mut self, epochs: usize) -> Self {
+        self.usage = Arc::new(UsageTracker::new(epochs));
+        self
+    }
+
+    /// A snapshot of the records written to and read from the database since the storage
+    /// manager was created, by type of record and by epoch
+    pub fn usage_stats(&self) -> StorageUsage {
+        self.usage.snapshot()
+    }
+
+    /// Serializes records with the given [RecordCodec] in [StorageManager::encode_record] and
+    /// [StorageManager::decode_record], for databases and pipelines which store records as bytes.
+    /// A [DualReadCodec](crate::storage::codec::DualReadCodec) can be given to migrate from one
+    /// codec to another.
+    pub fn with_record_codec(mut self, codec: impl RecordCodec + 'static) -> Self {
+        self.codec = Some(Arc::new(codec));
+        self
+    }
+
+    /// Returns the name of the storage manager's record codec, if it has one
+    pub fn record_codec_name(&self) -> Option<&'static str> {
+        self.codec.as_ref().map(|codec| codec.name())
+    }
+
+    /// Serializes a record with the storage manager's record codec
+    pub fn encode_record(&self, record: &DbRecord) -> Result<Vec<u8>, StorageError> {
+        self.record_codec()?.encode(record)
+    }
+
+    /// Deserializes a record with the storage manager's record codec
+    pub fn decode_record(&self, bytes: &[u8]) -> Result<DbRecord, StorageError> {
+        self.record_codec()?.decode(bytes)
+    }
+
+    fn record_codec(&self) -> Result<&dyn RecordCodec, StorageError> {
+        self.codec.as_deref().ok_or_else(|| {
+            StorageError::Other("The storage manager has no record codec".to_string())
+        })
+    }
+
+    /// Replays the writes of any transaction commits which were recorded in the write-ahead log,
+    /// but did not complete, returning the number of commits replayed. This is called when a
+    /// [Directory](crate::directory::Directory) is created, and should be called before any other
+    /// writes after a crash.
+    pub async fn recover_write_ahead_log(&self) -> Result<usize, StorageError> {
+        let wal = match &self.wal {
+            Some(wal) => wal,
+            None => return Ok(0),
+        };
+        self.check_writable()?;
+        let mut num_replayed = 0;
+        for WalEntry {
+            epoch,
+            previous_epoch,
+            records,
+        } in wal.pending().await?
+        {
+            // the records are written as in a commit, conditional on the head of the tree
+            let mut writes = records.clone();
+            let head = match writes.pop() {
+                Some(DbRecord::Azks(azks)) => DbRecord::Azks(azks),
+                other => {
+                    return Err(StorageError::Transaction(format!(
+                        "The last record in the write-ahead log entry for epoch {epoch} is NOT an Azks record {other:?}"
+                    )))
+                }
+            };
+            info!(
+                epoch,
+                num_records = records.len(),
+                "Replaying the writes of an incomplete transaction commit"
+            );
+            let usage = UsageDelta::writes(&records);
+            match self
+                .db
+                .batch_set_if_versions(
+                    writes,
+                    vec![(head, previous_epoch)],
+                    DbSetState::TransactionCommit,
+                )
+                .await
+            {
+                Ok(()) => {}
+                Err(StorageError::Conflict(_)) => {
+                    // The head of the tree is written last, so if it is at the epoch of the
+                    // entry then the interrupted commit wrote all of its records, and otherwise
+                    // another publisher committed the epoch
+                    let current_epoch = self
+                        .db
+                        .get::<Azks>(&DEFAULT_AZKS_KEY)
+                        .await?
+                        .version_stamp();
+                    if current_epoch != epoch {
+                        warn!(
+                            epoch,
+                            current_epoch,
+                            "Discarding a write-ahead log entry for an epoch which was committed by another publisher"
+                        );
+                    }
+                    wal.complete(epoch).await?;
+                    continue;
+                }
+                Err(err) => return Err(err),
+            }
+            if let Some(cache) = &self.cache {
+                cache.batch_put(&records).await;
+            }
+            self.usage.apply(usage);
+            self.increment_metric(METRIC_BATCH_SET);
+            wal.complete(epoch).await?;
+            num_replayed += 1;
         }
+        Ok(num_replayed)
     }
 
     /// Retrieve a reference to the database implementation
@@ -119,7 +433,7 @@ impl<Db: Database> StorageManager<Db> {
     }
 
     /// Log metrics from the storage manager (cache, transaction, and storage hit rates etc)
-    pub async fn log_metrics(&self, level: log::Level) {
+    pub async fn log_metrics(&self, level: tracing::Level) {
         if let Some(cache) = &self.cache {
             cache.log_metrics(level)
         }
@@ -134,45 +448,20 @@ impl<Db: Database> StorageManager<Db> {
                 .map(|metric| metric.swap(0, Ordering::Relaxed))
                 .collect::<Vec<_>>();
 
-            let msg = format!(
-                "
-===================================================
-============ Database operation counts ============
-===================================================
-    SET {}, 
-    BATCH SET {}, 
-    GET {}, 
-    BATCH GET {}
-    TOMBSTONE {}
-    GET USER STATE {}
-    GET USER DATA {}
-    GET USER STATE VERSIONS {}
-===================================================
-============ Database operation timing ============
-===================================================
-    TIME READ {} ms
-    TIME WRITE {} ms",
-                snapshot[METRIC_SET],
-                snapshot[METRIC_BATCH_SET],
-                snapshot[METRIC_GET],
-                snapshot[METRIC_BATCH_GET],
-                snapshot[METRIC_TOMBSTONE],
-                snapshot[METRIC_GET_USER_STATE],
-                snapshot[METRIC_GET_USER_DATA],
-                snapshot[METRIC_GET_USER_STATE_VERSIONS],
-                snapshot[METRIC_READ_TIME],
-                snapshot[METRIC_WRITE_TIME]
+            dyn_event!(
+                level,
+                sets = snapshot[METRIC_SET],
+                batch_sets = snapshot[METRIC_BATCH_SET],
+                gets = snapshot[METRIC_GET],
+                batch_gets = snapshot[METRIC_BATCH_GET],
+                tombstones = snapshot[METRIC_TOMBSTONE],
+                get_user_states = snapshot[METRIC_GET_USER_STATE],
+                get_user_datas = snapshot[METRIC_GET_USER_DATA],
+                get_user_state_versions = snapshot[METRIC_GET_USER_STATE_VERSIONS],
+                read_time_ms = snapshot[METRIC_READ_TIME],
+                write_time_ms = snapshot[METRIC_WRITE_TIME],
+                "Database operation metrics"
             );
-
-            match level {
-                // Currently logs cannot be captured unless they are
-                // println!. Normally Level::Trace should use the trace! macro.
-                log::Level::Trace => println!("{msg}"),
-                log::Level::Debug => debug!("{}", msg),
-                log::Level::Info => info!("{}", msg),
-                log::Level::Warn => warn!("{}", msg),
-                _ => error!("{}", msg),
-            }
         }
     }
 
@@ -189,9 +478,39 @@ impl<Db: Database> StorageManager<Db> {
         started
     }
 
-    /// Commit a transaction in the database
+    /// Commit a transaction in the database. The records are written with
+    /// [Database::batch_set_if_versions], with the head of the tree written only if it is still
+    /// at the epoch the transaction was built upon, so that the commit fails with a
+    /// [StorageError::Conflict] (without writing any of its records) if another publisher has
+    /// already committed the epoch.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     pub async fn commit_transaction(&self) -> Result<u64, StorageError> {
+        self.commit_transaction_from(None, None).await
+    }
+
+    /// Commit a transaction in the database, as with [StorageManager::commit_transaction], whose
+    /// epoch was built upon `previous_epoch` rather than the epoch before it, since the epochs in
+    /// between were skipped (see [crate::scheduling]). If a publish lease is given, it is renewed
+    /// in the same conditional write as the head of the tree, so that the commit fails with a
+    /// [StorageError::LeaseHeld] if the lease has been taken over by another publisher.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    pub async fn commit_transaction_after(
+        &self,
+        previous_epoch: u64,
+        lease: Option<&PublishLeaseGuard<Db>>,
+    ) -> Result<u64, StorageError> {
+        self.commit_transaction_from(Some(previous_epoch), lease)
+            .await
+    }
+
+    async fn commit_transaction_from(
+        &self,
+        previous_epoch: Option<u64>,
+        lease: Option<&PublishLeaseGuard<Db>>,
+    ) -> Result<u64, StorageError> {
+        self.check_writable()?;
         // this retrieves all the trans operations, and "de-activates" the transaction flag
+        let mut timer = OperationTimer::new(StorageOperation::CommitTransaction);
         let records = self.transaction.commit_transaction()?;
         let num_records = records.len();
 
@@ -206,25 +525,78 @@ impl<Db: Database> StorageManager<Db> {
             return Ok(0);
         }
 
-        let _epoch = match records.last() {
-            Some(DbRecord::Azks(azks)) => Ok(azks.latest_epoch),
+        // the records written in the background before this epoch must be durable before the
+        // epoch is committed
+        self.flush_and_wait().await?;
+
+        let mut writes = records.clone();
+        let (epoch, head) = match writes.pop() {
+            Some(DbRecord::Azks(azks)) => Ok((azks.latest_epoch, DbRecord::Azks(azks))),
             other => Err(StorageError::Transaction(format!(
                 "The last record in the transaction log is NOT an Azks record {other:?}"
             ))),
         }?;
+        let previous_epoch = previous_epoch.or(epoch.checked_sub(1));
+
+        // record the intent to write, before any of the records are written
+        if let Some(wal) = &self.wal {
+            wal.append(WalEntry {
+                epoch,
+                previous_epoch,
+                records: records.clone(),
+            })
+            .await?;
+        }
+
+        // Write to the database, advancing the head of the tree from the epoch the transaction
+        // was built upon (and renewing the lease, which fences off a publisher whose lease was
+        // taken over) in the same conditional write as the rest of the records
+        let record_types = self
+            .slow_operation_thresholds
+            .record_types(StorageOperation::CommitTransaction, &records);
+        let usage = UsageDelta::writes(&records);
+        let mut conditional = vec![(head, previous_epoch)];
+        if let Some(lease) = lease {
+            conditional.push(lease.renewal());
+        }
+        let result = self
+            .tic_toc(
+                METRIC_WRITE_TIME,
+                &mut timer,
+                self.db
+                    .batch_set_if_versions(writes, conditional, DbSetState::TransactionCommit),
+            )
+            .await;
+        if let Err(err) = result {
+            if !matches!(err, StorageError::Conflict(_)) {
+                // the records may have been partially written, and are replayed from the
+                // write-ahead log on recovery
+                return Err(err);
+            }
+            // nothing was written
+            if let Some(wal) = &self.wal {
+                wal.complete(epoch).await?;
+            }
+            return match lease {
+                Some(lease) if !lease.is_current().await? => Err(StorageError::LeaseHeld(format!(
+                    "The publish lease with token {} was taken over by another publisher",
+                    lease.token()
+                ))),
+                _ => Err(err),
+            };
+        }
 
         // update the cache
         if let Some(cache) = &self.cache {
             cache.batch_put(&records).await;
         }
-
-        // Write to the database
-        self.tic_toc(
-            METRIC_WRITE_TIME,
-            self.db.batch_set(records, DbSetState::TransactionCommit),
-        )
-        .await?;
+        self.usage.apply(usage);
         self.increment_metric(METRIC_BATCH_SET);
+        if let Some(wal) = &self.wal {
+            wal.complete(epoch).await?;
+        }
+        self.slow_operation_thresholds
+            .check(timer, record_types, num_records);
         Ok(num_records as u64)
     }
 
@@ -244,6 +616,11 @@ impl<Db: Database> StorageManager<Db> {
         self.transaction.is_transaction_active()
     }
 
+    /// The total size of the records in the active transaction, if any
+    pub(crate) fn transaction_size_of(&self) -> usize {
+        self.transaction.size_of()
+    }
+
     /// Disable cache cleaning (if present)
     pub fn disable_cache_cleaning(&self) {
         if let Some(cache) = &self.cache {
@@ -260,61 +637,98 @@ impl<Db: Database> StorageManager<Db> {
 
     /// Store a record in the database
     pub async fn set(&self, record: DbRecord) -> Result<(), StorageError> {
+        self.check_writable()?;
         // we're in a transaction, set the item in the transaction
         if self.is_transaction_active() {
             self.transaction.set(&record);
             return Ok(());
         }
+        let mut timer = OperationTimer::new(StorageOperation::Set);
 
         // update the cache
         if let Some(cache) = &self.cache {
             cache.put(&record).await;
         }
 
+        if let Some(write_behind) = &self.write_behind {
+            write_behind.enqueue(&self.usage, vec![record]).await?;
+            self.increment_metric(METRIC_SET);
+            return Ok(());
+        }
+
         // write to the database
-        self.tic_toc(METRIC_WRITE_TIME, self.db.set(record)).await?;
+        let record_type = record.storage_type();
+        let usage = UsageDelta::writes(std::slice::from_ref(&record));
+        self.tic_toc(METRIC_WRITE_TIME, &mut timer, self.db.set(record))
+            .await?;
+        self.usage.apply(usage);
         self.increment_metric(METRIC_SET);
+        self.slow_operation_thresholds.check(timer, record_type, 1);
         Ok(())
     }
 
     /// Set a batch of records in the database
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(num_records = records.len())))]
     pub async fn batch_set(&self, records: Vec<DbRecord>) -> Result<(), StorageError> {
         if records.is_empty() {
             // nothing to do, save the cycles
             return Ok(());
         }
+        self.check_writable()?;
 
         // we're in a transaction, set the items in the transaction
         if self.is_transaction_active() {
             self.transaction.batch_set(&records);
             return Ok(());
         }
+        let mut timer = OperationTimer::new(StorageOperation::BatchSet);
 
         // update the cache
         if let Some(cache) = &self.cache {
             cache.batch_put(&records).await;
         }
 
+        if let Some(write_behind) = &self.write_behind {
+            write_behind.enqueue(&self.usage, records).await?;
+            self.increment_metric(METRIC_BATCH_SET);
+            return Ok(());
+        }
+
         // Write to the database
+        let num_records = records.len();
+        let record_types = self
+            .slow_operation_thresholds
+            .record_types(StorageOperation::BatchSet, &records);
+        let usage = UsageDelta::writes(&records);
         self.tic_toc(
             METRIC_WRITE_TIME,
+            &mut timer,
             self.db.batch_set(records, DbSetState::General),
         )
         .await?;
+        self.usage.apply(usage);
         self.increment_metric(METRIC_BATCH_SET);
+        self.slow_operation_thresholds
+            .check(timer, record_types, num_records);
         Ok(())
     }
 
     /// Retrieve a stored record directly from the data layer, ignoring any caching or transaction processes
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     pub async fn get_direct<St: Storable>(
         &self,
         id: &St::StorageKey,
     ) -> Result<DbRecord, StorageError> {
-        // cache miss, read direct from db
+        self.flush_and_wait().await?;
+        let mut timer = OperationTimer::new(StorageOperation::Get);
         let record = self
-            .tic_toc(METRIC_READ_TIME, self.db.get::<St>(id))
+            .tic_toc(METRIC_READ_TIME, &mut timer, self.db.get::<St>(id))
             .await?;
+        self.usage
+            .apply(UsageDelta::reads(std::slice::from_ref(&record)));
         self.increment_metric(METRIC_GET);
+        self.slow_operation_thresholds
+            .check(timer, St::data_type(), 1);
         Ok(record)
     }
 
@@ -329,6 +743,13 @@ impl<Db: Database> StorageManager<Db> {
             }
         }
 
+        // a record which was written in the background may not be in the database yet
+        if let Some(write_behind) = &self.write_behind {
+            if let Some(result) = write_behind.get(&St::get_full_binary_key_id(id)) {
+                return Some(result);
+            }
+        }
+
         // check for a cache hit
         if let Some(cache) = &self.cache {
             if let Some(result) = cache.hit_test::<St>(id).await {
@@ -348,17 +769,23 @@ impl<Db: Database> StorageManager<Db> {
         // cache miss, read direct from db
         self.increment_metric(METRIC_GET);
 
+        let mut timer = OperationTimer::new(StorageOperation::Get);
         let record = self
-            .tic_toc(METRIC_READ_TIME, self.db.get::<St>(id))
+            .tic_toc(METRIC_READ_TIME, &mut timer, self.db.get::<St>(id))
             .await?;
+        self.usage
+            .apply(UsageDelta::reads(std::slice::from_ref(&record)));
         if let Some(cache) = &self.cache {
             // cache the result
             cache.put(&record).await;
         }
+        self.slow_operation_thresholds
+            .check(timer, St::data_type(), 1);
         Ok(record)
     }
 
     /// Retrieve a batch of records by id from the database
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(num_records = ids.len())))]
     pub async fn batch_get<St: Storable>(
         &self,
         ids: &[St::StorageKey],
@@ -369,6 +796,7 @@ impl<Db: Database> StorageManager<Db> {
             // nothing to retrieve, save the cycles
             return Ok(records);
         }
+        let mut timer = OperationTimer::new(StorageOperation::BatchGet);
 
         let mut key_set: HashSet<St::StorageKey> = ids.iter().cloned().collect();
 
@@ -385,6 +813,14 @@ impl<Db: Database> StorageManager<Db> {
                 }
             }
 
+            if let Some(write_behind) = &self.write_behind {
+                if let Some(result) = write_behind.get(&St::get_full_binary_key_id(id)) {
+                    records.push(result);
+                    key_set.remove(id);
+                    continue;
+                }
+            }
+
             // check if item is cached
             if let Some(cache) = &self.cache {
                 if let Some(result) = cache.hit_test::<St>(id).await {
@@ -399,8 +835,9 @@ impl<Db: Database> StorageManager<Db> {
             // these are items to be retrieved from the backing database (not in pending transaction or in the object cache)
             let keys = key_set.into_iter().collect::<Vec<_>>();
             let mut results = self
-                .tic_toc(METRIC_READ_TIME, self.db.batch_get::<St>(&keys))
+                .tic_toc(METRIC_READ_TIME, &mut timer, self.db.batch_get::<St>(&keys))
                 .await?;
+            self.usage.apply(UsageDelta::reads(&results));
 
             // cache the db returned results
             if let Some(cache) = &self.cache {
@@ -410,9 +847,38 @@ impl<Db: Database> StorageManager<Db> {
             records.append(&mut results);
             self.increment_metric(METRIC_BATCH_GET);
         }
+        self.slow_operation_thresholds
+            .check(timer, St::data_type(), ids.len());
         Ok(records)
     }
 
+    /// Shuts the storage manager (and all of its clones) down, so that a service can drain
+    /// cleanly: the records which were written in the background are flushed to the database,
+    /// the background flusher is stopped, the cache is emptied, and the database is closed (see
+    /// [Database::close]). Writes are refused once the shutdown has begun, while reads are
+    /// served until the database is closed. Shutting down again does nothing.
+    ///
+    /// If the records written in the background fail to be flushed, the database is still
+    /// closed, and the failure is returned.
+    pub async fn shutdown(&self) -> Result<(), StorageError> {
+        if self.shut_down.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        let flushed = match &self.write_behind {
+            Some(write_behind) => write_behind.shutdown().await,
+            None => Ok(()),
+        };
+        self.flush_cache().await;
+        let closed = self.db.close().await;
+        info!("Shut down the storage manager");
+        flushed.and(closed)
+    }
+
+    /// Returns whether the storage manager has been shut down
+    pub fn is_shut_down(&self) -> bool {
+        self.shut_down.load(Ordering::SeqCst)
+    }
+
     /// Flush the caching of objects (if present)
     pub async fn flush_cache(&self) {
         if let Some(cache) = &self.cache {
@@ -420,7 +886,34 @@ impl<Db: Database> StorageManager<Db> {
         }
     }
 
-    /// Tombstones all value states for a given AkdLabel, up to and including a given epoch
+    /// Evicts all of the expired items from the cache (if present), returning the number of items evicted
+    pub fn evict_expired_cache_items(&self) -> usize {
+        self.cache.as_ref().map_or(0, |cache| cache.evict_expired())
+    }
+
+    /// The number of items in the cache, if present
+    pub fn cache_len(&self) -> Option<usize> {
+        self.cache.as_ref().map(|cache| cache.len())
+    }
+
+    /// A snapshot of the contents of the cache, and of the accesses to and evictions from it,
+    /// if the cache is present
+    pub async fn cache_stats(&self) -> Option<CacheStats> {
+        match &self.cache {
+            Some(cache) => Some(cache.stats().await),
+            None => None,
+        }
+    }
+
+    /// Returns whether cache-cleaning is enabled, if the cache is present
+    pub fn is_cache_cleaning_enabled(&self) -> Option<bool> {
+        self.cache.as_ref().map(|cache| cache.is_clean_enabled())
+    }
+
+    /// Tombstones all value states for a given AkdLabel, up to and including a given epoch.
+    /// The values are replaced by [TOMBSTONE](crate::TOMBSTONE), which carries no metadata, while
+    /// existing tombstones (including those which carry [crate::TombstoneMetadata]) are kept.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     pub async fn tombstone_value_states(
         &self,
         username: &AkdLabel,
@@ -429,18 +922,22 @@ impl<Db: Database> StorageManager<Db> {
         let key_data = self.get_user_data(username).await?;
         let mut new_data = vec![];
         for value_state in key_data.states.into_iter() {
-            if value_state.epoch <= epoch && value_state.value.0 != crate::TOMBSTONE {
+            if value_state.epoch <= epoch
+                && value_state.value.0 != crate::TOMBSTONE
+                && crate::TombstoneMetadata::from_value(&value_state.value).is_none()
+            {
                 new_data.push(DbRecord::ValueState(ValueState {
                     epoch: value_state.epoch,
                     label: value_state.label,
                     value: crate::AkdValue(crate::TOMBSTONE.to_vec()),
                     username: value_state.username,
                     version: value_state.version,
+                    retention_class: value_state.retention_class,
                 }));
             }
         }
         if !new_data.is_empty() {
-            debug!("Tombstoning {} entries", new_data.len());
+            debug!(batch_size = new_data.len(), "Tombstoning value states");
             self.batch_set(new_data).await?;
             self.increment_metric(METRIC_TOMBSTONE);
         }
@@ -448,21 +945,75 @@ impl<Db: Database> StorageManager<Db> {
         Ok(())
     }
 
+    /// Soft-deletes the value states of a label which were published up to and including the
+    /// given epoch, by flagging them with a [SoftDeletion], while the value states themselves are
+    /// kept as they are. If the label is already soft-deleted, its existing soft deletion (and so
+    /// its retention window) is kept. The label must have a value state by the given epoch.
+    pub async fn soft_delete_label(
+        &self,
+        username: &AkdLabel,
+        epoch: u64,
+    ) -> Result<(), StorageError> {
+        if self.get_soft_deletion(username).await?.is_some() {
+            return Ok(());
+        }
+        self.get_user_state(username, ValueStateRetrievalFlag::LeqEpoch(epoch))
+            .await?;
+        debug!(epoch, "Soft-deleting a label");
+        self.set(DbRecord::SoftDeletion(SoftDeletion {
+            username: username.clone(),
+            epoch,
+        }))
+        .await
+    }
+
+    /// Retrieves the soft deletion of a label, if the label is soft-deleted
+    pub async fn get_soft_deletion(
+        &self,
+        username: &AkdLabel,
+    ) -> Result<Option<SoftDeletion>, StorageError> {
+        match self.get::<SoftDeletion>(username).await {
+            Ok(DbRecord::SoftDeletion(deletion)) => Ok(Some(deletion)),
+            Ok(other) => Err(StorageError::Other(format!(
+                "Expected a soft deletion, but retrieved {:?}",
+                other.storage_type()
+            ))),
+            Err(StorageError::NotFound(_)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
     /// Retrieve the specified user state object based on the retrieval flag from the database
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     pub async fn get_user_state(
         &self,
         username: &AkdLabel,
         flag: ValueStateRetrievalFlag,
     ) -> Result<ValueState, StorageError> {
+        self.flush_and_wait().await?;
+        let mut timer = OperationTimer::new(StorageOperation::GetUserState);
         let maybe_db_state = match self
-            .tic_toc(METRIC_READ_TIME, self.db.get_user_state(username, flag))
+            .tic_toc(
+                METRIC_READ_TIME,
+                &mut timer,
+                self.db.get_user_state(username, flag),
+            )
             .await
         {
             Err(StorageError::NotFound(_)) => Ok(None),
             Ok(something) => Ok(Some(something)),
             Err(other) => Err(other),
         }?;
+        if let Some(state) = &maybe_db_state {
+            self.usage.apply(UsageDelta::reads_of_type(
+                StorageType::ValueState,
+                1,
+                state.size_of() as u64,
+            ));
+        }
         self.increment_metric(METRIC_GET_USER_STATE);
+        self.slow_operation_thresholds
+            .check(timer, StorageType::ValueState, 1);
 
         // in the event we are in a transaction, there may be an updated object in the
         // transactional storage. Therefore we should update the db retrieved value if
@@ -497,16 +1048,32 @@ impl<Db: Database> StorageManager<Db> {
     }
 
     /// Retrieve all values states for a given user
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     pub async fn get_user_data(&self, username: &AkdLabel) -> Result<KeyData, StorageError> {
+        self.flush_and_wait().await?;
+        let mut timer = OperationTimer::new(StorageOperation::GetUserData);
         let maybe_db_data = match self
-            .tic_toc(METRIC_READ_TIME, self.db.get_user_data(username))
+            .tic_toc(
+                METRIC_READ_TIME,
+                &mut timer,
+                self.db.get_user_data(username),
+            )
             .await
         {
             Err(StorageError::NotFound(_)) => Ok(None),
             Ok(something) => Ok(Some(something)),
             Err(other) => Err(other),
         }?;
+        if let Some(data) = &maybe_db_data {
+            self.usage.apply(UsageDelta::reads_of_type(
+                StorageType::ValueState,
+                data.states.len() as u64,
+                data.states.iter().map(|state| state.size_of() as u64).sum(),
+            ));
+        }
         self.increment_metric(METRIC_GET_USER_DATA);
+        self.slow_operation_thresholds
+            .check(timer, StorageType::ValueState, 1);
 
         if self.is_transaction_active() {
             // there are transaction-based values in the current transaction, they should override database-retrieved values
@@ -521,7 +1088,7 @@ impl<Db: Database> StorageManager<Db> {
 
             let transaction_records = self
                 .transaction
-                .get_users_data(&[username.clone()])
+                .get_users_data(std::slice::from_ref(username))
                 .remove(username)
                 .unwrap_or_default();
             for transaction_record in transaction_records.into_iter() {
@@ -543,18 +1110,32 @@ impl<Db: Database> StorageManager<Db> {
     }
 
     /// Retrieve the user -> state version mapping in bulk. This is the same as get_user_state in a loop, but with less data retrieved from the storage layer
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(num_labels = usernames.len())))]
     pub async fn get_user_state_versions(
         &self,
         usernames: &[AkdLabel],
         flag: ValueStateRetrievalFlag,
     ) -> Result<HashMap<AkdLabel, (u64, AkdValue)>, StorageError> {
+        self.flush_and_wait().await?;
+        let mut timer = OperationTimer::new(StorageOperation::GetUserStateVersions);
         let mut data = self
             .tic_toc(
                 METRIC_READ_TIME,
+                &mut timer,
                 self.db.get_user_state_versions(usernames, flag),
             )
             .await?;
+        // only the version and value of each state are retrieved
+        self.usage.apply(UsageDelta::reads_of_type(
+            StorageType::ValueState,
+            data.len() as u64,
+            data.iter()
+                .map(|(label, (_, value))| (label.size_of() + 8 + value.size_of()) as u64)
+                .sum(),
+        ));
         self.increment_metric(METRIC_GET_USER_STATE_VERSIONS);
+        self.slow_operation_thresholds
+            .check(timer, StorageType::ValueState, usernames.len());
 
         // in the event we are in a transaction, there may be an updated object in the
         // transactional storage. Therefore we should update the db retrieved value if
@@ -624,20 +1205,179 @@ impl<Db: Database> StorageManager<Db> {
         }
     }
 
-    async fn tic_toc<T>(&self, _metric: Metric, f: impl std::future::Future<Output = T>) -> T {
+    async fn tic_toc<T>(
+        &self,
+        metric: Metric,
+        timer: &mut OperationTimer,
+        f: impl std::future::Future<Output = T>,
+    ) -> T {
+        let tic = std::time::Instant::now();
+        let out = f.await;
+        timer.add_database_time(tic.elapsed());
+
         #[cfg(feature = "runtime_metrics")]
         {
-            let tic = std::time::Instant::now();
-            let out = f.await;
             let delta = std::time::Instant::now().duration_since(tic);
+            self.metrics[metric].fetch_add(delta.as_millis() as u64, Ordering::Relaxed);
+        }
+        let operation = if metric == METRIC_WRITE_TIME {
+            "write"
+        } else {
+            "read"
+        };
+        crate::instrumentation::record_storage_operation(operation, tic);
 
-            self.metrics[_metric].fetch_add(delta.as_millis() as u64, Ordering::Relaxed);
+        out
+    }
+}
 
-            out
+impl<Db: StorageUtil> StorageManager<Db> {
+    /// Streams all stored value states directly from the data layer, ignoring any caching or
+    /// transaction processes. See [StorageUtil::iter_user_states].
+    pub fn iter_user_states(&self) -> BoxStream<'_, Result<ValueState, StorageError>> {
+        self.increment_metric(METRIC_BATCH_GET);
+        let states = move || {
+            self.db
+                .iter_user_states()
+                .inspect(|state| {
+                    if let Ok(state) = state {
+                        self.usage.apply(UsageDelta::reads_of_type(
+                            StorageType::ValueState,
+                            1,
+                            state.size_of() as u64,
+                        ));
+                    }
+                })
+                .boxed()
+        };
+        if self.write_behind.is_none() {
+            return states();
         }
-        #[cfg(not(feature = "runtime_metrics"))]
-        {
-            f.await
+        // the states are only listed once the records written in the background are flushed
+        futures::stream::once(async move {
+            match self.flush_and_wait().await {
+                Ok(()) => states(),
+                Err(err) => futures::stream::once(async { Err(err) }).boxed(),
+            }
+        })
+        .flatten()
+        .boxed()
+    }
+
+    /// Retrieve all stored records of a type directly from the data layer, ignoring any caching or
+    /// transaction processes
+    pub async fn get_all_direct<St: Storable>(&self) -> Result<Vec<DbRecord>, StorageError> {
+        self.flush_and_wait().await?;
+        let mut timer = OperationTimer::new(StorageOperation::GetAll);
+        let records = self
+            .tic_toc(
+                METRIC_READ_TIME,
+                &mut timer,
+                self.db.batch_get_type_direct::<St>(),
+            )
+            .await?;
+        self.usage.apply(UsageDelta::reads(&records));
+        self.increment_metric(METRIC_BATCH_GET);
+        self.slow_operation_thresholds
+            .check(timer, St::data_type(), records.len());
+        Ok(records)
+    }
+
+    /// Reverses the soft deletion of a label, returning whether the label was soft-deleted.
+    /// A label can no longer be restored once its soft deletion has been purged.
+    pub async fn restore_label(&self, username: &AkdLabel) -> Result<bool, StorageError> {
+        if self.get_soft_deletion(username).await?.is_none() {
+            return Ok(false);
         }
+        debug!("Restoring a soft-deleted label");
+        self.batch_delete_direct::<SoftDeletion>(std::slice::from_ref(username))
+            .await?;
+        Ok(true)
+    }
+
+    /// Retrieves all of the soft deletions directly from the data layer
+    pub async fn get_soft_deletions(&self) -> Result<Vec<SoftDeletion>, StorageError> {
+        Ok(self
+            .get_all_direct::<SoftDeletion>()
+            .await?
+            .into_iter()
+            .filter_map(|record| match record {
+                DbRecord::SoftDeletion(deletion) => Some(deletion),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Permanently erases the labels which were soft-deleted at least `retention_epochs` epochs
+    /// before `latest_epoch`, by tombstoning their soft-deleted value states (see
+    /// [StorageManager::tombstone_value_states]) and then removing their soft deletions.
+    /// Returns the labels which were erased.
+    pub async fn purge_soft_deletions(
+        &self,
+        latest_epoch: u64,
+        retention_epochs: u64,
+    ) -> Result<Vec<AkdLabel>, StorageError> {
+        let Some(cutoff_epoch) = latest_epoch.checked_sub(retention_epochs) else {
+            return Ok(vec![]);
+        };
+        let mut purged = vec![];
+        for deletion in self.get_soft_deletions().await? {
+            if deletion.epoch <= cutoff_epoch {
+                self.tombstone_value_states(&deletion.username, deletion.epoch)
+                    .await?;
+                purged.push(deletion.username);
+            }
+        }
+        if !purged.is_empty() {
+            info!(
+                num_labels = purged.len(),
+                cutoff_epoch, "Purging soft-deleted labels"
+            );
+            // the soft deletions are only removed once their value states are tombstoned, so
+            // that an interrupted purge is completed by the next one
+            self.batch_delete_direct::<SoftDeletion>(&purged).await?;
+        }
+        Ok(purged)
+    }
+
+    /// Retrieves the anchor receipts of an epoch directly from the data layer, ordered by the
+    /// name of their anchor
+    pub async fn get_anchor_receipts(
+        &self,
+        epoch: u64,
+    ) -> Result<Vec<AnchorReceipt>, StorageError> {
+        let mut receipts = self
+            .get_all_direct::<AnchorReceipt>()
+            .await?
+            .into_iter()
+            .filter_map(|record| match record {
+                DbRecord::AnchorReceipt(receipt) if receipt.epoch == epoch => Some(receipt),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        receipts.sort_by(|a, b| a.anchor.cmp(&b.anchor));
+        Ok(receipts)
+    }
+
+    /// Deletes a batch of records directly from the data layer, ignoring any transaction
+    /// processes. The cache is flushed, since it may hold any of the deleted records.
+    pub async fn batch_delete_direct<St: Storable>(
+        &self,
+        ids: &[St::StorageKey],
+    ) -> Result<(), StorageError> {
+        self.check_writable()?;
+        // a queued write must not recreate a record after it is deleted
+        self.flush_and_wait().await?;
+        let mut timer = OperationTimer::new(StorageOperation::BatchDelete);
+        self.tic_toc(
+            METRIC_WRITE_TIME,
+            &mut timer,
+            self.db.batch_delete_direct::<St>(ids),
+        )
+        .await?;
+        self.flush_cache().await;
+        self.slow_operation_thresholds
+            .check(timer, St::data_type(), ids.len());
+        Ok(())
     }
 }
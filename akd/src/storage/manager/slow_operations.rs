@@ -0,0 +1,166 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Logging of the storage manager's operations which exceed a latency threshold. A slow
+//! operation is logged as a `tracing` warning with the operation, the types of the records
+//! involved, the size of the batch, and how its time was split between the database and the
+//! storage manager itself (its cache and transaction log).
+
+use crate::storage::types::{DbRecord, StorageType};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// The operations which the storage manager performs against the database
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StorageOperation {
+    /// The retrieval of a single record
+    Get,
+    /// The retrieval of a batch of records
+    BatchGet,
+    /// The write of a single record
+    Set,
+    /// The write of a batch of records
+    BatchSet,
+    /// The write of the records of a transaction
+    CommitTransaction,
+    /// The retrieval of a single value state of a label
+    GetUserState,
+    /// The retrieval of all of the value states of a label
+    GetUserData,
+    /// The retrieval of the versions of a batch of labels
+    GetUserStateVersions,
+    /// The retrieval of all of the records of a type
+    GetAll,
+    /// The deletion of a batch of records
+    BatchDelete,
+}
+
+impl std::fmt::Display for StorageOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let operation = match self {
+            StorageOperation::Get => "get",
+            StorageOperation::BatchGet => "batch_get",
+            StorageOperation::Set => "set",
+            StorageOperation::BatchSet => "batch_set",
+            StorageOperation::CommitTransaction => "commit_transaction",
+            StorageOperation::GetUserState => "get_user_state",
+            StorageOperation::GetUserData => "get_user_data",
+            StorageOperation::GetUserStateVersions => "get_user_state_versions",
+            StorageOperation::GetAll => "get_all",
+            StorageOperation::BatchDelete => "batch_delete",
+        };
+        write!(f, "{operation}")
+    }
+}
+
+/// The latency thresholds above which the operations of a storage manager are logged as slow.
+/// By default, no operation is logged.
+///
+/// ```
+/// use akd::storage::manager::{SlowOperationThresholds, StorageOperation};
+/// use std::time::Duration;
+///
+/// // Log any operation slower than 100ms, and commits slower than a second
+/// let thresholds = SlowOperationThresholds::all(Duration::from_millis(100))
+///     .with_threshold(StorageOperation::CommitTransaction, Duration::from_secs(1));
+/// assert_eq!(
+///     Some(Duration::from_millis(100)),
+///     thresholds.threshold(StorageOperation::BatchGet)
+/// );
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SlowOperationThresholds {
+    default: Option<Duration>,
+    thresholds: HashMap<StorageOperation, Duration>,
+}
+
+impl SlowOperationThresholds {
+    /// Logs any operation which takes longer than `threshold`
+    pub fn all(threshold: Duration) -> Self {
+        Self {
+            default: Some(threshold),
+            thresholds: HashMap::new(),
+        }
+    }
+
+    /// Sets the threshold for one kind of operation, which overrides the threshold set
+    /// for all operations
+    pub fn with_threshold(mut self, operation: StorageOperation, threshold: Duration) -> Self {
+        self.thresholds.insert(operation, threshold);
+        self
+    }
+
+    /// The threshold for a kind of operation, if operations of the kind are logged
+    pub fn threshold(&self, operation: StorageOperation) -> Option<Duration> {
+        self.thresholds.get(&operation).copied().or(self.default)
+    }
+
+    /// The distinct types of a batch of records, if the operation is logged
+    pub(crate) fn record_types(
+        &self,
+        operation: StorageOperation,
+        records: &[DbRecord],
+    ) -> Vec<StorageType> {
+        let mut types = vec![];
+        if self.threshold(operation).is_some() {
+            for record in records {
+                let storage_type = record.storage_type();
+                if !types.contains(&storage_type) {
+                    types.push(storage_type);
+                }
+            }
+        }
+        types
+    }
+
+    /// Logs the timed operation if it took longer than its threshold
+    pub(crate) fn check(
+        &self,
+        timer: OperationTimer,
+        key_type: impl std::fmt::Debug,
+        batch_size: usize,
+    ) {
+        let Some(threshold) = self.threshold(timer.operation) else {
+            return;
+        };
+        let elapsed = timer.start.elapsed();
+        if elapsed > threshold {
+            warn!(
+                operation = %timer.operation,
+                key_type = ?key_type,
+                batch_size,
+                elapsed_ms = elapsed.as_millis() as u64,
+                database_ms = timer.database.as_millis() as u64,
+                manager_ms = elapsed.saturating_sub(timer.database).as_millis() as u64,
+                threshold_ms = threshold.as_millis() as u64,
+                "Slow storage operation"
+            );
+        }
+    }
+}
+
+/// Times an operation of the storage manager, separating the time spent in the database
+pub(crate) struct OperationTimer {
+    operation: StorageOperation,
+    start: Instant,
+    database: Duration,
+}
+
+impl OperationTimer {
+    pub(crate) fn new(operation: StorageOperation) -> Self {
+        Self {
+            operation,
+            start: Instant::now(),
+            database: Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn add_database_time(&mut self, elapsed: Duration) {
+        self.database += elapsed;
+    }
+}
@@ -294,3 +294,718 @@ async fn test_storage_manager_cache_populated_by_batch_get() {
             .await
     );
 }
+
+#[tokio::test]
+async fn test_storage_manager_logs_slow_operations() {
+    use std::sync::Mutex;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Level, Metadata};
+
+    /// A subscriber which records the fields of each warning
+    #[derive(Default)]
+    struct Warnings(Mutex<Vec<HashMap<String, String>>>);
+
+    struct Fields<'a>(&'a mut HashMap<String, String>);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{value:?}"));
+        }
+    }
+
+    impl tracing::Subscriber for Warnings {
+        fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+            *metadata.level() == Level::WARN
+        }
+        fn new_span(&self, _: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = HashMap::new();
+            event.record(&mut Fields(&mut fields));
+            self.0.lock().unwrap().push(fields);
+        }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    let warnings = Arc::new(Warnings::default());
+    let _guard = tracing::subscriber::set_default(warnings.clone());
+
+    // Only batch writes are logged, and every one of them exceeds the threshold
+    let storage_manager = StorageManager::new_no_cache(AsyncInMemoryDatabase::new())
+        .with_slow_operation_thresholds(
+            SlowOperationThresholds::default()
+                .with_threshold(StorageOperation::BatchSet, Duration::ZERO),
+        );
+    let azks = Azks {
+        latest_epoch: 0,
+        num_nodes: 0,
+    };
+    storage_manager
+        .batch_set(vec![
+            DbRecord::Azks(azks.clone()),
+            DbRecord::ValueState(DbRecord::build_user_state(
+                b"hello".to_vec(),
+                b"world".to_vec(),
+                1,
+                0,
+                [0u8; 32],
+                1,
+                None,
+            )),
+        ])
+        .await
+        .unwrap();
+    storage_manager.set(DbRecord::Azks(azks)).await.unwrap();
+    storage_manager
+        .get::<Azks>(&crate::append_only_zks::DEFAULT_AZKS_KEY)
+        .await
+        .unwrap();
+
+    let warnings = warnings.0.lock().unwrap();
+    assert_eq!(1, warnings.len());
+    assert_eq!("batch_set", warnings[0]["operation"]);
+    assert_eq!("[Azks, ValueState]", warnings[0]["key_type"]);
+    assert_eq!("2", warnings[0]["batch_size"]);
+    for field in ["elapsed_ms", "database_ms", "manager_ms", "threshold_ms"] {
+        assert!(warnings[0].contains_key(field), "{field} was not logged");
+    }
+}
+
+#[tokio::test]
+async fn test_storage_manager_cache_partition() {
+    use crate::storage::cache::CachePartition;
+
+    let partition = CachePartition {
+        item_lifetime: None,
+        memory_limit_bytes: Some(1024),
+    };
+    let storage_manager = StorageManager::new(AsyncInMemoryDatabase::new(), None, None, None)
+        .with_cache_partition(StorageType::ValueState, partition);
+    storage_manager
+        .set(DbRecord::ValueState(DbRecord::build_user_state(
+            b"hello".to_vec(),
+            b"world".to_vec(),
+            1,
+            0,
+            [0u8; 32],
+            1,
+            None,
+        )))
+        .await
+        .unwrap();
+
+    let stats = storage_manager.cache_stats().await.unwrap();
+    let value_state_stats = stats.for_type(StorageType::ValueState).unwrap();
+    assert_eq!(
+        (1, true, Some(1024)),
+        (
+            value_state_stats.entries,
+            value_state_stats.partitioned,
+            value_state_stats.memory_limit_bytes
+        )
+    );
+    assert!(!stats.for_type(StorageType::TreeNode).unwrap().partitioned);
+
+    // Partitions have no effect without a cache
+    let storage_manager = StorageManager::new_no_cache(AsyncInMemoryDatabase::new())
+        .with_cache_partition(StorageType::ValueState, partition);
+    assert!(!storage_manager.has_cache());
+}
+
+#[cfg(any(
+    feature = "bincode_codec",
+    feature = "msgpack_codec",
+    feature = "protobuf_codec"
+))]
+fn codec_test_records() -> Vec<DbRecord> {
+    let label = NodeLabel::new([7u8; 32], 12);
+    let child = NodeLabel::new([8u8; 32], 13);
+    vec![
+        DbRecord::Azks(DbRecord::build_azks(3, 17)),
+        DbRecord::TreeNode(DbRecord::build_tree_node_with_previous_value(
+            label.label_val,
+            label.label_len,
+            3,
+            1,
+            [0u8; 32],
+            0,
+            3,
+            Some(child),
+            None,
+            [1u8; 32],
+            Some(2),
+            Some(1),
+            Some([0u8; 32]),
+            Some(0),
+            Some(3),
+            None,
+            Some(child),
+            Some([2u8; 32]),
+        )),
+        DbRecord::ValueState(DbRecord::build_user_state(
+            b"user".to_vec(),
+            b"value".to_vec(),
+            2,
+            label.label_len,
+            label.label_val,
+            3,
+            None,
+        )),
+        DbRecord::ValueState(DbRecord::build_user_state(
+            b"user".to_vec(),
+            b"value".to_vec(),
+            3,
+            label.label_len,
+            label.label_val,
+            4,
+            Some(crate::RetentionClass::LegalHold),
+        )),
+        DbRecord::OperationRecord(
+            DbRecord::build_operation_record(
+                4,
+                OperationKind::Prune as u8,
+                3,
+                1234,
+                "operator".to_string(),
+                "ticket".to_string(),
+                "pruned 1 value".to_string(),
+            )
+            .unwrap(),
+        ),
+        DbRecord::SoftDeletion(SoftDeletion {
+            username: AkdLabel::from("user"),
+            epoch: 3,
+        }),
+    ]
+}
+
+#[cfg(any(
+    feature = "bincode_codec",
+    feature = "msgpack_codec",
+    feature = "protobuf_codec"
+))]
+#[test]
+fn test_record_codecs() {
+    use crate::storage::codec::RecordCodec;
+
+    let mut codecs: Vec<Box<dyn RecordCodec>> = vec![];
+    #[cfg(feature = "bincode_codec")]
+    codecs.push(Box::new(crate::storage::codec::BincodeCodec));
+    #[cfg(feature = "msgpack_codec")]
+    codecs.push(Box::new(crate::storage::codec::MessagePackCodec));
+    #[cfg(feature = "protobuf_codec")]
+    codecs.push(Box::new(crate::storage::codec::ProtobufCodec));
+
+    for codec in codecs {
+        for record in codec_test_records() {
+            let bytes = codec.encode(&record).unwrap();
+            assert_eq!(
+                record,
+                codec.decode(&bytes).unwrap(),
+                "{} codec",
+                codec.name()
+            );
+        }
+        assert!(codec.decode(&[0xff; 3]).is_err(), "{} codec", codec.name());
+    }
+}
+
+#[cfg(all(feature = "bincode_codec", feature = "protobuf_codec"))]
+#[test]
+fn test_storage_manager_dual_read_codec() {
+    use crate::storage::codec::{BincodeCodec, DualReadCodec, ProtobufCodec, RecordCodec};
+
+    let storage_manager = StorageManager::new_no_cache(AsyncInMemoryDatabase::new())
+        .with_record_codec(DualReadCodec::new(ProtobufCodec, BincodeCodec));
+    assert_eq!(Some("protobuf"), storage_manager.record_codec_name());
+
+    for record in codec_test_records() {
+        // records written with the old codec can still be read
+        let old_bytes = BincodeCodec.encode(&record).unwrap();
+        assert_eq!(record, storage_manager.decode_record(&old_bytes).unwrap());
+
+        // and new records are written with the new codec
+        let new_bytes = storage_manager.encode_record(&record).unwrap();
+        assert_eq!(ProtobufCodec.encode(&record).unwrap(), new_bytes);
+        assert_eq!(record, storage_manager.decode_record(&new_bytes).unwrap());
+    }
+}
+
+#[test]
+fn test_storage_manager_without_record_codec() {
+    let storage_manager = StorageManager::new_no_cache(AsyncInMemoryDatabase::new());
+    assert_eq!(None, storage_manager.record_codec_name());
+    let record = DbRecord::Azks(DbRecord::build_azks(1, 1));
+    assert!(storage_manager.encode_record(&record).is_err());
+    assert!(storage_manager.decode_record(&[]).is_err());
+}
+
+#[tokio::test]
+async fn test_storage_manager_soft_deletion() {
+    let storage_manager = StorageManager::new(AsyncInMemoryDatabase::new(), None, None, None);
+    let alice = AkdLabel::from("alice");
+    let bob = AkdLabel::from("bob");
+    let records = [(&alice, 1), (&alice, 2), (&bob, 2)]
+        .into_iter()
+        .map(|(username, epoch)| {
+            DbRecord::ValueState(DbRecord::build_user_state(
+                username.0.clone(),
+                format!("value {epoch}").into_bytes(),
+                epoch,
+                0,
+                [0u8; 32],
+                epoch,
+                None,
+            ))
+        })
+        .collect::<Vec<_>>();
+    storage_manager.batch_set(records).await.unwrap();
+
+    // only labels with value states can be soft-deleted
+    assert!(storage_manager
+        .soft_delete_label(&AkdLabel::from("carol"), 2)
+        .await
+        .is_err());
+
+    storage_manager.soft_delete_label(&alice, 2).await.unwrap();
+    storage_manager.soft_delete_label(&bob, 2).await.unwrap();
+    // soft-deleting again keeps the original retention window
+    storage_manager.soft_delete_label(&alice, 3).await.unwrap();
+    assert_eq!(
+        Some(SoftDeletion {
+            username: alice.clone(),
+            epoch: 2
+        }),
+        storage_manager.get_soft_deletion(&alice).await.unwrap()
+    );
+
+    // a soft deletion leaves the value states in place, and can be reversed
+    assert_eq!(
+        AkdValue::from("value 2"),
+        storage_manager
+            .get_user_state(&alice, ValueStateRetrievalFlag::MaxEpoch)
+            .await
+            .unwrap()
+            .value
+    );
+    assert!(storage_manager.restore_label(&bob).await.unwrap());
+    assert!(!storage_manager.restore_label(&bob).await.unwrap());
+    assert_eq!(None, storage_manager.get_soft_deletion(&bob).await.unwrap());
+
+    // nothing is purged within the retention window
+    assert!(storage_manager
+        .purge_soft_deletions(3, 2)
+        .await
+        .unwrap()
+        .is_empty());
+
+    assert_eq!(
+        vec![alice.clone()],
+        storage_manager.purge_soft_deletions(4, 2).await.unwrap()
+    );
+    let alice_data = storage_manager.get_user_data(&alice).await.unwrap();
+    assert_eq!(2, alice_data.states.len());
+    assert!(alice_data
+        .states
+        .iter()
+        .all(|state| state.value.0 == crate::TOMBSTONE));
+    assert_eq!(
+        AkdValue::from("value 2"),
+        storage_manager
+            .get_user_state(&bob, ValueStateRetrievalFlag::MaxEpoch)
+            .await
+            .unwrap()
+            .value
+    );
+
+    // a purged label can no longer be restored
+    assert!(storage_manager
+        .get_soft_deletions()
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(!storage_manager.restore_label(&alice).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_storage_manager_usage_stats() {
+    let storage_manager = StorageManager::new_no_cache(AsyncInMemoryDatabase::new());
+    let node = |label_len: u32, epoch: u64| {
+        DbRecord::TreeNode(DbRecord::build_tree_node_with_previous_value(
+            [0u8; 32],
+            label_len,
+            epoch,
+            epoch,
+            [0u8; 32],
+            0,
+            1,
+            None,
+            None,
+            EMPTY_DIGEST,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        ))
+    };
+
+    // the writes of each epoch's commit are attributed to that epoch
+    for epoch in 0..=1u64 {
+        assert!(storage_manager.begin_transaction());
+        let mut records = (0..=epoch as u32)
+            .map(|i| node(i, epoch))
+            .collect::<Vec<_>>();
+        records.push(DbRecord::Azks(DbRecord::build_azks(epoch, epoch)));
+        storage_manager.batch_set(records).await.unwrap();
+        storage_manager.commit_transaction().await.unwrap();
+    }
+    let node_size = node(0, 0).size_of() as u64;
+
+    // reads are attributed to the latest epoch
+    storage_manager
+        .batch_get::<TreeNodeWithPreviousValue>(&[NodeKey(NodeLabel::new([0u8; 32], 0))])
+        .await
+        .unwrap();
+
+    let usage = storage_manager.usage_stats();
+    assert_eq!(
+        StorageTypeUsage {
+            records_written: 3,
+            bytes_written: 3 * node_size,
+            records_read: 1,
+            bytes_read: node_size,
+        },
+        usage.for_type(StorageType::TreeNode)
+    );
+    assert_eq!(2, usage.for_type(StorageType::Azks).records_written);
+    assert_eq!(
+        StorageTypeUsage::default(),
+        usage.for_type(StorageType::ValueState)
+    );
+    assert_eq!(
+        vec![0, 1],
+        usage.by_epoch.keys().copied().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        1,
+        usage
+            .for_type_in_epochs(StorageType::TreeNode, 0, 0)
+            .records_written
+    );
+    let epoch_1 = usage.for_type_in_epochs(StorageType::TreeNode, 1, 1);
+    assert_eq!((2, 1), (epoch_1.records_written, epoch_1.records_read));
+
+    // only the most recent epochs are kept
+    let storage_manager =
+        StorageManager::new_no_cache(AsyncInMemoryDatabase::new()).with_usage_epochs(1);
+    for epoch in 1..=3 {
+        storage_manager
+            .set(DbRecord::Azks(DbRecord::build_azks(epoch, 0)))
+            .await
+            .unwrap();
+    }
+    let usage = storage_manager.usage_stats();
+    assert_eq!(vec![3], usage.by_epoch.keys().copied().collect::<Vec<_>>());
+    assert_eq!(3, usage.for_type(StorageType::Azks).records_written);
+}
+
+fn write_behind_node(label_len: u32, epoch: u64) -> DbRecord {
+    DbRecord::TreeNode(DbRecord::build_tree_node_with_previous_value(
+        [0u8; 32],
+        label_len,
+        epoch,
+        epoch,
+        [0u8; 32],
+        0,
+        1,
+        None,
+        None,
+        EMPTY_DIGEST,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    ))
+}
+
+#[tokio::test]
+async fn test_storage_manager_write_behind() {
+    let db = AsyncInMemoryDatabase::new();
+    let storage_manager =
+        StorageManager::new_no_cache(db.clone()).with_write_behind(WriteBehindOptions {
+            queue_capacity: 2,
+            max_batch_size: 2,
+            ..Default::default()
+        });
+    assert!(storage_manager.has_write_behind());
+    let key = NodeKey(NodeLabel::new([0u8; 32], 0));
+
+    // a write to a queued record replaces it, and is read back before it is flushed
+    storage_manager.set(write_behind_node(0, 0)).await.unwrap();
+    storage_manager.set(write_behind_node(0, 1)).await.unwrap();
+    assert_eq!(1, storage_manager.pending_writes());
+    assert_eq!(
+        Ok(write_behind_node(0, 1)),
+        storage_manager.get::<TreeNodeWithPreviousValue>(&key).await
+    );
+    assert!(matches!(
+        db.get::<TreeNodeWithPreviousValue>(&key).await,
+        Err(StorageError::NotFound(_))
+    ));
+
+    storage_manager.flush_and_wait().await.unwrap();
+    assert_eq!(0, storage_manager.pending_writes());
+    assert_eq!(
+        Ok(write_behind_node(0, 1)),
+        db.get::<TreeNodeWithPreviousValue>(&key).await
+    );
+    assert_eq!(
+        1,
+        storage_manager
+            .usage_stats()
+            .for_type(StorageType::TreeNode)
+            .records_written
+    );
+
+    // a batch larger than the queue is accepted once the queue is empty, after which writers
+    // wait for the flusher
+    storage_manager
+        .batch_set((1..=3).map(|i| write_behind_node(i, 0)).collect())
+        .await
+        .unwrap();
+    storage_manager.set(write_behind_node(4, 0)).await.unwrap();
+    assert!(storage_manager.pending_writes() <= 2);
+
+    // each epoch's commit waits for the queue to be flushed
+    assert!(storage_manager.begin_transaction());
+    storage_manager
+        .set(DbRecord::Azks(DbRecord::build_azks(0, 5)))
+        .await
+        .unwrap();
+    storage_manager.commit_transaction().await.unwrap();
+    assert_eq!(0, storage_manager.pending_writes());
+    for i in 1..=4 {
+        assert!(db
+            .get::<TreeNodeWithPreviousValue>(&NodeKey(NodeLabel::new([0u8; 32], i)))
+            .await
+            .is_ok());
+    }
+}
+
+#[tokio::test]
+async fn test_storage_manager_write_behind_retries() {
+    use crate::tests::MockLocalDatabase;
+    use std::sync::atomic::AtomicUsize;
+
+    // a database which fails the first two batch writes
+    let test_db = AsyncInMemoryDatabase::new();
+    let mut db = MockLocalDatabase::default();
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let (tmp_db, tmp_attempts) = (test_db.clone(), attempts.clone());
+    db.expect_batch_set().returning(move |records, state| {
+        if tmp_attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst) < 2 {
+            return Err(StorageError::Connection("Unavailable".to_string()));
+        }
+        futures::executor::block_on(tmp_db.batch_set(records, state))
+    });
+    let storage_manager = StorageManager::new_no_cache(db).with_write_behind(WriteBehindOptions {
+        max_retries: 1,
+        retry_backoff: Duration::from_millis(1),
+        ..Default::default()
+    });
+
+    // the batch is retried once, after which the failure is reported and the batch is kept
+    storage_manager.set(write_behind_node(0, 0)).await.unwrap();
+    assert_eq!(
+        Err(StorageError::Connection("Unavailable".to_string())),
+        storage_manager.flush_and_wait().await
+    );
+    assert_eq!(2, attempts.load(std::sync::atomic::Ordering::SeqCst));
+    assert_eq!(1, storage_manager.pending_writes());
+
+    // the flusher tries again once the failure has been reported
+    storage_manager.flush_and_wait().await.unwrap();
+    assert_eq!(0, storage_manager.pending_writes());
+    assert!(test_db
+        .get::<TreeNodeWithPreviousValue>(&NodeKey(NodeLabel::new([0u8; 32], 0)))
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+async fn test_storage_manager_shutdown() {
+    use crate::tests::MockLocalDatabase;
+
+    // a database which must be closed exactly once
+    let test_db = AsyncInMemoryDatabase::new();
+    let mut db = MockLocalDatabase::default();
+    let tmp_db = test_db.clone();
+    db.expect_batch_set().returning(move |records, state| {
+        futures::executor::block_on(tmp_db.batch_set(records, state))
+    });
+    db.expect_close().times(1).returning(|| Ok(()));
+    let storage_manager =
+        StorageManager::new(db, None, None, None).with_write_behind(WriteBehindOptions::default());
+
+    // the records written in the background are flushed by the shutdown of any clone
+    storage_manager.set(write_behind_node(0, 0)).await.unwrap();
+    storage_manager.set(write_behind_node(1, 0)).await.unwrap();
+    assert_eq!(Some(2), storage_manager.cache_len());
+    assert!(!storage_manager.is_shut_down());
+    storage_manager.clone().shutdown().await.unwrap();
+    assert!(storage_manager.is_shut_down());
+    assert_eq!(0, storage_manager.pending_writes());
+    assert_eq!(Some(0), storage_manager.cache_len());
+    for i in 0..=1 {
+        assert!(test_db
+            .get::<TreeNodeWithPreviousValue>(&NodeKey(NodeLabel::new([0u8; 32], i)))
+            .await
+            .is_ok());
+    }
+
+    // writes are refused afterwards, and shutting down again does nothing
+    assert!(matches!(
+        storage_manager.set(write_behind_node(2, 0)).await,
+        Err(StorageError::Connection(_))
+    ));
+    storage_manager.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_publish_lease() {
+    let db = AsyncInMemoryDatabase::new();
+    let first = StorageManager::new_no_cache(db.clone())
+        .with_publish_lease(PublishLeaseOptions::new("first"));
+    let second = StorageManager::new_no_cache(db.clone())
+        .with_publish_lease(PublishLeaseOptions::new("second"));
+    assert!(StorageManager::new_no_cache(db.clone())
+        .acquire_publish_lease()
+        .await
+        .unwrap()
+        .is_none());
+
+    // the lease is exclusive until it is released
+    let lease = first.acquire_publish_lease().await.unwrap().unwrap();
+    assert_eq!(1, lease.token());
+    assert!(matches!(
+        second.acquire_publish_lease().await,
+        Err(StorageError::LeaseHeld(_))
+    ));
+    lease.renew().await.unwrap();
+    lease.release().await.unwrap();
+    let lease = second.acquire_publish_lease().await.unwrap().unwrap();
+    assert_eq!(2, lease.token());
+
+    // once a lease has expired and been taken over, its former holder can neither renew it
+    // nor release the new holder's lease
+    let taken_over = PublishLease {
+        holder: "first".to_string(),
+        token: 3,
+        expires_at_ms: u64::MAX,
+    };
+    db.set(DbRecord::PublishLease(taken_over.clone()))
+        .await
+        .unwrap();
+    assert!(matches!(
+        lease.renew().await,
+        Err(StorageError::LeaseHeld(_))
+    ));
+    lease.release().await.unwrap();
+    assert_eq!(
+        Ok(DbRecord::PublishLease(taken_over)),
+        db.get::<PublishLease>(&DEFAULT_PUBLISH_LEASE_KEY).await
+    );
+
+    // a follower cannot acquire the lease
+    assert!(matches!(
+        first
+            .clone()
+            .with_role(StorageRole::Follower)
+            .acquire_publish_lease()
+            .await,
+        Err(StorageError::ReadOnly(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_commit_with_expired_publish_lease() {
+    let db = AsyncInMemoryDatabase::new();
+    let azks = |latest_epoch| {
+        DbRecord::Azks(Azks {
+            latest_epoch,
+            num_nodes: 1,
+        })
+    };
+    let state = |value: &str| {
+        DbRecord::ValueState(ValueState {
+            value: AkdValue::from(value),
+            version: 1,
+            label: NodeLabel::new(crate::utils::byte_arr_from_u64(1), 64),
+            epoch: 2,
+            username: AkdLabel::from("user"),
+            retention_class: None,
+        })
+    };
+    let state_key = ValueStateKey(b"user".to_vec(), 2);
+    db.set(azks(1)).await.unwrap();
+    let first = StorageManager::new_no_cache(db.clone())
+        .with_publish_lease(PublishLeaseOptions::new("first"));
+    let second = StorageManager::new_no_cache(db.clone())
+        .with_publish_lease(PublishLeaseOptions::new("second"));
+
+    let lease = first.acquire_publish_lease().await.unwrap().unwrap();
+    assert!(first.begin_transaction());
+    first.set(state("first")).await.unwrap();
+    first.set(azks(2)).await.unwrap();
+
+    // the lease lapses before its holder commits, e.g. while the holder is paused, and is
+    // taken over by another publisher
+    db.set(DbRecord::PublishLease(PublishLease {
+        holder: "first".to_string(),
+        token: lease.token(),
+        expires_at_ms: 0,
+    }))
+    .await
+    .unwrap();
+    let second_lease = second.acquire_publish_lease().await.unwrap().unwrap();
+
+    // the former holder's commit is fenced off, and none of its records are written
+    assert!(matches!(
+        first.commit_transaction_after(1, Some(&lease)).await,
+        Err(StorageError::LeaseHeld(_))
+    ));
+    assert_eq!(
+        Ok(azks(1)),
+        db.get::<Azks>(&crate::append_only_zks::DEFAULT_AZKS_KEY)
+            .await
+    );
+    assert!(matches!(
+        db.get::<ValueState>(&state_key).await,
+        Err(StorageError::NotFound(_))
+    ));
+
+    // while the new holder commits the epoch
+    assert!(second.begin_transaction());
+    second.set(state("second")).await.unwrap();
+    second.set(azks(2)).await.unwrap();
+    second
+        .commit_transaction_after(1, Some(&second_lease))
+        .await
+        .unwrap();
+    assert_eq!(Ok(state("second")), db.get::<ValueState>(&state_key).await);
+    second_lease.release().await.unwrap();
+}
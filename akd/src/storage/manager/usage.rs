@@ -0,0 +1,199 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Accounting of the records which the storage manager writes to and reads from the database,
+//! by type of record and by epoch, so that the growth of a database can be attributed to the
+//! types of record and the epochs responsible for it. Sizes are the approximate in-memory sizes
+//! of the records ([akd_core::SizeOf]), which the sizes of their stored encodings track.
+
+use crate::storage::types::{DbRecord, StorageType};
+use akd_core::SizeOf;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+/// The number of epochs whose usage is kept by default
+pub const DEFAULT_USAGE_EPOCHS: usize = 256;
+
+/// The records of one type written to and read from the database
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StorageTypeUsage {
+    /// The number of records written
+    pub records_written: u64,
+    /// The approximate size of the records written, in bytes
+    pub bytes_written: u64,
+    /// The number of records read
+    pub records_read: u64,
+    /// The approximate size of the records read, in bytes
+    pub bytes_read: u64,
+}
+
+impl StorageTypeUsage {
+    fn add(&mut self, other: &StorageTypeUsage) {
+        self.records_written += other.records_written;
+        self.bytes_written += other.bytes_written;
+        self.records_read += other.records_read;
+        self.bytes_read += other.bytes_read;
+    }
+}
+
+/// A snapshot of the records written to and read from the database by a storage manager,
+/// since it was created. Reads which are served by the cache or the transaction log are
+/// not counted.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StorageUsage {
+    /// The usage of each type of record
+    pub by_type: HashMap<StorageType, StorageTypeUsage>,
+    /// The usage of each type of record, for each of the most recent epochs. Accesses are
+    /// attributed to the latest epoch which the storage manager has seen at the time, so the
+    /// writes of an epoch's publish are attributed to that epoch.
+    pub by_epoch: BTreeMap<u64, HashMap<StorageType, StorageTypeUsage>>,
+}
+
+impl StorageUsage {
+    /// The usage of one type of record
+    pub fn for_type(&self, storage_type: StorageType) -> StorageTypeUsage {
+        self.by_type.get(&storage_type).copied().unwrap_or_default()
+    }
+
+    /// The usage of one type of record, in the epochs within the given range (inclusive)
+    pub fn for_type_in_epochs(
+        &self,
+        storage_type: StorageType,
+        from_epoch: u64,
+        to_epoch: u64,
+    ) -> StorageTypeUsage {
+        let mut usage = StorageTypeUsage::default();
+        for by_type in self.by_epoch.range(from_epoch..=to_epoch).map(|(_, v)| v) {
+            if let Some(type_usage) = by_type.get(&storage_type) {
+                usage.add(type_usage);
+            }
+        }
+        usage
+    }
+}
+
+/// The usage of a single access to the database, which is applied to a [UsageTracker] once
+/// the access has succeeded
+#[derive(Debug, Default)]
+pub(crate) struct UsageDelta {
+    epoch: Option<u64>,
+    is_write: bool,
+    by_type: HashMap<StorageType, StorageTypeUsage>,
+}
+
+impl UsageDelta {
+    /// The usage of writing a batch of records
+    pub(crate) fn writes(records: &[DbRecord]) -> Self {
+        let mut delta = Self::of_records(records, |usage, size| {
+            usage.records_written += 1;
+            usage.bytes_written += size;
+        });
+        delta.is_write = true;
+        delta
+    }
+
+    /// The usage of reading a batch of records
+    pub(crate) fn reads(records: &[DbRecord]) -> Self {
+        Self::of_records(records, |usage, size| {
+            usage.records_read += 1;
+            usage.bytes_read += size;
+        })
+    }
+
+    /// The usage of reading a number of records of one type, whose total size is given
+    pub(crate) fn reads_of_type(storage_type: StorageType, count: u64, bytes: u64) -> Self {
+        let mut delta = Self::default();
+        delta.by_type.insert(
+            storage_type,
+            StorageTypeUsage {
+                records_read: count,
+                bytes_read: bytes,
+                ..Default::default()
+            },
+        );
+        delta
+    }
+
+    fn of_records(records: &[DbRecord], count: impl Fn(&mut StorageTypeUsage, u64)) -> Self {
+        let mut delta = Self::default();
+        for record in records {
+            // an Azks record carries the latest epoch of the directory
+            if let DbRecord::Azks(azks) = record {
+                delta.epoch = delta.epoch.max(Some(azks.latest_epoch));
+            }
+            count(
+                delta.by_type.entry(record.storage_type()).or_default(),
+                record.size_of() as u64,
+            );
+        }
+        delta
+    }
+}
+
+#[derive(Debug)]
+struct UsageState {
+    epoch: u64,
+    usage: StorageUsage,
+}
+
+/// The running totals of a [StorageUsage]
+#[derive(Debug)]
+pub(crate) struct UsageTracker {
+    state: Mutex<UsageState>,
+    max_epochs: usize,
+}
+
+impl UsageTracker {
+    pub(crate) fn new(max_epochs: usize) -> Self {
+        Self {
+            state: Mutex::new(UsageState {
+                epoch: 0,
+                usage: StorageUsage::default(),
+            }),
+            max_epochs,
+        }
+    }
+
+    /// Adds the usage of an access to the overall usage and to the usage of the current epoch.
+    /// A written Azks record sets the current epoch (which may move backwards, after a
+    /// rollback), while a read one only advances it, as it may be stale.
+    pub(crate) fn apply(&self, delta: UsageDelta) {
+        if delta.by_type.is_empty() {
+            return;
+        }
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        match delta.epoch {
+            Some(epoch) if delta.is_write => state.epoch = epoch,
+            Some(epoch) => state.epoch = state.epoch.max(epoch),
+            None => {}
+        }
+        let current_epoch = state.epoch;
+        let add = |totals: &mut HashMap<StorageType, StorageTypeUsage>| {
+            for (storage_type, usage) in &delta.by_type {
+                totals.entry(*storage_type).or_default().add(usage);
+            }
+        };
+        add(&mut state.usage.by_type);
+        if self.max_epochs > 0 {
+            let by_epoch = &mut state.usage.by_epoch;
+            add(by_epoch.entry(current_epoch).or_default());
+            while by_epoch.len() > self.max_epochs {
+                by_epoch.pop_first();
+            }
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> StorageUsage {
+        match self.state.lock() {
+            Ok(state) => state.usage.clone(),
+            Err(poisoned) => poisoned.into_inner().usage.clone(),
+        }
+    }
+}
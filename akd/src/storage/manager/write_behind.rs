@@ -0,0 +1,319 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! The write-behind mode of the storage manager, in which the writes made outside of a
+//! transaction are acknowledged once they are queued, and are written to the database by a
+//! background flusher.
+//!
+//! The queue is bounded, so writers wait for the flusher once it is full. Writes to a record
+//! which is still queued replace the queued record, so that only its latest version is written.
+//! A batch which fails to be written is retried with a backoff, and if it still fails, it is put
+//! back at the front of the queue and the failure is reported to the next writer or to the next
+//! [WriteBehind::flush_and_wait], after which the flusher tries again. Durability is only
+//! guaranteed for the writes which were queued before a successful
+//! [WriteBehind::flush_and_wait].
+
+use super::usage::{UsageDelta, UsageTracker};
+use crate::errors::StorageError;
+use crate::storage::types::DbRecord;
+use crate::storage::{Database, DbSetState};
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// The default maximum number of records waiting to be flushed
+pub const DEFAULT_WRITE_BEHIND_QUEUE_CAPACITY: usize = 10_000;
+/// The default maximum number of records written in one batch
+pub const DEFAULT_WRITE_BEHIND_BATCH_SIZE: usize = 1_000;
+
+/// The options of the write-behind mode of a [StorageManager](crate::storage::StorageManager)
+#[derive(Clone, Debug)]
+pub struct WriteBehindOptions {
+    /// The maximum number of distinct records waiting to be flushed. Writers wait for the
+    /// flusher once the queue is full.
+    pub queue_capacity: usize,
+    /// The maximum number of records written to the database in one batch
+    pub max_batch_size: usize,
+    /// The number of times a failed batch is retried before its failure is reported
+    pub max_retries: usize,
+    /// The delay before the first retry of a failed batch, which doubles with each retry
+    pub retry_backoff: Duration,
+}
+
+impl Default for WriteBehindOptions {
+    fn default() -> Self {
+        Self {
+            queue_capacity: DEFAULT_WRITE_BEHIND_QUEUE_CAPACITY,
+            max_batch_size: DEFAULT_WRITE_BEHIND_BATCH_SIZE,
+            max_retries: 3,
+            retry_backoff: Duration::from_millis(100),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    /// The keys of the queued records, in the order in which they were first queued
+    order: VecDeque<Vec<u8>>,
+    queued: HashMap<Vec<u8>, DbRecord>,
+    /// The records of the batch being written by the flusher
+    in_flight: HashMap<Vec<u8>, DbRecord>,
+    error: Option<StorageError>,
+    started: bool,
+    closed: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    /// Wakes the flusher when there are records to write
+    work: Notify,
+    /// Wakes the writers and flushes which are waiting on the flusher
+    progress: Notify,
+    options: WriteBehindOptions,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        match self.queue.lock() {
+            Ok(queue) => queue,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+type Spawner = Box<dyn Fn(Arc<Shared>, Arc<UsageTracker>) -> JoinHandle<()> + Send + Sync>;
+
+/// The queue of a storage manager's write-behind mode, which is flushed by a background task.
+/// The flusher is started by the first write, and stops (once it has written the queued
+/// records) when the queue is shut down or dropped.
+pub(crate) struct WriteBehind {
+    shared: Arc<Shared>,
+    spawn_flusher: Spawner,
+    flusher: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl WriteBehind {
+    pub(crate) fn new<Db: Database + 'static>(db: Arc<Db>, options: WriteBehindOptions) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                queue: Mutex::new(Queue::default()),
+                work: Notify::new(),
+                progress: Notify::new(),
+                options,
+            }),
+            spawn_flusher: Box::new(move |shared, usage| {
+                tokio::spawn(run_flusher(shared, db.clone(), usage))
+            }),
+            flusher: Mutex::new(None),
+        }
+    }
+
+    /// The number of records which have not been written yet
+    pub(crate) fn pending(&self) -> usize {
+        let queue = self.shared.lock();
+        queue.queued.len() + queue.in_flight.len()
+    }
+
+    /// The latest version of a record which has not been written yet, if any
+    pub(crate) fn get(&self, key: &[u8]) -> Option<DbRecord> {
+        let queue = self.shared.lock();
+        queue
+            .queued
+            .get(key)
+            .or_else(|| queue.in_flight.get(key))
+            .cloned()
+    }
+
+    /// Queues records to be written by the flusher, waiting for space in the queue if it is
+    /// full. Fails with the last failure of the flusher, if it has not been reported yet, in
+    /// which case the records are not queued.
+    pub(crate) async fn enqueue(
+        &self,
+        usage: &Arc<UsageTracker>,
+        records: Vec<DbRecord>,
+    ) -> Result<(), StorageError> {
+        let records = records
+            .into_iter()
+            .map(|record| (record.get_full_binary_id(), record))
+            .collect::<Vec<_>>();
+        loop {
+            let progress = self.shared.progress.notified();
+            tokio::pin!(progress);
+            progress.as_mut().enable();
+            {
+                let mut queue = self.shared.lock();
+                if queue.closed {
+                    return Err(StorageError::Connection(
+                        "The write-behind queue has been shut down".to_string(),
+                    ));
+                }
+                if let Some(err) = queue.error.take() {
+                    self.shared.work.notify_one();
+                    return Err(err);
+                }
+                let num_new = records
+                    .iter()
+                    .filter(|(key, _)| !queue.queued.contains_key(key))
+                    .count();
+                // a batch larger than the queue is accepted once the queue is empty
+                if queue.queued.is_empty()
+                    || queue.queued.len() + num_new <= self.shared.options.queue_capacity
+                {
+                    for (key, record) in records {
+                        if queue.queued.insert(key.clone(), record).is_none() {
+                            queue.order.push_back(key);
+                        }
+                    }
+                    if !queue.started {
+                        queue.started = true;
+                        let flusher = (self.spawn_flusher)(self.shared.clone(), usage.clone());
+                        *lock_flusher(&self.flusher) = Some(flusher);
+                    }
+                    self.shared.work.notify_one();
+                    return Ok(());
+                }
+            }
+            progress.await;
+        }
+    }
+
+    /// Waits until all of the records queued so far have been written to the database, failing
+    /// with the last failure of the flusher, if it has not been reported yet
+    pub(crate) async fn flush_and_wait(&self) -> Result<(), StorageError> {
+        loop {
+            let progress = self.shared.progress.notified();
+            tokio::pin!(progress);
+            progress.as_mut().enable();
+            {
+                let mut queue = self.shared.lock();
+                if let Some(err) = queue.error.take() {
+                    self.shared.work.notify_one();
+                    return Err(err);
+                }
+                if queue.queued.is_empty() && queue.in_flight.is_empty() {
+                    return Ok(());
+                }
+            }
+            progress.await;
+        }
+    }
+
+    /// Flushes the queue and then stops the flusher, waiting for it to finish. Records which
+    /// are queued afterwards are refused. If the queue fails to be flushed, the failure is
+    /// returned, and the flusher makes a last attempt to write the records which were not
+    /// written before it stops.
+    pub(crate) async fn shutdown(&self) -> Result<(), StorageError> {
+        let flushed = self.flush_and_wait().await;
+        self.shared.lock().closed = true;
+        self.shared.work.notify_one();
+        let flusher = lock_flusher(&self.flusher).take();
+        if let Some(flusher) = flusher {
+            if let Err(err) = flusher.await {
+                warn!("The write-behind flusher failed to stop cleanly: {err}");
+            }
+        }
+        flushed
+    }
+}
+
+fn lock_flusher(flusher: &Mutex<Option<JoinHandle<()>>>) -> MutexGuard<'_, Option<JoinHandle<()>>> {
+    match flusher.lock() {
+        Ok(flusher) => flusher,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+impl Drop for WriteBehind {
+    fn drop(&mut self) {
+        self.shared.lock().closed = true;
+        self.shared.work.notify_one();
+    }
+}
+
+async fn run_flusher<Db: Database>(shared: Arc<Shared>, db: Arc<Db>, usage: Arc<UsageTracker>) {
+    loop {
+        let batch = {
+            let mut queue = shared.lock();
+            if queue.error.is_none() && !queue.order.is_empty() {
+                let size = queue.order.len().min(shared.options.max_batch_size);
+                let keys = queue.order.drain(..size).collect::<Vec<_>>();
+                for key in keys {
+                    if let Some(record) = queue.queued.remove(&key) {
+                        queue.in_flight.insert(key, record);
+                    }
+                }
+                Some(queue.in_flight.values().cloned().collect::<Vec<_>>())
+            } else if queue.closed {
+                if !queue.queued.is_empty() {
+                    warn!(
+                        num_records = queue.queued.len(),
+                        "Dropping the unwritten records of a closed write-behind queue"
+                    );
+                }
+                return;
+            } else {
+                None
+            }
+        };
+        let Some(batch) = batch else {
+            shared.work.notified().await;
+            continue;
+        };
+
+        let result = write_with_retries(&shared.options, db.as_ref(), batch.clone()).await;
+        let mut queue = shared.lock();
+        let in_flight = std::mem::take(&mut queue.in_flight);
+        match result {
+            Ok(()) => {
+                debug!(num_records = batch.len(), "Flushed write-behind records");
+                usage.apply(UsageDelta::writes(&batch));
+            }
+            Err(err) => {
+                // put the batch back at the front of the queue, unless a record has been
+                // replaced since, and hold on to the failure until it is reported
+                for (key, record) in in_flight {
+                    if !queue.queued.contains_key(&key) {
+                        queue.queued.insert(key.clone(), record);
+                        queue.order.push_front(key);
+                    }
+                }
+                queue.error = Some(err);
+            }
+        }
+        drop(queue);
+        shared.progress.notify_waiters();
+    }
+}
+
+async fn write_with_retries<Db: Database>(
+    options: &WriteBehindOptions,
+    db: &Db,
+    records: Vec<DbRecord>,
+) -> Result<(), StorageError> {
+    let mut backoff = options.retry_backoff;
+    let mut attempt = 0;
+    loop {
+        match db.batch_set(records.clone(), DbSetState::General).await {
+            Ok(()) => return Ok(()),
+            Err(err) if attempt < options.max_retries => {
+                attempt += 1;
+                warn!(
+                    attempt,
+                    num_records = records.len(),
+                    "Failed to flush write-behind records, retrying: {err}"
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
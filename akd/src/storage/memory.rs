@@ -13,10 +13,11 @@ use crate::errors::StorageError;
 use crate::storage::types::{
     DbRecord, KeyData, StorageType, ValueState, ValueStateKey, ValueStateRetrievalFlag,
 };
-use crate::storage::{Database, Storable, StorageUtil};
+use crate::storage::{check_version, Database, DbSetState, Storable, StorageUtil};
 use crate::{AkdLabel, AkdValue};
 use async_trait::async_trait;
 use dashmap::DashMap;
+use futures::stream::{self, BoxStream, StreamExt};
 use std::collections::HashMap;
 use std::sync::Arc;
 
@@ -30,6 +31,9 @@ type UserValueMap = HashMap<Epoch, ValueState>;
 pub struct AsyncInMemoryDatabase {
     db: Arc<DashMap<Vec<u8>, DbRecord>>,
     user_info: Arc<DashMap<Vec<u8>, UserValueMap>>,
+    // held by every conditional write, so that the versions compared by a conditional batch
+    // cannot change before its records are written
+    conditional_writes: Arc<tokio::sync::Mutex<()>>,
 }
 
 unsafe impl Send for AsyncInMemoryDatabase {}
@@ -41,12 +45,26 @@ impl AsyncInMemoryDatabase {
         Self::default()
     }
 
+    /// Clears all records from the database
     #[cfg(test)]
     pub fn clear(&self) {
         self.db.clear();
         self.user_info.clear();
     }
 
+    /// The version stamp of the stored record with the same id as `record`, if there is one
+    fn stored_version(&self, record: &DbRecord) -> Option<u64> {
+        if let DbRecord::ValueState(value_state) = record {
+            return self
+                .user_info
+                .get(value_state.username.as_slice())
+                .and_then(|states| states.get(&value_state.epoch).map(|state| state.epoch));
+        }
+        self.db
+            .get(&record.get_full_binary_id())
+            .map(|stored| stored.version_stamp())
+    }
+
     async fn get_internal<St: Storable>(
         &self,
         id: &St::StorageKey,
@@ -108,6 +126,34 @@ impl Database for AsyncInMemoryDatabase {
         Ok(())
     }
 
+    async fn set_if_version(
+        &self,
+        record: DbRecord,
+        expected_version: Option<u64>,
+    ) -> Result<(), StorageError> {
+        let _guard = self.conditional_writes.lock().await;
+        check_version(&record, expected_version, self.stored_version(&record))?;
+        self.set(record).await
+    }
+
+    async fn batch_set_if_versions(
+        &self,
+        records: Vec<DbRecord>,
+        conditional: Vec<(DbRecord, Option<u64>)>,
+        state: DbSetState,
+    ) -> Result<(), StorageError> {
+        let _guard = self.conditional_writes.lock().await;
+        for (record, expected_version) in &conditional {
+            check_version(record, *expected_version, self.stored_version(record))?;
+        }
+        self.batch_set(records, state).await?;
+        self.batch_set(
+            conditional.into_iter().map(|(record, _)| record).collect(),
+            state,
+        )
+        .await
+    }
+
     /// Retrieve a stored record from the data layer
     async fn get<St: Storable>(&self, id: &St::StorageKey) -> Result<DbRecord, StorageError> {
         #[cfg(feature = "slow_internal_db")]
@@ -139,7 +185,7 @@ impl Database for AsyncInMemoryDatabase {
         if let Some(result) = self.user_info.get(&username.0) {
             let mut results: Vec<ValueState> = result.values().cloned().collect::<Vec<_>>();
             // return ordered by epoch (from smallest -> largest)
-            results.sort_by(|a, b| a.epoch.cmp(&b.epoch));
+            results.sort_by_key(|a| a.epoch);
 
             Ok(KeyData { states: results })
         } else {
@@ -242,12 +288,37 @@ impl StorageUtil for AsyncInMemoryDatabase {
                 DbRecord::Azks(_) => St::data_type() == StorageType::Azks,
                 DbRecord::TreeNode(_) => St::data_type() == StorageType::TreeNode,
                 DbRecord::ValueState(_) => St::data_type() == StorageType::ValueState,
+                DbRecord::OperationRecord(_) => St::data_type() == StorageType::OperationRecord,
+                DbRecord::SoftDeletion(_) => St::data_type() == StorageType::SoftDeletion,
+                DbRecord::AnchorReceipt(_) => St::data_type() == StorageType::AnchorReceipt,
+                DbRecord::PublishLease(_) => St::data_type() == StorageType::PublishLease,
+                DbRecord::AuditEpoch(_) => St::data_type() == StorageType::AuditEpoch,
             })
             .collect();
 
         Ok(records)
     }
 
+    fn iter_user_states(&self) -> BoxStream<'_, Result<ValueState, StorageError>> {
+        // only the labels are collected up front, and the states of each label are copied
+        // out of the map as the stream reaches it
+        let usernames = self
+            .user_info
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect::<Vec<_>>();
+        stream::iter(usernames)
+            .flat_map(move |username| {
+                let states = self
+                    .user_info
+                    .get(&username)
+                    .map(|states| states.values().cloned().collect::<Vec<_>>())
+                    .unwrap_or_default();
+                stream::iter(states.into_iter().map(Ok))
+            })
+            .boxed()
+    }
+
     async fn batch_get_all_direct(&self) -> Result<Vec<DbRecord>, StorageError> {
         // get value states
         let u_records = self
@@ -266,4 +337,26 @@ impl StorageUtil for AsyncInMemoryDatabase {
 
         Ok(records)
     }
+    async fn batch_delete_direct<St: Storable>(
+        &self,
+        ids: &[St::StorageKey],
+    ) -> Result<(), StorageError> {
+        for id in ids.iter() {
+            let bin_id = St::get_full_binary_key_id(id);
+            if St::data_type() == StorageType::ValueState {
+                if let Ok(ValueStateKey(username, epoch)) =
+                    ValueState::key_from_full_binary(&bin_id)
+                {
+                    if let Some(mut states) = self.user_info.get_mut(&username) {
+                        states.remove(&epoch);
+                    }
+                    self.user_info
+                        .remove_if(&username, |_, states| states.is_empty());
+                    continue;
+                }
+            }
+            self.db.remove(&bin_id);
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,249 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A [Database] which mirrors all writes to a second "shadow" database, allowing a
+//! directory to be migrated to a new storage system without downtime.
+//!
+//! All writes are applied to the primary database and then to the shadow, while
+//! reads are served by the primary. Once the shadow has been backfilled with the
+//! records written before mirroring began, read verification can be enabled to
+//! compare every read against the shadow, and the shadow can be promoted to the
+//! primary when no mismatches are observed.
+
+use crate::errors::StorageError;
+use crate::storage::types::{DbRecord, KeyData, ValueState, ValueStateRetrievalFlag};
+use crate::storage::{Database, DbSetState, Storable, StorageUtil};
+use crate::{AkdLabel, AkdValue};
+
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tracing::warn;
+
+/// A [Database] which writes to both a primary and a shadow database, and reads from the primary
+#[derive(Clone, Debug)]
+pub struct MirroredDatabase<P: Database, S: Database> {
+    primary: P,
+    shadow: S,
+    verify_reads: bool,
+    read_mismatches: Arc<AtomicU64>,
+}
+
+impl<P: Database, S: Database> MirroredDatabase<P, S> {
+    /// Creates a new mirrored database, without read verification
+    pub fn new(primary: P, shadow: S) -> Self {
+        Self {
+            primary,
+            shadow,
+            verify_reads: false,
+            read_mismatches: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Enables or disables read verification. When enabled, every read is also
+    /// served by the shadow, and any difference from the result of the primary is
+    /// logged and counted in [MirroredDatabase::read_mismatches]. The result of
+    /// the primary is always the one returned.
+    pub fn with_read_verification(mut self, verify_reads: bool) -> Self {
+        self.verify_reads = verify_reads;
+        self
+    }
+
+    /// The primary database, which serves all reads
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
+    /// The shadow database, which receives a copy of all writes
+    pub fn shadow(&self) -> &S {
+        &self.shadow
+    }
+
+    /// The number of reads for which the shadow returned a different result than
+    /// the primary, since the database was created
+    pub fn read_mismatches(&self) -> u64 {
+        self.read_mismatches.load(Ordering::Relaxed)
+    }
+
+    /// Compares the result of a read from the primary to the result of the same read from
+    /// the shadow. Records which are missing from both databases are considered a match.
+    fn check_read<T: PartialEq>(
+        &self,
+        operation: &str,
+        primary: &Result<T, StorageError>,
+        shadow: Result<T, StorageError>,
+    ) {
+        let matched = match (primary, &shadow) {
+            (Ok(primary), Ok(shadow)) => primary == shadow,
+            (Err(StorageError::NotFound(_)), Err(StorageError::NotFound(_))) => true,
+            _ => false,
+        };
+        if !matched {
+            self.read_mismatches.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                operation,
+                primary = describe(primary),
+                shadow = describe(&shadow),
+                "Mirrored read mismatch"
+            );
+        }
+    }
+}
+
+fn describe<T>(result: &Result<T, StorageError>) -> String {
+    match result {
+        Ok(_) => "succeeded".to_string(),
+        Err(err) => format!("failed with {err}"),
+    }
+}
+
+#[async_trait]
+impl<P: Database, S: Database> Database for MirroredDatabase<P, S> {
+    async fn set(&self, record: DbRecord) -> Result<(), StorageError> {
+        self.primary.set(record.clone()).await?;
+        self.shadow.set(record).await
+    }
+
+    async fn batch_set(
+        &self,
+        records: Vec<DbRecord>,
+        state: DbSetState,
+    ) -> Result<(), StorageError> {
+        self.primary.batch_set(records.clone(), state).await?;
+        self.shadow.batch_set(records, state).await
+    }
+
+    async fn set_if_version(
+        &self,
+        record: DbRecord,
+        expected_version: Option<u64>,
+    ) -> Result<(), StorageError> {
+        // the primary decides whether the write succeeds, and the shadow follows it
+        self.primary
+            .set_if_version(record.clone(), expected_version)
+            .await?;
+        self.shadow.set(record).await
+    }
+
+    async fn batch_set_if_versions(
+        &self,
+        records: Vec<DbRecord>,
+        conditional: Vec<(DbRecord, Option<u64>)>,
+        state: DbSetState,
+    ) -> Result<(), StorageError> {
+        // the primary decides whether the writes succeed, and the shadow follows it
+        let mut shadow_records = records.clone();
+        shadow_records.extend(conditional.iter().map(|(record, _)| record.clone()));
+        self.primary
+            .batch_set_if_versions(records, conditional, state)
+            .await?;
+        self.shadow.batch_set(shadow_records, state).await
+    }
+
+    async fn get<St: Storable>(&self, id: &St::StorageKey) -> Result<DbRecord, StorageError> {
+        let result = self.primary.get::<St>(id).await;
+        if self.verify_reads {
+            self.check_read("get", &result, self.shadow.get::<St>(id).await);
+        }
+        result
+    }
+
+    async fn batch_get<St: Storable>(
+        &self,
+        ids: &[St::StorageKey],
+    ) -> Result<Vec<DbRecord>, StorageError> {
+        let mut result = self.primary.batch_get::<St>(ids).await;
+        if self.verify_reads {
+            // The order of the records returned by a batch get is unspecified, so
+            // both results are sorted before they are compared
+            let mut shadow = self.shadow.batch_get::<St>(ids).await;
+            if let Ok(records) = &mut result {
+                records.sort();
+            }
+            if let Ok(records) = &mut shadow {
+                records.sort();
+            }
+            self.check_read("batch_get", &result, shadow);
+        }
+        result
+    }
+
+    async fn get_user_data(&self, username: &AkdLabel) -> Result<KeyData, StorageError> {
+        let result = self.primary.get_user_data(username).await;
+        if self.verify_reads {
+            self.check_read(
+                "get_user_data",
+                &result,
+                self.shadow.get_user_data(username).await,
+            );
+        }
+        result
+    }
+
+    async fn get_user_state(
+        &self,
+        username: &AkdLabel,
+        flag: ValueStateRetrievalFlag,
+    ) -> Result<ValueState, StorageError> {
+        let result = self.primary.get_user_state(username, flag).await;
+        if self.verify_reads {
+            self.check_read(
+                "get_user_state",
+                &result,
+                self.shadow.get_user_state(username, flag).await,
+            );
+        }
+        result
+    }
+
+    async fn get_user_state_versions(
+        &self,
+        usernames: &[AkdLabel],
+        flag: ValueStateRetrievalFlag,
+    ) -> Result<HashMap<AkdLabel, (u64, AkdValue)>, StorageError> {
+        let result = self.primary.get_user_state_versions(usernames, flag).await;
+        if self.verify_reads {
+            self.check_read(
+                "get_user_state_versions",
+                &result,
+                self.shadow.get_user_state_versions(usernames, flag).await,
+            );
+        }
+        result
+    }
+
+    async fn close(&self) -> Result<(), StorageError> {
+        let primary = self.primary.close().await;
+        let shadow = self.shadow.close().await;
+        primary.and(shadow)
+    }
+}
+
+#[async_trait]
+impl<P: StorageUtil, S: StorageUtil> StorageUtil for MirroredDatabase<P, S> {
+    async fn batch_get_type_direct<St: Storable>(&self) -> Result<Vec<DbRecord>, StorageError> {
+        self.primary.batch_get_type_direct::<St>().await
+    }
+
+    async fn batch_get_all_direct(&self) -> Result<Vec<DbRecord>, StorageError> {
+        self.primary.batch_get_all_direct().await
+    }
+
+    fn iter_user_states(&self) -> BoxStream<'_, Result<ValueState, StorageError>> {
+        self.primary.iter_user_states()
+    }
+
+    async fn batch_delete_direct<St: Storable>(
+        &self,
+        ids: &[St::StorageKey],
+    ) -> Result<(), StorageError> {
+        self.primary.batch_delete_direct::<St>(ids).await?;
+        self.shadow.batch_delete_direct::<St>(ids).await
+    }
+}
@@ -0,0 +1,332 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A [Database] with programmable performance characteristics, for testing how a directory
+//! behaves on slow or unreliable storage without a real database.
+//!
+//! Each operation on a [MockDatabase] is delayed by a latency which depends on its kind and the
+//! number of records it touches, plus a random jitter, and fails at a configurable rate. The
+//! number of operations in progress at once can be capped, to model a database with a limited
+//! capacity, in which case the excess operations wait for a slot. The randomness is seeded, so a
+//! sequence of operations sees the same latencies and failures in every run, and with tokio's
+//! paused clock (`#[tokio::test(start_paused = true)]`) the delays themselves take no wall-clock
+//! time, so tests can assert on the simulated durations of publishes and lookups.
+
+use crate::errors::StorageError;
+use crate::storage::memory::AsyncInMemoryDatabase;
+use crate::storage::types::{DbRecord, KeyData, ValueState, ValueStateRetrievalFlag};
+use crate::storage::{Database, DbSetState, Storable, StorageUtil};
+use crate::{AkdLabel, AkdValue};
+
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+/// The performance characteristics of a [MockDatabase]
+#[derive(Clone, Debug)]
+pub struct MockDatabaseOptions {
+    /// The latency of every read
+    pub read_latency: Duration,
+    /// The latency of every write
+    pub write_latency: Duration,
+    /// The additional latency of each record read or written by a batch operation
+    pub per_record_latency: Duration,
+    /// The maximum random latency added to each operation (uniformly distributed)
+    pub jitter: Duration,
+    /// The probability that a read fails with a [StorageError::Connection]
+    pub read_failure_rate: f64,
+    /// The probability that a write fails with a [StorageError::Connection], in which case
+    /// nothing is written
+    pub write_failure_rate: f64,
+    /// The maximum number of operations in progress at once, if limited
+    pub max_concurrent_operations: Option<usize>,
+    /// The seed of the jitter and of the failures
+    pub seed: u64,
+}
+
+impl Default for MockDatabaseOptions {
+    fn default() -> Self {
+        Self {
+            read_latency: Duration::ZERO,
+            write_latency: Duration::ZERO,
+            per_record_latency: Duration::ZERO,
+            jitter: Duration::ZERO,
+            read_failure_rate: 0.0,
+            write_failure_rate: 0.0,
+            max_concurrent_operations: None,
+            seed: 0,
+        }
+    }
+}
+
+/// The operations served by a [MockDatabase] since it was created
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MockDatabaseStats {
+    /// The number of read operations (including failed ones)
+    pub reads: u64,
+    /// The number of write operations (including failed ones)
+    pub writes: u64,
+    /// The number of operations which were failed on purpose
+    pub injected_failures: u64,
+    /// The total latency added to the operations
+    pub total_latency: Duration,
+}
+
+#[derive(Debug)]
+struct MockState {
+    options: MockDatabaseOptions,
+    rng: Mutex<StdRng>,
+    capacity: Option<Semaphore>,
+    reads: AtomicU64,
+    writes: AtomicU64,
+    injected_failures: AtomicU64,
+    total_latency_nanos: AtomicU64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OperationKind {
+    Read,
+    Write,
+}
+
+/// A [Database] which delays and fails the operations on an underlying database (by default,
+/// an [AsyncInMemoryDatabase]) according to its [MockDatabaseOptions]
+#[derive(Clone, Debug)]
+pub struct MockDatabase<Db: Database = AsyncInMemoryDatabase> {
+    db: Db,
+    state: Arc<MockState>,
+}
+
+impl MockDatabase {
+    /// Creates a new mock database, backed by an empty in-memory database
+    pub fn new(options: MockDatabaseOptions) -> Self {
+        Self::wrap(AsyncInMemoryDatabase::new(), options)
+    }
+}
+
+impl<Db: Database> MockDatabase<Db> {
+    /// Creates a new mock database, which serves its operations from the given database
+    pub fn wrap(db: Db, options: MockDatabaseOptions) -> Self {
+        Self {
+            db,
+            state: Arc::new(MockState {
+                rng: Mutex::new(StdRng::seed_from_u64(options.seed)),
+                capacity: options.max_concurrent_operations.map(Semaphore::new),
+                options,
+                reads: AtomicU64::new(0),
+                writes: AtomicU64::new(0),
+                injected_failures: AtomicU64::new(0),
+                total_latency_nanos: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// The database which serves the operations
+    pub fn inner(&self) -> &Db {
+        &self.db
+    }
+
+    /// The operations served since the database was created
+    pub fn stats(&self) -> MockDatabaseStats {
+        MockDatabaseStats {
+            reads: self.state.reads.load(Ordering::Relaxed),
+            writes: self.state.writes.load(Ordering::Relaxed),
+            injected_failures: self.state.injected_failures.load(Ordering::Relaxed),
+            total_latency: Duration::from_nanos(
+                self.state.total_latency_nanos.load(Ordering::Relaxed),
+            ),
+        }
+    }
+
+    /// Runs an operation touching `num_records` records once it has a slot and has been
+    /// delayed, unless it is chosen to fail
+    async fn run<T>(
+        &self,
+        kind: OperationKind,
+        num_records: usize,
+        operation: impl std::future::Future<Output = Result<T, StorageError>>,
+    ) -> Result<T, StorageError> {
+        let options = &self.state.options;
+        let _permit = match &self.state.capacity {
+            Some(capacity) => Some(capacity.acquire().await.map_err(|err| {
+                StorageError::Connection(format!("Mock database is closed: {err}"))
+            })?),
+            None => None,
+        };
+
+        let (base_latency, failure_rate, counter) = match kind {
+            OperationKind::Read => (
+                options.read_latency,
+                options.read_failure_rate,
+                &self.state.reads,
+            ),
+            OperationKind::Write => (
+                options.write_latency,
+                options.write_failure_rate,
+                &self.state.writes,
+            ),
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        let (jitter, fail) = {
+            let mut rng = match self.state.rng.lock() {
+                Ok(rng) => rng,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            let jitter = if options.jitter.is_zero() {
+                Duration::ZERO
+            } else {
+                options.jitter.mul_f64(rng.gen::<f64>())
+            };
+            (
+                jitter,
+                failure_rate > 0.0 && rng.gen_bool(failure_rate.min(1.0)),
+            )
+        };
+        let latency = base_latency + options.per_record_latency * num_records as u32 + jitter;
+        self.state
+            .total_latency_nanos
+            .fetch_add(latency.as_nanos() as u64, Ordering::Relaxed);
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+
+        if fail {
+            self.state.injected_failures.fetch_add(1, Ordering::Relaxed);
+            let kind = match kind {
+                OperationKind::Read => "read",
+                OperationKind::Write => "write",
+            };
+            return Err(StorageError::Connection(format!(
+                "Injected failure of a mock database {kind}"
+            )));
+        }
+        operation.await
+    }
+}
+
+#[async_trait]
+impl<Db: Database> Database for MockDatabase<Db> {
+    async fn set(&self, record: DbRecord) -> Result<(), StorageError> {
+        self.run(OperationKind::Write, 1, self.db.set(record)).await
+    }
+
+    async fn batch_set(
+        &self,
+        records: Vec<DbRecord>,
+        state: DbSetState,
+    ) -> Result<(), StorageError> {
+        let num_records = records.len();
+        self.run(
+            OperationKind::Write,
+            num_records,
+            self.db.batch_set(records, state),
+        )
+        .await
+    }
+
+    async fn set_if_version(
+        &self,
+        record: DbRecord,
+        expected_version: Option<u64>,
+    ) -> Result<(), StorageError> {
+        self.run(
+            OperationKind::Write,
+            1,
+            self.db.set_if_version(record, expected_version),
+        )
+        .await
+    }
+
+    async fn batch_set_if_versions(
+        &self,
+        records: Vec<DbRecord>,
+        conditional: Vec<(DbRecord, Option<u64>)>,
+        state: DbSetState,
+    ) -> Result<(), StorageError> {
+        let num_records = records.len() + conditional.len();
+        self.run(
+            OperationKind::Write,
+            num_records,
+            self.db.batch_set_if_versions(records, conditional, state),
+        )
+        .await
+    }
+
+    async fn get<St: Storable>(&self, id: &St::StorageKey) -> Result<DbRecord, StorageError> {
+        self.run(OperationKind::Read, 1, self.db.get::<St>(id))
+            .await
+    }
+
+    async fn batch_get<St: Storable>(
+        &self,
+        ids: &[St::StorageKey],
+    ) -> Result<Vec<DbRecord>, StorageError> {
+        self.run(OperationKind::Read, ids.len(), self.db.batch_get::<St>(ids))
+            .await
+    }
+
+    async fn get_user_data(&self, username: &AkdLabel) -> Result<KeyData, StorageError> {
+        self.run(OperationKind::Read, 1, self.db.get_user_data(username))
+            .await
+    }
+
+    async fn get_user_state(
+        &self,
+        username: &AkdLabel,
+        flag: ValueStateRetrievalFlag,
+    ) -> Result<ValueState, StorageError> {
+        self.run(
+            OperationKind::Read,
+            1,
+            self.db.get_user_state(username, flag),
+        )
+        .await
+    }
+
+    async fn get_user_state_versions(
+        &self,
+        usernames: &[AkdLabel],
+        flag: ValueStateRetrievalFlag,
+    ) -> Result<HashMap<AkdLabel, (u64, AkdValue)>, StorageError> {
+        self.run(
+            OperationKind::Read,
+            usernames.len(),
+            self.db.get_user_state_versions(usernames, flag),
+        )
+        .await
+    }
+}
+
+/// The utility functions are served by the underlying database directly, without delays or
+/// failures, as they are used to inspect and repair the database rather than in serving
+#[async_trait]
+impl<Db: StorageUtil> StorageUtil for MockDatabase<Db> {
+    async fn batch_get_type_direct<St: Storable>(&self) -> Result<Vec<DbRecord>, StorageError> {
+        self.db.batch_get_type_direct::<St>().await
+    }
+
+    async fn batch_get_all_direct(&self) -> Result<Vec<DbRecord>, StorageError> {
+        self.db.batch_get_all_direct().await
+    }
+
+    fn iter_user_states(&self) -> BoxStream<'_, Result<ValueState, StorageError>> {
+        self.db.iter_user_states()
+    }
+
+    async fn batch_delete_direct<St: Storable>(
+        &self,
+        ids: &[St::StorageKey],
+    ) -> Result<(), StorageError> {
+        self.db.batch_delete_direct::<St>(ids).await
+    }
+}
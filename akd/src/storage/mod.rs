@@ -8,10 +8,15 @@
 //! Storage module for a auditable key directory
 
 use crate::errors::StorageError;
-use crate::storage::types::{DbRecord, StorageType};
-use crate::{AkdLabel, AkdValue};
+use crate::storage::types::{
+    AnchorReceipt, AuditEpoch, DbRecord, OperationRecord, PublishLease, SoftDeletion, StorageType,
+    ValueState,
+};
+use crate::tree_node::TreeNodeWithPreviousValue;
+use crate::{AkdLabel, AkdValue, Azks};
 
 use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
 #[cfg(feature = "serde_serialization")]
 use serde::{de::DeserializeOwned, Serialize};
 use std::collections::HashMap;
@@ -19,21 +24,30 @@ use std::hash::Hash;
 use std::marker::{Send, Sync};
 
 pub mod cache;
+pub mod codec;
+#[cfg(feature = "dyn_traits")]
+pub mod dynamic;
+pub mod keys;
 pub mod transaction;
 pub mod types;
+pub mod wal;
 
 /*
 Various implementations supported by the library are imported here and usable at various checkpoints
 */
 pub mod manager;
 pub mod memory;
+pub mod mirrored;
+#[cfg(any(test, feature = "public_tests"))]
+pub mod mock;
 
-pub use manager::StorageManager;
+pub use manager::{StorageManager, StorageRole};
 
 #[cfg(any(test, feature = "public_tests"))]
 pub mod tests;
 
 /// Denotes the "state" when a batch_set is being called in the data layer
+#[derive(Clone, Copy, Debug)]
 pub enum DbSetState {
     /// Being called as part of a transaction commit operation
     TransactionCommit,
@@ -104,14 +118,78 @@ pub trait Database: Send + Sync {
         state: DbSetState,
     ) -> Result<(), StorageError>;
 
+    /// Set a record in the database only if the stored record with the same id has the expected
+    /// [version stamp](DbRecord::version_stamp), or if no record is stored when the expected version
+    /// is `None`. Otherwise, nothing is written and a [StorageError::Conflict] is returned.
+    ///
+    /// The default implementation reads the stored record before writing, and so is not atomic.
+    /// Databases which support conditional writes should override it with one which is.
+    async fn set_if_version(
+        &self,
+        record: DbRecord,
+        expected_version: Option<u64>,
+    ) -> Result<(), StorageError> {
+        check_version(
+            &record,
+            expected_version,
+            stored_version(self, &record).await?,
+        )?;
+        self.set(record).await
+    }
+
+    /// Set multiple records in the database as with [Database::batch_set], along with the
+    /// `conditional` records, each of which is paired with the [version stamp](DbRecord::version_stamp)
+    /// expected of the stored record with the same id (as with [Database::set_if_version]). Either
+    /// every record is written, or (if any stored version differs) none is, and a
+    /// [StorageError::Conflict] is returned. The conditional records are written after the others,
+    /// so that the head of the tree is not observed before the records of its epoch.
+    ///
+    /// The default implementation compares the stored versions before writing the records, then
+    /// writes each conditional record with [Database::set_if_version], and so is not atomic.
+    /// Databases which support transactions should override it with one which is.
+    async fn batch_set_if_versions(
+        &self,
+        records: Vec<DbRecord>,
+        conditional: Vec<(DbRecord, Option<u64>)>,
+        state: DbSetState,
+    ) -> Result<(), StorageError> {
+        for (record, expected_version) in &conditional {
+            check_version(
+                record,
+                *expected_version,
+                stored_version(self, record).await?,
+            )?;
+        }
+        self.batch_set(records, state).await?;
+        for (record, expected_version) in conditional {
+            self.set_if_version(record, expected_version).await?;
+        }
+        Ok(())
+    }
+
     /// Retrieve a stored record from the database
     async fn get<St: Storable>(&self, id: &St::StorageKey) -> Result<DbRecord, StorageError>;
 
-    /// Retrieve a batch of records by id from the database
+    /// Retrieve a batch of records by id from the database. Ids which are not found are
+    /// omitted from the result, and the order of the records returned is unspecified.
+    ///
+    /// The default implementation retrieves each record with [Database::get]. Databases
+    /// which can retrieve many records in a single operation should override it, since the
+    /// proof generation and preloading of the directory retrieve nodes in batches.
     async fn batch_get<St: Storable>(
         &self,
         ids: &[St::StorageKey],
-    ) -> Result<Vec<DbRecord>, StorageError>;
+    ) -> Result<Vec<DbRecord>, StorageError> {
+        let mut records = Vec::with_capacity(ids.len());
+        for id in ids {
+            match self.get::<St>(id).await {
+                Ok(record) => records.push(record),
+                Err(StorageError::NotFound(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(records)
+    }
 
     /* User data searching */
 
@@ -131,9 +209,56 @@ pub trait Database: Send + Sync {
         usernames: &[AkdLabel],
         flag: types::ValueStateRetrievalFlag,
     ) -> Result<HashMap<AkdLabel, (u64, AkdValue)>, StorageError>;
+
+    /// Closes the connections of the database (such as its connection pool), once the storage
+    /// manager which uses it has been shut down with [StorageManager::shutdown]. The database
+    /// may fail the operations which are made after it is closed. The default does nothing.
+    async fn close(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
 }
 
-/// Optional storage layer utility functions for debug and test purposes
+/// Retrieves the version stamp of the stored record with the same id as `record`, if there is one
+async fn stored_version<Db: Database + ?Sized>(
+    db: &Db,
+    record: &DbRecord,
+) -> Result<Option<u64>, StorageError> {
+    let stored = match record {
+        DbRecord::Azks(azks) => db.get::<Azks>(&azks.get_id()).await,
+        DbRecord::TreeNode(node) => db.get::<TreeNodeWithPreviousValue>(&node.get_id()).await,
+        DbRecord::ValueState(state) => db.get::<ValueState>(&state.get_id()).await,
+        DbRecord::OperationRecord(operation) => {
+            db.get::<OperationRecord>(&operation.get_id()).await
+        }
+        DbRecord::SoftDeletion(deletion) => db.get::<SoftDeletion>(&deletion.get_id()).await,
+        DbRecord::AnchorReceipt(receipt) => db.get::<AnchorReceipt>(&receipt.get_id()).await,
+        DbRecord::PublishLease(lease) => db.get::<PublishLease>(&lease.get_id()).await,
+        DbRecord::AuditEpoch(audit_epoch) => db.get::<AuditEpoch>(&audit_epoch.get_id()).await,
+    };
+    match stored {
+        Ok(stored) => Ok(Some(stored.version_stamp())),
+        Err(StorageError::NotFound(_)) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Checks that the version stamp of a stored record is the one expected by a conditional write
+pub(crate) fn check_version(
+    record: &DbRecord,
+    expected_version: Option<u64>,
+    stored_version: Option<u64>,
+) -> Result<(), StorageError> {
+    if expected_version == stored_version {
+        return Ok(());
+    }
+    Err(StorageError::Conflict(format!(
+        "Expected {:?} record to have version {expected_version:?}, but the stored version is {stored_version:?}",
+        record.storage_type()
+    )))
+}
+
+/// Optional storage layer utility functions for debug and test purposes, which are
+/// also required by the administrative recovery operations of a directory
 #[async_trait]
 pub trait StorageUtil: Database {
     /// Retrieves all stored records of a given type from the data layer, ignoring any caching or transaction pending
@@ -141,4 +266,36 @@ pub trait StorageUtil: Database {
 
     /// Retrieves all stored records from the data layer, ignoring any caching or transaction pending
     async fn batch_get_all_direct(&self) -> Result<Vec<DbRecord>, StorageError>;
+
+    /// Streams all stored value states from the data layer in an unspecified order, ignoring any
+    /// caching or transaction pending. States are only retrieved as the stream is polled, so a
+    /// consumer which falls behind does not cause more states to be held in memory.
+    ///
+    /// The default implementation retrieves every value state with
+    /// [StorageUtil::batch_get_type_direct] before yielding the first, and should be overridden
+    /// by databases which can page through their value states.
+    fn iter_user_states(&self) -> BoxStream<'_, Result<ValueState, StorageError>> {
+        stream::once(self.batch_get_type_direct::<ValueState>())
+            .flat_map(|result| {
+                let states = match result {
+                    Ok(records) => records
+                        .into_iter()
+                        .filter_map(|record| match record {
+                            DbRecord::ValueState(state) => Some(Ok(state)),
+                            _ => None,
+                        })
+                        .collect(),
+                    Err(err) => vec![Err(err)],
+                };
+                stream::iter(states)
+            })
+            .boxed()
+    }
+
+    /// Deletes a batch of records by id from the data layer, ignoring any caching or transaction pending.
+    /// Ids which are not found are ignored.
+    async fn batch_delete_direct<St: Storable>(
+        &self,
+        ids: &[St::StorageKey],
+    ) -> Result<(), StorageError>;
 }
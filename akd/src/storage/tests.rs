@@ -9,38 +9,24 @@
 
 use crate::errors::StorageError;
 use crate::storage::types::*;
-use crate::storage::Database;
 use crate::storage::StorageManager;
+use crate::storage::{Database, DbSetState};
 use crate::tree_node::*;
 use crate::utils::byte_arr_from_u64;
 use crate::NodeLabel;
 use crate::{AkdLabel, AkdValue};
 
+use crate::test_utils::thread_test_rng;
 use akd_core::hash::EMPTY_DIGEST;
 use akd_core::AzksValue;
 use rand::distributions::Alphanumeric;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use std::time::{Duration, Instant};
 
 type Azks = crate::append_only_zks::Azks;
 type TreeNode = crate::tree_node::TreeNode;
 type PvTreeNode = crate::tree_node::TreeNodeWithPreviousValue;
 
-// *** Tests *** //
-
-#[cfg(test)]
-mod memory_storage_tests {
-    use crate::storage::memory::AsyncInMemoryDatabase;
-    use serial_test::serial;
-
-    #[tokio::test]
-    #[serial]
-    async fn test_in_memory_db() {
-        let db = AsyncInMemoryDatabase::new();
-        crate::storage::tests::run_test_cases_for_storage_impl(db).await;
-    }
-}
-
 // *** Run the test cases for a given data-layer impl *** //
 /// Run the storage-layer test suite for a given storage implementation.
 /// This is public because it can be used by other implemented storage layers
@@ -49,6 +35,8 @@ pub async fn run_test_cases_for_storage_impl<S: Database>(db: S) -> StorageManag
     test_get_and_set_item(&db).await;
     test_user_data(&db).await;
     test_batch_get_items(&db).await;
+    test_conditional_set(&db).await;
+    test_conditional_batch_set(&db).await;
 
     let manager = StorageManager::new_no_cache(db);
     test_transactions(&manager).await;
@@ -130,6 +118,7 @@ async fn test_get_and_set_item<Ns: Database>(storage: &Ns) {
         label: NodeLabel::new(byte_arr_from_u64(1), 1),
         version: 1,
         value: AkdValue::from("abc123"),
+        retention_class: None,
     };
     let set_result = storage.set(DbRecord::ValueState(value.clone())).await;
     assert_eq!(Ok(()), set_result);
@@ -149,7 +138,7 @@ async fn test_get_and_set_item<Ns: Database>(storage: &Ns) {
 async fn test_batch_get_items<Ns: Database>(storage: &Ns) {
     let mut rand_users: Vec<Vec<u8>> = vec![];
     for _ in 0..20 {
-        let str: String = thread_rng()
+        let str: String = thread_test_rng()
             .sample_iter(&Alphanumeric)
             .take(30)
             .map(char::from)
@@ -159,8 +148,7 @@ async fn test_batch_get_items<Ns: Database>(storage: &Ns) {
 
     let mut data = Vec::new();
 
-    let mut epoch = 1;
-    for value in rand_users.iter() {
+    for (epoch, value) in (1..).zip(rand_users.iter()) {
         for user in rand_users.iter() {
             data.push(DbRecord::ValueState(ValueState {
                 value: AkdValue(value.clone()),
@@ -171,9 +159,9 @@ async fn test_batch_get_items<Ns: Database>(storage: &Ns) {
                 },
                 epoch,
                 username: AkdLabel(user.clone()),
+                retention_class: None,
             }));
         }
-        epoch += 1;
     }
 
     let tic = Instant::now();
@@ -317,7 +305,7 @@ async fn test_batch_get_items<Ns: Database>(storage: &Ns) {
 async fn test_transactions<S: Database>(storage: &StorageManager<S>) {
     let mut rand_users: Vec<Vec<u8>> = vec![];
     for _ in 0..20 {
-        let str: String = thread_rng()
+        let str: String = thread_test_rng()
             .sample_iter(&Alphanumeric)
             .take(30)
             .map(char::from)
@@ -327,8 +315,7 @@ async fn test_transactions<S: Database>(storage: &StorageManager<S>) {
 
     let mut data = Vec::new();
 
-    let mut epoch = 1;
-    for value in rand_users.iter() {
+    for (epoch, value) in (1..).zip(rand_users.iter()) {
         for user in rand_users.iter() {
             data.push(DbRecord::ValueState(ValueState {
                 value: AkdValue(value.clone()),
@@ -339,9 +326,9 @@ async fn test_transactions<S: Database>(storage: &StorageManager<S>) {
                 },
                 epoch,
                 username: AkdLabel(user.clone()),
+                retention_class: None,
             }));
         }
-        epoch += 1;
     }
 
     data.push(DbRecord::Azks(Azks {
@@ -359,8 +346,9 @@ async fn test_transactions<S: Database>(storage: &StorageManager<S>) {
                     copied_state.epoch += 10000;
                     DbRecord::ValueState(copied_state)
                 }
+                // a commit must advance the head of the tree by a single epoch
                 DbRecord::Azks(azks) => DbRecord::Azks(Azks {
-                    latest_epoch: azks.latest_epoch + 10000,
+                    latest_epoch: azks.latest_epoch + 1,
                     num_nodes: azks.num_nodes,
                 }),
                 _ => new_item,
@@ -394,15 +382,122 @@ async fn test_transactions<S: Database>(storage: &StorageManager<S>) {
     }
 }
 
+async fn test_conditional_set<S: Database>(storage: &S) {
+    let azks = |latest_epoch| {
+        DbRecord::Azks(Azks {
+            latest_epoch,
+            num_nodes: 10,
+        })
+    };
+    assert_eq!(Ok(()), storage.set(azks(40)).await);
+
+    // writes expecting a different version, or no record at all, are rejected
+    for expected_version in [Some(39), Some(41), None] {
+        assert!(matches!(
+            storage.set_if_version(azks(41), expected_version).await,
+            Err(StorageError::Conflict(_))
+        ));
+    }
+    let got = storage
+        .get::<Azks>(&crate::append_only_zks::DEFAULT_AZKS_KEY)
+        .await;
+    assert_eq!(Ok(azks(40)), got);
+
+    assert_eq!(Ok(()), storage.set_if_version(azks(41), Some(40)).await);
+    let got = storage
+        .get::<Azks>(&crate::append_only_zks::DEFAULT_AZKS_KEY)
+        .await;
+    assert_eq!(Ok(azks(41)), got);
+
+    // a record which is not stored can only be written when no version is expected
+    let state = DbRecord::ValueState(ValueState {
+        value: AkdValue::from("value"),
+        version: 1,
+        label: NodeLabel::new(byte_arr_from_u64(1), 1),
+        epoch: 41,
+        username: AkdLabel::from("conditional_set_user"),
+        retention_class: None,
+    });
+    assert!(matches!(
+        storage.set_if_version(state.clone(), Some(41)).await,
+        Err(StorageError::Conflict(_))
+    ));
+    assert_eq!(Ok(()), storage.set_if_version(state.clone(), None).await);
+    assert!(matches!(
+        storage.set_if_version(state, None).await,
+        Err(StorageError::Conflict(_))
+    ));
+}
+
+async fn test_conditional_batch_set<S: Database>(storage: &S) {
+    let azks = |latest_epoch| {
+        DbRecord::Azks(Azks {
+            latest_epoch,
+            num_nodes: 10,
+        })
+    };
+    let state = |epoch| {
+        DbRecord::ValueState(ValueState {
+            value: AkdValue::from("value"),
+            version: epoch,
+            label: NodeLabel::new(byte_arr_from_u64(epoch), 64),
+            epoch,
+            username: AkdLabel::from("conditional_batch_set_user"),
+            retention_class: None,
+        })
+    };
+    assert_eq!(Ok(()), storage.set(azks(50)).await);
+
+    // none of the records are written when a version does not match
+    assert!(matches!(
+        storage
+            .batch_set_if_versions(
+                vec![state(51)],
+                vec![(azks(51), Some(49))],
+                DbSetState::General
+            )
+            .await,
+        Err(StorageError::Conflict(_))
+    ));
+    let got = storage
+        .get::<Azks>(&crate::append_only_zks::DEFAULT_AZKS_KEY)
+        .await;
+    assert_eq!(Ok(azks(50)), got);
+    let got = storage
+        .get::<ValueState>(&ValueStateKey(b"conditional_batch_set_user".to_vec(), 51))
+        .await;
+    assert!(matches!(got, Err(StorageError::NotFound(_))));
+
+    // and all of them are written when every version matches
+    assert_eq!(
+        Ok(()),
+        storage
+            .batch_set_if_versions(
+                vec![state(51)],
+                vec![(azks(51), Some(50))],
+                DbSetState::General
+            )
+            .await
+    );
+    let got = storage
+        .get::<Azks>(&crate::append_only_zks::DEFAULT_AZKS_KEY)
+        .await;
+    assert_eq!(Ok(azks(51)), got);
+    let got = storage
+        .get::<ValueState>(&ValueStateKey(b"conditional_batch_set_user".to_vec(), 51))
+        .await;
+    assert_eq!(Ok(state(51)), got);
+}
+
 async fn test_user_data<S: Database>(storage: &S) {
-    let rand_user = thread_rng()
+    let rand_user = thread_test_rng()
         .sample_iter(&Alphanumeric)
         .take(30)
         .map(char::from)
         .collect::<String>()
         .as_bytes()
         .to_vec();
-    let rand_value = thread_rng()
+    let rand_value = thread_test_rng()
         .sample_iter(&Alphanumeric)
         .take(1028)
         .map(char::from)
@@ -418,6 +513,7 @@ async fn test_user_data<S: Database>(storage: &S) {
         },
         epoch: 1u64,
         username: AkdLabel(rand_user),
+        retention_class: None,
     };
     let mut sample_state_2 = sample_state.clone();
     sample_state_2.username = AkdLabel::from("test_user");
@@ -491,6 +587,7 @@ async fn test_user_data<S: Database>(storage: &S) {
             label: NodeLabel::new(byte_arr_from_u64(1), 1),
             value: AkdValue(rand_value.clone()),
             username: sample_state.username.clone(),
+            retention_class: None,
         }),
         specific_result
     );
@@ -506,6 +603,7 @@ async fn test_user_data<S: Database>(storage: &S) {
                 label: NodeLabel::new(byte_arr_from_u64(1), 1),
                 value: AkdValue(rand_value.clone()),
                 username: sample_state.username.clone(),
+                retention_class: None,
             },
             state
         );
@@ -534,6 +632,7 @@ async fn test_user_data<S: Database>(storage: &S) {
             label: NodeLabel::new(byte_arr_from_u64(1), 1),
             value: AkdValue(rand_value.clone()),
             username: sample_state.username.clone(),
+            retention_class: None,
         }),
         specific_result
     );
@@ -548,6 +647,7 @@ async fn test_user_data<S: Database>(storage: &S) {
             label: NodeLabel::new(byte_arr_from_u64(1), 1),
             value: AkdValue(rand_value.clone()),
             username: sample_state.username.clone(),
+            retention_class: None,
         }),
         specific_result
     );
@@ -562,6 +662,7 @@ async fn test_user_data<S: Database>(storage: &S) {
             label: NodeLabel::new(byte_arr_from_u64(1), 1),
             value: AkdValue(rand_value.clone()),
             username: sample_state.username.clone(),
+            retention_class: None,
         }),
         specific_result
     );
@@ -596,7 +697,7 @@ async fn test_user_data<S: Database>(storage: &S) {
 async fn test_tombstoning_data<S: Database>(
     storage: &StorageManager<S>,
 ) -> Result<(), crate::errors::AkdError> {
-    let rand_user = thread_rng()
+    let rand_user = thread_test_rng()
         .sample_iter(&Alphanumeric)
         .take(30)
         .map(char::from)
@@ -614,6 +715,7 @@ async fn test_tombstoning_data<S: Database>(
         },
         epoch: 1u64,
         username: AkdLabel(rand_user.clone()),
+        retention_class: None,
     };
     let mut sample_state2 = sample_state.clone();
     sample_state2.username = AkdLabel::from("tombstone_test_user");
@@ -688,3 +790,258 @@ async fn test_tombstoning_data<S: Database>(
 
     Ok(())
 }
+
+// *** Tests *** //
+
+#[cfg(test)]
+mod memory_storage_tests {
+    use crate::errors::StorageError;
+    use crate::storage::memory::AsyncInMemoryDatabase;
+    use crate::storage::mirrored::MirroredDatabase;
+    use crate::storage::mock::{MockDatabase, MockDatabaseOptions};
+    use crate::storage::types::{DbRecord, KeyData, ValueState, ValueStateRetrievalFlag};
+    use crate::storage::{Database, DbSetState, Storable, StorageUtil};
+    use crate::tree_node::{NodeKey, TreeNodeWithPreviousValue};
+    use crate::{AkdLabel, AkdValue};
+    use futures::TryStreamExt;
+    use serial_test::serial;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_in_memory_db() {
+        let db = AsyncInMemoryDatabase::new();
+        crate::storage::tests::run_test_cases_for_storage_impl(db).await;
+    }
+
+    /// A database which does not override the default implementations of the [Database] trait
+    struct DefaultMethodsDatabase(AsyncInMemoryDatabase);
+
+    #[async_trait::async_trait]
+    impl Database for DefaultMethodsDatabase {
+        async fn set(&self, record: DbRecord) -> Result<(), StorageError> {
+            self.0.set(record).await
+        }
+
+        async fn batch_set(
+            &self,
+            records: Vec<DbRecord>,
+            state: DbSetState,
+        ) -> Result<(), StorageError> {
+            self.0.batch_set(records, state).await
+        }
+
+        async fn get<St: Storable>(&self, id: &St::StorageKey) -> Result<DbRecord, StorageError> {
+            self.0.get::<St>(id).await
+        }
+
+        async fn get_user_data(&self, username: &AkdLabel) -> Result<KeyData, StorageError> {
+            self.0.get_user_data(username).await
+        }
+
+        async fn get_user_state(
+            &self,
+            username: &AkdLabel,
+            flag: ValueStateRetrievalFlag,
+        ) -> Result<ValueState, StorageError> {
+            self.0.get_user_state(username, flag).await
+        }
+
+        async fn get_user_state_versions(
+            &self,
+            usernames: &[AkdLabel],
+            flag: ValueStateRetrievalFlag,
+        ) -> Result<HashMap<AkdLabel, (u64, AkdValue)>, StorageError> {
+            self.0.get_user_state_versions(usernames, flag).await
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl StorageUtil for DefaultMethodsDatabase {
+        async fn batch_get_type_direct<St: Storable>(&self) -> Result<Vec<DbRecord>, StorageError> {
+            self.0.batch_get_type_direct::<St>().await
+        }
+
+        async fn batch_get_all_direct(&self) -> Result<Vec<DbRecord>, StorageError> {
+            self.0.batch_get_all_direct().await
+        }
+
+        async fn batch_delete_direct<St: Storable>(
+            &self,
+            ids: &[St::StorageKey],
+        ) -> Result<(), StorageError> {
+            self.0.batch_delete_direct::<St>(ids).await
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_default_methods_db() {
+        let db = DefaultMethodsDatabase(AsyncInMemoryDatabase::new());
+        crate::storage::tests::run_test_cases_for_storage_impl(db).await;
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_iter_user_states() {
+        let manager =
+            crate::storage::tests::run_test_cases_for_storage_impl(AsyncInMemoryDatabase::new())
+                .await;
+        let db = manager.get_db();
+        let mut expected = db
+            .batch_get_type_direct::<ValueState>()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter_map(|record| match record {
+                DbRecord::ValueState(state) => Some(state),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        expected.sort_by(|a, b| (&a.username, a.epoch).cmp(&(&b.username, b.epoch)));
+        assert!(!expected.is_empty());
+
+        // both the in-memory database and the default implementation stream every value state
+        let default_db = DefaultMethodsDatabase(db.as_ref().clone());
+        for stream in [db.iter_user_states(), default_db.iter_user_states()] {
+            let mut streamed = stream.try_collect::<Vec<_>>().await.unwrap();
+            streamed.sort_by(|a, b| (&a.username, a.epoch).cmp(&(&b.username, b.epoch)));
+            assert_eq!(expected, streamed);
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_mirrored_db() {
+        let db = MirroredDatabase::new(AsyncInMemoryDatabase::new(), AsyncInMemoryDatabase::new())
+            .with_read_verification(true);
+        let manager = crate::storage::tests::run_test_cases_for_storage_impl(db).await;
+        let db = manager.get_db();
+
+        // every write was mirrored, so no reads differed and both copies are identical
+        assert_eq!(0, db.read_mismatches());
+        let mut primary = db.primary().batch_get_all_direct().await.unwrap();
+        let mut shadow = db.shadow().batch_get_all_direct().await.unwrap();
+        primary.sort();
+        shadow.sort();
+        assert_eq!(primary, shadow);
+
+        // a record written only to the primary is still served, but counted as a mismatch
+        let record = primary
+            .iter()
+            .find_map(|record| match record {
+                DbRecord::TreeNode(node) => Some(node.clone()),
+                _ => None,
+            })
+            .unwrap();
+        let mut changed = record.clone();
+        changed.latest_node.last_epoch += 1;
+        let changed = DbRecord::TreeNode(changed);
+        db.primary()
+            .batch_set(vec![changed.clone()], DbSetState::General)
+            .await
+            .unwrap();
+        let got = db
+            .get::<TreeNodeWithPreviousValue>(&NodeKey(record.label))
+            .await
+            .unwrap();
+        assert_eq!(changed, got);
+        assert_eq!(1, db.read_mismatches());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_mock_db() {
+        let db = MockDatabase::new(MockDatabaseOptions::default());
+        let manager = crate::storage::tests::run_test_cases_for_storage_impl(db).await;
+        let stats = manager.get_db().stats();
+        assert!(stats.reads > 0 && stats.writes > 0);
+        assert_eq!(0, stats.injected_failures);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_mock_db_latency() {
+        let options = MockDatabaseOptions {
+            read_latency: Duration::from_millis(5),
+            write_latency: Duration::from_millis(20),
+            per_record_latency: Duration::from_millis(1),
+            jitter: Duration::from_millis(3),
+            max_concurrent_operations: Some(1),
+            seed: 42,
+            ..Default::default()
+        };
+        let records = |db: MockDatabase| async move {
+            let records = (0..10)
+                .map(|i| {
+                    DbRecord::ValueState(ValueState {
+                        value: AkdValue::from("value"),
+                        version: 1,
+                        label: crate::NodeLabel::new([i; 32], 256),
+                        epoch: 1,
+                        username: AkdLabel(vec![i]),
+                        retention_class: None,
+                    })
+                })
+                .collect::<Vec<_>>();
+            let start = tokio::time::Instant::now();
+            // the writes queue behind each other for the only slot
+            let (first, second) = futures::join!(
+                db.batch_set(records[..5].to_vec(), DbSetState::General),
+                db.batch_set(records[5..].to_vec(), DbSetState::General)
+            );
+            first.unwrap();
+            second.unwrap();
+            db.get_user_data(&AkdLabel(vec![0])).await.unwrap();
+            (start.elapsed(), db.stats())
+        };
+
+        let (elapsed, stats) = records(MockDatabase::new(options.clone())).await;
+        // the timer rounds each of the three sleeps up to a whole millisecond
+        assert!(stats.total_latency <= elapsed);
+        assert!(elapsed <= stats.total_latency + Duration::from_millis(3));
+        assert_eq!(2, stats.writes);
+        assert_eq!(1, stats.reads);
+        let min = Duration::from_millis(2 * 25 + 6);
+        assert!(min <= stats.total_latency && stats.total_latency <= min + 3 * options.jitter);
+
+        // the same seed gives the same latencies
+        let (_, again) = records(MockDatabase::new(options)).await;
+        assert_eq!(stats, again);
+    }
+
+    #[tokio::test]
+    async fn test_mock_db_failures() {
+        let db = MockDatabase::new(MockDatabaseOptions {
+            write_failure_rate: 1.0,
+            ..Default::default()
+        });
+        let record = DbRecord::ValueState(ValueState {
+            value: AkdValue::from("value"),
+            version: 1,
+            label: crate::NodeLabel::new([0; 32], 256),
+            epoch: 1,
+            username: AkdLabel::from("user"),
+            retention_class: None,
+        });
+        assert!(matches!(
+            db.set(record).await,
+            Err(StorageError::Connection(_))
+        ));
+        // a failed write does not reach the underlying database
+        assert!(matches!(
+            db.get_user_data(&AkdLabel::from("user")).await,
+            Err(StorageError::NotFound(_))
+        ));
+        assert_eq!(1, db.stats().injected_failures);
+
+        // a directory cannot be created on storage which rejects every write
+        let storage = crate::storage::StorageManager::new_no_cache(db);
+        let result = crate::Directory::<crate::WhatsAppV1Configuration, _, _>::new(
+            storage,
+            crate::ecvrf::HardCodedAkdVRF {},
+        )
+        .await;
+        assert!(result.is_err());
+    }
+}
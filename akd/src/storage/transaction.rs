@@ -12,10 +12,11 @@ use crate::storage::types::DbRecord;
 use crate::storage::types::ValueState;
 use crate::storage::types::ValueStateRetrievalFlag;
 use crate::storage::Storable;
+use akd_core::SizeOf;
 
-use dashmap::DashMap;
 #[cfg(feature = "runtime_metrics")]
-use log::{debug, error, info, trace, warn};
+use crate::instrumentation::dyn_event;
+use dashmap::DashMap;
 use std::collections::HashMap;
 #[cfg(feature = "runtime_metrics")]
 use std::sync::atomic::AtomicU64;
@@ -71,22 +72,19 @@ impl Transaction {
         self.mods.len()
     }
 
+    /// Get the total size of the items currently in the transaction modifications set
+    pub fn size_of(&self) -> usize {
+        self.mods.iter().map(|p| p.value().size_of()).sum()
+    }
+
     /// Log metrics about the current transaction instance. Metrics will be cleared after log call
-    pub fn log_metrics(&self, _level: log::Level) {
+    pub fn log_metrics(&self, _level: tracing::Level) {
         #[cfg(feature = "runtime_metrics")]
         {
             let r = self.num_reads.swap(0, Ordering::Relaxed);
             let w = self.num_writes.swap(0, Ordering::Relaxed);
 
-            let msg = format!("Transaction writes: {w}, Transaction reads: {r}");
-
-            match _level {
-                log::Level::Trace => trace!("{}", msg),
-                log::Level::Debug => debug!("{}", msg),
-                log::Level::Info => info!("{}", msg),
-                log::Level::Warn => warn!("{}", msg),
-                _ => error!("{}", msg),
-            }
+            dyn_event!(_level, writes = w, reads = r, "Transaction metrics");
         }
     }
 
@@ -209,7 +207,7 @@ impl Transaction {
 
         // sort all the value lists by epoch
         for (_k, v) in results.iter_mut() {
-            v.sort_unstable_by(|a, b| a.epoch.cmp(&b.epoch));
+            v.sort_unstable_by_key(|a| a.epoch);
         }
 
         results
@@ -225,7 +223,7 @@ impl Transaction {
         flag: ValueStateRetrievalFlag,
     ) -> Option<ValueState> {
         let intermediate = self
-            .get_users_data(&[username.clone()])
+            .get_users_data(std::slice::from_ref(username))
             .remove(username)
             .unwrap_or_default();
         let out = Self::find_appropriate_item(intermediate, flag);
@@ -325,6 +323,7 @@ mod tests {
             label: NodeLabel::new(byte_arr_from_u64(1), 1),
             version: 1,
             value: AkdValue::from("abc123"),
+            retention_class: None,
         });
         let value2 = DbRecord::ValueState(ValueState {
             username: AkdLabel::from("test"),
@@ -332,6 +331,7 @@ mod tests {
             label: NodeLabel::new(byte_arr_from_u64(1), 1),
             version: 2,
             value: AkdValue::from("abc1234"),
+            retention_class: None,
         });
 
         let records = vec![azks, node1, node2, value1, value2];
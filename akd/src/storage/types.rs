@@ -9,11 +9,11 @@
 
 use akd_core::AzksValue;
 
+use crate::storage::keys;
 use crate::storage::Storable;
 use crate::tree_node::{TreeNode, TreeNodeType, TreeNodeWithPreviousValue};
-use crate::{AkdLabel, AkdValue};
+use crate::{AkdLabel, AkdValue, RetentionClass};
 use crate::{Azks, NodeLabel};
-use std::convert::TryInto;
 
 /// Various elements that can be stored
 #[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
@@ -26,6 +26,16 @@ pub enum StorageType {
     /// Better to keep ValueState = 4 as is?
     /// ValueState
     ValueState = 4,
+    /// OperationRecord
+    OperationRecord = 5,
+    /// SoftDeletion
+    SoftDeletion = 6,
+    /// AnchorReceipt
+    AnchorReceipt = 7,
+    /// PublishLease
+    PublishLease = 8,
+    /// AuditEpoch
+    AuditEpoch = 9,
 }
 
 /// State for a value at a given version for that key
@@ -54,6 +64,10 @@ pub struct ValueState {
     pub epoch: u64,
     /// The username associated to this value state (username + epoch is the record key)
     pub username: AkdLabel,
+    /// The retention class assigned to the label at this value state, or [None] if the label
+    /// keeps the class assigned at an earlier value state (see `Directory::set_retention_class`)
+    #[cfg_attr(feature = "serde_serialization", serde(default))]
+    pub retention_class: Option<RetentionClass>,
 }
 
 impl akd_core::SizeOf for ValueState {
@@ -63,6 +77,7 @@ impl akd_core::SizeOf for ValueState {
             + self.label.size_of()
             + std::mem::size_of::<u64>()
             + self.username.size_of()
+            + std::mem::size_of::<Option<RetentionClass>>()
     }
 }
 
@@ -78,25 +93,11 @@ impl crate::storage::Storable for ValueState {
     }
 
     fn get_full_binary_key_id(key: &ValueStateKey) -> Vec<u8> {
-        let mut result = vec![StorageType::ValueState as u8];
-        result.extend_from_slice(&key.1.to_be_bytes());
-        result.extend_from_slice(&key.0);
-
-        result
+        keys::encode_value_state_key(key)
     }
 
     fn key_from_full_binary(bin: &[u8]) -> Result<ValueStateKey, String> {
-        if bin.len() < 10 {
-            return Err("Not enough bytes to form a proper key".to_string());
-        }
-
-        if bin[0] != StorageType::ValueState as u8 {
-            return Err("Not a value state key".to_string());
-        }
-
-        let epoch_bytes: [u8; 8] = bin[1..=8].try_into().expect("Slice with incorrect length");
-        let epoch = u64::from_be_bytes(epoch_bytes);
-        Ok(ValueStateKey(bin[9..].to_vec(), epoch))
+        keys::decode_value_state_key(bin)
     }
 }
 
@@ -114,10 +115,304 @@ impl ValueState {
             label,
             epoch,
             username,
+            retention_class: None,
         }
     }
 }
 
+/// The kind of a mutating operation which is recorded in the operations log of a directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub enum OperationKind {
+    /// A publish of a new epoch
+    Publish = 1,
+    /// A prune of the values published before a cutoff epoch
+    Prune = 2,
+    /// A rollback of the directory to an earlier epoch
+    Rollback = 3,
+    /// A rebuild of the tree from the stored values
+    Rebuild = 4,
+    /// A redaction of a value
+    Redaction = 5,
+    /// An assignment of a retention class to a label
+    RetentionChange = 6,
+}
+
+impl OperationKind {
+    /// Parses an operation kind from its byte representation
+    pub fn from_u8(kind: u8) -> Result<Self, String> {
+        match kind {
+            1 => Ok(Self::Publish),
+            2 => Ok(Self::Prune),
+            3 => Ok(Self::Rollback),
+            4 => Ok(Self::Rebuild),
+            5 => Ok(Self::Redaction),
+            6 => Ok(Self::RetentionChange),
+            _ => Err(format!("Unknown operation kind {kind}")),
+        }
+    }
+}
+
+/// An entry in the append-only operations log of a directory, which records who performed
+/// a mutating operation on the directory, and when
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct OperationRecord {
+    /// The position of the record in the operations log, starting from 0
+    pub sequence: u64,
+    /// The kind of operation
+    pub kind: OperationKind,
+    /// The epoch of the directory after the operation
+    pub epoch: u64,
+    /// The time at which the operation completed, in milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    /// The identity of the operator who performed the operation
+    pub identity: String,
+    /// Operator-supplied metadata, such as a change ticket or a reason for the operation
+    pub metadata: String,
+    /// A human-readable description of the effects of the operation
+    pub detail: String,
+}
+
+impl akd_core::SizeOf for OperationRecord {
+    fn size_of(&self) -> usize {
+        std::mem::size_of::<u64>() * 3
+            + std::mem::size_of::<OperationKind>()
+            + self.identity.len()
+            + self.metadata.len()
+            + self.detail.len()
+    }
+}
+
+impl crate::storage::Storable for OperationRecord {
+    type StorageKey = u64;
+
+    fn data_type() -> StorageType {
+        StorageType::OperationRecord
+    }
+
+    fn get_id(&self) -> u64 {
+        self.sequence
+    }
+
+    fn get_full_binary_key_id(key: &u64) -> Vec<u8> {
+        keys::encode_integer_key(StorageType::OperationRecord, *key)
+    }
+
+    fn key_from_full_binary(bin: &[u8]) -> Result<u64, String> {
+        keys::decode_integer_key(bin, StorageType::OperationRecord)
+    }
+}
+
+/// A soft deletion of a label, which flags the label's value states for erasure without
+/// removing or altering them, so that lookups and proofs are unaffected until the deletion is
+/// purged. A soft deletion can be reversed by
+/// [StorageManager::restore_label](crate::storage::StorageManager::restore_label) until its
+/// retention window has passed, after which
+/// [StorageManager::purge_soft_deletions](crate::storage::StorageManager::purge_soft_deletions)
+/// tombstones the flagged value states.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct SoftDeletion {
+    /// The label whose value states are soft-deleted
+    pub username: AkdLabel,
+    /// The latest epoch of the directory when the label was soft-deleted. The value states
+    /// published up to and including this epoch are soft-deleted, and the retention window
+    /// is counted from this epoch.
+    pub epoch: u64,
+}
+
+impl akd_core::SizeOf for SoftDeletion {
+    fn size_of(&self) -> usize {
+        self.username.size_of() + std::mem::size_of::<u64>()
+    }
+}
+
+impl crate::storage::Storable for SoftDeletion {
+    type StorageKey = AkdLabel;
+
+    fn data_type() -> StorageType {
+        StorageType::SoftDeletion
+    }
+
+    fn get_id(&self) -> AkdLabel {
+        self.username.clone()
+    }
+
+    fn get_full_binary_key_id(key: &AkdLabel) -> Vec<u8> {
+        keys::encode_label_key(StorageType::SoftDeletion, key)
+    }
+
+    fn key_from_full_binary(bin: &[u8]) -> Result<AkdLabel, String> {
+        keys::decode_label_key(bin, StorageType::SoftDeletion)
+    }
+}
+
+/// The key of an [AnchorReceipt]: the anchored epoch, and the name of the anchor
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct AnchorReceiptKey(pub u64, pub String);
+
+/// The receipt with which an external system attests to the root hash of an epoch, as
+/// produced by an [Anchor](crate::anchoring::Anchor). See [crate::anchoring].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct AnchorReceipt {
+    /// The anchored epoch
+    pub epoch: u64,
+    /// The name of the anchor which produced the receipt
+    pub anchor: String,
+    /// The root hash of the anchored epoch
+    pub root_hash: crate::Digest,
+    /// The time at which the receipt was stored, in milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    /// The receipt, in the format of the anchor
+    pub receipt: Vec<u8>,
+}
+
+impl akd_core::SizeOf for AnchorReceipt {
+    fn size_of(&self) -> usize {
+        std::mem::size_of::<u64>() * 2
+            + self.anchor.len()
+            + self.root_hash.len()
+            + self.receipt.len()
+    }
+}
+
+impl crate::storage::Storable for AnchorReceipt {
+    type StorageKey = AnchorReceiptKey;
+
+    fn data_type() -> StorageType {
+        StorageType::AnchorReceipt
+    }
+
+    fn get_id(&self) -> AnchorReceiptKey {
+        AnchorReceiptKey(self.epoch, self.anchor.clone())
+    }
+
+    fn get_full_binary_key_id(key: &AnchorReceiptKey) -> Vec<u8> {
+        keys::encode_anchor_receipt_key(key)
+    }
+
+    fn key_from_full_binary(bin: &[u8]) -> Result<AnchorReceiptKey, String> {
+        keys::decode_anchor_receipt_key(bin)
+    }
+}
+
+/// The key of the [PublishLease] of a directory, of which there is only one
+pub const DEFAULT_PUBLISH_LEASE_KEY: u8 = 1u8;
+
+/// A lease on publishing to a directory's storage, which at most one publisher holds at a
+/// time (see [StorageManager::with_publish_lease](crate::storage::StorageManager::with_publish_lease)).
+/// The lease is written with [Database::set_if_version](crate::storage::Database::set_if_version),
+/// and its fencing token is its version stamp, so a publisher whose lease was taken over fails
+/// to renew it instead of overwriting the new holder's lease.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct PublishLease {
+    /// The identity of the publisher holding the lease
+    pub holder: String,
+    /// The fencing token of the lease, which is incremented each time the lease is acquired
+    pub token: u64,
+    /// The time at which the lease expires unless it is renewed, in milliseconds since the
+    /// Unix epoch. A released lease expires at 0.
+    pub expires_at_ms: u64,
+}
+
+impl akd_core::SizeOf for PublishLease {
+    fn size_of(&self) -> usize {
+        std::mem::size_of::<u64>() * 2 + self.holder.len()
+    }
+}
+
+impl crate::storage::Storable for PublishLease {
+    type StorageKey = u8;
+
+    fn data_type() -> StorageType {
+        StorageType::PublishLease
+    }
+
+    fn get_id(&self) -> u8 {
+        DEFAULT_PUBLISH_LEASE_KEY
+    }
+
+    fn get_full_binary_key_id(key: &u8) -> Vec<u8> {
+        keys::encode_integer_key(StorageType::PublishLease, *key as u64)
+    }
+
+    fn key_from_full_binary(bin: &[u8]) -> Result<u8, String> {
+        let key = keys::decode_integer_key(bin, StorageType::PublishLease)?;
+        u8::try_from(key).map_err(|_| format!("PublishLease key {key} out of range"))
+    }
+}
+
+/// A canonical audit epoch, which consolidates the mini-epochs published since the previous
+/// audit epoch (see [crate::consolidation]). Audit epochs are numbered from 1, and each one
+/// covers the epochs after the end of the previous one, up to and including its own end.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct AuditEpoch {
+    /// The number of the audit epoch, starting from 1
+    pub index: u64,
+    /// The last epoch of the previous audit epoch, or 0 for the first audit epoch
+    pub start_epoch: u64,
+    /// The root hash of the directory at the start epoch
+    pub start_root_hash: crate::Digest,
+    /// The last epoch covered by the audit epoch
+    pub end_epoch: u64,
+    /// The root hash of the directory at the end epoch
+    pub end_root_hash: crate::Digest,
+    /// The time at which the audit epoch was consolidated, in milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+}
+
+impl akd_core::SizeOf for AuditEpoch {
+    fn size_of(&self) -> usize {
+        std::mem::size_of::<u64>() * 4 + self.start_root_hash.len() + self.end_root_hash.len()
+    }
+}
+
+impl crate::storage::Storable for AuditEpoch {
+    type StorageKey = u64;
+
+    fn data_type() -> StorageType {
+        StorageType::AuditEpoch
+    }
+
+    fn get_id(&self) -> u64 {
+        self.index
+    }
+
+    fn get_full_binary_key_id(key: &u64) -> Vec<u8> {
+        keys::encode_integer_key(StorageType::AuditEpoch, *key)
+    }
+
+    fn key_from_full_binary(bin: &[u8]) -> Result<u64, String> {
+        keys::decode_integer_key(bin, StorageType::AuditEpoch)
+    }
+}
+
 /// Data associated with a given key. That is all the states at the various epochs
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(
@@ -162,6 +457,16 @@ pub enum DbRecord {
     TreeNode(TreeNodeWithPreviousValue),
     /// The state of the value for a particular key.
     ValueState(ValueState),
+    /// An entry in the operations log
+    OperationRecord(OperationRecord),
+    /// A soft deletion of a label
+    SoftDeletion(SoftDeletion),
+    /// A receipt of the anchoring of an epoch's root hash
+    AnchorReceipt(AnchorReceipt),
+    /// The lease on publishing to the directory
+    PublishLease(PublishLease),
+    /// A canonical audit epoch
+    AuditEpoch(AuditEpoch),
 }
 
 impl akd_core::SizeOf for DbRecord {
@@ -170,6 +475,11 @@ impl akd_core::SizeOf for DbRecord {
             DbRecord::Azks(azks) => azks.size_of(),
             DbRecord::TreeNode(node) => node.size_of(),
             DbRecord::ValueState(state) => state.size_of(),
+            DbRecord::OperationRecord(record) => record.size_of(),
+            DbRecord::SoftDeletion(deletion) => deletion.size_of(),
+            DbRecord::AnchorReceipt(receipt) => receipt.size_of(),
+            DbRecord::PublishLease(lease) => lease.size_of(),
+            DbRecord::AuditEpoch(audit_epoch) => audit_epoch.size_of(),
         }
     }
 }
@@ -180,11 +490,34 @@ impl Clone for DbRecord {
             DbRecord::Azks(azks) => DbRecord::Azks(azks.clone()),
             DbRecord::TreeNode(node) => DbRecord::TreeNode(node.clone()),
             DbRecord::ValueState(state) => DbRecord::ValueState(state.clone()),
+            DbRecord::OperationRecord(record) => DbRecord::OperationRecord(record.clone()),
+            DbRecord::SoftDeletion(deletion) => DbRecord::SoftDeletion(deletion.clone()),
+            DbRecord::AnchorReceipt(receipt) => DbRecord::AnchorReceipt(receipt.clone()),
+            DbRecord::PublishLease(lease) => DbRecord::PublishLease(lease.clone()),
+            DbRecord::AuditEpoch(audit_epoch) => DbRecord::AuditEpoch(audit_epoch.clone()),
         }
     }
 }
 
 impl DbRecord {
+    /// The version stamp of the record, which changes each time the record with the same id
+    /// is rewritten. This is the epoch at which the record was last modified (or the fencing
+    /// token of a [PublishLease]), and is used by
+    /// [Database::set_if_version](crate::storage::Database::set_if_version) to detect
+    /// concurrent modifications.
+    pub fn version_stamp(&self) -> u64 {
+        match &self {
+            DbRecord::Azks(azks) => azks.latest_epoch,
+            DbRecord::TreeNode(node) => node.latest_node.last_epoch,
+            DbRecord::ValueState(state) => state.epoch,
+            DbRecord::OperationRecord(record) => record.epoch,
+            DbRecord::SoftDeletion(deletion) => deletion.epoch,
+            DbRecord::AnchorReceipt(receipt) => receipt.epoch,
+            DbRecord::PublishLease(lease) => lease.token,
+            DbRecord::AuditEpoch(audit_epoch) => audit_epoch.end_epoch,
+        }
+    }
+
     /// Compte a serialized id from the record's fields. This id is useful to use as key
     /// in key-value stores.
     pub fn get_full_binary_id(&self) -> Vec<u8> {
@@ -192,6 +525,25 @@ impl DbRecord {
             DbRecord::Azks(azks) => azks.get_full_binary_id(),
             DbRecord::TreeNode(node) => node.get_full_binary_id(),
             DbRecord::ValueState(state) => state.get_full_binary_id(),
+            DbRecord::OperationRecord(record) => record.get_full_binary_id(),
+            DbRecord::SoftDeletion(deletion) => deletion.get_full_binary_id(),
+            DbRecord::AnchorReceipt(receipt) => receipt.get_full_binary_id(),
+            DbRecord::PublishLease(lease) => lease.get_full_binary_id(),
+            DbRecord::AuditEpoch(audit_epoch) => audit_epoch.get_full_binary_id(),
+        }
+    }
+
+    /// The type of the record
+    pub fn storage_type(&self) -> StorageType {
+        match &self {
+            DbRecord::Azks(_) => StorageType::Azks,
+            DbRecord::TreeNode(_) => StorageType::TreeNode,
+            DbRecord::ValueState(_) => StorageType::ValueState,
+            DbRecord::OperationRecord(_) => StorageType::OperationRecord,
+            DbRecord::SoftDeletion(_) => StorageType::SoftDeletion,
+            DbRecord::AnchorReceipt(_) => StorageType::AnchorReceipt,
+            DbRecord::PublishLease(_) => StorageType::PublishLease,
+            DbRecord::AuditEpoch(_) => StorageType::AuditEpoch,
         }
     }
 
@@ -276,6 +628,7 @@ impl DbRecord {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     /// Build a user state from the properties
     pub fn build_user_state(
         username: Vec<u8>,
@@ -284,6 +637,7 @@ impl DbRecord {
         label_len: u32,
         label_val: [u8; 32],
         epoch: u64,
+        retention_class: Option<RetentionClass>,
     ) -> ValueState {
         ValueState {
             value: AkdValue(plaintext_val),
@@ -291,6 +645,29 @@ impl DbRecord {
             label: NodeLabel::new(label_val, label_len),
             epoch,
             username: AkdLabel(username),
+            retention_class,
         }
     }
+
+    #[allow(clippy::too_many_arguments)]
+    /// Build an operation record from the properties
+    pub fn build_operation_record(
+        sequence: u64,
+        kind: u8,
+        epoch: u64,
+        timestamp_ms: u64,
+        identity: String,
+        metadata: String,
+        detail: String,
+    ) -> Result<OperationRecord, String> {
+        Ok(OperationRecord {
+            sequence,
+            kind: OperationKind::from_u8(kind)?,
+            epoch,
+            timestamp_ms,
+            identity,
+            metadata,
+            detail,
+        })
+    }
 }
@@ -0,0 +1,337 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A write-ahead log, which makes the commit of an epoch's writes effectively atomic on
+//! [Database](crate::storage::Database) implementations without native transactions.
+//!
+//! When a [StorageManager](crate::storage::StorageManager) is given a write-ahead log, the
+//! records of each committed transaction are appended to the log before any of them are
+//! written to the database, and the entry is marked as completed once the database write
+//! has succeeded. If the process crashes (or the database write fails) part way through a
+//! commit, the entry remains pending, and is replayed by
+//! [StorageManager::recover_write_ahead_log](crate::storage::StorageManager::recover_write_ahead_log)
+//! when the directory is next started. Since every record write is an overwrite, replaying
+//! an entry whose records were (partially or entirely) written already is harmless.
+//!
+//! A [FileWriteAheadLog] keeps the log in a file, so that a commit interrupted by a crash
+//! can be completed after a restart. Its entries are serialized with [WalEntry::encode],
+//! whose records are encoded with a [RecordCodec].
+
+use crate::errors::StorageError;
+use crate::storage::codec::RecordCodec;
+use crate::storage::types::DbRecord;
+
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// The version of the encoding of a [WalEntry] (see [WalEntry::encode])
+pub const WAL_ENTRY_ENCODING_VERSION: u8 = 1;
+
+/// The records of a committed transaction, recorded in a [WriteAheadLog]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalEntry {
+    /// The epoch which the transaction committed
+    pub epoch: u64,
+    /// The epoch upon which the transaction was built, which is the epoch before `epoch`
+    /// unless the epochs in between were skipped (see [crate::scheduling])
+    pub previous_epoch: Option<u64>,
+    /// The records written by the transaction
+    pub records: Vec<DbRecord>,
+}
+
+impl WalEntry {
+    /// Serializes the entry, encoding each of its records with `codec`. The encoding is the
+    /// version ([WAL_ENTRY_ENCODING_VERSION]), the epoch (as a big-endian u64), a flag byte
+    /// followed by the previous epoch (if there is one), the number of records (as a
+    /// big-endian u32), and each encoded record prefixed by its length (as a big-endian u32).
+    pub fn encode(&self, codec: &dyn RecordCodec) -> Result<Vec<u8>, StorageError> {
+        let mut bytes = vec![WAL_ENTRY_ENCODING_VERSION];
+        bytes.extend_from_slice(&self.epoch.to_be_bytes());
+        match self.previous_epoch {
+            Some(previous_epoch) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&previous_epoch.to_be_bytes());
+            }
+            None => bytes.push(0),
+        }
+        bytes.extend_from_slice(&encode_len(self.records.len())?);
+        for record in &self.records {
+            let record = codec.encode(record)?;
+            bytes.extend_from_slice(&encode_len(record.len())?);
+            bytes.extend_from_slice(&record);
+        }
+        Ok(bytes)
+    }
+
+    /// Deserializes an entry which was serialized by [WalEntry::encode] with the same codec
+    pub fn decode(bytes: &[u8], codec: &dyn RecordCodec) -> Result<Self, StorageError> {
+        let mut reader = ByteReader(bytes);
+        let version = reader.take(1)?[0];
+        if version != WAL_ENTRY_ENCODING_VERSION {
+            return Err(StorageError::Other(format!(
+                "Unsupported write-ahead log entry version {version}"
+            )));
+        }
+        let epoch = reader.read_u64()?;
+        let previous_epoch = match reader.take(1)?[0] {
+            0 => None,
+            1 => Some(reader.read_u64()?),
+            flag => {
+                return Err(StorageError::Other(format!(
+                    "Invalid previous epoch flag {flag} in write-ahead log entry"
+                )))
+            }
+        };
+        let num_records = reader.read_u32()?;
+        let mut records = Vec::new();
+        for _ in 0..num_records {
+            let len = reader.read_u32()? as usize;
+            records.push(codec.decode(reader.take(len)?)?);
+        }
+        if !reader.0.is_empty() {
+            return Err(StorageError::Other(
+                "Trailing bytes after write-ahead log entry".to_string(),
+            ));
+        }
+        Ok(Self {
+            epoch,
+            previous_epoch,
+            records,
+        })
+    }
+}
+
+fn encode_len(len: usize) -> Result<[u8; 4], StorageError> {
+    u32::try_from(len)
+        .map(u32::to_be_bytes)
+        .map_err(|_| StorageError::Other(format!("Write-ahead log entry too large ({len})")))
+}
+
+struct ByteReader<'a>(&'a [u8]);
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], StorageError> {
+        if self.0.len() < len {
+            return Err(StorageError::Other(
+                "Truncated write-ahead log entry".to_string(),
+            ));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, StorageError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, StorageError> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+}
+
+/// A durable log of the records of each committed transaction, which is written before
+/// the records themselves are written to the database
+#[async_trait]
+pub trait WriteAheadLog: Send + Sync {
+    /// Durably records the intent to write an epoch's records. This must not return until
+    /// the entry would survive a crash.
+    async fn append(&self, entry: WalEntry) -> Result<(), StorageError>;
+
+    /// Marks the entry of an epoch as completed, since all of its records were written
+    async fn complete(&self, epoch: u64) -> Result<(), StorageError>;
+
+    /// Retrieves the entries which were appended but not completed, in order of epoch
+    async fn pending(&self) -> Result<Vec<WalEntry>, StorageError>;
+}
+
+/// An in-memory [WriteAheadLog]. As it does not survive the process, this is only
+/// useful for testing, or when the log's lifetime is managed by the caller.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryWriteAheadLog {
+    entries: Arc<Mutex<BTreeMap<u64, WalEntry>>>,
+}
+
+impl InMemoryWriteAheadLog {
+    /// Creates a new, empty, in-memory write-ahead log
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl WriteAheadLog for InMemoryWriteAheadLog {
+    async fn append(&self, entry: WalEntry) -> Result<(), StorageError> {
+        let mut entries = self
+            .entries
+            .lock()
+            .map_err(|err| StorageError::Other(format!("Write-ahead log lock poisoned: {err}")))?;
+        entries.insert(entry.epoch, entry);
+        Ok(())
+    }
+
+    async fn complete(&self, epoch: u64) -> Result<(), StorageError> {
+        let mut entries = self
+            .entries
+            .lock()
+            .map_err(|err| StorageError::Other(format!("Write-ahead log lock poisoned: {err}")))?;
+        entries.remove(&epoch);
+        Ok(())
+    }
+
+    async fn pending(&self) -> Result<Vec<WalEntry>, StorageError> {
+        let entries = self
+            .entries
+            .lock()
+            .map_err(|err| StorageError::Other(format!("Write-ahead log lock poisoned: {err}")))?;
+        Ok(entries.values().cloned().collect())
+    }
+}
+
+const FRAME_APPEND: u8 = 1;
+const FRAME_COMPLETE: u8 = 2;
+
+/// A [WriteAheadLog] kept in a file, which survives a crash of the process. Each appended
+/// entry and each completion is written as a frame at the end of the file (a tag byte, the
+/// length of the frame's payload as a big-endian u32, and the payload: the entry encoded
+/// with [WalEntry::encode], or the completed epoch as a big-endian u64), and the file is
+/// synced before the operation returns. The file is truncated once every entry has been
+/// completed, so that it does not grow without bound.
+///
+/// A frame which was only partially written when the process crashed belongs to an entry
+/// whose append did not return, and so whose records were not written to the database: it
+/// is discarded when the log is opened.
+#[derive(Clone)]
+pub struct FileWriteAheadLog {
+    path: PathBuf,
+    codec: Arc<dyn RecordCodec>,
+    state: Arc<Mutex<FileWalState>>,
+}
+
+struct FileWalState {
+    file: File,
+    entries: BTreeMap<u64, WalEntry>,
+}
+
+impl FileWriteAheadLog {
+    /// Opens the write-ahead log at `path`, creating the file if it does not exist, and
+    /// reading the entries which are pending in it. The records of the entries are encoded
+    /// with `codec`, which must be the codec the log was written with.
+    pub fn open(
+        path: impl AsRef<Path>,
+        codec: impl RecordCodec + 'static,
+    ) -> Result<Self, StorageError> {
+        let path = path.as_ref().to_path_buf();
+        let codec: Arc<dyn RecordCodec> = Arc::new(codec);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)
+            .map_err(|err| io_error(&path, err))?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)
+            .map_err(|err| io_error(&path, err))?;
+
+        let mut entries = BTreeMap::new();
+        let mut reader = ByteReader(&bytes);
+        let mut valid_len = 0;
+        while let Ok(header) = reader.take(5) {
+            let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+            let Ok(payload) = reader.take(len) else {
+                break;
+            };
+            match header[0] {
+                FRAME_APPEND => {
+                    let entry = WalEntry::decode(payload, codec.as_ref())?;
+                    entries.insert(entry.epoch, entry);
+                }
+                FRAME_COMPLETE => {
+                    entries.remove(&ByteReader(payload).read_u64()?);
+                }
+                _ => break,
+            }
+            valid_len += 5 + len;
+        }
+        if valid_len < bytes.len() {
+            // The tail of the file is a frame which was not completely written
+            file.set_len(valid_len as u64)
+                .and_then(|_| file.sync_all())
+                .map_err(|err| io_error(&path, err))?;
+        }
+
+        Ok(Self {
+            path,
+            codec,
+            state: Arc::new(Mutex::new(FileWalState { file, entries })),
+        })
+    }
+
+    /// The path of the file which holds the log
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, FileWalState>, StorageError> {
+        self.state
+            .lock()
+            .map_err(|err| StorageError::Other(format!("Write-ahead log lock poisoned: {err}")))
+    }
+
+    fn write_frame(&self, file: &mut File, tag: u8, payload: &[u8]) -> Result<(), StorageError> {
+        let mut frame = vec![tag];
+        frame.extend_from_slice(&encode_len(payload.len())?);
+        frame.extend_from_slice(payload);
+        file.write_all(&frame)
+            .and_then(|_| file.sync_data())
+            .map_err(|err| io_error(&self.path, err))
+    }
+}
+
+#[async_trait]
+impl WriteAheadLog for FileWriteAheadLog {
+    async fn append(&self, entry: WalEntry) -> Result<(), StorageError> {
+        let payload = entry.encode(self.codec.as_ref())?;
+        let mut state = self.lock()?;
+        self.write_frame(&mut state.file, FRAME_APPEND, &payload)?;
+        state.entries.insert(entry.epoch, entry);
+        Ok(())
+    }
+
+    async fn complete(&self, epoch: u64) -> Result<(), StorageError> {
+        let mut state = self.lock()?;
+        state.entries.remove(&epoch);
+        if state.entries.is_empty() {
+            // Nothing in the log is pending any longer
+            state
+                .file
+                .set_len(0)
+                .and_then(|_| state.file.sync_data())
+                .map_err(|err| io_error(&self.path, err))
+        } else {
+            self.write_frame(&mut state.file, FRAME_COMPLETE, &epoch.to_be_bytes())
+        }
+    }
+
+    async fn pending(&self) -> Result<Vec<WalEntry>, StorageError> {
+        Ok(self.lock()?.entries.values().cloned().collect())
+    }
+}
+
+fn io_error(path: &Path, err: std::io::Error) -> StorageError {
+    StorageError::Other(format!(
+        "Failed to access the write-ahead log at {}: {err}",
+        path.display()
+    ))
+}
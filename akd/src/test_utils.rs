@@ -6,56 +6,78 @@
 // of this source tree. You may select, at your option, one of the above-listed licenses.
 
 //! This module contains common test utilities for crates generating tests utilizing the
-//! AKD crate
+//! AKD crate: seedable randomness, loggers for test environments, corrupted proofs (see
+//! [proof_mutator]), and suites of tests which exercise a directory end-to-end, so that
+//! storage backends can be tested against them
 
+use crate::ecvrf::VRFKeyStorage;
+use crate::storage::{Database, StorageManager};
+use crate::{
+    AkdLabel, AkdValue, Configuration, Directory, HistoryParams, HistoryVerificationParams,
+};
+pub use akd_core::proof_mutator;
+pub use akd_core::test_utils::{
+    test_rng, test_seed, thread_test_rng, ThreadTestRng, TEST_SEED_ENV_VAR,
+};
 use colored::*;
 use log::{Level, Metadata, Record};
 use once_cell::sync::OnceCell;
-use std::sync::Once;
+use rand::distributions::Alphanumeric;
+use rand::seq::IteratorRandom;
+use rand::Rng;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Mutex, Once};
 use std::time::{Duration, Instant};
 
 static EPOCH: OnceCell<Instant> = OnceCell::new();
 static LOGGER: TestConsoleLogger = TestConsoleLogger {};
 static INIT_ONCE: Once = Once::new();
 
-pub(crate) struct TestConsoleLogger;
-
-impl TestConsoleLogger {
-    pub(crate) fn format_log_record(record: &Record) {
-        let target = {
-            if let Some(target_str) = record.target().split(':').last() {
-                if let Some(line) = record.line() {
-                    format!(" ({target_str}:{line})")
-                } else {
-                    format!(" ({target_str})")
-                }
+/// Formats a log record with the time elapsed since the logger was initialized
+fn format_log_record(record: &Record) -> String {
+    let target = {
+        if let Some(target_str) = record.target().split(':').next_back() {
+            if let Some(line) = record.line() {
+                format!(" ({target_str}:{line})")
             } else {
-                "".to_string()
+                format!(" ({target_str})")
             }
-        };
-
-        let toc = if let Some(epoch) = EPOCH.get() {
-            Instant::now() - *epoch
         } else {
-            Duration::from_millis(0)
-        };
+            "".to_string()
+        }
+    };
+
+    let toc = if let Some(epoch) = EPOCH.get() {
+        Instant::now() - *epoch
+    } else {
+        Duration::from_millis(0)
+    };
+
+    let seconds = toc.as_secs();
+    let hours = seconds / 3600;
+    let minutes = (seconds / 60) % 60;
+    let seconds = seconds % 60;
+    let miliseconds = toc.subsec_millis();
 
-        let seconds = toc.as_secs();
-        let hours = seconds / 3600;
-        let minutes = (seconds / 60) % 60;
-        let seconds = seconds % 60;
-        let miliseconds = toc.subsec_millis();
-
-        let msg = format!(
-            "[{:02}:{:02}:{:02}.{:03}] {:6} {}{}",
-            hours,
-            minutes,
-            seconds,
-            miliseconds,
-            record.level(),
-            record.args(),
-            target
-        );
+    format!(
+        "[{:02}:{:02}:{:02}.{:03}] {:6} {}{}",
+        hours,
+        minutes,
+        seconds,
+        miliseconds,
+        record.level(),
+        record.args(),
+        target
+    )
+}
+
+pub(crate) struct TestConsoleLogger;
+
+impl TestConsoleLogger {
+    pub(crate) fn format_log_record(record: &Record) {
+        let msg = format_log_record(record);
         let msg = match record.level() {
             Level::Trace | Level::Debug => msg.white(),
             Level::Info => msg.blue(),
@@ -81,6 +103,40 @@ impl log::Log for TestConsoleLogger {
     fn flush(&self) {}
 }
 
+pub(crate) struct TestFileLogger {
+    sink: Mutex<File>,
+}
+
+impl TestFileLogger {
+    pub(crate) fn new<T: AsRef<Path>>(path: T) -> io::Result<Self> {
+        let file = File::create(path)?;
+        Ok(Self {
+            sink: Mutex::new(file),
+        })
+    }
+}
+
+impl log::Log for TestFileLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if let Ok(mut sink) = self.sink.lock() {
+            let _ = writeln!(sink, "{}", format_log_record(record));
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut sink) = self.sink.lock() {
+            let _ = sink.flush();
+        }
+    }
+}
+
 /// Initialize the logger for console logging within test environments.
 /// This is safe to call multiple times, but it will only initialize the logger
 /// to the log-level _first_ set. If you want a specific log-level (e.g. Debug)
@@ -98,6 +154,24 @@ pub fn init_logger(level: Level) {
     });
 }
 
+/// Initialize the logger for logging to a file within test environments, such as
+/// integration tests whose console output would be too noisy. As with [init_logger],
+/// only the first logger initialized in a process takes effect, at the log-level
+/// _first_ set.
+///
+/// Panics if the file cannot be created.
+pub fn init_file_logger<P: AsRef<Path>>(path: P, level: Level) {
+    EPOCH.get_or_init(Instant::now);
+
+    INIT_ONCE.call_once(|| {
+        let logger = TestFileLogger::new(path).expect("Failed to create the test log file");
+        // the logger lives for the rest of the process
+        log::set_logger(Box::leak(Box::new(logger)))
+            .map(|()| log::set_max_level(level.to_level_filter()))
+            .unwrap();
+    });
+}
+
 /// Global test startup constructor. Only runs in the TEST profile. Each
 /// crate which wants logging enabled in tests being run should make this call
 /// itself.
@@ -106,3 +180,204 @@ pub fn init_logger(level: Level) {
 fn test_start() {
     init_logger(Level::Info);
 }
+
+/// Generates `num_users` random alphanumeric labels
+fn random_users(rng: &mut impl Rng, num_users: usize) -> Vec<String> {
+    (0..num_users)
+        .map(|_| {
+            rng.sample_iter(&Alphanumeric)
+                .take(30)
+                .map(char::from)
+                .collect()
+        })
+        .collect()
+}
+
+/// The suite of tests to run against a fully-instantiated and storage-backed directory,
+/// such as for a new [Database] implementation. This publishes 3 epochs of updates to
+/// `num_users` random labels, then generates and verifies 10 random lookup proofs, 2
+/// random history proofs, and an audit proof from epoch 1 to epoch 2.
+///
+/// Panics if any operation fails, or if any proof fails to verify.
+pub async fn directory_test_suite<TC: Configuration, S: Database + 'static, V: VRFKeyStorage>(
+    storage: &StorageManager<S>,
+    num_users: usize,
+    vrf: &V,
+) {
+    let mut rng = test_rng();
+    let users = random_users(&mut rng, num_users);
+    let mut root_hashes = vec![];
+    let dir = Directory::<TC, _, _>::new(storage.clone(), vrf.clone())
+        .await
+        .unwrap_or_else(|error| panic!("Error initializing directory: {error:?}"));
+
+    // Publish 3 epochs of user material
+    for i in 1..=3 {
+        let data = users
+            .iter()
+            .map(|user| (AkdLabel::from(user), AkdValue::from(&format!("{i}"))))
+            .collect();
+        if let Err(error) = dir.publish(data).await {
+            panic!("Error publishing batch {error:?}");
+        }
+        let root_hash = dir.get_epoch_hash().await.unwrap().1;
+        root_hashes.push(root_hash);
+    }
+    let vrf_pk = dir.get_public_key().await.unwrap();
+
+    // Perform 10 random lookup proofs on the published users
+    for user in users.iter().choose_multiple(&mut rng, 10) {
+        let label = AkdLabel::from(user);
+        let (proof, epoch_hash) = dir
+            .lookup(label.clone())
+            .await
+            .unwrap_or_else(|error| panic!("Error looking up user information {error:?}"));
+        if let Err(error) = crate::client::lookup_verify::<TC>(
+            vrf_pk.as_bytes(),
+            epoch_hash.hash(),
+            epoch_hash.epoch(),
+            label,
+            proof,
+        ) {
+            panic!("Lookup proof failed to verify {error:?}");
+        }
+    }
+
+    // Perform 2 random history proofs on the published material
+    for user in users.iter().choose_multiple(&mut rng, 2) {
+        let label = AkdLabel::from(user);
+        let (proof, epoch_hash) = dir
+            .key_history(&label, HistoryParams::default())
+            .await
+            .unwrap_or_else(|error| panic!("Error performing key history retrieval {error:?}"));
+        if let Err(error) = crate::client::key_history_verify::<TC>(
+            vrf_pk.as_bytes(),
+            epoch_hash.hash(),
+            epoch_hash.epoch(),
+            label,
+            proof,
+            HistoryVerificationParams::default(),
+        ) {
+            panic!("History proof failed to verify {error:?}");
+        }
+    }
+
+    // Perform an audit proof from 1u64 -> 2u64
+    storage.log_metrics(tracing::Level::INFO).await;
+    log::warn!("Beginning audit proof generation");
+    storage.flush_cache().await;
+    let proof = dir
+        .audit(1u64, 2u64)
+        .await
+        .unwrap_or_else(|error| panic!("Error perform audit proof retrieval {error:?}"));
+    storage.log_metrics(tracing::Level::INFO).await;
+    log::warn!("Done with audit proof generation");
+    crate::auditor::audit_verify::<TC>(vec![root_hashes[0], root_hashes[1]], proof)
+        .await
+        .unwrap();
+}
+
+/// Compares individual and batched lookups against a fully-instantiated and storage-backed
+/// directory. This publishes `num_epochs` epochs of updates to `num_users` random labels,
+/// then generates and verifies lookup proofs for `num_lookups` of them, first one by one and
+/// then in a single batch, logging the time taken and the storage metrics of each.
+///
+/// Panics if any operation fails, or if any proof fails to verify.
+pub async fn lookup_test_suite<TC: Configuration, S: Database + 'static, V: VRFKeyStorage>(
+    storage: &StorageManager<S>,
+    vrf: &V,
+    num_users: u64,
+    num_epochs: u64,
+    num_lookups: usize,
+) {
+    let mut rng = test_rng();
+    let users = random_users(&mut rng, num_users as usize);
+    let dir = Directory::<TC, _, _>::new(storage.clone(), vrf.clone())
+        .await
+        .unwrap_or_else(|error| panic!("Error initializing directory: {error:?}"));
+    log::info!("AKD Directory started. Beginning tests");
+
+    // Publish `num_epochs` epochs of user material
+    for i in 1..=num_epochs {
+        let data = users
+            .iter()
+            .map(|user| (AkdLabel::from(user), AkdValue::from(&format!("{i}"))))
+            .collect();
+        if let Err(error) = dir.publish(data).await {
+            panic!("Error publishing batch {error:?}");
+        }
+        log::info!("Published epoch {i}");
+    }
+
+    // Pick a set of users to lookup
+    let labels = users
+        .iter()
+        .choose_multiple(&mut rng, num_lookups)
+        .into_iter()
+        .map(AkdLabel::from)
+        .collect::<Vec<_>>();
+    let vrf_pk = dir.get_public_key().await.unwrap();
+
+    log::warn!("Metrics after publish(es).");
+    reset_metrics(storage).await;
+
+    let start = Instant::now();
+    // Lookup selected users one by one
+    for label in labels.clone() {
+        let (proof, epoch_hash) = dir
+            .lookup(label.clone())
+            .await
+            .unwrap_or_else(|error| panic!("Error looking up user information {error:?}"));
+        if let Err(error) = crate::client::lookup_verify::<TC>(
+            vrf_pk.as_bytes(),
+            epoch_hash.hash(),
+            epoch_hash.epoch(),
+            label,
+            proof,
+        ) {
+            panic!("Lookup proof failed to verify {error:?}");
+        }
+    }
+    log::warn!(
+        "Individual {} lookups took {}ms.",
+        num_lookups,
+        start.elapsed().as_millis()
+    );
+
+    log::warn!("Metrics after individual lookups:");
+    reset_metrics(storage).await;
+
+    let start = Instant::now();
+    // Bulk lookup selected users
+    let (proofs, epoch_hash) = dir
+        .batch_lookup(&labels)
+        .await
+        .unwrap_or_else(|error| panic!("Error batch looking up user information {error:?}"));
+    assert_eq!(labels.len(), proofs.len());
+    for (i, (label, proof)) in labels.iter().zip(proofs).enumerate() {
+        if let Err(error) = crate::client::lookup_verify::<TC>(
+            vrf_pk.as_bytes(),
+            epoch_hash.hash(),
+            epoch_hash.epoch(),
+            label.clone(),
+            proof,
+        ) {
+            panic!("Batch lookup failed to verify for index {i} {error:?}");
+        }
+    }
+    log::warn!(
+        "Bulk {} lookups took {}ms.",
+        num_lookups,
+        start.elapsed().as_millis()
+    );
+
+    log::warn!("Metrics after lookup proofs: ");
+    reset_metrics(storage).await;
+}
+
+/// Logs (which resets) the storage metrics and flushes the cache, so that the efficiency of
+/// the next operations can be assessed on their own
+async fn reset_metrics<S: Database>(storage: &StorageManager<S>) {
+    storage.log_metrics(tracing::Level::WARN).await;
+    storage.flush_cache().await;
+}
@@ -7,29 +7,68 @@
 
 //! Contains the tests for the high-level API (directory, auditor, client)
 
+mod concurrency;
+mod differential;
+mod golden;
+mod malicious_server;
+mod proof_mutator;
+
 use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
 
-use crate::{errors::DirectoryError, test_config};
+use crate::errors::DirectoryError;
 use akd_core::{configuration::Configuration, hash::DIGEST_BYTES};
 use rand::{rngs::StdRng, SeedableRng};
 
 use crate::{
-    auditor::{audit_verify, verify_consecutive_append_only},
-    client::{key_history_verify, lookup_verify},
-    directory::{Directory, PublishCorruption, ReadOnlyDirectory},
+    anchoring::{CalendarClient, NotaryAnchor, OpenTimestampsAnchor},
+    audit_lite::{AuditLiteState, MAX_PREFIX_BITS},
+    audit_path::AuditPath,
+    auditor::{audit_verify, audit_verify_multi_epoch},
+    canonical::{CanonicalEncode, CANONICAL_ENCODING_VERSION},
+    client::{
+        aggregated_key_history_verify, compact_key_history_verify, key_history_verify,
+        key_history_verify_recent_epochs, linked_key_history_verify, lookup_latest_verify,
+        lookup_verify, lookup_verify_result, selective_key_history_verify,
+        sharded_key_history_verify, sharded_lookup_verify,
+    },
+    coalescing::LookupCoalescingMetrics,
+    coniks::{AuthenticationPath, ProofType, SignedTreeRoot},
+    consolidation::{verify_audit_epoch, verify_mini_epoch},
+    delta_sync::{delta_sync_verify, LabelDelta},
+    directory::{
+        Directory, LimitEnforcement, LimitViolation, Operator, PublishCorruption, PublishLimits,
+        RateLimit, ReadOnlyDirectory,
+    },
+    divergence::Divergence,
     ecvrf::{HardCodedAkdVRF, VRFKeyStorage},
     errors::{AkdError, StorageError},
+    health::{HealthCheckOptions, HealthStatus},
+    maintenance::{MaintenanceConfig, MaintenanceTask, RetentionPolicy, ScheduledTask},
+    prefetch::PrefetchConfig,
+    proof_chunks::{
+        AppendOnlyProofChunk, ChunkedAuditOptions, InMemoryProofChunkStore, ProofChunkStore,
+    },
+    publish_queue::{PublishQueue, PublishQueueMetrics},
+    scheduling::{WallClockEpochs, MAX_EPOCH_GAP},
+    sharded_directory::{shard_index, ShardedDirectory},
+    spot_check::{spot_check_verify, MAX_SPOT_CHECK_SAMPLES},
     storage::{
-        manager::StorageManager,
+        manager::{PublishLeaseOptions, StorageManager},
         memory::AsyncInMemoryDatabase,
-        types::{DbRecord, KeyData, ValueState, ValueStateRetrievalFlag},
-        Database, DbSetState, Storable,
+        mock::{MockDatabase, MockDatabaseOptions},
+        types::{
+            DbRecord, KeyData, OperationKind, OperationRecord, ValueState, ValueStateRetrievalFlag,
+        },
+        Database, DbSetState, Storable, StorageRole, StorageUtil,
     },
-    tree_node::TreeNodeWithPreviousValue,
-    AkdLabel, AkdValue, AppendOnlyProof, Azks, EpochHash, HistoryParams, HistoryVerificationParams,
+    tree_node::{NodeKey, TreeNodeType, TreeNodeWithPreviousValue},
+    AkdLabel, AkdValue, Azks, AzksValue, DirectoryExport, EpochHash, HistoryAggregate,
+    HistoryParams, HistoryVerificationParams, NodeLabel, RetentionClass, UpdateDisclosure,
     VerifyResult,
 };
 
+#[allow(dead_code)]
 #[derive(Clone)]
 pub struct LocalDatabase;
 
@@ -68,6 +107,7 @@ mockall::mock! {
             usernames: &[AkdLabel],
             flag: ValueStateRetrievalFlag,
         ) -> Result<HashMap<AkdLabel, (u64, AkdValue)>, StorageError>;
+        async fn close(&self) -> Result<(), StorageError>;
     }
 }
 
@@ -98,6 +138,10 @@ fn setup_mocked_db(db: &mut MockLocalDatabase, test_db: &AsyncInMemoryDatabase)
     db.expect_get::<Azks>()
         .returning(move |key| futures::executor::block_on(tmp_db.get::<Azks>(key)));
 
+    let tmp_db = test_db.clone();
+    db.expect_get::<OperationRecord>()
+        .returning(move |key| futures::executor::block_on(tmp_db.get::<OperationRecord>(key)));
+
     // ===== Batch Get ===== //
     let tmp_db = test_db.clone();
     db.expect_batch_get::<Azks>()
@@ -170,143 +214,492 @@ async fn test_directory_azks_bootstrapping<TC: Configuration>() -> Result<(), Ak
     Ok(())
 }
 
-// A simple test to ensure that the empty tree hashes to the correct value
-test_config!(test_empty_tree_root_hash);
-async fn test_empty_tree_root_hash<TC: Configuration>() -> Result<(), AkdError> {
+// A more complex publish test
+test_config!(test_complex_publish);
+async fn test_complex_publish<TC: Configuration>() -> Result<(), AkdError> {
     let db = AsyncInMemoryDatabase::new();
     let storage = StorageManager::new_no_cache(db);
     let vrf = HardCodedAkdVRF {};
-    let akd: Directory<_, AsyncInMemoryDatabase, HardCodedAkdVRF> =
-        Directory::<TC, _, _>::new(storage, vrf).await?;
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+
+    let num_entries = 10000;
+    let mut entries = vec![];
+    let mut rng = StdRng::seed_from_u64(42);
+    for _ in 0..num_entries {
+        let label = AkdLabel::random(&mut rng);
+        let value = AkdValue::random(&mut rng);
+        entries.push((label, value));
+    }
+    akd.publish(entries).await?;
+    Ok(())
+}
+
+// The conformance suite, for each configuration with the in-memory database
+#[cfg(feature = "whatsapp_v1")]
+crate::conformance_suite!(
+    conformance_whatsapp_v1_config,
+    crate::WhatsAppV1Configuration,
+    AsyncInMemoryDatabase::new
+);
+#[cfg(feature = "experimental")]
+crate::conformance_suite!(
+    conformance_experimental_config,
+    crate::ExperimentalConfiguration<crate::ExampleLabel>,
+    AsyncInMemoryDatabase::new
+);
+
+// A publish which spans multiple chunks of the publish pipeline, where some of
+// the labels are updates to existing labels and others are new. The tree nodes are
+// only preloaded by the pipeline when the storage has a cache, which must not change
+// the resulting tree.
+test_config!(test_multi_chunk_publish);
+async fn test_multi_chunk_publish<TC: Configuration>() -> Result<(), AkdError> {
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(
+        StorageManager::new_no_cache(AsyncInMemoryDatabase::new()),
+        vrf.clone(),
+    )
+    .await?;
+    let cached = Directory::<TC, _, _>::new(
+        StorageManager::new(
+            AsyncInMemoryDatabase::new(),
+            Some(Duration::from_secs(3600)),
+            None,
+            None,
+        ),
+        vrf,
+    )
+    .await?;
+
+    let num_entries = 2500;
+    let mut rng = StdRng::seed_from_u64(42);
+    let labels = (0..num_entries)
+        .map(|_| AkdLabel::random(&mut rng))
+        .collect::<Vec<_>>();
+
+    // Publish the first half of the labels
+    let updates = labels[..num_entries / 2]
+        .iter()
+        .map(|label| (label.clone(), AkdValue::random(&mut rng)))
+        .collect::<Vec<_>>();
+    akd.publish(updates.clone()).await?;
+    cached.publish(updates).await?;
+
+    // Update all of the labels, including the ones which were not yet published
+    let updates = labels
+        .iter()
+        .map(|label| (label.clone(), AkdValue::random(&mut rng)))
+        .collect::<Vec<_>>();
+    let EpochHash(epoch, root_hash) = akd.publish(updates.clone()).await?;
+    assert_eq!(EpochHash(epoch, root_hash), cached.publish(updates).await?);
+
+    let vrf_pk = akd.get_public_key().await?;
+    for (i, label) in [0, num_entries / 2 - 1, num_entries / 2, num_entries - 1]
+        .into_iter()
+        .map(|i| (i, labels[i].clone()))
+    {
+        let (lookup_proof, _) = akd.lookup(label.clone()).await?;
+        let result = lookup_verify::<TC>(vrf_pk.as_bytes(), root_hash, epoch, label, lookup_proof)?;
+        let expected_version = if i < num_entries / 2 { 2 } else { 1 };
+        assert_eq!(expected_version, result.version);
+    }
+    Ok(())
+}
+
+// Checks that publish, proof generation, storage, and cache operations emit their metrics
+#[cfg(feature = "metrics")]
+test_config!(test_metrics_are_emitted);
+#[cfg(feature = "metrics")]
+async fn test_metrics_are_emitted<TC: Configuration>() -> Result<(), AkdError> {
+    use crate::instrumentation::*;
+    use metrics::{Counter, Gauge, Histogram, Key, KeyName, Metadata, SharedString, Unit};
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    /// A recorder which only records the keys (names and labels) of the metrics which are emitted
+    #[derive(Default)]
+    struct KeyRecorder(Mutex<HashSet<Key>>);
+
+    impl KeyRecorder {
+        fn record(&self, key: &Key) {
+            self.0.lock().unwrap().insert(key.clone());
+        }
+    }
+
+    impl metrics::Recorder for KeyRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            self.record(key);
+            Counter::noop()
+        }
+        fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+            self.record(key);
+            Gauge::noop()
+        }
+        fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+            self.record(key);
+            Histogram::noop()
+        }
+    }
 
-    let hash = akd.get_epoch_hash().await?.1;
+    // The test runs on a single-threaded runtime, so the local recorder sees every metric
+    let recorder = KeyRecorder::default();
+    let _guard = metrics::set_default_local_recorder(&recorder);
+
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new(db, None, None, None);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+    for i in 0..2 {
+        akd.publish(vec![(
+            AkdLabel::from("hello"),
+            AkdValue(format!("world{i}").as_bytes().to_vec()),
+        )])
+        .await?;
+    }
+    akd.lookup(AkdLabel::from("hello")).await?;
+    akd.batch_lookup(&[AkdLabel::from("hello")]).await?;
+    akd.lookup_absent(AkdLabel::from("absent")).await?;
+    akd.key_history(&AkdLabel::from("hello"), HistoryParams::default())
+        .await?;
+    akd.key_history(&AkdLabel::from("hello"), HistoryParams::RecentEpochs(1))
+        .await?;
+    akd.audit(1, 2).await?;
+
+    let keys = recorder.0.lock().unwrap().clone();
+    let names = keys
+        .iter()
+        .map(|key| key.name().to_string())
+        .collect::<HashSet<_>>();
+    for name in [
+        PUBLISH_TOTAL,
+        PUBLISH_UPDATES_TOTAL,
+        EPOCH_DURATION_SECONDS,
+        EPOCH,
+        PROOF_GENERATION_SECONDS,
+        PROOF_SIZE_BYTES,
+        STORAGE_OPERATION_SECONDS,
+        CACHE_HITS_TOTAL,
+        CACHE_MISSES_TOTAL,
+    ] {
+        assert!(names.contains(name), "{name} was not emitted");
+    }
 
-    // Ensuring that the root hash of an empty tree is equal to the following constant
+    // The proof sizes are broken down by the query, and history proofs by their parameters
+    let mut proof_size_labels = keys
+        .iter()
+        .filter(|key| key.name() == PROOF_SIZE_BYTES)
+        .map(|key| {
+            key.labels()
+                .map(|label| format!("{}={}", label.key(), label.value()))
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect::<Vec<_>>();
+    proof_size_labels.sort();
     assert_eq!(
-        TC::compute_root_hash_from_val(&TC::empty_root_value()),
-        hash
+        vec![
+            "operation=audit",
+            "operation=batch_lookup",
+            "operation=key_history,params=complete",
+            "operation=key_history,params=recent_epochs",
+            "operation=lookup",
+            "operation=lookup_absent",
+        ],
+        proof_size_labels
     );
-
     Ok(())
 }
 
-// A simple publish test to make sure a publish doesn't throw an error.
-test_config!(test_simple_publish);
-async fn test_simple_publish<TC: Configuration>() -> Result<(), AkdError> {
+// Checks that the storage accesses made by a publish, including those made from the tasks
+// which it spawns, are recorded within the span of the publish
+#[cfg(feature = "tracing")]
+test_config!(test_storage_spans_are_nested_in_directory_spans);
+#[cfg(feature = "tracing")]
+async fn test_storage_spans_are_nested_in_directory_spans<TC: Configuration>(
+) -> Result<(), AkdError> {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata};
+
+    /// A subscriber which records the name and parent of each span
+    #[derive(Default)]
+    struct SpanTree {
+        next_id: AtomicU64,
+        spans: Mutex<HashMap<u64, (&'static Metadata<'static>, Option<u64>)>>,
+        entered: Mutex<Vec<u64>>,
+    }
+
+    impl SpanTree {
+        fn ancestors(&self, name: &str) -> Vec<Vec<&'static str>> {
+            let spans = self.spans.lock().unwrap();
+            spans
+                .values()
+                .filter(|(metadata, _)| metadata.name() == name)
+                .map(|(_, mut parent)| {
+                    let mut ancestors = vec![];
+                    while let Some(id) = parent {
+                        ancestors.push(spans[&id].0.name());
+                        parent = spans[&id].1;
+                    }
+                    ancestors
+                })
+                .collect()
+        }
+    }
+
+    impl tracing::Subscriber for SpanTree {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+            let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+            let parent = if let Some(parent) = attributes.parent() {
+                Some(parent.into_u64())
+            } else if attributes.is_contextual() {
+                self.entered.lock().unwrap().last().copied()
+            } else {
+                None
+            };
+            self.spans
+                .lock()
+                .unwrap()
+                .insert(id, (attributes.metadata(), parent));
+            Id::from_u64(id)
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, _: &Event<'_>) {}
+        fn enter(&self, span: &Id) {
+            self.entered.lock().unwrap().push(span.into_u64());
+        }
+        fn exit(&self, span: &Id) {
+            let mut entered = self.entered.lock().unwrap();
+            if let Some(position) = entered.iter().rposition(|id| *id == span.into_u64()) {
+                entered.remove(position);
+            }
+        }
+        fn current_span(&self) -> tracing_core::span::Current {
+            match self.entered.lock().unwrap().last() {
+                Some(id) => {
+                    let metadata = self.spans.lock().unwrap()[id].0;
+                    tracing_core::span::Current::new(Id::from_u64(*id), metadata)
+                }
+                None => tracing_core::span::Current::none(),
+            }
+        }
+    }
+
+    // The test runs on a single-threaded runtime, so spawned tasks also see the subscriber
+    let subscriber = Arc::new(SpanTree::default());
+    let _guard = tracing::subscriber::set_default(subscriber.clone());
+
     let db = AsyncInMemoryDatabase::new();
     let storage = StorageManager::new_no_cache(db);
     let vrf = HardCodedAkdVRF {};
     let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
-    // Make sure you can publish and that something so simple
-    // won't throw errors.
     akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world"))])
         .await?;
+    akd.lookup(AkdLabel::from("hello")).await?;
+
+    // The previous versions are retrieved by a task spawned by the publish
+    let retrievals = subscriber.ancestors("get_user_state_versions");
+    assert!(!retrievals.is_empty());
+    assert!(retrievals
+        .iter()
+        .all(|ancestors| ancestors.contains(&"publish")));
+    let reads = subscriber.ancestors("get_user_state");
+    assert!(reads.iter().any(|ancestors| ancestors.contains(&"lookup")));
     Ok(())
 }
 
-// A more complex publish test
-test_config!(test_complex_publish);
-async fn test_complex_publish<TC: Configuration>() -> Result<(), AkdError> {
+// Checks that publish, lookup, batch lookup, and audit each record a timing report with their
+// phases
+#[cfg(feature = "profiling")]
+test_config!(test_profiling_timing_reports);
+#[cfg(feature = "profiling")]
+async fn test_profiling_timing_reports<TC: Configuration>() -> Result<(), AkdError> {
+    use crate::profiling::{Operation, Phase};
+
     let db = AsyncInMemoryDatabase::new();
     let storage = StorageManager::new_no_cache(db);
     let vrf = HardCodedAkdVRF {};
     let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+    assert_eq!(None, akd.last_timing_report(Operation::Publish));
 
-    let num_entries = 10000;
-    let mut entries = vec![];
-    let mut rng = StdRng::seed_from_u64(42);
-    for _ in 0..num_entries {
-        let label = AkdLabel::random(&mut rng);
-        let value = AkdValue::random(&mut rng);
-        entries.push((label, value));
+    for i in 0..2 {
+        akd.publish(vec![(
+            AkdLabel::from("hello"),
+            AkdValue(format!("world{i}").as_bytes().to_vec()),
+        )])
+        .await?;
+    }
+    akd.lookup(AkdLabel::from("hello")).await?;
+    akd.batch_lookup(&[AkdLabel::from("hello")]).await?;
+    akd.audit(1, 2).await?;
+
+    let expected = [
+        (
+            Operation::Publish,
+            vec![
+                Phase::Preload,
+                Phase::VrfEvaluation,
+                Phase::Hash,
+                Phase::TreeMutation,
+                Phase::Write,
+                Phase::Commit,
+            ],
+        ),
+        (
+            Operation::Lookup,
+            vec![Phase::Preload, Phase::ProofAssembly],
+        ),
+        (
+            Operation::BatchLookup,
+            vec![Phase::Preload, Phase::ProofAssembly],
+        ),
+        (Operation::Audit, vec![Phase::Preload, Phase::ProofAssembly]),
+    ];
+    for (operation, phases) in expected {
+        let report = akd
+            .last_timing_report(operation)
+            .expect("Missing timing report");
+        assert_eq!(operation, report.operation);
+        assert_eq!(
+            phases,
+            report.phases.iter().map(|t| t.phase).collect::<Vec<_>>()
+        );
+        let phase_total = report.phases.iter().map(|t| t.duration).sum();
+        assert!(report.total >= phase_total);
     }
-    akd.publish(entries).await?;
     Ok(())
 }
 
-// A simple lookup test, for a tree with two elements:
-// ensure that calculation of a lookup proof doesn't throw an error and
-// that the output of akd.lookup verifies on the client.
-test_config!(test_simple_lookup);
-async fn test_simple_lookup<TC: Configuration>() -> Result<(), AkdError> {
+// Checks that the report returned by a publish accounts for the bytes read and written by
+// each of its phases
+#[cfg(feature = "profiling")]
+test_config!(test_publish_with_report);
+#[cfg(feature = "profiling")]
+async fn test_publish_with_report<TC: Configuration>() -> Result<(), AkdError> {
+    use crate::profiling::{Operation, Phase};
+
     let db = AsyncInMemoryDatabase::new();
-    let storage = StorageManager::new_no_cache(db);
+    let storage = StorageManager::new(db, None, None, None);
     let vrf = HardCodedAkdVRF {};
     let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
-    // Add two labels and corresponding values to the akd
-    akd.publish(vec![
-        (AkdLabel::from("hello"), AkdValue::from("world")),
-        (AkdLabel::from("hello2"), AkdValue::from("world2")),
-    ])
-    .await?;
-    // Get the lookup proof
-    let (lookup_proof, root_hash) = akd.lookup(AkdLabel::from("hello")).await?;
-    // Get the VRF public key
-    let vrf_pk = akd.get_public_key().await?;
-    // Verify the lookup proof
-    lookup_verify::<TC>(
-        vrf_pk.as_bytes(),
-        root_hash.hash(),
-        root_hash.epoch(),
-        AkdLabel::from("hello"),
-        lookup_proof,
-    )?;
+
+    let mut reports = vec![];
+    for i in 0..2 {
+        let (epoch_hash, report) = akd
+            .publish_with_report(vec![(
+                AkdLabel::from("hello"),
+                AkdValue(format!("world{i}").as_bytes().to_vec()),
+            )])
+            .await?;
+        assert_eq!(i + 1, epoch_hash.epoch());
+        assert_eq!(
+            Some(&report),
+            akd.last_timing_report(Operation::Publish).as_ref()
+        );
+        reports.push(report);
+    }
+
+    // Both publishes preload the tree, but only the second reads a previous version of the
+    // label, from a larger tree
+    assert!(reports[0].phase_bytes(Phase::Preload) > 0);
+    assert!(reports[1].phase_bytes(Phase::Preload) > reports[0].phase_bytes(Phase::Preload));
+    for report in reports {
+        assert!(report.phase_bytes(Phase::TreeMutation) > 0);
+        assert!(report.phase_bytes(Phase::Write) > 0);
+        // The commit writes the tree nodes and the records of the write phase
+        assert_eq!(
+            report.phase_bytes(Phase::TreeMutation) + report.phase_bytes(Phase::Write),
+            report.phase_bytes(Phase::Commit)
+        );
+        assert_eq!(0, report.phase_bytes(Phase::VrfEvaluation));
+    }
     Ok(())
 }
 
-// This test also covers #144: That key history doesn't fail on very small trees,
-// i.e. trees with a potentially empty child for the root node.
-// Other that it is just a simple check to see that a valid key history proof passes.
-test_config!(test_small_key_history);
-async fn test_small_key_history<TC: Configuration>() -> Result<(), AkdError> {
-    // This test has an akd with a single label: "hello"
-    // The value of this label is updated two times.
-    // Then the test verifies the key history.
+// A signer for epoch root hashes which simply hashes the message, for testing
+// the signed lookup flow without a real signature scheme
+struct HashEpochSigner<TC>(std::marker::PhantomData<TC>);
+
+impl<TC: Configuration> akd_core::signing::EpochSigner for HashEpochSigner<TC> {
+    fn sign_epoch_message(&self, message: &[u8]) -> Vec<u8> {
+        TC::hash(message).to_vec()
+    }
+}
+
+impl<TC: Configuration> akd_core::signing::EpochSignatureVerifier for HashEpochSigner<TC> {
+    fn verify_epoch_message(&self, message: &[u8], signature: &[u8]) -> bool {
+        TC::hash(message).as_slice() == signature
+    }
+}
+
+// Checks that a signed lookup response verifies, and that it is rejected when
+// paired with a different root hash or epoch than the one that was signed
+test_config!(test_signed_lookup);
+async fn test_signed_lookup<TC: Configuration>() -> Result<(), AkdError> {
     let db = AsyncInMemoryDatabase::new();
     let storage = StorageManager::new_no_cache(db);
     let vrf = HardCodedAkdVRF {};
     let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
-    // Publish the first value for the label "hello"
-    // Epoch here will be 1
+    let signer = HashEpochSigner::<TC>(std::marker::PhantomData);
+
     akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world"))])
         .await?;
-    // Publish the second value for the label "hello"
-    // Epoch here will be 2
+    let stale_response = akd.signed_lookup(AkdLabel::from("hello"), &signer).await?;
     akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world2"))])
         .await?;
-
-    // Get the key_history_proof for the label "hello"
-    let (key_history_proof, root_hash) = akd
-        .key_history(&AkdLabel::from("hello"), HistoryParams::default())
-        .await?;
-    // Get the VRF public key
+    let response = akd.signed_lookup(AkdLabel::from("hello"), &signer).await?;
     let vrf_pk = akd.get_public_key().await?;
-    // Verify the key history proof
-    let result = key_history_verify::<TC>(
+
+    let result = crate::client::signed_lookup_verify::<TC>(
         vrf_pk.as_bytes(),
-        root_hash.hash(),
-        root_hash.epoch(),
+        &signer,
         AkdLabel::from("hello"),
-        key_history_proof,
-        HistoryVerificationParams::default(),
+        response.clone(),
     )?;
+    assert_eq!(AkdValue::from("world2"), result.value);
+
+    // A proof paired with the signed root hash of another epoch is rejected
+    let mut mismatched = response.clone();
+    mismatched.root_hash = stale_response.root_hash;
+    mismatched.epoch = stale_response.epoch;
+    mismatched.epoch_signature = stale_response.epoch_signature.clone();
+    assert!(crate::client::signed_lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        &signer,
+        AkdLabel::from("hello"),
+        mismatched,
+    )
+    .is_err());
 
-    assert_eq!(
-        result,
-        vec![
-            VerifyResult {
-                epoch: 2,
-                version: 2,
-                value: AkdValue::from("world2"),
-            },
-            VerifyResult {
-                epoch: 1,
-                version: 1,
-                value: AkdValue::from("world"),
-            },
-        ]
-    );
+    // A root hash which was not signed for the epoch is rejected
+    let mut unsigned = response.clone();
+    unsigned.root_hash = stale_response.root_hash;
+    assert!(crate::client::signed_lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        &signer,
+        AkdLabel::from("hello"),
+        unsigned,
+    )
+    .is_err());
 
+    // A response from a directory with a different configuration is rejected
+    let mut wrong_configuration = response;
+    wrong_configuration.configuration_fingerprint = [0u8; DIGEST_BYTES];
+    assert!(crate::client::signed_lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        &signer,
+        AkdLabel::from("hello"),
+        wrong_configuration,
+    )
+    .is_err());
     Ok(())
 }
 
@@ -451,7 +844,7 @@ async fn test_simple_key_history<TC: Configuration>() -> Result<(), AkdError> {
         borked_proof,
         HistoryVerificationParams::default(),
     );
-    assert!(matches!(result, Err(_)), "{}", "{result:?}");
+    assert!(result.is_err(), "{}", "{result:?}");
 
     Ok(())
 }
@@ -540,926 +933,5240 @@ async fn test_complex_verification_many_versions<TC: Configuration>() -> Result<
     Ok(())
 }
 
-// This test is testing the key_history function with a limited history.
-// We also want this update to verify.
-test_config!(test_limited_key_history);
-async fn test_limited_key_history<TC: Configuration>() -> Result<(), AkdError> {
+// This test ensures that a compacted history proof shares sibling proofs between
+// the update proofs, and that it verifies the same as the original proof.
+test_config!(test_compact_key_history);
+async fn test_compact_key_history<TC: Configuration>() -> Result<(), AkdError> {
     let db = AsyncInMemoryDatabase::new();
-    let storage_manager = StorageManager::new_no_cache(db);
+    let storage = StorageManager::new_no_cache(db);
     let vrf = HardCodedAkdVRF {};
-    // epoch 0
-    let akd = Directory::<TC, _, _>::new(storage_manager, vrf).await?;
-
-    // epoch 1
-    akd.publish(vec![
-        (AkdLabel::from("hello"), AkdValue::from("world")),
-        (AkdLabel::from("hello2"), AkdValue::from("world2")),
-    ])
-    .await?;
-
-    // epoch 2
-    akd.publish(vec![
-        (AkdLabel::from("hello"), AkdValue::from("world_2")),
-        (AkdLabel::from("hello2"), AkdValue::from("world2_2")),
-    ])
-    .await?;
-
-    // epoch 3
-    akd.publish(vec![
-        (AkdLabel::from("hello"), AkdValue::from("world3")),
-        (AkdLabel::from("hello2"), AkdValue::from("world4")),
-    ])
-    .await?;
-
-    // epoch 4
-    akd.publish(vec![
-        (AkdLabel::from("hello3"), AkdValue::from("world")),
-        (AkdLabel::from("hello4"), AkdValue::from("world2")),
-    ])
-    .await?;
-
-    // epoch 5
-    akd.publish(vec![(
-        AkdLabel::from("hello"),
-        AkdValue::from("world_updated"),
-    )])
-    .await?;
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
 
-    // epoch 6
-    akd.publish(vec![
-        (AkdLabel::from("hello3"), AkdValue::from("world6")),
-        (AkdLabel::from("hello4"), AkdValue::from("world12")),
-    ])
+    let mut rng = StdRng::seed_from_u64(42);
+    akd.publish(
+        (0..100)
+            .map(|_| (AkdLabel::random(&mut rng), AkdValue::random(&mut rng)))
+            .collect(),
+    )
     .await?;
+    for i in 0..10 {
+        akd.publish(vec![(
+            AkdLabel::from("hello"),
+            AkdValue(format!("world{i}").into_bytes()),
+        )])
+        .await?;
+    }
 
-    // epoch 7
-    akd.publish(vec![
-        (AkdLabel::from("hello3"), AkdValue::from("world7")),
-        (AkdLabel::from("hello4"), AkdValue::from("world13")),
-    ])
-    .await?;
-    // Get the VRF public key
+    let (key_history_proof, EpochHash(current_epoch, root_hash)) = akd
+        .key_history(&AkdLabel::from("hello"), HistoryParams::default())
+        .await?;
     let vrf_pk = akd.get_public_key().await?;
 
-    // Get the current epoch and the current root hash for this akd.
-    let current_azks = akd.retrieve_azks().await?;
-    let current_epoch = current_azks.get_latest_epoch();
-
-    // "hello" was updated in epochs 1,2,3,5. Pull the latest item from the history (i.e. a lookup proof)
-    let (history_proof, root_hash) = akd
-        .key_history(
-            &AkdLabel::from("hello"),
-            HistoryParams::MostRecentInsecure(1),
+    let compact_proof = key_history_proof.compact();
+    let num_sibling_proofs = key_history_proof
+        .update_proofs
+        .iter()
+        .flat_map(|proof| {
+            std::iter::once(&proof.existence_proof).chain(&proof.previous_version_proof)
+        })
+        .chain(
+            key_history_proof
+                .non_existence_until_marker_proofs
+                .iter()
+                .chain(&key_history_proof.non_existence_of_future_marker_proofs)
+                .map(|proof| &proof.longest_prefix_membership_proof),
         )
-        .await?;
-    assert_eq!(1, history_proof.update_proofs.len());
-    assert_eq!(5, history_proof.update_proofs[0].epoch);
+        .map(|proof| proof.sibling_proofs.len())
+        .sum::<usize>();
+    assert!(compact_proof.sibling_proofs.len() < num_sibling_proofs);
+    assert_eq!(key_history_proof, compact_proof.clone().expand()?);
 
-    // Now check that the key history verifies
-    key_history_verify::<TC>(
+    let results = compact_key_history_verify::<TC>(
         vrf_pk.as_bytes(),
-        root_hash.hash(),
+        root_hash,
         current_epoch,
         AkdLabel::from("hello"),
-        history_proof,
+        compact_proof.clone(),
         HistoryVerificationParams::default(),
     )?;
-
-    // Take the top 3 results, and check that we're getting the right epoch updates
-    let (history_proof, root_hash) = akd
-        .key_history(
-            &AkdLabel::from("hello"),
-            HistoryParams::MostRecentInsecure(3),
-        )
-        .await?;
-    assert_eq!(3, history_proof.update_proofs.len());
-    assert_eq!(5, history_proof.update_proofs[0].epoch);
-    assert_eq!(3, history_proof.update_proofs[1].epoch);
-    assert_eq!(2, history_proof.update_proofs[2].epoch);
-
-    // Now check that the key history verifies
-    key_history_verify::<TC>(
+    assert_eq!(10, results.len());
+
+    // A reference outside of the sibling table fails to verify
+    let mut invalid_proof = compact_proof.clone();
+    invalid_proof.update_proofs[0]
+        .existence_proof
+        .sibling_proofs
+        .push(compact_proof.sibling_proofs.len() as u32);
+    assert!(compact_key_history_verify::<TC>(
         vrf_pk.as_bytes(),
-        root_hash.hash(),
+        root_hash,
         current_epoch,
         AkdLabel::from("hello"),
-        history_proof,
+        invalid_proof,
         HistoryVerificationParams::default(),
-    )?;
-
-    // "hello" was updated in epochs 1,2,3,5. Pull the updates since epoch 3 (inclusive)
-    let (history_proof, root_hash) = akd
-        .key_history(
-            &AkdLabel::from("hello"),
-            HistoryParams::SinceEpochInsecure(3),
-        )
-        .await?;
-    assert_eq!(2, history_proof.update_proofs.len());
-    assert_eq!(5, history_proof.update_proofs[0].epoch);
-    assert_eq!(3, history_proof.update_proofs[1].epoch);
-
-    // Now check that the key history verifies
-    key_history_verify::<TC>(
+    )
+    .is_err());
+
+    // A reference to the wrong sibling fails to verify
+    let mut invalid_proof = compact_proof;
+    invalid_proof.update_proofs[0]
+        .existence_proof
+        .sibling_proofs
+        .swap(0, 1);
+    assert!(compact_key_history_verify::<TC>(
         vrf_pk.as_bytes(),
-        root_hash.hash(),
+        root_hash,
         current_epoch,
         AkdLabel::from("hello"),
-        history_proof,
+        invalid_proof,
         HistoryVerificationParams::default(),
-    )?;
+    )
+    .is_err());
 
     Ok(())
 }
 
-// This test covers the tests for PR #224, addresses issue #222: That key history does fail on a small tree,
-// when malicious updates are made.
-// Other that it is just a simple check to see that a valid key history proof passes.
-test_config!(test_malicious_key_history);
-async fn test_malicious_key_history<TC: Configuration>() -> Result<(), AkdError> {
-    // This test has an akd with a single label: "hello", followed by an
-    // insertion of a new label "hello2". Meanwhile, the server has a one epoch
-    // delay in marking the first version for "hello" as stale, which should
-    // be caught by key history verifications for "hello".
+// This test ensures that an epoch-bounded history proof contains exactly the updates
+// needed to prove completeness within the window, and that omitting updates is detected.
+test_config!(test_recent_epochs_key_history);
+async fn test_recent_epochs_key_history<TC: Configuration>() -> Result<(), AkdError> {
     let db = AsyncInMemoryDatabase::new();
     let storage = StorageManager::new_no_cache(db);
     let vrf = HardCodedAkdVRF {};
     let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
-    // Publish the first value for the label "hello"
-    // Epoch here will be 1
-    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world"))])
-        .await?;
-    // Publish the second value for the label "hello" without marking the first value as stale
-    // Epoch here will be 2
-    let corruption_2 = PublishCorruption::UnmarkedStaleVersion(AkdLabel::from("hello"));
-    akd.publish_malicious_update(
-        vec![(AkdLabel::from("hello"), AkdValue::from("world2"))],
-        corruption_2,
-    )
-    .await?;
 
-    // Get the key_history_proof for the label "hello"
-    let (key_history_proof, root_hash) = akd
-        .key_history(&AkdLabel::from("hello"), HistoryParams::default())
-        .await?;
-    // Get the VRF public key
+    // "hello" is updated in epochs 1, 2, 3, 6, and 9, while other labels are updated in every epoch
+    for epoch in 1..=10u64 {
+        let mut updates = vec![(
+            AkdLabel(format!("other{epoch}").into_bytes()),
+            AkdValue::from("value"),
+        )];
+        if [1, 2, 3, 6, 9].contains(&epoch) {
+            updates.push((
+                AkdLabel::from("hello"),
+                AkdValue(format!("world{epoch}").into_bytes()),
+            ));
+        }
+        akd.publish(updates).await?;
+    }
     let vrf_pk = akd.get_public_key().await?;
-    // Verify the key history proof: This should fail since the server did not mark the version 1 for
-    // this username as stale, upon adding version 2.
-    key_history_verify::<TC>(
+
+    // (window size, expected epochs of the returned updates)
+    let cases: [(u64, Vec<u64>); 5] = [
+        (0, vec![9]),
+        (1, vec![9]),
+        (2, vec![9, 6]),
+        (5, vec![9, 6, 3]),
+        (8, vec![9, 6, 3, 2]),
+    ];
+    for (num_epochs, expected_epochs) in cases {
+        let (proof, EpochHash(current_epoch, root_hash)) = akd
+            .key_history(
+                &AkdLabel::from("hello"),
+                HistoryParams::RecentEpochs(num_epochs),
+            )
+            .await?;
+        let results = key_history_verify_recent_epochs::<TC>(
+            vrf_pk.as_bytes(),
+            root_hash,
+            current_epoch,
+            AkdLabel::from("hello"),
+            proof,
+            num_epochs,
+            HistoryVerificationParams::default(),
+        )?;
+        assert_eq!(
+            expected_epochs,
+            results
+                .iter()
+                .map(|result| result.epoch)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    // A window reaching back before the first update includes the complete history
+    let (proof, EpochHash(current_epoch, root_hash)) = akd
+        .key_history(&AkdLabel::from("hello"), HistoryParams::RecentEpochs(100))
+        .await?;
+    assert_eq!(5, proof.update_proofs.len());
+    key_history_verify_recent_epochs::<TC>(
         vrf_pk.as_bytes(),
-        root_hash.hash(),
-        root_hash.epoch(),
+        root_hash,
+        current_epoch,
         AkdLabel::from("hello"),
-        key_history_proof,
+        proof,
+        100,
         HistoryVerificationParams::default(),
-    ).expect_err("The key history proof should fail here since the previous value was not marked stale at all");
+    )?;
 
-    // Mark the first value for the label "hello" as stale
-    // Epoch here will be 3
-    let corruption_3 = PublishCorruption::MarkVersionStale(AkdLabel::from("hello"), 1);
-    akd.publish_malicious_update(
-        vec![(AkdLabel::from("hello2"), AkdValue::from("world"))],
-        corruption_3,
+    // Omitting the updates at the start of the window is detected
+    let (mut proof, EpochHash(current_epoch, root_hash)) = akd
+        .key_history(&AkdLabel::from("hello"), HistoryParams::RecentEpochs(8))
+        .await?;
+    proof.update_proofs.truncate(2);
+    assert!(key_history_verify_recent_epochs::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        current_epoch,
+        AkdLabel::from("hello"),
+        proof,
+        8,
+        HistoryVerificationParams::default(),
     )
-    .await?;
+    .is_err());
 
-    // Get the key_history_proof for the label "hello"
-    let (key_history_proof, root_hash) = akd
-        .key_history(&AkdLabel::from("hello"), HistoryParams::default())
+    // A proof for only the most recent update does not cover a longer window
+    let (proof, EpochHash(current_epoch, root_hash)) = akd
+        .key_history(
+            &AkdLabel::from("hello"),
+            HistoryParams::MostRecentInsecure(1),
+        )
         .await?;
-    // Get the VRF public key
-    let vrf_pk = akd.get_public_key().await?;
-    // Verify the key history proof: This should still fail, since the server added the version number too late.
-    key_history_verify::<TC>(
+    assert!(key_history_verify_recent_epochs::<TC>(
         vrf_pk.as_bytes(),
-        root_hash.hash(),
-        root_hash.epoch(),
+        root_hash,
+        current_epoch,
         AkdLabel::from("hello"),
-        key_history_proof,
+        proof,
+        5,
         HistoryVerificationParams::default(),
-    ).expect_err("The key history proof should fail here since the previous value was marked stale one epoch too late.");
+    )
+    .is_err());
 
     Ok(())
 }
 
-// This test ensures valid audit proofs pass for various epochs and
-// that invalid audit proofs fail.
-test_config!(test_simple_audit);
-async fn test_simple_audit<TC: Configuration>() -> Result<(), AkdError> {
+// Checks that corrupted interior nodes can be recovered by rebuilding the tree from
+// the stored value states, and that a rebuild which does not reproduce the recorded
+// root hash is rejected without modifying storage
+test_config!(test_rebuild_from_values);
+async fn test_rebuild_from_values<TC: Configuration>() -> Result<(), AkdError> {
     let db = AsyncInMemoryDatabase::new();
-    let storage = StorageManager::new_no_cache(db);
+    let storage = StorageManager::new_no_cache(db.clone());
     let vrf = HardCodedAkdVRF {};
     let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
 
-    akd.publish(vec![
-        (AkdLabel::from("hello"), AkdValue::from("world")),
-        (AkdLabel::from("hello2"), AkdValue::from("world2")),
-    ])
-    .await?;
-
-    // Get the root hash after the first server publish
-    let root_hash_1 = akd.get_epoch_hash().await?.1;
-
-    akd.publish(vec![
-        (AkdLabel::from("hello"), AkdValue::from("world_2")),
-        (AkdLabel::from("hello2"), AkdValue::from("world2_2")),
-    ])
-    .await?;
+    for epoch in 1..=3u64 {
+        akd.publish(
+            (0..10)
+                .filter(|i| epoch == 1 || i % epoch == 0)
+                .map(|i| {
+                    (
+                        AkdLabel(format!("label{i}").into_bytes()),
+                        AkdValue(format!("value{i}.{epoch}").into_bytes()),
+                    )
+                })
+                .collect(),
+        )
+        .await?;
+    }
+    let EpochHash(_, root_hash) = akd.get_epoch_hash().await?;
+    let mut original_records = db.batch_get_all_direct().await?;
+    original_records.retain(|record| !matches!(record, DbRecord::OperationRecord(_)));
+    original_records.sort();
+
+    // Corrupt the hashes of all of the interior nodes
+    let corrupted_records = original_records
+        .iter()
+        .filter_map(|record| match record {
+            DbRecord::TreeNode(node) if node.latest_node.node_type == TreeNodeType::Interior => {
+                let mut node = node.clone();
+                node.latest_node.hash = AzksValue([0u8; DIGEST_BYTES]);
+                if let Some(previous_node) = node.previous_node.as_mut() {
+                    previous_node.hash = AzksValue([0u8; DIGEST_BYTES]);
+                }
+                Some(DbRecord::TreeNode(node))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert!(!corrupted_records.is_empty());
+    db.batch_set(corrupted_records, DbSetState::General).await?;
 
-    // Get the root hash after the second server publish
-    let root_hash_2 = akd.get_epoch_hash().await?.1;
+    let vrf_pk = akd.get_public_key().await?;
+    let (proof, epoch_hash) = akd.lookup(AkdLabel::from("label0")).await?;
+    assert!(lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        epoch_hash.hash(),
+        epoch_hash.epoch(),
+        AkdLabel::from("label0"),
+        proof,
+    )
+    .is_err());
+
+    // The rebuild restores every record to its original state
+    assert_eq!(EpochHash(3, root_hash), akd.rebuild_from_values().await?);
+    // The operations log records the rebuild, and so is excluded from the comparison
+    let mut rebuilt_records = db.batch_get_all_direct().await?;
+    rebuilt_records.retain(|record| !matches!(record, DbRecord::OperationRecord(_)));
+    rebuilt_records.sort();
+    assert_eq!(original_records, rebuilt_records);
+
+    // A value state which does not match the tree leads to a different root hash
+    let mut state = db
+        .get_user_state(
+            &AkdLabel::from("label1"),
+            ValueStateRetrievalFlag::SpecificVersion(1),
+        )
+        .await?;
+    state.value = AkdValue::from("tampered");
+    db.set(DbRecord::ValueState(state)).await?;
+    assert!(matches!(
+        akd.rebuild_from_values().await,
+        Err(AkdError::Directory(DirectoryError::Rebuild(_)))
+    ));
+    let mut records = db.batch_get_all_direct().await?;
+    records.sort();
+    assert_eq!(
+        original_records
+            .iter()
+            .filter(|record| matches!(record, DbRecord::TreeNode(_)))
+            .collect::<Vec<_>>(),
+        records
+            .iter()
+            .filter(|record| matches!(record, DbRecord::TreeNode(_)))
+            .collect::<Vec<_>>()
+    );
 
-    akd.publish(vec![
-        (AkdLabel::from("hello"), AkdValue::from("world3")),
-        (AkdLabel::from("hello2"), AkdValue::from("world4")),
-    ])
-    .await?;
+    Ok(())
+}
 
-    // Get the root hash after the third server publish
-    let root_hash_3 = akd.get_epoch_hash().await?.1;
+// Checks that rolling back the directory restores the exact storage state of the earlier
+// epoch, produces a verifiable rollback record, and allows publishing to resume
+test_config!(test_rollback_to);
+async fn test_rollback_to<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db.clone());
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+    let signer = HashEpochSigner::<TC>(std::marker::PhantomData);
+
+    let updates_for_epoch = |epoch: u64| {
+        (0..10)
+            .filter(|i| epoch == 1 || i % epoch == 0)
+            .map(|i| {
+                (
+                    AkdLabel(format!("label{i}").into_bytes()),
+                    AkdValue(format!("value{i}.{epoch}").into_bytes()),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    akd.publish(updates_for_epoch(1)).await?;
+    let EpochHash(_, rollback_root_hash) = akd.publish(updates_for_epoch(2)).await?;
+    let mut rollback_records = db.batch_get_all_direct().await?;
+    rollback_records.retain(|record| !matches!(record, DbRecord::OperationRecord(_)));
+    rollback_records.sort();
+    akd.publish(updates_for_epoch(3)).await?;
+    let EpochHash(_, head_root_hash) = akd.publish(updates_for_epoch(4)).await?;
+
+    // Only earlier epochs can be rolled back to
+    assert!(matches!(
+        akd.rollback_to(4, &signer).await,
+        Err(AkdError::Directory(DirectoryError::InvalidEpoch(_)))
+    ));
 
-    akd.publish(vec![
-        (AkdLabel::from("hello3"), AkdValue::from("world")),
-        (AkdLabel::from("hello4"), AkdValue::from("world2")),
-    ])
-    .await?;
+    let record = akd.rollback_to(2, &signer).await?;
+    assert_eq!(
+        (4, head_root_hash),
+        (record.from_epoch, record.from_root_hash)
+    );
+    assert_eq!(
+        (2, rollback_root_hash),
+        (record.to_epoch, record.to_root_hash)
+    );
+    assert!(akd_core::signing::verify_rollback_record::<TC>(
+        &signer, &record
+    ));
+    let mut tampered = record.clone();
+    tampered.to_root_hash = head_root_hash;
+    assert!(!akd_core::signing::verify_rollback_record::<TC>(
+        &signer, &tampered
+    ));
 
-    // Get the root hash after the fourth server publish
-    let root_hash_4 = akd.get_epoch_hash().await?.1;
+    // The operations log is append-only, and so is not rolled back
+    let mut records = db.batch_get_all_direct().await?;
+    records.retain(|record| !matches!(record, DbRecord::OperationRecord(_)));
+    records.sort();
+    assert_eq!(rollback_records, records);
+    assert_eq!(
+        EpochHash(2, rollback_root_hash),
+        akd.get_epoch_hash().await?
+    );
 
+    // Publishing resumes from the reinstated epoch
     akd.publish(vec![(
-        AkdLabel::from("hello"),
-        AkdValue::from("world_updated"),
+        AkdLabel::from("label3"),
+        AkdValue::from("new value"),
     )])
     .await?;
+    let vrf_pk = akd.get_public_key().await?;
+    let (proof, EpochHash(current_epoch, root_hash)) = akd
+        .key_history(&AkdLabel::from("label3"), HistoryParams::default())
+        .await?;
+    let results = key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        current_epoch,
+        AkdLabel::from("label3"),
+        proof,
+        HistoryVerificationParams::default(),
+    )?;
+    assert_eq!(
+        vec![
+            (3, AkdValue::from("new value")),
+            (1, AkdValue::from("value3.1"))
+        ],
+        results
+            .into_iter()
+            .map(|result| (result.epoch, result.value))
+            .collect::<Vec<_>>()
+    );
+
+    Ok(())
+}
 
-    // Get the root hash after the fifth server publish
-    let root_hash_5 = akd.get_epoch_hash().await?.1;
+test_config!(test_linked_key_history);
+async fn test_linked_key_history<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
 
+    akd.publish(vec![(AkdLabel::from("a"), AkdValue::from("a1"))])
+        .await?;
     akd.publish(vec![
-        (AkdLabel::from("hello3"), AkdValue::from("world6")),
-        (AkdLabel::from("hello4"), AkdValue::from("world12")),
+        (AkdLabel::from("a"), AkdValue::from("a2")),
+        (AkdLabel::from("other"), AkdValue::from("other")),
     ])
     .await?;
-
-    // Get the root hash after the 6th server publish
-    let root_hash_6 = akd.get_epoch_hash().await?.1;
-
-    // This is to ensure that an audit of two consecutive, although relatively old epochs is calculated correctly.
-    let audit_proof_1 = akd.audit(1, 2).await?;
-    audit_verify::<TC>(vec![root_hash_1, root_hash_2], audit_proof_1).await?;
-
-    // This is to ensure that an audit of 3 consecutive epochs although not the most recent is calculated correctly.
-    let audit_proof_2 = akd.audit(1, 3).await?;
-    audit_verify::<TC>(vec![root_hash_1, root_hash_2, root_hash_3], audit_proof_2).await?;
-
-    // This is to ensure that an audit of 4 consecutive epochs is calculated correctly.
-    let audit_proof_3 = akd.audit(1, 4).await?;
-    audit_verify::<TC>(
-        vec![root_hash_1, root_hash_2, root_hash_3, root_hash_4],
-        audit_proof_3,
+    akd.rename_label(
+        AkdLabel::from("a"),
+        AkdLabel::from("b"),
+        AkdValue::from("b1"),
     )
     .await?;
-
-    // This is to ensure that an audit of 5 consecutive epochs is calculated correctly.
-    let audit_proof_4 = akd.audit(1, 5).await?;
-    audit_verify::<TC>(
-        vec![
-            root_hash_1,
-            root_hash_2,
-            root_hash_3,
-            root_hash_4,
-            root_hash_5,
-        ],
-        audit_proof_4,
+    akd.publish(vec![(AkdLabel::from("b"), AkdValue::from("b2"))])
+        .await?;
+    akd.rename_label(
+        AkdLabel::from("b"),
+        AkdLabel::from("c"),
+        AkdValue::from("c1"),
     )
     .await?;
 
-    // Test correct audit of two consecutive epochs but not starting at epoch 1.
-    let audit_proof_5 = akd.audit(2, 3).await?;
-    audit_verify::<TC>(vec![root_hash_2, root_hash_3], audit_proof_5).await?;
+    // A label can only be renamed once, and only to an unregistered label
+    assert!(matches!(
+        akd.rename_label(
+            AkdLabel::from("a"),
+            AkdLabel::from("d"),
+            AkdValue::from("d1")
+        )
+        .await,
+        Err(AkdError::Directory(DirectoryError::Publish(_)))
+    ));
+    assert!(matches!(
+        akd.rename_label(
+            AkdLabel::from("c"),
+            AkdLabel::from("other"),
+            AkdValue::from("c2")
+        )
+        .await,
+        Err(AkdError::Directory(DirectoryError::LabelRegistered(_)))
+    ));
 
-    // Test correct audit of 3 consecutive epochs but not starting at epoch 1.
-    let audit_proof_6 = akd.audit(2, 4).await?;
-    audit_verify::<TC>(vec![root_hash_2, root_hash_3, root_hash_4], audit_proof_6).await?;
+    // Users cannot publish links, whether directly or as the value of a renamed label
+    assert!(matches!(
+        akd.publish(vec![(
+            AkdLabel::from("other"),
+            AkdValue::link_to(&AkdLabel::from("c"))
+        )])
+        .await,
+        Err(AkdError::Directory(DirectoryError::Publish(_)))
+    ));
+    assert!(matches!(
+        akd.rename_label(
+            AkdLabel::from("other"),
+            AkdLabel::from("d"),
+            AkdValue::link_to(&AkdLabel::from("c"))
+        )
+        .await,
+        Err(AkdError::Directory(DirectoryError::Publish(_)))
+    ));
 
-    // Test correct audit of 3 consecutive epochs ending at epoch 6 -- the last epoch
-    let audit_proof_7 = akd.audit(4, 6).await?;
-    audit_verify::<TC>(vec![root_hash_4, root_hash_5, root_hash_6], audit_proof_7).await?;
+    // The old label now resolves to a link to the new one
+    let (proof, _) = akd.lookup(AkdLabel::from("a")).await?;
+    assert_eq!(Some(AkdLabel::from("b")), proof.value.link_target());
 
-    // The audit_verify function should throw an AuditorError when the proof has a different
-    // number of epochs than needed for hashes
-    let audit_proof_8 = akd.audit(4, 6).await?;
-    let invalid_audit_verification = audit_verify::<TC>(
+    let vrf_pk = akd.get_public_key().await?;
+    let (proof, EpochHash(epoch, root_hash)) = akd
+        .linked_key_history(&AkdLabel::from("a"), HistoryParams::default())
+        .await?;
+    assert_eq!(3, proof.segments.len());
+    let results = linked_key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("a"),
+        proof.clone(),
+        HistoryVerificationParams::default(),
+    )?;
+    assert_eq!(
         vec![
-            root_hash_1,
-            root_hash_2,
-            root_hash_3,
-            root_hash_4,
-            root_hash_5,
+            (AkdLabel::from("c"), 5, AkdValue::from("c1")),
+            (AkdLabel::from("b"), 4, AkdValue::from("b2")),
+            (AkdLabel::from("b"), 3, AkdValue::from("b1")),
+            (AkdLabel::from("a"), 2, AkdValue::from("a2")),
+            (AkdLabel::from("a"), 1, AkdValue::from("a1")),
         ],
-        audit_proof_8,
+        results
+            .into_iter()
+            .map(|linked| (linked.label, linked.result.epoch, linked.result.value))
+            .collect::<Vec<_>>()
+    );
+
+    // The chain of links cannot be cut short, or started from a different label
+    let mut truncated = proof.clone();
+    truncated.segments.pop();
+    assert!(linked_key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("a"),
+        truncated,
+        HistoryVerificationParams::default(),
     )
-    .await;
-    assert!(matches!(
-        invalid_audit_verification,
-        Err(AkdError::AuditErr(_))
-    ));
+    .is_err());
+    assert!(linked_key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("b"),
+        proof,
+        HistoryVerificationParams::default(),
+    )
+    .is_err());
+
+    Ok(())
+}
+
+test_config!(test_publish_with_limits);
+async fn test_publish_with_limits<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
 
-    // The audit_verify function should throw an AuditorError when the proof does not have the same
-    // number of epochs as proofs
-    let audit_proof_9 = akd.audit(1, 5).await?;
-    let audit_proof_10 = akd.audit(4, 6).await?;
-    let invalid_audit_proof = AppendOnlyProof {
-        proofs: audit_proof_10.proofs,
-        epochs: audit_proof_9.epochs,
+    for epoch in 1..=3 {
+        let mut updates = vec![(
+            AkdLabel::from("label0"),
+            AkdValue(format!("value0.{epoch}").into_bytes()),
+        )];
+        if epoch == 1 {
+            updates.push((AkdLabel::from("label1"), AkdValue::from("value1.1")));
+        }
+        akd.publish(updates).await?;
+    }
+    let updates = vec![
+        (AkdLabel::from("label0"), AkdValue::from("value0.4")),
+        (AkdLabel::from("label1"), AkdValue::from("value1.4")),
+    ];
+
+    // A publish which exceeds the limits is rejected by default
+    let mut limits = PublishLimits {
+        max_versions: Some(3),
+        ..Default::default()
     };
-    let invalid_audit_verification = audit_verify::<TC>(
-        vec![
-            root_hash_1,
-            root_hash_2,
-            root_hash_3,
-            root_hash_4,
-            root_hash_5,
-        ],
-        invalid_audit_proof,
-    )
-    .await;
     assert!(matches!(
-        invalid_audit_verification,
-        Err(AkdError::AuditErr(_))
+        akd.publish_with_limits(updates.clone(), &limits).await,
+        Err(AkdError::Directory(DirectoryError::Publish(_)))
     ));
+    assert_eq!(3, akd.get_epoch_hash().await?.epoch());
 
-    // The verify_consecutive_append_only function should throw an AzksErr error when the computed
-    // end root hash is not equal to the end hash
-    let audit_proof_11 = akd.audit(1, 2).await?;
-    let verification = verify_consecutive_append_only::<TC>(
-        &audit_proof_11.proofs[0],
-        root_hash_1,
-        root_hash_3, // incorrect end hash - should be root_hash_2
-        audit_proof_11.epochs[0] + 1,
-    )
-    .await;
-    assert!(matches!(verification, Err(AkdError::AzksErr(_))));
-
-    // The audit should be of more than 1 epoch
-    let invalid_audit = akd.audit(3, 3).await;
-    assert!(matches!(invalid_audit, Err(_)));
+    // When truncating, only the violating updates are dropped
+    limits.enforcement = LimitEnforcement::Truncate;
+    let result = akd.publish_with_limits(updates, &limits).await?;
+    assert_eq!(4, result.epoch_hash.epoch());
+    assert_eq!(
+        vec![LimitViolation::MaxVersions {
+            label: AkdLabel::from("label0"),
+            max_versions: 3,
+        }],
+        result.violations
+    );
+    let (proof, _) = akd.lookup(AkdLabel::from("label0")).await?;
+    assert_eq!(3, proof.version);
+    let (proof, _) = akd.lookup(AkdLabel::from("label1")).await?;
+    assert_eq!(2, proof.version);
+
+    // label1 was updated in epoch 4, which is within the window for epoch 5
+    let rate_limit = RateLimit {
+        max_updates: 1,
+        window_epochs: 2,
+    };
+    let limits = PublishLimits {
+        rate_limit: Some(rate_limit),
+        enforcement: LimitEnforcement::Truncate,
+        ..Default::default()
+    };
+    let result = akd
+        .publish_with_limits(
+            vec![
+                (AkdLabel::from("label1"), AkdValue::from("value1.5")),
+                (AkdLabel::from("label2"), AkdValue::from("value2.5")),
+            ],
+            &limits,
+        )
+        .await?;
+    assert_eq!(
+        vec![LimitViolation::RateLimit {
+            label: AkdLabel::from("label1"),
+            rate_limit,
+        }],
+        result.violations
+    );
 
-    // The audit epochs must be increasing
-    let invalid_audit = akd.audit(3, 2).await;
-    assert!(matches!(invalid_audit, Err(_)));
+    // The window for epoch 7 no longer includes epoch 4
+    akd.publish(vec![(AkdLabel::from("label2"), AkdValue::from("value2.6"))])
+        .await?;
+    let result = akd
+        .publish_with_limits(
+            vec![(AkdLabel::from("label1"), AkdValue::from("value1.7"))],
+            &limits,
+        )
+        .await?;
+    assert_eq!(7, result.epoch_hash.epoch());
+    assert!(result.violations.is_empty());
 
-    // The audit should throw an error when queried for an epoch which hasn't yet taken place!
-    let invalid_audit = akd.audit(6, 7).await;
-    assert!(matches!(invalid_audit, Err(_)));
+    // A batch which brings its labels to the limit can be resubmitted, since its updates no
+    // longer change the values of their labels
+    let limits = PublishLimits {
+        max_versions: Some(1),
+        ..Default::default()
+    };
+    let updates = vec![(AkdLabel::from("label3"), AkdValue::from("value3.8"))];
+    for _ in 0..2 {
+        let result = akd.publish_with_limits(updates.clone(), &limits).await?;
+        assert_eq!(8, result.epoch_hash.epoch());
+        assert!(result.violations.is_empty());
+    }
+    let (proof, _) = akd.lookup(AkdLabel::from("label3")).await?;
+    assert_eq!(1, proof.version);
 
     Ok(())
 }
 
-test_config!(test_read_during_publish);
-async fn test_read_during_publish<TC: Configuration>() -> Result<(), AkdError> {
+// Checks that labels and values which exceed the length limits, or labels with disallowed
+// bytes, are reported with the reason they were rejected
+test_config!(test_publish_content_limits);
+async fn test_publish_content_limits<TC: Configuration>() -> Result<(), AkdError> {
+    use crate::directory::BytePolicy;
+
     let db = AsyncInMemoryDatabase::new();
-    let storage = StorageManager::new_no_cache(db.clone());
+    let storage = StorageManager::new_no_cache(db);
     let vrf = HardCodedAkdVRF {};
     let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
 
-    // Publish once
-    akd.publish(vec![
-        (AkdLabel::from("hello"), AkdValue::from("world")),
-        (AkdLabel::from("hello2"), AkdValue::from("world2")),
-    ])
-    .await
-    .unwrap();
-    // Get the root hash after the first publish
-    let root_hash_1 = akd.get_epoch_hash().await?.1;
-    // Publish updates for the same labels.
-    akd.publish(vec![
-        (AkdLabel::from("hello"), AkdValue::from("world_2")),
-        (AkdLabel::from("hello2"), AkdValue::from("world2_2")),
-    ])
-    .await
-    .unwrap();
-
-    // Get the root hash after the second publish
-    let root_hash_2 = akd.get_epoch_hash().await?.1;
-
-    // Make the current azks a "checkpoint" to reset to later
-    let checkpoint_azks = akd.retrieve_azks().await.unwrap();
+    let updates = vec![
+        (AkdLabel::from("valid"), AkdValue::from("value")),
+        (AkdLabel::from("too long"), AkdValue::from("value")),
+        (AkdLabel::from("tab\t"), AkdValue::from("value")),
+        (AkdLabel::from("large"), AkdValue(vec![0u8; 17])),
+    ];
+    let mut limits = PublishLimits {
+        max_label_bytes: Some(6),
+        max_value_bytes: Some(16),
+        label_bytes: BytePolicy::PrintableAscii,
+        ..Default::default()
+    };
+    let expected = vec![
+        LimitViolation::LabelTooLong {
+            label: AkdLabel::from("too long"),
+            max_label_bytes: 6,
+        },
+        LimitViolation::DisallowedLabelByte {
+            label: AkdLabel::from("tab\t"),
+            index: 3,
+            byte: b'\t',
+        },
+        LimitViolation::ValueTooLong {
+            label: AkdLabel::from("large"),
+            value_bytes: 17,
+            max_value_bytes: 16,
+        },
+    ];
 
-    // Publish for the third time
-    akd.publish(vec![
-        (AkdLabel::from("hello"), AkdValue::from("world_3")),
-        (AkdLabel::from("hello2"), AkdValue::from("world2_3")),
-    ])
-    .await
-    .unwrap();
+    assert!(matches!(
+        akd.publish_with_limits(updates.clone(), &limits).await,
+        Err(AkdError::Directory(DirectoryError::Publish(_)))
+    ));
+    limits.enforcement = LimitEnforcement::Truncate;
+    let result = akd.publish_with_limits(updates, &limits).await?;
+    assert_eq!(expected, result.violations);
+    assert_eq!(
+        vec!["max_label_bytes", "label_bytes", "max_value_bytes"],
+        result
+            .violations
+            .iter()
+            .map(LimitViolation::limit)
+            .collect::<Vec<_>>()
+    );
+    akd.lookup(AkdLabel::from("valid")).await?;
+    assert!(akd.lookup(AkdLabel::from("large")).await.is_err());
+
+    // The same limits can be checked as labels and values are created
+    assert!(AkdLabel::new("valid", &limits.label_constraints()).is_ok());
+    assert!(AkdLabel::new("too long", &limits.label_constraints()).is_err());
+    assert!(AkdLabel::new("tab\t", &limits.label_constraints()).is_err());
+    assert!(AkdValue::new([0u8; 16], &limits.value_constraints()).is_ok());
+    assert!(AkdValue::new([0u8; 17], &limits.value_constraints()).is_err());
+
+    // The byte policies report the first disallowed byte
+    assert_eq!(None, BytePolicy::Any.first_disallowed(b"\x00\xff"));
+    assert_eq!(
+        None,
+        BytePolicy::Utf8.first_disallowed("caf\u{e9}".as_bytes())
+    );
+    assert_eq!(
+        Some((3, 0xc3)),
+        BytePolicy::PrintableAscii.first_disallowed("caf\u{e9}".as_bytes())
+    );
+    assert_eq!(Some((1, 0xff)), BytePolicy::Utf8.first_disallowed(b"a\xff"));
+    assert_eq!(Some((1, 0x07)), BytePolicy::Utf8.first_disallowed(b"a\x07"));
+    assert_eq!(
+        Some((2, b'-')),
+        BytePolicy::Only(b"0123456789").first_disallowed(b"12-34")
+    );
 
-    // Reset the azks record back to previous epoch, to emulate an akd reader
-    // communicating with storage that is in the middle of a publish operation
-    db.set(DbRecord::Azks(checkpoint_azks))
-        .await
-        .expect("Error resetting directory to previous epoch");
+    Ok(())
+}
 
-    // re-create the directory instance so it refreshes from storage
-    let storage = StorageManager::new_no_cache(db.clone());
+// Checks that updates which would not change the values of their labels are reported as
+// skipped, and do not create new versions or epochs
+test_config!(test_publish_skips_unchanged_values);
+async fn test_publish_skips_unchanged_values<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
     let vrf = HardCodedAkdVRF {};
-    let akd = ReadOnlyDirectory::<TC, _, _>::new(storage, vrf)
-        .await
-        .unwrap();
-
-    // Get the VRF public key
-    let vrf_pk = akd.get_public_key().await.unwrap();
-
-    // Lookup proof should contain the checkpoint epoch's value and still verify
-    let (lookup_proof, root_hash) = akd.lookup(AkdLabel::from("hello")).await.unwrap();
-    assert_eq!(AkdValue::from("world_2"), lookup_proof.value);
-    lookup_verify::<TC>(
-        vrf_pk.as_bytes(),
-        root_hash.hash(),
-        root_hash.epoch(),
-        AkdLabel::from("hello"),
-        lookup_proof,
-    )
-    .unwrap();
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+    let limits = PublishLimits::default();
 
-    // History proof should not contain the third epoch's update but still verify
-    let (history_proof, root_hash) = akd
-        .key_history(&AkdLabel::from("hello"), HistoryParams::default())
-        .await
-        .unwrap();
-    key_history_verify::<TC>(
-        vrf_pk.as_bytes(),
-        root_hash.hash(),
-        root_hash.epoch(),
-        AkdLabel::from("hello"),
-        history_proof,
-        HistoryVerificationParams::default(),
-    )
-    .unwrap();
+    akd.publish(vec![
+        (AkdLabel::from("label0"), AkdValue::from("value0")),
+        (AkdLabel::from("label1"), AkdValue::from("value1")),
+    ])
+    .await?;
 
-    // Audit proof should only work up until checkpoint's epoch
-    let audit_proof = akd.audit(1, 2).await.unwrap();
-    audit_verify::<TC>(vec![root_hash_1, root_hash_2], audit_proof)
-        .await
-        .unwrap();
+    // Retrying the same updates publishes nothing
+    let result = akd
+        .publish_with_limits(
+            vec![
+                (AkdLabel::from("label0"), AkdValue::from("value0")),
+                (AkdLabel::from("label1"), AkdValue::from("value1")),
+            ],
+            &limits,
+        )
+        .await?;
+    assert_eq!(1, result.epoch_hash.epoch());
+    assert_eq!(
+        vec![AkdLabel::from("label0"), AkdLabel::from("label1")],
+        result.skipped
+    );
 
-    let invalid_audit = akd.audit(2, 3).await;
-    assert!(matches!(invalid_audit, Err(_)));
+    // Only the updates which change their values are published
+    let result = akd
+        .publish_with_limits(
+            vec![
+                (AkdLabel::from("label0"), AkdValue::from("value0")),
+                (AkdLabel::from("label1"), AkdValue::from("value1.2")),
+                (AkdLabel::from("label2"), AkdValue::from("value2")),
+            ],
+            &limits,
+        )
+        .await?;
+    assert_eq!(2, result.epoch_hash.epoch());
+    assert_eq!(vec![AkdLabel::from("label0")], result.skipped);
+    assert!(result.violations.is_empty());
+    let (proof, _) = akd.lookup(AkdLabel::from("label0")).await?;
+    assert_eq!((1, 1), (proof.version, proof.epoch));
+    let (proof, _) = akd.lookup(AkdLabel::from("label1")).await?;
+    assert_eq!((2, 2), (proof.version, proof.epoch));
 
     Ok(())
 }
 
-// The read-only mode of a directory is meant to simply read from memory.
-// This test makes sure it throws errors appropriately, i.e. when trying to
-// write to a read-only directory and when trying to read a directory when none
-// exists in storage.
-test_config!(test_directory_read_only_mode);
-async fn test_directory_read_only_mode<TC: Configuration>() -> Result<(), AkdError> {
+test_config!(test_publish_queued);
+async fn test_publish_queued<TC: Configuration>() -> Result<(), AkdError> {
     let db = AsyncInMemoryDatabase::new();
     let storage = StorageManager::new_no_cache(db);
     let vrf = HardCodedAkdVRF {};
-    // There is no AZKS object in the storage layer, directory construction should fail
-    let akd = ReadOnlyDirectory::<TC, _, _>::new(storage, vrf).await;
-    assert!(matches!(akd, Err(_)));
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+    let queue = PublishQueue::new();
+
+    queue.submit(AkdLabel::from("label0"), AkdValue::from("first"));
+    queue.submit(AkdLabel::from("label1"), AkdValue::from("value"));
+    queue.submit(AkdLabel::from("label0"), AkdValue::from("second"));
+    assert_eq!(2, queue.len());
+
+    let EpochHash(epoch, root_hash) = akd.publish_queued(&queue).await?;
+    assert_eq!(1, epoch);
+    assert!(queue.is_empty());
+    assert_eq!(
+        PublishQueueMetrics {
+            submitted: 3,
+            dropped_duplicates: 1,
+            published: 2,
+        },
+        queue.metrics()
+    );
+
+    // Only the latest submission for the label was published
+    let vrf_pk = akd.get_public_key().await?;
+    let (proof, _) = akd.lookup(AkdLabel::from("label0")).await?;
+    let result = lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("label0"),
+        proof,
+    )?;
+    assert_eq!(
+        (1, AkdValue::from("second")),
+        (result.version, result.value)
+    );
 
     Ok(())
 }
 
-// This test is meant to test the function poll_for_azks_change
-// which is meant to detect changes in the azks, to prevent inconsistencies
-// between the local cache and storage.
-test_config!(test_directory_polling_azks_change);
-async fn test_directory_polling_azks_change<TC: Configuration>() -> Result<(), AkdError> {
+test_config!(test_maintenance_tasks);
+async fn test_maintenance_tasks<TC: Configuration>() -> Result<(), AkdError> {
     let db = AsyncInMemoryDatabase::new();
-    let storage = StorageManager::new(db, None, None, None);
+    let storage = StorageManager::new(db.clone(), Some(Duration::from_millis(50)), None, None);
     let vrf = HardCodedAkdVRF {};
-    // writer will write the AZKS record
-    let writer = Directory::<TC, _, _>::new(storage.clone(), vrf.clone()).await?;
+    let akd = Directory::<TC, _, _>::new(storage.clone(), vrf).await?;
 
-    writer
-        .publish(vec![
-            (AkdLabel::from("hello"), AkdValue::from("world")),
-            (AkdLabel::from("hello2"), AkdValue::from("world2")),
-        ])
+    for epoch in 1..=3 {
+        akd.publish(
+            (0..4)
+                .map(|i| {
+                    (
+                        AkdLabel(format!("label{i}").into_bytes()),
+                        AkdValue(format!("value{i}.{epoch}").into_bytes()),
+                    )
+                })
+                .collect(),
+        )
         .await?;
+    }
 
-    // reader will not write the AZKS but will be "polling" for AZKS changes
-    let reader = ReadOnlyDirectory::<TC, _, _>::new(storage, vrf).await?;
+    // Warming the cache loads at most the 7 nodes in the top 3 levels of the tree
+    storage.flush_cache().await;
+    let report = akd
+        .run_maintenance_task(MaintenanceTask::CacheWarmup { depth: 2 })
+        .await?;
+    assert!(report.records > 0 && report.records <= 7);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let report = akd
+        .run_maintenance_task(MaintenanceTask::Compaction)
+        .await?;
+    assert!(report.records > 0);
 
-    // start the poller
-    let (tx, mut rx) = tokio::sync::mpsc::channel(10);
-    let reader_clone = reader.clone();
-    let _join_handle = tokio::task::spawn(async move {
-        reader_clone
-            .poll_for_azks_changes(tokio::time::Duration::from_millis(100), Some(tx))
-            .await
+    // Only the versions published in epochs 1 and 2 are pruned, and lookups still verify
+    let report = akd
+        .run_maintenance_task(MaintenanceTask::Pruning { retain_epochs: 1 })
+        .await?;
+    assert_eq!(8, report.records);
+    let tombstoned = db
+        .batch_get_type_direct::<ValueState>()
+        .await?
+        .into_iter()
+        .filter(|record| {
+            matches!(record, DbRecord::ValueState(state) if state.value.0 == crate::TOMBSTONE)
+        })
+        .count();
+    assert_eq!(8, tombstoned);
+    let report = akd
+        .run_maintenance_task(MaintenanceTask::Pruning { retain_epochs: 1 })
+        .await?;
+    assert_eq!(0, report.records);
+    let vrf_pk = akd.get_public_key().await?;
+    let (proof, EpochHash(epoch, root_hash)) = akd.lookup(AkdLabel::from("label0")).await?;
+    lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("label0"),
+        proof,
+    )?;
+
+    let report = akd
+        .run_maintenance_task(MaintenanceTask::IntegrityCheck { sample_size: 5 })
+        .await?;
+    assert!(report.records >= 5);
+
+    // The scheduled tasks run in the background until they are shut down
+    storage.flush_cache().await;
+    let handle = akd.spawn_maintenance(MaintenanceConfig {
+        tasks: vec![ScheduledTask {
+            task: MaintenanceTask::CacheWarmup { depth: 0 },
+            interval: Duration::from_millis(1),
+            jitter: Duration::from_millis(1),
+        }],
+        max_concurrent_tasks: 1,
     });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(storage
+        .get_from_cache_only::<TreeNodeWithPreviousValue>(&NodeKey(NodeLabel::root()))
+        .await
+        .is_some());
+    handle.shutdown();
+
+    // Corrupting the hash of the root node is detected by the integrity check
+    let DbRecord::TreeNode(mut root) = db
+        .get::<TreeNodeWithPreviousValue>(&NodeKey(NodeLabel::root()))
+        .await?
+    else {
+        panic!("Expected a tree node");
+    };
+    root.latest_node.hash = AzksValue([0u8; DIGEST_BYTES]);
+    db.set(DbRecord::TreeNode(root)).await?;
+    storage.flush_cache().await;
+    assert!(matches!(
+        akd.run_maintenance_task(MaintenanceTask::IntegrityCheck { sample_size: 1 })
+            .await,
+        Err(AkdError::Directory(DirectoryError::Integrity(_)))
+    ));
 
-    // wait for a second to make sure the poller has started
-    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    Ok(())
+}
 
-    // verify a lookup proof, which will populate the cache
-    async_poll_helper_proof(&reader, AkdValue::from("world")).await?;
+// Checks that registered hooks are notified of publishes, failed publishes, prunes,
+// and integrity violations, including those of clones of the directory
+test_config!(test_directory_hooks);
+async fn test_directory_hooks<TC: Configuration>() -> Result<(), AkdError> {
+    use crate::hooks::DirectoryHooks;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct RecordingHook(Arc<Mutex<Vec<String>>>);
+
+    impl DirectoryHooks for RecordingHook {
+        fn on_epoch_published(&self, epoch_hash: &EpochHash, num_updates: usize) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("published {} {num_updates}", epoch_hash.epoch()));
+        }
 
-    // publish epoch 2
-    writer
+        fn on_publish_failed(&self, _error: &AkdError) {
+            self.0.lock().unwrap().push("publish failed".to_string());
+        }
+
+        fn on_prune_completed(&self, cutoff_epoch: u64, num_tombstoned: usize) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("pruned {cutoff_epoch} {num_tombstoned}"));
+        }
+
+        fn on_integrity_violation(&self, _detail: &str) {
+            self.0
+                .lock()
+                .unwrap()
+                .push("integrity violation".to_string());
+        }
+    }
+
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db.clone());
+    let akd = Directory::<TC, _, _>::new(storage, HardCodedAkdVRF {}).await?;
+    let hook = RecordingHook::default();
+    akd.register_hook(hook.clone());
+    let akd = akd.clone();
+
+    akd.publish(vec![
+        (AkdLabel::from("hello"), AkdValue::from("world")),
+        (AkdLabel::from("hello2"), AkdValue::from("world2")),
+    ])
+    .await?;
+    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world3"))])
+        .await?;
+    // A publish which does not change the directory does not produce an epoch
+    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world3"))])
+        .await?;
+    assert!(akd
         .publish(vec![
-            (AkdLabel::from("hello"), AkdValue::from("world_2")),
-            (AkdLabel::from("hello2"), AkdValue::from("world2_2")),
+            (AkdLabel::from("hello"), AkdValue::from("world4")),
+            (AkdLabel::from("hello"), AkdValue::from("world5")),
         ])
+        .await
+        .is_err());
+    akd.run_maintenance_task(MaintenanceTask::Pruning { retain_epochs: 1 })
         .await?;
 
-    // assert that the change is picked up in a reasonable time-frame and the cache is flushed
-    let notification = tokio::time::timeout(tokio::time::Duration::from_secs(10), rx.recv()).await;
-    assert!(matches!(notification, Ok(Some(()))));
+    let DbRecord::TreeNode(mut root) = db
+        .get::<TreeNodeWithPreviousValue>(&NodeKey(NodeLabel::root()))
+        .await?
+    else {
+        panic!("Expected a tree node");
+    };
+    root.latest_node.hash = AzksValue([0u8; DIGEST_BYTES]);
+    db.set(DbRecord::TreeNode(root)).await?;
+    assert!(akd
+        .run_maintenance_task(MaintenanceTask::IntegrityCheck { sample_size: 1 })
+        .await
+        .is_err());
 
-    async_poll_helper_proof(&reader, AkdValue::from("world_2")).await?;
+    assert_eq!(
+        vec![
+            "published 1 2",
+            "published 2 1",
+            "publish failed",
+            "pruned 1 1",
+            "integrity violation",
+        ],
+        *hook.0.lock().unwrap()
+    );
 
     Ok(())
 }
 
-test_config!(test_tombstoned_key_history);
-async fn test_tombstoned_key_history<TC: Configuration>() -> Result<(), AkdError> {
+// Checks that the tree can be exported for visualization in full, down to a depth
+// limit, and around the neighborhood of a label
+test_config!(test_visualize_tree);
+async fn test_visualize_tree<TC: Configuration>() -> Result<(), AkdError> {
+    use crate::visualization::TreeVisualizationOptions;
+
     let db = AsyncInMemoryDatabase::new();
     let storage = StorageManager::new_no_cache(db);
-    let vrf = HardCodedAkdVRF {};
-    // epoch 0
-    let akd = Directory::<TC, _, _>::new(storage.clone(), vrf).await?;
-
-    // epoch 1
-    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world"))])
-        .await?;
-
-    // epoch 2
-    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world2"))])
-        .await?;
-
-    // epoch 3
-    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world3"))])
+    let akd = Directory::<TC, _, _>::new(storage, HardCodedAkdVRF {}).await?;
+    akd.publish(
+        (0..8)
+            .map(|i| {
+                (
+                    AkdLabel(format!("label{i}").into_bytes()),
+                    AkdValue::from("value"),
+                )
+            })
+            .collect(),
+    )
+    .await?;
+    akd.publish(vec![(AkdLabel::from("label0"), AkdValue::from("value2"))])
         .await?;
 
-    // epoch 4
-    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world4"))])
+    // The full tree holds a leaf for each of the 9 versions, and a stale leaf for the update
+    let full = akd
+        .visualize_tree(&TreeVisualizationOptions::default())
         .await?;
-
-    // epoch 5
-    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world5"))])
+    assert_eq!(2, full.epoch);
+    let num_leaves = full
+        .nodes
+        .iter()
+        .filter(|node| node.node_type == TreeNodeType::Leaf)
+        .count();
+    assert_eq!(10, num_leaves);
+    assert_eq!(2 * num_leaves - 1, full.nodes.len());
+    assert!(full
+        .nodes
+        .iter()
+        .all(|node| !node.truncated && !node.on_path));
+    let dot = full.to_dot();
+    assert!(dot.starts_with("digraph azks {"));
+    assert_eq!(full.nodes.len() - 1, dot.matches(" -> ").count());
+    let json = full.to_json();
+    assert!(json.starts_with("{\"epoch\":2,\"nodes\":["));
+    assert_eq!(full.nodes.len(), json.matches("\"on_path\":false").count());
+
+    // The depth limit truncates the nodes at the limit which have children
+    let top = akd
+        .visualize_tree(&TreeVisualizationOptions {
+            max_depth: Some(1),
+            neighborhood: None,
+        })
         .await?;
-
-    // Epochs 1-5, we're going to tombstone 1 & 2
-
-    // Get the VRF public key
-    let vrf_pk = akd.get_public_key().await?;
-
-    // tombstone epochs 1 & 2
-    storage
-        .tombstone_value_states(&AkdLabel::from("hello"), 2)
+    let top_labels = top.nodes.iter().map(|node| node.label).collect::<Vec<_>>();
+    let full_labels = full.nodes.iter().map(|node| node.label).collect::<Vec<_>>();
+    assert_eq!(&full_labels[..top_labels.len()], &top_labels[..]);
+    assert!(top.nodes.iter().all(|node| node.depth <= 1));
+    assert!(top
+        .nodes
+        .iter()
+        .all(|node| node.truncated == (node.depth == 1 && node.node_type != TreeNodeType::Leaf)));
+
+    // The neighborhood of a label is the path to its latest leaf, and the siblings on the path
+    let (proof, _) = akd.lookup(AkdLabel::from("label0")).await?;
+    let leaf_label = proof.existence_proof.label;
+    let neighborhood = akd
+        .visualize_tree(&TreeVisualizationOptions {
+            max_depth: None,
+            neighborhood: Some(AkdLabel::from("label0")),
+        })
         .await?;
+    let path = neighborhood
+        .nodes
+        .iter()
+        .filter(|node| node.on_path)
+        .collect::<Vec<_>>();
+    assert_eq!(leaf_label, path.last().unwrap().label);
+    assert!(path.iter().all(|node| node.label.is_prefix_of(&leaf_label)));
+    assert_eq!(2 * path.len() - 1, neighborhood.nodes.len());
+    assert!(neighborhood
+        .nodes
+        .iter()
+        .all(|node| node.on_path || node.node_type == TreeNodeType::Leaf || node.truncated));
 
-    // Now get a history proof for this key
-    let (history_proof, root_hash) = akd
-        .key_history(&AkdLabel::from("hello"), HistoryParams::default())
-        .await?;
-    assert_eq!(5, history_proof.update_proofs.len());
+    Ok(())
+}
 
-    // If we request a proof with tombstones but without saying we're OK with tombstones, throw an err
-    let tombstones = key_history_verify::<TC>(
-        vrf_pk.as_bytes(),
-        root_hash.hash(),
-        root_hash.epoch(),
-        AkdLabel::from("hello"),
-        history_proof.clone(),
-        HistoryVerificationParams::default(),
-    );
-    assert!(matches!(tombstones, Err(_)));
+// Checks that a directory whose configuration is selected at runtime produces the same
+// tree and proofs as a directory of the corresponding generic configuration
+#[cfg(feature = "experimental")]
+test_config!(test_runtime_directory);
+#[cfg(feature = "experimental")]
+async fn test_runtime_directory<TC: Configuration>() -> Result<(), AkdError> {
+    use crate::runtime_directory::{ConfigurationKind, RuntimeDirectory};
+    use akd_core::signing::configuration_fingerprint;
+
+    let kind = *ConfigurationKind::ALL
+        .iter()
+        .find(|kind| kind.fingerprint::<crate::ExampleLabel>() == configuration_fingerprint::<TC>())
+        .expect("Every built-in configuration can be selected at runtime");
+    assert_eq!(Some(kind), ConfigurationKind::from_name(&kind.to_string()));
+    assert_eq!(None, ConfigurationKind::from_name("unknown"));
+
+    let generic = Directory::<TC, _, _>::new(
+        StorageManager::new_no_cache(AsyncInMemoryDatabase::new()),
+        HardCodedAkdVRF {},
+    )
+    .await?;
+    let runtime = RuntimeDirectory::<_, _>::new(
+        kind,
+        StorageManager::new_no_cache(AsyncInMemoryDatabase::new()),
+        HardCodedAkdVRF {},
+    )
+    .await?;
+    assert_eq!(kind, runtime.clone().configuration());
+
+    for epoch in 0..3 {
+        let updates = vec![
+            (
+                AkdLabel::from("hello"),
+                AkdValue(format!("world{epoch}").into_bytes()),
+            ),
+            (
+                AkdLabel(format!("label{epoch}").into_bytes()),
+                AkdValue::from("value"),
+            ),
+        ];
+        assert_eq!(
+            generic.publish(updates.clone()).await?,
+            runtime.publish(updates).await?
+        );
+    }
 
-    // We should be able to verify tombstones assuming the client is accepting
-    // of tombstoned states
-    let results = key_history_verify::<TC>(
+    let vrf_pk = runtime.get_public_key().await?;
+    let (proof, EpochHash(epoch, root_hash)) = runtime.lookup(AkdLabel::from("hello")).await?;
+    assert_eq!(EpochHash(epoch, root_hash), generic.get_epoch_hash().await?);
+    let result = lookup_verify::<TC>(
         vrf_pk.as_bytes(),
-        root_hash.hash(),
-        root_hash.epoch(),
+        root_hash,
+        epoch,
         AkdLabel::from("hello"),
-        history_proof,
-        HistoryVerificationParams::AllowMissingValues,
+        proof,
     )?;
-    assert_ne!(crate::TOMBSTONE, results[0].value.0);
-    assert_ne!(crate::TOMBSTONE, results[1].value.0);
-    assert_ne!(crate::TOMBSTONE, results[2].value.0);
-    assert_eq!(crate::TOMBSTONE, results[3].value.0);
-    assert_eq!(crate::TOMBSTONE, results[4].value.0);
+    assert_eq!(AkdValue::from("world2"), result.value);
+    assert_eq!(generic.audit(1, 3).await?, runtime.audit(1, 3).await?);
 
     Ok(())
 }
 
-test_config!(test_publish_op_makes_no_get_requests);
-async fn test_publish_op_makes_no_get_requests<TC: Configuration>() -> Result<(), AkdError> {
-    let test_db = AsyncInMemoryDatabase::new();
+// Checks that labels are normalized by the configuration on publish and on lookup, so that
+// the different encodings of a label refer to the same entry
+test_config!(test_label_normalization);
+async fn test_label_normalization<TC: Configuration>() -> Result<(), AkdError> {
+    use crate::configuration::{LabelNormalizer, NormalizedConfiguration};
+    use std::borrow::Cow;
+
+    #[derive(Clone)]
+    struct AsciiLowercase;
+
+    impl LabelNormalizer for AsciiLowercase {
+        fn normalize(label: &AkdLabel) -> Cow<'_, AkdLabel> {
+            if label.iter().any(u8::is_ascii_uppercase) {
+                Cow::Owned(AkdLabel(label.to_ascii_lowercase()))
+            } else {
+                Cow::Borrowed(label)
+            }
+        }
+    }
+    type NC<TC> = NormalizedConfiguration<TC, AsciiLowercase>;
 
-    let mut db = MockLocalDatabase {
-        ..Default::default()
-    };
-    setup_mocked_db(&mut db, &test_db);
-
-    let storage = StorageManager::new_no_cache(db);
-    let vrf = HardCodedAkdVRF {};
-    let akd = Directory::<TC, _, _>::new(storage, vrf)
-        .await
-        .expect("Failed to create directory");
-
-    // Create a set with 2 updates, (label, value) pairs
-    // ("hello10", "hello10")
-    // ("hello11", "hello11")
-    let mut updates = vec![];
-    for i in 0..2 {
-        updates.push((
-            AkdLabel(format!("hello1{i}").as_bytes().to_vec()),
-            AkdValue(format!("hello1{i}").as_bytes().to_vec()),
-        ));
-    }
-    // Publish the updates. Now the akd's epoch will be 1.
-    akd.publish(updates)
-        .await
-        .expect("Failed to do initial publish");
+    let db = AsyncInMemoryDatabase::new();
+    let akd = Directory::<NC<TC>, _, _>::new(
+        StorageManager::new_no_cache(db.clone()),
+        HardCodedAkdVRF {},
+    )
+    .await?;
+    let unnormalized = Directory::<TC, _, _>::new(
+        StorageManager::new_no_cache(AsyncInMemoryDatabase::new()),
+        HardCodedAkdVRF {},
+    )
+    .await?;
 
-    // create a new mock, this time which explodes on any "get" of tree-nodes (shouldn't happen). It is still backed by the same
-    // async in-mem db so all previous data should be there
-    let mut db2 = MockLocalDatabase {
-        ..Default::default()
-    };
-    setup_mocked_db(&mut db2, &test_db);
-    db2.expect_get::<TreeNodeWithPreviousValue>()
-        .returning(|_| Err(StorageError::Other("Boom!".to_string())));
+    // Labels which only differ before normalization are duplicates
+    assert!(matches!(
+        akd.publish(vec![
+            (AkdLabel::from("Alice"), AkdValue::from("value1")),
+            (AkdLabel::from("alice"), AkdValue::from("value2")),
+        ])
+        .await,
+        Err(AkdError::Directory(DirectoryError::Publish(_)))
+    ));
 
-    let storage = StorageManager::new_no_cache(db2);
-    let vrf = HardCodedAkdVRF {};
-    let akd = Directory::<TC, _, _>::new(storage, vrf)
-        .await
-        .expect("Failed to create directory");
+    // Publishing and looking up different encodings refers to the same entry, which is
+    // identical to the entry of the normalized label without normalization
+    akd.publish(vec![(AkdLabel::from("Alice"), AkdValue::from("value1"))])
+        .await?;
+    akd.publish(vec![(AkdLabel::from("ALICE"), AkdValue::from("value2"))])
+        .await?;
+    unnormalized
+        .publish(vec![(AkdLabel::from("alice"), AkdValue::from("value1"))])
+        .await?;
+    let EpochHash(epoch, root_hash) = unnormalized
+        .publish(vec![(AkdLabel::from("alice"), AkdValue::from("value2"))])
+        .await?;
+    assert_eq!(EpochHash(epoch, root_hash), akd.get_epoch_hash().await?);
+    let states = db.get_user_data(&AkdLabel::from("alice")).await?.states;
+    assert_eq!(2, states.len());
 
-    // create more updates
-    let mut updates = vec![];
-    for i in 0..2 {
-        updates.push((
-            AkdLabel(format!("hello1{i}").as_bytes().to_vec()),
-            AkdValue(format!("hello1{}", i + 1).as_bytes().to_vec()),
-        ));
-    }
+    let vrf_pk = akd.get_public_key().await?;
+    let (proof, _) = akd.lookup(AkdLabel::from("aLiCe")).await?;
+    let result = lookup_verify::<NC<TC>>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("aLiCe"),
+        proof.clone(),
+    )?;
+    assert_eq!(
+        (2, AkdValue::from("value2")),
+        (result.version, result.value)
+    );
+    // Without normalization, the proof is for a different label
+    assert!(lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("aLiCe"),
+        proof,
+    )
+    .is_err());
 
-    // try to publish again, this time with the "boom" returning from any mocked get-calls
-    // on tree nodes
-    akd.publish(updates)
-        .await
-        .expect("Failed to do subsequent publish");
+    let (history, _) = akd
+        .key_history(&AkdLabel::from("ALICE"), HistoryParams::default())
+        .await?;
+    let results = key_history_verify::<NC<TC>>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("Alice"),
+        history,
+        HistoryVerificationParams::default(),
+    )?;
+    assert_eq!(2, results.len());
 
     Ok(())
 }
 
-// Test coverage on issue #144, verification failures with
-// small trees (<4 nodes) in both the tests below
-// Note that the use of a VRF means that that the label
-// depends on the hash function being used.
-// The below two tests are identical except for the hash function being used.
+// Checks that a directory whose marker versions are the powers of four produces lookup and
+// history proofs which verify under the same strategy, but not under the default one
+test_config!(test_marker_strategy);
+async fn test_marker_strategy<TC: Configuration>() -> Result<(), AkdError> {
+    use crate::configuration::{ExponentialMarkers, MarkerConfiguration};
+
+    type MC<TC> = MarkerConfiguration<TC, ExponentialMarkers<4>>;
 
-// Test lookup in a smaller tree with 2 leaves, using the Blake3 hash function.
-test_config!(test_simple_lookup_for_small_tree);
-async fn test_simple_lookup_for_small_tree<TC: Configuration>() -> Result<(), AkdError> {
     let db = AsyncInMemoryDatabase::new();
     let storage = StorageManager::new_no_cache(db);
-    let vrf = HardCodedAkdVRF {};
-    // epoch 0
-    let akd = Directory::<TC, _, _>::new(storage, vrf.clone()).await?;
-
-    // Create a set with 2 updates, (label, value) pairs
-    // ("hello10", "hello10")
-    // ("hello11", "hello11")
-    let mut updates = vec![];
-    for i in 0..2 {
-        updates.push((
-            AkdLabel(format!("hello1{i}").as_bytes().to_vec()),
-            AkdValue(format!("hello1{i}").as_bytes().to_vec()),
-        ));
+    let akd = Directory::<MC<TC>, _, _>::new(storage, HardCodedAkdVRF {}).await?;
+
+    for i in 1..=6 {
+        let mut updates = vec![(
+            AkdLabel::from("alice"),
+            AkdValue(format!("value{i}").into()),
+        )];
+        if i <= 3 {
+            updates.push((AkdLabel::from("bob"), AkdValue(format!("value{i}").into())));
+        }
+        akd.publish(updates).await?;
     }
-    // Publish the updates. Now the akd's epoch will be 1.
-    akd.publish(updates).await?;
-
-    // The label we will lookup is "hello10"
-    let target_label = AkdLabel(format!("hello1{}", 0).as_bytes().to_vec());
-
-    // retrieve the lookup proof
-    let (lookup_proof, root_hash) = akd.lookup(target_label.clone()).await?;
-
-    // Get the VRF public key
-    let vrf_pk = vrf.get_vrf_public_key().await?;
+    let mut root_hash = akd.get_epoch_hash().await?.1;
+    for i in 7..=20 {
+        root_hash = akd
+            .publish(vec![(
+                AkdLabel(format!("padding{i}").into_bytes()),
+                AkdValue::from("value"),
+            )])
+            .await?
+            .1;
+    }
+    let vrf_pk = akd.get_public_key().await?;
 
-    // perform the "traditional" AKD verification
-    let akd_result = crate::client::lookup_verify::<TC>(
+    // The marker version of version 3 is 1 rather than 2
+    let (proof, _) = akd.lookup(AkdLabel::from("bob")).await?;
+    let result = lookup_verify::<MC<TC>>(
         vrf_pk.as_bytes(),
-        root_hash.hash(),
-        root_hash.epoch(),
-        target_label.clone(),
-        lookup_proof,
+        root_hash,
+        20,
+        AkdLabel::from("bob"),
+        proof.clone(),
     )?;
+    assert_eq!(3, result.version);
+    assert!(lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        20,
+        AkdLabel::from("bob"),
+        proof
+    )
+    .is_err());
 
-    // check the two results to make sure they both verify
-    assert_eq!(
-        akd_result,
-        VerifyResult {
-            epoch: 1,
-            version: 1,
-            value: AkdValue::from("hello10"),
-        },
-    );
+    // After version 6, the versions until the next marker are 7 to 15, and the only future
+    // marker up to epoch 20 is 16
+    let (history, _) = akd
+        .key_history(&AkdLabel::from("alice"), HistoryParams::default())
+        .await?;
+    assert_eq!(9, history.until_marker_vrf_proofs.len());
+    assert_eq!(1, history.future_marker_vrf_proofs.len());
+    let results = key_history_verify::<MC<TC>>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        20,
+        AkdLabel::from("alice"),
+        history.clone(),
+        HistoryVerificationParams::default(),
+    )?;
+    assert_eq!(6, results.len());
+    assert!(key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        20,
+        AkdLabel::from("alice"),
+        history,
+        HistoryVerificationParams::default(),
+    )
+    .is_err());
 
     Ok(())
 }
 
-// Test lookup_verify where version number exceeds epoch (and it should throw an error)
-test_config!(test_lookup_verify_invalid_version_number);
-async fn test_lookup_verify_invalid_version_number<TC: Configuration>() -> Result<(), AkdError> {
+// Checks that a directory with truncated node labels produces proofs with labels of that
+// length, which verify only under the same truncation
+test_config!(test_truncated_node_labels);
+async fn test_truncated_node_labels<TC: Configuration>() -> Result<(), AkdError> {
+    use crate::configuration::TruncatedLabelConfiguration;
+
+    type TL<TC> = TruncatedLabelConfiguration<TC, 64>;
+
     let db = AsyncInMemoryDatabase::new();
     let storage = StorageManager::new_no_cache(db);
-    let vrf = HardCodedAkdVRF {};
-    // epoch 0
-    let akd = Directory::<TC, _, _>::new(storage, vrf.clone()).await?;
-
-    // Create a set with 2 updates, (label, value) pairs
-    // ("hello10", "hello10")
-    // ("hello11", "hello11")
-    let mut updates = vec![];
-    for i in 0..2 {
-        updates.push((
-            AkdLabel(format!("hello1{i}").as_bytes().to_vec()),
-            AkdValue(format!("hello1{i}").as_bytes().to_vec()),
-        ));
-    }
-    // Repeatedly publish the updates. Afterwards, the akd's epoch will be 10.
-    for _ in 0..10 {
-        akd.publish(updates.clone()).await?;
+    let akd = Directory::<TL<TC>, _, _>::new(storage, HardCodedAkdVRF {}).await?;
+
+    let mut root_hashes = vec![akd.get_epoch_hash().await?.1];
+    for i in 1..=3 {
+        let updates = (0..20)
+            .map(|j| {
+                (
+                    AkdLabel(format!("user{j}").into_bytes()),
+                    AkdValue(format!("value{i}").into_bytes()),
+                )
+            })
+            .collect::<Vec<_>>();
+        root_hashes.push(akd.publish(updates).await?.1);
     }
+    let root_hash = root_hashes[3];
+    let vrf_pk = akd.get_public_key().await?;
 
-    // The label we will lookup is "hello10"
-    let target_label = AkdLabel(format!("hello1{}", 0).as_bytes().to_vec());
-
-    // retrieve the lookup proof
-    let (lookup_proof, root_hash) = akd.lookup(target_label.clone()).await?;
-
-    // Get the VRF public key
-    let vrf_pk = vrf.get_vrf_public_key().await?;
+    let (proof, _) = akd.lookup(AkdLabel::from("user7")).await?;
+    assert_eq!(64, proof.existence_proof.label.label_len);
+    let result = lookup_verify::<TL<TC>>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        3,
+        AkdLabel::from("user7"),
+        proof.clone(),
+    )?;
+    assert_eq!(3, result.version);
+    assert!(lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        3,
+        AkdLabel::from("user7"),
+        proof
+    )
+    .is_err());
 
-    let akd_result = crate::client::lookup_verify::<TC>(
+    let (history, _) = akd
+        .key_history(&AkdLabel::from("user7"), HistoryParams::default())
+        .await?;
+    let results = key_history_verify::<TL<TC>>(
         vrf_pk.as_bytes(),
-        root_hash.hash(),
-        root_hash.epoch() - 1, // To fake a lower epoch and trigger the error condition
-        target_label.clone(),
-        lookup_proof,
-    );
+        root_hash,
+        3,
+        AkdLabel::from("user7"),
+        history,
+        HistoryVerificationParams::default(),
+    )?;
+    assert_eq!(3, results.len());
 
-    // Check that the result is a verification error
-    match akd_result {
-        Err(akd_core::verify::VerificationError::LookupProof(_)) => (),
-        _ => panic!("Expected an invalid epoch error"),
-    }
+    let audit_proof = akd.audit(1, 3).await?;
+    crate::auditor::audit_verify::<TL<TC>>(root_hashes[1..].to_vec(), audit_proof).await?;
 
     Ok(())
 }
 
-// Test for attempting to publish duplicate entries as updates to the directory
-test_config!(test_publish_duplicate_entries);
-async fn test_publish_duplicate_entries<TC: Configuration>() -> Result<(), AkdError> {
+// Checks that tombstones which carry metadata verify, and that their epochs and the
+// commitments to the values they replaced are checked by clients
+test_config!(test_tombstone_metadata);
+async fn test_tombstone_metadata<TC: Configuration>() -> Result<(), AkdError> {
+    use crate::configuration::TombstoneMetadataConfiguration;
+    use crate::{HistoryProof, TombstoneMetadata};
+
+    type TM<TC> = TombstoneMetadataConfiguration<TC>;
+
     let db = AsyncInMemoryDatabase::new();
     let storage = StorageManager::new_no_cache(db);
-    let vrf = HardCodedAkdVRF {};
-    let akd = Directory::<TC, _, _>::new(storage, vrf.clone()).await?;
+    let akd = Directory::<TM<TC>, _, _>::new(storage, HardCodedAkdVRF {}).await?;
 
-    // Create a set of updates
-    let mut updates = vec![];
-    for i in 0..10 {
-        updates.push((
-            AkdLabel(format!("hello1{i}").as_bytes().to_vec()),
-            AkdValue(format!("hello1{i}").as_bytes().to_vec()),
-        ));
+    // Values which would be mistaken for tombstones cannot be published
+    let metadata = TombstoneMetadata {
+        tombstoned_at: 1,
+        commitment: AzksValue([0u8; DIGEST_BYTES]),
+    };
+    assert!(matches!(
+        akd.publish(vec![(AkdLabel::from("alice"), metadata.to_value())])
+            .await,
+        Err(AkdError::Directory(DirectoryError::Publish(_)))
+    ));
+
+    for i in 1..=3 {
+        akd.publish(vec![(
+            AkdLabel::from("alice"),
+            AkdValue(format!("value{i}").into()),
+        )])
+        .await?;
     }
+    for i in 4..=5 {
+        akd.publish(vec![(
+            AkdLabel(format!("padding{i}").into_bytes()),
+            AkdValue::from("value"),
+        )])
+        .await?;
+    }
+
+    // The versions published before epoch 3 are tombstoned at epoch 5
+    let report = akd
+        .run_maintenance_task(MaintenanceTask::Pruning { retain_epochs: 2 })
+        .await?;
+    assert_eq!(2, report.records);
 
-    // Now add a duplicate entry
-    updates.push(updates[0].clone());
+    let vrf_pk = akd.get_public_key().await?;
+    let (history, EpochHash(epoch, root_hash)) = akd
+        .key_history(&AkdLabel::from("alice"), HistoryParams::default())
+        .await?;
+    let verify = |history: HistoryProof| {
+        key_history_verify::<TM<TC>>(
+            vrf_pk.as_bytes(),
+            root_hash,
+            epoch,
+            AkdLabel::from("alice"),
+            history,
+            HistoryVerificationParams::AllowMissingValues,
+        )
+    };
+    let results = verify(history.clone())?;
+    assert_eq!(AkdValue::from("value3"), results[0].value);
+    for result in &results[1..] {
+        assert!(TM::<TC>::is_tombstone(&result.value));
+        assert_eq!(
+            Some(5),
+            TM::<TC>::tombstone_metadata(&result.value).map(|metadata| metadata.tombstoned_at)
+        );
+    }
 
-    // Attempt to publish -- this should throw an error because of the duplicate entry
-    let Err(AkdError::Directory(DirectoryError::Publish(_))) = akd.publish(updates).await else {
-        panic!("Expected a directory publish error");
+    // Tombstones whose epochs are out of range, or whose commitments are not those of the
+    // tombstoned values, do not verify
+    let with_metadata = |update: fn(&mut TombstoneMetadata)| {
+        let mut history = history.clone();
+        let proof = &mut history.update_proofs[1];
+        let mut metadata = TM::<TC>::tombstone_metadata(&proof.value).unwrap();
+        update(&mut metadata);
+        proof.value = metadata.to_value();
+        history
     };
+    assert!(verify(with_metadata(|metadata| metadata.tombstoned_at = 1)).is_err());
+    assert!(verify(with_metadata(|metadata| metadata.tombstoned_at = 6)).is_err());
+    assert!(verify(with_metadata(|metadata| metadata.commitment.0[0] ^= 1)).is_err());
+    verify(with_metadata(|metadata| metadata.tombstoned_at = 2))?;
 
     Ok(())
 }
 
-// Test key history verification for error handling of malformed key history proofs
-test_config!(test_key_history_verify_malformed);
-async fn test_key_history_verify_malformed<TC: Configuration>() -> Result<(), AkdError> {
+// Checks that redacted values verify as redaction records in key histories, and that their
+// epochs and the commitments to the values they replaced are checked by clients
+test_config!(test_redaction);
+async fn test_redaction<TC: Configuration>() -> Result<(), AkdError> {
+    use crate::{HistoryProof, RedactionRecord};
+
     let db = AsyncInMemoryDatabase::new();
     let storage = StorageManager::new_no_cache(db);
-    let vrf = HardCodedAkdVRF {};
-    let akd = Directory::<TC, _, _>::new(storage, vrf.clone()).await?;
+    let akd = Directory::<TC, _, _>::new(storage, HardCodedAkdVRF {}).await?;
 
-    let mut rng = rand::rngs::OsRng;
-    for _ in 0..100 {
-        let mut updates = vec![];
-        updates.push((
-            AkdLabel(format!("label").as_bytes().to_vec()),
-            AkdValue::random(&mut rng),
-        ));
-        akd.publish(updates.clone()).await?;
-    }
+    // Values which would be mistaken for redaction records cannot be published
+    let forged = RedactionRecord {
+        redacted_at: 1,
+        commitment: AzksValue([0u8; DIGEST_BYTES]),
+        reason: "forged".to_string(),
+    };
+    assert!(matches!(
+        akd.publish(vec![(AkdLabel::from("alice"), forged.to_value())])
+            .await,
+        Err(AkdError::Directory(DirectoryError::Publish(_)))
+    ));
 
-    for _ in 0..100 {
-        let mut updates = vec![];
-        updates.push((
-            AkdLabel(format!("another label").as_bytes().to_vec()),
-            AkdValue::random(&mut rng),
-        ));
-        akd.publish(updates.clone()).await?;
+    for i in 1..=3 {
+        akd.publish(vec![(
+            AkdLabel::from("alice"),
+            AkdValue(format!("value{i}").into()),
+        )])
+        .await?;
     }
 
-    // Get the latest root hash
-    let EpochHash(current_epoch, root_hash) = akd.get_epoch_hash().await?;
-    // Get the VRF public key
-    let vrf_pk = akd.get_public_key().await?;
-    let target_label = AkdLabel(format!("label").as_bytes().to_vec());
-
-    let (key_history_proof, _) = akd
-        .key_history(&target_label, HistoryParams::default())
+    // The latest version cannot be redacted, nor can a version be redacted twice
+    assert!(matches!(
+        akd.redact(&AkdLabel::from("alice"), 3, "legal hold").await,
+        Err(AkdError::Directory(DirectoryError::Redaction(_)))
+    ));
+    let record = akd
+        .redact(&AkdLabel::from("alice"), 2, "legal hold")
         .await?;
+    assert_eq!(3, record.redacted_at);
+    assert_eq!(
+        Some(record.clone()),
+        RedactionRecord::from_value(&record.to_value())
+    );
+    assert!(matches!(
+        akd.redact(&AkdLabel::from("alice"), 2, "legal hold").await,
+        Err(AkdError::Directory(DirectoryError::Redaction(_)))
+    ));
+    assert!(akd
+        .redact(&AkdLabel::from("bob"), 1, "legal hold")
+        .await
+        .is_err());
 
-    // Normal verification should succeed
-    key_history_verify::<TC>(
-        vrf_pk.as_bytes(),
-        root_hash,
-        current_epoch,
-        target_label.clone(),
-        key_history_proof.clone(),
+    let operations = akd.operations(0, 100).await?;
+    assert!(operations
+        .iter()
+        .any(|operation| operation.kind == OperationKind::Redaction));
+
+    akd.publish(vec![(AkdLabel::from("padding"), AkdValue::from("value"))])
+        .await?;
+    let vrf_pk = akd.get_public_key().await?;
+    let (history, EpochHash(epoch, root_hash)) = akd
+        .key_history(&AkdLabel::from("alice"), HistoryParams::default())
+        .await?;
+    let verify = |history: HistoryProof, params| {
+        key_history_verify::<TC>(
+            vrf_pk.as_bytes(),
+            root_hash,
+            epoch,
+            AkdLabel::from("alice"),
+            history,
+            params,
+        )
+    };
+    let results = verify(
+        history.clone(),
+        HistoryVerificationParams::AllowMissingValues,
+    )?;
+    assert_eq!(AkdValue::from("value3"), results[0].value);
+    assert_eq!(Some(record), RedactionRecord::from_value(&results[1].value));
+    assert_eq!(AkdValue::from("value1"), results[2].value);
+    assert!(verify(history.clone(), HistoryVerificationParams::default()).is_err());
+
+    // Redaction records whose epochs are out of range, or whose commitments are not those of
+    // the redacted values, do not verify
+    let with_record = |update: fn(&mut RedactionRecord)| {
+        let mut history = history.clone();
+        let proof = &mut history.update_proofs[1];
+        let mut record = RedactionRecord::from_value(&proof.value).unwrap();
+        update(&mut record);
+        proof.value = record.to_value();
+        history
+    };
+    let allow_missing = HistoryVerificationParams::AllowMissingValues;
+    assert!(verify(with_record(|record| record.redacted_at = 1), allow_missing).is_err());
+    assert!(verify(with_record(|record| record.redacted_at = 5), allow_missing).is_err());
+    assert!(verify(
+        with_record(|record| record.commitment.0[0] ^= 1),
+        allow_missing
+    )
+    .is_err());
+    verify(
+        with_record(|record| record.reason = "other".to_string()),
+        allow_missing,
+    )?;
+
+    Ok(())
+}
+
+// Checks that the default commitment scheme leaves the tree unchanged, and that a directory
+// which uses Pedersen commitments produces proofs which only verify under the same scheme
+#[cfg(feature = "pedersen")]
+test_config!(test_commitment_schemes);
+#[cfg(feature = "pedersen")]
+async fn test_commitment_schemes<TC: Configuration>() -> Result<(), AkdError> {
+    use crate::configuration::{CommitmentConfiguration, HashCommitments, PedersenCommitments};
+
+    type HC<TC> = CommitmentConfiguration<TC, HashCommitments>;
+    type PC<TC> = CommitmentConfiguration<TC, PedersenCommitments>;
+
+    let updates = |epoch: u64| {
+        vec![
+            (
+                AkdLabel::from("hello"),
+                AkdValue(format!("world{epoch}").into()),
+            ),
+            (
+                AkdLabel::from("hello2"),
+                AkdValue(format!("world2.{epoch}").into()),
+            ),
+        ]
+    };
+    let default = Directory::<TC, _, _>::new(
+        StorageManager::new_no_cache(AsyncInMemoryDatabase::new()),
+        HardCodedAkdVRF {},
+    )
+    .await?;
+    let hashed = Directory::<HC<TC>, _, _>::new(
+        StorageManager::new_no_cache(AsyncInMemoryDatabase::new()),
+        HardCodedAkdVRF {},
+    )
+    .await?;
+    let pedersen = Directory::<PC<TC>, _, _>::new(
+        StorageManager::new_no_cache(AsyncInMemoryDatabase::new()),
+        HardCodedAkdVRF {},
+    )
+    .await?;
+    for epoch in 1..=2 {
+        let default_hash = default.publish(updates(epoch)).await?;
+        assert_eq!(default_hash, hashed.publish(updates(epoch)).await?);
+        assert_ne!(default_hash, pedersen.publish(updates(epoch)).await?);
+    }
+
+    let vrf_pk = pedersen.get_public_key().await?;
+    let (proof, EpochHash(epoch, root_hash)) = pedersen.lookup(AkdLabel::from("hello")).await?;
+    lookup_verify::<PC<TC>>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("hello"),
+        proof.clone(),
+    )?;
+    assert!(lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("hello"),
+        proof,
+    )
+    .is_err());
+
+    let (history, _) = pedersen
+        .key_history(&AkdLabel::from("hello"), HistoryParams::default())
+        .await?;
+    let results = key_history_verify::<PC<TC>>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("hello"),
+        history.clone(),
         HistoryVerificationParams::default(),
     )?;
+    assert_eq!(2, results.len());
+    assert!(key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("hello"),
+        history,
+        HistoryVerificationParams::default(),
+    )
+    .is_err());
 
-    let mut malformed_proof_1 = key_history_proof.clone();
-    malformed_proof_1.until_marker_vrf_proofs = key_history_proof.until_marker_vrf_proofs
-        [..key_history_proof.until_marker_vrf_proofs.len() - 1]
-        .to_vec();
-    let mut malformed_proof_2 = key_history_proof.clone();
-    malformed_proof_2.non_existence_until_marker_proofs = key_history_proof
-        .non_existence_until_marker_proofs
-        [..key_history_proof.non_existence_until_marker_proofs.len() - 1]
-        .to_vec();
-    let mut malformed_proof_3 = key_history_proof.clone();
-    malformed_proof_3.future_marker_vrf_proofs = key_history_proof.future_marker_vrf_proofs
-        [..key_history_proof.future_marker_vrf_proofs.len() - 1]
-        .to_vec();
-    let mut malformed_proof_4 = key_history_proof.clone();
-    malformed_proof_4.non_existence_of_future_marker_proofs = key_history_proof
-        .non_existence_of_future_marker_proofs[..key_history_proof
-        .non_existence_of_future_marker_proofs
-        .len()
-        - 1]
-        .to_vec();
+    Ok(())
+}
 
-    // Malformed proof verification should fail
-    for malformed_proof in [
-        malformed_proof_1,
-        malformed_proof_2,
-        malformed_proof_3,
-        malformed_proof_4,
-    ] {
-        assert!(key_history_verify::<TC>(
+// Checks that a directory which hashes its tree with Poseidon produces lookup, history, and
+// audit proofs which only verify under the same configuration
+#[cfg(feature = "poseidon")]
+test_config!(test_poseidon_configuration);
+#[cfg(feature = "poseidon")]
+async fn test_poseidon_configuration<TC: Configuration>() -> Result<(), AkdError> {
+    use crate::configuration::PoseidonConfiguration;
+
+    type PC<TC> = PoseidonConfiguration<TC>;
+
+    let akd = Directory::<PC<TC>, _, _>::new(
+        StorageManager::new_no_cache(AsyncInMemoryDatabase::new()),
+        HardCodedAkdVRF {},
+    )
+    .await?;
+    let mut root_hashes = vec![];
+    for epoch in 1..=2 {
+        let EpochHash(_, root_hash) = akd
+            .publish(vec![
+                (
+                    AkdLabel::from("hello"),
+                    AkdValue(format!("world{epoch}").into()),
+                ),
+                (
+                    AkdLabel::from("hello2"),
+                    AkdValue(format!("world2.{epoch}").into()),
+                ),
+            ])
+            .await?;
+        root_hashes.push(root_hash);
+    }
+
+    let vrf_pk = akd.get_public_key().await?;
+    let (proof, EpochHash(epoch, root_hash)) = akd.lookup(AkdLabel::from("hello")).await?;
+    lookup_verify::<PC<TC>>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("hello"),
+        proof.clone(),
+    )?;
+    assert!(lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("hello"),
+        proof,
+    )
+    .is_err());
+
+    let (history, _) = akd
+        .key_history(&AkdLabel::from("hello"), HistoryParams::default())
+        .await?;
+    let results = key_history_verify::<PC<TC>>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("hello"),
+        history,
+        HistoryVerificationParams::default(),
+    )?;
+    assert_eq!(2, results.len());
+
+    let audit_proof = akd.audit(1, 2).await?;
+    crate::auditor::audit_verify::<PC<TC>>(root_hashes.clone(), audit_proof.clone()).await?;
+    assert!(crate::auditor::audit_verify::<TC>(root_hashes, audit_proof)
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+// Checks that membership proofs can be converted into audit paths which verify
+// on their own, and back into the original membership proofs
+test_config!(test_spot_check);
+async fn test_spot_check<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+
+    // The spot check of an empty tree has no samples
+    let (proof, EpochHash(epoch, root_hash)) = akd.spot_check(8).await?;
+    assert!(proof.samples.is_empty());
+    spot_check_verify::<TC>(root_hash, epoch, 8, &proof)?;
+
+    akd.publish(
+        (0..20)
+            .map(|i| {
+                (
+                    AkdLabel(format!("label{i}").into_bytes()),
+                    AkdValue::from("value"),
+                )
+            })
+            .collect(),
+    )
+    .await?;
+    let EpochHash(_, previous_root_hash) = akd.get_epoch_hash().await?;
+    akd.publish(vec![(AkdLabel::from("label0"), AkdValue::from("updated"))])
+        .await?;
+
+    let sample_size = 16;
+    let (proof, EpochHash(epoch, root_hash)) = akd.spot_check(sample_size).await?;
+    assert_eq!(2, epoch);
+    assert_eq!(sample_size as usize, proof.samples.len());
+    spot_check_verify::<TC>(root_hash, epoch, sample_size, &proof)?;
+    // The sample is deterministic for an epoch
+    assert_eq!(proof, akd.spot_check(sample_size).await?.0);
+
+    // The proof only verifies for its own epoch, root hash and sample size
+    assert!(spot_check_verify::<TC>(root_hash, epoch - 1, sample_size, &proof).is_err());
+    assert!(spot_check_verify::<TC>(previous_root_hash, epoch, sample_size, &proof).is_err());
+    assert!(spot_check_verify::<TC>(root_hash, epoch, sample_size + 1, &proof).is_err());
+
+    // The server cannot choose which leaves are revealed
+    let distinct = (1..proof.samples.len())
+        .find(|i| proof.samples[*i].proof.label != proof.samples[0].proof.label)
+        .expect("All samples are of the same leaf");
+    let mut swapped = proof.clone();
+    swapped.samples.swap(0, distinct);
+    assert!(spot_check_verify::<TC>(root_hash, epoch, sample_size, &swapped).is_err());
+
+    // Nor misstate the epoch of a leaf
+    let mut misdated = proof.clone();
+    misdated.samples[0].leaf_epoch = epoch + 1;
+    assert!(spot_check_verify::<TC>(root_hash, epoch, sample_size, &misdated).is_err());
+
+    // Nor stop short of a leaf
+    let mut truncated = proof.clone();
+    let path = &mut truncated.samples[0].proof;
+    let parent = path.sibling_proofs.pop().unwrap();
+    path.label = parent.label;
+    assert!(spot_check_verify::<TC>(root_hash, epoch, sample_size, &truncated).is_err());
+
+    assert!(akd.spot_check(MAX_SPOT_CHECK_SAMPLES + 1).await.is_err());
+    Ok(())
+}
+
+test_config!(test_audit_path_conversion);
+async fn test_audit_path_conversion<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+
+    let labels = (0..20)
+        .map(|i| AkdLabel(format!("label{i}").into_bytes()))
+        .collect::<Vec<_>>();
+    akd.publish(
+        labels
+            .iter()
+            .map(|label| (label.clone(), AkdValue::from("value")))
+            .collect(),
+    )
+    .await?;
+
+    for label in labels {
+        let (proof, root_hash) = akd.lookup(label).await?;
+        let membership_proof = proof.existence_proof;
+        let audit_path = AuditPath::from_membership_proof(&membership_proof)?;
+        assert_eq!(
+            1 << membership_proof.sibling_proofs.len(),
+            audit_path.tree_size
+        );
+        assert_eq!(membership_proof.sibling_proofs.len(), audit_path.path.len());
+        audit_path.verify::<TC>(root_hash.hash())?;
+
+        // Moving the leaf to a different position does not verify
+        let mut moved = audit_path.clone();
+        moved.leaf_index ^= 1;
+        assert!(moved.verify::<TC>(root_hash.hash()).is_err());
+
+        // An index outside of the tree is rejected
+        let mut out_of_range = audit_path.clone();
+        out_of_range.leaf_index = out_of_range.tree_size;
+        assert!(out_of_range.verify::<TC>(root_hash.hash()).is_err());
+
+        assert_eq!(membership_proof, audit_path.into_membership_proof()?);
+    }
+
+    Ok(())
+}
+
+// Checks that lookup and non-existence proofs can be converted into CONIKS authentication
+// paths which verify on their own, and that signed tree roots are linked across epochs
+test_config!(test_coniks_conversion);
+async fn test_coniks_conversion<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+    let vrf_pk = akd.get_public_key().await?;
+    let signer = HashEpochSigner::<TC>(std::marker::PhantomData);
+
+    let mut strs: Vec<SignedTreeRoot> = vec![];
+    for epoch in 1..=3u64 {
+        let updates = (0..10)
+            .map(|i| {
+                (
+                    AkdLabel(format!("label{i}").into_bytes()),
+                    AkdValue(format!("value{epoch}").into_bytes()),
+                )
+            })
+            .collect();
+        let EpochHash(epoch, root_hash) = akd.publish(updates).await?;
+        let signature = akd_core::signing::EpochSigner::sign_epoch_message(
+            &signer,
+            &akd_core::signing::epoch_signature_message::<TC>(epoch, &root_hash),
+        );
+        strs.push(SignedTreeRoot::new::<TC>(
+            epoch,
+            root_hash,
+            signature,
+            strs.last(),
+        ));
+    }
+    strs[0].verify::<TC>(&signer, None)?;
+    for pair in strs.windows(2) {
+        pair[1].verify::<TC>(&signer, Some(&pair[0]))?;
+    }
+    // The chain breaks if an epoch is skipped, or an earlier root is altered
+    assert!(strs[2].verify::<TC>(&signer, Some(&strs[0])).is_err());
+    let mut altered = strs[0].clone();
+    altered.tree_hash = strs[1].tree_hash;
+    assert!(strs[1].verify::<TC>(&signer, Some(&altered)).is_err());
+    assert!(altered.verify::<TC>(&signer, None).is_err());
+
+    let root_hash = strs[2].tree_hash;
+    for i in 0..10 {
+        let label = AkdLabel(format!("label{i}").into_bytes());
+        let (proof, _) = akd.lookup(label.clone()).await?;
+        let path = AuthenticationPath::from_lookup_proof(&proof);
+        assert_eq!(ProofType::ProofOfInclusion, path.proof_type());
+        assert_eq!(Some(AkdValue::from("value3")), path.leaf.value);
+        path.verify::<TC>(vrf_pk.as_bytes(), root_hash, &label, proof.version)?;
+        assert!(path
+            .verify::<TC>(vrf_pk.as_bytes(), root_hash, &label, proof.version - 1)
+            .is_err());
+
+        // A different value does not verify against the leaf's commitment
+        let mut forged = path.clone();
+        forged.leaf.value = Some(AkdValue::from("forged"));
+        assert!(forged
+            .verify::<TC>(vrf_pk.as_bytes(), root_hash, &label, proof.version)
+            .is_err());
+
+        assert_eq!(proof.existence_proof, path.into_membership_proof()?);
+    }
+
+    for i in 10..20 {
+        let label = AkdLabel(format!("label{i}").into_bytes());
+        let (proof, _) = akd.lookup_absent(label.clone()).await?;
+        let path = AuthenticationPath::from_non_existence_proof::<TC>(&proof)?;
+        assert_eq!(ProofType::ProofOfAbsence, path.proof_type());
+        path.verify::<TC>(vrf_pk.as_bytes(), root_hash, &label, 1)?;
+        assert!(path
+            .verify::<TC>(vrf_pk.as_bytes(), strs[1].tree_hash, &label, 1)
+            .is_err());
+        assert!(path.clone().into_membership_proof().is_err());
+        assert_eq!(proof, path.into_non_existence_proof::<TC>()?);
+    }
+
+    Ok(())
+}
+
+// Checks that a latest-version lookup verifies, and that it fails to verify when
+// any of the proofs that no newer version exists are withheld
+test_config!(test_lookup_verify_result);
+async fn test_lookup_verify_result<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+
+    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world"))])
+        .await?;
+    akd.publish(vec![(AkdLabel::from("other"), AkdValue::from("value"))])
+        .await?;
+    let vrf_pk = akd.get_public_key().await?;
+
+    let (proof, EpochHash(current_epoch, root_hash)) = akd.lookup(AkdLabel::from("hello")).await?;
+    let result = lookup_verify_result::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        current_epoch,
+        AkdLabel::from("hello"),
+        proof.clone(),
+    )?;
+    assert_eq!(AkdLabel::from("hello"), result.label);
+    assert_eq!(root_hash, result.root_hash);
+    assert_eq!(2, result.current_epoch);
+    assert_eq!(proof, result.proof);
+    assert_eq!(1, result.epochs_since_update());
+    assert!(!result.is_updated_in_current_epoch());
+    assert!(result.is_first_version());
+    assert!(!result.is_tombstone::<TC>());
+    result.assert_value_equals(&AkdValue::from("world"))?;
+    assert!(result
+        .assert_value_equals(&AkdValue::from("other"))
+        .is_err());
+    assert_eq!(
+        lookup_verify::<TC>(
             vrf_pk.as_bytes(),
             root_hash,
             current_epoch,
-            target_label.clone(),
-            malformed_proof,
-            HistoryVerificationParams::default(),
-        )
-        .is_err());
+            AkdLabel::from("hello"),
+            proof.clone(),
+        )?,
+        VerifyResult::from(result)
+    );
+
+    // The result is only produced for a valid proof
+    assert!(lookup_verify_result::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        current_epoch,
+        AkdLabel::from("other"),
+        proof,
+    )
+    .is_err());
+    Ok(())
+}
+
+test_config!(test_lookup_latest);
+async fn test_lookup_latest<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+
+    // "hello" is updated in epochs 1 and 2, and then left unchanged until epoch 9
+    for epoch in 1..=9u64 {
+        let mut updates = vec![(
+            AkdLabel(format!("other{epoch}").into_bytes()),
+            AkdValue::from("value"),
+        )];
+        if epoch <= 2 {
+            updates.push((
+                AkdLabel::from("hello"),
+                AkdValue(format!("world{epoch}").into_bytes()),
+            ));
+        }
+        akd.publish(updates).await?;
     }
+    let vrf_pk = akd.get_public_key().await?;
+
+    let (proof, EpochHash(current_epoch, root_hash)) =
+        akd.lookup_latest(AkdLabel::from("hello")).await?;
+    let result = lookup_latest_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        current_epoch,
+        AkdLabel::from("hello"),
+        proof.clone(),
+    )?;
+    assert_eq!(
+        VerifyResult {
+            epoch: 2,
+            version: 2,
+            value: AkdValue::from("world2"),
+        },
+        result
+    );
+
+    // Withholding the non-existence of the next version is detected
+    let mut missing_until_marker = proof.clone();
+    missing_until_marker.until_marker_vrf_proofs.clear();
+    missing_until_marker
+        .non_existence_until_marker_proofs
+        .clear();
+    assert!(lookup_latest_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        current_epoch,
+        AkdLabel::from("hello"),
+        missing_until_marker,
+    )
+    .is_err());
+
+    // Withholding the non-existence of a future marker is detected
+    let mut missing_future_marker = proof.clone();
+    missing_future_marker.future_marker_vrf_proofs.pop();
+    missing_future_marker
+        .non_existence_of_future_marker_proofs
+        .pop();
+    assert!(lookup_latest_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        current_epoch,
+        AkdLabel::from("hello"),
+        missing_future_marker,
+    )
+    .is_err());
+
+    // The proof is only valid for the epoch at which it was generated
+    assert!(lookup_latest_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        current_epoch + 8,
+        AkdLabel::from("hello"),
+        proof,
+    )
+    .is_err());
 
     Ok(())
 }
 
-/*
-=========== Test Helpers ===========
-*/
+// Checks that a selective-disclosure history proof only reveals the values
+// of the versions within the disclosed epochs, and that it verifies
+test_config!(test_selective_key_history);
+async fn test_selective_key_history<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
 
-async fn async_poll_helper_proof<TC: Configuration, T: Database + 'static, V: VRFKeyStorage>(
-    reader: &ReadOnlyDirectory<TC, T, V>,
-    value: AkdValue,
-) -> Result<(), AkdError> {
-    // reader should read "hello" and this will populate the "cache" a log
-    let (lookup_proof, root_hash) = reader.lookup(AkdLabel::from("hello")).await?;
-    assert_eq!(value, lookup_proof.value);
-    let pk = reader.get_public_key().await?;
-    lookup_verify::<TC>(
-        pk.as_bytes(),
-        root_hash.hash(),
-        root_hash.epoch(),
+    // "hello" is updated in epochs 1 through 5
+    for epoch in 1..=5u64 {
+        akd.publish(vec![(
+            AkdLabel::from("hello"),
+            AkdValue(format!("world{epoch}").into_bytes()),
+        )])
+        .await?;
+    }
+    let vrf_pk = akd.get_public_key().await?;
+
+    let (proof, EpochHash(current_epoch, root_hash)) = akd
+        .selective_key_history(&AkdLabel::from("hello"), HistoryParams::default(), 2, 3)
+        .await?;
+    let results = selective_key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        current_epoch,
         AkdLabel::from("hello"),
-        lookup_proof,
+        proof.clone(),
     )?;
+    assert_eq!(
+        vec![
+            (5, None),
+            (4, None),
+            (3, Some(AkdValue::from("world3"))),
+            (2, Some(AkdValue::from("world2"))),
+            (1, None),
+        ],
+        results
+            .into_iter()
+            .map(|result| (result.epoch, result.value))
+            .collect::<Vec<_>>()
+    );
+
+    // A hidden version with the wrong commitment fails to verify
+    let mut wrong_commitment = proof.clone();
+    wrong_commitment.update_proofs[0].disclosure = UpdateDisclosure::Hidden {
+        commitment: AzksValue([0u8; DIGEST_BYTES]),
+    };
+    assert!(selective_key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        current_epoch,
+        AkdLabel::from("hello"),
+        wrong_commitment,
+    )
+    .is_err());
+
+    // Hiding a version within the disclosed epochs fails to verify, even with its
+    // correct commitment taken from a proof where it was hidden
+    let (other_proof, _) = akd
+        .selective_key_history(&AkdLabel::from("hello"), HistoryParams::default(), 5, 5)
+        .await?;
+    let mut hidden_in_range = proof.clone();
+    hidden_in_range.update_proofs[2].disclosure = other_proof.update_proofs[2].disclosure.clone();
+    assert!(matches!(
+        hidden_in_range.update_proofs[2].disclosure,
+        UpdateDisclosure::Hidden { .. }
+    ));
+    assert!(selective_key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        current_epoch,
+        AkdLabel::from("hello"),
+        hidden_in_range,
+    )
+    .is_err());
+
+    // With the disclosed epochs narrowed to exclude it, the same hidden version verifies
+    let mut narrowed = proof;
+    narrowed.disclosed_start_epoch = 2;
+    narrowed.disclosed_end_epoch = 2;
+    narrowed.update_proofs[2].disclosure = other_proof.update_proofs[2].disclosure.clone();
+    selective_key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        current_epoch,
+        AkdLabel::from("hello"),
+        narrowed,
+    )?;
+
+    Ok(())
+}
+
+test_config!(test_aggregated_key_history);
+async fn test_aggregated_key_history<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+
+    // "hello" is updated in epochs 1 through 6
+    for epoch in 1..=6u64 {
+        akd.publish(vec![(
+            AkdLabel::from("hello"),
+            AkdValue(format!("world{epoch}").into_bytes()),
+        )])
+        .await?;
+    }
+    let vrf_pk = akd.get_public_key().await?;
+    let versions = |results: &[VerifyResult]| {
+        results
+            .iter()
+            .map(|result| result.version)
+            .collect::<Vec<_>>()
+    };
+
+    // Without an aggregate, the proof holds the complete history
+    let (proof, EpochHash(current_epoch, root_hash)) =
+        akd.aggregated_key_history(&AkdLabel::from("hello")).await?;
+    let result = aggregated_key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        current_epoch,
+        AkdLabel::from("hello"),
+        proof,
+        HistoryVerificationParams::default(),
+    )?;
+    assert_eq!(None, result.aggregate);
+    assert_eq!(vec![6, 5, 4, 3, 2, 1], versions(&result.recent));
+
+    // The latest version cannot be aggregated
+    assert!(akd
+        .aggregate_history(AkdLabel::from("hello"), 6)
+        .await
+        .is_err());
+    akd.aggregate_history(AkdLabel::from("hello"), 4).await?;
+    let (proof, EpochHash(current_epoch, root_hash)) =
+        akd.aggregated_key_history(&AkdLabel::from("hello")).await?;
+    let result = aggregated_key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        current_epoch,
+        AkdLabel::from("hello"),
+        proof.clone(),
+        HistoryVerificationParams::default(),
+    )?;
+    assert_eq!(vec![6, 5], versions(&result.recent));
+    let aggregate = result.aggregate.expect("The history has been aggregated");
+    assert_eq!((4, 4), (aggregate.last_version, aggregate.last_epoch));
+
+    // A client which holds the earlier versions checks them against the aggregate
+    let (complete, _) = akd
+        .key_history(&AkdLabel::from("hello"), HistoryParams::Complete)
+        .await?;
+    assert_eq!(
+        aggregate,
+        HistoryAggregate::default().extend::<TC>(&complete.update_proofs[2..])?
+    );
+    assert_ne!(
+        aggregate,
+        HistoryAggregate::default().extend::<TC>(&complete.update_proofs[3..])?
+    );
+
+    // Omitting the aggregate, or an explicit version after it, fails to verify
+    let mut without_aggregate = proof.clone();
+    without_aggregate.aggregate = None;
+    assert!(aggregated_key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        current_epoch,
+        AkdLabel::from("hello"),
+        without_aggregate,
+        HistoryVerificationParams::default(),
+    )
+    .is_err());
+    let (most_recent, _) = akd
+        .key_history(
+            &AkdLabel::from("hello"),
+            HistoryParams::MostRecentInsecure(1),
+        )
+        .await?;
+    let mut with_gap = proof.clone();
+    with_gap.recent = most_recent;
+    assert!(aggregated_key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        current_epoch,
+        AkdLabel::from("hello"),
+        with_gap,
+        HistoryVerificationParams::default(),
+    )
+    .is_err());
+
+    // A later aggregate extends the earlier one
+    assert!(akd
+        .aggregate_history(AkdLabel::from("hello"), 4)
+        .await
+        .is_err());
+    akd.aggregate_history(AkdLabel::from("hello"), 5).await?;
+    let (proof, EpochHash(current_epoch, root_hash)) =
+        akd.aggregated_key_history(&AkdLabel::from("hello")).await?;
+    let result = aggregated_key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        current_epoch,
+        AkdLabel::from("hello"),
+        proof,
+        HistoryVerificationParams::default(),
+    )?;
+    assert_eq!(vec![6], versions(&result.recent));
+    assert_eq!(
+        Some(HistoryAggregate::default().extend::<TC>(&complete.update_proofs[1..])?),
+        result.aggregate
+    );
+
+    Ok(())
+}
+
+// Checks that batch tombstoning applies the retention policy of each label, and that the
+// histories of the tombstoned labels still verify, hide, and aggregate their removed values
+test_config!(test_tombstone_older_than);
+async fn test_tombstone_older_than<TC: Configuration>() -> Result<(), AkdError> {
+    use crate::configuration::TombstoneMetadataConfiguration;
+    use crate::HistoryProof;
+
+    type TM<TC> = TombstoneMetadataConfiguration<TC>;
+
+    let db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TM<TC>, _, _>::new(StorageManager::new_no_cache(db.clone()), vrf.clone())
+        .await?;
+
+    // "alice", "bob", and "carol" are updated in epochs 1 through 3
+    for epoch in 1..=3 {
+        akd.publish(
+            ["alice", "bob", "carol"]
+                .into_iter()
+                .map(|label| {
+                    (
+                        AkdLabel::from(label),
+                        AkdValue(format!("{label}{epoch}").into_bytes()),
+                    )
+                })
+                .collect(),
+        )
+        .await?;
+    }
+    for epoch in 4..=5 {
+        akd.publish(vec![(
+            AkdLabel(format!("padding{epoch}").into_bytes()),
+            AkdValue::from("value"),
+        )])
+        .await?;
+    }
+
+    // A follower cannot tombstone values
+    let follower = Directory::<TM<TC>, _, _>::new(
+        StorageManager::new_no_cache(db).with_role(StorageRole::Follower),
+        vrf,
+    )
+    .await?;
+    assert!(matches!(
+        follower.tombstone_older_than(0).await,
+        Err(AkdError::Directory(DirectoryError::ReadOnlyDirectory(_)))
+    ));
+
+    // By default, the versions published before epoch 2 are tombstoned, while "bob" is
+    // retained indefinitely and "carol" only keeps its latest version
+    let policy = RetentionPolicy::new(4)
+        .with_override(AkdLabel::from("bob"), None)
+        .with_override(AkdLabel::from("carol"), Some(0));
+    assert_eq!(3, akd.apply_retention_policy(&policy).await?);
+    assert_eq!(0, akd.apply_retention_policy(&policy).await?);
+
+    let vrf_pk = akd.get_public_key().await?;
+    let verify = |label: &str, history: HistoryProof, params, EpochHash(epoch, root_hash)| {
+        key_history_verify::<TM<TC>>(
+            vrf_pk.as_bytes(),
+            root_hash,
+            epoch,
+            AkdLabel::from(label),
+            history,
+            params,
+        )
+    };
+    for (label, num_tombstoned) in [("alice", 1), ("bob", 0), ("carol", 2)] {
+        let (history, epoch_hash) = akd
+            .key_history(&AkdLabel::from(label), HistoryParams::default())
+            .await?;
+        let results = verify(
+            label,
+            history.clone(),
+            HistoryVerificationParams::AllowMissingValues,
+            epoch_hash.clone(),
+        )?;
+        assert_eq!(
+            num_tombstoned,
+            results
+                .iter()
+                .filter(|result| TM::<TC>::is_tombstone(&result.value))
+                .count()
+        );
+        assert_eq!(
+            num_tombstoned == 0,
+            verify(
+                label,
+                history,
+                HistoryVerificationParams::default(),
+                epoch_hash
+            )
+            .is_ok()
+        );
+    }
+
+    // The tombstoned versions of "carol" are still hidden and aggregated by the commitments
+    // recorded in their tombstones
+    let (proof, EpochHash(epoch, root_hash)) = akd
+        .selective_key_history(&AkdLabel::from("carol"), HistoryParams::default(), 3, 3)
+        .await?;
+    let results = selective_key_history_verify::<TM<TC>>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("carol"),
+        proof,
+    )?;
+    assert_eq!(
+        vec![Some(AkdValue::from("carol3")), None, None],
+        results
+            .into_iter()
+            .map(|result| result.value)
+            .collect::<Vec<_>>()
+    );
+
+    akd.aggregate_history(AkdLabel::from("carol"), 2).await?;
+    let (proof, EpochHash(epoch, root_hash)) =
+        akd.aggregated_key_history(&AkdLabel::from("carol")).await?;
+    let result = aggregated_key_history_verify::<TM<TC>>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("carol"),
+        proof,
+        HistoryVerificationParams::default(),
+    )?;
+    let (complete, _) = akd
+        .key_history(&AkdLabel::from("carol"), HistoryParams::Complete)
+        .await?;
+    assert_eq!(
+        Some(HistoryAggregate::default().extend::<TM<TC>>(&complete.update_proofs[1..])?),
+        result.aggregate
+    );
+
+    // Without the overrides, the first two versions of "bob" are tombstoned as well, along
+    // with the second version of "alice", since the aggregate was published in epoch 6
+    assert_eq!(3, akd.tombstone_older_than(4).await?);
+
+    Ok(())
+}
+
+// Checks that the retention classes of labels are kept by their later versions, take
+// precedence over the retention policy when values are pruned, and are reflected in exports
+test_config!(test_retention_classes);
+async fn test_retention_classes<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+
+    // "a", "b", and "c" are updated in epochs 1 through 3
+    for epoch in 1..=3 {
+        akd.publish(
+            ["a", "b", "c"]
+                .into_iter()
+                .map(|label| {
+                    (
+                        AkdLabel::from(label),
+                        AkdValue(format!("{label}{epoch}").into_bytes()),
+                    )
+                })
+                .collect(),
+        )
+        .await?;
+    }
+
+    // A class can only be assigned to a label which has been published
+    assert!(akd
+        .set_retention_class(&AkdLabel::from("d"), RetentionClass::LegalHold)
+        .await
+        .is_err());
+    akd.set_retention_class(&AkdLabel::from("b"), RetentionClass::LegalHold)
+        .await?;
+    akd.set_retention_class(&AkdLabel::from("c"), RetentionClass::Ephemeral)
+        .await?;
+
+    // The classes are kept by the versions published after they were assigned
+    akd.publish(vec![
+        (AkdLabel::from("b"), AkdValue::from("b4")),
+        (AkdLabel::from("c"), AkdValue::from("c4")),
+    ])
+    .await?;
+    let classes = |export: DirectoryExport| {
+        export
+            .entries
+            .into_iter()
+            .map(|entry| entry.retention_class)
+            .collect::<Vec<_>>()
+    };
+    for (label, class) in [
+        ("a", RetentionClass::Standard),
+        ("b", RetentionClass::LegalHold),
+        ("c", RetentionClass::Ephemeral),
+    ] {
+        assert_eq!(class, akd.retention_class(&AkdLabel::from(label)).await?);
+    }
+    assert_eq!(
+        vec![
+            RetentionClass::Standard,
+            RetentionClass::LegalHold,
+            RetentionClass::Ephemeral
+        ],
+        classes(akd.export(4).await?)
+    );
+    assert_eq!(
+        vec![RetentionClass::Standard; 3],
+        classes(akd.export(2).await?)
+    );
+
+    // Values of "a" published before epoch 2 are tombstoned, while "b" keeps all of its
+    // values despite its override, and "c" only keeps its latest value
+    let policy = RetentionPolicy::new(3).with_override(AkdLabel::from("b"), Some(0));
+    assert_eq!(4, akd.apply_retention_policy(&policy).await?);
+    let vrf_pk = akd.get_public_key().await?;
+    let (history, EpochHash(epoch, root_hash)) = akd
+        .key_history(&AkdLabel::from("b"), HistoryParams::default())
+        .await?;
+    key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("b"),
+        history,
+        HistoryVerificationParams::default(),
+    )?;
+
+    // Once "b" is released from its hold, its older values are pruned like those of "a"
+    akd.set_retention_class(&AkdLabel::from("b"), RetentionClass::Standard)
+        .await?;
+    assert_eq!(
+        RetentionClass::Standard,
+        akd.retention_class(&AkdLabel::from("b")).await?
+    );
+    assert_eq!(4, akd.tombstone_older_than(0).await?);
+
+    Ok(())
+}
+
+// This test is testing the key_history function with a limited history.
+// We also want this update to verify.
+test_config!(test_limited_key_history);
+async fn test_limited_key_history<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage_manager = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    // epoch 0
+    let akd = Directory::<TC, _, _>::new(storage_manager, vrf).await?;
+
+    // epoch 1
+    akd.publish(vec![
+        (AkdLabel::from("hello"), AkdValue::from("world")),
+        (AkdLabel::from("hello2"), AkdValue::from("world2")),
+    ])
+    .await?;
+
+    // epoch 2
+    akd.publish(vec![
+        (AkdLabel::from("hello"), AkdValue::from("world_2")),
+        (AkdLabel::from("hello2"), AkdValue::from("world2_2")),
+    ])
+    .await?;
+
+    // epoch 3
+    akd.publish(vec![
+        (AkdLabel::from("hello"), AkdValue::from("world3")),
+        (AkdLabel::from("hello2"), AkdValue::from("world4")),
+    ])
+    .await?;
+
+    // epoch 4
+    akd.publish(vec![
+        (AkdLabel::from("hello3"), AkdValue::from("world")),
+        (AkdLabel::from("hello4"), AkdValue::from("world2")),
+    ])
+    .await?;
+
+    // epoch 5
+    akd.publish(vec![(
+        AkdLabel::from("hello"),
+        AkdValue::from("world_updated"),
+    )])
+    .await?;
+
+    // epoch 6
+    akd.publish(vec![
+        (AkdLabel::from("hello3"), AkdValue::from("world6")),
+        (AkdLabel::from("hello4"), AkdValue::from("world12")),
+    ])
+    .await?;
+
+    // epoch 7
+    akd.publish(vec![
+        (AkdLabel::from("hello3"), AkdValue::from("world7")),
+        (AkdLabel::from("hello4"), AkdValue::from("world13")),
+    ])
+    .await?;
+    // Get the VRF public key
+    let vrf_pk = akd.get_public_key().await?;
+
+    // Get the current epoch and the current root hash for this akd.
+    let current_azks = akd.retrieve_azks().await?;
+    let current_epoch = current_azks.get_latest_epoch();
+
+    // "hello" was updated in epochs 1,2,3,5. Pull the latest item from the history (i.e. a lookup proof)
+    let (history_proof, root_hash) = akd
+        .key_history(
+            &AkdLabel::from("hello"),
+            HistoryParams::MostRecentInsecure(1),
+        )
+        .await?;
+    assert_eq!(1, history_proof.update_proofs.len());
+    assert_eq!(5, history_proof.update_proofs[0].epoch);
+
+    // Now check that the key history verifies
+    key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash.hash(),
+        current_epoch,
+        AkdLabel::from("hello"),
+        history_proof,
+        HistoryVerificationParams::default(),
+    )?;
+
+    // Take the top 3 results, and check that we're getting the right epoch updates
+    let (history_proof, root_hash) = akd
+        .key_history(
+            &AkdLabel::from("hello"),
+            HistoryParams::MostRecentInsecure(3),
+        )
+        .await?;
+    assert_eq!(3, history_proof.update_proofs.len());
+    assert_eq!(5, history_proof.update_proofs[0].epoch);
+    assert_eq!(3, history_proof.update_proofs[1].epoch);
+    assert_eq!(2, history_proof.update_proofs[2].epoch);
+
+    // Now check that the key history verifies
+    key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash.hash(),
+        current_epoch,
+        AkdLabel::from("hello"),
+        history_proof,
+        HistoryVerificationParams::default(),
+    )?;
+
+    // "hello" was updated in epochs 1,2,3,5. Pull the updates since epoch 3 (inclusive)
+    let (history_proof, root_hash) = akd
+        .key_history(
+            &AkdLabel::from("hello"),
+            HistoryParams::SinceEpochInsecure(3),
+        )
+        .await?;
+    assert_eq!(2, history_proof.update_proofs.len());
+    assert_eq!(5, history_proof.update_proofs[0].epoch);
+    assert_eq!(3, history_proof.update_proofs[1].epoch);
+
+    // Now check that the key history verifies
+    key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash.hash(),
+        current_epoch,
+        AkdLabel::from("hello"),
+        history_proof,
+        HistoryVerificationParams::default(),
+    )?;
+
+    Ok(())
+}
+
+// This test covers the tests for PR #224, addresses issue #222: That key history does fail on a small tree,
+// when malicious updates are made.
+// Other that it is just a simple check to see that a valid key history proof passes.
+test_config!(test_malicious_key_history);
+async fn test_malicious_key_history<TC: Configuration>() -> Result<(), AkdError> {
+    // This test has an akd with a single label: "hello", followed by an
+    // insertion of a new label "hello2". Meanwhile, the server has a one epoch
+    // delay in marking the first version for "hello" as stale, which should
+    // be caught by key history verifications for "hello".
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+    // Publish the first value for the label "hello"
+    // Epoch here will be 1
+    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world"))])
+        .await?;
+    // Publish the second value for the label "hello" without marking the first value as stale
+    // Epoch here will be 2
+    let corruption_2 = PublishCorruption::UnmarkedStaleVersion(AkdLabel::from("hello"));
+    akd.publish_malicious_update(
+        vec![(AkdLabel::from("hello"), AkdValue::from("world2"))],
+        corruption_2,
+    )
+    .await?;
+
+    // Get the key_history_proof for the label "hello"
+    let (key_history_proof, root_hash) = akd
+        .key_history(&AkdLabel::from("hello"), HistoryParams::default())
+        .await?;
+    // Get the VRF public key
+    let vrf_pk = akd.get_public_key().await?;
+    // Verify the key history proof: This should fail since the server did not mark the version 1 for
+    // this username as stale, upon adding version 2.
+    key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash.hash(),
+        root_hash.epoch(),
+        AkdLabel::from("hello"),
+        key_history_proof,
+        HistoryVerificationParams::default(),
+    ).expect_err("The key history proof should fail here since the previous value was not marked stale at all");
+
+    // Mark the first value for the label "hello" as stale
+    // Epoch here will be 3
+    let corruption_3 = PublishCorruption::MarkVersionStale(AkdLabel::from("hello"), 1);
+    akd.publish_malicious_update(
+        vec![(AkdLabel::from("hello2"), AkdValue::from("world"))],
+        corruption_3,
+    )
+    .await?;
+
+    // Get the key_history_proof for the label "hello"
+    let (key_history_proof, root_hash) = akd
+        .key_history(&AkdLabel::from("hello"), HistoryParams::default())
+        .await?;
+    // Get the VRF public key
+    let vrf_pk = akd.get_public_key().await?;
+    // Verify the key history proof: This should still fail, since the server added the version number too late.
+    key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash.hash(),
+        root_hash.epoch(),
+        AkdLabel::from("hello"),
+        key_history_proof,
+        HistoryVerificationParams::default(),
+    ).expect_err("The key history proof should fail here since the previous value was marked stale one epoch too late.");
+
+    Ok(())
+}
+
+// This test ensures that identical directories produce byte-identical canonical
+// encodings of their proofs and epoch metadata.
+test_config!(test_canonical_encoding_is_deterministic);
+async fn test_canonical_encoding_is_deterministic<TC: Configuration>() -> Result<(), AkdError> {
+    let mut encodings = vec![];
+    for _ in 0..2 {
+        let db = AsyncInMemoryDatabase::new();
+        let storage = StorageManager::new_no_cache(db);
+        let vrf = HardCodedAkdVRF {};
+        let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+        for i in 0..3 {
+            akd.publish(vec![
+                (
+                    AkdLabel::from("hello"),
+                    AkdValue(format!("world{i}").into_bytes()),
+                ),
+                (
+                    AkdLabel(format!("user{i}").into_bytes()),
+                    AkdValue::from("value"),
+                ),
+            ])
+            .await?;
+        }
+        let (lookup_proof, epoch_hash) = akd.lookup(AkdLabel::from("hello")).await?;
+        let (history_proof, _) = akd
+            .key_history(&AkdLabel::from("hello"), HistoryParams::default())
+            .await?;
+        let audit_proof = akd.audit(1, 3).await?;
+        encodings.push((
+            lookup_proof.to_canonical_bytes(),
+            history_proof.to_canonical_bytes(),
+            audit_proof.to_canonical_bytes(),
+            epoch_hash.to_canonical_bytes(),
+        ));
+    }
+    assert_eq!(encodings[0], encodings[1]);
+
+    let mut expected_epoch_hash = vec![CANONICAL_ENCODING_VERSION];
+    expected_epoch_hash.extend_from_slice(&3u64.to_be_bytes());
+    expected_epoch_hash.extend_from_slice(&encodings[0].3[9..]);
+    assert_eq!(41, encodings[0].3.len());
+    assert_eq!(expected_epoch_hash, encodings[0].3);
+
+    Ok(())
+}
+
+// This test ensures that a single multi-epoch audit proof verifies against the root hashes
+// at its start and end epochs, and that it fails to verify when tampered with.
+test_config!(test_multi_epoch_audit);
+async fn test_multi_epoch_audit<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+
+    let mut root_hashes = vec![akd.get_epoch_hash().await?.1];
+    for i in 0..7 {
+        let mut updates = vec![(
+            AkdLabel::from("hello"),
+            AkdValue(format!("world{i}").into_bytes()),
+        )];
+        // Only insert a new user in some of the epochs, so that the chunks differ in size
+        if i % 3 == 0 {
+            updates.push((
+                AkdLabel(format!("user{i}").into_bytes()),
+                AkdValue::from("value"),
+            ));
+        }
+        akd.publish(updates).await?;
+        root_hashes.push(akd.get_epoch_hash().await?.1);
+    }
+
+    for (start, end) in [(1, 2), (1, 7), (2, 5), (3, 7)] {
+        let proof = akd.audit_multi_epoch(start, end).await?;
+        assert_eq!((end - start) as usize, proof.insertions.len());
+        audit_verify_multi_epoch::<TC>(
+            root_hashes[start as usize],
+            root_hashes[end as usize],
+            &proof,
+        )
+        .await?;
+
+        // The combined proof covers the same leaves as the per-epoch proofs
+        let per_epoch_proof = akd.audit(start, end).await?;
+        let mut per_epoch_inserted = per_epoch_proof
+            .proofs
+            .into_iter()
+            .flat_map(|p| p.inserted)
+            .collect::<Vec<_>>();
+        let mut inserted = proof
+            .insertions
+            .iter()
+            .flat_map(|chunk| chunk.inserted.clone())
+            .collect::<Vec<_>>();
+        per_epoch_inserted.sort_by_key(|element| element.label);
+        inserted.sort_by_key(|element| element.label);
+        assert_eq!(per_epoch_inserted, inserted);
+    }
+
+    let proof = akd.audit_multi_epoch(1, 7).await?;
+
+    // An incorrect end hash should fail verification
+    let verification = audit_verify_multi_epoch::<TC>(root_hashes[1], root_hashes[6], &proof).await;
+    assert!(matches!(verification, Err(AkdError::AzksErr(_))));
+
+    // Attributing leaves to the wrong epoch should fail verification
+    let mut tampered = proof.clone();
+    tampered.insertions[0].epoch += 1;
+    tampered.insertions.remove(1);
+    let verification =
+        audit_verify_multi_epoch::<TC>(root_hashes[1], root_hashes[7], &tampered).await;
+    assert!(verification.is_err());
+
+    // Chunks outside of the covered epochs are rejected
+    let mut tampered = proof.clone();
+    tampered.end_epoch = 5;
+    let verification =
+        audit_verify_multi_epoch::<TC>(root_hashes[1], root_hashes[7], &tampered).await;
+    assert!(matches!(verification, Err(AkdError::AuditErr(_))));
+
+    // Removing an inserted leaf should fail verification
+    let mut tampered = proof;
+    tampered.insertions[2].inserted.pop();
+    let verification =
+        audit_verify_multi_epoch::<TC>(root_hashes[1], root_hashes[7], &tampered).await;
+    assert!(matches!(verification, Err(AkdError::AzksErr(_))));
+
+    // The epochs must be increasing and must have taken place
+    assert!(akd.audit_multi_epoch(3, 3).await.is_err());
+    assert!(akd.audit_multi_epoch(3, 2).await.is_err());
+    assert!(akd.audit_multi_epoch(6, 8).await.is_err());
+
+    Ok(())
+}
+
+test_config!(test_consolidation);
+async fn test_consolidation<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+
+    // nothing has been published yet
+    assert_eq!(None, akd.consolidate().await?);
+    assert_eq!(None, akd.latest_audit_epoch().await?);
+
+    let mut mini_epochs = vec![akd.get_epoch_hash().await?];
+    let mut audit_epochs = vec![];
+    for (i, num_mini_epochs) in [3u64, 1, 2].into_iter().enumerate() {
+        for j in 0..num_mini_epochs {
+            mini_epochs.push(
+                akd.publish(vec![(
+                    AkdLabel(format!("user{i}-{j}").into_bytes()),
+                    AkdValue::from("value"),
+                )])
+                .await?,
+            );
+        }
+        let report = akd
+            .run_maintenance_task(MaintenanceTask::Consolidation)
+            .await?;
+        assert_eq!(num_mini_epochs as usize, report.records);
+        audit_epochs.push(akd.latest_audit_epoch().await?.unwrap());
+    }
+    // no mini-epochs have been published since the last consolidation
+    assert_eq!(None, akd.consolidate().await?);
+    assert_eq!(
+        vec![(1, 0, 3), (2, 3, 4), (3, 4, 6)],
+        audit_epochs
+            .iter()
+            .map(|audit_epoch| (
+                audit_epoch.index,
+                audit_epoch.start_epoch,
+                audit_epoch.end_epoch
+            ))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(audit_epochs[1], akd.audit_epoch(2).await?);
+    assert!(akd.audit_epoch(4).await.is_err());
+
+    // each audit epoch is verified with a single proof, and chains onto the previous one
+    let mut previous = None;
+    for audit_epoch in audit_epochs.iter() {
+        let proof = akd.audit_epoch_proof(audit_epoch.index).await?;
+        verify_audit_epoch::<TC>(audit_epoch, previous, &proof).await?;
+        previous = Some(audit_epoch);
+    }
+    let proof = akd.audit_epoch_proof(2).await?;
+    assert!(verify_audit_epoch::<TC>(&audit_epochs[1], None, &proof)
+        .await
+        .is_err());
+    assert!(
+        verify_audit_epoch::<TC>(&audit_epochs[2], Some(&audit_epochs[1]), &proof)
+            .await
+            .is_err()
+    );
+
+    // each mini-epoch is covered by its audit epoch
+    for mini_epoch in mini_epochs.iter().skip(1) {
+        let proof = akd.mini_epoch_proof(mini_epoch.epoch()).await?;
+        let audit_epoch = &audit_epochs[proof.audit_epoch as usize - 1];
+        assert!(
+            audit_epoch.start_epoch < mini_epoch.epoch()
+                && mini_epoch.epoch() <= audit_epoch.end_epoch
+        );
+        assert_eq!(
+            mini_epoch.epoch() == audit_epoch.end_epoch,
+            proof.proof.is_none()
+        );
+        verify_mini_epoch::<TC>(mini_epoch.clone(), audit_epoch, &proof).await?;
+
+        // a root hash which was not published at the mini-epoch is rejected
+        let forged = EpochHash(mini_epoch.epoch(), mini_epochs[0].hash());
+        assert!(verify_mini_epoch::<TC>(forged, audit_epoch, &proof)
+            .await
+            .is_err());
+    }
+    let proof = akd.mini_epoch_proof(2).await?;
+    assert!(
+        verify_mini_epoch::<TC>(mini_epochs[2].clone(), &audit_epochs[1], &proof)
+            .await
+            .is_err()
+    );
+
+    // mini-epochs which have not been consolidated have no proof
+    akd.publish(vec![(AkdLabel::from("latest"), AkdValue::from("value"))])
+        .await?;
+    assert!(akd.mini_epoch_proof(0).await.is_err());
+    assert!(akd.mini_epoch_proof(7).await.is_err());
+
+    // audit epochs are final
+    let signer = HashEpochSigner::<TC>(std::marker::PhantomData);
+    assert!(matches!(
+        akd.rollback_to(5, &signer).await,
+        Err(AkdError::Directory(DirectoryError::InvalidEpoch(_)))
+    ));
+    akd.rollback_to(6, &signer).await?;
+
+    Ok(())
+}
+
+test_config!(test_read_during_publish);
+async fn test_read_during_publish<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db.clone());
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+
+    // Publish once
+    akd.publish(vec![
+        (AkdLabel::from("hello"), AkdValue::from("world")),
+        (AkdLabel::from("hello2"), AkdValue::from("world2")),
+    ])
+    .await
+    .unwrap();
+    // Get the root hash after the first publish
+    let root_hash_1 = akd.get_epoch_hash().await?.1;
+    // Publish updates for the same labels.
+    akd.publish(vec![
+        (AkdLabel::from("hello"), AkdValue::from("world_2")),
+        (AkdLabel::from("hello2"), AkdValue::from("world2_2")),
+    ])
+    .await
+    .unwrap();
+
+    // Get the root hash after the second publish
+    let root_hash_2 = akd.get_epoch_hash().await?.1;
+
+    // Make the current azks a "checkpoint" to reset to later
+    let checkpoint_azks = akd.retrieve_azks().await.unwrap();
+
+    // Publish for the third time
+    akd.publish(vec![
+        (AkdLabel::from("hello"), AkdValue::from("world_3")),
+        (AkdLabel::from("hello2"), AkdValue::from("world2_3")),
+    ])
+    .await
+    .unwrap();
+
+    // Reset the azks record back to previous epoch, to emulate an akd reader
+    // communicating with storage that is in the middle of a publish operation
+    db.set(DbRecord::Azks(checkpoint_azks))
+        .await
+        .expect("Error resetting directory to previous epoch");
+
+    // re-create the directory instance so it refreshes from storage
+    let storage = StorageManager::new_no_cache(db.clone());
+    let vrf = HardCodedAkdVRF {};
+    let akd = ReadOnlyDirectory::<TC, _, _>::new(storage, vrf)
+        .await
+        .unwrap();
+
+    // Get the VRF public key
+    let vrf_pk = akd.get_public_key().await.unwrap();
+
+    // Lookup proof should contain the checkpoint epoch's value and still verify
+    let (lookup_proof, root_hash) = akd.lookup(AkdLabel::from("hello")).await.unwrap();
+    assert_eq!(AkdValue::from("world_2"), lookup_proof.value);
+    lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash.hash(),
+        root_hash.epoch(),
+        AkdLabel::from("hello"),
+        lookup_proof,
+    )
+    .unwrap();
+
+    // History proof should not contain the third epoch's update but still verify
+    let (history_proof, root_hash) = akd
+        .key_history(&AkdLabel::from("hello"), HistoryParams::default())
+        .await
+        .unwrap();
+    key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash.hash(),
+        root_hash.epoch(),
+        AkdLabel::from("hello"),
+        history_proof,
+        HistoryVerificationParams::default(),
+    )
+    .unwrap();
+
+    // Audit proof should only work up until checkpoint's epoch
+    let audit_proof = akd.audit(1, 2).await.unwrap();
+    audit_verify::<TC>(vec![root_hash_1, root_hash_2], audit_proof)
+        .await
+        .unwrap();
+
+    let invalid_audit = akd.audit(2, 3).await;
+    assert!(invalid_audit.is_err());
+
+    Ok(())
+}
+
+// This test is meant to test the function poll_for_azks_change
+// which is meant to detect changes in the azks, to prevent inconsistencies
+// between the local cache and storage.
+test_config!(test_directory_polling_azks_change);
+async fn test_directory_polling_azks_change<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new(db, None, None, None);
+    let vrf = HardCodedAkdVRF {};
+    // writer will write the AZKS record
+    let writer = Directory::<TC, _, _>::new(storage.clone(), vrf.clone()).await?;
+
+    writer
+        .publish(vec![
+            (AkdLabel::from("hello"), AkdValue::from("world")),
+            (AkdLabel::from("hello2"), AkdValue::from("world2")),
+        ])
+        .await?;
+
+    // reader will not write the AZKS but will be "polling" for AZKS changes
+    let reader = ReadOnlyDirectory::<TC, _, _>::new(storage, vrf).await?;
+
+    // start the poller
+    let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+    let reader_clone = reader.clone();
+    let _join_handle = tokio::task::spawn(async move {
+        reader_clone
+            .poll_for_azks_changes(tokio::time::Duration::from_millis(100), Some(tx))
+            .await
+    });
+
+    // wait for a second to make sure the poller has started
+    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+
+    // verify a lookup proof, which will populate the cache
+    async_poll_helper_proof(&reader, AkdValue::from("world")).await?;
+
+    // publish epoch 2
+    writer
+        .publish(vec![
+            (AkdLabel::from("hello"), AkdValue::from("world_2")),
+            (AkdLabel::from("hello2"), AkdValue::from("world2_2")),
+        ])
+        .await?;
+
+    // assert that the change is picked up in a reasonable time-frame and the cache is flushed
+    let notification = tokio::time::timeout(tokio::time::Duration::from_secs(10), rx.recv()).await;
+    assert!(matches!(notification, Ok(Some(()))));
+
+    async_poll_helper_proof(&reader, AkdValue::from("world_2")).await?;
+
+    Ok(())
+}
+
+// Checks that a commit which fails part way through writing its records is replayed from
+// the write-ahead log when the directory is next started
+test_config!(test_write_ahead_log_recovery);
+async fn test_write_ahead_log_recovery<TC: Configuration>() -> Result<(), AkdError> {
+    use crate::storage::wal::{InMemoryWriteAheadLog, WriteAheadLog};
+
+    let test_db = AsyncInMemoryDatabase::new();
+    let wal = InMemoryWriteAheadLog::new();
+    let vrf = HardCodedAkdVRF {};
+    let storage = StorageManager::new_no_cache(test_db.clone()).with_write_ahead_log(wal.clone());
+    let akd = Directory::<TC, _, _>::new(storage, vrf.clone()).await?;
+    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world"))])
+        .await?;
+    assert!(wal.pending().await?.is_empty());
+
+    // A database which crashes after writing half of the records of a commit
+    let mut db = MockLocalDatabase {
+        ..Default::default()
+    };
+    let tmp_db = test_db.clone();
+    db.expect_batch_set()
+        .withf(|_, state| matches!(state, DbSetState::TransactionCommit))
+        .returning(move |records, state| {
+            let half = records.len() / 2;
+            futures::executor::block_on(tmp_db.batch_set(records[..half].to_vec(), state))?;
+            Err(StorageError::Connection("Crashed".to_string()))
+        });
+    setup_mocked_db(&mut db, &test_db);
+    let storage = StorageManager::new_no_cache(db).with_write_ahead_log(wal.clone());
+    let akd = Directory::<TC, _, _>::new(storage, vrf.clone()).await?;
+    assert!(akd
+        .publish(vec![
+            (AkdLabel::from("hello"), AkdValue::from("world2")),
+            (AkdLabel::from("hello2"), AkdValue::from("world3")),
+        ])
+        .await
+        .is_err());
+    let pending = wal.pending().await?;
+    assert_eq!(vec![2], pending.iter().map(|e| e.epoch).collect::<Vec<_>>());
+    // the records of the tree were not all written, and so neither was the head of the tree
+    let azks = test_db
+        .get::<Azks>(&crate::append_only_zks::DEFAULT_AZKS_KEY)
+        .await?;
+    assert!(matches!(azks, DbRecord::Azks(azks) if azks.latest_epoch == 1));
+
+    // Restarting the directory completes the commit
+    let storage = StorageManager::new_no_cache(test_db.clone()).with_write_ahead_log(wal.clone());
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+    assert!(wal.pending().await?.is_empty());
+    let EpochHash(epoch, root_hash) = akd.get_epoch_hash().await?;
+    assert_eq!(2, epoch);
+    let vrf_pk = akd.get_public_key().await?;
+    for (label, value) in [("hello", "world2"), ("hello2", "world3")] {
+        let (proof, _) = akd.lookup(AkdLabel::from(label)).await?;
+        assert_eq!(AkdValue::from(value), proof.value);
+        lookup_verify::<TC>(
+            vrf_pk.as_bytes(),
+            root_hash,
+            epoch,
+            AkdLabel::from(label),
+            proof,
+        )?;
+    }
+
+    Ok(())
+}
+
+// Checks that a commit which fails part way through writing its records is recorded in a
+// file-backed write-ahead log, from which it is replayed after the process restarts
+test_config!(test_file_write_ahead_log_recovery);
+async fn test_file_write_ahead_log_recovery<TC: Configuration>() -> Result<(), AkdError> {
+    use crate::storage::codec::ProtobufCodec;
+    use crate::storage::wal::{FileWriteAheadLog, WriteAheadLog};
+    use std::io::Write;
+
+    // each configuration's test has its own log
+    static NEXT_LOG: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "akd_wal_{}_{}.log",
+        std::process::id(),
+        NEXT_LOG.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+    ));
+    let test_db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    let wal = FileWriteAheadLog::open(&path, ProtobufCodec)?;
+    let storage = StorageManager::new_no_cache(test_db.clone()).with_write_ahead_log(wal);
+    let akd = Directory::<TC, _, _>::new(storage, vrf.clone()).await?;
+    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world"))])
+        .await?;
+    drop(akd);
+    // the log is emptied once every entry is completed
+    assert_eq!(0, std::fs::metadata(&path).unwrap().len());
+
+    // A database which crashes after writing half of the records of a commit
+    let mut db = MockLocalDatabase {
+        ..Default::default()
+    };
+    let tmp_db = test_db.clone();
+    db.expect_batch_set()
+        .withf(|_, state| matches!(state, DbSetState::TransactionCommit))
+        .returning(move |records, state| {
+            let half = records.len() / 2;
+            futures::executor::block_on(tmp_db.batch_set(records[..half].to_vec(), state))?;
+            Err(StorageError::Connection("Crashed".to_string()))
+        });
+    setup_mocked_db(&mut db, &test_db);
+    let wal = FileWriteAheadLog::open(&path, ProtobufCodec)?;
+    let storage = StorageManager::new_no_cache(db).with_write_ahead_log(wal);
+    let akd = Directory::<TC, _, _>::new(storage, vrf.clone()).await?;
+    assert!(akd
+        .publish(vec![
+            (AkdLabel::from("hello"), AkdValue::from("world2")),
+            (AkdLabel::from("hello2"), AkdValue::from("world3")),
+        ])
+        .await
+        .is_err());
+    drop(akd);
+    // the process crashes while appending the entry of another commit
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap()
+        .write_all(&[1, 0, 0, 1, 0, 1])
+        .unwrap();
+
+    // Reopening the log after the restart finds the pending entry, but not the partial one
+    let wal = FileWriteAheadLog::open(&path, ProtobufCodec)?;
+    let pending = wal.pending().await?;
+    assert_eq!(vec![2], pending.iter().map(|e| e.epoch).collect::<Vec<_>>());
+    let storage = StorageManager::new_no_cache(test_db.clone()).with_write_ahead_log(wal.clone());
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+    assert!(wal.pending().await?.is_empty());
+    let EpochHash(epoch, root_hash) = akd.get_epoch_hash().await?;
+    assert_eq!(2, epoch);
+    let vrf_pk = akd.get_public_key().await?;
+    for (label, value) in [("hello", "world2"), ("hello2", "world3")] {
+        let (proof, _) = akd.lookup(AkdLabel::from(label)).await?;
+        assert_eq!(AkdValue::from(value), proof.value);
+        lookup_verify::<TC>(
+            vrf_pk.as_bytes(),
+            root_hash,
+            epoch,
+            AkdLabel::from(label),
+            proof,
+        )?;
+    }
+    assert!(FileWriteAheadLog::open(&path, ProtobufCodec)?
+        .pending()
+        .await?
+        .is_empty());
+    std::fs::remove_file(&path).unwrap();
+
+    Ok(())
+}
+
+// Checks that when two publishers build the same epoch, only the first to commit it
+// succeeds, and the tree is left intact
+test_config!(test_concurrent_publish_conflict);
+async fn test_concurrent_publish_conflict<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    let akd1 =
+        Directory::<TC, _, _>::new(StorageManager::new_no_cache(db.clone()), vrf.clone()).await?;
+    akd1.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world"))])
+        .await?;
+
+    // The second publisher caches the head of the tree at epoch 1, and so continues to
+    // build upon it after the first publisher has committed epoch 2
+    let akd2 = Directory::<TC, _, _>::new(
+        StorageManager::new(db.clone(), None, None, None),
+        vrf.clone(),
+    )
+    .await?;
+    akd2.get_epoch_hash().await?;
+    akd1.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world2"))])
+        .await?;
+    let result = akd2
+        .publish(vec![(AkdLabel::from("hello2"), AkdValue::from("world3"))])
+        .await;
+    assert!(matches!(
+        result,
+        Err(AkdError::Storage(StorageError::Conflict(_)))
+    ));
+
+    // None of the records of the second publisher were written
+    assert!(matches!(
+        db.get_user_state(&AkdLabel::from("hello2"), ValueStateRetrievalFlag::MaxEpoch)
+            .await,
+        Err(StorageError::NotFound(_))
+    ));
+    let EpochHash(epoch, root_hash) = akd1.get_epoch_hash().await?;
+    assert_eq!(2, epoch);
+    let (proof, _) = akd1.lookup(AkdLabel::from("hello")).await?;
+    lookup_verify::<TC>(
+        akd1.get_public_key().await?.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("hello"),
+        proof,
+    )?;
+
+    Ok(())
+}
+
+// Checks that publishes which fail part way through (here, on reads from flaky storage) leave
+// the cache able to evict the records of old epochs, and the directory able to publish again
+test_config!(test_failed_publishes_do_not_stop_cache_eviction);
+async fn test_failed_publishes_do_not_stop_cache_eviction<TC: Configuration>(
+) -> Result<(), AkdError> {
+    const MAX_ATTEMPTS: usize = 50;
+    let db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    Directory::<TC, _, _>::new(StorageManager::new_no_cache(db.clone()), vrf.clone()).await?;
+
+    let flaky = MockDatabase::wrap(
+        db,
+        MockDatabaseOptions {
+            read_failure_rate: 0.3,
+            seed: 1,
+            ..Default::default()
+        },
+    );
+    let memory_limit_bytes = 64 * 1024;
+    let storage = StorageManager::new(
+        flaky.clone(),
+        Some(Duration::from_secs(3600)),
+        Some(memory_limit_bytes),
+        Some(Duration::from_millis(2)),
+    );
+    let mut akd = None;
+    for _ in 0..MAX_ATTEMPTS {
+        if let Ok(directory) = Directory::<TC, _, _>::new(storage.clone(), vrf.clone()).await {
+            akd = Some(directory);
+            break;
+        }
+    }
+    let akd = akd.expect("The directory could not be created");
+
+    for epoch in 1..=10u64 {
+        let updates = (0..20)
+            .map(|i| {
+                (
+                    AkdLabel::from(format!("user {i} of epoch {epoch}").as_str()),
+                    AkdValue::from("value"),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut attempts = 0;
+        while let Err(error) = akd.publish(updates.clone()).await {
+            attempts += 1;
+            assert!(
+                attempts < MAX_ATTEMPTS,
+                "Epoch {epoch} could not be published: {error:?}"
+            );
+            // a failed publish must not leave its transaction behind
+            assert!(!storage.is_transaction_active());
+            assert_eq!(Some(true), storage.is_cache_cleaning_enabled());
+        }
+        tokio::time::sleep(Duration::from_millis(3)).await;
+    }
+    assert!(flaky.stats().injected_failures > 0);
+
+    // the AZKS is always cached, so retrieving it cleans the cache without adding to it
+    storage
+        .get::<Azks>(&crate::append_only_zks::DEFAULT_AZKS_KEY)
+        .await?;
+    let stats = storage.cache_stats().await.unwrap();
+    let azks_bytes = stats
+        .for_type(crate::storage::types::StorageType::Azks)
+        .map_or(0, |azks| azks.bytes);
+    assert!(
+        stats.bytes - azks_bytes <= memory_limit_bytes,
+        "The cache holds {} bytes, over its limit of {memory_limit_bytes}",
+        stats.bytes - azks_bytes
+    );
+    Ok(())
+}
+
+test_config!(test_publish_op_makes_no_get_requests);
+async fn test_publish_op_makes_no_get_requests<TC: Configuration>() -> Result<(), AkdError> {
+    let test_db = AsyncInMemoryDatabase::new();
+
+    let mut db = MockLocalDatabase {
+        ..Default::default()
+    };
+    setup_mocked_db(&mut db, &test_db);
+
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf)
+        .await
+        .expect("Failed to create directory");
+
+    // Create a set with 2 updates, (label, value) pairs
+    // ("hello10", "hello10")
+    // ("hello11", "hello11")
+    let mut updates = vec![];
+    for i in 0..2 {
+        updates.push((
+            AkdLabel(format!("hello1{i}").as_bytes().to_vec()),
+            AkdValue(format!("hello1{i}").as_bytes().to_vec()),
+        ));
+    }
+    // Publish the updates. Now the akd's epoch will be 1.
+    akd.publish(updates)
+        .await
+        .expect("Failed to do initial publish");
+
+    // create a new mock, this time which explodes on any "get" of tree-nodes (shouldn't happen). It is still backed by the same
+    // async in-mem db so all previous data should be there
+    let mut db2 = MockLocalDatabase {
+        ..Default::default()
+    };
+    setup_mocked_db(&mut db2, &test_db);
+    db2.expect_get::<TreeNodeWithPreviousValue>()
+        .returning(|_| Err(StorageError::Other("Boom!".to_string())));
+
+    let storage = StorageManager::new_no_cache(db2);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf)
+        .await
+        .expect("Failed to create directory");
+
+    // create more updates
+    let mut updates = vec![];
+    for i in 0..2 {
+        updates.push((
+            AkdLabel(format!("hello1{i}").as_bytes().to_vec()),
+            AkdValue(format!("hello1{}", i + 1).as_bytes().to_vec()),
+        ));
+    }
+
+    // try to publish again, this time with the "boom" returning from any mocked get-calls
+    // on tree nodes
+    akd.publish(updates)
+        .await
+        .expect("Failed to do subsequent publish");
+
+    Ok(())
+}
+
+// Test coverage on issue #144, verification failures with
+// small trees (<4 nodes) in both the tests below
+// Note that the use of a VRF means that that the label
+// depends on the hash function being used.
+// The below two tests are identical except for the hash function being used.
+
+// Test lookup in a smaller tree with 2 leaves, using the Blake3 hash function.
+test_config!(test_simple_lookup_for_small_tree);
+async fn test_simple_lookup_for_small_tree<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    // epoch 0
+    let akd = Directory::<TC, _, _>::new(storage, vrf.clone()).await?;
+
+    // Create a set with 2 updates, (label, value) pairs
+    // ("hello10", "hello10")
+    // ("hello11", "hello11")
+    let mut updates = vec![];
+    for i in 0..2 {
+        updates.push((
+            AkdLabel(format!("hello1{i}").as_bytes().to_vec()),
+            AkdValue(format!("hello1{i}").as_bytes().to_vec()),
+        ));
+    }
+    // Publish the updates. Now the akd's epoch will be 1.
+    akd.publish(updates).await?;
+
+    // The label we will lookup is "hello10"
+    let target_label = AkdLabel(format!("hello1{}", 0).as_bytes().to_vec());
+
+    // retrieve the lookup proof
+    let (lookup_proof, root_hash) = akd.lookup(target_label.clone()).await?;
+
+    // Get the VRF public key
+    let vrf_pk = vrf.get_vrf_public_key().await?;
+
+    // perform the "traditional" AKD verification
+    let akd_result = crate::client::lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash.hash(),
+        root_hash.epoch(),
+        target_label.clone(),
+        lookup_proof,
+    )?;
+
+    // check the two results to make sure they both verify
+    assert_eq!(
+        akd_result,
+        VerifyResult {
+            epoch: 1,
+            version: 1,
+            value: AkdValue::from("hello10"),
+        },
+    );
+
+    Ok(())
+}
+
+// Test lookup_verify where version number exceeds epoch (and it should throw an error)
+test_config!(test_lookup_verify_invalid_version_number);
+async fn test_lookup_verify_invalid_version_number<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    // epoch 0
+    let akd = Directory::<TC, _, _>::new(storage, vrf.clone()).await?;
+
+    // Create a set with 2 updates, (label, value) pairs
+    // ("hello10", "hello10")
+    // ("hello11", "hello11")
+    let mut updates = vec![];
+    for i in 0..2 {
+        updates.push((
+            AkdLabel(format!("hello1{i}").as_bytes().to_vec()),
+            AkdValue(format!("hello1{i}").as_bytes().to_vec()),
+        ));
+    }
+    // Repeatedly publish the updates. Afterwards, the akd's epoch will be 10.
+    for _ in 0..10 {
+        akd.publish(updates.clone()).await?;
+    }
+
+    // The label we will lookup is "hello10"
+    let target_label = AkdLabel(format!("hello1{}", 0).as_bytes().to_vec());
+
+    // retrieve the lookup proof
+    let (lookup_proof, root_hash) = akd.lookup(target_label.clone()).await?;
+
+    // Get the VRF public key
+    let vrf_pk = vrf.get_vrf_public_key().await?;
+
+    let akd_result = crate::client::lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash.hash(),
+        root_hash.epoch() - 1, // To fake a lower epoch and trigger the error condition
+        target_label.clone(),
+        lookup_proof,
+    );
+
+    // Check that the result is a verification error
+    match akd_result {
+        Err(akd_core::verify::VerificationError::LookupProof(_)) => (),
+        _ => panic!("Expected an invalid epoch error"),
+    }
+
+    Ok(())
+}
+
+// Test key history verification for error handling of malformed key history proofs
+test_config!(test_key_history_verify_malformed);
+async fn test_key_history_verify_malformed<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf.clone()).await?;
+
+    let mut rng = crate::test_utils::test_rng();
+    for _ in 0..100 {
+        let updates = vec![(
+            AkdLabel("label".to_string().as_bytes().to_vec()),
+            AkdValue::random(&mut rng),
+        )];
+        akd.publish(updates.clone()).await?;
+    }
+
+    for _ in 0..100 {
+        let updates = vec![(
+            AkdLabel("another label".to_string().as_bytes().to_vec()),
+            AkdValue::random(&mut rng),
+        )];
+        akd.publish(updates.clone()).await?;
+    }
+
+    // Get the latest root hash
+    let EpochHash(current_epoch, root_hash) = akd.get_epoch_hash().await?;
+    // Get the VRF public key
+    let vrf_pk = akd.get_public_key().await?;
+    let target_label = AkdLabel("label".to_string().as_bytes().to_vec());
+
+    let (key_history_proof, _) = akd
+        .key_history(&target_label, HistoryParams::default())
+        .await?;
+
+    // Normal verification should succeed
+    key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        current_epoch,
+        target_label.clone(),
+        key_history_proof.clone(),
+        HistoryVerificationParams::default(),
+    )?;
+
+    let mut malformed_proof_1 = key_history_proof.clone();
+    malformed_proof_1.until_marker_vrf_proofs = key_history_proof.until_marker_vrf_proofs
+        [..key_history_proof.until_marker_vrf_proofs.len() - 1]
+        .to_vec();
+    let mut malformed_proof_2 = key_history_proof.clone();
+    malformed_proof_2.non_existence_until_marker_proofs = key_history_proof
+        .non_existence_until_marker_proofs
+        [..key_history_proof.non_existence_until_marker_proofs.len() - 1]
+        .to_vec();
+    let mut malformed_proof_3 = key_history_proof.clone();
+    malformed_proof_3.future_marker_vrf_proofs = key_history_proof.future_marker_vrf_proofs
+        [..key_history_proof.future_marker_vrf_proofs.len() - 1]
+        .to_vec();
+    let mut malformed_proof_4 = key_history_proof.clone();
+    malformed_proof_4.non_existence_of_future_marker_proofs = key_history_proof
+        .non_existence_of_future_marker_proofs[..key_history_proof
+        .non_existence_of_future_marker_proofs
+        .len()
+        - 1]
+        .to_vec();
+
+    // Malformed proof verification should fail
+    for malformed_proof in [
+        malformed_proof_1,
+        malformed_proof_2,
+        malformed_proof_3,
+        malformed_proof_4,
+    ] {
+        assert!(key_history_verify::<TC>(
+            vrf_pk.as_bytes(),
+            root_hash,
+            current_epoch,
+            target_label.clone(),
+            malformed_proof,
+            HistoryVerificationParams::default(),
+        )
+        .is_err());
+    }
+
+    Ok(())
+}
+
+/*
+=========== Test Helpers ===========
+*/
+
+async fn async_poll_helper_proof<TC: Configuration, T: Database + 'static, V: VRFKeyStorage>(
+    reader: &ReadOnlyDirectory<TC, T, V>,
+    value: AkdValue,
+) -> Result<(), AkdError> {
+    // reader should read "hello" and this will populate the "cache" a log
+    let (lookup_proof, root_hash) = reader.lookup(AkdLabel::from("hello")).await?;
+    assert_eq!(value, lookup_proof.value);
+    let pk = reader.get_public_key().await?;
+    lookup_verify::<TC>(
+        pk.as_bytes(),
+        root_hash.hash(),
+        root_hash.epoch(),
+        AkdLabel::from("hello"),
+        lookup_proof,
+    )?;
+    Ok(())
+}
+
+// Checks that a directory built with a builder has the options given to the builder
+test_config!(test_directory_builder);
+async fn test_directory_builder<TC: Configuration>() -> Result<(), AkdError> {
+    use crate::hooks::DirectoryHooks;
+    use crate::storage::manager::StorageRole;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct RecordingHook(Arc<Mutex<Vec<u64>>>);
+
+    impl DirectoryHooks for RecordingHook {
+        fn on_epoch_published(&self, epoch_hash: &EpochHash, _num_updates: usize) {
+            self.0.lock().unwrap().push(epoch_hash.epoch());
+        }
+    }
+
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+
+    // The storage manager and VRF are required
+    assert!(matches!(
+        Directory::<TC, _, HardCodedAkdVRF>::builder()
+            .storage(storage.clone())
+            .build()
+            .await,
+        Err(AkdError::Directory(DirectoryError::Builder(_)))
+    ));
+    assert!(matches!(
+        Directory::<TC, AsyncInMemoryDatabase, _>::builder()
+            .vrf(HardCodedAkdVRF {})
+            .build()
+            .await,
+        Err(AkdError::Directory(DirectoryError::Builder(_)))
+    ));
+    // A read-only directory cannot create the tree
+    assert!(Directory::<TC, _, _>::builder()
+        .storage(storage.clone())
+        .vrf(HardCodedAkdVRF {})
+        .read_only(true)
+        .build()
+        .await
+        .is_err());
+
+    let hook = RecordingHook::default();
+    let akd = Directory::<TC, _, _>::builder()
+        .storage(storage.clone())
+        .vrf(HardCodedAkdVRF {})
+        .operator(Operator {
+            identity: "alice".to_string(),
+            metadata: "ticket-1".to_string(),
+        })
+        .hook(hook.clone())
+        .hot_labels(vec![AkdLabel::from("hello")])
+        .build()
+        .await?;
+    assert_eq!(StorageRole::Leader, akd.role());
+    assert_eq!(vec![AkdLabel::from("hello")], akd.hot_labels());
+    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world"))])
+        .await?;
+    assert_eq!(vec![1], *hook.0.lock().unwrap());
+    let operations = akd.operations(0, 10).await?;
+    assert_eq!("alice", operations[0].identity);
+    assert_eq!("ticket-1", operations[0].metadata);
+
+    let follower = Directory::<TC, _, _>::builder()
+        .storage(storage)
+        .vrf(HardCodedAkdVRF {})
+        .read_only(true)
+        .build()
+        .await?;
+    assert_eq!(StorageRole::Follower, follower.role());
+    assert!(follower
+        .publish(vec![(AkdLabel::from("hello"), AkdValue::from("world2"))])
+        .await
+        .is_err());
+    Ok(())
+}
+
+test_config!(test_operations_log);
+async fn test_operations_log<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage.clone(), vrf.clone()).await?;
+    let signer = HashEpochSigner::<TC>(std::marker::PhantomData);
+    assert_eq!(0, akd.operation_count().await?);
+
+    akd.set_operator(Operator {
+        identity: "alice".to_string(),
+        metadata: "ticket-1".to_string(),
+    });
+    for epoch in 1..=3 {
+        akd.publish(vec![(
+            AkdLabel::from("hello"),
+            AkdValue(format!("world{epoch}").into_bytes()),
+        )])
+        .await?;
+    }
+    akd.set_operator(Operator {
+        identity: "bob".to_string(),
+        metadata: "ticket-2".to_string(),
+    });
+    akd.rebuild_from_values().await?;
+    akd.rollback_to(2, &signer).await?;
+    akd.run_maintenance_task(MaintenanceTask::Pruning { retain_epochs: 1 })
+        .await?;
+
+    // A new instance continues the log from where the previous one left off
+    let akd = Directory::<TC, _, _>::new(storage.clone(), vrf.clone()).await?;
+    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world4"))])
+        .await?;
+
+    let operations = akd.operations(0, 100).await?;
+    assert_eq!(7, akd.operation_count().await?);
+    assert_eq!(
+        vec![
+            (0, OperationKind::Publish, 1, "alice"),
+            (1, OperationKind::Publish, 2, "alice"),
+            (2, OperationKind::Publish, 3, "alice"),
+            (3, OperationKind::Rebuild, 3, "bob"),
+            (4, OperationKind::Rollback, 2, "bob"),
+            (5, OperationKind::Prune, 2, "bob"),
+            (6, OperationKind::Publish, 3, ""),
+        ],
+        operations
+            .iter()
+            .map(|operation| (
+                operation.sequence,
+                operation.kind,
+                operation.epoch,
+                operation.identity.as_str()
+            ))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!("ticket-2", operations[4].metadata);
+    assert!(operations
+        .windows(2)
+        .all(|pair| pair[0].timestamp_ms <= pair[1].timestamp_ms));
+
+    // The log can be read in pages, including from a read-only directory
+    let read_only = ReadOnlyDirectory::<TC, _, _>::new(storage, vrf).await?;
+    assert_eq!(operations[5..], read_only.operations(5, 10).await?[..]);
+    Ok(())
+}
+
+test_config!(test_export);
+async fn test_export<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+
+    let first = akd
+        .publish(vec![
+            (AkdLabel::from("b"), AkdValue::from("b1")),
+            (AkdLabel::from("a"), AkdValue::from("a1")),
+        ])
+        .await?;
+    let second = akd
+        .publish(vec![
+            (AkdLabel::from("a"), AkdValue::from("a2")),
+            (AkdLabel::from("c"), AkdValue::from("c1")),
+        ])
+        .await?;
+
+    let export = akd.export(1).await?;
+    assert_eq!(first.hash(), export.root_hash);
+    assert_eq!(
+        vec![(AkdLabel::from("a"), 1), (AkdLabel::from("b"), 1)],
+        export
+            .entries
+            .iter()
+            .map(|entry| (entry.label.clone(), entry.version))
+            .collect::<Vec<_>>()
+    );
+
+    let export = akd.export(2).await?;
+    assert_eq!(second.hash(), export.root_hash);
+    assert_eq!(3, export.entries.len());
+
+    // Each entry matches the membership proof for its version in the history proof
+    for entry in export.entries.iter() {
+        let (history, _) = akd
+            .key_history(&entry.label, HistoryParams::default())
+            .await?;
+        let update = history
+            .update_proofs
+            .iter()
+            .find(|update| update.version == entry.version)
+            .expect("version in history");
+        assert_eq!(entry.epoch, update.epoch);
+        assert_eq!(entry.node_label, update.existence_proof.label);
+        assert_eq!(
+            TC::hash_leaf_with_commitment(entry.commitment, entry.epoch).0,
+            update.existence_proof.hash_val.0
+        );
+    }
+
+    assert!(matches!(
+        akd.export(3).await,
+        Err(AkdError::Directory(DirectoryError::InvalidEpoch(_)))
+    ));
+    Ok(())
+}
+
+test_config!(test_health);
+async fn test_health<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new(db, None, None, None);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage.clone(), vrf).await?;
+
+    let report = akd.health(&HealthCheckOptions::default()).await;
+    assert_eq!(HealthStatus::Healthy, report.status);
+    assert_eq!(Some(0), report.epoch);
+    assert_eq!(None, report.last_publish_age);
+
+    let options = HealthCheckOptions {
+        max_publish_age: Some(Duration::from_secs(60)),
+        audited_epoch: Some(0),
+        max_audit_lag: 1,
+    };
+    // Nothing has been published yet
+    let report = akd.health(&options).await;
+    assert_eq!(HealthStatus::Degraded, report.last_publish.status);
+    assert_eq!(HealthStatus::Degraded, report.status);
+    assert!(report.is_serving());
+
+    akd.publish(vec![(AkdLabel::from("a"), AkdValue::from("a1"))])
+        .await?;
+    let report = akd.health(&options).await;
+    assert_eq!(HealthStatus::Healthy, report.status, "{report}");
+    assert_eq!(Some(1), report.audit_lag);
+    assert!(report.last_publish_age.is_some());
+    assert!(report.cache_items.unwrap_or_default() > 0);
+
+    akd.publish(vec![(AkdLabel::from("a"), AkdValue::from("a2"))])
+        .await?;
+    let report = akd.health(&options).await;
+    assert_eq!(HealthStatus::Degraded, report.audit.status);
+    assert_eq!(Some(2), report.audit_lag);
+
+    // Cache cleaning which is left disabled outside of a transaction is reported
+    storage.disable_cache_cleaning();
+    let report = akd.health(&HealthCheckOptions::default()).await;
+    assert_eq!(HealthStatus::Degraded, report.cache.status);
+    Ok(())
+}
+
+/// A calendar which returns a fixed pending attestation for every digest
+struct FakeCalendar;
+
+#[async_trait::async_trait]
+impl CalendarClient for FakeCalendar {
+    async fn submit_digest(&self, _digest: &crate::Digest) -> Result<Vec<u8>, AkdError> {
+        Ok(vec![0x00, 0x83, 0xdf, 0xe3, 0x0d, 0x2e, 0xf9, 0x0c, 0x8e])
+    }
+}
+
+test_config!(test_anchoring);
+async fn test_anchoring<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage.clone(), vrf.clone()).await?;
+    let timestamps = OpenTimestampsAnchor::new(FakeCalendar);
+    let notary = NotaryAnchor::new("notary", |epoch, root_hash| {
+        Box::pin(async move { Ok([&epoch.to_be_bytes()[..], &root_hash[..]].concat()) })
+    });
+
+    let first = akd
+        .publish(vec![(AkdLabel::from("hello"), AkdValue::from("world"))])
+        .await?;
+    let receipt = akd.anchor(&timestamps).await?;
+    assert_eq!(1, receipt.epoch);
+    assert_eq!(first.hash(), receipt.root_hash);
+    akd.anchor(&notary).await?;
+
+    let (proof, anchored) = akd.lookup_anchored(AkdLabel::from("hello")).await?;
+    assert_eq!(first, anchored.epoch_hash);
+    assert_eq!(
+        vec!["notary", OpenTimestampsAnchor::<FakeCalendar>::NAME],
+        anchored
+            .receipts
+            .iter()
+            .map(|receipt| receipt.anchor.as_str())
+            .collect::<Vec<_>>()
+    );
+    anchored.verify(&[&timestamps, &notary])?;
+    lookup_verify::<TC>(
+        vrf.get_vrf_public_key().await?.as_bytes(),
+        anchored.epoch_hash.hash(),
+        anchored.epoch_hash.epoch(),
+        AkdLabel::from("hello"),
+        proof,
+    )?;
+
+    // The receipts of an earlier epoch are not served with the next epoch
+    akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world2"))])
+        .await?;
+    let read_only = ReadOnlyDirectory::<TC, _, _>::new(storage, vrf).await?;
+    let anchored = read_only.get_anchored_root().await?;
+    assert!(anchored.receipts.is_empty());
+    assert!(matches!(
+        anchored.verify(&[&timestamps]),
+        Err(AkdError::Directory(DirectoryError::Anchoring(_)))
+    ));
+
+    // A receipt which does not match the root hash fails to verify
+    let mut tampered = akd.get_anchored_root().await?;
+    tampered.receipts.push(receipt);
+    assert!(tampered.verify(&[]).is_err());
+    Ok(())
+}
+
+test_config!(test_sharded_directory);
+async fn test_sharded_directory<TC: Configuration>() -> Result<(), AkdError> {
+    let storages = (0..4)
+        .map(|_| StorageManager::new_no_cache(AsyncInMemoryDatabase::new()))
+        .collect();
+    let akd = ShardedDirectory::<TC, _, _>::new(storages, HardCodedAkdVRF {}).await?;
+    let vrf_pk = akd.get_public_key().await?;
+    assert_eq!(4, akd.num_shards());
+
+    // a publish is split by shard, and only the shards with updates advance
+    let labels = (0..16)
+        .map(|i| AkdLabel::from(format!("label {i}").as_str()))
+        .collect::<Vec<_>>();
+    let root = akd
+        .publish(
+            labels
+                .iter()
+                .map(|label| (label.clone(), AkdValue::from("value 1")))
+                .collect(),
+        )
+        .await?;
+    assert_eq!(root, akd.get_root().await?);
+    let shard = shard_index::<TC>(&labels[0], 4);
+    let root = akd
+        .publish(vec![(labels[0].clone(), AkdValue::from("value 2"))])
+        .await?;
+    for (index, shard_root) in root.shard_roots.iter().enumerate() {
+        let published = labels
+            .iter()
+            .any(|label| shard_index::<TC>(label, 4) == index as u32);
+        let expected = u64::from(published) + u64::from(index as u32 == shard);
+        assert_eq!(expected, shard_root.epoch);
+    }
+
+    // lookups and histories verify against the top-level root hash
+    for label in labels.iter() {
+        let (proof, root_hash) = akd.lookup(label.clone()).await?;
+        assert_eq!(root.root_hash, root_hash);
+        let result =
+            sharded_lookup_verify::<TC>(vrf_pk.as_bytes(), root_hash, label.clone(), proof)?;
+        assert_eq!(
+            AkdValue::from("value 1") == result.value,
+            label != &labels[0]
+        );
+    }
+    let (proof, root_hash) = akd
+        .key_history(&labels[0], HistoryParams::default())
+        .await?;
+    let results = sharded_key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        labels[0].clone(),
+        proof,
+        HistoryVerificationParams::default(),
+    )?;
+    assert_eq!(2, results.len());
+
+    // a proof does not verify for a label of another shard, or against another root
+    let (proof, root_hash) = akd.lookup(labels[0].clone()).await?;
+    let other = labels
+        .iter()
+        .find(|label| shard_index::<TC>(label, 4) != shard)
+        .unwrap();
+    assert!(sharded_lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        other.clone(),
+        proof.clone()
+    )
+    .is_err());
+    let mut forged = proof.clone();
+    forged.inclusion.shard_root = root.shard_roots[(shard as usize + 1) % 4];
+    assert!(
+        sharded_lookup_verify::<TC>(vrf_pk.as_bytes(), root_hash, labels[0].clone(), forged)
+            .is_err()
+    );
+    akd.publish(vec![(other.clone(), AkdValue::from("value 2"))])
+        .await?;
+    let (_, new_root_hash) = akd.lookup(labels[0].clone()).await?;
+    assert_ne!(root_hash, new_root_hash);
+    assert!(sharded_lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        new_root_hash,
+        labels[0].clone(),
+        proof
+    )
+    .is_err());
+
+    // the number of shards must be a power of two
+    let storages = (0..3)
+        .map(|_| StorageManager::new_no_cache(AsyncInMemoryDatabase::new()))
+        .collect();
+    assert!(matches!(
+        ShardedDirectory::<TC, _, _>::new(storages, HardCodedAkdVRF {}).await,
+        Err(AkdError::Directory(DirectoryError::Sharding(_)))
+    ));
+    Ok(())
+}
+
+test_config!(test_leader_and_follower);
+async fn test_leader_and_follower<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    let leader =
+        Directory::<TC, _, _>::new(StorageManager::new_no_cache(db.clone()), vrf.clone()).await?;
+    leader
+        .publish(vec![(AkdLabel::from("hello"), AkdValue::from("world"))])
+        .await?;
+
+    // the follower caches records for longer than the test runs, so only a refresh can
+    // bring it up to date with the leader
+    let storage = StorageManager::new(db.clone(), Some(Duration::from_secs(3600)), None, None)
+        .with_role(StorageRole::Follower);
+    let follower = Directory::<TC, _, _>::new(storage.clone(), vrf).await?;
+    assert_eq!(StorageRole::Follower, follower.role());
+    assert_eq!(1, follower.get_epoch_hash().await?.epoch());
+
+    // the follower refuses every write
+    assert!(matches!(
+        follower
+            .publish(vec![(AkdLabel::from("hello"), AkdValue::from("fork"))])
+            .await,
+        Err(AkdError::Directory(DirectoryError::ReadOnlyDirectory(_)))
+    ));
+    assert!(matches!(
+        follower
+            .rollback_to(0, &HashEpochSigner::<TC>(std::marker::PhantomData))
+            .await,
+        Err(AkdError::Directory(DirectoryError::ReadOnlyDirectory(_)))
+    ));
+    let azks = storage
+        .get::<Azks>(&crate::append_only_zks::DEFAULT_AZKS_KEY)
+        .await?;
+    assert!(matches!(
+        storage.set(azks).await,
+        Err(StorageError::ReadOnly(_))
+    ));
+    assert!(matches!(
+        Directory::<TC, _, _>::new(
+            StorageManager::new_no_cache(AsyncInMemoryDatabase::new())
+                .with_role(StorageRole::Follower),
+            HardCodedAkdVRF {},
+        )
+        .await,
+        Err(AkdError::Directory(DirectoryError::ReadOnlyDirectory(_)))
+    ));
+
+    // a refresh picks up the epoch published by the leader
+    leader
+        .publish(vec![(AkdLabel::from("hello"), AkdValue::from("world 2"))])
+        .await?;
+    assert_eq!(1, follower.get_epoch_hash().await?.epoch());
+    assert_eq!(2, follower.refresh().await?);
+    let epoch_hash = follower.get_epoch_hash().await?;
+    assert_eq!(leader.get_epoch_hash().await?, epoch_hash);
+    let (proof, _) = follower.lookup(AkdLabel::from("hello")).await?;
+    let result = lookup_verify::<TC>(
+        follower.get_public_key().await?.as_bytes(),
+        epoch_hash.hash(),
+        epoch_hash.epoch(),
+        AkdLabel::from("hello"),
+        proof,
+    )?;
+    assert_eq!(AkdValue::from("world 2"), result.value);
+
+    // a spawned follower refreshes as soon as it is notified of a new epoch
+    let handle = follower.spawn_follower(Duration::from_secs(3600)).await?;
+    assert_eq!(2, handle.epoch());
+    let mut epochs = handle.subscribe();
+    leader
+        .publish(vec![(AkdLabel::from("hello"), AkdValue::from("world 3"))])
+        .await?;
+    handle.notify_epoch();
+    tokio::time::timeout(Duration::from_secs(10), epochs.changed())
+        .await
+        .expect("the follower refreshes when notified")
+        .expect("the follower is running");
+    assert_eq!(3, handle.epoch());
+    assert_eq!(3, follower.get_epoch_hash().await?.epoch());
+    handle.shutdown();
+    Ok(())
+}
+
+test_config!(test_publish_lease);
+async fn test_publish_lease<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    let first = Directory::<TC, _, _>::new(
+        StorageManager::new_no_cache(db.clone())
+            .with_publish_lease(PublishLeaseOptions::new("first")),
+        vrf.clone(),
+    )
+    .await?;
+    let second_storage = StorageManager::new_no_cache(db.clone())
+        .with_publish_lease(PublishLeaseOptions::new("second"));
+    let second = Directory::<TC, _, _>::new(second_storage.clone(), vrf).await?;
+
+    // the lease is released after each publish, so publishers may take turns
+    first
+        .publish(vec![(AkdLabel::from("first"), AkdValue::from("1"))])
+        .await?;
+    second
+        .publish(vec![(AkdLabel::from("second"), AkdValue::from("1"))])
+        .await?;
+
+    // while the lease is held, the other publisher fails without modifying the directory
+    let lease = second_storage.acquire_publish_lease().await?.unwrap();
+    let result = first
+        .publish(vec![(AkdLabel::from("first"), AkdValue::from("2"))])
+        .await;
+    assert!(matches!(
+        result,
+        Err(AkdError::Storage(StorageError::LeaseHeld(_)))
+    ));
+    assert_eq!(2, first.get_epoch_hash().await?.epoch());
+    assert!(matches!(
+        first
+            .rollback_to(1, &HashEpochSigner::<TC>(std::marker::PhantomData))
+            .await,
+        Err(AkdError::Storage(StorageError::LeaseHeld(_)))
+    ));
+    assert_eq!(2, first.get_epoch_hash().await?.epoch());
+    lease.release().await?;
+    first
+        .publish(vec![(AkdLabel::from("first"), AkdValue::from("2"))])
+        .await?;
+    assert_eq!(3, second.get_epoch_hash().await?.epoch());
+    Ok(())
+}
+
+test_config!(test_detect_divergence);
+async fn test_detect_divergence<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db.clone());
+    let (akd, report) = Directory::<TC, _, _>::new_checked(storage, HardCodedAkdVRF {}).await?;
+    assert!(report.is_consistent());
+    for epoch in 1..=2 {
+        akd.publish(vec![(
+            AkdLabel::from("label"),
+            AkdValue(format!("value {epoch}").into_bytes()),
+        )])
+        .await?;
+    }
+    let report = akd.detect_divergence().await?;
+    assert!(report.is_consistent(), "{report}");
+    assert_eq!(
+        (2, 2, true),
+        (
+            report.head_epoch,
+            report.num_operations,
+            report.root_hash_checked
+        )
+    );
+
+    // a second publisher which logged its own publish of epoch 2, and wrote the value state of
+    // an epoch 3 which it never committed
+    let mut operations = akd.operations(0, 2).await?;
+    let rival = OperationRecord {
+        sequence: 2,
+        identity: "rival".to_string(),
+        ..operations.remove(1)
+    };
+    let orphan = ValueState {
+        value: AkdValue::from("orphan"),
+        version: 3,
+        label: NodeLabel::root(),
+        epoch: 3,
+        username: AkdLabel::from("label"),
+        retention_class: None,
+    };
+    db.batch_set(
+        vec![
+            DbRecord::OperationRecord(rival.clone()),
+            DbRecord::ValueState(orphan.clone()),
+        ],
+        DbSetState::General,
+    )
+    .await?;
+    let report = akd.detect_divergence().await?;
+    assert_eq!(
+        vec![
+            Divergence::ConflictingPublish {
+                expected_epoch: 3,
+                operation: rival,
+                previous: akd.operations(1, 1).await?.remove(0),
+            },
+            Divergence::OrphanedValueStates {
+                states: vec![orphan]
+            },
+        ],
+        report.divergences
+    );
+    assert!(report.root_hash_checked);
+
+    // a head which was advanced without being logged
+    let mut azks = akd.retrieve_azks().await?;
+    azks.latest_epoch = 3;
+    db.set(DbRecord::Azks(azks)).await?;
+    let report = akd.detect_divergence().await?;
+    assert!(report.divergences.contains(&Divergence::HeadMismatch {
+        head_epoch: 3,
+        logged_epoch: 2
+    }));
+    assert!(report
+        .divergences
+        .iter()
+        .any(|divergence| matches!(divergence, Divergence::RootHashMismatch { epoch: 3, .. })));
+    Ok(())
+}
+
+// Checks that the lookups of hot labels are answered with the proofs precomputed when the
+// directory moved to its current epoch, without reading their value states
+test_config!(test_hot_labels);
+async fn test_hot_labels<TC: Configuration>() -> Result<(), AkdError> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let test_db = AsyncInMemoryDatabase::new();
+    let num_state_reads = Arc::new(AtomicUsize::new(0));
+    let mut db = MockLocalDatabase {
+        ..Default::default()
+    };
+    let tmp_db = test_db.clone();
+    let reads = num_state_reads.clone();
+    db.expect_get_user_state().returning(move |label, flag| {
+        reads.fetch_add(1, Ordering::SeqCst);
+        futures::executor::block_on(tmp_db.get_user_state(label, flag))
+    });
+    setup_mocked_db(&mut db, &test_db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(StorageManager::new_no_cache(db), vrf.clone()).await?;
+    let vrf_pk = akd.get_public_key().await?;
+
+    // labels which have not been published yet have no precomputed proof
+    let hot = vec![AkdLabel::from("alice"), AkdLabel::from("bob")];
+    assert_eq!(0, akd.set_hot_labels(hot.clone()).await?);
+    assert_eq!(hot, akd.hot_labels());
+
+    for version in 1..=2u64 {
+        akd.publish(vec![
+            (
+                AkdLabel::from("alice"),
+                AkdValue(format!("alice {version}").into_bytes()),
+            ),
+            (
+                AkdLabel::from("carol"),
+                AkdValue(format!("carol {version}").into_bytes()),
+            ),
+        ])
+        .await?;
+        // the proofs are precomputed in the background once the publish has returned
+        akd.wait_for_hot_lookups().await?;
+
+        let reads = num_state_reads.load(Ordering::SeqCst);
+        let (proof, epoch_hash) = akd.lookup(AkdLabel::from("alice")).await?;
+        assert_eq!(reads, num_state_reads.load(Ordering::SeqCst));
+        let result = lookup_verify::<TC>(
+            vrf_pk.as_bytes(),
+            epoch_hash.hash(),
+            epoch_hash.epoch(),
+            AkdLabel::from("alice"),
+            proof,
+        )?;
+        assert_eq!(version, result.version);
+        assert_eq!(version, epoch_hash.epoch());
+
+        // the other labels are looked up as usual
+        let (proof, epoch_hash) = akd.lookup(AkdLabel::from("carol")).await?;
+        assert!(num_state_reads.load(Ordering::SeqCst) > reads);
+        lookup_verify::<TC>(
+            vrf_pk.as_bytes(),
+            epoch_hash.hash(),
+            epoch_hash.epoch(),
+            AkdLabel::from("carol"),
+            proof,
+        )?;
+    }
+
+    // a follower precomputes the proofs of its own hot labels when it refreshes
+    let follower = Directory::<TC, _, _>::new(
+        StorageManager::new_no_cache(test_db.clone()).with_role(StorageRole::Follower),
+        vrf,
+    )
+    .await?;
+    assert_eq!(
+        1,
+        follower
+            .set_hot_labels(vec![AkdLabel::from("carol")])
+            .await?
+    );
+    akd.publish(vec![(AkdLabel::from("carol"), AkdValue::from("carol 3"))])
+        .await?;
+    assert_eq!(3, follower.refresh().await?);
+    let (proof, epoch_hash) = follower.lookup(AkdLabel::from("carol")).await?;
+    assert_eq!(3, epoch_hash.epoch());
+    let result = lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        epoch_hash.hash(),
+        epoch_hash.epoch(),
+        AkdLabel::from("carol"),
+        proof,
+    )?;
+    assert_eq!(AkdValue::from("carol 3"), result.value);
+    Ok(())
+}
+
+test_config!(test_proof_cache);
+async fn test_proof_cache<TC: Configuration>() -> Result<(), AkdError> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let test_db = AsyncInMemoryDatabase::new();
+    let num_state_reads = Arc::new(AtomicUsize::new(0));
+    let mut db = MockLocalDatabase {
+        ..Default::default()
+    };
+    let tmp_db = test_db.clone();
+    let reads = num_state_reads.clone();
+    db.expect_get_user_state().returning(move |label, flag| {
+        reads.fetch_add(1, Ordering::SeqCst);
+        futures::executor::block_on(tmp_db.get_user_state(label, flag))
+    });
+    setup_mocked_db(&mut db, &test_db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::builder()
+        .storage(StorageManager::new_no_cache(db))
+        .vrf(vrf.clone())
+        .proof_cache_capacity(2)
+        .build()
+        .await?;
+    let vrf_pk = akd.get_public_key().await?;
+    let signer = HashEpochSigner::<TC>(std::marker::PhantomData);
+    let lookup = |akd_label: &str| {
+        let akd = akd.clone();
+        let vrf_pk = vrf_pk.clone();
+        let akd_label = AkdLabel::from(akd_label);
+        async move {
+            let (proof, epoch_hash) = akd.lookup(akd_label.clone()).await?;
+            let result = lookup_verify::<TC>(
+                vrf_pk.as_bytes(),
+                epoch_hash.hash(),
+                epoch_hash.epoch(),
+                akd_label,
+                proof,
+            )?;
+            Ok::<_, AkdError>((epoch_hash, result.value))
+        }
+    };
+
+    akd.publish(vec![
+        (AkdLabel::from("a"), AkdValue::from("a1")),
+        (AkdLabel::from("b"), AkdValue::from("b1")),
+        (AkdLabel::from("c"), AkdValue::from("c1")),
+    ])
+    .await?;
+    let epoch_hash_1 = akd.get_epoch_hash().await?;
+
+    // the second lookup of a label within an epoch is answered from the cache
+    assert_eq!(0, akd.cached_proofs());
+    assert_eq!(AkdValue::from("a1"), lookup("a").await?.1);
+    assert_eq!(1, akd.cached_proofs());
+    let reads = num_state_reads.load(Ordering::SeqCst);
+    assert_eq!(
+        (epoch_hash_1.clone(), AkdValue::from("a1")),
+        lookup("a").await?
+    );
+    assert_eq!(reads, num_state_reads.load(Ordering::SeqCst));
+
+    // the oldest proofs are evicted beyond the capacity
+    lookup("b").await?;
+    lookup("c").await?;
+    assert_eq!(2, akd.cached_proofs());
+    let reads = num_state_reads.load(Ordering::SeqCst);
+    lookup("a").await?;
+    assert!(num_state_reads.load(Ordering::SeqCst) > reads);
+
+    // a publish leaves the cache as it is, the new value is served, and the proofs of the
+    // earlier epoch are discarded once a proof of the new epoch is cached
+    let epoch_hash_2 = akd
+        .publish(vec![(AkdLabel::from("a"), AkdValue::from("a2"))])
+        .await?;
+    assert_eq!(2, akd.cached_proofs());
+    assert_eq!(
+        (epoch_hash_2.clone(), AkdValue::from("a2")),
+        lookup("a").await?
+    );
+    assert_eq!(1, akd.cached_proofs());
+    assert_eq!(
+        (epoch_hash_2.clone(), AkdValue::from("b1")),
+        lookup("b").await?
+    );
+    assert_eq!(2, akd.cached_proofs());
+
+    // a publish which commits no epoch keeps the cached proofs of the current epoch
+    assert_eq!(
+        epoch_hash_2,
+        akd.publish(vec![(AkdLabel::from("a"), AkdValue::from("a2"))])
+            .await?
+    );
+    assert_eq!(2, akd.cached_proofs());
+    let reads = num_state_reads.load(Ordering::SeqCst);
+    assert_eq!((epoch_hash_2, AkdValue::from("a2")), lookup("a").await?);
+    assert_eq!(reads, num_state_reads.load(Ordering::SeqCst));
+
+    // a follower serves its cached proofs at the epoch it has refreshed to, and discards them
+    // when it refreshes to a new epoch
+    let follower = Directory::<TC, _, _>::builder()
+        .storage(StorageManager::new(
+            test_db.clone(),
+            Some(Duration::from_secs(60)),
+            None,
+            None,
+        ))
+        .vrf(vrf)
+        .read_only(true)
+        .proof_cache_capacity(2)
+        .build()
+        .await?;
+    follower.lookup(AkdLabel::from("a")).await?;
+    let epoch_hash_3 = akd
+        .publish(vec![(AkdLabel::from("a"), AkdValue::from("a3"))])
+        .await?;
+    let (_, epoch_hash) = follower.lookup(AkdLabel::from("a")).await?;
+    assert_eq!(2, epoch_hash.epoch());
+    assert_eq!(1, follower.cached_proofs());
+    assert_eq!(3, follower.refresh().await?);
+    assert_eq!(0, follower.cached_proofs());
+    let (proof, epoch_hash) = follower.lookup(AkdLabel::from("a")).await?;
+    assert_eq!(epoch_hash_3, epoch_hash);
+    let result = lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        epoch_hash.hash(),
+        epoch_hash.epoch(),
+        AkdLabel::from("a"),
+        proof,
+    )?;
+    assert_eq!(AkdValue::from("a3"), result.value);
+
+    // disabling the cache discards the cached proofs
+    lookup("a").await?;
+    akd.set_proof_cache_capacity(0);
+    assert_eq!(0, akd.cached_proofs());
+    lookup("a").await?;
+    assert_eq!(0, akd.cached_proofs());
+
+    // a rollback discards every cached proof, and proofs are cached again at the earlier epoch
+    let leader = Directory::<TC, _, _>::builder()
+        .storage(StorageManager::new_no_cache(test_db))
+        .vrf(HardCodedAkdVRF {})
+        .proof_cache_capacity(2)
+        .build()
+        .await?;
+    leader.lookup(AkdLabel::from("a")).await?;
+    assert_eq!(1, leader.cached_proofs());
+    leader.rollback_to(1, &signer).await?;
+    assert_eq!(0, leader.cached_proofs());
+    let (proof, epoch_hash) = leader.lookup(AkdLabel::from("a")).await?;
+    assert_eq!(epoch_hash_1, epoch_hash);
+    let result = lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        epoch_hash.hash(),
+        epoch_hash.epoch(),
+        AkdLabel::from("a"),
+        proof,
+    )?;
+    assert_eq!(AkdValue::from("a1"), result.value);
+    assert_eq!(1, leader.cached_proofs());
+    Ok(())
+}
+
+test_config!(
+    test_lookup_coalescing,
+    flavor = "multi_thread",
+    worker_threads = 4
+);
+async fn test_lookup_coalescing<TC: Configuration>() -> Result<(), AkdError> {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const NUM_LOOKUPS: usize = 8;
+
+    // reads of value states are slowed down once `slow` is set, so that the concurrent lookups
+    // overlap
+    let test_db = AsyncInMemoryDatabase::new();
+    let num_state_reads = Arc::new(AtomicUsize::new(0));
+    let slow = Arc::new(AtomicBool::new(false));
+    let mut db = MockLocalDatabase {
+        ..Default::default()
+    };
+    let tmp_db = test_db.clone();
+    let reads = num_state_reads.clone();
+    let is_slow = slow.clone();
+    db.expect_get_user_state().returning(move |label, flag| {
+        reads.fetch_add(1, Ordering::SeqCst);
+        if is_slow.load(Ordering::SeqCst) {
+            std::thread::sleep(Duration::from_millis(200));
+        }
+        futures::executor::block_on(tmp_db.get_user_state(label, flag))
+    });
+    setup_mocked_db(&mut db, &test_db);
+    let akd = Directory::<TC, _, _>::builder()
+        .storage(StorageManager::new_no_cache(db))
+        .vrf(HardCodedAkdVRF {})
+        .coalesce_lookups(true)
+        .build()
+        .await?;
+    let vrf_pk = akd.get_public_key().await?;
+    let epoch_hash = akd
+        .publish(vec![
+            (AkdLabel::from("hello"), AkdValue::from("world")),
+            (AkdLabel::from("hello2"), AkdValue::from("world2")),
+        ])
+        .await?;
+
+    // a lookup which does not overlap with another is not coalesced
+    let reads = num_state_reads.load(Ordering::SeqCst);
+    let (expected_proof, _) = akd.lookup(AkdLabel::from("hello")).await?;
+    let reads_per_lookup = num_state_reads.load(Ordering::SeqCst) - reads;
+    assert_eq!(
+        LookupCoalescingMetrics {
+            lookups: 1,
+            coalesced: 0,
+        },
+        akd.lookup_coalescing_metrics()
+    );
+
+    slow.store(true, Ordering::SeqCst);
+    let reads = num_state_reads.load(Ordering::SeqCst);
+    let lookups = (0..NUM_LOOKUPS)
+        .map(|_| {
+            let akd = akd.clone();
+            tokio::spawn(async move { akd.lookup(AkdLabel::from("hello")).await })
+        })
+        .collect::<Vec<_>>();
+    for lookup in lookups {
+        let (proof, lookup_epoch_hash) = lookup.await.expect("Lookup panicked")?;
+        assert_eq!(epoch_hash, lookup_epoch_hash);
+        assert_eq!(expected_proof, proof);
+        lookup_verify::<TC>(
+            vrf_pk.as_bytes(),
+            epoch_hash.hash(),
+            epoch_hash.epoch(),
+            AkdLabel::from("hello"),
+            proof,
+        )?;
+    }
+    slow.store(false, Ordering::SeqCst);
+
+    // only the lookups which were not coalesced read from the storage
+    let metrics = akd.lookup_coalescing_metrics();
+    assert_eq!(1 + NUM_LOOKUPS as u64, metrics.lookups);
+    assert!(metrics.coalesced > 0);
+    assert_eq!(
+        (metrics.lookups - metrics.coalesced - 1) as usize * reads_per_lookup,
+        num_state_reads.load(Ordering::SeqCst) - reads
+    );
+
+    // lookups of different labels are not coalesced
+    let (first, second) = tokio::join!(
+        akd.lookup(AkdLabel::from("hello")),
+        akd.lookup(AkdLabel::from("hello2"))
+    );
+    assert_eq!(first?.0, expected_proof);
+    lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        epoch_hash.hash(),
+        epoch_hash.epoch(),
+        AkdLabel::from("hello2"),
+        second?.0,
+    )?;
+    assert_eq!(metrics.coalesced, akd.lookup_coalescing_metrics().coalesced);
+
+    // coalescing is opt-in
+    akd.set_lookup_coalescing(false);
+    akd.lookup(AkdLabel::from("hello")).await?;
+    assert_eq!(metrics.lookups + 2, akd.lookup_coalescing_metrics().lookups);
+    Ok(())
+}
+
+// Checks that once a client has looked up its contacts together, the nodes of the other
+// contacts are prefetched when it looks up one of them again, so that their lookups read less
+// from the storage
+test_config!(test_prefetch);
+async fn test_prefetch<TC: Configuration>() -> Result<(), AkdError> {
+    let db = MockDatabase::new(MockDatabaseOptions::default());
+    let storage = StorageManager::new(db.clone(), Some(Duration::from_secs(3600)), None, None);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::builder()
+        .storage(storage.clone())
+        .vrf(vrf.clone())
+        .prefetching(PrefetchConfig {
+            co_lookup_window: Duration::from_secs(3600),
+            ..Default::default()
+        })
+        .build()
+        .await?;
+    let vrf_pk = akd.get_public_key().await?;
+    akd.publish(
+        (0..32)
+            .map(|i| {
+                (
+                    AkdLabel::from(format!("user {i}").as_str()),
+                    AkdValue::from("value"),
+                )
+            })
+            .collect(),
+    )
+    .await?;
+    let contacts = (0..4)
+        .map(|i| AkdLabel::from(format!("user {i}").as_str()))
+        .collect::<Vec<_>>();
+
+    // nothing is prefetched before any lookups are recorded
+    assert_eq!(0, akd.prefetch().await?);
+    akd.batch_lookup(&contacts).await?;
+    akd.prefetch().await?;
+
+    // the reads of the lookups of the other contacts, once the client comes back
+    let reads_of_contacts = || async {
+        let reads = db.stats().reads;
+        for contact in contacts[1..].iter() {
+            let (proof, epoch_hash) = akd.lookup(contact.clone()).await?;
+            lookup_verify::<TC>(
+                vrf_pk.as_bytes(),
+                epoch_hash.hash(),
+                epoch_hash.epoch(),
+                contact.clone(),
+                proof,
+            )?;
+        }
+        Ok::<_, AkdError>(db.stats().reads - reads)
+    };
+    storage.flush_cache().await;
+    akd.lookup(contacts[0].clone()).await?;
+    let cold_reads = reads_of_contacts().await?;
+
+    storage.flush_cache().await;
+    akd.lookup(contacts[0].clone()).await?;
+    let report = akd.run_maintenance_task(MaintenanceTask::Prefetch).await?;
+    assert_eq!(contacts.len(), report.records);
+    let prefetched_reads = reads_of_contacts().await?;
+    assert!(
+        prefetched_reads < cold_reads,
+        "{prefetched_reads} reads after prefetching, and {cold_reads} without"
+    );
+
+    // prefetching is opt-in, and has no effect without a cache
+    akd.lookup(contacts[0].clone()).await?;
+    akd.set_prefetching(None);
+    assert_eq!(0, akd.prefetch().await?);
+    let no_cache = Directory::<TC, _, _>::builder()
+        .storage(StorageManager::new_no_cache(db.clone()))
+        .vrf(vrf)
+        .prefetching(PrefetchConfig::default())
+        .build()
+        .await?;
+    no_cache.lookup(contacts[0].clone()).await?;
+    assert_eq!(0, no_cache.prefetch().await?);
+    Ok(())
+}
+
+// Checks that a directory can be backed by a database and VRF key storage chosen at runtime,
+// through their object-safe forms
+#[cfg(feature = "dyn_traits")]
+test_config!(test_dyn_backends);
+#[cfg(feature = "dyn_traits")]
+async fn test_dyn_backends<TC: Configuration>() -> Result<(), AkdError> {
+    use crate::ecvrf::DynVRFKeyStorage;
+    use crate::storage::dynamic::DynDatabase;
+    use crate::storage::types::ValueStateKey;
+    use std::sync::Arc;
+
+    let db: Arc<dyn DynDatabase> = Arc::new(AsyncInMemoryDatabase::new());
+    let vrf: Arc<dyn DynVRFKeyStorage> = Arc::new(HardCodedAkdVRF {});
+    let storage = StorageManager::new_no_cache(db.clone());
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+
+    akd.publish(vec![
+        (AkdLabel::from("alice"), AkdValue::from("alice 1")),
+        (AkdLabel::from("bob"), AkdValue::from("bob 1")),
+    ])
+    .await?;
+    let EpochHash(epoch, root_hash) = akd
+        .publish(vec![(AkdLabel::from("alice"), AkdValue::from("alice 2"))])
+        .await?;
+
+    let vrf_pk = akd.get_public_key().await?;
+    let (proof, _) = akd.lookup(AkdLabel::from("alice")).await?;
+    let result = lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        epoch,
+        AkdLabel::from("alice"),
+        proof,
+    )?;
+    assert_eq!(AkdValue::from("alice 2"), result.value);
+
+    // Records of different types are retrieved together by their full binary keys
+    let keys = vec![
+        Azks::get_full_binary_key_id(&crate::append_only_zks::DEFAULT_AZKS_KEY),
+        ValueState::get_full_binary_key_id(&ValueStateKey(b"bob".to_vec(), 1)),
+        ValueState::get_full_binary_key_id(&ValueStateKey(b"carol".to_vec(), 1)),
+    ];
+    let records = db.batch_get_by_binary_keys(&keys).await?;
+    assert_eq!(2, records.len());
+    assert!(records
+        .iter()
+        .any(|record| matches!(record, DbRecord::Azks(_))));
+    assert!(records
+        .iter()
+        .any(|record| matches!(record, DbRecord::ValueState(state) if state.value == AkdValue::from("bob 1"))));
+    assert!(db.get_by_binary_key(&[0xff]).await.is_err());
+    Ok(())
+}
+
+test_config!(test_epoch_scheduling);
+async fn test_epoch_scheduling<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+    let vrf_pk = akd.get_public_key().await?;
+
+    let EpochHash(_, root_hash_1) = akd
+        .publish(vec![(AkdLabel::from("hello"), AkdValue::from("world"))])
+        .await?;
+
+    // A caller-provided epoch skips the epochs in between, which hold the tree of epoch 1
+    let EpochHash(epoch, root_hash_5) = akd
+        .publish_at_epoch(vec![(AkdLabel::from("hello"), AkdValue::from("world2"))], 5)
+        .await?;
+    assert_eq!(5, epoch);
+    let (proof, _) = akd.lookup(AkdLabel::from("hello")).await?;
+    let result = lookup_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash_5,
+        5,
+        AkdLabel::from("hello"),
+        proof,
+    )?;
+    assert_eq!(5, result.epoch);
+    assert_eq!(AkdValue::from("world2"), result.value);
+    let audit_proof = akd.audit(1, 5).await?;
+    audit_verify::<TC>(
+        vec![
+            root_hash_1,
+            root_hash_1,
+            root_hash_1,
+            root_hash_1,
+            root_hash_5,
+        ],
+        audit_proof,
+    )
+    .await?;
+
+    // A scheduled epoch is committed even without updates
+    assert_eq!(
+        EpochHash(6, root_hash_5),
+        akd.publish_at_epoch(vec![], 6).await?
+    );
+
+    // The epoch must be ahead of the current epoch, but not too far
+    for epoch in [5, 6, 7 + MAX_EPOCH_GAP] {
+        assert!(matches!(
+            akd.publish_at_epoch(
+                vec![(AkdLabel::from("hello"), AkdValue::from("world3"))],
+                epoch
+            )
+            .await,
+            Err(AkdError::Directory(DirectoryError::InvalidEpoch(_)))
+        ));
+    }
+    assert_eq!(6, akd.get_epoch_hash().await?.0);
+
+    // A wall-clock schedule publishes the epoch of the current period, once
+    let period = Duration::from_secs(3600);
+    let schedule = WallClockEpochs::new(SystemTime::now() - 10 * period, period)?;
+    assert_eq!(11, schedule.epoch_at(SystemTime::now()));
+    assert_eq!(0, schedule.epoch_at(SystemTime::now() - 11 * period));
+    assert!(schedule.until_next(11) > Duration::ZERO);
+    assert_eq!(Duration::ZERO, schedule.until_next(5));
+    assert!(WallClockEpochs::new(SystemTime::now(), Duration::ZERO).is_err());
+    akd.set_epoch_source(schedule);
+    assert_eq!(
+        11,
+        akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world3"))])
+            .await?
+            .0
+    );
+    assert!(matches!(
+        akd.publish(vec![(AkdLabel::from("hello"), AkdValue::from("world4"))])
+            .await,
+        Err(AkdError::Directory(DirectoryError::InvalidEpoch(_)))
+    ));
+
+    let history_params = HistoryParams::default();
+    let (history_proof, EpochHash(current_epoch, root_hash)) = akd
+        .key_history(&AkdLabel::from("hello"), history_params)
+        .await?;
+    let results = key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        root_hash,
+        current_epoch,
+        AkdLabel::from("hello"),
+        history_proof,
+        HistoryVerificationParams::default(),
+    )?;
+    assert_eq!(
+        vec![11, 5, 1],
+        results
+            .iter()
+            .map(|result| result.epoch)
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+test_config!(test_chunked_audit);
+async fn test_chunked_audit<TC: Configuration>() -> Result<(), AkdError> {
+    /// A store which fails to save any chunk after the first `limit` chunks
+    struct InterruptedStore {
+        inner: InMemoryProofChunkStore,
+        saved: std::sync::atomic::AtomicUsize,
+        limit: usize,
+    }
+
+    #[async_trait::async_trait]
+    impl ProofChunkStore for InterruptedStore {
+        async fn save(&self, chunk: AppendOnlyProofChunk) -> Result<(), StorageError> {
+            if self.saved.fetch_add(1, std::sync::atomic::Ordering::SeqCst) >= self.limit {
+                return Err(StorageError::Other("Interrupted".to_string()));
+            }
+            self.inner.save(chunk).await
+        }
+
+        async fn chunks(&self, epoch: u64) -> Result<Vec<AppendOnlyProofChunk>, StorageError> {
+            self.inner.chunks(epoch).await
+        }
+
+        async fn clear(&self, epoch: u64) -> Result<(), StorageError> {
+            self.inner.clear(epoch).await
+        }
+    }
+
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+
+    let mut root_hashes = vec![];
+    for epoch in 0..4 {
+        let updates = (0..20)
+            .map(|i| {
+                (
+                    AkdLabel(format!("user{}", epoch * 10 + i).into_bytes()),
+                    AkdValue(format!("value{epoch}").into_bytes()),
+                )
+            })
+            .collect();
+        root_hashes.push(akd.publish(updates).await?.1);
+    }
+
+    // The chunked proof verifies, and holds the same elements as the single-pass proof
+    let options = ChunkedAuditOptions {
+        split_depth: 2,
+        max_concurrent_chunks: 3,
+    };
+    let store = InterruptedStore {
+        inner: InMemoryProofChunkStore::new(),
+        saved: std::sync::atomic::AtomicUsize::new(0),
+        limit: usize::MAX,
+    };
+    let proof = akd.audit_chunked(1, 4, &store, &options).await?;
+    audit_verify::<TC>(root_hashes.clone(), proof.clone()).await?;
+    let single_pass = akd.audit(1, 4).await?;
+    assert_eq!(single_pass.epochs, proof.epochs);
+    for (chunked, single) in proof.proofs.iter().zip(single_pass.proofs.iter()) {
+        let mut chunked = chunked.clone();
+        let mut single = single.clone();
+        for proof in [&mut chunked, &mut single] {
+            proof.inserted.sort_by_key(|element| element.label);
+            proof.unchanged_nodes.sort_by_key(|element| element.label);
+        }
+        assert_eq!(single, chunked);
+    }
+    assert!(store.chunks(1).await?.is_empty());
+    let num_chunks = store.saved.into_inner();
+    assert!(num_chunks > 3);
+
+    // An interrupted audit resumes from the chunks which it saved
+    let interrupted = InterruptedStore {
+        inner: InMemoryProofChunkStore::new(),
+        saved: std::sync::atomic::AtomicUsize::new(0),
+        limit: 3,
+    };
+    assert!(akd
+        .audit_chunked(1, 4, &interrupted, &options)
+        .await
+        .is_err());
+    let saved = interrupted.inner.chunks(1).await?;
+    assert_eq!(3, saved.len());
+    assert_eq!(1, saved.iter().filter(|chunk| chunk.is_head()).count());
+    let resumed = InterruptedStore {
+        inner: interrupted.inner,
+        saved: std::sync::atomic::AtomicUsize::new(0),
+        limit: usize::MAX,
+    };
+    assert_eq!(proof, akd.audit_chunked(1, 4, &resumed, &options).await?);
+    assert_eq!(num_chunks - 3, resumed.saved.into_inner());
+    Ok(())
+}
+
+test_config!(test_audit_lite);
+async fn test_audit_lite<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+
+    let updates = (0..20)
+        .map(|i| {
+            (
+                AkdLabel(format!("user{i}").into_bytes()),
+                AkdValue(b"value".to_vec()),
+            )
+        })
+        .collect();
+    akd.publish(updates).await?;
+    assert!(akd.audit_lite_snapshot(0).await.is_err());
+    assert!(akd.audit_lite_snapshot(MAX_PREFIX_BITS + 1).await.is_err());
+
+    // The snapshot is checked against the root hash of its epoch
+    let (snapshot, EpochHash(epoch, root_hash)) = akd.audit_lite_snapshot(4).await?;
+    assert_eq!(1, epoch);
+    assert!(snapshot.subtrees.len() <= 16);
+    let mut tampered = snapshot.clone();
+    tampered.subtrees.pop();
+    assert!(AuditLiteState::new::<TC>(tampered, root_hash)
+        .await
+        .is_err());
+    let mut state = AuditLiteState::new::<TC>(snapshot, root_hash).await?;
+
+    // Each epoch's proof lists only the subtrees which changed
+    for epoch in 2..5 {
+        let updates = vec![
+            (
+                AkdLabel(format!("user{epoch}").into_bytes()),
+                AkdValue(format!("value{epoch}").into_bytes()),
+            ),
+            (
+                AkdLabel(format!("new_user{epoch}").into_bytes()),
+                AkdValue(format!("value{epoch}").into_bytes()),
+            ),
+        ];
+        let EpochHash(_, root_hash) = akd.publish(updates).await?;
+        let (proof, epoch_hash) = akd.audit_lite(4).await?;
+        assert_eq!(EpochHash(epoch, root_hash), epoch_hash);
+        // The update inserts the leaves of the new version and of the stale previous version
+        assert_eq!(3, proof.num_inserted);
+        assert!(!proof.changed.is_empty() && proof.changed.len() <= 3);
+        state.apply::<TC>(&proof, root_hash).await?;
+        assert_eq!(epoch, state.epoch());
+    }
+
+    // A tampered proof is rejected, and leaves the state unchanged
+    let updates = vec![(AkdLabel::from("user10"), AkdValue::from("value5"))];
+    let EpochHash(_, root_hash) = akd.publish(updates).await?;
+    let (proof, _) = akd.audit_lite(4).await?;
+    let before = state.clone();
+    let mut tampered = proof.clone();
+    tampered.num_inserted += 1;
+    assert!(state.apply::<TC>(&tampered, root_hash).await.is_err());
+    let mut tampered = proof.clone();
+    tampered.changed[0].current.value = AzksValue([0u8; DIGEST_BYTES]);
+    assert!(state.apply::<TC>(&tampered, root_hash).await.is_err());
+    let mut tampered = proof.clone();
+    tampered.changed.clear();
+    tampered.num_inserted = 0;
+    assert!(state.apply::<TC>(&tampered, root_hash).await.is_err());
+    assert!(state
+        .apply::<TC>(&proof, [0u8; DIGEST_BYTES])
+        .await
+        .is_err());
+    assert_eq!(before, state);
+    state.apply::<TC>(&proof, root_hash).await?;
+    assert_eq!(5, state.epoch());
+
+    // A proof which does not follow the audited epoch is rejected
+    assert!(state.apply::<TC>(&proof, root_hash).await.is_err());
+
+    Ok(())
+}
+
+test_config!(test_delta_sync);
+async fn test_delta_sync<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+    let vrf_pk = akd.get_public_key().await?;
+
+    let since = akd
+        .publish(vec![
+            (AkdLabel::from("a"), AkdValue::from("a1")),
+            (AkdLabel::from("b"), AkdValue::from("b1")),
+        ])
+        .await?;
+    let EpochHash(_, root_hash_2) = akd
+        .publish(vec![(AkdLabel::from("a"), AkdValue::from("a2"))])
+        .await?;
+    akd.publish(vec![(AkdLabel::from("c"), AkdValue::from("c3"))])
+        .await?;
+    let current = akd
+        .publish(vec![(AkdLabel::from("a"), AkdValue::from("a4"))])
+        .await?;
+
+    // "a" and "c" changed since epoch 1, "b" did not, and "d" was never published
+    let labels = ["a", "b", "c", "d"].map(AkdLabel::from).to_vec();
+    let response = akd.delta_sync(since.epoch(), &labels).await?;
+    assert!(matches!(response.deltas[3].1, LabelDelta::Absent(_)));
+    let result = delta_sync_verify::<TC>(
+        vrf_pk.as_bytes(),
+        since.clone(),
+        &labels,
+        response.clone(),
+        HistoryVerificationParams::default(),
+    )
+    .await?;
+    assert_eq!(result.epoch_hash, current);
+    let updates = result
+        .labels
+        .iter()
+        .map(|label| {
+            (
+                label
+                    .updates
+                    .iter()
+                    .map(|update| update.epoch)
+                    .collect::<Vec<_>>(),
+                label.latest.as_ref().map(|latest| latest.value.clone()),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        updates,
+        vec![
+            (vec![4, 2], Some(AkdValue::from("a4"))),
+            (vec![], Some(AkdValue::from("b1"))),
+            (vec![3], Some(AkdValue::from("c3"))),
+            (vec![], None),
+        ]
+    );
+    assert_eq!(
+        result
+            .changed()
+            .map(|label| label.label.clone())
+            .collect::<Vec<_>>(),
+        vec![AkdLabel::from("a"), AkdLabel::from("c")]
+    );
+    // Only the updates since epoch 1, along with the update at it, are included
+    match &response.deltas[0].1 {
+        LabelDelta::History(proof) => assert_eq!(proof.update_proofs.len(), 3),
+        LabelDelta::Absent(_) => panic!("\"a\" has been published"),
+    }
+
+    // A client which is already up to date receives no consistency proof
+    let response = akd.delta_sync(current.epoch(), &labels).await?;
+    assert!(response.consistency_proof.is_none());
+    let result = delta_sync_verify::<TC>(
+        vrf_pk.as_bytes(),
+        current.clone(),
+        &labels,
+        response,
+        HistoryVerificationParams::default(),
+    )
+    .await?;
+    assert_eq!(result.changed().count(), 0);
+
+    // A response which does not follow on from the last sync, or does not match the
+    // requested labels, is rejected
+    let response = akd.delta_sync(since.epoch(), &labels).await?;
+    assert!(delta_sync_verify::<TC>(
+        vrf_pk.as_bytes(),
+        EpochHash(since.epoch(), root_hash_2),
+        &labels,
+        response.clone(),
+        HistoryVerificationParams::default(),
+    )
+    .await
+    .is_err());
+    let mut swapped = response.clone();
+    swapped.deltas.swap(0, 1);
+    assert!(delta_sync_verify::<TC>(
+        vrf_pk.as_bytes(),
+        since.clone(),
+        &labels,
+        swapped,
+        HistoryVerificationParams::default(),
+    )
+    .await
+    .is_err());
+    let mut truncated = response.clone();
+    truncated.deltas.pop();
+    assert!(delta_sync_verify::<TC>(
+        vrf_pk.as_bytes(),
+        since.clone(),
+        &labels,
+        truncated,
+        HistoryVerificationParams::default(),
+    )
+    .await
+    .is_err());
+    // A history which omits the updates since the last sync is rejected
+    let mut stale = response.clone();
+    let (stale_proof, _) = akd
+        .key_history(&AkdLabel::from("a"), HistoryParams::RecentEpochs(1))
+        .await?;
+    stale.deltas[0].1 = LabelDelta::History(stale_proof);
+    assert!(delta_sync_verify::<TC>(
+        vrf_pk.as_bytes(),
+        since.clone(),
+        &labels,
+        stale,
+        HistoryVerificationParams::default(),
+    )
+    .await
+    .is_err());
+
+    // A client cannot sync from an epoch which has not been published
+    assert!(matches!(
+        akd.delta_sync(current.epoch() + 1, &labels).await,
+        Err(AkdError::Directory(DirectoryError::InvalidEpoch(_)))
+    ));
+
     Ok(())
 }
@@ -0,0 +1,303 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A stress test of queries served while epochs are being published. Readers run lookups and
+//! key-history queries continuously on a multi-threaded runtime while a writer publishes, and
+//! every response must be consistent with a single committed epoch: its proof verifies against
+//! the root hash which the writer published for the response's epoch, and the values it
+//! returns are exactly those of the labels as of that epoch.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::client::{key_history_verify, lookup_verify};
+use crate::directory::Directory;
+use crate::ecvrf::HardCodedAkdVRF;
+use crate::errors::AkdError;
+use crate::storage::manager::StorageManager;
+use crate::storage::memory::AsyncInMemoryDatabase;
+use crate::{
+    AkdLabel, AkdValue, Configuration, Digest, EpochHash, HistoryParams, HistoryVerificationParams,
+    VerifyResult,
+};
+
+const NUM_LABELS: u64 = 16;
+const NUM_EPOCHS: u64 = 8;
+const NUM_READERS: usize = 3;
+
+fn label(i: u64) -> AkdLabel {
+    AkdLabel::from(format!("user {i}").as_str())
+}
+
+fn value(i: u64, epoch: u64) -> AkdValue {
+    AkdValue::from(format!("user {i} at epoch {epoch}").as_str())
+}
+
+/// Whether label `i` is updated in `epoch`: every label is registered in the first epoch,
+/// and a rotating quarter of them is updated in each later one
+fn is_updated(i: u64, epoch: u64) -> bool {
+    epoch == 1 || (i + epoch).is_multiple_of(4)
+}
+
+/// The history of label `i` as of `epoch`, most recent first
+fn expected_history(i: u64, epoch: u64) -> Vec<VerifyResult> {
+    let epochs = (1..=epoch)
+        .filter(|&e| is_updated(i, e))
+        .collect::<Vec<_>>();
+    epochs
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(version, &e)| VerifyResult {
+            epoch: e,
+            version: version as u64 + 1,
+            value: value(i, e),
+        })
+        .collect()
+}
+
+/// A verified response to a reader's query
+struct Observation {
+    label: u64,
+    epoch_hash: EpochHash,
+    is_history: bool,
+    /// The results of a key-history query, or the single result of a lookup
+    results: Vec<VerifyResult>,
+}
+
+test_config!(
+    test_queries_during_publish,
+    flavor = "multi_thread",
+    worker_threads = 4
+);
+async fn test_queries_during_publish<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new(db, Some(Duration::from_secs(60)), None, None);
+    let akd = Directory::<TC, _, _>::new(storage, HardCodedAkdVRF {}).await?;
+    let vrf_pk = akd.get_public_key().await?;
+
+    let publish = |epoch: u64| {
+        let updates = (0..NUM_LABELS)
+            .filter(|&i| is_updated(i, epoch))
+            .map(|i| (label(i), value(i, epoch)))
+            .collect::<Vec<_>>();
+        akd.publish(updates)
+    };
+    let mut root_hashes = HashMap::<u64, Digest>::new();
+    let EpochHash(epoch, root_hash) = publish(1).await?;
+    root_hashes.insert(epoch, root_hash);
+
+    let done = Arc::new(AtomicBool::new(false));
+    let readers = (0..NUM_READERS)
+        .map(|reader| {
+            let akd = akd.clone();
+            let vrf_pk = vrf_pk.clone();
+            let done = done.clone();
+            tokio::spawn(async move {
+                let mut observations = vec![];
+                let mut query = reader as u64;
+                while !done.load(Ordering::Acquire) {
+                    let i = query % NUM_LABELS;
+                    let is_history = query % 2 == 1;
+                    let (epoch_hash, results) = if !is_history {
+                        let (proof, epoch_hash) = akd.lookup(label(i)).await?;
+                        let result = lookup_verify::<TC>(
+                            vrf_pk.as_bytes(),
+                            epoch_hash.hash(),
+                            epoch_hash.epoch(),
+                            label(i),
+                            proof,
+                        )?;
+                        (epoch_hash, vec![result])
+                    } else {
+                        let (proof, epoch_hash) =
+                            akd.key_history(&label(i), HistoryParams::default()).await?;
+                        let results = key_history_verify::<TC>(
+                            vrf_pk.as_bytes(),
+                            epoch_hash.hash(),
+                            epoch_hash.epoch(),
+                            label(i),
+                            proof,
+                            HistoryVerificationParams::default(),
+                        )?;
+                        (epoch_hash, results)
+                    };
+                    observations.push(Observation {
+                        label: i,
+                        epoch_hash,
+                        is_history,
+                        results,
+                    });
+                    query += NUM_READERS as u64;
+                }
+                Ok::<_, AkdError>(observations)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for epoch in 2..=NUM_EPOCHS {
+        let EpochHash(published_epoch, root_hash) = publish(epoch).await?;
+        assert_eq!(epoch, published_epoch);
+        root_hashes.insert(epoch, root_hash);
+        // give the readers a chance to observe every epoch
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    done.store(true, Ordering::Release);
+
+    let mut observed_epochs = vec![];
+    for reader in readers {
+        let observations = reader.await.expect("Reader panicked")?;
+        assert!(!observations.is_empty());
+        let mut last_epoch = 0;
+        for Observation {
+            label: i,
+            epoch_hash: EpochHash(epoch, root_hash),
+            is_history,
+            results,
+        } in observations
+        {
+            // each reader sees the epochs in the order they were committed
+            assert!(
+                last_epoch <= epoch,
+                "Epoch {epoch} was served after {last_epoch}"
+            );
+            last_epoch = epoch;
+            observed_epochs.push(epoch);
+
+            assert_eq!(
+                Some(&root_hash),
+                root_hashes.get(&epoch),
+                "A response was served with an uncommitted root hash for epoch {epoch}"
+            );
+            let expected = expected_history(i, epoch);
+            if is_history {
+                assert_eq!(expected, results, "History of label {i} at epoch {epoch}");
+            } else {
+                assert_eq!(
+                    expected[..1],
+                    results,
+                    "Lookup of label {i} at epoch {epoch}"
+                );
+            }
+        }
+    }
+    // the queries overlapped with the publishes, rather than all running before or after them
+    observed_epochs.sort();
+    observed_epochs.dedup();
+    assert!(
+        observed_epochs.len() > 1,
+        "The readers only observed epochs {observed_epochs:?}"
+    );
+    Ok(())
+}
+
+// Checks that, with the proof cache enabled, no lookup is answered with a proof of an epoch
+// earlier than the latest one which had been published when the lookup started
+test_config!(
+    test_cached_lookups_during_publish,
+    flavor = "multi_thread",
+    worker_threads = 4
+);
+async fn test_cached_lookups_during_publish<TC: Configuration>() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new(db, Some(Duration::from_secs(60)), None, None);
+    let akd = Directory::<TC, _, _>::builder()
+        .storage(storage)
+        .vrf(HardCodedAkdVRF {})
+        .proof_cache_capacity(NUM_LABELS as usize)
+        .build()
+        .await?;
+    let vrf_pk = akd.get_public_key().await?;
+
+    let publish = |epoch: u64| {
+        let updates = (0..NUM_LABELS)
+            .filter(|&i| is_updated(i, epoch))
+            .map(|i| (label(i), value(i, epoch)))
+            .collect::<Vec<_>>();
+        akd.publish(updates)
+    };
+    let mut root_hashes = HashMap::<u64, Digest>::new();
+    let EpochHash(epoch, root_hash) = publish(1).await?;
+    root_hashes.insert(epoch, root_hash);
+    // the latest epoch whose publish has returned
+    let published = Arc::new(AtomicU64::new(epoch));
+
+    let done = Arc::new(AtomicBool::new(false));
+    let readers = (0..NUM_READERS)
+        .map(|reader| {
+            let akd = akd.clone();
+            let vrf_pk = vrf_pk.clone();
+            let done = done.clone();
+            let published = published.clone();
+            tokio::spawn(async move {
+                let mut observations = vec![];
+                // a few labels are looked up repeatedly, so that most lookups hit the cache
+                let mut query = reader as u64;
+                while !done.load(Ordering::Acquire) {
+                    let i = query % 4;
+                    let published_before = published.load(Ordering::Acquire);
+                    let (proof, epoch_hash) = akd.lookup(label(i)).await?;
+                    assert!(
+                        epoch_hash.epoch() >= published_before,
+                        "A proof of epoch {} was served after epoch {published_before} was published",
+                        epoch_hash.epoch()
+                    );
+                    let result = lookup_verify::<TC>(
+                        vrf_pk.as_bytes(),
+                        epoch_hash.hash(),
+                        epoch_hash.epoch(),
+                        label(i),
+                        proof,
+                    )?;
+                    observations.push((i, epoch_hash, result));
+                    query += 1;
+                }
+                Ok::<_, AkdError>(observations)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for epoch in 2..=NUM_EPOCHS {
+        let EpochHash(published_epoch, root_hash) = publish(epoch).await?;
+        assert_eq!(epoch, published_epoch);
+        root_hashes.insert(epoch, root_hash);
+        published.store(epoch, Ordering::Release);
+        // give the readers a chance to observe every epoch
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    done.store(true, Ordering::Release);
+
+    let mut observed_epochs = vec![];
+    for reader in readers {
+        let observations = reader.await.expect("Reader panicked")?;
+        assert!(!observations.is_empty());
+        for (i, EpochHash(epoch, root_hash), result) in observations {
+            observed_epochs.push(epoch);
+            assert_eq!(
+                Some(&root_hash),
+                root_hashes.get(&epoch),
+                "A response was served with an uncommitted root hash for epoch {epoch}"
+            );
+            assert_eq!(
+                expected_history(i, epoch)[..1],
+                [result],
+                "Lookup of label {i} at epoch {epoch}"
+            );
+        }
+    }
+    observed_epochs.sort();
+    observed_epochs.dedup();
+    assert!(
+        observed_epochs.len() > 1,
+        "The readers only observed epochs {observed_epochs:?}"
+    );
+    // only the proofs of the latest epoch remain cached
+    assert!(akd.cached_proofs() <= 4);
+    Ok(())
+}
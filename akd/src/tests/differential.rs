@@ -0,0 +1,376 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Differential testing of proof generation and verification. A [ReferenceVerifier], written
+//! from the description of the proofs rather than from [akd_core::verify], checks the lookup
+//! and non-existence proofs of a directory under a randomized workload, alongside the client
+//! verification. Both must accept every honest proof, and must reach the same decision on every
+//! tampered one, so that a refactor which silently changes the semantics of the proofs (on
+//! either side) is caught.
+//!
+//! The workload is drawn from [crate::test_utils::test_rng], so a failing run can be replayed
+//! with its seed, and can be lengthened with the [EPOCHS_ENV_VAR] environment variable.
+
+use std::collections::HashMap;
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::client::{lookup_verify, nonexistence_verify};
+use crate::directory::Directory;
+use crate::ecvrf::{HardCodedAkdVRF, Proof, VRFKeyStorage, VRFPublicKey};
+use crate::errors::AkdError;
+use crate::storage::manager::StorageManager;
+use crate::storage::memory::AsyncInMemoryDatabase;
+use crate::test_utils::test_rng;
+use crate::{
+    AkdLabel, AkdValue, AzksElement, Configuration, Digest, Direction, EpochHash, LookupProof,
+    MembershipProof, NodeLabel, NonExistenceProof, NonMembershipProof, VersionFreshness,
+};
+
+/// The environment variable which sets the number of epochs of the randomized workload
+const EPOCHS_ENV_VAR: &str = "AKD_DIFFERENTIAL_EPOCHS";
+
+const DEFAULT_EPOCHS: usize = 3;
+
+/// The number of distinct labels which the workload updates
+const NUM_LABELS: usize = 8;
+
+/// An independent verifier of lookup and non-existence proofs, which checks that:
+/// * each node label is the output of the VRF on the (normalized) label, freshness and version,
+/// * a leaf is included in the tree by hashing its path up to the root, and
+/// * a label is excluded from the tree by showing the node which would be its closest ancestor,
+///   neither of whose children leads to the label.
+struct ReferenceVerifier<TC: Configuration> {
+    vrf_public_key: VRFPublicKey,
+    _config: std::marker::PhantomData<TC>,
+}
+
+impl<TC: Configuration> ReferenceVerifier<TC> {
+    fn new(vrf_public_key: VRFPublicKey) -> Self {
+        Self {
+            vrf_public_key,
+            _config: std::marker::PhantomData,
+        }
+    }
+
+    async fn node_label(
+        &self,
+        label: &AkdLabel,
+        freshness: VersionFreshness,
+        version: u64,
+        vrf_proof: &[u8],
+    ) -> Result<NodeLabel, String> {
+        let proof = Proof::try_from(vrf_proof).map_err(|err| err.to_string())?;
+        let alpha = TC::get_hash_from_label_input(&TC::normalize_label(label), freshness, version);
+        self.vrf_public_key
+            .verify(&proof, &alpha)
+            .map_err(|err| err.to_string())?;
+        Ok(HardCodedAkdVRF {}
+            .get_node_label_from_vrf_proof::<TC>(proof)
+            .await)
+    }
+
+    /// The root hash of the tree in which the proof's node is included
+    fn root_hash_of(proof: &MembershipProof) -> Digest {
+        let node = proof.sibling_proofs.iter().rev().fold(
+            AzksElement {
+                label: proof.label,
+                value: proof.hash_val,
+            },
+            |node, parent| {
+                let [sibling] = parent.siblings;
+                let (left, right) = match parent.direction {
+                    Direction::Left => (node, sibling),
+                    Direction::Right => (sibling, node),
+                };
+                AzksElement {
+                    label: parent.label,
+                    value: TC::compute_parent_hash_from_children(
+                        &left.value,
+                        &left.label.value::<TC>(),
+                        &right.value,
+                        &right.label.value::<TC>(),
+                    ),
+                }
+            },
+        );
+        TC::compute_root_hash_from_val(&node.value)
+    }
+
+    fn check_inclusion(
+        root_hash: Digest,
+        label: NodeLabel,
+        proof: &MembershipProof,
+    ) -> Result<(), String> {
+        if proof.label != label {
+            return Err(format!("Inclusion proof is for {:?}", proof.label));
+        }
+        if Self::root_hash_of(proof) != root_hash {
+            return Err(format!("Path of {label:?} does not lead to the root"));
+        }
+        Ok(())
+    }
+
+    fn check_exclusion(
+        root_hash: Digest,
+        label: NodeLabel,
+        proof: &NonMembershipProof,
+    ) -> Result<(), String> {
+        if proof.label != label {
+            return Err(format!("Exclusion proof is for {:?}", proof.label));
+        }
+        let [left, right] = proof.longest_prefix_children;
+        let empty = TC::empty_label();
+        // a (non-empty) child which is a prefix of the label would contain it
+        if [left, right]
+            .iter()
+            .any(|child| child.label != empty && child.label.is_prefix_of(&label))
+        {
+            return Err(format!(
+                "A child of the closest ancestor leads to {label:?}"
+            ));
+        }
+        // the closest ancestor is where the children's labels diverge, or the root if it only
+        // has a single child
+        let ancestor = if left.label == empty || right.label == empty {
+            NodeLabel::root()
+        } else {
+            left.label.get_longest_common_prefix::<TC>(right.label)
+        };
+        if ancestor != proof.longest_prefix || !ancestor.is_prefix_of(&label) {
+            return Err(format!(
+                "{ancestor:?} is not the closest ancestor of {label:?}"
+            ));
+        }
+        let ancestor_value = TC::compute_parent_hash_from_children(
+            &left.value,
+            &left.label.value::<TC>(),
+            &right.value,
+            &right.label.value::<TC>(),
+        );
+        if proof.longest_prefix_membership_proof.hash_val != ancestor_value {
+            return Err("The children do not hash to their parent".to_string());
+        }
+        Self::check_inclusion(root_hash, ancestor, &proof.longest_prefix_membership_proof)
+    }
+
+    async fn verify_lookup(
+        &self,
+        root_hash: Digest,
+        current_epoch: u64,
+        label: &AkdLabel,
+        proof: &LookupProof,
+    ) -> Result<AkdValue, String> {
+        if proof.version > current_epoch {
+            return Err(format!("Version {} is from the future", proof.version));
+        }
+
+        let leaf = TC::hash_leaf_with_value(&proof.value, proof.epoch, &proof.commitment_nonce);
+        if leaf.0 != proof.existence_proof.hash_val.0 {
+            return Err("The value is not committed to by the leaf".to_string());
+        }
+        let existence = self
+            .node_label(
+                label,
+                VersionFreshness::Fresh,
+                proof.version,
+                &proof.existence_vrf_proof,
+            )
+            .await?;
+        Self::check_inclusion(root_hash, existence, &proof.existence_proof)?;
+
+        let marker = self
+            .node_label(
+                label,
+                VersionFreshness::Fresh,
+                TC::marker_version(proof.version),
+                &proof.marker_vrf_proof,
+            )
+            .await?;
+        Self::check_inclusion(root_hash, marker, &proof.marker_proof)?;
+
+        let stale = self
+            .node_label(
+                label,
+                VersionFreshness::Stale,
+                proof.version,
+                &proof.freshness_vrf_proof,
+            )
+            .await?;
+        Self::check_exclusion(root_hash, stale, &proof.freshness_proof)?;
+        Ok(proof.value.clone())
+    }
+
+    async fn verify_nonexistence(
+        &self,
+        root_hash: Digest,
+        label: &AkdLabel,
+        proof: &NonExistenceProof,
+    ) -> Result<(), String> {
+        let first_version = self
+            .node_label(label, VersionFreshness::Fresh, 1, &proof.vrf_proof)
+            .await?;
+        Self::check_exclusion(root_hash, first_version, &proof.non_membership_proof)
+    }
+}
+
+/// Tampered variants of a lookup proof, each named by how it was tampered with
+fn tampered_lookups(proof: &LookupProof, other: &LookupProof) -> Vec<(&'static str, LookupProof)> {
+    let mut tampered = vec![];
+    let mut tamper = |name, f: &dyn Fn(&mut LookupProof)| {
+        let mut proof = proof.clone();
+        f(&mut proof);
+        tampered.push((name, proof));
+    };
+    tamper("value", &|p| p.value.0.push(0));
+    tamper("epoch", &|p| p.epoch += 1);
+    tamper("version", &|p| p.version += 1);
+    tamper("commitment nonce", &|p| p.commitment_nonce[0] ^= 1);
+    tamper("existence VRF proof", &|p| p.existence_vrf_proof[0] ^= 1);
+    tamper("marker VRF proof", &|p| {
+        p.marker_vrf_proof = other.marker_vrf_proof.clone()
+    });
+    tamper("freshness VRF proof", &|p| {
+        p.freshness_vrf_proof = other.freshness_vrf_proof.clone()
+    });
+    tamper("existence path", &|p| {
+        if let Some(parent) = p.existence_proof.sibling_proofs.last_mut() {
+            parent.siblings[0].value.0[0] ^= 1;
+        }
+    });
+    tamper("existence direction", &|p| {
+        if let Some(parent) = p.existence_proof.sibling_proofs.last_mut() {
+            parent.direction = match parent.direction {
+                Direction::Left => Direction::Right,
+                Direction::Right => Direction::Left,
+            };
+        }
+    });
+    tamper("truncated marker path", &|p| {
+        p.marker_proof.sibling_proofs.pop();
+    });
+    tamper("freshness children", &|p| {
+        p.freshness_proof.longest_prefix_children.swap(0, 1)
+    });
+    tamper("freshness proof", &|p| {
+        p.freshness_proof = other.freshness_proof.clone()
+    });
+    tamper("other label's proof", &|p| *p = other.clone());
+    tampered
+}
+
+test_config!(test_differential_verification);
+async fn test_differential_verification<TC: Configuration>() -> Result<(), AkdError> {
+    let num_epochs = std::env::var(EPOCHS_ENV_VAR)
+        .ok()
+        .and_then(|epochs| epochs.parse().ok())
+        .unwrap_or(DEFAULT_EPOCHS);
+    let mut rng = test_rng();
+
+    let storage = StorageManager::new_no_cache(AsyncInMemoryDatabase::new());
+    let akd = Directory::<TC, _, _>::new(storage, HardCodedAkdVRF {}).await?;
+    let vrf_pk = akd.get_public_key().await?;
+    let reference = ReferenceVerifier::<TC>::new(vrf_pk.clone());
+
+    let labels = (0..NUM_LABELS)
+        .map(|i| AkdLabel::from(format!("user {i}").as_str()))
+        .collect::<Vec<_>>();
+    let mut latest_values = HashMap::new();
+    let mut previous_root_hash = None;
+
+    for _ in 0..num_epochs {
+        // at least two labels are updated, so that each lookup has another to be confused with
+        let num_updates = rng.gen_range(2..=NUM_LABELS / 2);
+        let updated = labels
+            .choose_multiple(&mut rng, num_updates)
+            .cloned()
+            .collect::<Vec<_>>();
+        let updates = updated
+            .into_iter()
+            .map(|label| (label, AkdValue(rng.gen::<[u8; 8]>().to_vec())))
+            .collect::<Vec<_>>();
+        latest_values.extend(updates.iter().cloned());
+        let EpochHash(epoch, root_hash) = akd.publish(updates).await?;
+
+        let mut lookups = vec![];
+        for label in &labels {
+            if !latest_values.contains_key(label) {
+                let (proof, _) = akd.lookup_absent(label.clone()).await?;
+                let client = nonexistence_verify::<TC>(
+                    vrf_pk.as_bytes(),
+                    root_hash,
+                    label.clone(),
+                    proof.clone(),
+                );
+                let expected = reference
+                    .verify_nonexistence(root_hash, label, &proof)
+                    .await;
+                assert!(
+                    client.is_ok() && expected.is_ok(),
+                    "Non-existence of {label:?} at epoch {epoch}: client {client:?}, \
+                     reference {expected:?}"
+                );
+                continue;
+            }
+
+            let (proof, _) = akd.lookup(label.clone()).await?;
+            let client = lookup_verify::<TC>(
+                vrf_pk.as_bytes(),
+                root_hash,
+                epoch,
+                label.clone(),
+                proof.clone(),
+            )
+            .map(|result| result.value);
+            let expected = reference
+                .verify_lookup(root_hash, epoch, label, &proof)
+                .await;
+            assert_eq!(
+                Ok(&latest_values[label]),
+                expected.as_ref(),
+                "Lookup of {label:?} at epoch {epoch} was rejected by the reference"
+            );
+            assert!(
+                client.as_ref().ok() == expected.as_ref().ok(),
+                "Lookup of {label:?} at epoch {epoch}: client {client:?}, reference {expected:?}"
+            );
+            lookups.push((label, proof));
+        }
+
+        // a proof served against any other root hash, and any tampered proof, must be rejected
+        // by both verifiers (the proofs of one label are tampered with in each epoch)
+        let i = rng.gen_range(0..lookups.len());
+        let (label, proof) = &lookups[i];
+        let (_, other) = &lookups[(i + 1) % lookups.len()];
+        let mut attempts = tampered_lookups(proof, other)
+            .into_iter()
+            .map(|(name, proof)| (name, root_hash, proof))
+            .collect::<Vec<_>>();
+        if let Some(previous_root_hash) = previous_root_hash {
+            attempts.push(("previous root hash", previous_root_hash, proof.clone()));
+        }
+        for (name, root_hash, proof) in attempts {
+            let client = lookup_verify::<TC>(
+                vrf_pk.as_bytes(),
+                root_hash,
+                epoch,
+                (*label).clone(),
+                proof.clone(),
+            );
+            let expected = reference
+                .verify_lookup(root_hash, epoch, label, &proof)
+                .await;
+            assert!(
+                client.is_err() && expected.is_err(),
+                "Lookup of {label:?} at epoch {epoch} with a tampered {name}: \
+                 client {client:?}, reference {expected:?}"
+            );
+        }
+        previous_root_hash = Some(root_hash);
+    }
+    Ok(())
+}
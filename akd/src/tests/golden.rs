@@ -0,0 +1,218 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Golden proofs for each built-in configuration. A directory is built from a fixed history of
+//! publishes, and its root hashes and serialized proofs are compared against the fixtures in
+//! `src/tests/golden`, whose proofs are also re-verified against their root hashes. If these
+//! tests fail, label hashing, commitments or proof serialization have changed in a way which
+//! breaks existing clients. If the change is intended, the fixtures can be regenerated by
+//! running the tests with the [REGENERATE_ENV_VAR] environment variable set.
+
+use std::collections::BTreeMap;
+
+use akd_core::proto::specs::types;
+use protobuf::Message;
+
+use crate::auditor::audit_verify;
+use crate::canonical::CanonicalEncode;
+use crate::client::{key_history_verify, lookup_verify};
+use crate::directory::Directory;
+use crate::ecvrf::HardCodedAkdVRF;
+use crate::errors::AkdError;
+use crate::storage::manager::StorageManager;
+use crate::storage::memory::AsyncInMemoryDatabase;
+use crate::{
+    AkdLabel, AkdValue, AppendOnlyProof, Digest, HistoryParams, HistoryProof,
+    HistoryVerificationParams, LookupProof, NamedConfiguration,
+};
+
+/// The environment variable which, when set, rewrites the fixtures rather than checking them
+const REGENERATE_ENV_VAR: &str = "AKD_REGENERATE_GOLDEN";
+
+const FIXTURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/golden");
+
+/// The label whose proofs are captured
+const LABEL: &str = "alice";
+
+/// The publishes of the fixture's directory, one per epoch
+fn golden_history() -> Vec<Vec<(AkdLabel, AkdValue)>> {
+    let update = |label: &str, value: &str| (AkdLabel::from(label), AkdValue::from(value));
+    vec![
+        vec![update("alice", "alice 1"), update("bob", "bob 1")],
+        vec![update("alice", "alice 2"), update("carol", "carol 1")],
+        vec![update("alice", "alice 3"), update("bob", "bob 2")],
+    ]
+}
+
+/// The root hashes and serialized proofs of the fixture's directory, by name
+async fn generate_golden_values<TC: NamedConfiguration>(
+) -> Result<BTreeMap<String, String>, AkdError> {
+    let storage = StorageManager::new_no_cache(AsyncInMemoryDatabase::new());
+    let akd = Directory::<TC, _, _>::new(storage, HardCodedAkdVRF {}).await?;
+    let mut values = BTreeMap::new();
+    let vrf_pk = akd.get_public_key().await?;
+    values.insert("vrf_public_key".to_string(), hex::encode(vrf_pk.as_bytes()));
+
+    for updates in golden_history() {
+        let epoch_hash = akd.publish(updates).await?;
+        values.insert(
+            format!("root_hash_{}", epoch_hash.epoch()),
+            hex::encode(epoch_hash.hash()),
+        );
+    }
+    let num_epochs = golden_history().len() as u64;
+
+    let (lookup_proof, _) = akd.lookup(AkdLabel::from(LABEL)).await?;
+    values.insert(
+        "lookup_proof".to_string(),
+        hex::encode(
+            types::LookupProof::from(&lookup_proof)
+                .write_to_bytes()
+                .unwrap(),
+        ),
+    );
+    values.insert(
+        "lookup_proof_canonical".to_string(),
+        hex::encode(lookup_proof.to_canonical_bytes()),
+    );
+
+    let (history_proof, _) = akd
+        .key_history(&AkdLabel::from(LABEL), HistoryParams::default())
+        .await?;
+    values.insert(
+        "history_proof".to_string(),
+        hex::encode(
+            types::HistoryProof::from(&history_proof)
+                .write_to_bytes()
+                .unwrap(),
+        ),
+    );
+    values.insert(
+        "history_proof_canonical".to_string(),
+        hex::encode(history_proof.to_canonical_bytes()),
+    );
+
+    let audit_proof = akd.audit(1, num_epochs).await?;
+    values.insert(
+        "audit_proof".to_string(),
+        hex::encode(
+            types::AppendOnlyProof::from(&audit_proof)
+                .write_to_bytes()
+                .unwrap(),
+        ),
+    );
+    values.insert(
+        "audit_proof_canonical".to_string(),
+        hex::encode(audit_proof.to_canonical_bytes()),
+    );
+    Ok(values)
+}
+
+fn format_fixture<TC: NamedConfiguration>(values: &BTreeMap<String, String>) -> String {
+    let mut out = format!(
+        "# @generated Golden proofs of the {} configuration. Regenerate with:\n\
+         # {REGENERATE_ENV_VAR}=1 cargo test -p akd --lib golden\n",
+        TC::name()
+    );
+    for (name, value) in values {
+        out.push_str(&format!("{name} = {value}\n"));
+    }
+    out
+}
+
+fn parse_fixture(contents: &str) -> BTreeMap<String, String> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (name, value) = line
+                .split_once(" = ")
+                .unwrap_or_else(|| panic!("Malformed fixture line: {line}"));
+            (name.to_string(), value.to_string())
+        })
+        .collect()
+}
+
+fn decode<T: Message>(values: &BTreeMap<String, String>, name: &str) -> T {
+    T::parse_from_bytes(&hex::decode(&values[name]).unwrap()).unwrap()
+}
+
+fn digest(values: &BTreeMap<String, String>, name: &str) -> Digest {
+    hex::decode(&values[name]).unwrap().try_into().unwrap()
+}
+
+/// Re-verifies the proofs of a fixture against its root hashes
+async fn verify_golden_values<TC: NamedConfiguration>(
+    values: &BTreeMap<String, String>,
+) -> Result<(), AkdError> {
+    let num_epochs = golden_history().len() as u64;
+    let vrf_pk = hex::decode(&values["vrf_public_key"]).unwrap();
+    let root_hashes = (1..=num_epochs)
+        .map(|epoch| digest(values, &format!("root_hash_{epoch}")))
+        .collect::<Vec<_>>();
+    let latest_root_hash = root_hashes[root_hashes.len() - 1];
+
+    let lookup_proof =
+        LookupProof::try_from(&decode::<types::LookupProof>(values, "lookup_proof")).unwrap();
+    let result = lookup_verify::<TC>(
+        &vrf_pk,
+        latest_root_hash,
+        num_epochs,
+        AkdLabel::from(LABEL),
+        lookup_proof,
+    )?;
+    assert_eq!(AkdValue::from("alice 3"), result.value);
+
+    let history_proof =
+        HistoryProof::try_from(&decode::<types::HistoryProof>(values, "history_proof")).unwrap();
+    let results = key_history_verify::<TC>(
+        &vrf_pk,
+        latest_root_hash,
+        num_epochs,
+        AkdLabel::from(LABEL),
+        history_proof,
+        HistoryVerificationParams::default(),
+    )?;
+    assert_eq!(
+        vec![3, 2, 1],
+        results
+            .iter()
+            .map(|result| result.epoch)
+            .collect::<Vec<_>>()
+    );
+
+    let audit_proof =
+        AppendOnlyProof::try_from(&decode::<types::AppendOnlyProof>(values, "audit_proof"))
+            .unwrap();
+    audit_verify::<TC>(root_hashes, audit_proof).await
+}
+
+test_config!(test_golden_proofs);
+async fn test_golden_proofs<TC: NamedConfiguration>() -> Result<(), AkdError> {
+    let path = format!("{FIXTURE_DIR}/{}.txt", TC::name());
+    let generated = generate_golden_values::<TC>().await?;
+    if std::env::var_os(REGENERATE_ENV_VAR).is_some() {
+        std::fs::write(&path, format_fixture::<TC>(&generated)).unwrap();
+    }
+
+    let golden = parse_fixture(&std::fs::read_to_string(&path).unwrap());
+    verify_golden_values::<TC>(&golden).await?;
+
+    // compare value by value, so that a failure names what has changed
+    assert_eq!(
+        golden.keys().collect::<Vec<_>>(),
+        generated.keys().collect::<Vec<_>>()
+    );
+    for (name, value) in &golden {
+        assert!(
+            value == &generated[name],
+            "The {name} of the {} configuration has changed",
+            TC::name()
+        );
+    }
+    Ok(())
+}
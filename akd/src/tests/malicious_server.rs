@@ -0,0 +1,222 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A simulation of a malicious server, which wraps a real [Directory] and serves subtly wrong
+//! proofs. Each [Attack] documents a way in which a server may try to mislead its clients, and
+//! the tests check that client verification (and auditing) rejects every one of them, while
+//! the proofs of the honest server are accepted.
+
+use std::collections::HashMap;
+
+use crate::auditor::audit_verify;
+use crate::client::{key_history_verify, lookup_verify};
+use crate::directory::Directory;
+use crate::ecvrf::HardCodedAkdVRF;
+use crate::errors::AkdError;
+use crate::storage::manager::StorageManager;
+use crate::storage::memory::AsyncInMemoryDatabase;
+use crate::{
+    AkdLabel, AkdValue, AppendOnlyProof, Configuration, Digest, Direction, EpochHash,
+    HistoryParams, HistoryProof, HistoryVerificationParams, LookupProof,
+};
+
+/// The attacks of a malicious server against which client verification defends
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Attack {
+    /// A lookup proof whose value has been replaced, so that the value no longer matches
+    /// the commitment in the tree
+    ForgedValue,
+    /// A lookup proof which carries the VRF output of a different label, in an attempt to
+    /// pass off that label's value as the value of the label which was looked up
+    WrongVrfOutput,
+    /// A lookup proof in which one of the siblings on the path to the root is placed on the
+    /// wrong side, so that the path hashes to a different root
+    SwappedSiblings,
+    /// A lookup proof which was valid at an earlier epoch (before the label's latest update),
+    /// served against the latest root, to hide the latest value
+    StaleRoot,
+    /// A history proof which omits the label's latest version, to hide it from its owner
+    OmittedLatestVersion,
+    /// A history proof which omits one of the label's intermediate versions
+    OmittedIntermediateVersion,
+    /// A history proof whose versions are out of order
+    ReorderedVersions,
+    /// An append-only proof from which one of the epoch's inserted leaves was removed, to hide
+    /// an update from auditors
+    OmittedInsertion,
+}
+
+impl Attack {
+    const ALL: [Attack; 8] = [
+        Attack::ForgedValue,
+        Attack::WrongVrfOutput,
+        Attack::SwappedSiblings,
+        Attack::StaleRoot,
+        Attack::OmittedLatestVersion,
+        Attack::OmittedIntermediateVersion,
+        Attack::ReorderedVersions,
+        Attack::OmittedInsertion,
+    ];
+}
+
+/// The label which the clients look up
+const TARGET: &str = "alice";
+
+/// A real directory, whose proofs are corrupted according to an [Attack]
+struct MaliciousServer<TC: Configuration> {
+    akd: Directory<TC, AsyncInMemoryDatabase, HardCodedAkdVRF>,
+    /// The lookup proof of each label before its latest update
+    stale_lookups: HashMap<AkdLabel, LookupProof>,
+    /// The root hash of each epoch
+    root_hashes: Vec<Digest>,
+}
+
+impl<TC: Configuration> MaliciousServer<TC> {
+    async fn new() -> Result<Self, AkdError> {
+        let storage = StorageManager::new_no_cache(AsyncInMemoryDatabase::new());
+        let akd = Directory::<TC, _, _>::new(storage, HardCodedAkdVRF {}).await?;
+        let mut server = Self {
+            akd,
+            stale_lookups: HashMap::new(),
+            root_hashes: vec![],
+        };
+        for updates in [
+            vec![("alice", "alice 1"), ("bob", "bob 1")],
+            vec![("alice", "alice 2"), ("carol", "carol 1")],
+            vec![("alice", "alice 3"), ("bob", "bob 2")],
+        ] {
+            server.publish(updates).await?;
+        }
+        Ok(server)
+    }
+
+    async fn publish(&mut self, updates: Vec<(&str, &str)>) -> Result<(), AkdError> {
+        let updates = updates
+            .into_iter()
+            .map(|(label, value)| (AkdLabel::from(label), AkdValue::from(value)))
+            .collect::<Vec<_>>();
+        if !self.root_hashes.is_empty() {
+            for (label, _) in &updates {
+                if let Ok((proof, _)) = self.akd.lookup(label.clone()).await {
+                    self.stale_lookups.insert(label.clone(), proof);
+                }
+            }
+        }
+        let EpochHash(_, root_hash) = self.akd.publish(updates).await?;
+        self.root_hashes.push(root_hash);
+        Ok(())
+    }
+
+    async fn lookup(
+        &self,
+        label: &AkdLabel,
+        attack: Option<Attack>,
+    ) -> Result<(LookupProof, EpochHash), AkdError> {
+        let (mut proof, epoch_hash) = self.akd.lookup(label.clone()).await?;
+        match attack {
+            Some(Attack::ForgedValue) => proof.value = AkdValue::from("forged"),
+            Some(Attack::WrongVrfOutput) => {
+                let (other, _) = self.akd.lookup(AkdLabel::from("bob")).await?;
+                proof.existence_vrf_proof = other.existence_vrf_proof;
+            }
+            Some(Attack::SwappedSiblings) => {
+                let sibling = &mut proof.existence_proof.sibling_proofs[0];
+                sibling.direction = match sibling.direction {
+                    Direction::Left => Direction::Right,
+                    Direction::Right => Direction::Left,
+                };
+            }
+            Some(Attack::StaleRoot) => proof = self.stale_lookups[label].clone(),
+            _ => {}
+        }
+        Ok((proof, epoch_hash))
+    }
+
+    async fn key_history(
+        &self,
+        label: &AkdLabel,
+        attack: Option<Attack>,
+    ) -> Result<(HistoryProof, EpochHash), AkdError> {
+        let (mut proof, epoch_hash) = self
+            .akd
+            .key_history(label, HistoryParams::default())
+            .await?;
+        // the update proofs are ordered from the latest version to the earliest
+        match attack {
+            Some(Attack::OmittedLatestVersion) => {
+                proof.update_proofs.remove(0);
+            }
+            Some(Attack::OmittedIntermediateVersion) => {
+                proof.update_proofs.remove(1);
+            }
+            Some(Attack::ReorderedVersions) => proof.update_proofs.swap(0, 1),
+            _ => {}
+        }
+        Ok((proof, epoch_hash))
+    }
+
+    async fn audit(&self, attack: Option<Attack>) -> Result<AppendOnlyProof, AkdError> {
+        let mut proof = self.akd.audit(1, self.root_hashes.len() as u64).await?;
+        if attack == Some(Attack::OmittedInsertion) {
+            proof.proofs[0].inserted.pop();
+        }
+        Ok(proof)
+    }
+
+    /// Serves the proofs of an attack (or of the honest server, with no attack) to a client
+    /// and an auditor, returning the result of their verification
+    async fn verify(&self, attack: Option<Attack>) -> Result<(), AkdError> {
+        let vrf_pk = self.akd.get_public_key().await?;
+        let label = AkdLabel::from(TARGET);
+
+        let (proof, epoch_hash) = self.lookup(&label, attack).await?;
+        let result = lookup_verify::<TC>(
+            vrf_pk.as_bytes(),
+            epoch_hash.hash(),
+            epoch_hash.epoch(),
+            label.clone(),
+            proof,
+        )?;
+        assert_eq!(AkdValue::from("alice 3"), result.value);
+
+        let (proof, epoch_hash) = self.key_history(&label, attack).await?;
+        let results = key_history_verify::<TC>(
+            vrf_pk.as_bytes(),
+            epoch_hash.hash(),
+            epoch_hash.epoch(),
+            label,
+            proof,
+            HistoryVerificationParams::default(),
+        )?;
+        assert_eq!(
+            vec![3, 2, 1],
+            results
+                .iter()
+                .map(|result| result.version)
+                .collect::<Vec<_>>()
+        );
+
+        let proof = self.audit(attack).await?;
+        audit_verify::<TC>(self.root_hashes.clone(), proof).await
+    }
+}
+
+test_config!(test_malicious_server_attacks_are_rejected);
+async fn test_malicious_server_attacks_are_rejected<TC: Configuration>() -> Result<(), AkdError> {
+    let server = MaliciousServer::<TC>::new().await?;
+
+    // the honest server's proofs are accepted
+    server.verify(None).await?;
+
+    for attack in Attack::ALL {
+        assert!(
+            server.verify(Some(attack)).await.is_err(),
+            "The {attack:?} attack was not detected"
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,120 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Tests that the client and auditor verification are robust to every mutation of the
+//! [crate::test_utils::proof_mutator] (rejecting it, or returning the original result)
+
+use crate::auditor::audit_verify;
+use crate::client::{key_history_verify, lookup_verify, nonexistence_verify};
+use crate::directory::Directory;
+use crate::ecvrf::HardCodedAkdVRF;
+use crate::errors::AkdError;
+use crate::storage::manager::StorageManager;
+use crate::storage::memory::AsyncInMemoryDatabase;
+use crate::test_utils::proof_mutator::Mutate;
+use crate::{
+    AkdLabel, AkdValue, AppendOnlyProof, Configuration, HistoryParams, HistoryVerificationParams,
+};
+
+test_config!(test_verification_is_robust_to_proof_mutations);
+async fn test_verification_is_robust_to_proof_mutations<TC: Configuration>() -> Result<(), AkdError>
+{
+    let storage = StorageManager::new_no_cache(AsyncInMemoryDatabase::new());
+    let akd = Directory::<TC, _, _>::new(storage, HardCodedAkdVRF {}).await?;
+    let mut root_hashes = vec![];
+    for updates in [
+        vec![("alice", "alice 1"), ("bob", "bob 1")],
+        vec![("alice", "alice 2"), ("carol", "carol 1")],
+    ] {
+        let updates = updates
+            .into_iter()
+            .map(|(label, value)| (AkdLabel::from(label), AkdValue::from(value)))
+            .collect();
+        root_hashes.push(akd.publish(updates).await?.hash());
+    }
+    let vrf_pk = akd.get_public_key().await?;
+    let label = AkdLabel::from("alice");
+
+    let (proof, epoch_hash) = akd.lookup(label.clone()).await?;
+    let verify = |proof| {
+        lookup_verify::<TC>(
+            vrf_pk.as_bytes(),
+            epoch_hash.hash(),
+            epoch_hash.epoch(),
+            label.clone(),
+            proof,
+        )
+    };
+    let expected = verify(proof.clone())?;
+    for mutation in proof.mutations() {
+        let result = verify(mutation.proof);
+        assert!(
+            result.is_err() || result.as_ref() == Ok(&expected),
+            "Lookup proof accepted with {}: {result:?}",
+            mutation.description
+        );
+    }
+
+    let (proof, epoch_hash) = akd.key_history(&label, HistoryParams::default()).await?;
+    let verify = |proof| {
+        key_history_verify::<TC>(
+            vrf_pk.as_bytes(),
+            epoch_hash.hash(),
+            epoch_hash.epoch(),
+            label.clone(),
+            proof,
+            HistoryVerificationParams::default(),
+        )
+    };
+    let expected = verify(proof.clone())?;
+    for mutation in proof.mutations() {
+        let result = verify(mutation.proof);
+        // a history proof may be limited to the most recent versions
+        assert!(
+            result
+                .as_ref()
+                .map_or(true, |results| expected.starts_with(results)),
+            "History proof accepted with {}: {result:?}",
+            mutation.description
+        );
+    }
+
+    let (proof, epoch_hash) = akd.lookup_absent(AkdLabel::from("dave")).await?;
+    for mutation in proof.mutations() {
+        let result = nonexistence_verify::<TC>(
+            vrf_pk.as_bytes(),
+            epoch_hash.hash(),
+            AkdLabel::from("dave"),
+            mutation.proof,
+        );
+        // a non-existence proof has no result besides its success
+        assert!(
+            result.is_err(),
+            "Non-existence proof accepted with {}",
+            mutation.description
+        );
+    }
+
+    // the auditor only depends on the sets of inserted and unchanged nodes, not their order
+    let sorted = |mut proof: AppendOnlyProof| {
+        for single in &mut proof.proofs {
+            single.inserted.sort();
+            single.unchanged_nodes.sort();
+        }
+        proof
+    };
+    let proof = akd.audit(1, 2).await?;
+    for mutation in proof.mutations() {
+        let result = audit_verify::<TC>(root_hashes.clone(), mutation.proof.clone()).await;
+        assert!(
+            result.is_err() || sorted(mutation.proof) == sorted(proof.clone()),
+            "Audit proof accepted with {}",
+            mutation.description
+        );
+    }
+    Ok(())
+}
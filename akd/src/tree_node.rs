@@ -19,7 +19,6 @@ use akd_core::configuration::Configuration;
 #[cfg(feature = "serde_serialization")]
 use akd_core::utils::serde_helpers::{azks_value_hex_deserialize, azks_value_hex_serialize};
 use std::cmp::{max, min};
-use std::convert::TryInto;
 use std::marker::Sync;
 
 /// There are three types of nodes: root, leaf and interior.
@@ -107,43 +106,26 @@ impl Storable for TreeNodeWithPreviousValue {
     }
 
     fn get_full_binary_key_id(key: &NodeKey) -> Vec<u8> {
-        let mut result = vec![StorageType::TreeNode as u8];
-        result.extend_from_slice(&key.0.label_len.to_be_bytes());
-        result.extend_from_slice(&key.0.label_val);
-        result
+        crate::storage::keys::encode_node_key(key)
     }
 
     fn key_from_full_binary(bin: &[u8]) -> Result<NodeKey, String> {
-        if bin.len() < 37 {
-            return Err("Not enough bytes to form a proper key".to_string());
-        }
-
-        if bin[0] != StorageType::TreeNode as u8 {
-            return Err("Not a tree node key".to_string());
-        }
-
-        let len_bytes: [u8; 4] = bin[1..=4].try_into().expect("Slice with incorrect length");
-        let val_bytes: [u8; 32] = bin[5..=36].try_into().expect("Slice with incorrect length");
-        let len = u32::from_be_bytes(len_bytes);
-
-        Ok(NodeKey(NodeLabel::new(val_bytes, len)))
+        crate::storage::keys::decode_node_key(bin)
     }
 }
 
 impl TreeNodeWithPreviousValue {
     /// Determine which of the previous + latest nodes to retrieve based on the
     /// target epoch. If it should be older than the latest node, and there is no
-    /// previous node, it returns Not Found
-    pub(crate) fn determine_node_to_get(
-        &self,
-        target_epoch: u64,
-    ) -> Result<TreeNode, StorageError> {
+    /// previous node, it returns Not Found. The record is consumed so that the
+    /// selected node can be moved out of it rather than cloned.
+    pub(crate) fn determine_node_to_get(self, target_epoch: u64) -> Result<TreeNode, StorageError> {
         // If a publish is currently underway, and "some" nodes have been updated to future values
         // our "target_epoch" may point to some older data. Therefore we may need to load a previous
         // version of this node.
         if self.latest_node.last_epoch > target_epoch {
-            if let Some(previous_node) = &self.previous_node {
-                Ok(previous_node.clone())
+            if let Some(previous_node) = self.previous_node {
+                Ok(previous_node)
             } else {
                 // no previous, return not found
                 Err(StorageError::NotFound(format!(
@@ -154,7 +136,7 @@ impl TreeNodeWithPreviousValue {
             }
         } else {
             // Otherwise the currently targeted epoch just points to the most up-to-date value, retrieve that
-            Ok(self.latest_node.clone())
+            Ok(self.latest_node)
         }
     }
 
@@ -171,10 +153,10 @@ impl TreeNodeWithPreviousValue {
     }
 
     pub(crate) async fn write_to_storage<S: Database>(
-        &self,
+        self,
         storage: &StorageManager<S>,
     ) -> Result<(), StorageError> {
-        storage.set(DbRecord::TreeNode(self.clone())).await
+        storage.set(DbRecord::TreeNode(self)).await
     }
 
     pub(crate) async fn get_appropriate_tree_node_from_storage<S: Database>(
@@ -465,6 +447,33 @@ impl TreeNode {
         }
     }
 
+    /// Loads (from storage) both the left and right children of a node with a single batch
+    /// retrieval, returning them in the order of [Direction::Left] then [Direction::Right]
+    pub(crate) async fn get_child_nodes<S: Database>(
+        &self,
+        storage: &StorageManager<S>,
+        epoch: u64,
+    ) -> Result<[Option<TreeNode>; 2], AkdError> {
+        let child_keys = [self.left_child, self.right_child]
+            .into_iter()
+            .flatten()
+            .map(NodeKey)
+            .collect::<Vec<_>>();
+        if child_keys.is_empty() {
+            return Ok([None, None]);
+        }
+        let mut children = Self::batch_get_from_storage(storage, &child_keys, epoch).await?;
+        let mut take_child = |label: Option<NodeLabel>| {
+            label.and_then(|label| {
+                let index = children.iter().position(|child| child.label == label)?;
+                Some(children.swap_remove(index))
+            })
+        };
+        let left = take_child(self.left_child);
+        let right = take_child(self.right_child);
+        Ok([left, right])
+    }
+
     pub(crate) fn get_child_label(&self, direction: Direction) -> Option<NodeLabel> {
         match direction {
             Direction::Left => self.left_child,
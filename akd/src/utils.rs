@@ -29,7 +29,7 @@ pub(crate) fn random_label(rng: &mut impl rand::Rng) -> crate::NodeLabel {
     }
 }
 
-/// NOTE(new_config): Add a new configuration here
+// NOTE(new_config): Add a new configuration here
 
 /// Macro used for running tests with different configurations
 #[cfg(any(test, feature = "public_tests"))]
@@ -50,4 +50,20 @@ macro_rules! test_config {
             }
         }
     };
+    // With arguments for the tokio test runtime, e.g. `flavor = "multi_thread"`
+    ( $x:ident, $($runtime:tt)+ ) => {
+        paste::paste! {
+            #[cfg(feature = "whatsapp_v1")]
+            #[tokio::test($($runtime)+)]
+            async fn [<$x _ whatsapp_v1_config>]() -> Result<(), AkdError> {
+                $x::<$crate::WhatsAppV1Configuration>().await
+            }
+
+            #[cfg(feature = "experimental")]
+            #[tokio::test($($runtime)+)]
+            async fn [<$x _ experimental_config>]() -> Result<(), AkdError> {
+                $x::<$crate::ExperimentalConfiguration<$crate::ExampleLabel>>().await
+            }
+        }
+    };
 }
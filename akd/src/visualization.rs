@@ -0,0 +1,225 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Export of the tree of a directory for visualization, when debugging or teaching.
+//!
+//! `Directory::visualize_tree` reads the nodes of the tree at the latest epoch into a
+//! [TreeVisualization], which can be rendered as a graphviz DOT graph with
+//! [TreeVisualization::to_dot] (e.g. `dot -Tsvg tree.dot > tree.svg`) or as JSON with
+//! [TreeVisualization::to_json]. Nodes are identified by the bits of their label, and the
+//! edge to each child is labeled with the bit which leads to it (0 for left, 1 for right), so
+//! that bugs in the prefixes or directions of nodes can be spotted at a glance.
+//!
+//! The export of a large tree can be limited to its top levels with
+//! [TreeVisualizationOptions::max_depth], or to the neighborhood of a label (the path from
+//! the root towards the label's leaf, along with the siblings of the nodes on the path) with
+//! [TreeVisualizationOptions::neighborhood].
+
+use crate::errors::AkdError;
+use crate::storage::manager::StorageManager;
+use crate::storage::Database;
+use crate::tree_node::{NodeKey, TreeNode, TreeNodeType};
+use crate::{AkdLabel, AzksValue, NodeLabel};
+use std::fmt::Write;
+
+/// The number of leading bits of a label which are shown in a DOT graph before it is abbreviated
+const DOT_LABEL_BITS: u32 = 16;
+
+/// The parts of the tree which are exported by `Directory::visualize_tree`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TreeVisualizationOptions {
+    /// The number of levels below the root to export. If not set, every level is exported.
+    pub max_depth: Option<usize>,
+    /// If set, only the path from the root towards the leaf of the latest version of this
+    /// label is expanded, along with the siblings of the nodes on the path. If the label has
+    /// not been published, the path leads to where its first version would be inserted.
+    pub neighborhood: Option<AkdLabel>,
+}
+
+/// A node of the tree, as exported for visualization
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VisualizedNode {
+    /// The label of the node
+    pub label: NodeLabel,
+    /// The type of the node
+    pub node_type: TreeNodeType,
+    /// The number of levels between the node and the root
+    pub depth: usize,
+    /// The last epoch in which the node was updated
+    pub last_epoch: u64,
+    /// The minimum last epoch of the node's descendants
+    pub min_descendant_epoch: u64,
+    /// The hash of the node
+    pub hash: AzksValue,
+    /// The label of the node's left child, if it has one
+    pub left_child: Option<NodeLabel>,
+    /// The label of the node's right child, if it has one
+    pub right_child: Option<NodeLabel>,
+    /// Whether the node is on the path towards the label of the exported neighborhood
+    pub on_path: bool,
+    /// Whether the node has children which were not exported
+    pub truncated: bool,
+}
+
+/// The nodes of a tree which have been exported for visualization, in breadth-first order
+/// starting from the root
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeVisualization {
+    /// The epoch at which the tree was exported
+    pub epoch: u64,
+    /// The exported nodes
+    pub nodes: Vec<VisualizedNode>,
+}
+
+impl TreeVisualization {
+    /// Reads the nodes of the tree at `epoch`, expanding a node only if it is above the depth
+    /// limit and, when `target` is set, if its label is a prefix of `target`
+    pub(crate) async fn collect<S: Database>(
+        storage: &StorageManager<S>,
+        epoch: u64,
+        max_depth: Option<usize>,
+        target: Option<NodeLabel>,
+    ) -> Result<Self, AkdError> {
+        let mut nodes = vec![];
+        let mut keys = vec![NodeKey(NodeLabel::root())];
+        let mut depth = 0;
+        while !keys.is_empty() {
+            let mut level = TreeNode::batch_get_from_storage(storage, &keys, epoch).await?;
+            level.sort_by_key(|node| (node.label.label_val, node.label.label_len));
+            keys = vec![];
+            for node in level {
+                let on_path = target.is_some_and(|target| node.label.is_prefix_of(&target));
+                let children = [node.left_child, node.right_child];
+                let expand = max_depth.is_none_or(|max_depth| depth < max_depth)
+                    && (target.is_none() || on_path);
+                if expand {
+                    keys.extend(children.into_iter().flatten().map(NodeKey));
+                }
+                nodes.push(VisualizedNode {
+                    label: node.label,
+                    node_type: node.node_type,
+                    depth,
+                    last_epoch: node.last_epoch,
+                    min_descendant_epoch: node.min_descendant_epoch,
+                    hash: node.hash,
+                    left_child: node.left_child,
+                    right_child: node.right_child,
+                    on_path,
+                    truncated: !expand && children.iter().any(Option::is_some),
+                });
+            }
+            depth += 1;
+        }
+        Ok(Self { epoch, nodes })
+    }
+
+    /// Renders the tree as a graphviz DOT graph. Leaves are drawn as boxes, the nodes on the
+    /// path towards the label of a neighborhood are filled, and nodes whose children were not
+    /// exported are dashed.
+    pub fn to_dot(&self) -> String {
+        let index_of = |label: &NodeLabel| self.nodes.iter().position(|node| node.label == *label);
+        let mut dot = String::new();
+        let _ = writeln!(dot, "digraph azks {{");
+        let _ = writeln!(dot, "    label=\"epoch {}\";", self.epoch);
+        let _ = writeln!(dot, "    node [fontname=\"monospace\"];");
+        for (index, node) in self.nodes.iter().enumerate() {
+            let mut attributes = vec![format!(
+                "label=\"{}\\nepoch {}\"",
+                dot_label_bits(&node.label),
+                node.last_epoch
+            )];
+            if node.node_type == TreeNodeType::Leaf {
+                attributes.push("shape=box".to_string());
+            }
+            match (node.on_path, node.truncated) {
+                (true, true) => attributes.push("style=\"filled,dashed\"".to_string()),
+                (true, false) => attributes.push("style=filled".to_string()),
+                (false, true) => attributes.push("style=dashed".to_string()),
+                (false, false) => {}
+            }
+            let _ = writeln!(dot, "    n{index} [{}];", attributes.join(", "));
+        }
+        for (index, node) in self.nodes.iter().enumerate() {
+            for (bit, child) in [(0, node.left_child), (1, node.right_child)] {
+                if let Some(child_index) = child.as_ref().and_then(index_of) {
+                    let _ = writeln!(dot, "    n{index} -> n{child_index} [label=\"{bit}\"];");
+                }
+            }
+        }
+        let _ = writeln!(dot, "}}");
+        dot
+    }
+
+    /// Renders the tree as a JSON object, with the epoch and the list of nodes. Each label is
+    /// encoded as the hex of its value along with its length in bits.
+    pub fn to_json(&self) -> String {
+        let label_json = |label: &NodeLabel| {
+            format!(
+                "{{\"value\":\"{}\",\"len\":{}}}",
+                hex::encode(label.label_val),
+                label.label_len
+            )
+        };
+        let child_json = |child: &Option<NodeLabel>| match child {
+            Some(label) => label_json(label),
+            None => "null".to_string(),
+        };
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| {
+                format!(
+                    "{{\"label\":{},\"bits\":\"{}\",\"node_type\":\"{:?}\",\"depth\":{},\
+                     \"last_epoch\":{},\"min_descendant_epoch\":{},\"hash\":\"{}\",\
+                     \"left_child\":{},\"right_child\":{},\"on_path\":{},\"truncated\":{}}}",
+                    label_json(&node.label),
+                    label_bits(&node.label),
+                    node.node_type,
+                    node.depth,
+                    node.last_epoch,
+                    node.min_descendant_epoch,
+                    hex::encode(node.hash.0),
+                    child_json(&node.left_child),
+                    child_json(&node.right_child),
+                    node.on_path,
+                    node.truncated
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"epoch\":{},\"nodes\":[{}]}}",
+            self.epoch,
+            nodes.join(",")
+        )
+    }
+}
+
+/// The bits of a label, from the root downwards
+fn label_bits(label: &NodeLabel) -> String {
+    (0..label.label_len.min(256))
+        .map(|i| {
+            if (label.label_val[(i / 8) as usize] >> (7 - i % 8)) & 1 == 0 {
+                '0'
+            } else {
+                '1'
+            }
+        })
+        .collect()
+}
+
+/// The bits of a label as shown in a DOT graph, where the root is shown as ε and long labels
+/// are abbreviated to their leading bits and their length
+fn dot_label_bits(label: &NodeLabel) -> String {
+    match label.label_len {
+        0 => "ε".to_string(),
+        len if len <= DOT_LABEL_BITS => label_bits(label),
+        len => format!(
+            "{}… ({len} bits)",
+            label_bits(&label.get_prefix(DOT_LABEL_BITS))
+        ),
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Tests that the heap held by the cache stays bounded as records are cycled through it. These
+//! are kept out of the tests of the library, as they install a global allocator which counts
+//! every allocation.
+
+use akd::storage::cache::{CachePartition, TimedCache};
+use akd::storage::types::{DbRecord, StorageType, ValueState};
+use akd::{AkdLabel, AkdValue, NodeLabel, SizeOf};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+/// Counts the bytes allocated (net of those freed) by each thread, so that a test can measure
+/// the heap held by the cache it fills without interference from the tests running alongside
+/// it. The caches in these tests are only accessed from the test's own thread. The allocator
+/// is only installed in this test crate, rather than in the tests of the library.
+struct CountingAllocator;
+
+thread_local! {
+    static NET_ALLOCATED_BYTES: Cell<isize> = const { Cell::new(0) };
+}
+
+fn record_allocation(bytes: isize) {
+    // the count is unavailable while the thread is being torn down, when it no longer matters
+    let _ = NET_ALLOCATED_BYTES.try_with(|net| net.set(net.get() + bytes));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            record_allocation(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            record_allocation(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        record_allocation(-(layout.size() as isize));
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            record_allocation(new_size as isize - layout.size() as isize);
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocated_bytes() -> isize {
+    NET_ALLOCATED_BYTES.with(|net| net.get())
+}
+
+/// The environment variable which overrides the number of insertions of the growth tests, such
+/// as to cycle millions of records through the cache in a soak test
+const CYCLES_ENV_VAR: &str = "AKD_CACHE_GROWTH_CYCLES";
+const DEFAULT_CYCLES: u64 = 50_000;
+/// The number of records inserted between pauses, which give the cache the chance to clean
+const RECORDS_PER_EPOCH: u64 = 1_000;
+const NUM_USERS: u64 = 100;
+
+fn value_state(i: u64) -> DbRecord {
+    // a distinct key for every insertion, as if each user were updated in every epoch
+    DbRecord::ValueState(ValueState {
+        epoch: i / NUM_USERS,
+        version: i / NUM_USERS,
+        label: NodeLabel {
+            label_len: 256,
+            label_val: [(i % NUM_USERS) as u8; 32],
+        },
+        value: AkdValue::from("some value"),
+        username: AkdLabel(format!("user {}", i % NUM_USERS).into_bytes()),
+        retention_class: None,
+    })
+}
+
+fn tree_node(i: u64) -> DbRecord {
+    use akd::tree_node::{TreeNode, TreeNodeType, TreeNodeWithPreviousValue};
+
+    let mut label_val = [0u8; 32];
+    label_val[..8].copy_from_slice(&i.to_be_bytes());
+    let node = |last_epoch| TreeNode {
+        label: NodeLabel::new(label_val, 64),
+        last_epoch,
+        min_descendant_epoch: 1,
+        parent: NodeLabel::root(),
+        node_type: TreeNodeType::Leaf,
+        left_child: None,
+        right_child: None,
+        hash: akd::AzksValue(akd::hash::EMPTY_DIGEST),
+    };
+    DbRecord::TreeNode(TreeNodeWithPreviousValue {
+        label: NodeLabel::new(label_val, 64),
+        latest_node: node(2),
+        previous_node: Some(node(1)),
+    })
+}
+
+/// Cycles the records produced by `record` through the cache, pausing every
+/// [RECORDS_PER_EPOCH] records so that it can clean, and asserts that the number of entries
+/// and the heap held by the cache never exceed their bounds
+async fn assert_bounded_growth(
+    cache: TimedCache,
+    record: impl Fn(u64) -> DbRecord,
+    max_entries: usize,
+) {
+    let cycles = std::env::var(CYCLES_ENV_VAR)
+        .map(|cycles| cycles.parse().expect("Invalid number of cycles"))
+        .unwrap_or(DEFAULT_CYCLES);
+    // the heap of a single entry: its slot in the map (a key, and a record which is sized for
+    // the largest type of record, with its expiry) and the data of its record, doubled for the
+    // spare capacity of the map
+    let max_entry_bytes =
+        2 * (std::mem::size_of::<(Vec<u8>, (Instant, DbRecord))>() + record(0).size_of()) as isize;
+    let max_bytes = max_entries as isize * max_entry_bytes;
+
+    let baseline = allocated_bytes();
+    let mut peak_entries = 0;
+    let mut peak_bytes = 0;
+    for i in 0..cycles {
+        cache.put(&record(i)).await;
+        if (i + 1) % RECORDS_PER_EPOCH == 0 {
+            peak_entries = peak_entries.max(cache.len());
+            peak_bytes = peak_bytes.max(allocated_bytes() - baseline);
+            assert!(
+                peak_entries <= max_entries && peak_bytes <= max_bytes,
+                "The cache grew to {peak_entries} entries and {peak_bytes} bytes after {} \
+                 insertions (bounds {max_entries} entries and {max_bytes} bytes)",
+                i + 1
+            );
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+    }
+
+    // the memory held by the cache is returned once it is gone
+    let held_bytes = allocated_bytes();
+    let num_entries = cache.len();
+    drop(cache);
+    let released_bytes = held_bytes - allocated_bytes();
+    assert!(
+        released_bytes <= max_bytes && (num_entries == 0 || released_bytes > 0),
+        "{released_bytes} bytes were released by a cache of {num_entries} entries"
+    );
+}
+
+#[tokio::test]
+async fn test_cache_growth_is_bounded_by_expiry() {
+    // records live for 20ms and are evicted at least every 5ms (once the clean is due) while
+    // each epoch of records takes at least 2ms to insert, so at most 13 epochs of records can
+    // be retained at once
+    let cache = TimedCache::new(
+        Some(Duration::from_millis(20)),
+        None,
+        Some(Duration::from_millis(5)),
+    );
+    assert_bounded_growth(cache, value_state, 14 * RECORDS_PER_EPOCH as usize).await;
+}
+
+#[tokio::test]
+async fn test_cache_growth_is_bounded_by_memory_pressure() {
+    // records never expire, so only the memory limit bounds the cache, which is enforced
+    // after each epoch of records
+    let memory_limit_bytes = 256 * 1024;
+    let cache = TimedCache::new(
+        Some(Duration::from_secs(3600)),
+        Some(memory_limit_bytes),
+        Some(Duration::from_millis(2)),
+    );
+    let max_entries = memory_limit_bytes / value_state(0).size_of() + RECORDS_PER_EPOCH as usize;
+    assert_bounded_growth(cache, value_state, max_entries).await;
+}
+
+#[tokio::test]
+async fn test_cache_partition_growth_is_bounded_by_memory_pressure() {
+    let memory_limit_bytes = 256 * 1024;
+    let cache = TimedCache::new(
+        Some(Duration::from_secs(3600)),
+        None,
+        Some(Duration::from_millis(2)),
+    )
+    .with_partition(
+        StorageType::TreeNode,
+        CachePartition {
+            item_lifetime: None,
+            memory_limit_bytes: Some(memory_limit_bytes),
+        },
+    );
+    let max_entries = memory_limit_bytes / tree_node(0).size_of() + RECORDS_PER_EPOCH as usize;
+    assert_bounded_growth(cache, tree_node, max_entries).await;
+}
@@ -0,0 +1,152 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Conversion of [MembershipProof]s into audit paths in the style of RFC 6962, so that
+//! tooling built for transparency logs can display and process them.
+//!
+//! The path from the root of the tree to a leaf of depth `d` is treated as the path to a
+//! leaf of a perfect binary tree with `2^d` leaves: the [AuditPath::leaf_index] is formed
+//! by the directions taken from the root (most significant bit first, with a 1 for each
+//! right child), the [AuditPath::tree_size] is `2^d`, and the [AuditPath::path] lists the
+//! sibling hashes ordered from the leaf up to the root. Since the node hashes of an AKD
+//! also commit to the labels of the nodes, the labels along the path are carried
+//! alongside the hashes, which allows an [AuditPath] to be verified on its own with
+//! [AuditPath::verify], or to be converted back into the [MembershipProof] it came from.
+
+use crate::configuration::Configuration;
+use crate::hash::Digest;
+use crate::verify::VerificationError;
+use crate::{AzksElement, AzksValue, Direction, MembershipProof, NodeLabel, SiblingProof};
+
+#[cfg(feature = "nostd")]
+use alloc::format;
+#[cfg(feature = "nostd")]
+use alloc::vec::Vec;
+
+/// The maximum depth of a [MembershipProof] which can be expressed as an [AuditPath],
+/// so that the tree size fits in a `u64`
+pub const MAX_AUDIT_PATH_DEPTH: usize = 63;
+
+/// A [MembershipProof] expressed as an audit path in the style of RFC 6962
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct AuditPath {
+    /// The index of the leaf within a perfect binary tree of [AuditPath::tree_size] leaves
+    pub leaf_index: u64,
+    /// The number of leaves of the perfect binary tree which the path is taken from
+    pub tree_size: u64,
+    /// The hash of the leaf
+    pub leaf_hash: Digest,
+    /// The hashes of the siblings of the nodes along the path, from the leaf up to the root
+    pub path: Vec<Digest>,
+    /// The label of the leaf
+    pub leaf_label: NodeLabel,
+    /// The labels of the siblings, in the same order as [AuditPath::path]
+    pub sibling_labels: Vec<NodeLabel>,
+    /// The labels of the parents of each node along the path, in the same
+    /// order as [AuditPath::path], ending with the label of the root
+    pub parent_labels: Vec<NodeLabel>,
+}
+
+impl AuditPath {
+    /// Converts a [MembershipProof] into an [AuditPath], which fails if the proof
+    /// is deeper than [MAX_AUDIT_PATH_DEPTH]
+    pub fn from_membership_proof(proof: &MembershipProof) -> Result<Self, VerificationError> {
+        let depth = proof.sibling_proofs.len();
+        if depth > MAX_AUDIT_PATH_DEPTH {
+            return Err(VerificationError::MembershipProof(format!(
+                "Membership proof of depth {depth} exceeds the maximum audit path depth of {MAX_AUDIT_PATH_DEPTH}"
+            )));
+        }
+
+        let mut leaf_index = 0u64;
+        for sibling_proof in proof.sibling_proofs.iter() {
+            leaf_index = (leaf_index << 1) | sibling_proof.direction as u64;
+        }
+
+        let leaf_to_root = proof.sibling_proofs.iter().rev();
+        Ok(Self {
+            leaf_index,
+            tree_size: 1 << depth,
+            leaf_hash: proof.hash_val.0,
+            path: leaf_to_root
+                .clone()
+                .map(|sibling_proof| sibling_proof.siblings[0].value.0)
+                .collect(),
+            leaf_label: proof.label,
+            sibling_labels: leaf_to_root
+                .clone()
+                .map(|sibling_proof| sibling_proof.siblings[0].label)
+                .collect(),
+            parent_labels: leaf_to_root
+                .map(|sibling_proof| sibling_proof.label)
+                .collect(),
+        })
+    }
+
+    /// Converts the [AuditPath] back into the [MembershipProof] it was created from
+    pub fn into_membership_proof(self) -> Result<MembershipProof, VerificationError> {
+        let depth = self.path.len();
+        if depth > MAX_AUDIT_PATH_DEPTH
+            || self.tree_size != 1 << depth
+            || self.leaf_index >= self.tree_size
+            || self.sibling_labels.len() != depth
+            || self.parent_labels.len() != depth
+        {
+            return Err(VerificationError::MembershipProof(format!(
+                "Malformed audit path: leaf index {}, tree size {}, and {} hashes with ({}, {}) labels",
+                self.leaf_index,
+                self.tree_size,
+                depth,
+                self.sibling_labels.len(),
+                self.parent_labels.len()
+            )));
+        }
+
+        let mut sibling_proofs = Vec::with_capacity(depth);
+        for (level, ((hash, sibling_label), parent_label)) in self
+            .path
+            .into_iter()
+            .zip(self.sibling_labels)
+            .zip(self.parent_labels)
+            .enumerate()
+        {
+            let direction = if (self.leaf_index >> level) & 1 == 1 {
+                Direction::Right
+            } else {
+                Direction::Left
+            };
+            sibling_proofs.push(SiblingProof {
+                label: parent_label,
+                siblings: [AzksElement {
+                    label: sibling_label,
+                    value: AzksValue(hash),
+                }],
+                direction,
+            });
+        }
+        // The sibling proofs of a membership proof are ordered from the root down to the leaf
+        sibling_proofs.reverse();
+
+        Ok(MembershipProof {
+            label: self.leaf_label,
+            hash_val: AzksValue(self.leaf_hash),
+            sibling_proofs,
+        })
+    }
+
+    /// Verifies the [AuditPath] with respect to the root hash, as a [MembershipProof]
+    pub fn verify<TC: Configuration>(&self, root_hash: Digest) -> Result<(), VerificationError> {
+        crate::verify::base::verify_membership::<TC>(
+            root_hash,
+            &self.clone().into_membership_proof()?,
+        )
+    }
+}
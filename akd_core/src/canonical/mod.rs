@@ -0,0 +1,391 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A canonical, deterministic byte encoding for proofs, verification results, and
+//! epoch metadata, so that they can be hashed or signed reproducibly across versions
+//! of this crate and across implementations in other languages.
+//!
+//! The encoding produced by [CanonicalEncode::to_canonical_bytes] begins with the
+//! single byte [CANONICAL_ENCODING_VERSION], followed by the encoding of the value,
+//! which is built from the following rules:
+//! * `u64`s are encoded as 8 bytes, big-endian
+//! * [Digest]s and [AzksValue]s are encoded as their 32 raw bytes
+//! * Byte strings ([AkdLabel], [AkdValue], VRF proofs, nonces, signatures) are encoded as
+//!   their length (as a `u64`) followed by the bytes
+//! * [NodeLabel]s are encoded as their bit length (4 bytes, big-endian) followed by the
+//!   32 bytes of the label value
+//! * [Direction]s are encoded as a single byte, 0 for left and 1 for right
+//! * [RetentionClass]es are encoded as a single byte, 0 for standard, 1 for ephemeral, and
+//!   2 for legal hold
+//! * Sequences are encoded as the number of elements (as a `u64`) followed by each element
+//! * Optional values are encoded as the byte 0 when absent, or the byte 1 followed by the value
+//! * Structs are encoded as the concatenation of their fields, in declaration order
+//!
+//! Any change to these rules, or to the fields of an encoded type, must be accompanied
+//! by a change to [CANONICAL_ENCODING_VERSION]. The exception is the encoding of
+//! [DirectoryExport]s (and their [ExportEntry]s), which begins with
+//! [EXPORT_ENCODING_VERSION] instead, so that changes to the export do not change the
+//! encodings (and hashes) of proofs.
+
+use crate::configuration::Configuration;
+use crate::hash::Digest;
+use crate::spot_check::{SpotCheckProof, SpotCheckSample};
+use crate::{
+    AggregatedHistoryProof, AkdLabel, AkdValue, AppendOnlyProof, AzksElement, AzksValue,
+    DelegationCertificate, Direction, DirectoryExport, EpochInsertions, ExportEntry, HistoryProof,
+    LatestVersionProof, LinkedHistoryProof, LinkedHistorySegment, LookupProof, MembershipProof,
+    MultiEpochAppendOnlyProof, NodeLabel, NonExistenceProof, NonMembershipProof, RetentionClass,
+    SiblingProof, SignedLookupResponse, SingleAppendOnlyProof, UpdateProof, VerifyResult,
+};
+
+#[cfg(feature = "nostd")]
+use alloc::vec;
+#[cfg(feature = "nostd")]
+use alloc::vec::Vec;
+
+#[cfg(test)]
+mod tests;
+
+/// The version of the canonical encoding, which prefixes every encoded value
+pub const CANONICAL_ENCODING_VERSION: u8 = 1;
+
+/// The version of the canonical encoding of [DirectoryExport]s and [ExportEntry]s, which
+/// prefixes their encoded values in place of [CANONICAL_ENCODING_VERSION]. Version 2 added
+/// the [RetentionClass] of each entry.
+pub const EXPORT_ENCODING_VERSION: u8 = 2;
+
+/// A type with a canonical byte encoding
+pub trait CanonicalEncode {
+    /// Appends the canonical encoding of this value (without the version prefix) to `out`
+    fn canonical_encode(&self, out: &mut Vec<u8>);
+
+    /// Returns the canonical encoding of this value, prefixed with [CANONICAL_ENCODING_VERSION]
+    fn to_canonical_bytes(&self) -> Vec<u8> {
+        let mut out = vec![CANONICAL_ENCODING_VERSION];
+        self.canonical_encode(&mut out);
+        out
+    }
+
+    /// Returns the hash of the canonical encoding of this value under the provided configuration
+    fn canonical_hash<TC: Configuration>(&self) -> Digest {
+        TC::hash(&self.to_canonical_bytes())
+    }
+}
+
+/// Encodes the epoch metadata of a directory, which consists of an
+/// epoch and the root hash of the directory at that epoch
+pub fn encode_epoch_metadata(epoch: u64, root_hash: &Digest, out: &mut Vec<u8>) {
+    epoch.canonical_encode(out);
+    root_hash.canonical_encode(out);
+}
+
+fn encode_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    (bytes.len() as u64).canonical_encode(out);
+    out.extend_from_slice(bytes);
+}
+
+fn encode_byte_strings(items: &[Vec<u8>], out: &mut Vec<u8>) {
+    (items.len() as u64).canonical_encode(out);
+    for item in items {
+        encode_bytes(item, out);
+    }
+}
+
+impl CanonicalEncode for u64 {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+impl CanonicalEncode for Digest {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+}
+
+impl<T: CanonicalEncode> CanonicalEncode for Vec<T> {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u64).canonical_encode(out);
+        for item in self {
+            item.canonical_encode(out);
+        }
+    }
+}
+
+impl<T: CanonicalEncode> CanonicalEncode for Option<T> {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        match self {
+            None => out.push(0u8),
+            Some(value) => {
+                out.push(1u8);
+                value.canonical_encode(out);
+            }
+        }
+    }
+}
+
+impl CanonicalEncode for AkdLabel {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        encode_bytes(&self.0, out);
+    }
+}
+
+impl CanonicalEncode for AkdValue {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        encode_bytes(&self.0, out);
+    }
+}
+
+impl CanonicalEncode for AzksValue {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.0.canonical_encode(out);
+    }
+}
+
+impl CanonicalEncode for NodeLabel {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.label_len.to_be_bytes());
+        out.extend_from_slice(&self.label_val);
+    }
+}
+
+impl CanonicalEncode for Direction {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+}
+
+impl CanonicalEncode for RetentionClass {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+}
+
+impl CanonicalEncode for AzksElement {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.label.canonical_encode(out);
+        self.value.canonical_encode(out);
+    }
+}
+
+impl CanonicalEncode for SiblingProof {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.label.canonical_encode(out);
+        for sibling in self.siblings.iter() {
+            sibling.canonical_encode(out);
+        }
+        self.direction.canonical_encode(out);
+    }
+}
+
+impl CanonicalEncode for MembershipProof {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.label.canonical_encode(out);
+        self.hash_val.canonical_encode(out);
+        self.sibling_proofs.canonical_encode(out);
+    }
+}
+
+impl CanonicalEncode for NonMembershipProof {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.label.canonical_encode(out);
+        self.longest_prefix.canonical_encode(out);
+        for child in self.longest_prefix_children.iter() {
+            child.canonical_encode(out);
+        }
+        self.longest_prefix_membership_proof.canonical_encode(out);
+    }
+}
+
+impl CanonicalEncode for LookupProof {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.epoch.canonical_encode(out);
+        self.value.canonical_encode(out);
+        self.version.canonical_encode(out);
+        encode_bytes(&self.existence_vrf_proof, out);
+        self.existence_proof.canonical_encode(out);
+        encode_bytes(&self.marker_vrf_proof, out);
+        self.marker_proof.canonical_encode(out);
+        encode_bytes(&self.freshness_vrf_proof, out);
+        self.freshness_proof.canonical_encode(out);
+        encode_bytes(&self.commitment_nonce, out);
+    }
+}
+
+impl CanonicalEncode for LatestVersionProof {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.lookup_proof.canonical_encode(out);
+        encode_byte_strings(&self.until_marker_vrf_proofs, out);
+        self.non_existence_until_marker_proofs.canonical_encode(out);
+        encode_byte_strings(&self.future_marker_vrf_proofs, out);
+        self.non_existence_of_future_marker_proofs
+            .canonical_encode(out);
+    }
+}
+
+impl CanonicalEncode for NonExistenceProof {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        encode_bytes(&self.vrf_proof, out);
+        self.non_membership_proof.canonical_encode(out);
+    }
+}
+
+impl CanonicalEncode for SignedLookupResponse {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.proof.canonical_encode(out);
+        encode_epoch_metadata(self.epoch, &self.root_hash, out);
+        encode_bytes(&self.epoch_signature, out);
+        self.configuration_fingerprint.canonical_encode(out);
+        // The delegation is only encoded when present, so that the encoding of a response
+        // signed by the root key is unchanged
+        if let Some(delegation) = &self.delegation {
+            delegation.canonical_encode(out);
+        }
+    }
+}
+
+impl CanonicalEncode for DelegationCertificate {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        encode_bytes(&self.delegate_public_key, out);
+        self.first_epoch.canonical_encode(out);
+        self.last_epoch.canonical_encode(out);
+        encode_bytes(&self.signature, out);
+    }
+}
+
+impl CanonicalEncode for UpdateProof {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.epoch.canonical_encode(out);
+        self.value.canonical_encode(out);
+        self.version.canonical_encode(out);
+        encode_bytes(&self.existence_vrf_proof, out);
+        self.existence_proof.canonical_encode(out);
+        match &self.previous_version_vrf_proof {
+            None => out.push(0u8),
+            Some(proof) => {
+                out.push(1u8);
+                encode_bytes(proof, out);
+            }
+        }
+        self.previous_version_proof.canonical_encode(out);
+        encode_bytes(&self.commitment_nonce, out);
+    }
+}
+
+impl CanonicalEncode for HistoryProof {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.update_proofs.canonical_encode(out);
+        encode_byte_strings(&self.until_marker_vrf_proofs, out);
+        self.non_existence_until_marker_proofs.canonical_encode(out);
+        encode_byte_strings(&self.future_marker_vrf_proofs, out);
+        self.non_existence_of_future_marker_proofs
+            .canonical_encode(out);
+    }
+}
+
+impl CanonicalEncode for AggregatedHistoryProof {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.aggregate.canonical_encode(out);
+        self.recent.canonical_encode(out);
+    }
+}
+
+impl CanonicalEncode for LinkedHistorySegment {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.label.canonical_encode(out);
+        self.proof.canonical_encode(out);
+    }
+}
+
+impl CanonicalEncode for LinkedHistoryProof {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.segments.canonical_encode(out);
+    }
+}
+
+impl CanonicalEncode for ExportEntry {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.label.canonical_encode(out);
+        self.version.canonical_encode(out);
+        self.epoch.canonical_encode(out);
+        self.node_label.canonical_encode(out);
+        self.commitment.canonical_encode(out);
+        self.retention_class.canonical_encode(out);
+    }
+
+    fn to_canonical_bytes(&self) -> Vec<u8> {
+        let mut out = vec![EXPORT_ENCODING_VERSION];
+        self.canonical_encode(&mut out);
+        out
+    }
+}
+
+impl CanonicalEncode for DirectoryExport {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        encode_epoch_metadata(self.epoch, &self.root_hash, out);
+        self.entries.canonical_encode(out);
+    }
+
+    fn to_canonical_bytes(&self) -> Vec<u8> {
+        let mut out = vec![EXPORT_ENCODING_VERSION];
+        self.canonical_encode(&mut out);
+        out
+    }
+}
+
+impl CanonicalEncode for VerifyResult {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.epoch.canonical_encode(out);
+        self.version.canonical_encode(out);
+        self.value.canonical_encode(out);
+    }
+}
+
+impl CanonicalEncode for SingleAppendOnlyProof {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.inserted.canonical_encode(out);
+        self.unchanged_nodes.canonical_encode(out);
+    }
+}
+
+impl CanonicalEncode for AppendOnlyProof {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.proofs.canonical_encode(out);
+        self.epochs.canonical_encode(out);
+    }
+}
+
+impl CanonicalEncode for EpochInsertions {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.epoch.canonical_encode(out);
+        self.inserted.canonical_encode(out);
+    }
+}
+
+impl CanonicalEncode for MultiEpochAppendOnlyProof {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.start_epoch.canonical_encode(out);
+        self.end_epoch.canonical_encode(out);
+        self.unchanged_nodes.canonical_encode(out);
+        self.insertions.canonical_encode(out);
+    }
+}
+
+impl CanonicalEncode for SpotCheckSample {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.commitment.canonical_encode(out);
+        self.leaf_epoch.canonical_encode(out);
+        self.proof.canonical_encode(out);
+    }
+}
+
+impl CanonicalEncode for SpotCheckProof {
+    fn canonical_encode(&self, out: &mut Vec<u8>) {
+        self.epoch.canonical_encode(out);
+        self.samples.canonical_encode(out);
+    }
+}
@@ -0,0 +1,268 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Golden-byte tests for the canonical encoding. If any of these tests fail, the
+//! canonical encoding has changed and [CANONICAL_ENCODING_VERSION] (or, for exports,
+//! [EXPORT_ENCODING_VERSION]) must be updated.
+
+use super::*;
+#[cfg(feature = "nostd")]
+use alloc::string::String;
+#[cfg(feature = "nostd")]
+use alloc::vec;
+
+// ================= Test helpers ================= //
+
+fn label(byte: u8, len: u32) -> NodeLabel {
+    NodeLabel {
+        label_val: [byte; 32],
+        label_len: len,
+    }
+}
+
+fn element(byte: u8) -> AzksElement {
+    AzksElement {
+        label: label(byte, 256),
+        value: AzksValue([byte + 1; 32]),
+    }
+}
+
+fn membership_proof() -> MembershipProof {
+    MembershipProof {
+        label: label(0xaa, 3),
+        hash_val: AzksValue([0xbb; 32]),
+        sibling_proofs: vec![SiblingProof {
+            label: label(0xcc, 1),
+            siblings: [element(0x10)],
+            direction: Direction::Right,
+        }],
+    }
+}
+
+fn lookup_proof() -> LookupProof {
+    LookupProof {
+        epoch: 3,
+        value: AkdValue::from("value"),
+        version: 2,
+        existence_vrf_proof: vec![1; 80],
+        existence_proof: membership_proof(),
+        marker_vrf_proof: vec![2; 80],
+        marker_proof: membership_proof(),
+        freshness_vrf_proof: vec![3; 80],
+        freshness_proof: NonMembershipProof {
+            label: label(0x20, 256),
+            longest_prefix: label(0x20, 2),
+            longest_prefix_children: [element(0x30), element(0x40)],
+            longest_prefix_membership_proof: membership_proof(),
+        },
+        commitment_nonce: vec![4; 32],
+    }
+}
+
+fn repeat(hex_byte: &str, count: usize) -> String {
+    hex_byte.repeat(count)
+}
+
+// ================= Tests ================= //
+
+#[test]
+fn test_golden_verify_result() {
+    let result = VerifyResult {
+        epoch: 1,
+        version: 2,
+        value: AkdValue::from("hi"),
+    };
+    assert_eq!(
+        "010000000000000001000000000000000200000000000000026869",
+        hex::encode(result.to_canonical_bytes())
+    );
+}
+
+#[test]
+fn test_golden_epoch_metadata() {
+    let mut out = vec![];
+    encode_epoch_metadata(7, &[0x11; 32], &mut out);
+    assert_eq!(
+        ["0000000000000007", &repeat("11", 32)].concat(),
+        hex::encode(out)
+    );
+}
+
+#[test]
+fn test_golden_membership_proof() {
+    let expected = [
+        "01",
+        // label
+        "00000003",
+        &repeat("aa", 32),
+        // hash_val
+        &repeat("bb", 32),
+        // one sibling proof
+        "0000000000000001",
+        "00000001",
+        &repeat("cc", 32),
+        "00000100",
+        &repeat("10", 32),
+        &repeat("11", 32),
+        "01",
+    ]
+    .concat();
+    assert_eq!(
+        expected,
+        hex::encode(membership_proof().to_canonical_bytes())
+    );
+}
+
+#[test]
+fn test_golden_update_proof_options() {
+    let mut proof = UpdateProof {
+        epoch: 1,
+        value: AkdValue::from("v"),
+        version: 1,
+        existence_vrf_proof: vec![0xee],
+        existence_proof: MembershipProof {
+            label: label(0, 0),
+            hash_val: AzksValue([0; 32]),
+            sibling_proofs: vec![],
+        },
+        previous_version_vrf_proof: None,
+        previous_version_proof: None,
+        commitment_nonce: vec![0xff],
+    };
+    let prefix = [
+        "01",
+        "0000000000000001",
+        "000000000000000176",
+        "0000000000000001",
+        "0000000000000001ee",
+        "00000000",
+        &repeat("00", 32),
+        &repeat("00", 32),
+        "0000000000000000",
+    ]
+    .concat();
+    let nonce = "0000000000000001ff";
+    assert_eq!(
+        [prefix.as_str(), "00", "00", nonce].concat(),
+        hex::encode(proof.to_canonical_bytes())
+    );
+
+    proof.previous_version_vrf_proof = Some(vec![0xdd, 0xdd]);
+    assert_eq!(
+        [prefix.as_str(), "01", "0000000000000002dddd", "00", nonce].concat(),
+        hex::encode(proof.to_canonical_bytes())
+    );
+}
+
+#[test]
+fn test_golden_append_only_proofs() {
+    let proof = AppendOnlyProof {
+        proofs: vec![SingleAppendOnlyProof {
+            inserted: vec![element(0x01)],
+            unchanged_nodes: vec![],
+        }],
+        epochs: vec![5],
+    };
+    let expected = [
+        "01",
+        "0000000000000001",
+        "0000000000000001",
+        "00000100",
+        &repeat("01", 32),
+        &repeat("02", 32),
+        "0000000000000000",
+        "0000000000000001",
+        "0000000000000005",
+    ]
+    .concat();
+    assert_eq!(expected, hex::encode(proof.to_canonical_bytes()));
+
+    let proof = MultiEpochAppendOnlyProof {
+        start_epoch: 5,
+        end_epoch: 6,
+        unchanged_nodes: vec![],
+        insertions: vec![EpochInsertions {
+            epoch: 6,
+            inserted: vec![element(0x01)],
+        }],
+    };
+    let expected = [
+        "01",
+        "0000000000000005",
+        "0000000000000006",
+        "0000000000000000",
+        "0000000000000001",
+        "0000000000000006",
+        "0000000000000001",
+        "00000100",
+        &repeat("01", 32),
+        &repeat("02", 32),
+    ]
+    .concat();
+    assert_eq!(expected, hex::encode(proof.to_canonical_bytes()));
+}
+
+#[test]
+fn test_golden_directory_export() {
+    let export = DirectoryExport {
+        epoch: 2,
+        root_hash: [0x11; 32],
+        entries: vec![ExportEntry {
+            label: AkdLabel::from("a"),
+            version: 3,
+            epoch: 1,
+            node_label: label(0x22, 256),
+            commitment: AzksValue([0x33; 32]),
+            retention_class: RetentionClass::LegalHold,
+        }],
+    };
+    let expected = [
+        // the export version, which is independent of the version of proofs
+        "02",
+        "0000000000000002",
+        &repeat("11", 32),
+        "0000000000000001",
+        "000000000000000161",
+        "0000000000000003",
+        "0000000000000001",
+        "00000100",
+        &repeat("22", 32),
+        &repeat("33", 32),
+        "02",
+    ]
+    .concat();
+    assert_eq!(expected, hex::encode(export.to_canonical_bytes()));
+}
+
+#[cfg(feature = "experimental")]
+#[test]
+fn test_golden_lookup_proof_hash() {
+    type TC = crate::ExperimentalConfiguration<crate::ExampleLabel>;
+    let proof = lookup_proof();
+    assert_eq!(
+        proof.to_canonical_bytes(),
+        proof.clone().to_canonical_bytes()
+    );
+    assert_eq!(
+        "e8a921a91f913c84e05e39c13ecf50d8d4e484e3edb01884357156143e3d0e21",
+        hex::encode(proof.canonical_hash::<TC>())
+    );
+}
+
+#[test]
+fn test_fields_are_encoded() {
+    let proof = lookup_proof();
+    let mut other = proof.clone();
+    other.freshness_proof.longest_prefix_children.swap(0, 1);
+    assert_ne!(proof.to_canonical_bytes(), other.to_canonical_bytes());
+
+    // Moving bytes between adjacent byte strings changes the encoding
+    let mut other = proof.clone();
+    other.existence_vrf_proof.push(2);
+    other.marker_vrf_proof.pop();
+    assert_ne!(proof.to_canonical_bytes(), other.to_canonical_bytes());
+}
@@ -0,0 +1,57 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Defines a configuration which commits to values with a scheme other than that of
+//! another configuration
+
+use core::marker::PhantomData;
+
+use super::traits::CommitmentScheme;
+use crate::configuration::{Configuration, ConfigurationWrapper};
+use crate::{AkdValue, AzksValue, AzksValueWithEpoch, NodeLabel};
+
+/// A configuration which commits to values with `C`, and is otherwise identical to `TC` (including
+/// the derivation of the nonces of the commitments). The commitments stored in the tree differ
+/// between the two configurations, unless `C` is the scheme of `TC`, so a directory cannot switch
+/// between them once values have been published. Clients verify the values of lookups and key
+/// histories with the same scheme, by recomputing their commitments.
+///
+/// ```
+/// use akd_core::configuration::{CommitmentConfiguration, HashCommitments};
+///
+/// # #[cfg(feature = "experimental")]
+/// type Config = CommitmentConfiguration<
+///     akd_core::ExperimentalConfiguration<akd_core::ExampleLabel>,
+///     HashCommitments,
+/// >;
+/// ```
+#[derive(Clone)]
+pub struct CommitmentConfiguration<TC, C>(PhantomData<(TC, C)>);
+
+impl<TC: Configuration, C: CommitmentScheme> ConfigurationWrapper
+    for CommitmentConfiguration<TC, C>
+{
+    type Inner = TC;
+
+    fn hash_leaf_with_value(value: &AkdValue, epoch: u64, nonce: &[u8]) -> AzksValueWithEpoch {
+        TC::hash_leaf_with_commitment(<Self as Configuration>::commit_value(value, nonce), epoch)
+    }
+
+    fn commit_value(value: &AkdValue, nonce: &[u8]) -> AzksValue {
+        C::commit::<TC>(value, nonce)
+    }
+
+    fn compute_fresh_azks_value(
+        commitment_key: &[u8],
+        label: &NodeLabel,
+        version: u64,
+        value: &AkdValue,
+    ) -> AzksValue {
+        let nonce = TC::get_commitment_nonce(commitment_key, label, version, value);
+        <Self as Configuration>::commit_value(value, &nonce)
+    }
+}
@@ -12,7 +12,6 @@ use core::marker::PhantomData;
 use super::traits::DomainLabel;
 use crate::configuration::Configuration;
 use crate::hash::{Digest, DIGEST_BYTES};
-use crate::utils::i2osp_array;
 use crate::{AkdLabel, AkdValue, AzksValue, AzksValueWithEpoch, NodeLabel, VersionFreshness};
 
 #[cfg(feature = "nostd")]
@@ -25,16 +24,6 @@ pub struct ExperimentalConfiguration<L>(PhantomData<L>);
 unsafe impl<L> Send for ExperimentalConfiguration<L> {}
 unsafe impl<L> Sync for ExperimentalConfiguration<L> {}
 
-impl<L: DomainLabel> ExperimentalConfiguration<L> {
-    /// Used by the client to supply a commitment nonce and value to reconstruct the commitment, via:
-    /// commitment = H(i2osp_array(value), i2osp_array(nonce))
-    fn generate_commitment_from_nonce_client(value: &crate::AkdValue, nonce: &[u8]) -> AzksValue {
-        AzksValue(<Self as Configuration>::hash(
-            &[i2osp_array(value), i2osp_array(nonce)].concat(),
-        ))
-    }
-}
-
 impl<L: DomainLabel> Configuration for ExperimentalConfiguration<L> {
     fn hash(item: &[u8]) -> crate::hash::Digest {
         // Hash(domain label || item)
@@ -57,7 +46,7 @@ impl<L: DomainLabel> Configuration for ExperimentalConfiguration<L> {
         epoch: u64,
         nonce: &[u8],
     ) -> AzksValueWithEpoch {
-        let commitment = Self::generate_commitment_from_nonce_client(value, nonce);
+        let commitment = Self::commit_value(value, nonce);
         Self::hash_leaf_with_commitment(commitment, epoch)
     }
 
@@ -96,9 +85,7 @@ impl<L: DomainLabel> Configuration for ExperimentalConfiguration<L> {
         value: &AkdValue,
     ) -> AzksValue {
         let nonce = Self::get_commitment_nonce(commitment_key, label, version, value);
-        AzksValue(Self::hash(
-            &[i2osp_array(value), i2osp_array(&nonce)].concat(),
-        ))
+        Self::commit_value(value, &nonce)
     }
 
     /// To convert a regular label (arbitrary string of bytes) into a [NodeLabel], we compute the
@@ -108,6 +95,7 @@ impl<L: DomainLabel> Configuration for ExperimentalConfiguration<L> {
     /// - I2OSP(len(label) as u64, label)
     /// - A single byte encoded as 0u8 if "stale", 1u8 if "fresh"
     /// - A u64 representing the version
+    ///
     /// These are all interpreted as a single byte array and hashed together, with the output
     /// of the hash returned.
     fn get_hash_from_label_input(
@@ -0,0 +1,47 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Defines a configuration which chooses the marker versions of labels with a strategy
+//! other than that of another configuration
+
+use core::marker::PhantomData;
+
+use super::traits::MarkerStrategy;
+use crate::configuration::{Configuration, ConfigurationWrapper};
+
+/// A configuration which chooses the marker versions of labels with `M`, and is otherwise
+/// identical to `TC`. The tree is the same under both configurations, but the proofs of
+/// lookups and key histories differ, so clients must verify them with the same strategy as
+/// the directory which produced them.
+///
+/// For example, with markers at the powers of four, the proofs of non-existence of future
+/// versions of a label include fewer marker versions as the label ages, at the cost of more
+/// versions before the next marker:
+///
+/// ```
+/// use akd_core::configuration::{ExponentialMarkers, MarkerConfiguration};
+///
+/// # #[cfg(feature = "experimental")]
+/// type Config = MarkerConfiguration<
+///     akd_core::ExperimentalConfiguration<akd_core::ExampleLabel>,
+///     ExponentialMarkers<4>,
+/// >;
+/// ```
+#[derive(Clone)]
+pub struct MarkerConfiguration<TC, M>(PhantomData<(TC, M)>);
+
+impl<TC: Configuration, M: MarkerStrategy> ConfigurationWrapper for MarkerConfiguration<TC, M> {
+    type Inner = TC;
+
+    fn marker_version(version: u64) -> u64 {
+        M::marker_version(version)
+    }
+
+    fn next_marker_version(version: u64) -> u64 {
+        M::next_marker_version(version)
+    }
+}
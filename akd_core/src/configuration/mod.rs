@@ -7,8 +7,33 @@
 
 //! Defines the configuration trait and implementations for various configurations
 
+mod commitments;
+mod markers;
+mod normalized;
+mod tombstones;
 mod traits;
-pub use traits::{Configuration, DomainLabel, ExampleLabel};
+mod truncated;
+mod wrapper;
+pub use commitments::CommitmentConfiguration;
+pub use markers::MarkerConfiguration;
+pub use normalized::NormalizedConfiguration;
+pub use tombstones::TombstoneMetadataConfiguration;
+pub use traits::{
+    CommitmentScheme, Configuration, DomainLabel, ExampleLabel, ExponentialMarkers,
+    HashCommitments, LabelNormalizer, MarkerStrategy, PowerOfTwoMarkers,
+};
+pub use truncated::{TruncatedLabelConfiguration, MIN_NODE_LABEL_LEN};
+pub use wrapper::ConfigurationWrapper;
+
+#[cfg(feature = "pedersen")]
+mod pedersen;
+#[cfg(feature = "pedersen")]
+pub use pedersen::PedersenCommitments;
+
+#[cfg(feature = "poseidon")]
+mod poseidon;
+#[cfg(feature = "poseidon")]
+pub use poseidon::{Poseidon, PoseidonCommitments, PoseidonConfiguration};
 
 #[cfg(feature = "public_tests")]
 pub use traits::NamedConfiguration;
@@ -0,0 +1,60 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Defines a configuration which canonicalizes labels before using another configuration
+
+use core::marker::PhantomData;
+
+use super::traits::LabelNormalizer;
+use crate::configuration::{Configuration, ConfigurationWrapper};
+use crate::AkdLabel;
+use alloc::borrow::Cow;
+
+/// A configuration which normalizes labels with `N`, and is otherwise identical to `TC`.
+/// Since normalization only affects which label is looked up, the tree of a directory
+/// whose labels are all already normalized is the same under both configurations.
+///
+/// ```
+/// use akd_core::configuration::{LabelNormalizer, NormalizedConfiguration};
+/// use akd_core::AkdLabel;
+/// use std::borrow::Cow;
+///
+/// /// Treats labels as case-insensitive ASCII
+/// #[derive(Clone)]
+/// struct AsciiLowercase;
+///
+/// impl LabelNormalizer for AsciiLowercase {
+///     fn normalize(label: &AkdLabel) -> Cow<'_, AkdLabel> {
+///         if label.iter().any(u8::is_ascii_uppercase) {
+///             Cow::Owned(AkdLabel(label.to_ascii_lowercase()))
+///         } else {
+///             Cow::Borrowed(label)
+///         }
+///     }
+/// }
+///
+/// # #[cfg(feature = "experimental")]
+/// type Config = NormalizedConfiguration<
+///     akd_core::ExperimentalConfiguration<akd_core::ExampleLabel>,
+///     AsciiLowercase,
+/// >;
+/// ```
+#[derive(Clone)]
+pub struct NormalizedConfiguration<TC, N>(PhantomData<(TC, N)>);
+
+impl<TC: Configuration, N: LabelNormalizer> ConfigurationWrapper
+    for NormalizedConfiguration<TC, N>
+{
+    type Inner = TC;
+
+    fn normalize_label(label: &AkdLabel) -> Cow<'_, AkdLabel> {
+        match TC::normalize_label(label) {
+            Cow::Borrowed(label) => N::normalize(label),
+            Cow::Owned(label) => Cow::Owned(N::normalize(&label).into_owned()),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Defines Pedersen commitments to values over the Ristretto group
+
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+
+use super::traits::CommitmentScheme;
+use crate::configuration::Configuration;
+use crate::utils::i2osp_array;
+use crate::{AkdValue, AzksValue};
+
+/// The domain separator of the generator of the blinding factors
+const BLINDING_GENERATOR_DOMAIN: &[u8] = b"akd pedersen blinding generator";
+/// The domain separator of the scalars which are derived from values
+const VALUE_DOMAIN: &[u8] = b"akd pedersen value";
+/// The domain separator of the blinding factors which are derived from nonces
+const BLINDING_DOMAIN: &[u8] = b"akd pedersen blinding";
+
+/// Pedersen commitments over the Ristretto group: `commitment = v * G + r * H`, where `G` is
+/// the Ristretto basepoint, `H` is a generator with no known discrete logarithm relative to
+/// `G` (derived by hashing to the group), `v` is the hash of the value to a scalar, and `r` is
+/// the hash of the nonce to a scalar. The commitment is stored in the tree in its compressed
+/// 32-byte encoding.
+///
+/// The commitments are perfectly hiding, and binding under the discrete logarithm assumption
+/// (and the collision resistance of the hash of values to scalars). Unlike hash-based
+/// commitments, they are additively homomorphic, so proofs about committed values can be
+/// built from [PedersenCommitments::value_scalar], [PedersenCommitments::blinding_scalar],
+/// and [PedersenCommitments::blinding_generator].
+#[derive(Clone)]
+pub struct PedersenCommitments;
+
+impl PedersenCommitments {
+    /// The generator `H` of the blinding factors
+    pub fn blinding_generator<TC: Configuration>() -> RistrettoPoint {
+        RistrettoPoint::from_uniform_bytes(&wide_hash::<TC>(BLINDING_GENERATOR_DOMAIN, &[]))
+    }
+
+    /// The scalar `v` which a value is committed to
+    pub fn value_scalar<TC: Configuration>(value: &AkdValue) -> Scalar {
+        Scalar::from_bytes_mod_order_wide(&wide_hash::<TC>(VALUE_DOMAIN, &i2osp_array(value)))
+    }
+
+    /// The blinding factor `r` of a commitment with a nonce
+    pub fn blinding_scalar<TC: Configuration>(nonce: &[u8]) -> Scalar {
+        Scalar::from_bytes_mod_order_wide(&wide_hash::<TC>(BLINDING_DOMAIN, &i2osp_array(nonce)))
+    }
+}
+
+impl CommitmentScheme for PedersenCommitments {
+    fn commit<TC: Configuration>(value: &AkdValue, nonce: &[u8]) -> AzksValue {
+        let commitment = RISTRETTO_BASEPOINT_POINT * Self::value_scalar::<TC>(value)
+            + Self::blinding_generator::<TC>() * Self::blinding_scalar::<TC>(nonce);
+        AzksValue(commitment.compress().to_bytes())
+    }
+}
+
+/// Hashes the input to 64 bytes, which are reduced to a scalar or mapped to the group with
+/// negligible bias, by concatenating two domain-separated hashes of `TC`
+fn wide_hash<TC: Configuration>(domain: &[u8], input: &[u8]) -> [u8; 64] {
+    let mut output = [0u8; 64];
+    output[..32].copy_from_slice(&TC::hash(&[domain, &[0u8], input].concat()));
+    output[32..].copy_from_slice(&TC::hash(&[domain, &[1u8], input].concat()));
+    output
+}
@@ -0,0 +1,506 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Defines hashing and commitments with the Poseidon permutation over the scalar field of the
+//! Ristretto group, which are cheap to express in arithmetic circuits over that field (such as
+//! the rank-1 constraint systems of Bulletproofs)
+
+use core::marker::PhantomData;
+
+use curve25519_dalek::scalar::Scalar;
+
+use super::traits::CommitmentScheme;
+use crate::configuration::{Configuration, ConfigurationWrapper};
+use crate::{AkdValue, AzksValue, AzksValueWithEpoch, NodeLabel};
+
+#[cfg(feature = "nostd")]
+use alloc::vec;
+#[cfg(feature = "nostd")]
+use alloc::vec::Vec;
+
+/// The domain of the commitments to values
+const COMMITMENT_DOMAIN: u64 = 1;
+/// The domain of the hashes of leaves
+const LEAF_DOMAIN: u64 = 2;
+/// The domain of the hashes of parents from their children
+const PARENT_DOMAIN: u64 = 3;
+
+/// The number of bytes packed into each field element, which is the largest number of bytes
+/// whose values are all smaller than the order of the field
+const BYTES_PER_ELEMENT: usize = 31;
+
+/// The Poseidon permutation of width 3 over the scalar field of the Ristretto group (of prime
+/// order `2^252 + 27742317777372353535851937790883648493`), with the S-box `x^5`, 8 full rounds,
+/// and 57 partial rounds, for 128 bits of security. The round constants are generated with the
+/// Grain LFSR of the reference implementation, and the MDS matrix is the Cauchy matrix
+/// `M[i][j] = 1 / (i + j + 3)`.
+///
+/// [Poseidon::hash] is a sponge over the permutation, with a rate of 2 field elements.
+#[derive(Clone)]
+pub struct Poseidon;
+
+impl Poseidon {
+    /// The number of field elements in the state of the permutation
+    pub const WIDTH: usize = 3;
+    /// The number of full rounds, half of which come before the partial rounds
+    pub const FULL_ROUNDS: usize = 8;
+    /// The number of partial rounds
+    pub const PARTIAL_ROUNDS: usize = 57;
+
+    /// Applies the permutation to a state
+    pub fn permute(state: &mut [Scalar; 3]) {
+        let mds = mds_matrix();
+        let half_full_rounds = Self::FULL_ROUNDS / 2;
+        for round in 0..Self::FULL_ROUNDS + Self::PARTIAL_ROUNDS {
+            for (i, element) in state.iter_mut().enumerate() {
+                *element += round_constant(round * Self::WIDTH + i);
+            }
+            if round < half_full_rounds || round >= half_full_rounds + Self::PARTIAL_ROUNDS {
+                state
+                    .iter_mut()
+                    .for_each(|element| *element = sbox(*element));
+            } else {
+                state[0] = sbox(state[0]);
+            }
+            let mixed = core::array::from_fn(|i| {
+                (0..Self::WIDTH).fold(Scalar::ZERO, |sum, j| sum + mds[i][j] * state[j])
+            });
+            *state = mixed;
+        }
+    }
+
+    /// Hashes field elements in a domain, with a sponge whose capacity element is initialized
+    /// to the domain. The inputs are padded with a one and then zeros to a multiple of the rate.
+    pub fn hash(domain: u64, inputs: &[Scalar]) -> Scalar {
+        let mut state = [Scalar::from(domain), Scalar::ZERO, Scalar::ZERO];
+        let mut padded = inputs.to_vec();
+        padded.push(Scalar::ONE);
+        if padded.len() % 2 == 1 {
+            padded.push(Scalar::ZERO);
+        }
+        for chunk in padded.chunks(2) {
+            state[1] += chunk[0];
+            state[2] += chunk[1];
+            Self::permute(&mut state);
+        }
+        state[1]
+    }
+
+    /// Encodes bytes as field elements: their length, followed by the bytes in little-endian
+    /// chunks of 31 bytes, each of which is smaller than the order of the field
+    pub fn bytes_to_scalars(bytes: &[u8]) -> Vec<Scalar> {
+        let mut scalars = Vec::with_capacity(1 + bytes.len().div_ceil(BYTES_PER_ELEMENT));
+        scalars.push(Scalar::from(bytes.len() as u64));
+        for chunk in bytes.chunks(BYTES_PER_ELEMENT) {
+            let mut element = [0u8; 32];
+            element[..chunk.len()].copy_from_slice(chunk);
+            scalars.push(Scalar::from_bytes_mod_order(element));
+        }
+        scalars
+    }
+
+    /// The field element of a value stored in the tree, which is its canonical encoding if
+    /// it was produced by Poseidon, and is otherwise reduced modulo the order of the field
+    pub fn value_to_scalar(value: &[u8; 32]) -> Scalar {
+        Scalar::from_bytes_mod_order(*value)
+    }
+}
+
+/// Commitments with the Poseidon sponge: `commitment = Poseidon::hash(1, nonce || value)`,
+/// where the nonce and the value are encoded as field elements with
+/// [Poseidon::bytes_to_scalars]. The commitment is stored in the tree as the canonical
+/// encoding of the field element.
+///
+/// The commitments are hiding and binding when the permutation is modeled as a random
+/// permutation, as for hash-based commitments, and an opening can be proven in a circuit over
+/// the scalar field of the Ristretto group with a few hundred constraints per 62 bytes of the
+/// value. The commitments do not rely on the hash function of the configuration.
+#[derive(Clone)]
+pub struct PoseidonCommitments;
+
+impl CommitmentScheme for PoseidonCommitments {
+    fn commit<TC: Configuration>(value: &AkdValue, nonce: &[u8]) -> AzksValue {
+        let mut inputs = Poseidon::bytes_to_scalars(nonce);
+        inputs.extend(Poseidon::bytes_to_scalars(value));
+        AzksValue(Poseidon::hash(COMMITMENT_DOMAIN, &inputs).to_bytes())
+    }
+}
+
+/// A configuration which commits to values with [PoseidonCommitments], and which hashes the
+/// leaves and the parents of the tree with [Poseidon], and is otherwise identical to `TC`
+/// (including the derivation of node labels with the VRF, and of the nonces of the
+/// commitments). Every hash which an append-only proof is verified with is then a Poseidon
+/// hash, so that the verification of audits can be expressed as a circuit over the scalar field
+/// of the Ristretto group, towards succinct proofs of audits.
+///
+/// The leaves of the tree hash the commitment and epoch of a value as
+/// `Poseidon::hash(2, [commitment, epoch])`, and parents hash their children as
+/// `Poseidon::hash(3, [left value, left label, right value, right label])`, where the labels are
+/// encoded with [Poseidon::bytes_to_scalars]. The tree therefore differs from that of `TC`, and
+/// a directory cannot switch between them once values have been published.
+///
+/// ```
+/// use akd_core::configuration::PoseidonConfiguration;
+///
+/// # #[cfg(feature = "experimental")]
+/// type Config = PoseidonConfiguration<akd_core::ExperimentalConfiguration<akd_core::ExampleLabel>>;
+/// ```
+#[derive(Clone)]
+pub struct PoseidonConfiguration<TC>(PhantomData<TC>);
+
+impl<TC: Configuration> ConfigurationWrapper for PoseidonConfiguration<TC> {
+    type Inner = TC;
+
+    fn hash_leaf_with_value(value: &AkdValue, epoch: u64, nonce: &[u8]) -> AzksValueWithEpoch {
+        <Self as Configuration>::hash_leaf_with_commitment(
+            <Self as Configuration>::commit_value(value, nonce),
+            epoch,
+        )
+    }
+
+    fn hash_leaf_with_commitment(commitment: AzksValue, epoch: u64) -> AzksValueWithEpoch {
+        let inputs = [
+            Poseidon::value_to_scalar(&commitment.0),
+            Scalar::from(epoch),
+        ];
+        AzksValueWithEpoch(Poseidon::hash(LEAF_DOMAIN, &inputs).to_bytes())
+    }
+
+    fn commit_value(value: &AkdValue, nonce: &[u8]) -> AzksValue {
+        PoseidonCommitments::commit::<TC>(value, nonce)
+    }
+
+    fn compute_fresh_azks_value(
+        commitment_key: &[u8],
+        label: &NodeLabel,
+        version: u64,
+        value: &AkdValue,
+    ) -> AzksValue {
+        let nonce =
+            <Self as Configuration>::get_commitment_nonce(commitment_key, label, version, value);
+        <Self as Configuration>::commit_value(value, &nonce)
+    }
+
+    fn compute_parent_hash_from_children(
+        left_val: &AzksValue,
+        left_label: &[u8],
+        right_val: &AzksValue,
+        right_label: &[u8],
+    ) -> AzksValue {
+        let mut inputs = vec![Poseidon::value_to_scalar(&left_val.0)];
+        inputs.extend(Poseidon::bytes_to_scalars(left_label));
+        inputs.push(Poseidon::value_to_scalar(&right_val.0));
+        inputs.extend(Poseidon::bytes_to_scalars(right_label));
+        AzksValue(Poseidon::hash(PARENT_DOMAIN, &inputs).to_bytes())
+    }
+}
+
+/// The S-box of the permutation, `x^5`
+fn sbox(x: Scalar) -> Scalar {
+    let square = x * x;
+    square * square * x
+}
+
+fn round_constant(index: usize) -> Scalar {
+    decode_scalar(ROUND_CONSTANTS[index])
+}
+
+fn mds_matrix() -> [[Scalar; 3]; 3] {
+    let inverses = MDS_INVERSES.map(decode_scalar);
+    core::array::from_fn(|i| core::array::from_fn(|j| inverses[i + j]))
+}
+
+fn decode_scalar(hex_bytes: &str) -> Scalar {
+    let mut bytes = [0u8; 32];
+    // The constants are valid hex encodings of canonical field elements, as the tests check
+    let _ = hex::decode_to_slice(hex_bytes, &mut bytes);
+    Scalar::from_bytes_mod_order(bytes)
+}
+
+/// The inverses of 3 to 7, from which the MDS matrix is built, as little-endian encodings
+const MDS_INVERSES: [&str; 5] = [
+    "498d4e9311420c903913a56c94a694b8aaaaaaaaaaaaaaaaaaaaaaaaaaaaaa0a",
+    "f25eb8c553ca0dc2a0b539fa663ba70f0000000000000000000000000000000c",
+    "965d64df9ee036abf7b864ed92cb5f3733333333333333333333333333333303",
+    "9b3022f895520ff40758ce8739d0b9665555555555555555555555555555550d",
+    "22d5909fba32273143cdfe848dda1f4c92244992244992244992244992244902",
+];
+
+/// The round constants, as little-endian encodings, generated with the Grain LFSR of the
+/// reference implementation (for a prime field of 253 bits, a width of 3, 8 full rounds, and
+/// 57 partial rounds)
+const ROUND_CONSTANTS: [&str; 195] = [
+    "313ef2d00348be64f86faeb1471931dfc659b1a9e408ea16bffd7dd3c8c9d302",
+    "f51cd1035e34be84610df207d4cc6f20cef9f17931cc6dbaa21a94c127cc5201",
+    "9dbcaa71e3b2b5f98e11b02e36c81fe6c480f9d723203a499fc165cc140bcd0f",
+    "e585714bb808f48db4095d6b4e0c554609d5f8e1f5033673bcc71841606c3403",
+    "ecbc139923be143bd3ce26c02db219732312f28a27f0e3079d6aae52345b7602",
+    "3f8f7207f2cfd1e50f46eb8ed8b7c8c17f414aeb023c930c8f562086ca591b0a",
+    "caacd4bd223f77aefe236b8c0437ef7d1e7e9dc4693101cd028866aa8cd6f600",
+    "c01b8a2a9df71916f494d98bb6e6fbadb3e2770a770a2383d1cd33a99dcd170c",
+    "c83db196e10547c322998da3a6fa368d3d13cab4ddfc09303d16e4702a93d308",
+    "a15c2a485d9eb2b9296b9898fbad52bb93373810c3ee1c8391c2c57ebaa87a05",
+    "dd1e00943d4b9c521a895509eca34afad0c45383901d1dcb2f87cbe4bc8a330b",
+    "1f3831f11cc54a554a8587ae652b08979fa4ceb8f91b8a101e71a0ddf334e50c",
+    "b58d157ec3f0a0a73eb9ded66140ed5199bd4a0fa143e9ac2c55f13daa647f04",
+    "1ec36c0330397041608789f1065afef1badbf7682bc67df3f67eee9d8f6daf0b",
+    "c57dc2ad792cccb423e484b562eec7ac5c1f33af9aa5632ae0c30dd200225e0b",
+    "891ceb6e1c128c34bb15d31c144d3ae05c8a645606f6e015ca64597a6c42a100",
+    "1bb7ed45f6a06a9c250628ec62db2e91a955bd38e4ec743ec66cd8c6a522de05",
+    "0064c623311943bcb5a2b4a1fe3cd55c5a19614410c593af392782a4f86b2800",
+    "11f15e6a6384b1bc5289314079bbe4cc1ad65ce62e73a547dc467a34268c0804",
+    "6780e99616021f63181a47ec6a41f8ddd90d3996a82eb70a905464625f97490d",
+    "efbd46a5226e4a3172d9c3c9abdf812e146090b6d6fe0d5c9f3f843643154d0b",
+    "fa19ceecf75fdcb97d1aa97300ddbfc6590e8787e900d8069093a3808f1c8305",
+    "af67d3761420918701b0c3e928df1e34468dfc523a1b32a38137456a14d15100",
+    "faf4580ef16244a9cd21e25e66021cae469b06dbbbcab88f48728893b113560e",
+    "a69bab3a61792132b5683b3e763e12ca22a61d5529e691ee9381a9e064ccb107",
+    "e57ef29458aee3267122b20ba257022aaf107a0b1a4c6a8756c7c58a4f44f70c",
+    "c504133718064d32bb66142827503b9dd052c3d2d393353887b2e3ebca46d60f",
+    "93d4243ed51fa0f5f439ea2d74ed3c9567257283e06b935de3c2c13111f7fc07",
+    "4b066c4209a49eb4bb96668de84470cc5bb2905454767abdcf6d06d98786ba05",
+    "376016617f3469dafccd1803709ca37811fe07185a5221c92df1e37934a5f00a",
+    "ad811a17205665c2dede6abd6384ec8aa997b81dea868ceb13ade2f2859f8200",
+    "e94aa6c2336e4d022e9726d7e74548d3930989d7921b29a2935ea6c78b72e208",
+    "5c397329e8bab92400e96fc7063de272547005e23a5df18715bc3fd36ef91c06",
+    "4a2e200be9f127ebde50188408381e139f923c06282115a8b05c312873014203",
+    "584f89294ba281f94ba04ebdf0b7743872523525a5b181db1dcce9699460f807",
+    "ab81de43fa1f55b4559c8087a6d5afac0743f5f84f0ca6de40b44d2bfaf7a20a",
+    "7167b8dd05fd7c1f8feb8c7f7ac687a268eb20a5932e46d82b36b48dbaa92301",
+    "d274e727c63f82523dec4c26f588e9367fc24d3113233110e72167de54c18a02",
+    "5aa1778330100f85d3f05ea50a794b4cf111de9a0e06d3c04c02c186a10c2f0d",
+    "ceaf7ceb15b0d889c17edaf11cc1fc4b03e7209e9a8f80bf3e7f22bb27f78907",
+    "3c2b0b52d00f05bc391ba1efb195f209a64faec7fc379d3644a4f38cf8fff006",
+    "271103cfb9e8fe0d6b723b7253ab5c5a22749ae4f9ad5e013c2c9e0bdea33e0c",
+    "518e88bde480c09ecc68ec269e87e8dbb927a9db53c709605d2c90a737486b00",
+    "2edb990ab5a90dd89e6d58c9444ce3c7c7ce072e4c587eb8bda9823440165103",
+    "e524e62ee30599bf46938f5cc68b793f833b1ca0bc960bbe2d633bba0902fc0d",
+    "aa6368341afc3369541243c617bd614824ddb3603ee339482c249efdfae4bd03",
+    "ba04e3def4c571a4ec22f5d39d50d0f18b213e4e65eece175aa3bb9c4ea94006",
+    "b8cc4e850c9b138d8292c1d929ca207a7bc795e374e6990a004dfe469503990b",
+    "23e6cac8735bed36f4219c98ad131361afd59199c80a2d17c4cbfa620954fe0d",
+    "8755a84191ff79dab6c05841866c097db32a53e51afd1f3f14dbaa4b0c17ff06",
+    "2d323fe2a3bd997d02288a7eb157c04d369d864faf325caf69badf4143d9ca01",
+    "d3e6ef0d15a551658d663bb89095ee5c0a79e80d41f37a3ba4b00d0449ff0f08",
+    "1b0c76f08590f4dc5ae45ba6ea144f2c4bd6b3f63791608dd6a4e95566dbbb0b",
+    "a3614eed0da37f0926dc40b3ac39e82c8b9bec492a529e583fdea54b4dedfa0d",
+    "1a698c85e890f05b60bebd029e160243ade89d8ff5e0d63f5d3541768b4aec0f",
+    "a1f1a593beb5ba3fc5d4d8822b3bea22c60e59c74d3686b888c66f6cf57aa804",
+    "512c0b0270974990d9320d1e89db50c69858e9111fd1cfc96c495ca643ab6d01",
+    "36e6be183587333722451924991aa0b97b401e5be711ccd842d0441e4d5e9306",
+    "61d94b2e9cab01d25013db79aab4a0fd4280ddff1d01b36417f50cd9a8149009",
+    "af70df7a15f1ab4393d8bd75c9206f531c4a0649c96ada9ed8522ccd8cc71303",
+    "188eb39f69f8195bc6e39fcb623324626eb4c5c68e490672ba801f15d4619201",
+    "a19fa3da69cb55b8b22734e9dfb635332495b550f4392dfeffe0f17d377cb402",
+    "176440f706a730a09b4858c246b7631912f4018ce3878cb8134e036e753d4f0e",
+    "25b8347f9c9d9fea2e3c8f2ab1d8b69e798d61ba0b07ebd95e24f03cddba720c",
+    "64b37e0d08058f01f42745efb180432d30ffb8fcd3a34263aa85be24a694d10c",
+    "f15f8913c2356cbd7e2b9c5f7f7881d614b6f880e4ebe9d871f5d0a8e2eb480f",
+    "7cf121da68ea9d0ac1e20ac42ccdb8c4cc4d7404f78cb054c900a8c3c0fdda0d",
+    "74036a46464c1dcdcf6c8936f11bc306e914f3f22ca49ab42bd93f84d59dc30f",
+    "47b11fdc8aa64886e9851671faae9f1437a15ee13fbb6c3adec9b94abcf8740c",
+    "38836071de3e6cda4d8a23d10e9577471c314befd0d872f753ace961672b1506",
+    "9cbf80b0875c15b8968bf5d8c0980c6d1dc7b16a33400ca6f0e37e0dc638290c",
+    "57cb250b6738656ab1be0cf1e0d3a2a39d82a87bf481009ee70f5b95efe12f04",
+    "b61772aafba81435ce9d7a434b79404414a79cbd2e96d300fc62f6cadb33da07",
+    "8722462283ae466060f3e60b37bba63460bf9086f73454ca83951ab14a7e2f00",
+    "a719a31207a76f60c7d66b00c3ad3f804722f2a687a4b63484545120ad436907",
+    "a013f1cdda3b7f34a35f31a4a7587f83e65b5dab80b42f0dbab57dd0737f2c08",
+    "e1c508f2442e8975a83bbff731c37c7c4e57ae2e29e5fefa8d3e7d65c6be180f",
+    "755c033c1eac1026115ec7e945ea863b4023e446d9ab1afdd29aa6999c5d8107",
+    "fb5e92d78f70afaa902a506d31df2baf1269ce9d9b62196c231d21e7c915c10e",
+    "873db93050408e5e37ba9a5fe4d1469a7bd3d035af0c8f897028e796cb64b10f",
+    "4e23a03e989db21c8af6205e4e42be446eee77c2d39c105a5bad274462f1eb08",
+    "74be1b011e4a6b7a91fa3e8bb4893e582bb73829a2fd0562acc3e4e8d8e0740c",
+    "9a10d56b35a18f0ed89bb8fec652cbd09ceffd1854d32541cf376d9ee3e00802",
+    "b2d5036f5ca5eb130984128ecb25cceeba5c18425f57fb0384343231af368103",
+    "ef1715afe835e4ea96ba3ddb61a953e7324744148e5b6d591b40ed6c2355790f",
+    "ecb171f4b02b87557440a9096295039761d3ff7b6d0ffe1ba049f861e4428401",
+    "b14de65b0c1fbbd71ce1521bc3cbf093ea6f59b2a6ac200e78ea4d0b5d2b970f",
+    "c034f1b4f2fc7433472969e75a8941ad80d2e5e93dce37e9501b1b64ff3ba500",
+    "9ac27c30575cad248e39e6a14c26d157d88e7f388103dd655fc37d221b8f1700",
+    "5be30cd10e84a713e8d017142cb1c87d0202d7f414e416ace806171ed739f80f",
+    "9d5accb9bf41eb0e5c13a0556f6f1b754eb1a0db83821e7059926932e2f4730f",
+    "fd2b4a7e81769c1a84829d2afe8ca0a08d8ce2e6c4d2473d87dd56691467c50e",
+    "c9ed2af8152fb15aae411f7b09020af40b0f1d269e896e056cd64b8aca3ccb0d",
+    "c963528e477a43db92899073e3f127daf16b94bee6258216a4dbbf4802050f08",
+    "b8c5dfd2a6c26d2ae6b90f9004ce9aa20d0c2732be2649ed18e245b96c2a0a0c",
+    "4c80fc7c7c94c5143d95dfe041023bbd045f48ae936ea91c837becc6aa848403",
+    "261a341fc1d85c16c27768277bf60d2169684bcdaa3f79fdc6c9f4af50f6c409",
+    "411b49b79d7bb1480c9817b39d1aad05921c0ab956f948a8cb8971ac3709c70c",
+    "9887910c4b0478c6b0a0f47e26a3daab82c2f85b533de67ea8575458f5897e03",
+    "399fb7db76e3be13a9d998fff6b9c907c55ce570a1a6290dbbbdaf088018d706",
+    "d6d344a1d7b23fb3d2fd3be9f8b8d13e1bf09a43eaaaa18ef17005dc4aed1602",
+    "3cbffeaebdeb5792c8b58ae49f07f8edf77e8a10ef504ad760f3fd83b2d05e09",
+    "3bcc9cdd14f62050ac9e18994bc9b2996f908db93f6da99b10ad254ee42e5201",
+    "b24cd12217194a48b857b59c880323477dcaf6249cea7eaefc6afc424d83760e",
+    "03427c2cbc22043380a41f35edaf8b4e1966b1ac7057e6c14f6989fd332d8404",
+    "fbee44110ea851c7431301f66ebaad67e6040e334228dc1538cf85939ca71f0b",
+    "2f8a460df7576a44cd6cf632b57ba8bfa45c2a7229a9734bb9eb17ac2c1a4c08",
+    "cbde7f52bb4c38c2d2bedd784efdf9d5425aa128c89b0effa52aab9c9a474b05",
+    "b9fb2928961619cfb183e2836a27aeea33ca7cf8fee09230887113f70c6d110d",
+    "4560ded09edb0ecdae5a5dda0d0d1d80dba05dfc44393635c4518ba74b311706",
+    "124ead3b9c45aa99567e1532f2958e23cc85335acae78a850ffb6a04136d5d05",
+    "5bcc575f14c805f1c1880a9a2f6252487a5add76fb57e9f3315167d2aac04a05",
+    "e3688b2485521fff05950c4db8d95b9d8864538529fa226b57495dbfb453de01",
+    "4695632837f77f931c41df56231a28fdf2dc5ace262596b3df0ff545b8bb2600",
+    "003de560fafcd1ff0acb23f95843ddaa1b646ebad2e91a4da6c10e8ddbd7ae06",
+    "2388b99c2c020d8366610afb51de20bd6d1fcb7105b00f03396b7d2b6fc06805",
+    "885b93128b8c30abd83bd507d34dfba44c0bb1c4e41d4ea65ac8433930c7fd05",
+    "3b43994452c54fd72baae153c39f368136b37a0705d63daaf00556be5596d805",
+    "7ab0042dc6635e928f110e07e206a2d06aa1d0cea8d1bdda92bc9e88fa7e8401",
+    "8803a1c067776fcf2a96d09ad22cfa5d204afef8540a007f35357d3516f5a705",
+    "c08f947f875080894740088fa179a4bf805fd40093df3dd7bbf5550cf9075f06",
+    "1ab9982bfcb1e89565d00f49745ed2f308036640c1b9cb7b6bcc4d68fcbc840a",
+    "ea4916767dd926d6b1803e1f642ca1bd2989e5e4d60c7f067916a3fd903d9106",
+    "b8c580f3b688afcd70aae17164f5ba465aeefb909abe49dfb88b18096a91970e",
+    "a24dd6303caa3f9091e622f6dd68e05f18b9660ec8b561190e618476bb682605",
+    "7787cbd94530abcfa1dc6322a55bb3e07e117d4e7336fb507e3bfe0b68bed900",
+    "4e2fba129e8d718948171c320bcbd1a7765df3e8c4cc70d89d306a58c76ac306",
+    "51f50a91c0611a79dd3bb2415e3e8b1af99dc04ed1f47082bb54bed5f6362a0c",
+    "051afc060ca429fef7da9545bb66b8316363b0dd029b216f7bb36d3ca4e67105",
+    "17fe8513430573e0893064aa473c4f4631c021d72e28b0aad97f448a5770e403",
+    "42f257dc49cf14b30eba075d5a2d41eb5568b02b16c9925048722ba61f00660e",
+    "87030ccab9905d09be8996949aa9d706db4fe9491a26189446017eec1a71c308",
+    "33bd23b71aeafeebe865249f22a9907f193950c3fe0cba26f0f272451c228d0e",
+    "9caaa4a35da5393665e319bd304dcd170174ee4d65c246ff5f38d525008cbb05",
+    "4a7982a428967128c1fb944c760a13a7f6727900670650e39f33bc7742ea8204",
+    "062ccb882791621fa5b3cfd22fdfb48689806d5ccc0033adf507f12c1b11570a",
+    "bbc86de027716d2efab42db7a3b53924b64be2327a3f5e2114a1ab2db3837f05",
+    "979afe7df80e447551662ca2ce03cb2e4ff35d9813ad54304ae349503b18ea03",
+    "94ab315564de59eedaff50d968adbb2c62e69fdeac85d0d54baea1c677a9020a",
+    "fe3645e1cde62f495c86651d9e21ecb12b8f4080078efcaacfb3a31958eed009",
+    "01ab587b2983f1dc07d76a938011edb3cf16b1444daea26aaa15a2bb0814da01",
+    "86eac3b0b3df622bf9de3b13210a88f0b8356d6318524b845dbf755d25bb720c",
+    "b764e33bb15d86892f0a6c66ebd5e2ffde44850b3ffcd0f000231306a2d67a05",
+    "0c81ac4d09bbb4667d04e8655162cbe742079fa77f6ea5b0263c9bcd6d42020a",
+    "9b83da2c8d0c3eba941e4314b0804fae6556074cccd62e9eb1a24e18a40bc403",
+    "d9fa4330bb61b92c843ad2cad3597e80b4fa7e901d53e0192fd0540743e1300c",
+    "8b3efac876475c757da171939cb69c1622c1fe0d52f1f7bf62f43a42709f680c",
+    "1729ca4a354165fcd595e1630e8e9508ccd5c4b59cd4bc4822bd733c7fd5dc02",
+    "b6e950398344170d65a9bd7d13f6e5416db81f34a1674ee5d4f3c58a09a0dd07",
+    "a1a6426676459e60ec72d93b761151b4a427ceb65db0fcf083729651516f8502",
+    "ca7bbc22b9b14a0497f616a454a432acce94227c08a0fc1b290e1fef24871402",
+    "48d57be4bb13c52c9629a7d69c9c0b8bb940c3abda76244668e15a64a2819e0d",
+    "76e0245e2b73d3ccd5f85f7bc0b08b1a69fedfaae6464c3e70a590978959d50e",
+    "fa82746a861f68173bcd51f06efb723927fb7c520660041030befe8374ef2908",
+    "48dc2cbb404b91038187bf1517e09985e8775addf905c653220c534f621e2d0e",
+    "c8ff911ec2b0a2f7472182167836b58aeee089babd84118b03411436797eaf09",
+    "6df10809165019d7a9ab50ff98ed9159b25a270a8442d5f647b202717c4a5306",
+    "3cbaa8ac01dc83ab5283020f79c36af8f6c94c51c0938c4b5ed86a428eda350e",
+    "b5645ad3649aa945f0567392f7b666c913eb622022b900f73e9fed766b6cf208",
+    "0830e42f0d3b1d61e0124b52b8939da09ad909715fbf48babf40f7da367f0508",
+    "d18a45e73802fbfe0ca95f21220a7b8a5076814e2a730c9b0b83541ce8a0fc02",
+    "e5bd26587cd3a8696bca94aa724961e2864c57e83d2bbd296f7bb2978bcd610f",
+    "f0345e7be99c3588b8b4ce09050884365924d210d36b2d31305644fa3ed22805",
+    "9adc6a342ecc6033e6986f7908491af13159fd627dbf46d17b95a5cec525ad02",
+    "50b02e2280361961bf43b781a0d0a14c604c78a12af452608041d5658182850e",
+    "27dff9dc4254274b6e4638defcb55e294b2e4f2a83dfa5614c3bf36d01714b00",
+    "81682e80389e38b3f6b5f76da812569dec2ab1bb38a751e5c2a6775168c6a909",
+    "4fc16fb7e936f79699575c457c36fe25f0fb6253dba431f72453eb02efb77101",
+    "e8b2048d996eb8e3ddedcf054630a4f30cfad4be11b1e5d7145a743d3c4b0501",
+    "6aed6fef378db7d0b7375c22326b4823485a194b101e79064654d42401612904",
+    "fe7045adb311a4ae502c138839f09e2c572a75ea2defd3bb2eb83b60bba52c08",
+    "3e4f49893cef740b01f6eab487727a68f8e7749a3163a938a7c39c3d4465b003",
+    "a3cf601fc7b45f3f3b6bf18a7942d01563efe3e4b8e3b98e72122b8e75386509",
+    "5527ad501c73ca8c2ce562bb6c165f9e180746f9922f45afc40de15a5bfdb409",
+    "ec5a35006de2b598f038ff35a69a8041a57389d21dcb95b744917eef9628df06",
+    "270a7c2951b593c367ad5ee0e1bede065b35a717e94b221c67c93f3362fb6b0b",
+    "abf32025430758cb41c597f65c0773d5cfeedb7153b99146eb58011fdd695d0e",
+    "a0f642dd553fd0af96e6e72b8dd3da98942f9323350e63f183bedfe8200ce408",
+    "f7cd8f143961f85d8ad20eadef29507b7b359f37fe3112580c9b4d16a75fea00",
+    "74eb115b7993f50d04a4cac00d5d07ded491fc78e9a12ff5b35b8d6aeca21707",
+    "86158a3808df14cb8315995c82b8489bd8c87ee77b15d5e5f8a397308963b602",
+    "9ee322ba77f751e908c52f7b8ac148cca82d8434593a35bb34fb31d54220c30b",
+    "3cf1880fa24cb2455fe8cb2819f6d13c307ce30562aa42f2f0cf3d32b6f0bc01",
+    "61cb50f66f3805a7e3abc0f832f020a43014805bf71968c568c3d01228752d06",
+    "e70dcb74e5ed9eb52794963aeb8c8853cf655030148929cec3c5da4d6178a800",
+    "ed744dc9ed6adbab03242ce669fd2ca39668e8222f3d4f05e204b5ae5459b203",
+    "5395ef60adf48e19732e47d97e9044041139bc26ed71f40cb63f2307c4670306",
+    "cc22594fb0794597138d965a656734d4ed6f689b998491c8c39199aff4746000",
+    "0be656b507f349312ecd5450dc5dc341c16a03092970fdf0818c13272ea1d00a",
+    "1ac9a9ad1903ae71c281b3de172bd863f062fbf877fbb650822f3bc077780807",
+    "70eaa5070aa0fd0912fa8b1afc678a5b877f8b5acfc0457c19976ea3cb612b03",
+    "36a3f2d24ad9444355bd7d84be09df771d8c0176f6ff3146669922dfc7998b0d",
+    "a68e487c0c685208317b7bcbb870d60c859c6761d3c4145073b6e437e9c7bc0e",
+    "fae1672649e2cfee2bf9a367ad9a3217858162d5744034e797a07fa35fd38a08",
+    "6a0c9087dc07272ea4574adae3236f878182ec3efe93708399d7d9497723b509",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The round constants, as generated by the Grain LFSR of the reference implementation
+    fn grain_round_constants() -> Vec<[u8; 32]> {
+        // field (prime), S-box (x^alpha), field size, width, full rounds, partial rounds
+        let parameters: [(u64, usize); 6] = [(1, 2), (0, 4), (253, 12), (3, 12), (8, 10), (57, 10)];
+        let mut lfsr = Vec::with_capacity(80);
+        for (value, bits) in parameters {
+            lfsr.extend((0..bits).rev().map(|bit| (value >> bit) & 1 == 1));
+        }
+        lfsr.extend(core::iter::repeat_n(true, 30));
+        let mut next_bit = move || {
+            let bit = lfsr[62] ^ lfsr[51] ^ lfsr[38] ^ lfsr[23] ^ lfsr[13] ^ lfsr[0];
+            lfsr.remove(0);
+            lfsr.push(bit);
+            bit
+        };
+        for _ in 0..160 {
+            next_bit();
+        }
+        let mut filtered_bit = move || loop {
+            let (first, second) = (next_bit(), next_bit());
+            if first {
+                return second;
+            }
+        };
+        let mut constants = vec![];
+        while constants.len() < ROUND_CONSTANTS.len() {
+            // 253 bits, most significant first, rejected unless smaller than the order
+            let mut bytes = [0u8; 32];
+            for bit in (0..253).rev() {
+                if filtered_bit() {
+                    bytes[bit / 8] |= 1 << (bit % 8);
+                }
+            }
+            if Option::<Scalar>::from(Scalar::from_canonical_bytes(bytes)).is_some() {
+                constants.push(bytes);
+            }
+        }
+        constants
+    }
+
+    #[test]
+    fn test_constants() {
+        let expected = grain_round_constants();
+        for (constant, expected) in ROUND_CONSTANTS.iter().zip(expected) {
+            assert_eq!(hex::encode(expected), *constant);
+        }
+        for (k, inverse) in (3u64..).zip(MDS_INVERSES) {
+            assert_eq!(Scalar::ONE, decode_scalar(inverse) * Scalar::from(k));
+        }
+    }
+
+    #[test]
+    fn test_hash_domains_and_padding() {
+        let inputs = [Scalar::from(1u64), Scalar::from(2u64)];
+        assert_ne!(Poseidon::hash(1, &inputs), Poseidon::hash(2, &inputs));
+        // the padding distinguishes trailing zeros
+        assert_ne!(
+            Poseidon::hash(1, &inputs),
+            Poseidon::hash(1, &[inputs[0], inputs[1], Scalar::ZERO])
+        );
+        assert_ne!(Poseidon::hash(1, &[]), Poseidon::hash(1, &[Scalar::ZERO]));
+        // the encoding of bytes includes their length
+        assert_ne!(
+            Poseidon::bytes_to_scalars(&[]),
+            Poseidon::bytes_to_scalars(&[0u8])
+        );
+        assert_eq!(3, Poseidon::bytes_to_scalars(&[0xffu8; 62]).len());
+    }
+}
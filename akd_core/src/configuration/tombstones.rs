@@ -0,0 +1,38 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Defines a configuration which attaches metadata to the tombstones of another configuration
+
+use core::marker::PhantomData;
+
+use crate::configuration::{Configuration, ConfigurationWrapper};
+use crate::{AkdValue, TombstoneMetadata};
+
+/// A configuration which encodes its tombstones with [TombstoneMetadata::to_value], and is
+/// otherwise identical to `TC`. Tombstones of `TC` which carry no metadata (e.g. those written
+/// before switching to this configuration) are still recognized as tombstones.
+///
+/// Since the tombstones begin with [TOMBSTONE_METADATA_PREFIX](crate::TOMBSTONE_METADATA_PREFIX),
+/// the directory rejects updates whose values could be mistaken for them.
+#[derive(Clone)]
+pub struct TombstoneMetadataConfiguration<TC>(PhantomData<TC>);
+
+impl<TC: Configuration> ConfigurationWrapper for TombstoneMetadataConfiguration<TC> {
+    type Inner = TC;
+
+    fn tombstone_value(metadata: &TombstoneMetadata) -> AkdValue {
+        metadata.to_value()
+    }
+
+    fn is_tombstone(value: &AkdValue) -> bool {
+        TC::is_tombstone(value) || TombstoneMetadata::from_value(value).is_some()
+    }
+
+    fn tombstone_metadata(value: &AkdValue) -> Option<TombstoneMetadata> {
+        TombstoneMetadata::from_value(value)
+    }
+}
@@ -8,7 +8,12 @@
 //! Defines the configuration trait for customizing the directory's cryptographic operations
 
 use crate::hash::Digest;
-use crate::{AkdLabel, AkdValue, AzksValue, AzksValueWithEpoch, NodeLabel, VersionFreshness};
+use crate::utils::i2osp_array;
+use crate::{
+    AkdLabel, AkdValue, AzksValue, AzksValueWithEpoch, NodeLabel, TombstoneMetadata,
+    VersionFreshness,
+};
+use alloc::borrow::Cow;
 
 #[cfg(feature = "nostd")]
 use alloc::vec::Vec;
@@ -59,6 +64,13 @@ pub trait Configuration: Clone + Send + Sync + 'static {
         value: &AkdValue,
     ) -> Digest;
 
+    /// Commits to a value with the nonce of the commitment (as produced by
+    /// [Configuration::get_commitment_nonce]), as in [CommitmentScheme::commit]. By default,
+    /// this is the hash-based commitment of [HashCommitments].
+    fn commit_value(value: &AkdValue, nonce: &[u8]) -> AzksValue {
+        HashCommitments::commit::<Self>(value, nonce)
+    }
+
     /// Used by the server to produce a commitment for an AkdLabel, version, and AkdValue
     fn compute_fresh_azks_value(
         commitment_key: &[u8],
@@ -74,6 +86,7 @@ pub trait Configuration: Clone + Send + Sync + 'static {
     /// - I2OSP(len(label) as u64, label)
     /// - A single byte encoded as 0u8 if "stale", 1u8 if "fresh"
     /// - A u64 representing the version
+    ///
     /// These are all interpreted as a single byte array and hashed together, with the output
     /// of the hash returned.
     fn get_hash_from_label_input(
@@ -102,6 +115,148 @@ pub trait Configuration: Clone + Send + Sync + 'static {
 
     /// Returns the representation of the empty label
     fn empty_label() -> NodeLabel;
+
+    /// The number of bits of the VRF output of a label which form its [NodeLabel], and so the
+    /// depth of the leaves of the tree. By default, this is the full 256 bits.
+    ///
+    /// Shorter node labels make for shallower trees, and so for smaller proofs, but two labels
+    /// (or versions of a label) collide with a probability of `2^-bits`, so that among `n`
+    /// entries a collision occurs with a probability of about `n^2 / 2^(bits + 1)`. The
+    /// directory refuses to publish an update whose node label collides with another. Clients
+    /// verify node labels with the same number of bits, which must be at least
+    /// [MIN_NODE_LABEL_LEN](crate::configuration::MIN_NODE_LABEL_LEN) and at most 256.
+    fn node_label_len() -> u32 {
+        256
+    }
+
+    /// Canonicalizes a label before it is used, so that different encodings of the same
+    /// identity (e.g. differing in case, in Unicode normalization form, or in the formatting
+    /// of a phone number) refer to the same entry of the directory. The directory normalizes
+    /// the labels of every publish and query, and the VRF is always evaluated on the
+    /// normalized label, so that clients can verify proofs for the label they requested.
+    ///
+    /// The normalization must be idempotent. By default, labels are used as given.
+    fn normalize_label(label: &AkdLabel) -> Cow<'_, AkdLabel> {
+        Cow::Borrowed(label)
+    }
+
+    /// The largest marker version which is at most `version` (for `version >= 1`), as in
+    /// [MarkerStrategy::marker_version]. By default, the marker versions are the powers of two.
+    fn marker_version(version: u64) -> u64 {
+        PowerOfTwoMarkers::marker_version(version)
+    }
+
+    /// The smallest marker version which is greater than `version`, as in
+    /// [MarkerStrategy::next_marker_version]. By default, the marker versions are the powers
+    /// of two.
+    fn next_marker_version(version: u64) -> u64 {
+        PowerOfTwoMarkers::next_marker_version(version)
+    }
+
+    /// The value which replaces a value in storage when it is tombstoned (e.g. by data
+    /// retention policies), given the metadata of the tombstone. By default, this is
+    /// [TOMBSTONE](crate::TOMBSTONE), which discards the metadata.
+    fn tombstone_value(_metadata: &TombstoneMetadata) -> AkdValue {
+        AkdValue(crate::TOMBSTONE.to_vec())
+    }
+
+    /// Whether a value is a tombstone, as produced by [Configuration::tombstone_value]. By
+    /// default, [RedactionRecord](crate::RedactionRecord)s are also tombstones.
+    fn is_tombstone(value: &AkdValue) -> bool {
+        value.0 == crate::TOMBSTONE || crate::RedactionRecord::from_value(value).is_some()
+    }
+
+    /// The metadata carried by a tombstone, if any. Clients verify the metadata when a key
+    /// history is verified with tombstones allowed. By default, tombstones carry no metadata.
+    fn tombstone_metadata(_value: &AkdValue) -> Option<TombstoneMetadata> {
+        None
+    }
+}
+
+/// A strategy for choosing the marker versions of a label, which can be added to an existing
+/// configuration with [MarkerConfiguration](crate::configuration::MarkerConfiguration).
+///
+/// A lookup proves that the marker version at or below the looked-up version exists, and a
+/// history proves that no version after the latest one exists by proving the non-existence of
+/// every version up to the next marker version, and then of every later marker version up to
+/// the current epoch. Sparser markers therefore make the proofs of non-existence of future
+/// versions smaller for labels with long histories, at the cost of more non-existence proofs
+/// for the versions before the next marker.
+///
+/// The marker versions must start at 1 and be strictly increasing.
+pub trait MarkerStrategy: Clone + Send + Sync + 'static {
+    /// The largest marker version which is at most `version`, for `version >= 1`
+    fn marker_version(version: u64) -> u64;
+
+    /// The smallest marker version which is greater than `version`, or `u64::MAX` if there
+    /// is none
+    fn next_marker_version(version: u64) -> u64;
+}
+
+/// Marker versions at the powers of `BASE`, which must be at least 2
+#[derive(Clone)]
+pub struct ExponentialMarkers<const BASE: u64>;
+
+/// Marker versions at the powers of two, which is the default [MarkerStrategy]
+pub type PowerOfTwoMarkers = ExponentialMarkers<2>;
+
+impl<const BASE: u64> ExponentialMarkers<BASE> {
+    const VALID_BASE: () = assert!(
+        BASE >= 2,
+        "The base of the marker versions must be at least 2"
+    );
+}
+
+impl<const BASE: u64> MarkerStrategy for ExponentialMarkers<BASE> {
+    fn marker_version(version: u64) -> u64 {
+        let () = Self::VALID_BASE;
+        if BASE == 2 {
+            return 1 << (63 - version.max(1).leading_zeros());
+        }
+        let mut marker = 1u64;
+        while let Some(next) = marker.checked_mul(BASE).filter(|next| *next <= version) {
+            marker = next;
+        }
+        marker
+    }
+
+    fn next_marker_version(version: u64) -> u64 {
+        if version == 0 {
+            return 1;
+        }
+        Self::marker_version(version).saturating_mul(BASE)
+    }
+}
+
+/// A scheme for committing to the values of a directory, which can be added to an existing
+/// configuration with [CommitmentConfiguration](crate::configuration::CommitmentConfiguration).
+///
+/// The commitment must be hiding and binding, where the nonce is the opening of the commitment
+/// which the directory reveals to clients along with the value. Commitments are stored in the
+/// tree as [AzksValue]s, and so must be encoded in 32 bytes.
+pub trait CommitmentScheme: Clone + Send + Sync + 'static {
+    /// Commits to a value with the nonce of the commitment, using the hash function of `TC`
+    /// wherever the scheme relies on one
+    fn commit<TC: Configuration>(value: &AkdValue, nonce: &[u8]) -> AzksValue;
+}
+
+/// Hash-based commitments, which are the default [CommitmentScheme]:
+/// `commitment = H(i2osp_array(value), i2osp_array(nonce))`
+#[derive(Clone)]
+pub struct HashCommitments;
+
+impl CommitmentScheme for HashCommitments {
+    fn commit<TC: Configuration>(value: &AkdValue, nonce: &[u8]) -> AzksValue {
+        AzksValue(TC::hash(&[i2osp_array(value), i2osp_array(nonce)].concat()))
+    }
+}
+
+/// A strategy for canonicalizing labels, which can be added to an existing configuration
+/// with [NormalizedConfiguration](crate::configuration::NormalizedConfiguration)
+pub trait LabelNormalizer: Clone + Send + Sync + 'static {
+    /// Canonicalizes a label, as with [Configuration::normalize_label]. The normalization
+    /// must be idempotent.
+    fn normalize(label: &AkdLabel) -> Cow<'_, AkdLabel>;
 }
 
 /// For fixture generation / testing purposes only
@@ -0,0 +1,55 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Defines a configuration which truncates the node labels of another configuration
+
+use core::marker::PhantomData;
+
+use crate::configuration::{Configuration, ConfigurationWrapper};
+
+/// The smallest number of bits of a node label, as returned by
+/// [Configuration::node_label_len]
+pub const MIN_NODE_LABEL_LEN: u32 = 64;
+
+/// A configuration whose node labels are the first `BITS` bits of the VRF outputs of labels,
+/// and which is otherwise identical to `TC`. `BITS` must be between [MIN_NODE_LABEL_LEN] and
+/// 256. The tree differs from that of `TC` (unless `TC` truncates its node labels to the same
+/// length), so a directory cannot switch between them once values have been published.
+///
+/// For example, with 128-bit node labels, no path of the tree (and so of a lookup or history
+/// proof) is longer than 128 nodes, and collisions remain unlikely among billions of entries:
+///
+/// ```
+/// use akd_core::configuration::TruncatedLabelConfiguration;
+///
+/// # #[cfg(feature = "experimental")]
+/// type Config = TruncatedLabelConfiguration<
+///     akd_core::ExperimentalConfiguration<akd_core::ExampleLabel>,
+///     128,
+/// >;
+/// ```
+#[derive(Clone)]
+pub struct TruncatedLabelConfiguration<TC, const BITS: u32>(PhantomData<TC>);
+
+impl<TC, const BITS: u32> TruncatedLabelConfiguration<TC, BITS> {
+    const CHECK_LEN: () = assert!(
+        BITS >= MIN_NODE_LABEL_LEN && BITS <= 256,
+        "node labels must have between MIN_NODE_LABEL_LEN and 256 bits"
+    );
+}
+
+impl<TC: Configuration, const BITS: u32> ConfigurationWrapper
+    for TruncatedLabelConfiguration<TC, BITS>
+{
+    type Inner = TC;
+
+    fn node_label_len() -> u32 {
+        // Evaluated at compile time, so that an out-of-range length fails to build
+        let () = Self::CHECK_LEN;
+        BITS
+    }
+}
@@ -24,16 +24,6 @@ pub struct WhatsAppV1Configuration;
 unsafe impl Send for WhatsAppV1Configuration {}
 unsafe impl Sync for WhatsAppV1Configuration {}
 
-impl WhatsAppV1Configuration {
-    /// Used by the client to supply a commitment nonce and value to reconstruct the commitment, via:
-    /// commitment = H(i2osp_array(value), i2osp_array(nonce))
-    fn generate_commitment_from_nonce_client(value: &crate::AkdValue, nonce: &[u8]) -> AzksValue {
-        AzksValue(Self::hash(
-            &[i2osp_array(value), i2osp_array(nonce)].concat(),
-        ))
-    }
-}
-
 impl Configuration for WhatsAppV1Configuration {
     fn hash(item: &[u8]) -> crate::hash::Digest {
         ::blake3::hash(item).into()
@@ -58,7 +48,7 @@ impl Configuration for WhatsAppV1Configuration {
         epoch: u64,
         nonce: &[u8],
     ) -> AzksValueWithEpoch {
-        let commitment = Self::generate_commitment_from_nonce_client(value, nonce);
+        let commitment = Self::commit_value(value, nonce);
         Self::hash_leaf_with_commitment(commitment, epoch)
     }
 
@@ -105,9 +95,7 @@ impl Configuration for WhatsAppV1Configuration {
         value: &AkdValue,
     ) -> AzksValue {
         let nonce = Self::get_commitment_nonce(commitment_key, label, version, value);
-        AzksValue(Self::hash(
-            &[i2osp_array(value), i2osp_array(&nonce)].concat(),
-        ))
+        Self::commit_value(value, &nonce)
     }
 
     /// To convert a regular label (arbitrary string of bytes) into a [NodeLabel], we compute the
@@ -117,6 +105,7 @@ impl Configuration for WhatsAppV1Configuration {
     /// - I2OSP(len(label) as u64, label)
     /// - A single byte encoded as 0u8 if "stale", 1u8 if "fresh"
     /// - A u64 representing the version
+    ///
     /// These are all interpreted as a single byte array and hashed together, with the output
     /// of the hash returned.
     fn get_hash_from_label_input(
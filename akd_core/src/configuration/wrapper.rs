@@ -0,0 +1,267 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Defines the trait of configurations which change some of the operations of another
+//! configuration
+
+use crate::configuration::Configuration;
+use crate::hash::Digest;
+use crate::{
+    AkdLabel, AkdValue, AzksValue, AzksValueWithEpoch, NodeLabel, TombstoneMetadata,
+    VersionFreshness,
+};
+use alloc::borrow::Cow;
+
+#[cfg(feature = "nostd")]
+use alloc::vec::Vec;
+
+/// A configuration which is identical to [ConfigurationWrapper::Inner], except for the
+/// operations which it overrides. Every method forwards to the inner configuration by
+/// default, and every wrapper is a [Configuration], so that a wrapper only implements the
+/// operations which it changes.
+///
+/// Each method is the operation of [Configuration] with the same name.
+pub trait ConfigurationWrapper: Clone + Send + Sync + 'static {
+    /// The configuration which is wrapped
+    type Inner: Configuration;
+
+    /// See [Configuration::hash]
+    fn hash(item: &[u8]) -> Digest {
+        Self::Inner::hash(item)
+    }
+
+    /// See [Configuration::empty_root_value]
+    fn empty_root_value() -> AzksValue {
+        Self::Inner::empty_root_value()
+    }
+
+    /// See [Configuration::empty_node_hash]
+    fn empty_node_hash() -> AzksValue {
+        Self::Inner::empty_node_hash()
+    }
+
+    /// See [Configuration::hash_leaf_with_value]
+    fn hash_leaf_with_value(value: &AkdValue, epoch: u64, nonce: &[u8]) -> AzksValueWithEpoch {
+        Self::Inner::hash_leaf_with_value(value, epoch, nonce)
+    }
+
+    /// See [Configuration::hash_leaf_with_commitment]
+    fn hash_leaf_with_commitment(commitment: AzksValue, epoch: u64) -> AzksValueWithEpoch {
+        Self::Inner::hash_leaf_with_commitment(commitment, epoch)
+    }
+
+    /// See [Configuration::get_commitment_nonce]
+    fn get_commitment_nonce(
+        commitment_key: &[u8],
+        label: &NodeLabel,
+        version: u64,
+        value: &AkdValue,
+    ) -> Digest {
+        Self::Inner::get_commitment_nonce(commitment_key, label, version, value)
+    }
+
+    /// See [Configuration::commit_value]
+    fn commit_value(value: &AkdValue, nonce: &[u8]) -> AzksValue {
+        Self::Inner::commit_value(value, nonce)
+    }
+
+    /// See [Configuration::compute_fresh_azks_value]
+    fn compute_fresh_azks_value(
+        commitment_key: &[u8],
+        label: &NodeLabel,
+        version: u64,
+        value: &AkdValue,
+    ) -> AzksValue {
+        Self::Inner::compute_fresh_azks_value(commitment_key, label, version, value)
+    }
+
+    /// See [Configuration::get_hash_from_label_input]
+    fn get_hash_from_label_input(
+        label: &AkdLabel,
+        freshness: VersionFreshness,
+        version: u64,
+    ) -> Vec<u8> {
+        Self::Inner::get_hash_from_label_input(label, freshness, version)
+    }
+
+    /// See [Configuration::compute_parent_hash_from_children]
+    fn compute_parent_hash_from_children(
+        left_val: &AzksValue,
+        left_label: &[u8],
+        right_val: &AzksValue,
+        right_label: &[u8],
+    ) -> AzksValue {
+        Self::Inner::compute_parent_hash_from_children(left_val, left_label, right_val, right_label)
+    }
+
+    /// See [Configuration::compute_root_hash_from_val]
+    fn compute_root_hash_from_val(root_val: &AzksValue) -> Digest {
+        Self::Inner::compute_root_hash_from_val(root_val)
+    }
+
+    /// See [Configuration::stale_azks_value]
+    fn stale_azks_value() -> AzksValue {
+        Self::Inner::stale_azks_value()
+    }
+
+    /// See [Configuration::compute_node_label_value]
+    fn compute_node_label_value(bytes: &[u8]) -> Vec<u8> {
+        Self::Inner::compute_node_label_value(bytes)
+    }
+
+    /// See [Configuration::empty_label]
+    fn empty_label() -> NodeLabel {
+        Self::Inner::empty_label()
+    }
+
+    /// See [Configuration::node_label_len]
+    fn node_label_len() -> u32 {
+        Self::Inner::node_label_len()
+    }
+
+    /// See [Configuration::normalize_label]
+    fn normalize_label(label: &AkdLabel) -> Cow<'_, AkdLabel> {
+        Self::Inner::normalize_label(label)
+    }
+
+    /// See [Configuration::marker_version]
+    fn marker_version(version: u64) -> u64 {
+        Self::Inner::marker_version(version)
+    }
+
+    /// See [Configuration::next_marker_version]
+    fn next_marker_version(version: u64) -> u64 {
+        Self::Inner::next_marker_version(version)
+    }
+
+    /// See [Configuration::tombstone_value]
+    fn tombstone_value(metadata: &TombstoneMetadata) -> AkdValue {
+        Self::Inner::tombstone_value(metadata)
+    }
+
+    /// See [Configuration::is_tombstone]
+    fn is_tombstone(value: &AkdValue) -> bool {
+        Self::Inner::is_tombstone(value)
+    }
+
+    /// See [Configuration::tombstone_metadata]
+    fn tombstone_metadata(value: &AkdValue) -> Option<TombstoneMetadata> {
+        Self::Inner::tombstone_metadata(value)
+    }
+}
+
+// Every operation of [Configuration] with a default must be forwarded here (and added to
+// [ConfigurationWrapper]), or the wrappers would silently fall back to the default
+impl<W: ConfigurationWrapper> Configuration for W {
+    fn hash(item: &[u8]) -> Digest {
+        <W as ConfigurationWrapper>::hash(item)
+    }
+
+    fn empty_root_value() -> AzksValue {
+        <W as ConfigurationWrapper>::empty_root_value()
+    }
+
+    fn empty_node_hash() -> AzksValue {
+        <W as ConfigurationWrapper>::empty_node_hash()
+    }
+
+    fn hash_leaf_with_value(value: &AkdValue, epoch: u64, nonce: &[u8]) -> AzksValueWithEpoch {
+        <W as ConfigurationWrapper>::hash_leaf_with_value(value, epoch, nonce)
+    }
+
+    fn hash_leaf_with_commitment(commitment: AzksValue, epoch: u64) -> AzksValueWithEpoch {
+        <W as ConfigurationWrapper>::hash_leaf_with_commitment(commitment, epoch)
+    }
+
+    fn get_commitment_nonce(
+        commitment_key: &[u8],
+        label: &NodeLabel,
+        version: u64,
+        value: &AkdValue,
+    ) -> Digest {
+        <W as ConfigurationWrapper>::get_commitment_nonce(commitment_key, label, version, value)
+    }
+
+    fn commit_value(value: &AkdValue, nonce: &[u8]) -> AzksValue {
+        <W as ConfigurationWrapper>::commit_value(value, nonce)
+    }
+
+    fn compute_fresh_azks_value(
+        commitment_key: &[u8],
+        label: &NodeLabel,
+        version: u64,
+        value: &AkdValue,
+    ) -> AzksValue {
+        <W as ConfigurationWrapper>::compute_fresh_azks_value(commitment_key, label, version, value)
+    }
+
+    fn get_hash_from_label_input(
+        label: &AkdLabel,
+        freshness: VersionFreshness,
+        version: u64,
+    ) -> Vec<u8> {
+        <W as ConfigurationWrapper>::get_hash_from_label_input(label, freshness, version)
+    }
+
+    fn compute_parent_hash_from_children(
+        left_val: &AzksValue,
+        left_label: &[u8],
+        right_val: &AzksValue,
+        right_label: &[u8],
+    ) -> AzksValue {
+        <W as ConfigurationWrapper>::compute_parent_hash_from_children(
+            left_val,
+            left_label,
+            right_val,
+            right_label,
+        )
+    }
+
+    fn compute_root_hash_from_val(root_val: &AzksValue) -> Digest {
+        <W as ConfigurationWrapper>::compute_root_hash_from_val(root_val)
+    }
+
+    fn stale_azks_value() -> AzksValue {
+        <W as ConfigurationWrapper>::stale_azks_value()
+    }
+
+    fn compute_node_label_value(bytes: &[u8]) -> Vec<u8> {
+        <W as ConfigurationWrapper>::compute_node_label_value(bytes)
+    }
+
+    fn empty_label() -> NodeLabel {
+        <W as ConfigurationWrapper>::empty_label()
+    }
+
+    fn node_label_len() -> u32 {
+        <W as ConfigurationWrapper>::node_label_len()
+    }
+
+    fn normalize_label(label: &AkdLabel) -> Cow<'_, AkdLabel> {
+        <W as ConfigurationWrapper>::normalize_label(label)
+    }
+
+    fn marker_version(version: u64) -> u64 {
+        <W as ConfigurationWrapper>::marker_version(version)
+    }
+
+    fn next_marker_version(version: u64) -> u64 {
+        <W as ConfigurationWrapper>::next_marker_version(version)
+    }
+
+    fn tombstone_value(metadata: &TombstoneMetadata) -> AkdValue {
+        <W as ConfigurationWrapper>::tombstone_value(metadata)
+    }
+
+    fn is_tombstone(value: &AkdValue) -> bool {
+        <W as ConfigurationWrapper>::is_tombstone(value)
+    }
+
+    fn tombstone_metadata(value: &AkdValue) -> Option<TombstoneMetadata> {
+        <W as ConfigurationWrapper>::tombstone_metadata(value)
+    }
+}
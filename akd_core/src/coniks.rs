@@ -0,0 +1,434 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Conversion between AKD proofs and the structures of CONIKS, for deployments which are
+//! migrating from a CONIKS-based system and whose tooling consumes signed tree roots (STRs)
+//! and authentication paths.
+//!
+//! A [SignedTreeRoot] carries the root hash of an epoch with the directory's signature
+//! over it (see [crate::signing]). CONIKS links each STR to the previous one by its hash,
+//! which an AKD directory does not do itself, so the link is computed when the STRs are
+//! created, from the STR of the previous epoch, and checked by [SignedTreeRoot::verify].
+//!
+//! An [AuthenticationPath] is the path from the root of the tree down to the node at which
+//! a lookup index ends, with the siblings along the way (the "pruned tree"). For a proof of
+//! inclusion, the path ends at the leaf of the index, and for a proof of absence at the
+//! node where the path to the index leaves the tree, which is either empty or has a
+//! different index. These correspond to the existence proof of a [LookupProof] and to a
+//! [NonExistenceProof] respectively. Unlike in CONIKS, the node hashes of an AKD also
+//! commit to the labels of the nodes, so these are carried alongside the hashes, and
+//! CONIKS' tree nonce has no counterpart.
+//!
+//! Only the conversions which preserve the meaning of a proof are provided. In particular,
+//! a lookup proof also proves that its version is the latest one (with its marker and
+//! freshness proofs), which CONIKS has no structure for: an [AuthenticationPath] created
+//! from a [LookupProof] proves the inclusion of the value, but cannot be converted back
+//! into a lookup proof.
+
+use crate::configuration::Configuration;
+use crate::hash::{Digest, EMPTY_DIGEST};
+use crate::signing::{epoch_signature_message, EpochSignatureVerifier};
+use crate::verify::VerificationError;
+use crate::{
+    AkdLabel, AkdValue, AzksElement, AzksValue, Direction, LookupProof, MembershipProof, NodeLabel,
+    NonExistenceProof, NonMembershipProof, PrefixOrdering, SiblingProof, VersionFreshness,
+};
+
+#[cfg(feature = "nostd")]
+use alloc::format;
+#[cfg(feature = "nostd")]
+use alloc::vec::Vec;
+
+/// The domain separator for the hash of a [SignedTreeRoot]
+const STR_HASH_DOMAIN: &[u8] = b"AKD CONIKS signed tree root";
+
+/// The root hash of an epoch, signed by the directory and linked to the signed tree root
+/// of the previous epoch, in the style of a CONIKS STR
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct SignedTreeRoot {
+    /// The epoch of the root hash
+    pub epoch: u64,
+    /// The epoch of the previous signed tree root, or 0 for the first one
+    pub previous_epoch: u64,
+    /// The root hash of the epoch
+    pub tree_hash: Digest,
+    /// The hash of the previous signed tree root, or [EMPTY_DIGEST] for the first one
+    pub previous_str_hash: Digest,
+    /// The directory's signature over the epoch and root hash, as produced by an
+    /// [crate::signing::EpochSigner] over [epoch_signature_message]
+    pub signature: Vec<u8>,
+}
+
+impl SignedTreeRoot {
+    /// Creates the signed tree root of `epoch`, linked to the signed tree root of the
+    /// previous epoch, or to none if it is the first
+    pub fn new<TC: Configuration>(
+        epoch: u64,
+        root_hash: Digest,
+        signature: Vec<u8>,
+        previous: Option<&SignedTreeRoot>,
+    ) -> Self {
+        let (previous_epoch, previous_str_hash) = match previous {
+            Some(previous) => (previous.epoch, previous.hash::<TC>()),
+            None => (0, EMPTY_DIGEST),
+        };
+        Self {
+            epoch,
+            previous_epoch,
+            tree_hash: root_hash,
+            previous_str_hash,
+            signature,
+        }
+    }
+
+    /// The hash of the signed tree root, which the next one is linked to
+    pub fn hash<TC: Configuration>(&self) -> Digest {
+        TC::hash(
+            &[
+                STR_HASH_DOMAIN,
+                &self.epoch.to_be_bytes(),
+                &self.previous_epoch.to_be_bytes(),
+                &self.tree_hash,
+                &self.previous_str_hash,
+                &self.signature,
+            ]
+            .concat(),
+        )
+    }
+
+    /// Verifies that the directory signed the root hash, and that the signed tree root is
+    /// linked to the previous one (or, if there is none, that it is the first)
+    pub fn verify<TC: Configuration>(
+        &self,
+        verifier: &impl EpochSignatureVerifier,
+        previous: Option<&SignedTreeRoot>,
+    ) -> Result<(), VerificationError> {
+        let message = epoch_signature_message::<TC>(self.epoch, &self.tree_hash);
+        if !verifier.verify_epoch_message(&message, &self.signature) {
+            return Err(VerificationError::LookupProof(format!(
+                "Invalid signature for the signed tree root of epoch {}",
+                self.epoch
+            )));
+        }
+
+        let linked = match previous {
+            Some(previous) => {
+                previous.epoch < self.epoch
+                    && self.previous_epoch == previous.epoch
+                    && self.previous_str_hash == previous.hash::<TC>()
+            }
+            None => self.previous_epoch == 0 && self.previous_str_hash == EMPTY_DIGEST,
+        };
+        if !linked {
+            return Err(VerificationError::LookupProof(format!(
+                "The signed tree root of epoch {} is not linked to the previous one (epoch {})",
+                self.epoch, self.previous_epoch
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Whether an [AuthenticationPath] proves the inclusion or the absence of its index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum ProofType {
+    /// The path ends at a node which is empty, or whose index differs from the lookup index
+    ProofOfAbsence,
+    /// The path ends at the leaf of the lookup index
+    ProofOfInclusion,
+}
+
+/// The opening of the commitment of a leaf to its value
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct Commitment {
+    /// The nonce of the commitment (the salt, in CONIKS)
+    pub salt: Vec<u8>,
+    /// The epoch in which the value was committed to, which the leaf hash also commits to
+    pub epoch: u64,
+}
+
+/// The node which an [AuthenticationPath] ends at
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct ProofNode {
+    /// The depth of the node in the tree, which is the length of its label
+    pub level: u32,
+    /// The label of the node
+    pub index: NodeLabel,
+    /// The hash of the node
+    pub hash: Digest,
+    /// Whether the node is absent from the tree
+    pub is_empty: bool,
+    /// The value of the leaf, for a proof of inclusion which discloses it
+    pub value: Option<AkdValue>,
+    /// The opening of the commitment to the value, if the value is disclosed
+    pub commitment: Option<Commitment>,
+}
+
+/// The path from the root of the tree to the node at which a lookup index ends, in the
+/// style of a CONIKS authentication path
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct AuthenticationPath {
+    /// The index which was looked up, which is the output of the VRF of the label
+    pub lookup_index: NodeLabel,
+    /// The VRF proof that the lookup index is derived from the label
+    pub vrf_proof: Vec<u8>,
+    /// The siblings of the nodes along the path, from the root down to the sibling of
+    /// the [AuthenticationPath::leaf]
+    pub pruned_tree: Vec<AzksElement>,
+    /// The labels of the parents of the nodes along the path, in the same order as
+    /// [AuthenticationPath::pruned_tree], starting with the label of the root
+    pub path_labels: Vec<NodeLabel>,
+    /// The node at which the path ends
+    pub leaf: ProofNode,
+}
+
+impl AuthenticationPath {
+    /// Converts the existence proof of a [LookupProof] into a proof of inclusion, which
+    /// discloses the value and the opening of its commitment
+    pub fn from_lookup_proof(proof: &LookupProof) -> Self {
+        let mut path =
+            Self::from_membership_proof(&proof.existence_proof, proof.existence_vrf_proof.clone());
+        path.leaf.value = Some(proof.value.clone());
+        path.leaf.commitment = Some(Commitment {
+            salt: proof.commitment_nonce.clone(),
+            epoch: proof.epoch,
+        });
+        path
+    }
+
+    /// Converts a [MembershipProof] of the label with the given VRF proof into a proof of
+    /// inclusion, which does not disclose the value
+    pub fn from_membership_proof(proof: &MembershipProof, vrf_proof: Vec<u8>) -> Self {
+        Self {
+            lookup_index: proof.label,
+            vrf_proof,
+            pruned_tree: proof
+                .sibling_proofs
+                .iter()
+                .map(|sibling_proof| sibling_proof.siblings[0])
+                .collect(),
+            path_labels: proof
+                .sibling_proofs
+                .iter()
+                .map(|sibling_proof| sibling_proof.label)
+                .collect(),
+            leaf: ProofNode {
+                level: proof.label.get_len(),
+                index: proof.label,
+                hash: proof.hash_val.0,
+                is_empty: false,
+                value: None,
+                commitment: None,
+            },
+        }
+    }
+
+    /// Converts a [NonExistenceProof] into a proof of absence, which ends at the child of
+    /// the longest prefix of the lookup index in the direction of the index
+    pub fn from_non_existence_proof<TC: Configuration>(
+        proof: &NonExistenceProof,
+    ) -> Result<Self, VerificationError> {
+        let non_membership_proof = &proof.non_membership_proof;
+        let lookup_index = non_membership_proof.label;
+        let direction = direction_of(non_membership_proof.longest_prefix, lookup_index)
+            .ok_or_else(|| {
+                VerificationError::NonMembershipProof(format!(
+                    "The longest prefix {:?} is not a prefix of the label {:?}",
+                    non_membership_proof.longest_prefix, lookup_index
+                ))
+            })?;
+        let child = non_membership_proof.longest_prefix_children[direction as usize];
+        let sibling = non_membership_proof.longest_prefix_children[1 - direction as usize];
+
+        let mut path = Self::from_membership_proof(
+            &non_membership_proof.longest_prefix_membership_proof,
+            proof.vrf_proof.clone(),
+        );
+        path.lookup_index = lookup_index;
+        path.pruned_tree.push(sibling);
+        path.path_labels.push(non_membership_proof.longest_prefix);
+        path.leaf = ProofNode {
+            level: child.label.get_len(),
+            index: child.label,
+            hash: child.value.0,
+            is_empty: child.label == TC::empty_label(),
+            value: None,
+            commitment: None,
+        };
+        Ok(path)
+    }
+
+    /// Whether the path proves the inclusion or the absence of its index
+    pub fn proof_type(&self) -> ProofType {
+        if !self.leaf.is_empty && self.leaf.index == self.lookup_index {
+            ProofType::ProofOfInclusion
+        } else {
+            ProofType::ProofOfAbsence
+        }
+    }
+
+    /// Converts a proof of inclusion back into the [MembershipProof] of its leaf
+    pub fn into_membership_proof(self) -> Result<MembershipProof, VerificationError> {
+        if self.proof_type() != ProofType::ProofOfInclusion {
+            return Err(VerificationError::MembershipProof(format!(
+                "The authentication path of {:?} is not a proof of inclusion",
+                self.lookup_index
+            )));
+        }
+        Ok(MembershipProof {
+            label: self.leaf.index,
+            hash_val: AzksValue(self.leaf.hash),
+            sibling_proofs: self.sibling_proofs(self.pruned_tree.len())?,
+        })
+    }
+
+    /// Converts a proof of absence back into the [NonExistenceProof] it was created from
+    pub fn into_non_existence_proof<TC: Configuration>(
+        self,
+    ) -> Result<NonExistenceProof, VerificationError> {
+        let malformed = || {
+            VerificationError::NonMembershipProof(format!(
+                "The authentication path of {:?} is not a proof of absence",
+                self.lookup_index
+            ))
+        };
+        let depth = self.pruned_tree.len();
+        if self.proof_type() != ProofType::ProofOfAbsence || depth == 0 {
+            return Err(malformed());
+        }
+        let longest_prefix = self.path_labels[depth - 1];
+        let direction = direction_of(longest_prefix, self.lookup_index).ok_or_else(malformed)?;
+
+        let mut longest_prefix_children = [self.pruned_tree[depth - 1]; 2];
+        longest_prefix_children[direction as usize] = AzksElement {
+            label: self.leaf.index,
+            value: AzksValue(self.leaf.hash),
+        };
+        let longest_prefix_hash = TC::compute_parent_hash_from_children(
+            &longest_prefix_children[0].value,
+            &longest_prefix_children[0].label.value::<TC>(),
+            &longest_prefix_children[1].value,
+            &longest_prefix_children[1].label.value::<TC>(),
+        );
+
+        Ok(NonExistenceProof {
+            non_membership_proof: NonMembershipProof {
+                label: self.lookup_index,
+                longest_prefix,
+                longest_prefix_children,
+                longest_prefix_membership_proof: MembershipProof {
+                    label: longest_prefix,
+                    hash_val: longest_prefix_hash,
+                    sibling_proofs: self.sibling_proofs(depth - 1)?,
+                },
+            },
+            vrf_proof: self.vrf_proof,
+        })
+    }
+
+    /// Verifies the authentication path with respect to the root hash: that its lookup
+    /// index is the VRF output of `version` of the label, that the path leads from the root
+    /// to its leaf, and, if the leaf discloses its value, that the leaf commits to it
+    pub fn verify<TC: Configuration>(
+        &self,
+        vrf_public_key: &[u8],
+        root_hash: Digest,
+        akd_label: &AkdLabel,
+        version: u64,
+    ) -> Result<(), VerificationError> {
+        crate::verify::base::verify_label::<TC>(
+            vrf_public_key,
+            akd_label,
+            VersionFreshness::Fresh,
+            version,
+            &self.vrf_proof,
+            self.lookup_index,
+        )?;
+
+        match self.proof_type() {
+            ProofType::ProofOfInclusion => {
+                if let (Some(value), Some(commitment)) = (&self.leaf.value, &self.leaf.commitment) {
+                    let hash =
+                        TC::hash_leaf_with_value(value, commitment.epoch, &commitment.salt).0;
+                    if hash != self.leaf.hash {
+                        return Err(VerificationError::MembershipProof(
+                            "The leaf does not commit to the disclosed value".into(),
+                        ));
+                    }
+                }
+                crate::verify::base::verify_membership::<TC>(
+                    root_hash,
+                    &self.clone().into_membership_proof()?,
+                )
+            }
+            ProofType::ProofOfAbsence => crate::verify::base::verify_nonmembership::<TC>(
+                root_hash,
+                &self
+                    .clone()
+                    .into_non_existence_proof::<TC>()?
+                    .non_membership_proof,
+            ),
+        }
+    }
+
+    /// The [SiblingProof]s of the first `depth` nodes along the path, from the root down
+    fn sibling_proofs(&self, depth: usize) -> Result<Vec<SiblingProof>, VerificationError> {
+        if self.path_labels.len() != self.pruned_tree.len() || depth > self.pruned_tree.len() {
+            return Err(VerificationError::MembershipProof(format!(
+                "Malformed authentication path: {} siblings with {} labels",
+                self.pruned_tree.len(),
+                self.path_labels.len()
+            )));
+        }
+        self.pruned_tree[..depth]
+            .iter()
+            .zip(self.path_labels.iter())
+            .map(|(sibling, &label)| {
+                let direction = direction_of(label, self.lookup_index).ok_or_else(|| {
+                    VerificationError::MembershipProof(format!(
+                        "The label {label:?} along the path is not a prefix of the index {:?}",
+                        self.lookup_index
+                    ))
+                })?;
+                Ok(SiblingProof {
+                    label,
+                    siblings: [*sibling],
+                    direction,
+                })
+            })
+            .collect()
+    }
+}
+
+/// The direction from `parent` towards `index`, if `parent` is a proper prefix of it
+fn direction_of(parent: NodeLabel, index: NodeLabel) -> Option<Direction> {
+    match parent.get_prefix_ordering(index) {
+        PrefixOrdering::WithZero => Some(Direction::Left),
+        PrefixOrdering::WithOne => Some(Direction::Right),
+        PrefixOrdering::Invalid => None,
+    }
+}
@@ -28,6 +28,8 @@ mod traits;
 pub use crate::ecvrf::ecvrf_impl::{
     Output, Proof, VRFExpandedPrivateKey, VRFPrivateKey, VRFPublicKey,
 };
+#[cfg(feature = "dyn_traits")]
+pub use crate::ecvrf::traits::DynVRFKeyStorage;
 pub use crate::ecvrf::traits::VRFKeyStorage;
 #[cfg(feature = "nostd")]
 use alloc::boxed::Box;
@@ -65,6 +67,9 @@ impl core::fmt::Display for VrfError {
     }
 }
 
+#[cfg(not(feature = "nostd"))]
+impl std::error::Error for VrfError {}
+
 /// This is a version of VRFKeyStorage for testing purposes, which uses the example from the VRF crate.
 ///
 /// const KEY_MATERIAL: &str = "c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721";
@@ -192,7 +192,7 @@ fn test_expand_secret_key() {
     for tv in TESTVECTORS.iter() {
         let sk = from_string!(VRFPrivateKey, tv.SK);
         let esk = VRFExpandedPrivateKey::from(&sk);
-        let pk = VRFPublicKey::try_from(&sk).unwrap();
+        let pk = VRFPublicKey::from(&sk);
         assert_eq!(tv.PK, to_string!(pk));
         assert_eq!(tv.x, to_string!(esk.key));
     }
@@ -317,7 +317,7 @@ proptest! {
         keypair in uniform_keypair_strategy::<VRFPrivateKey, VRFPublicKey>()
     ) {
         let (pk, sk) = (&keypair.public_key, &keypair.private_key);
-        let pk_test = VRFPublicKey::try_from(sk).unwrap();
+        let pk_test = VRFPublicKey::from(sk);
         prop_assert_eq!(pk, &pk_test);
         let (input1, input2) = (hash1.hash.as_ref(), hash2.hash.as_ref());
         let proof1 = sk.prove(input1);
@@ -91,13 +91,13 @@ pub trait VRFKeyStorage: Clone + Sync + Send {
             freshness,
             version,
         );
-        NodeLabel::new(output.to_truncated_bytes(), 256)
+        NodeLabel::new(output.to_truncated_bytes(), 256).get_prefix(TC::node_label_len())
     }
 
     /// Returns the tree nodelabel that corresponds to a vrf proof.
-    async fn get_node_label_from_vrf_proof(&self, proof: Proof) -> NodeLabel {
+    async fn get_node_label_from_vrf_proof<TC: Configuration>(&self, proof: Proof) -> NodeLabel {
         let output: super::ecvrf_impl::Output = (&proof).into();
-        NodeLabel::new(output.to_truncated_bytes(), 256)
+        NodeLabel::new(output.to_truncated_bytes(), 256).get_prefix(TC::node_label_len())
     }
 
     /// Retrieve the proof for a specific label
@@ -120,7 +120,8 @@ pub trait VRFKeyStorage: Clone + Sync + Send {
         freshness: VersionFreshness,
         version: u64,
     ) -> Proof {
-        let hashed_label = TC::get_hash_from_label_input(label, freshness, version);
+        let hashed_label =
+            TC::get_hash_from_label_input(&TC::normalize_label(label), freshness, version);
         key.prove(&hashed_label)
     }
 
@@ -132,7 +133,8 @@ pub trait VRFKeyStorage: Clone + Sync + Send {
         freshness: VersionFreshness,
         version: u64,
     ) -> Output {
-        let hashed_label = TC::get_hash_from_label_input(label, freshness, version);
+        let hashed_label =
+            TC::get_hash_from_label_input(&TC::normalize_label(label), freshness, version);
         expanded_private_key.evaluate(pk, &hashed_label)
     }
 
@@ -212,3 +214,53 @@ pub trait VRFKeyStorage: Clone + Sync + Send {
         }
     }
 }
+
+/// An object-safe form of [VRFKeyStorage], so that the VRF key storage of a directory can be
+/// chosen at runtime (e.g. from a configuration file) and used as an
+/// `Arc<dyn DynVRFKeyStorage>`, which implements [VRFKeyStorage]. Every [VRFKeyStorage]
+/// implements this trait, and the methods of [VRFKeyStorage] which are generic over the
+/// configuration are derived from the ones here.
+#[cfg(feature = "dyn_traits")]
+#[async_trait]
+pub trait DynVRFKeyStorage: Sync + Send {
+    /// Retrieve the VRF Private key as a vector of bytes
+    async fn retrieve(&self) -> Result<Vec<u8>, VrfError>;
+
+    /// Retrieve the properly constructed VRF Private key
+    async fn get_vrf_private_key(&self) -> Result<VRFPrivateKey, VrfError>;
+
+    /// Retrieve the VRF Public key
+    async fn get_vrf_public_key(&self) -> Result<VRFPublicKey, VrfError>;
+}
+
+#[cfg(feature = "dyn_traits")]
+#[async_trait]
+impl<V: VRFKeyStorage> DynVRFKeyStorage for V {
+    async fn retrieve(&self) -> Result<Vec<u8>, VrfError> {
+        VRFKeyStorage::retrieve(self).await
+    }
+
+    async fn get_vrf_private_key(&self) -> Result<VRFPrivateKey, VrfError> {
+        VRFKeyStorage::get_vrf_private_key(self).await
+    }
+
+    async fn get_vrf_public_key(&self) -> Result<VRFPublicKey, VrfError> {
+        VRFKeyStorage::get_vrf_public_key(self).await
+    }
+}
+
+#[cfg(feature = "dyn_traits")]
+#[async_trait]
+impl VRFKeyStorage for alloc::sync::Arc<dyn DynVRFKeyStorage> {
+    async fn retrieve(&self) -> Result<Vec<u8>, VrfError> {
+        DynVRFKeyStorage::retrieve(self.as_ref()).await
+    }
+
+    async fn get_vrf_private_key(&self) -> Result<VRFPrivateKey, VrfError> {
+        DynVRFKeyStorage::get_vrf_private_key(self.as_ref()).await
+    }
+
+    async fn get_vrf_public_key(&self) -> Result<VRFPublicKey, VrfError> {
+        DynVRFKeyStorage::get_vrf_public_key(self.as_ref()).await
+    }
+}
@@ -0,0 +1,421 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Export of epoch root hashes as signed tree head observations, in the checkpoint format
+//! which is exchanged by the gossip and witnessing networks of transparency logs
+//! ([C2SP tlog-checkpoint](https://c2sp.org/tlog-checkpoint), signed as a
+//! [C2SP signed note](https://c2sp.org/signed-note)), so that the root hashes of an AKD
+//! can be observed and cosigned alongside those of other providers.
+//!
+//! A [Checkpoint] of a directory carries its origin (the name which identifies the
+//! directory), the epoch in place of the tree size, and the root hash of the epoch:
+//!
+//! ```text
+//! example.com/akd
+//! 42
+//! qMRKLqsMp4yGL/bi9v5BAuwZ5ODIQiUx2qCjOEDamQU=
+//! ```
+//!
+//! Epochs grow monotonically, as the tree sizes of logs do, so the consistency checks of
+//! observers which compare checkpoints by their sizes carry over. The proof that a tree
+//! only grew between two epochs is an AKD audit proof rather than a log consistency proof.
+//!
+//! A [SignedCheckpoint] is signed with an [EpochSigner] over the text of the checkpoint,
+//! and each signature is identified by the name and ID of the key which produced it (see
+//! [NoteKey]). Further signatures, such as the cosignatures of witnesses, are kept when a
+//! signed checkpoint is parsed and serialized again.
+
+use crate::hash::Digest;
+use crate::signing::{EpochSignatureVerifier, EpochSigner};
+
+#[cfg(feature = "nostd")]
+use alloc::format;
+#[cfg(feature = "nostd")]
+use alloc::string::{String, ToString};
+#[cfg(feature = "nostd")]
+use alloc::vec::Vec;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use sha2::{Digest as _, Sha256};
+
+/// The prefix of each signature line of a signed note (an em dash and a space)
+const SIGNATURE_LINE_PREFIX: &str = "\u{2014} ";
+/// The signature type of Ed25519 keys, which is hashed into their key IDs
+pub const ED25519_SIGNATURE_TYPE: u8 = 0x01;
+
+/// An error parsing or verifying a checkpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GossipError {
+    /// The text is not a well-formed checkpoint or signed note
+    Malformed(String),
+    /// The checkpoint is not signed by the expected key
+    Signature(String),
+}
+
+impl core::fmt::Display for GossipError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            GossipError::Malformed(msg) => write!(f, "Malformed checkpoint - {msg}"),
+            GossipError::Signature(msg) => write!(f, "Checkpoint signature error - {msg}"),
+        }
+    }
+}
+
+/// The root hash of an epoch, as a checkpoint of a transparency log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// The name which identifies the directory, conventionally a schema-less URL
+    pub origin: String,
+    /// The epoch of the root hash, in place of the size of the tree
+    pub epoch: u64,
+    /// The root hash of the epoch
+    pub root_hash: Digest,
+    /// Further lines of the checkpoint, which are signed along with it
+    pub extensions: Vec<String>,
+}
+
+impl Checkpoint {
+    /// Creates the checkpoint of `epoch` of the directory named `origin`
+    pub fn new(origin: &str, epoch: u64, root_hash: Digest) -> Self {
+        Self {
+            origin: origin.to_string(),
+            epoch,
+            root_hash,
+            extensions: Vec::new(),
+        }
+    }
+
+    /// The text of the checkpoint, which is the message that is signed
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "{}\n{}\n{}\n",
+            self.origin,
+            self.epoch,
+            BASE64.encode(self.root_hash)
+        );
+        for extension in self.extensions.iter() {
+            text.push_str(extension);
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Parses the text of a checkpoint, as produced by [Checkpoint::to_text]
+    pub fn from_text(text: &str) -> Result<Self, GossipError> {
+        let lines = text
+            .strip_suffix('\n')
+            .ok_or_else(|| GossipError::Malformed("The text does not end with a newline".into()))?
+            .split('\n')
+            .collect::<Vec<_>>();
+        if lines.len() < 3 || lines.iter().any(|line| line.is_empty()) {
+            return Err(GossipError::Malformed(format!(
+                "Expected an origin, an epoch and a root hash, got {} lines",
+                lines.len()
+            )));
+        }
+        // Leading zeros are rejected, so that a checkpoint has a single encoding
+        let epoch = match lines[1].parse::<u64>() {
+            Ok(epoch) if epoch.to_string() == lines[1] => epoch,
+            _ => {
+                return Err(GossipError::Malformed(format!(
+                    "Invalid epoch {}",
+                    lines[1]
+                )))
+            }
+        };
+        let root_hash = BASE64
+            .decode(lines[2])
+            .ok()
+            .and_then(|bytes| Digest::try_from(bytes).ok())
+            .ok_or_else(|| GossipError::Malformed(format!("Invalid root hash {}", lines[2])))?;
+        Ok(Self {
+            origin: lines[0].to_string(),
+            epoch,
+            root_hash,
+            extensions: lines[3..].iter().map(|line| line.to_string()).collect(),
+        })
+    }
+}
+
+/// The name and ID of a key which signs checkpoints. Observers look up the key of a
+/// signature by both, and ignore the signatures of keys which they do not know.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoteKey {
+    /// The name of the key, conventionally the origin of the directory
+    pub name: String,
+    /// The ID of the key, which is derived from its name, type and public key
+    pub id: [u8; 4],
+}
+
+impl NoteKey {
+    /// The key named `name` with the given signature type and public key
+    pub fn new(name: &str, signature_type: u8, public_key: &[u8]) -> Self {
+        let hash = Sha256::new()
+            .chain_update(name.as_bytes())
+            .chain_update(b"\n")
+            .chain_update([signature_type])
+            .chain_update(public_key)
+            .finalize();
+        Self {
+            name: name.to_string(),
+            id: [hash[0], hash[1], hash[2], hash[3]],
+        }
+    }
+
+    /// The Ed25519 key named `name`
+    pub fn ed25519(name: &str, public_key: &[u8; 32]) -> Self {
+        Self::new(name, ED25519_SIGNATURE_TYPE, public_key)
+    }
+
+    /// The verifier key of the key, in the `<name>+<hex id>+<base64 type and public key>`
+    /// format with which observers are configured
+    pub fn verifier_key(&self, signature_type: u8, public_key: &[u8]) -> String {
+        let mut key = Vec::with_capacity(1 + public_key.len());
+        key.push(signature_type);
+        key.extend_from_slice(public_key);
+        format!(
+            "{}+{}+{}",
+            self.name,
+            hex::encode(self.id),
+            BASE64.encode(key)
+        )
+    }
+}
+
+/// A signature of a [SignedCheckpoint]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoteSignature {
+    /// The name of the key which produced the signature
+    pub key_name: String,
+    /// The ID of the key which produced the signature
+    pub key_id: [u8; 4],
+    /// The signature of the text of the checkpoint
+    pub signature: Vec<u8>,
+}
+
+/// A [Checkpoint] with the signatures over its text, as a signed note
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedCheckpoint {
+    /// The checkpoint
+    pub checkpoint: Checkpoint,
+    /// The signatures of the checkpoint, of the directory and of any cosigners
+    pub signatures: Vec<NoteSignature>,
+}
+
+impl SignedCheckpoint {
+    /// Signs the checkpoint with the key of the directory
+    pub fn sign(checkpoint: Checkpoint, key: &NoteKey, signer: &impl EpochSigner) -> Self {
+        let mut signed = Self {
+            checkpoint,
+            signatures: Vec::new(),
+        };
+        signed.add_signature(key, signer);
+        signed
+    }
+
+    /// Adds a signature of the checkpoint with another key, such as a witness's
+    pub fn add_signature(&mut self, key: &NoteKey, signer: &impl EpochSigner) {
+        let signature = signer.sign_epoch_message(self.checkpoint.to_text().as_bytes());
+        self.signatures.push(NoteSignature {
+            key_name: key.name.clone(),
+            key_id: key.id,
+            signature,
+        });
+    }
+
+    /// Verifies that the checkpoint carries a valid signature of the key
+    pub fn verify(
+        &self,
+        key: &NoteKey,
+        verifier: &impl EpochSignatureVerifier,
+    ) -> Result<(), GossipError> {
+        let text = self.checkpoint.to_text();
+        let mut signatures = self
+            .signatures
+            .iter()
+            .filter(|signature| signature.key_name == key.name && signature.key_id == key.id)
+            .peekable();
+        if signatures.peek().is_none() {
+            return Err(GossipError::Signature(format!(
+                "The checkpoint is not signed by the key {}+{}",
+                key.name,
+                hex::encode(key.id)
+            )));
+        }
+        if signatures
+            .any(|signature| verifier.verify_epoch_message(text.as_bytes(), &signature.signature))
+        {
+            Ok(())
+        } else {
+            Err(GossipError::Signature(format!(
+                "Invalid signature of the key {}+{}",
+                key.name,
+                hex::encode(key.id)
+            )))
+        }
+    }
+
+    /// The signed note, which is the text of the checkpoint followed by a blank line and
+    /// a line for each signature
+    pub fn to_note(&self) -> String {
+        let mut note = self.checkpoint.to_text();
+        note.push('\n');
+        for signature in self.signatures.iter() {
+            let mut bytes = signature.key_id.to_vec();
+            bytes.extend_from_slice(&signature.signature);
+            note.push_str(&format!(
+                "{SIGNATURE_LINE_PREFIX}{} {}\n",
+                signature.key_name,
+                BASE64.encode(bytes)
+            ));
+        }
+        note
+    }
+
+    /// Parses a signed note, as produced by [SignedCheckpoint::to_note]. The signatures
+    /// are not verified.
+    pub fn from_note(note: &str) -> Result<Self, GossipError> {
+        let (text, signature_lines) = note.rsplit_once("\n\n").ok_or_else(|| {
+            GossipError::Malformed("The note has no blank line before its signatures".into())
+        })?;
+        let checkpoint = Checkpoint::from_text(&format!("{text}\n"))?;
+
+        let signature_lines = signature_lines
+            .strip_suffix('\n')
+            .ok_or_else(|| GossipError::Malformed("The note does not end with a newline".into()))?;
+        let signatures = signature_lines
+            .split('\n')
+            .map(|line| {
+                let malformed = || GossipError::Malformed(format!("Invalid signature line {line}"));
+                let (key_name, signature) = line
+                    .strip_prefix(SIGNATURE_LINE_PREFIX)
+                    .and_then(|line| line.rsplit_once(' '))
+                    .ok_or_else(malformed)?;
+                let bytes = BASE64.decode(signature).map_err(|_| malformed())?;
+                if key_name.is_empty() || bytes.len() < 4 {
+                    return Err(malformed());
+                }
+                Ok(NoteSignature {
+                    key_name: key_name.to_string(),
+                    key_id: [bytes[0], bytes[1], bytes[2], bytes[3]],
+                    signature: bytes[4..].to_vec(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            checkpoint,
+            signatures,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "vrf"))]
+mod tests {
+    use super::*;
+
+    fn signed_checkpoint() -> (SignedCheckpoint, NoteKey, ed25519_dalek::SigningKey) {
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]);
+        let key = NoteKey::ed25519("example.com/akd", signing_key.verifying_key().as_bytes());
+        let checkpoint = Checkpoint::new("example.com/akd", 42, [9u8; 32]);
+        (
+            SignedCheckpoint::sign(checkpoint, &key, &signing_key),
+            key,
+            signing_key,
+        )
+    }
+
+    #[test]
+    fn test_key_id() {
+        // The example verifier key of the signed note specification
+        let public_key = BASE64
+            .decode("ARpc2QcUPDhMQegwxbzhKqiBfsVkmqq/LDE4izWy10TW")
+            .unwrap();
+        assert_eq!(ED25519_SIGNATURE_TYPE, public_key[0]);
+        let key = NoteKey::new("PeterNeumann", public_key[0], &public_key[1..]);
+        assert_eq!("c74f20a3", hex::encode(key.id));
+        assert_eq!(
+            "PeterNeumann+c74f20a3+ARpc2QcUPDhMQegwxbzhKqiBfsVkmqq/LDE4izWy10TW",
+            key.verifier_key(public_key[0], &public_key[1..])
+        );
+    }
+
+    #[test]
+    fn test_checkpoint_note() {
+        let (signed, key, signing_key) = signed_checkpoint();
+        let note = signed.to_note();
+        let root_hash = BASE64.encode([9u8; 32]);
+        let text = format!("example.com/akd\n42\n{root_hash}\n");
+        assert!(note.starts_with(&format!("{text}\n\u{2014} example.com/akd ")));
+        assert_eq!(text, signed.checkpoint.to_text());
+
+        let parsed = SignedCheckpoint::from_note(&note).unwrap();
+        assert_eq!(signed, parsed);
+        parsed.verify(&key, &signing_key.verifying_key()).unwrap();
+
+        // the note is signed with ed25519 over the text of the checkpoint
+        use ed25519_dalek::Verifier;
+        let signature =
+            ed25519_dalek::Signature::from_slice(&parsed.signatures[0].signature).unwrap();
+        assert!(signing_key
+            .verifying_key()
+            .verify(text.as_bytes(), &signature)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_cosigned_checkpoint() {
+        let (mut signed, key, signing_key) = signed_checkpoint();
+        let witness_signing_key = ed25519_dalek::SigningKey::from_bytes(&[8u8; 32]);
+        let witness_key = NoteKey::ed25519(
+            "witness.example.com",
+            witness_signing_key.verifying_key().as_bytes(),
+        );
+        signed.add_signature(&witness_key, &witness_signing_key);
+
+        let parsed = SignedCheckpoint::from_note(&signed.to_note()).unwrap();
+        assert_eq!(2, parsed.signatures.len());
+        parsed.verify(&key, &signing_key.verifying_key()).unwrap();
+        parsed
+            .verify(&witness_key, &witness_signing_key.verifying_key())
+            .unwrap();
+        // a key is only checked against its own signatures
+        assert!(parsed
+            .verify(&key, &witness_signing_key.verifying_key())
+            .is_err());
+    }
+
+    #[test]
+    fn test_rejects_altered_checkpoints() {
+        let (signed, key, signing_key) = signed_checkpoint();
+        let verifying_key = signing_key.verifying_key();
+
+        let mut altered = signed.clone();
+        altered.checkpoint.epoch += 1;
+        assert!(altered.verify(&key, &verifying_key).is_err());
+
+        let mut altered = signed.clone();
+        altered.checkpoint.extensions.push("extension".to_string());
+        assert!(altered.verify(&key, &verifying_key).is_err());
+
+        let other_key = NoteKey::ed25519("example.com/other", verifying_key.as_bytes());
+        assert!(signed.verify(&other_key, &verifying_key).is_err());
+
+        let note = signed.to_note();
+        for malformed in [
+            note.replacen("42", "042", 1),
+            note.replacen("\n\n", "\n", 1),
+            note.replacen("\u{2014}", "-", 1),
+            note.trim_end().to_string(),
+            note.replacen("example.com/akd\n", "", 1),
+        ] {
+            assert!(
+                SignedCheckpoint::from_note(&malformed).is_err(),
+                "Parsed {malformed}"
+            );
+        }
+    }
+}
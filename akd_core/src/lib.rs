@@ -56,6 +56,7 @@
 //! - The label in bytes
 //! - A single byte encoded as `0u8` if "stale", `1u8` if "fresh"
 //! - A `u64` representing the version (starting at 1 for newly inserted labels, and incremented by 1 for each update)
+//!
 //! The resulting values are hashed together and used as the byte string (truncated to 256 bits) that is stored
 //! as the [NodeLabel].
 //!
@@ -69,6 +70,10 @@
 //! - `commitment_nonce = Hash(commitment_key, node_label, version, I2OSP(len(value) as u64), value)`
 //! - `commmitment = Hash(I2OSP(len(value) as u64), value, I2OSP(len(commitment_nonce) as u64), commitment_nonce)`
 //!
+//! The commitment itself is computed by [Configuration::commit_value], which a configuration can replace with another
+//! [configuration::CommitmentScheme] (such as Pedersen commitments, with the `pedersen` feature, or Poseidon
+//! commitments, with the `poseidon` feature).
+//!
 //! Finally, the commitment is hashed together with the epoch that it ends up being inserted into the tree,
 //! computed as: `azks_value = Hash(commitment, epoch)`
 //!
@@ -136,7 +141,7 @@
 //! Let `n` be the current version, and let `m` be the largest power
 //! of 2 that is at most `n`. The [LookupProof] consists of:
 //! - The `commitment_nonce` corresponding to the value, which the client
-//! can hash together with the value to reconstruct the commitment
+//!   can hash together with the value to reconstruct the commitment
 //! - A membership and VRF proof for version `n` being marked as fresh
 //! - A non-membership and VRF proof for version `n` being marked as stale
 //! - A membership and VRF proof for version `m` being marked as fresh
@@ -153,7 +158,7 @@
 //! Let `n` be the latest version, `n_next_pow` the next power of 2 after `n`, and `epoch_prev_pow` be the power of 2 that
 //! is at most the current epoch. The [HistoryProof] consists of:
 //! - A list of [UpdateProof]s, one for each version, which each contain a membership proof for the version `n` being fresh,
-//! and a membership proof for the version `n-1` being stale
+//!   and a membership proof for the version `n-1` being stale
 //! - A series of non-membership proofs for each version in the range `[n+1, n_next_pow]`
 //! - A series of non-membership proofs for each power of 2 in the range `[n_next_pow, epoch_prev_pow]`
 //!
@@ -173,6 +178,10 @@
 //! alongside the sibling nodes, to reconstruct the second epoch's root hash. For audit proofs that span multiple epochs and root hashes,
 //! these checks are repeated iteratively.
 //!
+//! Auditors which cannot afford to verify every append-only proof can instead spot check an epoch: the server proves the
+//! membership of leaves sampled pseudorandomly from the epoch's root hash, as a [spot_check::SpotCheckProof], which is
+//! verified with [spot_check::spot_check_verify].
+//!
 
 #![warn(missing_docs)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
@@ -182,8 +191,23 @@ extern crate alloc;
 #[cfg(all(feature = "protobuf", not(feature = "nostd")))]
 pub mod proto;
 
+pub mod audit_path;
+pub mod canonical;
+pub mod coniks;
 pub mod ecvrf;
+#[cfg(feature = "gossip")]
+pub mod gossip;
 pub mod hash;
+#[cfg(feature = "public_tests")]
+pub mod proof_mutator;
+pub mod sharding;
+pub mod signing;
+pub mod spot_check;
+#[cfg(all(
+    any(test, all(feature = "public_tests", feature = "rand")),
+    not(feature = "nostd")
+))]
+pub mod test_utils;
 pub mod utils;
 pub mod verify;
 
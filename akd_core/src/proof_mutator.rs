@@ -0,0 +1,374 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Systematically corrupted variants of valid proofs, for testing the robustness of clients
+//! which integrate the verifier (e.g. as a fuzzing corpus).
+//!
+//! Every field of a proof is corrupted in turn: bytes have their first and last bits flipped
+//! and are truncated or extended, integers are incremented and decremented, directions are
+//! flipped, and lists (such as the update proofs of a [HistoryProof]) are truncated, emptied,
+//! padded and reordered, in addition to each of their elements being corrupted. Each
+//! [Mutation] differs from the original proof, but not every one invalidates it, as some
+//! fields are not covered by verification (e.g. the label of the root). A verifier is robust
+//! if, for every mutation, it either rejects the proof or returns the same result as it does
+//! for the original proof (or, for a [HistoryProof], the results of some of its most recent
+//! versions, as a history may legitimately be limited to them).
+//!
+//! ```ignore
+//! for mutation in lookup_proof.mutations() {
+//!     let result = lookup_verify::<TC>(vrf_pk, root_hash, epoch, label.clone(), mutation.proof);
+//!     assert!(result.is_err() || result == expected, "{}", mutation.description);
+//! }
+//! ```
+
+use crate::hash::Digest;
+use crate::{
+    AppendOnlyProof, AzksElement, Direction, HistoryProof, LatestVersionProof, LookupProof,
+    MembershipProof, NodeLabel, NonExistenceProof, NonMembershipProof, SiblingProof,
+    SingleAppendOnlyProof, UpdateProof,
+};
+
+#[cfg(feature = "nostd")]
+use alloc::format;
+#[cfg(feature = "nostd")]
+use alloc::string::{String, ToString};
+#[cfg(feature = "nostd")]
+use alloc::vec;
+#[cfg(feature = "nostd")]
+use alloc::vec::Vec;
+
+/// A corrupted variant of a proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mutation<T> {
+    /// The path of the corrupted field within the proof, and how it was corrupted
+    pub description: String,
+    /// The corrupted proof
+    pub proof: T,
+}
+
+/// A proof of which systematically corrupted variants can be produced
+pub trait Mutate: Clone + PartialEq + Sized {
+    /// The corrupted variants of this proof, each differing from it
+    fn mutations(&self) -> Vec<Mutation<Self>>;
+}
+
+/// Collects the mutations of a proof, field by field
+struct Mutations<'a, T> {
+    original: &'a T,
+    mutations: Vec<Mutation<T>>,
+}
+
+impl<'a, T: Clone + PartialEq> Mutations<'a, T> {
+    fn new(original: &'a T) -> Self {
+        Self {
+            original,
+            mutations: vec![],
+        }
+    }
+
+    fn push(&mut self, description: String, mutate: impl FnOnce(&mut T)) {
+        let mut proof = self.original.clone();
+        mutate(&mut proof);
+        // a mutation which happens to leave the proof unchanged is not a corruption
+        if proof != *self.original {
+            self.mutations.push(Mutation { description, proof });
+        }
+    }
+
+    fn bytes(&mut self, name: &str, get: impl Fn(&mut T) -> &mut Vec<u8>) {
+        let len = get(&mut self.original.clone()).len();
+        if len > 0 {
+            self.push(format!("{name}: flipped the first bit"), |p| {
+                get(p)[0] ^= 0x80
+            });
+            self.push(format!("{name}: flipped the last bit"), |p| {
+                get(p)[len - 1] ^= 0x01
+            });
+            self.push(format!("{name}: truncated by one byte"), |p| {
+                get(p).pop();
+            });
+            self.push(format!("{name}: emptied"), |p| get(p).clear());
+        }
+        self.push(format!("{name}: extended by one byte"), |p| get(p).push(0));
+    }
+
+    fn digest(&mut self, name: &str, get: impl Fn(&mut T) -> &mut Digest) {
+        self.push(format!("{name}: flipped the first bit"), |p| {
+            get(p)[0] ^= 0x80
+        });
+        self.push(format!("{name}: flipped the last bit"), |p| {
+            let digest = get(p);
+            digest[digest.len() - 1] ^= 0x01
+        });
+    }
+
+    fn integer(&mut self, name: &str, get: impl Fn(&mut T) -> &mut u64) {
+        self.push(format!("{name}: incremented"), |p| {
+            let value = get(p);
+            *value = value.wrapping_add(1)
+        });
+        self.push(format!("{name}: decremented"), |p| {
+            let value = get(p);
+            *value = value.wrapping_sub(1)
+        });
+    }
+
+    fn direction(&mut self, name: &str, get: impl Fn(&mut T) -> &mut Direction) {
+        self.push(format!("{name}: flipped"), |p| {
+            let direction = get(p);
+            *direction = match direction {
+                Direction::Left => Direction::Right,
+                Direction::Right => Direction::Left,
+            }
+        });
+    }
+
+    fn label(&mut self, name: &str, get: impl Fn(&mut T) -> &mut NodeLabel) {
+        let len = get(&mut self.original.clone()).label_len;
+        if len > 0 {
+            // the bits of a label are numbered from the most significant bit of its first byte
+            let last_bit = len - 1;
+            self.push(format!("{name}: flipped the last bit"), |p| {
+                get(p).label_val[(last_bit / 8) as usize] ^= 0x80 >> (last_bit % 8)
+            });
+            self.push(format!("{name}: shortened by one bit"), |p| {
+                let label = get(p);
+                *label = label.get_prefix(len - 1)
+            });
+        }
+        self.push(format!("{name}: flipped the first bit"), |p| {
+            get(p).label_val[0] ^= 0x80
+        });
+    }
+
+    /// The mutations of a nested proof
+    fn field<F: Mutate>(&mut self, name: &str, get: impl Fn(&mut T) -> &mut F) {
+        let field = get(&mut self.original.clone()).clone();
+        for mutation in field.mutations() {
+            self.push(format!("{name}.{}", mutation.description), |p| {
+                *get(p) = mutation.proof
+            });
+        }
+    }
+
+    /// The mutations of the structure of a list: truncating, emptying, padding and reordering it
+    fn list_structure<F: Clone>(&mut self, name: &str, get: impl Fn(&mut T) -> &mut Vec<F>) {
+        let len = get(&mut self.original.clone()).len();
+        if len == 0 {
+            return;
+        }
+        self.push(format!("{name}: removed the first element"), |p| {
+            get(p).remove(0);
+        });
+        self.push(format!("{name}: removed the last element"), |p| {
+            get(p).pop();
+        });
+        self.push(format!("{name}: emptied"), |p| get(p).clear());
+        self.push(format!("{name}: duplicated the first element"), |p| {
+            let list = get(p);
+            list.insert(0, list[0].clone())
+        });
+        if len > 1 {
+            self.push(format!("{name}: swapped the first two elements"), |p| {
+                get(p).swap(0, 1)
+            });
+            self.push(format!("{name}: reversed"), |p| get(p).reverse());
+        }
+    }
+
+    /// The mutations of a list of nested proofs, and of each of its elements
+    fn list<F: Mutate>(&mut self, name: &str, get: impl Fn(&mut T) -> &mut Vec<F>) {
+        self.list_structure(name, &get);
+        let len = get(&mut self.original.clone()).len();
+        for i in 0..len {
+            self.field(&format!("{name}[{i}]"), |p| &mut get(p)[i]);
+        }
+    }
+
+    /// The mutations of a list of byte strings, and of each of its elements
+    fn byte_lists(&mut self, name: &str, get: impl Fn(&mut T) -> &mut Vec<Vec<u8>>) {
+        self.list_structure(name, &get);
+        let len = get(&mut self.original.clone()).len();
+        for i in 0..len {
+            self.bytes(&format!("{name}[{i}]"), |p| &mut get(p)[i]);
+        }
+    }
+
+    fn finish(self) -> Vec<Mutation<T>> {
+        self.mutations
+    }
+}
+
+impl Mutate for AzksElement {
+    fn mutations(&self) -> Vec<Mutation<Self>> {
+        let mut m = Mutations::new(self);
+        m.label("label", |p| &mut p.label);
+        m.digest("value", |p| &mut p.value.0);
+        m.finish()
+    }
+}
+
+impl Mutate for SiblingProof {
+    fn mutations(&self) -> Vec<Mutation<Self>> {
+        let mut m = Mutations::new(self);
+        m.label("label", |p| &mut p.label);
+        m.field("siblings[0]", |p| &mut p.siblings[0]);
+        m.direction("direction", |p| &mut p.direction);
+        m.finish()
+    }
+}
+
+impl Mutate for MembershipProof {
+    fn mutations(&self) -> Vec<Mutation<Self>> {
+        let mut m = Mutations::new(self);
+        m.label("label", |p| &mut p.label);
+        m.digest("hash_val", |p| &mut p.hash_val.0);
+        m.list("sibling_proofs", |p| &mut p.sibling_proofs);
+        m.finish()
+    }
+}
+
+impl Mutate for NonMembershipProof {
+    fn mutations(&self) -> Vec<Mutation<Self>> {
+        let mut m = Mutations::new(self);
+        m.label("label", |p| &mut p.label);
+        m.label("longest_prefix", |p| &mut p.longest_prefix);
+        m.push("longest_prefix_children: swapped".to_string(), |p| {
+            p.longest_prefix_children.swap(0, 1)
+        });
+        m.field("longest_prefix_children[0]", |p| {
+            &mut p.longest_prefix_children[0]
+        });
+        m.field("longest_prefix_children[1]", |p| {
+            &mut p.longest_prefix_children[1]
+        });
+        m.field("longest_prefix_membership_proof", |p| {
+            &mut p.longest_prefix_membership_proof
+        });
+        m.finish()
+    }
+}
+
+impl Mutate for LookupProof {
+    fn mutations(&self) -> Vec<Mutation<Self>> {
+        let mut m = Mutations::new(self);
+        m.integer("epoch", |p| &mut p.epoch);
+        m.bytes("value", |p| &mut p.value.0);
+        m.integer("version", |p| &mut p.version);
+        m.bytes("existence_vrf_proof", |p| &mut p.existence_vrf_proof);
+        m.field("existence_proof", |p| &mut p.existence_proof);
+        m.bytes("marker_vrf_proof", |p| &mut p.marker_vrf_proof);
+        m.field("marker_proof", |p| &mut p.marker_proof);
+        m.bytes("freshness_vrf_proof", |p| &mut p.freshness_vrf_proof);
+        m.field("freshness_proof", |p| &mut p.freshness_proof);
+        m.bytes("commitment_nonce", |p| &mut p.commitment_nonce);
+        m.finish()
+    }
+}
+
+impl Mutate for NonExistenceProof {
+    fn mutations(&self) -> Vec<Mutation<Self>> {
+        let mut m = Mutations::new(self);
+        m.bytes("vrf_proof", |p| &mut p.vrf_proof);
+        m.field("non_membership_proof", |p| &mut p.non_membership_proof);
+        m.finish()
+    }
+}
+
+impl Mutate for LatestVersionProof {
+    fn mutations(&self) -> Vec<Mutation<Self>> {
+        let mut m = Mutations::new(self);
+        m.field("lookup_proof", |p| &mut p.lookup_proof);
+        m.byte_lists("until_marker_vrf_proofs", |p| {
+            &mut p.until_marker_vrf_proofs
+        });
+        m.list("non_existence_until_marker_proofs", |p| {
+            &mut p.non_existence_until_marker_proofs
+        });
+        m.byte_lists("future_marker_vrf_proofs", |p| {
+            &mut p.future_marker_vrf_proofs
+        });
+        m.list("non_existence_of_future_marker_proofs", |p| {
+            &mut p.non_existence_of_future_marker_proofs
+        });
+        m.finish()
+    }
+}
+
+impl Mutate for UpdateProof {
+    fn mutations(&self) -> Vec<Mutation<Self>> {
+        let mut m = Mutations::new(self);
+        m.integer("epoch", |p| &mut p.epoch);
+        m.bytes("value", |p| &mut p.value.0);
+        m.integer("version", |p| &mut p.version);
+        m.bytes("existence_vrf_proof", |p| &mut p.existence_vrf_proof);
+        m.field("existence_proof", |p| &mut p.existence_proof);
+        if self.previous_version_vrf_proof.is_some() {
+            m.push("previous_version_vrf_proof: removed".to_string(), |p| {
+                p.previous_version_vrf_proof = None
+            });
+            m.bytes("previous_version_vrf_proof", |p| {
+                p.previous_version_vrf_proof
+                    .as_mut()
+                    .expect("present in the original proof")
+            });
+        }
+        if self.previous_version_proof.is_some() {
+            m.push("previous_version_proof: removed".to_string(), |p| {
+                p.previous_version_proof = None
+            });
+            m.field("previous_version_proof", |p| {
+                p.previous_version_proof
+                    .as_mut()
+                    .expect("present in the original proof")
+            });
+        }
+        m.bytes("commitment_nonce", |p| &mut p.commitment_nonce);
+        m.finish()
+    }
+}
+
+impl Mutate for HistoryProof {
+    fn mutations(&self) -> Vec<Mutation<Self>> {
+        let mut m = Mutations::new(self);
+        m.list("update_proofs", |p| &mut p.update_proofs);
+        m.byte_lists("until_marker_vrf_proofs", |p| {
+            &mut p.until_marker_vrf_proofs
+        });
+        m.list("non_existence_until_marker_proofs", |p| {
+            &mut p.non_existence_until_marker_proofs
+        });
+        m.byte_lists("future_marker_vrf_proofs", |p| {
+            &mut p.future_marker_vrf_proofs
+        });
+        m.list("non_existence_of_future_marker_proofs", |p| {
+            &mut p.non_existence_of_future_marker_proofs
+        });
+        m.finish()
+    }
+}
+
+impl Mutate for SingleAppendOnlyProof {
+    fn mutations(&self) -> Vec<Mutation<Self>> {
+        let mut m = Mutations::new(self);
+        m.list("inserted", |p| &mut p.inserted);
+        m.list("unchanged_nodes", |p| &mut p.unchanged_nodes);
+        m.finish()
+    }
+}
+
+impl Mutate for AppendOnlyProof {
+    fn mutations(&self) -> Vec<Mutation<Self>> {
+        let mut m = Mutations::new(self);
+        m.list("proofs", |p| &mut p.proofs);
+        m.list_structure("epochs", |p| &mut p.epochs);
+        for i in 0..self.epochs.len() {
+            m.integer(&format!("epochs[{i}]"), |p| &mut p.epochs[i]);
+        }
+        m.finish()
+    }
+}
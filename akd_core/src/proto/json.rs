@@ -0,0 +1,225 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A JSON serialization profile for proofs, for embedding proofs in the responses of web APIs
+//! and for inspecting them while debugging.
+//!
+//! The profile is a mapping of the protobuf messages of the `akd.v1` package (see
+//! `specs/types.proto`), so its field names are the protobuf field names, which are frozen by
+//! `specs/wire_contract.txt`, and a proof which round-trips through JSON has the same binary
+//! encoding as the original. A proof is encoded as an envelope object:
+//!
+//! ```json
+//! { "version": 1, "type": "LookupProof", "proof": { "epoch": "3", "value": "dmFsdWU", ... } }
+//! ```
+//!
+//! where `version` is [JSON_PROFILE_VERSION], `type` is the name of the protobuf message of the
+//! proof, and `proof` is the message, encoded with the following rules:
+//! * Messages are objects whose keys are the protobuf field names (in `snake_case`)
+//! * `bytes` fields are base64url strings, without padding
+//! * `uint64` fields are decimal strings, since JSON numbers are not precise beyond 2^53
+//! * `uint32` fields are numbers
+//! * `repeated` fields are arrays, and are omitted when empty
+//! * `optional` fields are omitted when absent
+//!
+//! Unknown keys are rejected when decoding, and a `uint64` field may also be given as a number.
+
+use super::specs::types;
+use super::ConversionError;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use core::convert::TryFrom;
+use protobuf::reflect::{
+    FieldDescriptor, ReflectFieldRef, ReflectValueBox, ReflectValueRef, RuntimeFieldType,
+    RuntimeType,
+};
+use protobuf::{MessageDyn, MessageFull};
+use serde_json::{Map, Value};
+
+/// The version of the JSON profile, which is carried by the envelope of every encoded proof
+pub const JSON_PROFILE_VERSION: u64 = 1;
+
+/// A proof which can be encoded with the JSON profile
+pub trait JsonProof: Sized {
+    /// Encodes the proof as an envelope object
+    fn to_json(&self) -> Value;
+
+    /// Decodes a proof from an envelope object produced by [JsonProof::to_json]
+    fn from_json(value: &Value) -> Result<Self, ConversionError>;
+
+    /// Encodes the proof as a JSON string
+    fn to_json_string(&self) -> String {
+        self.to_json().to_string()
+    }
+
+    /// Decodes a proof from a JSON string produced by [JsonProof::to_json_string]
+    fn from_json_str(json: &str) -> Result<Self, ConversionError> {
+        let value = serde_json::from_str(json).map_err(|err| {
+            ConversionError::Deserialization(format!("Invalid JSON for a proof: {err}"))
+        })?;
+        Self::from_json(&value)
+    }
+}
+
+macro_rules! impl_json_proof {
+    ($type:ty, $message:ty) => {
+        impl JsonProof for $type {
+            fn to_json(&self) -> Value {
+                encode_envelope(&<$message>::from(self))
+            }
+
+            fn from_json(value: &Value) -> Result<Self, ConversionError> {
+                <$type>::try_from(&decode_envelope::<$message>(value)?)
+            }
+        }
+    };
+}
+
+impl_json_proof!(crate::MembershipProof, types::MembershipProof);
+impl_json_proof!(crate::NonMembershipProof, types::NonMembershipProof);
+impl_json_proof!(crate::LookupProof, types::LookupProof);
+impl_json_proof!(crate::UpdateProof, types::UpdateProof);
+impl_json_proof!(crate::HistoryProof, types::HistoryProof);
+impl_json_proof!(crate::CompactHistoryProof, types::CompactHistoryProof);
+impl_json_proof!(crate::SingleAppendOnlyProof, types::SingleAppendOnlyProof);
+impl_json_proof!(crate::AppendOnlyProof, types::AppendOnlyProof);
+
+fn encode_envelope<M: MessageFull>(message: &M) -> Value {
+    let mut envelope = Map::new();
+    envelope.insert("version".to_string(), Value::from(JSON_PROFILE_VERSION));
+    envelope.insert("type".to_string(), Value::from(M::descriptor().name()));
+    envelope.insert("proof".to_string(), encode_message(message));
+    Value::Object(envelope)
+}
+
+fn decode_envelope<M: MessageFull>(value: &Value) -> Result<M, ConversionError> {
+    let envelope = value
+        .as_object()
+        .ok_or_else(|| invalid("The proof envelope is not an object".to_string()))?;
+    match envelope.get("version").and_then(Value::as_u64) {
+        Some(JSON_PROFILE_VERSION) => {}
+        Some(version) => return Err(invalid(format!("Unsupported JSON profile {version}"))),
+        None => return Err(invalid("The proof envelope has no version".to_string())),
+    }
+    let expected = M::descriptor().name().to_string();
+    match envelope.get("type").and_then(Value::as_str) {
+        Some(name) if name == expected => {}
+        Some(name) => {
+            return Err(invalid(format!(
+                "Expected a {expected}, but found a {name}"
+            )))
+        }
+        None => return Err(invalid("The proof envelope has no type".to_string())),
+    }
+    let proof = envelope
+        .get("proof")
+        .ok_or_else(|| invalid("The proof envelope has no proof".to_string()))?;
+    let mut message = M::new();
+    decode_message(proof, &mut message)?;
+    Ok(message)
+}
+
+fn encode_message(message: &dyn MessageDyn) -> Value {
+    let mut object = Map::new();
+    for field in message.descriptor_dyn().fields() {
+        let encoded = match field.get_reflect(message) {
+            ReflectFieldRef::Optional(value) => value.value().map(|value| encode_value(&value)),
+            ReflectFieldRef::Repeated(values) if values.is_empty() => None,
+            ReflectFieldRef::Repeated(values) => Some(Value::Array(
+                values
+                    .into_iter()
+                    .map(|value| encode_value(&value))
+                    .collect(),
+            )),
+            // The proofs have no map fields
+            ReflectFieldRef::Map(_) => None,
+        };
+        if let Some(encoded) = encoded {
+            object.insert(field.name().to_string(), encoded);
+        }
+    }
+    Value::Object(object)
+}
+
+fn encode_value(value: &ReflectValueRef<'_>) -> Value {
+    match value {
+        ReflectValueRef::U32(value) => Value::from(*value),
+        ReflectValueRef::U64(value) => Value::from(value.to_string()),
+        ReflectValueRef::Bytes(bytes) => Value::from(URL_SAFE_NO_PAD.encode(bytes)),
+        ReflectValueRef::Message(message) => encode_message(&**message),
+        // The proofs have no fields of any other type
+        _ => Value::Null,
+    }
+}
+
+fn decode_message(value: &Value, message: &mut dyn MessageDyn) -> Result<(), ConversionError> {
+    let descriptor = message.descriptor_dyn();
+    let object = value
+        .as_object()
+        .ok_or_else(|| invalid(format!("Expected a {} object", descriptor.name())))?;
+    for (name, value) in object {
+        let field = descriptor
+            .field_by_name(name)
+            .ok_or_else(|| invalid(format!("Unknown field {name} of {}", descriptor.name())))?;
+        match field.runtime_field_type() {
+            RuntimeFieldType::Singular(runtime_type) => {
+                let value = decode_value(&field, &runtime_type, value)?;
+                field.set_singular_field(message, value);
+            }
+            RuntimeFieldType::Repeated(runtime_type) => {
+                let values = value
+                    .as_array()
+                    .ok_or_else(|| invalid(format!("Field {name} must be an array")))?;
+                for value in values {
+                    let value = decode_value(&field, &runtime_type, value)?;
+                    field.mut_repeated(message).push(value);
+                }
+            }
+            RuntimeFieldType::Map(..) => {
+                return Err(invalid(format!("Unsupported map field {name}")));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn decode_value(
+    field: &FieldDescriptor,
+    runtime_type: &RuntimeType,
+    value: &Value,
+) -> Result<ReflectValueBox, ConversionError> {
+    let name = field.name();
+    match runtime_type {
+        RuntimeType::U32 => value
+            .as_u64()
+            .and_then(|value| u32::try_from(value).ok())
+            .map(ReflectValueBox::U32)
+            .ok_or_else(|| invalid(format!("Field {name} must be a 32-bit unsigned integer"))),
+        RuntimeType::U64 => match value {
+            Value::String(value) => value.parse().ok(),
+            value => value.as_u64(),
+        }
+        .map(ReflectValueBox::U64)
+        .ok_or_else(|| invalid(format!("Field {name} must be a 64-bit unsigned integer"))),
+        RuntimeType::VecU8 => value
+            .as_str()
+            .and_then(|value| URL_SAFE_NO_PAD.decode(value).ok())
+            .map(ReflectValueBox::Bytes)
+            .ok_or_else(|| invalid(format!("Field {name} must be unpadded base64url"))),
+        RuntimeType::Message(descriptor) => {
+            let mut message = descriptor.new_instance();
+            decode_message(value, &mut *message)?;
+            Ok(ReflectValueBox::Message(message))
+        }
+        other => Err(invalid(format!("Unsupported type {other} of field {name}"))),
+    }
+}
+
+fn invalid(message: String) -> ConversionError {
+    ConversionError::Deserialization(message)
+}
@@ -8,17 +8,25 @@
 //! This module contains all the protobuf types for type conversion between internal and external
 //! types. NOTE: Protobuf encoding is NOT supported in nostd environments. The generated code is using vector
 //! too heavily to be nostd compliant
+//!
+//! The messages are defined in the `akd.v1` package of `specs/types.proto`, which is a stable wire
+//! format for servers and clients written in other languages. The compatibility guarantees and the
+//! deprecation policy for it are documented in `specs/README.md`, and its fields are frozen by
+//! `specs/wire_contract.txt`.
 
 // Setup the protobuf specs
 pub mod specs;
 
+#[cfg(feature = "json")]
+pub mod json;
+
 #[cfg(test)]
 mod tests;
 
 use crate::{hash::Digest, AzksValue, Bit};
 
 use core::convert::{TryFrom, TryInto};
-use protobuf::MessageField;
+use protobuf::{Message, MessageField};
 
 const DIRECTION_BLINDING_FACTOR: u32 = 0x000Fu32;
 
@@ -578,3 +586,291 @@ impl TryFrom<&specs::types::AppendOnlyProof> for crate::AppendOnlyProof {
         Ok(Self { proofs, epochs })
     }
 }
+
+// ==============================================================
+// CompactMembershipProof
+// ==============================================================
+
+impl From<&crate::CompactMembershipProof> for specs::types::CompactMembershipProof {
+    fn from(input: &crate::CompactMembershipProof) -> Self {
+        Self {
+            label: MessageField::some((&input.label).into()),
+            hash_val: Some(input.hash_val.0.to_vec()),
+            sibling_proofs: input.sibling_proofs.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+impl TryFrom<&specs::types::CompactMembershipProof> for crate::CompactMembershipProof {
+    type Error = ConversionError;
+
+    fn try_from(input: &specs::types::CompactMembershipProof) -> Result<Self, Self::Error> {
+        require_messagefield!(input, label);
+        require!(input, has_hash_val);
+
+        let label: crate::NodeLabel = input.label.as_ref().unwrap().try_into()?;
+        let hash_val: Digest = hash_from_bytes!(input.hash_val());
+
+        Ok(Self {
+            label,
+            hash_val: AzksValue(hash_val),
+            sibling_proofs: input.sibling_proofs.clone(),
+        })
+    }
+}
+
+// ==============================================================
+// CompactNonMembershipProof
+// ==============================================================
+
+impl From<&crate::CompactNonMembershipProof> for specs::types::CompactNonMembershipProof {
+    fn from(input: &crate::CompactNonMembershipProof) -> Self {
+        Self {
+            label: MessageField::some((&input.label).into()),
+            longest_prefix: MessageField::some((&input.longest_prefix).into()),
+            longest_prefix_children: input
+                .longest_prefix_children
+                .iter()
+                .map(|child| child.into())
+                .collect::<Vec<_>>(),
+            longest_prefix_membership_proof: MessageField::some(
+                (&input.longest_prefix_membership_proof).into(),
+            ),
+            ..Default::default()
+        }
+    }
+}
+
+impl TryFrom<&specs::types::CompactNonMembershipProof> for crate::CompactNonMembershipProof {
+    type Error = ConversionError;
+
+    fn try_from(input: &specs::types::CompactNonMembershipProof) -> Result<Self, Self::Error> {
+        require_messagefield!(input, label);
+        require_messagefield!(input, longest_prefix);
+        require_messagefield!(input, longest_prefix_membership_proof);
+
+        let label: crate::NodeLabel = input.label.as_ref().unwrap().try_into()?;
+        let longest_prefix: crate::NodeLabel = input.longest_prefix.as_ref().unwrap().try_into()?;
+        let longest_prefix_membership_proof: crate::CompactMembershipProof = input
+            .longest_prefix_membership_proof
+            .as_ref()
+            .unwrap()
+            .try_into()?;
+
+        let longest_prefix_children =
+            convert_from_vector!(input.longest_prefix_children, crate::AzksElement);
+
+        Ok(Self {
+            label,
+            longest_prefix,
+            longest_prefix_children: longest_prefix_children.try_into().map_err(|_| {
+                ConversionError::Deserialization(
+                    "Required field longest_prefix_children must be 2 elements long".to_string(),
+                )
+            })?,
+            longest_prefix_membership_proof,
+        })
+    }
+}
+
+// ==============================================================
+// CompactUpdateProof
+// ==============================================================
+
+impl From<&crate::CompactUpdateProof> for specs::types::CompactUpdateProof {
+    fn from(input: &crate::CompactUpdateProof) -> Self {
+        Self {
+            epoch: Some(input.epoch),
+            value: Some(input.value.0.clone()),
+            version: Some(input.version),
+            existence_vrf_proof: Some(input.existence_vrf_proof.clone()),
+            existence_proof: MessageField::some((&input.existence_proof).into()),
+            previous_version_vrf_proof: input.previous_version_vrf_proof.as_ref().cloned(),
+            previous_version_proof: MessageField::from_option(
+                input.previous_version_proof.as_ref().map(|p| p.into()),
+            ),
+            commitment_nonce: Some(input.commitment_nonce.clone()),
+            ..Default::default()
+        }
+    }
+}
+
+impl TryFrom<&specs::types::CompactUpdateProof> for crate::CompactUpdateProof {
+    type Error = ConversionError;
+
+    fn try_from(input: &specs::types::CompactUpdateProof) -> Result<Self, Self::Error> {
+        require!(input, has_epoch);
+        require!(input, has_value);
+        require!(input, has_version);
+        require!(input, has_existence_vrf_proof);
+        require_messagefield!(input, existence_proof);
+        require!(input, has_commitment_nonce);
+        let previous_version_vrf_proof = input
+            .previous_version_vrf_proof
+            .as_ref()
+            .map(|item| item.to_vec());
+        let previous_version_proof: Option<crate::CompactMembershipProof> = input
+            .previous_version_proof
+            .as_ref()
+            .map(|item| item.try_into())
+            .transpose()?;
+
+        Ok(Self {
+            epoch: input.epoch(),
+            value: crate::AkdValue(input.value().to_vec()),
+            version: input.version(),
+            existence_vrf_proof: input.existence_vrf_proof().to_vec(),
+            existence_proof: input.existence_proof.as_ref().unwrap().try_into()?,
+            previous_version_vrf_proof,
+            previous_version_proof,
+            commitment_nonce: input.commitment_nonce().to_vec(),
+        })
+    }
+}
+
+// ==============================================================
+// CompactHistoryProof
+// ==============================================================
+
+impl From<&crate::CompactHistoryProof> for specs::types::CompactHistoryProof {
+    fn from(input: &crate::CompactHistoryProof) -> Self {
+        Self {
+            sibling_proofs: input
+                .sibling_proofs
+                .iter()
+                .map(|proof| proof.into())
+                .collect::<Vec<_>>(),
+            update_proofs: input
+                .update_proofs
+                .iter()
+                .map(|proof| proof.into())
+                .collect::<Vec<_>>(),
+            until_marker_vrf_proofs: input.until_marker_vrf_proofs.to_vec(),
+            non_existence_until_marker_proofs: input
+                .non_existence_until_marker_proofs
+                .iter()
+                .map(|proof| proof.into())
+                .collect::<Vec<_>>(),
+            future_marker_vrf_proofs: input.future_marker_vrf_proofs.to_vec(),
+            non_existence_of_future_marker_proofs: input
+                .non_existence_of_future_marker_proofs
+                .iter()
+                .map(|proof| proof.into())
+                .collect::<Vec<_>>(),
+            ..Default::default()
+        }
+    }
+}
+
+impl TryFrom<&specs::types::CompactHistoryProof> for crate::CompactHistoryProof {
+    type Error = ConversionError;
+
+    fn try_from(input: &specs::types::CompactHistoryProof) -> Result<Self, Self::Error> {
+        let sibling_proofs = convert_from_vector!(input.sibling_proofs, crate::SiblingProof);
+        let update_proofs = convert_from_vector!(input.update_proofs, crate::CompactUpdateProof);
+        let non_existence_until_marker_proofs = convert_from_vector!(
+            input.non_existence_until_marker_proofs,
+            crate::CompactNonMembershipProof
+        );
+        let non_existence_of_future_marker_proofs = convert_from_vector!(
+            input.non_existence_of_future_marker_proofs,
+            crate::CompactNonMembershipProof
+        );
+
+        Ok(Self {
+            sibling_proofs,
+            update_proofs,
+            until_marker_vrf_proofs: input.until_marker_vrf_proofs.to_vec(),
+            non_existence_until_marker_proofs,
+            future_marker_vrf_proofs: input.future_marker_vrf_proofs.to_vec(),
+            non_existence_of_future_marker_proofs,
+        })
+    }
+}
+
+// ==============================================================
+// Versioned proofs
+// ==============================================================
+
+/// Wraps an encoded proof in a [specs::types::VersionedProof] envelope
+fn encode_versioned(
+    version: crate::ProofVersion,
+    payload: &impl protobuf::Message,
+) -> Result<Vec<u8>, ConversionError> {
+    let envelope = specs::types::VersionedProof {
+        version: Some(version as u32),
+        payload: Some(payload.write_to_bytes()?),
+        ..Default::default()
+    };
+    Ok(envelope.write_to_bytes()?)
+}
+
+/// Unwraps a [specs::types::VersionedProof] envelope, returning the version and the encoded
+/// proof. Bytes without a version tag are an unversioned proof, which has the first version.
+fn decode_versioned(bytes: &[u8]) -> Result<(crate::ProofVersion, Vec<u8>), ConversionError> {
+    let envelope = specs::types::VersionedProof::parse_from_bytes(bytes)?;
+    if !envelope.has_version() {
+        return Ok((crate::ProofVersion::V1, bytes.to_vec()));
+    }
+    require!(envelope, has_payload);
+    let version = crate::ProofVersion::from_u32(envelope.version()).ok_or_else(|| {
+        ConversionError::Deserialization(format!(
+            "Unsupported proof version {}",
+            envelope.version()
+        ))
+    })?;
+    Ok((version, envelope.payload().to_vec()))
+}
+
+impl crate::VersionedLookupProof {
+    /// Encodes the proof, tagged with its version
+    pub fn encode(&self) -> Result<Vec<u8>, ConversionError> {
+        match self {
+            Self::V1(proof) => {
+                encode_versioned(self.version(), &specs::types::LookupProof::from(proof))
+            }
+        }
+    }
+
+    /// Decodes a proof produced by [Self::encode], or an unversioned [crate::LookupProof]
+    pub fn decode(bytes: &[u8]) -> Result<Self, ConversionError> {
+        let (version, payload) = decode_versioned(bytes)?;
+        match version {
+            crate::ProofVersion::V1 => Ok(Self::V1(
+                (&specs::types::LookupProof::parse_from_bytes(&payload)?).try_into()?,
+            )),
+            _ => Err(ConversionError::Deserialization(format!(
+                "Unsupported lookup proof version {version:?}"
+            ))),
+        }
+    }
+}
+
+impl crate::VersionedHistoryProof {
+    /// Encodes the proof, tagged with its version
+    pub fn encode(&self) -> Result<Vec<u8>, ConversionError> {
+        match self {
+            Self::V1(proof) => {
+                encode_versioned(self.version(), &specs::types::HistoryProof::from(proof))
+            }
+            Self::V2(proof) => encode_versioned(
+                self.version(),
+                &specs::types::CompactHistoryProof::from(proof),
+            ),
+        }
+    }
+
+    /// Decodes a proof produced by [Self::encode], or an unversioned [crate::HistoryProof]
+    pub fn decode(bytes: &[u8]) -> Result<Self, ConversionError> {
+        let (version, payload) = decode_versioned(bytes)?;
+        match version {
+            crate::ProofVersion::V1 => Ok(Self::V1(
+                (&specs::types::HistoryProof::parse_from_bytes(&payload)?).try_into()?,
+            )),
+            crate::ProofVersion::V2 => Ok(Self::V2(
+                (&specs::types::CompactHistoryProof::parse_from_bytes(&payload)?).try_into()?,
+            )),
+        }
+    }
+}
@@ -9,13 +9,14 @@
 
 use super::specs::types::*;
 use super::*;
+use crate::test_utils::thread_test_rng;
 use crate::{AzksValue, Direction};
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
 // ================= Test helpers ================= //
 
 fn random_hash() -> [u8; 32] {
-    thread_rng().gen::<[u8; 32]>()
+    thread_test_rng().gen::<[u8; 32]>()
 }
 
 fn random_azks_element() -> crate::AzksElement {
@@ -28,11 +29,133 @@ fn random_azks_element() -> crate::AzksElement {
 fn random_label() -> crate::NodeLabel {
     let label = crate::NodeLabel {
         label_val: random_hash(),
-        label_len: thread_rng().gen::<u32>() % 257, // Can be up to 256
+        label_len: thread_test_rng().gen::<u32>() % 257, // Can be up to 256
     };
     label.get_prefix(label.label_len)
 }
 
+fn random_lookup_proof() -> crate::LookupProof {
+    let mut rng = thread_test_rng();
+    crate::LookupProof {
+        epoch: rng.gen(),
+        value: crate::AkdValue(random_hash().to_vec()),
+        version: rng.gen(),
+        existence_vrf_proof: random_hash().to_vec(),
+        existence_proof: crate::MembershipProof {
+            label: random_label(),
+            hash_val: AzksValue(random_hash()),
+            sibling_proofs: vec![crate::SiblingProof {
+                label: random_label(),
+                siblings: [random_azks_element()],
+                direction: Direction::Right,
+            }],
+        },
+        marker_vrf_proof: random_hash().to_vec(),
+        marker_proof: crate::MembershipProof {
+            label: random_label(),
+            hash_val: AzksValue(random_hash()),
+            sibling_proofs: vec![crate::SiblingProof {
+                label: random_label(),
+                siblings: [random_azks_element()],
+                direction: Direction::Right,
+            }],
+        },
+        freshness_vrf_proof: random_hash().to_vec(),
+        freshness_proof: crate::NonMembershipProof {
+            label: random_label(),
+            longest_prefix: random_label(),
+            longest_prefix_children: [random_azks_element(), random_azks_element()],
+            longest_prefix_membership_proof: crate::MembershipProof {
+                label: random_label(),
+                hash_val: AzksValue(random_hash()),
+                sibling_proofs: vec![crate::SiblingProof {
+                    label: random_label(),
+                    siblings: [random_azks_element()],
+                    direction: Direction::Right,
+                }],
+            },
+        },
+        commitment_nonce: random_hash().to_vec(),
+    }
+}
+
+fn random_non_membership_proof() -> crate::NonMembershipProof {
+    crate::NonMembershipProof {
+        label: random_label(),
+        longest_prefix: random_label(),
+        longest_prefix_children: [random_azks_element(), random_azks_element()],
+        longest_prefix_membership_proof: crate::MembershipProof {
+            label: random_label(),
+            hash_val: AzksValue(random_hash()),
+            sibling_proofs: vec![crate::SiblingProof {
+                label: random_label(),
+                siblings: [random_azks_element()],
+                direction: Direction::Right,
+            }],
+        },
+    }
+}
+
+fn random_update_proof() -> crate::UpdateProof {
+    let mut rng = thread_test_rng();
+    crate::UpdateProof {
+        epoch: rng.gen(),
+        value: crate::AkdValue(random_hash().to_vec()),
+        version: rng.gen(),
+        existence_vrf_proof: random_hash().to_vec(),
+        existence_proof: crate::MembershipProof {
+            label: random_label(),
+            hash_val: AzksValue(random_hash()),
+            sibling_proofs: vec![crate::SiblingProof {
+                label: random_label(),
+                siblings: [random_azks_element()],
+                direction: Direction::Right,
+            }],
+        },
+        previous_version_vrf_proof: Some(random_hash().to_vec()),
+        previous_version_proof: Some(crate::MembershipProof {
+            label: random_label(),
+            hash_val: AzksValue(random_hash()),
+            sibling_proofs: vec![crate::SiblingProof {
+                label: random_label(),
+                siblings: [random_azks_element()],
+                direction: Direction::Right,
+            }],
+        }),
+        commitment_nonce: random_hash().to_vec(),
+    }
+}
+
+fn random_history_proof() -> crate::HistoryProof {
+    crate::HistoryProof {
+        update_proofs: vec![
+            random_update_proof(),
+            random_update_proof(),
+            random_update_proof(),
+        ],
+        until_marker_vrf_proofs: vec![
+            random_hash().to_vec(),
+            random_hash().to_vec(),
+            random_hash().to_vec(),
+        ],
+        non_existence_until_marker_proofs: vec![
+            random_non_membership_proof(),
+            random_non_membership_proof(),
+            random_non_membership_proof(),
+        ],
+        future_marker_vrf_proofs: vec![
+            random_hash().to_vec(),
+            random_hash().to_vec(),
+            random_hash().to_vec(),
+        ],
+        non_existence_of_future_marker_proofs: vec![
+            random_non_membership_proof(),
+            random_non_membership_proof(),
+            random_non_membership_proof(),
+        ],
+    }
+}
+
 // ================= Test cases ================= //
 
 #[test]
@@ -102,7 +225,7 @@ fn test_convert_non_membership_proof() {
 
 #[test]
 fn test_convert_lookup_proof() {
-    let mut rng = thread_rng();
+    let mut rng = thread_test_rng();
     let original = crate::LookupProof {
         epoch: rng.gen(),
         value: crate::AkdValue(random_hash().to_vec()),
@@ -151,7 +274,7 @@ fn test_convert_lookup_proof() {
 
 #[test]
 fn test_convert_update_proof() {
-    let mut rng = thread_rng();
+    let mut rng = thread_test_rng();
     let original = crate::UpdateProof {
         epoch: rng.gen(),
         value: crate::AkdValue(random_hash().to_vec()),
@@ -203,7 +326,7 @@ fn test_convert_history_proof() {
     }
 
     fn upd_proof() -> crate::UpdateProof {
-        let mut rng = thread_rng();
+        let mut rng = thread_test_rng();
         crate::UpdateProof {
             epoch: rng.gen(),
             value: crate::AkdValue(random_hash().to_vec()),
@@ -284,6 +407,145 @@ fn test_convert_single_append_only_proof() {
     assert_eq!(original, (&protobuf).try_into().unwrap());
 }
 
+#[test]
+fn test_convert_compact_history_proof() {
+    let original = random_history_proof().compact();
+
+    let protobuf: CompactHistoryProof = (&original).into();
+    assert_eq!(original, (&protobuf).try_into().unwrap());
+}
+
+#[test]
+fn test_versioned_history_proof_roundtrip() {
+    let history_proof = random_history_proof();
+    for version in [crate::ProofVersion::V1, crate::ProofVersion::V2] {
+        let original = crate::VersionedHistoryProof::new(history_proof.clone(), version);
+        let decoded = crate::VersionedHistoryProof::decode(&original.encode().unwrap()).unwrap();
+        assert_eq!(version, decoded.version());
+        assert_eq!(original, decoded);
+        assert_eq!(history_proof, decoded.into_history_proof().unwrap());
+    }
+
+    let original = crate::VersionedLookupProof::V1(random_lookup_proof());
+    let decoded = crate::VersionedLookupProof::decode(&original.encode().unwrap()).unwrap();
+    assert_eq!(original, decoded);
+}
+
+#[test]
+fn test_versioned_proof_accepts_unversioned_encoding() {
+    let history_proof = random_history_proof();
+    let bytes = HistoryProof::from(&history_proof).write_to_bytes().unwrap();
+    assert_eq!(
+        crate::VersionedHistoryProof::V1(history_proof),
+        crate::VersionedHistoryProof::decode(&bytes).unwrap()
+    );
+
+    let lookup_proof = random_lookup_proof();
+    let bytes = LookupProof::from(&lookup_proof).write_to_bytes().unwrap();
+    assert_eq!(
+        crate::VersionedLookupProof::V1(lookup_proof),
+        crate::VersionedLookupProof::decode(&bytes).unwrap()
+    );
+}
+
+#[test]
+fn test_versioned_proof_rejects_unknown_version() {
+    let envelope = VersionedProof {
+        version: Some(u32::MAX),
+        payload: Some(
+            HistoryProof::from(&random_history_proof())
+                .write_to_bytes()
+                .unwrap(),
+        ),
+        ..Default::default()
+    };
+    let bytes = envelope.write_to_bytes().unwrap();
+    assert!(crate::VersionedHistoryProof::decode(&bytes).is_err());
+    assert!(crate::VersionedLookupProof::decode(&bytes).is_err());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_json_proof_roundtrip() {
+    use super::json::JsonProof;
+
+    // Decoding the JSON of a proof gives the same proof, with the same binary encoding
+    let original = random_lookup_proof();
+    let decoded = crate::LookupProof::from_json_str(&original.to_json_string()).unwrap();
+    assert_eq!(
+        LookupProof::from(&original).write_to_bytes().unwrap(),
+        LookupProof::from(&decoded).write_to_bytes().unwrap()
+    );
+    assert_eq!(original, decoded);
+
+    let original = random_history_proof();
+    let decoded = crate::HistoryProof::from_json(&original.to_json()).unwrap();
+    assert_eq!(
+        HistoryProof::from(&original).write_to_bytes().unwrap(),
+        HistoryProof::from(&decoded).write_to_bytes().unwrap()
+    );
+    assert_eq!(original, decoded);
+
+    let original = random_history_proof().compact();
+    let decoded = crate::CompactHistoryProof::from_json(&original.to_json()).unwrap();
+    assert_eq!(original, decoded);
+
+    let original = crate::AppendOnlyProof {
+        proofs: vec![crate::SingleAppendOnlyProof {
+            inserted: vec![random_azks_element(), random_azks_element()],
+            unchanged_nodes: vec![random_azks_element()],
+        }],
+        epochs: vec![thread_test_rng().gen()],
+    };
+    let decoded = crate::AppendOnlyProof::from_json(&original.to_json()).unwrap();
+    assert_eq!(original, decoded);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_json_proof_profile() {
+    use super::json::JsonProof;
+
+    // The field names and the encodings of the fields are part of the profile
+    let proof = crate::MembershipProof {
+        label: crate::NodeLabel::root(),
+        hash_val: AzksValue([0xfb; 32]),
+        sibling_proofs: vec![],
+    };
+    let expected = r#"{"proof":{"hash_val":"-_v7-_v7-_v7-_v7-_v7-_v7-_v7-_v7-_v7-_v7-_s","label":{"label_len":0,"label_val":""}},"type":"MembershipProof","version":1}"#;
+    assert_eq!(expected, proof.to_json_string());
+    assert_eq!(
+        proof,
+        crate::MembershipProof::from_json_str(expected).unwrap()
+    );
+
+    // 64-bit integers are strings, but numbers are accepted
+    let proof = crate::AppendOnlyProof {
+        proofs: vec![],
+        epochs: vec![u64::MAX, 1],
+    };
+    let json = proof.to_json();
+    assert_eq!(
+        serde_json::json!(["18446744073709551615", "1"]),
+        json["proof"]["epochs"]
+    );
+    let mut numbers = json.clone();
+    numbers["proof"]["epochs"] = serde_json::json!([u64::MAX, 1]);
+    assert_eq!(proof, crate::AppendOnlyProof::from_json(&numbers).unwrap());
+
+    // The version, the type and the fields of the envelope are checked
+    let mut wrong_version = json.clone();
+    wrong_version["version"] = serde_json::json!(2);
+    assert!(crate::AppendOnlyProof::from_json(&wrong_version).is_err());
+    assert!(crate::HistoryProof::from_json(&json).is_err());
+    let mut unknown_field = json.clone();
+    unknown_field["proof"]["epoch"] = serde_json::json!("1");
+    assert!(crate::AppendOnlyProof::from_json(&unknown_field).is_err());
+    let mut padded = serde_json::from_str::<serde_json::Value>(expected).unwrap();
+    padded["proof"]["hash_val"] = serde_json::json!("-_v7-_v7-_v7-_v7-_v7-_v7-_v7-_v7-_v7-_v7-_s=");
+    assert!(crate::MembershipProof::from_json(&padded).is_err());
+}
+
 #[test]
 fn test_minimum_encoding_label_bytes() {
     let full_label: [u8; 32] = [
@@ -341,3 +603,111 @@ fn test_label_len_too_large() {
 
     assert!(crate::NodeLabel::try_from(&proto_label).is_err());
 }
+
+// ================= Wire compatibility ================= //
+
+/// The environment variable which, when set, records the fields which have been added to the
+/// spec in the wire contract
+const UPDATE_WIRE_CONTRACT_ENV_VAR: &str = "AKD_UPDATE_WIRE_CONTRACT";
+
+const WIRE_CONTRACT_PATH: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/src/proto/specs/wire_contract.txt"
+);
+
+/// One line per field of the compiled spec: its message and name, number, label and type
+fn wire_contract_of_spec() -> Vec<String> {
+    let mut lines = vec![];
+    for message in file_descriptor().messages() {
+        for field in message.fields() {
+            let proto = field.proto();
+            let label = format!("{:?}", proto.label()).replace("LABEL_", "");
+            let field_type = match proto.type_name() {
+                "" => format!("{:?}", proto.type_())
+                    .replace("TYPE_", "")
+                    .to_lowercase(),
+                type_name => type_name.to_string(),
+            };
+            lines.push(format!(
+                "{}.{} = {} {} {}",
+                message.full_name(),
+                field.name(),
+                field.number(),
+                label.to_lowercase(),
+                field_type
+            ));
+        }
+    }
+    lines
+}
+
+#[test]
+fn test_wire_contract() {
+    let spec = wire_contract_of_spec();
+    let contract = std::fs::read_to_string(WIRE_CONTRACT_PATH).unwrap_or_default();
+    let contract = contract
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+
+    let broken = contract
+        .iter()
+        .filter(|line| !spec.contains(line))
+        .collect::<Vec<_>>();
+    assert!(
+        broken.is_empty(),
+        "The spec breaks the wire contract of the fields {broken:#?}. Existing fields cannot be \
+         changed or removed (see src/proto/specs/README.md)"
+    );
+
+    let added = spec
+        .iter()
+        .filter(|line| !contract.contains(line))
+        .collect::<Vec<_>>();
+    if added.is_empty() {
+        return;
+    }
+    assert!(
+        std::env::var(UPDATE_WIRE_CONTRACT_ENV_VAR).is_ok(),
+        "The fields {added:#?} are missing from the wire contract. Run the test with \
+         {UPDATE_WIRE_CONTRACT_ENV_VAR}=1 to add them"
+    );
+    let mut file = "# The wire contract of the akd.v1 protobuf package, which is checked by\n\
+                    # test_wire_contract. Lines may be added, but never changed or removed\n\
+                    # (see README.md).\n"
+        .to_string();
+    for line in contract.iter().chain(added) {
+        file.push_str(line);
+        file.push('\n');
+    }
+    std::fs::write(WIRE_CONTRACT_PATH, file).unwrap();
+}
+
+#[test]
+fn test_wire_contract_of_spec() {
+    let spec = wire_contract_of_spec();
+    assert!(spec.contains(&"akd.v1.LookupProof.epoch = 1 optional uint64".to_string()));
+    assert!(spec.contains(
+        &"akd.v1.HistoryProof.update_proofs = 1 repeated .akd.v1.UpdateProof".to_string()
+    ));
+}
+
+#[test]
+fn test_decoding_ignores_unknown_fields() {
+    // a proof from a later release of the package, with a field this release does not know
+    let lookup_proof = random_lookup_proof();
+    let mut bytes = LookupProof::from(&lookup_proof).write_to_bytes().unwrap();
+    let mut os = protobuf::CodedOutputStream::vec(&mut bytes);
+    os.write_uint64(1000, 42).unwrap();
+    os.write_bytes(1001, b"a field from the future").unwrap();
+    os.flush().unwrap();
+    drop(os);
+
+    let decoded = LookupProof::parse_from_bytes(&bytes).unwrap();
+    assert_eq!(lookup_proof, (&decoded).try_into().unwrap());
+    assert_eq!(
+        crate::VersionedLookupProof::V1(lookup_proof),
+        crate::VersionedLookupProof::decode(&bytes).unwrap()
+    );
+}
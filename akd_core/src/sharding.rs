@@ -0,0 +1,313 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! The commitment of a sharded directory, whose label space is split across several trees.
+//!
+//! Each label belongs to the shard given by [shard_index], a hash of the (normalized) label
+//! reduced modulo the number of shards, and each shard is a complete tree of its own, with
+//! its own epochs. The latest [ShardRoot]s of the shards are combined into a single
+//! top-level root hash, which is the root of a binary Merkle tree over the shards:
+//!
+//! ```text
+//! leaf(i)      = H("akd.shard.leaf" || i (u32) || epoch (u64) || root hash of shard i)
+//! node(l, r)   = H("akd.shard.node" || l || r)
+//! top-level    = H("akd.shard.root" || number of shards (u32) || root of the tree of leaves)
+//! ```
+//!
+//! with integers in big-endian order and `H` the hash function of the configuration. The
+//! number of shards is a power of two (at most [MAX_SHARDS]), so that the tree is perfect.
+//!
+//! A proof of a sharded directory is the proof of the label's shard, along with a
+//! [ShardInclusionProof] which shows that the shard's root hash is committed to by the
+//! top-level root hash. Clients verify the two together with the functions of
+//! [crate::verify::sharded].
+
+use crate::configuration::Configuration;
+use crate::hash::Digest;
+#[cfg(feature = "serde_serialization")]
+use crate::utils::serde_helpers::{bytes_deserialize_hex, bytes_serialize_hex};
+use crate::verify::VerificationError;
+use crate::{AkdLabel, HistoryProof, LookupProof};
+
+#[cfg(feature = "nostd")]
+use alloc::format;
+#[cfg(feature = "nostd")]
+use alloc::vec;
+#[cfg(feature = "nostd")]
+use alloc::vec::Vec;
+
+/// The maximum number of shards of a sharded directory
+pub const MAX_SHARDS: u32 = 1 << 16;
+
+const SHARD_INDEX_DOMAIN: &[u8] = b"akd.shard.index";
+const SHARD_LEAF_DOMAIN: &[u8] = b"akd.shard.leaf";
+const SHARD_NODE_DOMAIN: &[u8] = b"akd.shard.node";
+const SHARD_ROOT_DOMAIN: &[u8] = b"akd.shard.root";
+
+/// The latest epoch of a shard, and its root hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct ShardRoot {
+    /// The epoch of the shard
+    pub epoch: u64,
+    /// The root hash of the shard at the epoch
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(serialize_with = "bytes_serialize_hex")
+    )]
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(deserialize_with = "bytes_deserialize_hex")
+    )]
+    pub root_hash: Digest,
+}
+
+/// A proof that the root of a shard is committed to by a top-level root hash
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct ShardInclusionProof {
+    /// The index of the shard
+    pub shard: u32,
+    /// The number of shards of the directory
+    pub num_shards: u32,
+    /// The root of the shard
+    pub shard_root: ShardRoot,
+    /// The hashes of the siblings of the nodes along the path from the shard's leaf to the
+    /// root of the tree of leaves, from the leaf up
+    pub siblings: Vec<Digest>,
+}
+
+/// A lookup proof of a sharded directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct ShardedLookupProof {
+    /// The proof that the root of the label's shard is committed to by the top-level root
+    pub inclusion: ShardInclusionProof,
+    /// The lookup proof of the label within its shard
+    pub proof: LookupProof,
+}
+
+/// A history proof of a sharded directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct ShardedHistoryProof {
+    /// The proof that the root of the label's shard is committed to by the top-level root
+    pub inclusion: ShardInclusionProof,
+    /// The history proof of the label within its shard
+    pub proof: HistoryProof,
+}
+
+/// Checks that a number of shards is a power of two of at most [MAX_SHARDS]
+pub fn check_num_shards(num_shards: u32) -> Result<(), VerificationError> {
+    if !num_shards.is_power_of_two() || num_shards > MAX_SHARDS {
+        return Err(VerificationError::ShardProof(format!(
+            "The number of shards must be a power of two of at most {MAX_SHARDS}, but is {num_shards}"
+        )));
+    }
+    Ok(())
+}
+
+/// Returns the index of the shard which a label belongs to, out of `num_shards` shards
+pub fn shard_index<TC: Configuration>(label: &AkdLabel, num_shards: u32) -> u32 {
+    let label = TC::normalize_label(label);
+    let hash = TC::hash(&[SHARD_INDEX_DOMAIN, &label.0].concat());
+    let mut prefix = [0u8; 8];
+    prefix.copy_from_slice(&hash[..8]);
+    (u64::from_be_bytes(prefix) % u64::from(num_shards.max(1))) as u32
+}
+
+fn leaf_hash<TC: Configuration>(shard: u32, shard_root: &ShardRoot) -> Digest {
+    TC::hash(
+        &[
+            SHARD_LEAF_DOMAIN,
+            &shard.to_be_bytes(),
+            &shard_root.epoch.to_be_bytes(),
+            &shard_root.root_hash,
+        ]
+        .concat(),
+    )
+}
+
+fn node_hash<TC: Configuration>(left: &Digest, right: &Digest) -> Digest {
+    TC::hash(&[SHARD_NODE_DOMAIN, left, right].concat())
+}
+
+fn top_level_hash<TC: Configuration>(num_shards: u32, tree_root: &Digest) -> Digest {
+    TC::hash(&[SHARD_ROOT_DOMAIN, &num_shards.to_be_bytes(), tree_root].concat())
+}
+
+/// The levels of the tree of leaves, from the leaves up to the root
+fn tree_levels<TC: Configuration>(
+    shard_roots: &[ShardRoot],
+) -> Result<Vec<Vec<Digest>>, VerificationError> {
+    let num_shards = u32::try_from(shard_roots.len()).unwrap_or(u32::MAX);
+    check_num_shards(num_shards)?;
+    let mut levels = vec![shard_roots
+        .iter()
+        .enumerate()
+        .map(|(shard, shard_root)| leaf_hash::<TC>(shard as u32, shard_root))
+        .collect::<Vec<_>>()];
+    while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+        let parents = level
+            .chunks(2)
+            .map(|pair| node_hash::<TC>(&pair[0], &pair[1]))
+            .collect();
+        levels.push(parents);
+    }
+    Ok(levels)
+}
+
+/// Computes the top-level root hash of the roots of the shards, in order of their indices
+pub fn top_level_root_hash<TC: Configuration>(
+    shard_roots: &[ShardRoot],
+) -> Result<Digest, VerificationError> {
+    let levels = tree_levels::<TC>(shard_roots)?;
+    Ok(top_level_hash::<TC>(
+        shard_roots.len() as u32,
+        &levels[levels.len() - 1][0],
+    ))
+}
+
+/// Generates the proof that the root of a shard is committed to by the top-level root hash
+/// of the roots of the shards, in order of their indices
+pub fn shard_inclusion_proof<TC: Configuration>(
+    shard_roots: &[ShardRoot],
+    shard: u32,
+) -> Result<ShardInclusionProof, VerificationError> {
+    let levels = tree_levels::<TC>(shard_roots)?;
+    let shard_root = *shard_roots.get(shard as usize).ok_or_else(|| {
+        VerificationError::ShardProof(format!(
+            "Shard {shard} is out of range of {} shards",
+            shard_roots.len()
+        ))
+    })?;
+    let siblings = levels[..levels.len() - 1]
+        .iter()
+        .enumerate()
+        .map(|(height, level)| level[((shard as usize) >> height) ^ 1])
+        .collect();
+    Ok(ShardInclusionProof {
+        shard,
+        num_shards: shard_roots.len() as u32,
+        shard_root,
+        siblings,
+    })
+}
+
+impl ShardInclusionProof {
+    /// Verifies that the root of the shard is committed to by a top-level root hash
+    pub fn verify<TC: Configuration>(
+        &self,
+        top_level_root: Digest,
+    ) -> Result<(), VerificationError> {
+        check_num_shards(self.num_shards)?;
+        if self.shard >= self.num_shards {
+            return Err(VerificationError::ShardProof(format!(
+                "Shard {} is out of range of {} shards",
+                self.shard, self.num_shards
+            )));
+        }
+        if 1usize << self.siblings.len() != self.num_shards as usize {
+            return Err(VerificationError::ShardProof(format!(
+                "The path of the shard has {} siblings, rather than the depth of a tree of {} shards",
+                self.siblings.len(),
+                self.num_shards
+            )));
+        }
+        let mut hash = leaf_hash::<TC>(self.shard, &self.shard_root);
+        for (height, sibling) in self.siblings.iter().enumerate() {
+            hash = if (self.shard >> height) & 1 == 0 {
+                node_hash::<TC>(&hash, sibling)
+            } else {
+                node_hash::<TC>(sibling, &hash)
+            };
+        }
+        if top_level_hash::<TC>(self.num_shards, &hash) != top_level_root {
+            return Err(VerificationError::ShardProof(format!(
+                "The root of shard {} is not committed to by the top-level root hash",
+                self.shard
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExperimentalConfiguration;
+
+    type TC = ExperimentalConfiguration<crate::ExampleLabel>;
+
+    fn shard_roots(num_shards: u32) -> Vec<ShardRoot> {
+        (0..num_shards)
+            .map(|shard| ShardRoot {
+                epoch: u64::from(shard) + 1,
+                root_hash: [shard as u8; 32],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_shard_inclusion_proofs() {
+        for num_shards in [1, 2, 8] {
+            let roots = shard_roots(num_shards);
+            let top_level_root = top_level_root_hash::<TC>(&roots).unwrap();
+            for shard in 0..num_shards {
+                let proof = shard_inclusion_proof::<TC>(&roots, shard).unwrap();
+                assert_eq!(num_shards.trailing_zeros() as usize, proof.siblings.len());
+                proof.verify::<TC>(top_level_root).unwrap();
+
+                // the proof binds the shard's epoch, root hash and index
+                let mut forged = proof.clone();
+                forged.shard_root.epoch += 1;
+                assert!(forged.verify::<TC>(top_level_root).is_err());
+                let mut forged = proof.clone();
+                forged.shard_root.root_hash[0] ^= 1;
+                assert!(forged.verify::<TC>(top_level_root).is_err());
+                if num_shards > 1 {
+                    let mut forged = proof.clone();
+                    forged.shard ^= 1;
+                    assert!(forged.verify::<TC>(top_level_root).is_err());
+                }
+            }
+        }
+
+        // the number of shards is committed to
+        let roots = shard_roots(2);
+        let top_level_root = top_level_root_hash::<TC>(&roots).unwrap();
+        let mut proof = shard_inclusion_proof::<TC>(&roots, 0).unwrap();
+        proof.num_shards = 1;
+        proof.siblings.clear();
+        assert!(proof.verify::<TC>(top_level_root).is_err());
+    }
+
+    #[test]
+    fn test_num_shards() {
+        assert!(top_level_root_hash::<TC>(&[]).is_err());
+        assert!(top_level_root_hash::<TC>(&shard_roots(3)).is_err());
+        assert!(shard_inclusion_proof::<TC>(&shard_roots(4), 4).is_err());
+        assert!(check_num_shards(MAX_SHARDS).is_ok());
+        assert!(check_num_shards(MAX_SHARDS * 2).is_err());
+
+        let label = AkdLabel::from("label");
+        assert_eq!(0, shard_index::<TC>(&label, 1));
+        assert!((0..64).all(|i| shard_index::<TC>(&AkdLabel::from(format!("{i}").as_str()), 4) < 4));
+    }
+}
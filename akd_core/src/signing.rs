@@ -0,0 +1,359 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Signing of epoch root hashes, so that a root hash can be delivered to a client
+//! alongside a proof without the client needing to obtain it over a separate channel.
+//!
+//! The directory signs the message produced by [epoch_signature_message], which binds
+//! together the [configuration_fingerprint], the epoch, and the root hash for that epoch.
+//! When the directory is rolled back to an earlier epoch, it instead signs the message
+//! produced by [rollback_signature_message], which binds together the epochs and root
+//! hashes from before and after the rollback.
+//!
+//! The root hashes can be signed by a short-lived online key rather than by the
+//! directory's root key, which can then be kept offline. The root key certifies the online
+//! key for a range of epochs with a [DelegationCertificate] (see
+//! [issue_delegation_certificate]), and the online key signs through a [DelegatedSigner],
+//! which serves the certificate alongside its signatures. A client holding only the root
+//! public key checks the chain with [verify_epoch_signature], so a compromised online key
+//! can only sign the root hashes of the epochs it was certified for. Rollback records are
+//! always verified against the root key.
+
+use crate::configuration::Configuration;
+use crate::hash::Digest;
+use crate::{AzksValue, DelegationCertificate, SignedRollbackRecord};
+
+#[cfg(feature = "nostd")]
+use alloc::boxed::Box;
+#[cfg(feature = "nostd")]
+use alloc::vec::Vec;
+
+/// The domain separator for computing a configuration fingerprint
+const FINGERPRINT_DOMAIN: &[u8] = b"AKD configuration fingerprint";
+/// The domain separator for the message which is signed for an epoch
+const EPOCH_SIGNATURE_DOMAIN: &[u8] = b"AKD epoch signature";
+/// The domain separator for the message which is signed for a rollback
+const ROLLBACK_SIGNATURE_DOMAIN: &[u8] = b"AKD rollback signature";
+/// The domain separator for the message which is signed for a delegation certificate
+const DELEGATION_DOMAIN: &[u8] = b"AKD delegation certificate";
+
+/// Produces signatures over epoch root hashes
+pub trait EpochSigner {
+    /// Signs the provided message, returning the encoded signature
+    fn sign_epoch_message(&self, message: &[u8]) -> Vec<u8>;
+
+    /// The certificate delegating the signing of root hashes to this signer, if it is not
+    /// the directory's root key
+    fn delegation_certificate(&self) -> Option<DelegationCertificate> {
+        None
+    }
+}
+
+/// Verifies signatures over epoch root hashes produced by an [EpochSigner]
+pub trait EpochSignatureVerifier {
+    /// Returns whether the signature is a valid signature of the message
+    fn verify_epoch_message(&self, message: &[u8], signature: &[u8]) -> bool;
+
+    /// Returns a verifier for the public key of a delegate of this verifier's key, in the
+    /// encoding of its signature scheme. By default, delegation is not supported.
+    fn delegate_verifier(&self, _public_key: &[u8]) -> Option<Box<dyn EpochSignatureVerifier>> {
+        None
+    }
+}
+
+#[cfg(feature = "vrf")]
+impl EpochSigner for ed25519_dalek::SigningKey {
+    fn sign_epoch_message(&self, message: &[u8]) -> Vec<u8> {
+        use ed25519_dalek::Signer;
+        self.sign(message).to_bytes().to_vec()
+    }
+}
+
+#[cfg(feature = "vrf")]
+impl EpochSignatureVerifier for ed25519_dalek::VerifyingKey {
+    fn verify_epoch_message(&self, message: &[u8], signature: &[u8]) -> bool {
+        match ed25519_dalek::Signature::from_slice(signature) {
+            Ok(signature) => self.verify_strict(message, &signature).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    fn delegate_verifier(&self, public_key: &[u8]) -> Option<Box<dyn EpochSignatureVerifier>> {
+        let public_key = ed25519_dalek::VerifyingKey::try_from(public_key).ok()?;
+        Some(Box::new(public_key))
+    }
+}
+
+/// An [EpochSigner] for an online key whose signing of root hashes has been delegated by
+/// the directory's root key, which serves its [DelegationCertificate] with its signatures
+pub struct DelegatedSigner<S: EpochSigner> {
+    signer: S,
+    certificate: DelegationCertificate,
+}
+
+impl<S: EpochSigner> DelegatedSigner<S> {
+    /// Creates a signer for the delegate's signing key and the certificate issued to it
+    pub fn new(signer: S, certificate: DelegationCertificate) -> Self {
+        Self {
+            signer,
+            certificate,
+        }
+    }
+
+    /// Returns whether the certificate allows the delegate to sign the root hash of the epoch
+    pub fn covers_epoch(&self, epoch: u64) -> bool {
+        (self.certificate.first_epoch..=self.certificate.last_epoch).contains(&epoch)
+    }
+}
+
+impl<S: EpochSigner> EpochSigner for DelegatedSigner<S> {
+    fn sign_epoch_message(&self, message: &[u8]) -> Vec<u8> {
+        self.signer.sign_epoch_message(message)
+    }
+
+    fn delegation_certificate(&self) -> Option<DelegationCertificate> {
+        Some(self.certificate.clone())
+    }
+}
+
+/// Computes a fingerprint of a [Configuration], which differs between configurations
+/// (and domain labels) whose hashes are incompatible. A client can compare the fingerprint
+/// carried by a response against its own to detect that it was produced by a directory
+/// running a different configuration.
+///
+/// The length of the node labels (see [Configuration::node_label_len]) is only included
+/// when the node labels are truncated, so that the fingerprints of configurations with
+/// full-length node labels are unchanged.
+pub fn configuration_fingerprint<TC: Configuration>() -> Digest {
+    let empty_leaf = TC::hash_leaf_with_commitment(TC::empty_node_hash(), 0);
+    let node_label_len = match TC::node_label_len() {
+        256 => Vec::new(),
+        len => len.to_be_bytes().to_vec(),
+    };
+    TC::hash(
+        &[
+            FINGERPRINT_DOMAIN,
+            &TC::empty_root_value().0,
+            &TC::empty_node_hash().0,
+            &TC::stale_azks_value().0,
+            &empty_leaf.0,
+            &TC::compute_root_hash_from_val(&AzksValue(TC::empty_node_hash().0)),
+            &node_label_len,
+        ]
+        .concat(),
+    )
+}
+
+/// The message which is signed by the directory for the root hash of an epoch
+pub fn epoch_signature_message<TC: Configuration>(epoch: u64, root_hash: &Digest) -> Vec<u8> {
+    [
+        EPOCH_SIGNATURE_DOMAIN,
+        &configuration_fingerprint::<TC>(),
+        &epoch.to_be_bytes(),
+        root_hash,
+    ]
+    .concat()
+}
+
+/// The message which is signed by the directory when it is rolled back from the head
+/// `from_epoch` (with root hash `from_root_hash`) to the earlier `to_epoch`
+pub fn rollback_signature_message<TC: Configuration>(
+    from_epoch: u64,
+    from_root_hash: &Digest,
+    to_epoch: u64,
+    to_root_hash: &Digest,
+) -> Vec<u8> {
+    [
+        ROLLBACK_SIGNATURE_DOMAIN,
+        &configuration_fingerprint::<TC>(),
+        &from_epoch.to_be_bytes(),
+        from_root_hash,
+        &to_epoch.to_be_bytes(),
+        to_root_hash,
+    ]
+    .concat()
+}
+
+/// The message which is signed by the root key to delegate the signing of the root hashes
+/// from `first_epoch` to `last_epoch` (inclusive) to the key `delegate_public_key`
+pub fn delegation_certificate_message<TC: Configuration>(
+    delegate_public_key: &[u8],
+    first_epoch: u64,
+    last_epoch: u64,
+) -> Vec<u8> {
+    [
+        DELEGATION_DOMAIN,
+        &configuration_fingerprint::<TC>(),
+        &(delegate_public_key.len() as u64).to_be_bytes(),
+        delegate_public_key,
+        &first_epoch.to_be_bytes(),
+        &last_epoch.to_be_bytes(),
+    ]
+    .concat()
+}
+
+/// Issues a certificate with the root key, delegating the signing of the root hashes from
+/// `first_epoch` to `last_epoch` (inclusive) to the key `delegate_public_key`
+pub fn issue_delegation_certificate<TC: Configuration>(
+    root_signer: &impl EpochSigner,
+    delegate_public_key: Vec<u8>,
+    first_epoch: u64,
+    last_epoch: u64,
+) -> DelegationCertificate {
+    let signature = root_signer.sign_epoch_message(&delegation_certificate_message::<TC>(
+        &delegate_public_key,
+        first_epoch,
+        last_epoch,
+    ));
+    DelegationCertificate {
+        delegate_public_key,
+        first_epoch,
+        last_epoch,
+        signature,
+    }
+}
+
+/// Returns whether the certificate was signed by the root key, and delegates the signing of
+/// the root hash of the epoch
+pub fn verify_delegation_certificate<TC: Configuration>(
+    root_verifier: &impl EpochSignatureVerifier,
+    certificate: &DelegationCertificate,
+    epoch: u64,
+) -> bool {
+    (certificate.first_epoch..=certificate.last_epoch).contains(&epoch)
+        && root_verifier.verify_epoch_message(
+            &delegation_certificate_message::<TC>(
+                &certificate.delegate_public_key,
+                certificate.first_epoch,
+                certificate.last_epoch,
+            ),
+            &certificate.signature,
+        )
+}
+
+/// Returns whether the signature over the root hash of the epoch was produced by the root
+/// key, or, if a delegation certificate is provided, by a delegate which the root key
+/// certified for the epoch
+pub fn verify_epoch_signature<TC: Configuration>(
+    root_verifier: &impl EpochSignatureVerifier,
+    delegation: Option<&DelegationCertificate>,
+    epoch: u64,
+    root_hash: &Digest,
+    signature: &[u8],
+) -> bool {
+    let message = epoch_signature_message::<TC>(epoch, root_hash);
+    match delegation {
+        None => root_verifier.verify_epoch_message(&message, signature),
+        Some(certificate) => {
+            verify_delegation_certificate::<TC>(root_verifier, certificate, epoch)
+                && root_verifier
+                    .delegate_verifier(&certificate.delegate_public_key)
+                    .is_some_and(|delegate| delegate.verify_epoch_message(&message, signature))
+        }
+    }
+}
+
+/// Returns whether the record was signed by the directory, and describes a rollback
+/// to an epoch before the one it was rolled back from
+pub fn verify_rollback_record<TC: Configuration>(
+    verifier: &impl EpochSignatureVerifier,
+    record: &SignedRollbackRecord,
+) -> bool {
+    record.to_epoch < record.from_epoch
+        && verifier.verify_epoch_message(
+            &rollback_signature_message::<TC>(
+                record.from_epoch,
+                &record.from_root_hash,
+                record.to_epoch,
+                &record.to_root_hash,
+            ),
+            &record.signature,
+        )
+}
+
+#[cfg(all(test, feature = "vrf"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ed25519_epoch_signature() {
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]);
+        let verifying_key = signing_key.verifying_key();
+
+        let signature = signing_key.sign_epoch_message(b"message");
+        assert!(verifying_key.verify_epoch_message(b"message", &signature));
+        assert!(!verifying_key.verify_epoch_message(b"other message", &signature));
+        assert!(!verifying_key.verify_epoch_message(b"message", &signature[1..]));
+    }
+
+    #[cfg(feature = "experimental")]
+    #[test]
+    fn test_truncated_configuration_fingerprint() {
+        type TC = crate::ExperimentalConfiguration<crate::ExampleLabel>;
+        type Truncated = crate::configuration::TruncatedLabelConfiguration<TC, 128>;
+        type Full = crate::configuration::TruncatedLabelConfiguration<TC, 256>;
+        assert_ne!(
+            configuration_fingerprint::<TC>(),
+            configuration_fingerprint::<Truncated>()
+        );
+        // Truncating to the full length leaves the tree, and so the fingerprint, unchanged
+        assert_eq!(
+            configuration_fingerprint::<TC>(),
+            configuration_fingerprint::<Full>()
+        );
+    }
+
+    #[cfg(feature = "experimental")]
+    #[test]
+    fn test_delegated_epoch_signature() {
+        type TC = crate::ExperimentalConfiguration<crate::ExampleLabel>;
+        let root_key = ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]);
+        let root_verifier = root_key.verifying_key();
+        let online_key = ed25519_dalek::SigningKey::from_bytes(&[8u8; 32]);
+        let certificate = issue_delegation_certificate::<TC>(
+            &root_key,
+            online_key.verifying_key().to_bytes().to_vec(),
+            10,
+            20,
+        );
+        let signer = DelegatedSigner::new(online_key, certificate.clone());
+        assert_eq!(Some(certificate.clone()), signer.delegation_certificate());
+
+        let sign = |epoch: u64| {
+            signer.sign_epoch_message(&epoch_signature_message::<TC>(epoch, &[epoch as u8; 32]))
+        };
+        let verify = |epoch: u64, delegation: Option<&DelegationCertificate>, signature: &[u8]| {
+            verify_epoch_signature::<TC>(
+                &root_verifier,
+                delegation,
+                epoch,
+                &[epoch as u8; 32],
+                signature,
+            )
+        };
+        assert!(signer.covers_epoch(10) && signer.covers_epoch(20));
+        assert!(verify(15, Some(&certificate), &sign(15)));
+        // The delegate's signature is not accepted as the root key's
+        assert!(!verify(15, None, &sign(15)));
+        // The delegate cannot sign outside of its certified epochs
+        assert!(!verify(21, Some(&certificate), &sign(21)));
+        assert!(!verify(9, Some(&certificate), &sign(9)));
+
+        // A certificate whose epochs were extended is rejected
+        let mut extended = certificate.clone();
+        extended.last_epoch = 30;
+        assert!(!verify(25, Some(&extended), &sign(25)));
+
+        // A certificate for another key is rejected
+        let mut substituted = certificate;
+        substituted.delegate_public_key = ed25519_dalek::SigningKey::from_bytes(&[9u8; 32])
+            .verifying_key()
+            .to_bytes()
+            .to_vec();
+        assert!(!verify(15, Some(&substituted), &sign(15)));
+    }
+}
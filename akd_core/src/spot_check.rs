@@ -0,0 +1,222 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Verifiable random spot checks of an epoch, for lightweight auditors which cannot afford
+//! to verify the full append-only proof of every epoch.
+//!
+//! A spot check of an epoch samples leaves of the tree at that epoch, which are chosen by
+//! 256-bit targets derived from the root hash of the epoch:
+//!
+//! ```text
+//! seed      = H("akd.spot_check.seed" || epoch (u64) || root hash)
+//! target(i) = H("akd.spot_check.target" || seed || i (u64))
+//! ```
+//!
+//! with integers in big-endian order and `H` the hash function of the configuration. Since
+//! the targets depend on the root hash, they are unpredictable to the server until it has
+//! committed to the epoch. The sampled leaf of a target is found by walking down from the
+//! root, and at each node taking the child given by the bit of the target at the depth of
+//! the node's label. The server proves the membership of each sampled leaf, along with the
+//! commitment and epoch of insertion which form the leaf's hash, and the auditor checks
+//! that the path of each proof follows its target, so that the server cannot choose which
+//! leaves are revealed.
+//!
+//! Each sample shows that a uniformly chosen region of the tree holds a well-formed leaf,
+//! which was inserted no later than the epoch being checked. A server which has corrupted
+//! a fraction `f` of the leaves (weighted by the size of their region of the label space)
+//! escapes detection by a spot check of `n` samples with a probability of `(1 - f)^n`.
+
+use crate::configuration::Configuration;
+use crate::hash::Digest;
+use crate::verify::base::verify_membership;
+use crate::verify::VerificationError;
+use crate::{AzksValue, Direction, MembershipProof};
+
+#[cfg(feature = "nostd")]
+use alloc::format;
+#[cfg(feature = "nostd")]
+use alloc::vec::Vec;
+
+/// The maximum number of samples of a spot check
+pub const MAX_SPOT_CHECK_SAMPLES: u64 = 1 << 12;
+
+const SPOT_CHECK_SEED_DOMAIN: &[u8] = b"akd.spot_check.seed";
+const SPOT_CHECK_TARGET_DOMAIN: &[u8] = b"akd.spot_check.target";
+
+/// A leaf sampled by a spot check, along with the proof of its membership
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct SpotCheckSample {
+    /// The commitment to the value of the leaf
+    pub commitment: AzksValue,
+    /// The epoch at which the leaf was inserted
+    pub leaf_epoch: u64,
+    /// The proof of membership of the leaf, whose path follows the sample's target
+    pub proof: MembershipProof,
+}
+
+/// The proof of a spot check of an epoch, with one sample for each target derived from the
+/// root hash of the epoch, in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct SpotCheckProof {
+    /// The epoch which was spot checked
+    pub epoch: u64,
+    /// The sampled leaves
+    pub samples: Vec<SpotCheckSample>,
+}
+
+/// The targets of a spot check of `sample_size` samples of the epoch with the given root hash
+pub fn spot_check_targets<TC: Configuration>(
+    root_hash: Digest,
+    epoch: u64,
+    sample_size: u64,
+) -> Vec<Digest> {
+    let seed = TC::hash(&[SPOT_CHECK_SEED_DOMAIN, &epoch.to_be_bytes(), &root_hash].concat());
+    (0..sample_size)
+        .map(|i| TC::hash(&[SPOT_CHECK_TARGET_DOMAIN, &seed, &i.to_be_bytes()].concat()))
+        .collect()
+}
+
+/// The direction taken towards a target by a node whose label has `depth` bits, which is
+/// given by the bit of the target at that index (most significant bit first). The depth
+/// must be below 256.
+pub fn target_direction(target: &Digest, depth: u32) -> Direction {
+    let byte = target[(depth / 8) as usize];
+    if (byte >> (7 - depth % 8)) & 1 == 1 {
+        Direction::Right
+    } else {
+        Direction::Left
+    }
+}
+
+/// Verifies a spot check of `sample_size` samples of an epoch against the epoch's root hash.
+/// The spot check of an empty tree has no samples.
+pub fn spot_check_verify<TC: Configuration>(
+    root_hash: Digest,
+    epoch: u64,
+    sample_size: u64,
+    proof: &SpotCheckProof,
+) -> Result<(), VerificationError> {
+    if proof.epoch != epoch {
+        return Err(VerificationError::SpotCheck(format!(
+            "The spot check is of epoch {}, but epoch {epoch} was expected",
+            proof.epoch
+        )));
+    }
+    if sample_size > MAX_SPOT_CHECK_SAMPLES {
+        return Err(VerificationError::SpotCheck(format!(
+            "The sample size {sample_size} exceeds the maximum of {MAX_SPOT_CHECK_SAMPLES}"
+        )));
+    }
+    if proof.samples.is_empty()
+        && root_hash == TC::compute_root_hash_from_val(&TC::empty_root_value())
+    {
+        return Ok(());
+    }
+    if proof.samples.len() as u64 != sample_size {
+        return Err(VerificationError::SpotCheck(format!(
+            "The spot check has {} samples, but {sample_size} were expected",
+            proof.samples.len()
+        )));
+    }
+
+    let targets = spot_check_targets::<TC>(root_hash, epoch, sample_size);
+    for (i, (sample, target)) in proof.samples.iter().zip(targets.iter()).enumerate() {
+        verify_sample::<TC>(root_hash, epoch, sample, target)
+            .map_err(|err| VerificationError::SpotCheck(format!("Sample {i}: {err}")))?;
+    }
+    Ok(())
+}
+
+fn verify_sample<TC: Configuration>(
+    root_hash: Digest,
+    epoch: u64,
+    sample: &SpotCheckSample,
+    target: &Digest,
+) -> Result<(), VerificationError> {
+    let proof = &sample.proof;
+    if proof.label.label_len != TC::node_label_len() {
+        return Err(VerificationError::SpotCheck(format!(
+            "The sampled node {:?} is not a leaf",
+            proof.label
+        )));
+    }
+    if sample.leaf_epoch == 0 || sample.leaf_epoch > epoch {
+        return Err(VerificationError::SpotCheck(format!(
+            "The sampled leaf was inserted at epoch {}, which is not in 1..={epoch}",
+            sample.leaf_epoch
+        )));
+    }
+    let leaf_hash = TC::hash_leaf_with_commitment(sample.commitment, sample.leaf_epoch);
+    if proof.hash_val.0 != leaf_hash.0 {
+        return Err(VerificationError::SpotCheck(
+            "The commitment and epoch of the sampled leaf do not match its hash".into(),
+        ));
+    }
+
+    // The only node with a single child is the root, whose empty sibling is bound by the
+    // root hash. Everywhere else, the path must follow the target.
+    let empty_label = TC::empty_label();
+    for sibling_proof in proof.sibling_proofs.iter() {
+        if sibling_proof.label.label_len >= proof.label.label_len {
+            return Err(VerificationError::SpotCheck(format!(
+                "The node {:?} on the path is not above the sampled leaf",
+                sibling_proof.label
+            )));
+        }
+        if sibling_proof.siblings[0].label == empty_label {
+            continue;
+        }
+        let expected = target_direction(target, sibling_proof.label.label_len);
+        if sibling_proof.direction != expected {
+            return Err(VerificationError::SpotCheck(format!(
+                "The path to the sampled leaf does not follow its target at node {:?}",
+                sibling_proof.label
+            )));
+        }
+    }
+
+    verify_membership::<TC>(root_hash, proof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExperimentalConfiguration;
+
+    type TC = ExperimentalConfiguration<crate::ExampleLabel>;
+
+    #[test]
+    fn test_targets_depend_on_root_and_epoch() {
+        let root = [1u8; 32];
+        let targets = spot_check_targets::<TC>(root, 3, 4);
+        assert_eq!(4, targets.len());
+        assert_eq!(targets, spot_check_targets::<TC>(root, 3, 4));
+        assert_eq!(targets[..2], spot_check_targets::<TC>(root, 3, 2)[..]);
+        assert_ne!(targets, spot_check_targets::<TC>([2u8; 32], 3, 4));
+        assert_ne!(targets, spot_check_targets::<TC>(root, 4, 4));
+    }
+
+    #[test]
+    fn test_target_direction() {
+        let mut target = [0u8; 32];
+        target[0] = 0b1010_0000;
+        target[31] = 0b0000_0001;
+        assert_eq!(Direction::Right, target_direction(&target, 0));
+        assert_eq!(Direction::Left, target_direction(&target, 1));
+        assert_eq!(Direction::Right, target_direction(&target, 2));
+        assert_eq!(Direction::Left, target_direction(&target, 254));
+        assert_eq!(Direction::Right, target_direction(&target, 255));
+    }
+}
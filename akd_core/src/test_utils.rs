@@ -0,0 +1,98 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Seedable randomness for tests and benchmarks, so that a failing run can be reproduced
+//! exactly from the seed which it logged
+
+use rand::rngs::StdRng;
+use rand::{CryptoRng, RngCore, SeedableRng};
+use std::cell::RefCell;
+use std::sync::OnceLock;
+
+/// The environment variable which sets the seed of [test_rng]
+pub const TEST_SEED_ENV_VAR: &str = "AKD_TEST_SEED";
+
+static SEED: OnceLock<u64> = OnceLock::new();
+
+/// The seed of [test_rng]: the value of the [TEST_SEED_ENV_VAR] environment variable if it is
+/// set, and otherwise a seed which is chosen at random once per process
+pub fn test_seed() -> u64 {
+    *SEED.get_or_init(|| match std::env::var(TEST_SEED_ENV_VAR) {
+        Ok(seed) => seed
+            .trim()
+            .parse()
+            .unwrap_or_else(|_| panic!("{TEST_SEED_ENV_VAR} is not a u64: {seed}")),
+        Err(_) => rand::rngs::OsRng.next_u64(),
+    })
+}
+
+/// A random number generator seeded with [test_seed]. The seed is printed, so that it appears
+/// in the output of a failing test, and the test can be re-run with the same randomness by
+/// setting [TEST_SEED_ENV_VAR] to it.
+pub fn test_rng() -> StdRng {
+    let seed = test_seed();
+    println!("Using random seed {seed} (set {TEST_SEED_ENV_VAR}={seed} to reproduce)");
+    StdRng::seed_from_u64(seed)
+}
+
+thread_local! {
+    static THREAD_RNG: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
+
+/// A handle to a random number generator of the current thread, which is seeded with
+/// [test_seed] (and prints it) on first use. As each test runs on its own thread, this is a
+/// reproducible replacement for `rand::thread_rng` in tests.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadTestRng;
+
+/// Returns a handle to the random number generator of the current thread (see [ThreadTestRng])
+pub fn thread_test_rng() -> ThreadTestRng {
+    ThreadTestRng
+}
+
+impl ThreadTestRng {
+    fn with<T>(&self, f: impl FnOnce(&mut StdRng) -> T) -> T {
+        THREAD_RNG.with(|rng| f(rng.borrow_mut().get_or_insert_with(test_rng)))
+    }
+}
+
+impl RngCore for ThreadTestRng {
+    fn next_u32(&mut self) -> u32 {
+        self.with(|rng| rng.next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.with(|rng| rng.next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.with(|rng| rng.fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.with(|rng| rng.try_fill_bytes(dest))
+    }
+}
+
+impl CryptoRng for ThreadTestRng {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rngs_are_reproducible() {
+        // each thread's generator starts from the same seed, as does each owned generator
+        let draw = || {
+            std::thread::spawn(|| thread_test_rng().next_u64())
+                .join()
+                .unwrap()
+        };
+        assert_eq!(draw(), draw());
+        assert_eq!(test_rng().next_u64(), draw());
+    }
+}
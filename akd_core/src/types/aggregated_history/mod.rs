@@ -0,0 +1,196 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A history proof whose older versions are summarized by a single committed digest.
+//!
+//! The size of a [HistoryProof] grows with the number of versions of a label, which is
+//! unbounded for labels that are updated very frequently. A directory can instead aggregate
+//! the versions of a label up to some version into a [HistoryAggregate], which is a hash
+//! chain over the version, epoch, and value commitment of each of them, and publish it as
+//! the value of the label's aggregate label (see [history_aggregate_label]). An
+//! [AggregatedHistoryProof] then consists of a lookup proof of the aggregate, along with a
+//! history proof of only the versions after it.
+//!
+//! Verifying an aggregated history proof checks that the aggregate is committed to by the
+//! directory, and that the explicit versions follow on from it, but not the versions within
+//! the aggregate. A party which holds the earlier versions (e.g. from an earlier history
+//! proof) can check them against the digest with [HistoryAggregate::extend].
+
+use crate::configuration::Configuration;
+use crate::hash::{Digest, DIGEST_BYTES, EMPTY_DIGEST};
+#[cfg(feature = "serde_serialization")]
+use crate::utils::serde_helpers::{bytes_deserialize_hex, bytes_serialize_hex};
+use crate::verify::VerificationError;
+use crate::{
+    removed_value_commitment, AkdLabel, AkdValue, AzksValue, HistoryProof, LookupProof,
+    UpdateProof, VerifyResult,
+};
+
+#[cfg(feature = "nostd")]
+use alloc::format;
+#[cfg(feature = "nostd")]
+use alloc::vec::Vec;
+
+/// The prefix of the labels under which the aggregates of the histories of other labels
+/// are published. Directories should not accept updates to labels which begin with this
+/// prefix from their users, as they would be interpreted as aggregates.
+pub const HISTORY_AGGREGATE_LABEL_PREFIX: &[u8] = b"\x00akd-aggregate\x00";
+
+/// The label under which the aggregate of the history of a (normalized) label is published
+pub fn history_aggregate_label(label: &AkdLabel) -> AkdLabel {
+    AkdLabel([HISTORY_AGGREGATE_LABEL_PREFIX, &label.0].concat())
+}
+
+/// A digest of the versions of a label from the first version up to `last_version`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct HistoryAggregate {
+    /// The last version which is aggregated, or zero if no version is
+    pub last_version: u64,
+    /// The epoch at which the last version was published
+    pub last_epoch: u64,
+    /// The hash chain over the aggregated versions
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(serialize_with = "bytes_serialize_hex")
+    )]
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(deserialize_with = "bytes_deserialize_hex")
+    )]
+    pub digest: Digest,
+}
+
+impl Default for HistoryAggregate {
+    fn default() -> Self {
+        Self {
+            last_version: 0,
+            last_epoch: 0,
+            digest: EMPTY_DIGEST,
+        }
+    }
+}
+
+impl HistoryAggregate {
+    /// The length of the value under which an aggregate is published
+    const VALUE_LEN: usize = 16 + DIGEST_BYTES;
+
+    /// Appends the next version of the label, given the commitment to its value, which fails
+    /// if the version or epoch does not follow the last aggregated one
+    pub fn append<TC: Configuration>(
+        &self,
+        version: u64,
+        epoch: u64,
+        commitment: &AzksValue,
+    ) -> Result<Self, VerificationError> {
+        if version != self.last_version + 1 || (self.last_version > 0 && epoch <= self.last_epoch) {
+            return Err(VerificationError::HistoryProof(format!(
+                "Version {version} at epoch {epoch} does not follow the aggregate of version {} at epoch {}",
+                self.last_version, self.last_epoch
+            )));
+        }
+        let mut data = Vec::with_capacity(2 * DIGEST_BYTES + 16);
+        data.extend_from_slice(&self.digest);
+        data.extend_from_slice(&version.to_be_bytes());
+        data.extend_from_slice(&epoch.to_be_bytes());
+        data.extend_from_slice(&commitment.0);
+        Ok(Self {
+            last_version: version,
+            last_epoch: epoch,
+            digest: TC::hash(&data),
+        })
+    }
+
+    /// Appends the versions of the given update proofs (in any order), whose commitments are
+    /// computed from their values and commitment nonces, or taken from the records of removed
+    /// values (see [removed_value_commitment]). A client which verified the earlier versions of
+    /// a label checks an aggregate of them by extending [HistoryAggregate::default] with their
+    /// update proofs.
+    pub fn extend<TC: Configuration>(
+        &self,
+        update_proofs: &[UpdateProof],
+    ) -> Result<Self, VerificationError> {
+        let mut update_proofs = update_proofs.iter().collect::<Vec<_>>();
+        update_proofs.sort_by_key(|update_proof| update_proof.version);
+        update_proofs
+            .into_iter()
+            .try_fold(*self, |aggregate, update_proof| {
+                let commitment = if TC::is_tombstone(&update_proof.value) {
+                    removed_value_commitment::<TC>(&update_proof.value).ok_or_else(|| {
+                        VerificationError::HistoryProof(format!(
+                            "Version {} has been tombstoned without recording its commitment",
+                            update_proof.version
+                        ))
+                    })?
+                } else {
+                    TC::commit_value(&update_proof.value, &update_proof.commitment_nonce)
+                };
+                aggregate.append::<TC>(update_proof.version, update_proof.epoch, &commitment)
+            })
+    }
+
+    /// The value under which the aggregate is published
+    pub fn to_value(&self) -> AkdValue {
+        AkdValue(
+            [
+                &self.last_version.to_be_bytes()[..],
+                &self.last_epoch.to_be_bytes(),
+                &self.digest,
+            ]
+            .concat(),
+        )
+    }
+
+    /// Parses the value under which an aggregate is published
+    pub fn from_value(value: &AkdValue) -> Option<Self> {
+        if value.0.len() != Self::VALUE_LEN {
+            return None;
+        }
+        let mut last_version = [0u8; 8];
+        last_version.copy_from_slice(&value.0[..8]);
+        let mut last_epoch = [0u8; 8];
+        last_epoch.copy_from_slice(&value.0[8..16]);
+        let mut digest = EMPTY_DIGEST;
+        digest.copy_from_slice(&value.0[16..]);
+        Some(Self {
+            last_version: u64::from_be_bytes(last_version),
+            last_epoch: u64::from_be_bytes(last_epoch),
+            digest,
+        })
+    }
+}
+
+/// The history of a label whose older versions may be summarized by a [HistoryAggregate]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct AggregatedHistoryProof {
+    /// The lookup proof of the aggregate of the older versions, or [None] if the history has
+    /// not been aggregated, in which case `recent` holds the complete history
+    pub aggregate: Option<LookupProof>,
+    /// The history proof of the versions after the aggregate
+    pub recent: HistoryProof,
+}
+
+/// The payload that is outputted as a result of successful verification of an
+/// [AggregatedHistoryProof]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct AggregatedVerifyResult {
+    /// The aggregate of the older versions, if any
+    pub aggregate: Option<HistoryAggregate>,
+    /// The versions after the aggregate, ordered from the most recent one
+    pub recent: Vec<VerifyResult>,
+}
@@ -0,0 +1,286 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A compacted encoding of a [HistoryProof].
+//!
+//! The membership and non-membership proofs contained in a history proof frequently
+//! share [SiblingProof]s, for instance the proofs for consecutive versions of a label
+//! share the siblings of any subtree which was unchanged between their epochs, and the
+//! marker non-membership proofs (which are all taken at the same epoch) share most of
+//! their paths near the root. A [CompactHistoryProof] stores every distinct sibling
+//! proof once in a shared table, and the individual proofs refer to entries of the
+//! table by index.
+
+use crate::verify::VerificationError;
+use crate::{
+    AkdValue, AzksElement, AzksValue, HistoryProof, MembershipProof, NodeLabel, NonMembershipProof,
+    SiblingProof, UpdateProof, ARITY,
+};
+
+#[cfg(feature = "nostd")]
+use alloc::collections::BTreeMap;
+#[cfg(feature = "nostd")]
+use alloc::format;
+#[cfg(feature = "nostd")]
+use alloc::vec::Vec;
+#[cfg(not(feature = "nostd"))]
+use std::collections::BTreeMap;
+
+/// A [MembershipProof] whose sibling proofs are indices into the
+/// sibling table of a [CompactHistoryProof]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct CompactMembershipProof {
+    /// The node label
+    pub label: NodeLabel,
+    /// The hash of the value
+    pub hash_val: AzksValue,
+    /// The indices of the parents of the node in question
+    pub sibling_proofs: Vec<u32>,
+}
+
+/// A [NonMembershipProof] whose membership proof of the longest prefix is compacted
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct CompactNonMembershipProof {
+    /// The label in question
+    pub label: NodeLabel,
+    /// The longest prefix in the tree
+    pub longest_prefix: NodeLabel,
+    /// The children of the longest prefix
+    pub longest_prefix_children: [AzksElement; ARITY],
+    /// The membership proof of the longest prefix
+    pub longest_prefix_membership_proof: CompactMembershipProof,
+}
+
+/// An [UpdateProof] whose membership proofs are compacted
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct CompactUpdateProof {
+    /// Epoch of this update
+    pub epoch: u64,
+    /// Value at this update
+    pub value: AkdValue,
+    /// Version at this update
+    pub version: u64,
+    /// VRF proof for the label for the current version
+    pub existence_vrf_proof: Vec<u8>,
+    /// Membership proof to show that the key was included in this epoch
+    pub existence_proof: CompactMembershipProof,
+    /// VRF proof for the label for the previous version which became stale
+    pub previous_version_vrf_proof: Option<Vec<u8>>,
+    /// Proof that previous value was set to old at this epoch
+    pub previous_version_proof: Option<CompactMembershipProof>,
+    /// Nonce for commitment value derived from raw AkdLabel and AkdValue
+    pub commitment_nonce: Vec<u8>,
+}
+
+/// A [HistoryProof] in which each distinct [SiblingProof] is only stored once
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct CompactHistoryProof {
+    /// The distinct sibling proofs, referenced by index from the other proofs
+    pub sibling_proofs: Vec<SiblingProof>,
+    /// The update proofs in the key history
+    pub update_proofs: Vec<CompactUpdateProof>,
+    /// VRF Proofs for the labels of the values until the next marker version
+    pub until_marker_vrf_proofs: Vec<Vec<u8>>,
+    /// Proof that the values until the next marker version did not exist at this time
+    pub non_existence_until_marker_proofs: Vec<CompactNonMembershipProof>,
+    /// VRF proofs for the labels of future marker entries
+    pub future_marker_vrf_proofs: Vec<Vec<u8>>,
+    /// Proof that future markers did not exist
+    pub non_existence_of_future_marker_proofs: Vec<CompactNonMembershipProof>,
+}
+
+/// Key identifying a distinct [SiblingProof]
+type SiblingKey = (NodeLabel, NodeLabel, AzksValue, u8);
+
+/// Accumulates the distinct sibling proofs while compacting a [HistoryProof]
+#[derive(Default)]
+struct SiblingTable {
+    sibling_proofs: Vec<SiblingProof>,
+    indices: BTreeMap<SiblingKey, u32>,
+}
+
+impl SiblingTable {
+    fn insert(&mut self, proof: &SiblingProof) -> u32 {
+        let key = (
+            proof.label,
+            proof.siblings[0].label,
+            proof.siblings[0].value,
+            proof.direction as u8,
+        );
+        let sibling_proofs = &mut self.sibling_proofs;
+        *self.indices.entry(key).or_insert_with(|| {
+            sibling_proofs.push(proof.clone());
+            (sibling_proofs.len() - 1) as u32
+        })
+    }
+
+    fn compact_membership(&mut self, proof: &MembershipProof) -> CompactMembershipProof {
+        CompactMembershipProof {
+            label: proof.label,
+            hash_val: proof.hash_val,
+            sibling_proofs: proof
+                .sibling_proofs
+                .iter()
+                .map(|sibling_proof| self.insert(sibling_proof))
+                .collect(),
+        }
+    }
+
+    fn compact_nonmembership(&mut self, proof: &NonMembershipProof) -> CompactNonMembershipProof {
+        CompactNonMembershipProof {
+            label: proof.label,
+            longest_prefix: proof.longest_prefix,
+            longest_prefix_children: proof.longest_prefix_children,
+            longest_prefix_membership_proof: self
+                .compact_membership(&proof.longest_prefix_membership_proof),
+        }
+    }
+}
+
+impl HistoryProof {
+    /// Converts this proof into a [CompactHistoryProof], storing each
+    /// distinct sibling proof only once
+    pub fn compact(&self) -> CompactHistoryProof {
+        let mut table = SiblingTable::default();
+        let update_proofs = self
+            .update_proofs
+            .iter()
+            .map(|proof| CompactUpdateProof {
+                epoch: proof.epoch,
+                value: proof.value.clone(),
+                version: proof.version,
+                existence_vrf_proof: proof.existence_vrf_proof.clone(),
+                existence_proof: table.compact_membership(&proof.existence_proof),
+                previous_version_vrf_proof: proof.previous_version_vrf_proof.clone(),
+                previous_version_proof: proof
+                    .previous_version_proof
+                    .as_ref()
+                    .map(|previous| table.compact_membership(previous)),
+                commitment_nonce: proof.commitment_nonce.clone(),
+            })
+            .collect();
+        let non_existence_until_marker_proofs = self
+            .non_existence_until_marker_proofs
+            .iter()
+            .map(|proof| table.compact_nonmembership(proof))
+            .collect();
+        let non_existence_of_future_marker_proofs = self
+            .non_existence_of_future_marker_proofs
+            .iter()
+            .map(|proof| table.compact_nonmembership(proof))
+            .collect();
+
+        CompactHistoryProof {
+            sibling_proofs: table.sibling_proofs,
+            update_proofs,
+            until_marker_vrf_proofs: self.until_marker_vrf_proofs.clone(),
+            non_existence_until_marker_proofs,
+            future_marker_vrf_proofs: self.future_marker_vrf_proofs.clone(),
+            non_existence_of_future_marker_proofs,
+        }
+    }
+}
+
+impl CompactHistoryProof {
+    /// Reconstructs the [HistoryProof] which this proof was compacted from. Fails if
+    /// any of the proofs refer to an index which is outside of the sibling table.
+    pub fn expand(self) -> Result<HistoryProof, VerificationError> {
+        let update_proofs = self
+            .update_proofs
+            .into_iter()
+            .map(|proof| {
+                Ok(UpdateProof {
+                    epoch: proof.epoch,
+                    value: proof.value,
+                    version: proof.version,
+                    existence_vrf_proof: proof.existence_vrf_proof,
+                    existence_proof: expand_membership(
+                        &self.sibling_proofs,
+                        proof.existence_proof,
+                    )?,
+                    previous_version_vrf_proof: proof.previous_version_vrf_proof,
+                    previous_version_proof: proof
+                        .previous_version_proof
+                        .map(|previous| expand_membership(&self.sibling_proofs, previous))
+                        .transpose()?,
+                    commitment_nonce: proof.commitment_nonce,
+                })
+            })
+            .collect::<Result<Vec<_>, VerificationError>>()?;
+        let non_existence_until_marker_proofs = self
+            .non_existence_until_marker_proofs
+            .into_iter()
+            .map(|proof| expand_nonmembership(&self.sibling_proofs, proof))
+            .collect::<Result<Vec<_>, VerificationError>>()?;
+        let non_existence_of_future_marker_proofs = self
+            .non_existence_of_future_marker_proofs
+            .into_iter()
+            .map(|proof| expand_nonmembership(&self.sibling_proofs, proof))
+            .collect::<Result<Vec<_>, VerificationError>>()?;
+
+        Ok(HistoryProof {
+            update_proofs,
+            until_marker_vrf_proofs: self.until_marker_vrf_proofs,
+            non_existence_until_marker_proofs,
+            future_marker_vrf_proofs: self.future_marker_vrf_proofs,
+            non_existence_of_future_marker_proofs,
+        })
+    }
+}
+
+fn expand_membership(
+    sibling_proofs: &[SiblingProof],
+    proof: CompactMembershipProof,
+) -> Result<MembershipProof, VerificationError> {
+    Ok(MembershipProof {
+        label: proof.label,
+        hash_val: proof.hash_val,
+        sibling_proofs: proof
+            .sibling_proofs
+            .into_iter()
+            .map(|index| {
+                sibling_proofs.get(index as usize).cloned().ok_or_else(|| {
+                    VerificationError::HistoryProof(format!(
+                        "Sibling proof index {index} is out of range of the {} shared sibling proofs",
+                        sibling_proofs.len()
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>, VerificationError>>()?,
+    })
+}
+
+fn expand_nonmembership(
+    sibling_proofs: &[SiblingProof],
+    proof: CompactNonMembershipProof,
+) -> Result<NonMembershipProof, VerificationError> {
+    Ok(NonMembershipProof {
+        label: proof.label,
+        longest_prefix: proof.longest_prefix,
+        longest_prefix_children: proof.longest_prefix_children,
+        longest_prefix_membership_proof: expand_membership(
+            sibling_proofs,
+            proof.longest_prefix_membership_proof,
+        )?,
+    })
+}
@@ -9,10 +9,10 @@
 //! to verify any of the following AKD proofs
 //!
 //! 1. Lookup
-//! 2: Key history
-//! 3: Audit (append-only)
+//! 2. Key history
+//! 3. Audit (append-only)
 
-use crate::hash::Digest;
+use crate::hash::{Digest, DIGEST_BYTES};
 #[cfg(feature = "serde_serialization")]
 use crate::utils::serde_helpers::{
     azks_value_hex_deserialize, azks_value_hex_serialize, bytes_deserialize_hex,
@@ -31,8 +31,16 @@ use rand::{CryptoRng, Rng};
 #[cfg(not(feature = "nostd"))]
 use std::cmp::{Ord, Ordering, PartialOrd};
 
+pub mod aggregated_history;
+pub use aggregated_history::*;
+pub mod compact_history;
+pub use compact_history::*;
 pub mod node_label;
 pub use node_label::*;
+pub mod selective_history;
+pub use selective_history::*;
+pub mod validation;
+pub use validation::*;
 
 // ============================================
 // Traits
@@ -153,8 +161,10 @@ impl Direction {
     }
 }
 
-/// The label of a particular entry in the AKD
-#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// The label of a particular entry in the AKD. Its [Debug] output is redacted to its length, so
+/// that user identifiers do not leak into logs. A label can be checked against the length and
+/// encoding constraints of an application as it is created, with [AkdLabel::new].
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[cfg_attr(
     feature = "serde_serialization",
     derive(serde::Serialize, serde::Deserialize)
@@ -203,6 +213,24 @@ impl core::convert::From<&String> for AkdLabel {
     }
 }
 
+impl core::convert::From<Vec<u8>> for AkdLabel {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl<const N: usize> core::convert::From<[u8; N]> for AkdLabel {
+    fn from(bytes: [u8; N]) -> Self {
+        Self(bytes.to_vec())
+    }
+}
+
+impl core::fmt::Debug for AkdLabel {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "AkdLabel(<{} bytes>)", self.0.len())
+    }
+}
+
 impl AkdLabel {
     #[cfg(feature = "rand")]
     /// Gets a random label
@@ -213,8 +241,10 @@ impl AkdLabel {
     }
 }
 
-/// The value of a particular entry in the AKD
-#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// The value of a particular entry in the AKD. Its [Debug] output is redacted to its length, so
+/// that user data do not leak into logs. A value can be checked against the length and
+/// encoding constraints of an application as it is created, with [AkdValue::new].
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[cfg_attr(
     feature = "serde_serialization",
     derive(serde::Serialize, serde::Deserialize)
@@ -263,6 +293,24 @@ impl core::convert::From<&String> for AkdValue {
     }
 }
 
+impl core::convert::From<Vec<u8>> for AkdValue {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl<const N: usize> core::convert::From<[u8; N]> for AkdValue {
+    fn from(bytes: [u8; N]) -> Self {
+        Self(bytes.to_vec())
+    }
+}
+
+impl core::fmt::Debug for AkdValue {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "AkdValue(<{} bytes>)", self.0.len())
+    }
+}
+
 impl AkdValue {
     #[cfg(feature = "rand")]
     /// Gets a random value for a AKD
@@ -271,8 +319,26 @@ impl AkdValue {
         rng.fill_bytes(&mut bytes);
         Self(bytes.to_vec())
     }
+
+    /// The link record which is published as the final value of a label whose
+    /// history has been moved to the target label
+    pub fn link_to(target: &AkdLabel) -> Self {
+        Self([LINK_VALUE_PREFIX, &target.0].concat())
+    }
+
+    /// Returns the label which this value links to, if it is a link record
+    pub fn link_target(&self) -> Option<AkdLabel> {
+        self.0
+            .strip_prefix(LINK_VALUE_PREFIX)
+            .map(|target| AkdLabel(target.to_vec()))
+    }
 }
 
+/// The prefix of a value which records that the history of a label has been moved to
+/// another label (see [AkdValue::link_to]). Directories should not accept updates whose
+/// values begin with this prefix from their users, as they would be interpreted as links.
+pub const LINK_VALUE_PREFIX: &[u8] = b"\x00akd-link\x00";
+
 /// The value to be hashed every time an empty node's hash is to be considered
 pub const EMPTY_VALUE: [u8; 1] = [0u8];
 
@@ -283,6 +349,117 @@ pub const EMPTY_VALUE: [u8; 1] = [0u8];
 /// See [GitHub issue #130](https://github.com/novifinancial/akd/issues/130) for more context
 pub const TOMBSTONE: &[u8] = &[];
 
+/// The prefix of a tombstone which carries [TombstoneMetadata] (see [TombstoneMetadata::to_value]).
+/// Directories which attach metadata to their tombstones should not accept updates whose values
+/// begin with this prefix from their users, as they would be interpreted as tombstones.
+pub const TOMBSTONE_METADATA_PREFIX: &[u8] = b"\x00akd-tombstone\x00";
+
+/// Metadata which a configuration can attach to the tombstone of a value (see
+/// [Configuration::tombstone_value](crate::configuration::Configuration::tombstone_value)), so
+/// that clients can tell when the value was removed. The commitment to the removed value is
+/// checked against the tree by clients when verifying a key history, which binds the tombstone
+/// to the update it replaced, and the epoch must lie between the epoch of that update and the
+/// epoch at which the history is verified.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TombstoneMetadata {
+    /// The epoch at which the value was tombstoned
+    pub tombstoned_at: u64,
+    /// The commitment to the value which was tombstoned, as stored in the tree
+    pub commitment: AzksValue,
+}
+
+impl TombstoneMetadata {
+    /// Encodes the metadata as a tombstone, consisting of [TOMBSTONE_METADATA_PREFIX] followed
+    /// by the big-endian epoch and the commitment
+    pub fn to_value(&self) -> AkdValue {
+        AkdValue(
+            [
+                TOMBSTONE_METADATA_PREFIX,
+                &self.tombstoned_at.to_be_bytes(),
+                &self.commitment.0,
+            ]
+            .concat(),
+        )
+    }
+
+    /// Decodes the metadata of a tombstone which was encoded by [TombstoneMetadata::to_value]
+    pub fn from_value(value: &AkdValue) -> Option<Self> {
+        let bytes = value.0.strip_prefix(TOMBSTONE_METADATA_PREFIX)?;
+        if bytes.len() != 8 + DIGEST_BYTES {
+            return None;
+        }
+        let (epoch, commitment) = bytes.split_at(8);
+        Some(Self {
+            tombstoned_at: u64::from_be_bytes(epoch.try_into().ok()?),
+            commitment: AzksValue(commitment.try_into().ok()?),
+        })
+    }
+}
+
+/// The prefix of a redaction record (see [RedactionRecord::to_value]). Directories should not
+/// accept updates whose values begin with this prefix from their users, as they would be
+/// interpreted as redaction records.
+pub const REDACTION_RECORD_PREFIX: &[u8] = b"\x00akd-redaction\x00";
+
+/// A record which replaces a value in storage when it is redacted (e.g. to comply with a legal
+/// request), which unlike a plain tombstone states when and why the value was redacted.
+/// Redaction records are tombstones under the default
+/// [Configuration::is_tombstone](crate::configuration::Configuration::is_tombstone), and clients
+/// which verify a key history with missing values allowed check them like [TombstoneMetadata]:
+/// the commitment to the redacted value must be the one in the tree, and the value must have
+/// been redacted no earlier than it was published, and no later than the current epoch. The
+/// reason is carried as given by the directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RedactionRecord {
+    /// The epoch at which the value was redacted
+    pub redacted_at: u64,
+    /// The commitment to the value which was redacted, as stored in the tree
+    pub commitment: AzksValue,
+    /// The reason for the redaction
+    pub reason: String,
+}
+
+impl RedactionRecord {
+    /// Encodes the record as a value, consisting of [REDACTION_RECORD_PREFIX] followed by the
+    /// big-endian epoch, the commitment, and the UTF-8 bytes of the reason
+    pub fn to_value(&self) -> AkdValue {
+        AkdValue(
+            [
+                REDACTION_RECORD_PREFIX,
+                &self.redacted_at.to_be_bytes(),
+                &self.commitment.0,
+                self.reason.as_bytes(),
+            ]
+            .concat(),
+        )
+    }
+
+    /// Decodes a redaction record which was encoded by [RedactionRecord::to_value]
+    pub fn from_value(value: &AkdValue) -> Option<Self> {
+        let bytes = value.0.strip_prefix(REDACTION_RECORD_PREFIX)?;
+        if bytes.len() < 8 + DIGEST_BYTES {
+            return None;
+        }
+        let (epoch, rest) = bytes.split_at(8);
+        let (commitment, reason) = rest.split_at(DIGEST_BYTES);
+        Some(Self {
+            redacted_at: u64::from_be_bytes(epoch.try_into().ok()?),
+            commitment: AzksValue(commitment.try_into().ok()?),
+            reason: core::str::from_utf8(reason).ok()?.to_string(),
+        })
+    }
+}
+
+/// The commitment to a removed value which is recorded by its [TombstoneMetadata] or
+/// [RedactionRecord], if any, so that the version can still be proven by its commitment
+pub fn removed_value_commitment<TC: crate::configuration::Configuration>(
+    value: &AkdValue,
+) -> Option<AzksValue> {
+    TC::tombstone_metadata(value)
+        .map(|metadata| metadata.commitment)
+        .or_else(|| RedactionRecord::from_value(value).map(|record| record.commitment))
+}
+
 // ============================================
 // Structs
 // ============================================
@@ -402,6 +579,7 @@ pub struct NonMembershipProof {
 /// * committed in the tree,
 /// * not too far ahead of the most recent marker version,
 /// * not stale when served.
+///
 /// This proof is sent in response to a lookup query for a particular key.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(
@@ -431,6 +609,173 @@ pub struct LookupProof {
     pub commitment_nonce: Vec<u8>,
 }
 
+/// A [LookupProof] for the latest version of a label, together with the proofs that
+/// no newer version exists as of the epoch it was generated at. These are the same
+/// non-existence proofs which conclude a [HistoryProof], so the client can establish
+/// that the version is the latest without requesting the label's full history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct LatestVersionProof {
+    /// The lookup proof for the latest version
+    pub lookup_proof: LookupProof,
+    /// VRF Proofs for the labels of the values until the next marker version
+    pub until_marker_vrf_proofs: Vec<Vec<u8>>,
+    /// Proof that the values until the next marker version did not exist at this time
+    pub non_existence_until_marker_proofs: Vec<NonMembershipProof>,
+    /// VRF proofs for the labels of future marker entries
+    pub future_marker_vrf_proofs: Vec<Vec<u8>>,
+    /// Proof that future markers did not exist
+    pub non_existence_of_future_marker_proofs: Vec<NonMembershipProof>,
+}
+
+/// Proof that a label has never been registered in the directory, which is shown by
+/// the non-membership of the label for its first version. This proof is sent in response
+/// to a lookup query for a label which is expected to be absent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct NonExistenceProof {
+    /// VRF proof for the label corresponding to the first version
+    pub vrf_proof: Vec<u8>,
+    /// Proof that the first version of the label is not in the tree
+    pub non_membership_proof: NonMembershipProof,
+}
+
+/// A [LookupProof] bundled together with the epoch and root hash it verifies against,
+/// a signature over that epoch's root hash, and the fingerprint of the configuration
+/// which produced it. Carrying these together prevents a client from pairing a proof
+/// with the root hash of a different epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct SignedLookupResponse {
+    /// The lookup proof
+    pub proof: LookupProof,
+    /// The epoch the proof was generated at
+    pub epoch: u64,
+    /// The root hash of the directory at the epoch
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(serialize_with = "bytes_serialize_hex")
+    )]
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(deserialize_with = "bytes_deserialize_hex")
+    )]
+    pub root_hash: Digest,
+    /// The directory's signature over the epoch and root hash
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(serialize_with = "bytes_serialize_hex")
+    )]
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(deserialize_with = "bytes_deserialize_hex")
+    )]
+    pub epoch_signature: Vec<u8>,
+    /// The fingerprint of the configuration the directory is running
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(serialize_with = "bytes_serialize_hex")
+    )]
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(deserialize_with = "bytes_deserialize_hex")
+    )]
+    pub configuration_fingerprint: Digest,
+    /// The certificate delegating the signing of the epoch's root hash to the key which
+    /// produced [SignedLookupResponse::epoch_signature], if it was not signed by the root key
+    #[cfg_attr(feature = "serde_serialization", serde(default))]
+    pub delegation: Option<DelegationCertificate>,
+}
+
+/// A certificate, signed by a directory's offline root signing key, which delegates the
+/// signing of the root hashes of a range of epochs to a short-lived online signing key.
+/// See [crate::signing].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct DelegationCertificate {
+    /// The public key of the delegate, in the encoding of the root key's signature scheme
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(serialize_with = "bytes_serialize_hex")
+    )]
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(deserialize_with = "bytes_deserialize_hex")
+    )]
+    pub delegate_public_key: Vec<u8>,
+    /// The first epoch whose root hash the delegate may sign
+    pub first_epoch: u64,
+    /// The last epoch whose root hash the delegate may sign
+    pub last_epoch: u64,
+    /// The root key's signature over the delegation
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(serialize_with = "bytes_serialize_hex")
+    )]
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(deserialize_with = "bytes_deserialize_hex")
+    )]
+    pub signature: Vec<u8>,
+}
+
+/// A record, signed by the directory, of the directory being rolled back from one epoch
+/// to an earlier one. Publishing the record allows auditors and clients to distinguish a
+/// deliberate rollback from the directory presenting an inconsistent view of its history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct SignedRollbackRecord {
+    /// The epoch which was the head of the directory before the rollback
+    pub from_epoch: u64,
+    /// The root hash of the directory at [SignedRollbackRecord::from_epoch]
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(serialize_with = "bytes_serialize_hex")
+    )]
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(deserialize_with = "bytes_deserialize_hex")
+    )]
+    pub from_root_hash: Digest,
+    /// The epoch which is the head of the directory after the rollback
+    pub to_epoch: u64,
+    /// The root hash of the directory at [SignedRollbackRecord::to_epoch]
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(serialize_with = "bytes_serialize_hex")
+    )]
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(deserialize_with = "bytes_deserialize_hex")
+    )]
+    pub to_root_hash: Digest,
+    /// The directory's signature over the rollback
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(serialize_with = "bytes_serialize_hex")
+    )]
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(deserialize_with = "bytes_deserialize_hex")
+    )]
+    pub signature: Vec<u8>,
+}
+
 /// A vector of UpdateProofs are sent as the proof to a history query for a particular key.
 /// For each version of the value associated with the key, the verifier must check that:
 /// * the version was included in the claimed epoch,
@@ -481,6 +826,222 @@ pub struct HistoryProof {
     pub non_existence_of_future_marker_proofs: Vec<NonMembershipProof>,
 }
 
+/// The history of a label whose history has been moved to another label, such as
+/// following a change of phone number
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct LinkedHistorySegment {
+    /// The label whose history is proven
+    pub label: AkdLabel,
+    /// The history of the label
+    pub proof: HistoryProof,
+}
+
+/// The histories of a chain of labels, each of which ends with a link record (see
+/// [AkdValue::link_to]) to the label of the next segment
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct LinkedHistoryProof {
+    /// The histories of the labels, ordered from the earliest label to the current one
+    pub segments: Vec<LinkedHistorySegment>,
+}
+
+/// A single update within a verified [LinkedHistoryProof]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct LinkedVerifyResult {
+    /// The label which the update was published for
+    pub label: AkdLabel,
+    /// The update
+    pub result: VerifyResult,
+}
+
+/// The retention requirements of the values of a label, which determine how long the values
+/// of its older versions are kept before they are tombstoned
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+#[repr(u8)]
+pub enum RetentionClass {
+    /// The values of older versions are kept as long as the retention policy of the directory
+    /// allows
+    #[default]
+    Standard = 0u8,
+    /// Only the value of the latest version is kept, and the values of older versions are
+    /// tombstoned whenever values are pruned
+    Ephemeral = 1u8,
+    /// The values of every version are kept, whatever the retention policy of the directory,
+    /// e.g. while they are subject to a legal hold
+    LegalHold = 2u8,
+}
+
+impl RetentionClass {
+    /// Parses a retention class from its byte representation, returning `None` if it is not
+    /// a known class
+    pub fn from_u8(class: u8) -> Option<Self> {
+        match class {
+            0 => Some(Self::Standard),
+            1 => Some(Self::Ephemeral),
+            2 => Some(Self::LegalHold),
+            _ => None,
+        }
+    }
+}
+
+/// The current version of a single label in a [DirectoryExport]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct ExportEntry {
+    /// The label
+    pub label: AkdLabel,
+    /// The version of the label which was current at the epoch of the export
+    pub version: u64,
+    /// The epoch in which the version was published
+    pub epoch: u64,
+    /// The label of the tree leaf for the version
+    pub node_label: NodeLabel,
+    /// The commitment to the value of the version, which is the value of its tree leaf.
+    /// The hash of the leaf, as found in the membership proof of the version in a
+    /// [HistoryProof], is the commitment hashed together with `epoch`.
+    pub commitment: AzksValue,
+    /// The retention class of the label at the epoch of the export
+    pub retention_class: RetentionClass,
+}
+
+/// A snapshot of the current version of every label in a directory at an epoch,
+/// which carries value commitments rather than plaintext values
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct DirectoryExport {
+    /// The epoch of the snapshot
+    pub epoch: u64,
+    /// The root hash of the directory at the epoch
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(serialize_with = "bytes_serialize_hex")
+    )]
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(deserialize_with = "bytes_deserialize_hex")
+    )]
+    pub root_hash: Digest,
+    /// The entries of the snapshot, sorted by label
+    pub entries: Vec<ExportEntry>,
+}
+
+/// The version of the format of a proof carried by a [VersionedLookupProof]
+/// or a [VersionedHistoryProof]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u32)]
+pub enum ProofVersion {
+    /// The original format of each proof
+    V1 = 1,
+    /// History proofs in the compacted format of [CompactHistoryProof]
+    V2 = 2,
+}
+
+impl ProofVersion {
+    /// The most recent version, which is produced by default
+    pub const LATEST: ProofVersion = ProofVersion::V2;
+
+    /// Parses a version number, returning `None` if it is not a known version
+    pub fn from_u32(version: u32) -> Option<Self> {
+        match version {
+            1 => Some(Self::V1),
+            2 => Some(Self::V2),
+            _ => None,
+        }
+    }
+}
+
+/// A [LookupProof] tagged with the version of its format. Decoding accepts every
+/// known version, as well as lookup proofs which were encoded without a version
+/// tag, so that servers and clients can be upgraded independently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum VersionedLookupProof {
+    /// The original lookup proof format, which is unchanged in later versions
+    V1(LookupProof),
+}
+
+impl VersionedLookupProof {
+    /// The version of the contained proof
+    pub fn version(&self) -> ProofVersion {
+        match self {
+            Self::V1(_) => ProofVersion::V1,
+        }
+    }
+
+    /// Extracts the lookup proof, for verification with [crate::verify::lookup_verify]
+    pub fn into_lookup_proof(self) -> LookupProof {
+        match self {
+            Self::V1(proof) => proof,
+        }
+    }
+}
+
+/// A history proof tagged with the version of its format. Decoding accepts every
+/// known version, as well as history proofs which were encoded without a version
+/// tag, so that servers and clients can be upgraded independently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum VersionedHistoryProof {
+    /// The original history proof format
+    V1(HistoryProof),
+    /// The compacted history proof format
+    V2(CompactHistoryProof),
+}
+
+impl VersionedHistoryProof {
+    /// Wraps a history proof in the requested format version
+    pub fn new(proof: HistoryProof, version: ProofVersion) -> Self {
+        match version {
+            ProofVersion::V1 => Self::V1(proof),
+            ProofVersion::V2 => Self::V2(proof.compact()),
+        }
+    }
+
+    /// The version of the contained proof
+    pub fn version(&self) -> ProofVersion {
+        match self {
+            Self::V1(_) => ProofVersion::V1,
+            Self::V2(_) => ProofVersion::V2,
+        }
+    }
+
+    /// Extracts the history proof, for verification with [crate::verify::key_history_verify].
+    /// Fails if a compacted proof refers to a sibling proof which it does not contain.
+    pub fn into_history_proof(self) -> Result<HistoryProof, crate::verify::VerificationError> {
+        match self {
+            Self::V1(proof) => Ok(proof),
+            Self::V2(proof) => proof.expand(),
+        }
+    }
+}
+
 /// The payload that is outputted as a result of successful verification of
 /// a [LookupProof] or [HistoryProof]. This includes the fields containing the
 /// epoch that the leaf was published in, the version corresponding to the value,
@@ -499,6 +1060,89 @@ pub struct VerifyResult {
     pub value: AkdValue,
 }
 
+/// The result of a verified lookup (see [crate::verify::lookup_verify_result]), which carries
+/// the label and value looked up together with their version, the epochs they were published
+/// at and verified against, and the proof itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct LookupResult {
+    /// The label which was looked up
+    pub label: AkdLabel,
+    /// The value of the label
+    pub value: AkdValue,
+    /// The version of the value
+    pub version: u64,
+    /// The epoch at which the value was published
+    pub epoch: u64,
+    /// The epoch of the root hash which the proof was verified against
+    pub current_epoch: u64,
+    /// The root hash which the proof was verified against
+    pub root_hash: Digest,
+    /// The verified proof
+    pub proof: LookupProof,
+}
+
+impl LookupResult {
+    /// The number of epochs which have been published since the value was
+    pub fn epochs_since_update(&self) -> u64 {
+        self.current_epoch.saturating_sub(self.epoch)
+    }
+
+    /// Whether the value was published at the epoch which the proof was verified against
+    pub fn is_updated_in_current_epoch(&self) -> bool {
+        self.epoch == self.current_epoch
+    }
+
+    /// Whether the value is the first version of the label
+    pub fn is_first_version(&self) -> bool {
+        self.version == 1
+    }
+
+    /// Whether the value is a tombstone of the configuration, in which case the label's
+    /// value has been removed
+    pub fn is_tombstone<TC: crate::configuration::Configuration>(&self) -> bool {
+        TC::is_tombstone(&self.value)
+    }
+
+    /// Checks that the value of the label is the one expected
+    pub fn assert_value_equals(
+        &self,
+        expected: &AkdValue,
+    ) -> Result<(), crate::verify::VerificationError> {
+        if &self.value != expected {
+            return Err(crate::verify::VerificationError::LookupProof(
+                alloc::format!(
+                    "The value of version {} of the label is not the one expected",
+                    self.version
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The epoch, version, and value of the result
+    pub fn to_verify_result(&self) -> VerifyResult {
+        VerifyResult {
+            epoch: self.epoch,
+            version: self.version,
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl From<LookupResult> for VerifyResult {
+    fn from(result: LookupResult) -> Self {
+        Self {
+            epoch: result.epoch,
+            version: result.version,
+            value: result.value,
+        }
+    }
+}
+
 /// Proof that no leaves were deleted from the initial epoch.
 /// This means that unchanged_nodes should hash to the initial root hash
 /// and the vec of inserted is the set of leaves inserted between these epochs.
@@ -532,3 +1176,41 @@ pub struct AppendOnlyProof {
     /// Epochs over which this audit is being performed
     pub epochs: Vec<u64>,
 }
+
+/// The leaves which were inserted into the tree in a single epoch
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct EpochInsertions {
+    /// The epoch in which the leaves were inserted
+    pub epoch: u64,
+    /// The inserted nodes & digests
+    pub inserted: Vec<AzksElement>,
+}
+
+/// Proof that no leaves were deleted between a start and an end epoch which
+/// may be arbitrarily far apart. Unlike an [AppendOnlyProof], which carries a
+/// separate [SingleAppendOnlyProof] for each intermediate epoch, this proof
+/// carries the nodes unchanged since the start epoch only once, and the inserted
+/// leaves are chunked by the epoch in which they were inserted.
+/// If we built the tree using the nodes in unchanged_nodes, it should result in the
+/// start root hash, and then inserting each chunk of leaves with its epoch of
+/// insertion should result in the end root hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct MultiEpochAppendOnlyProof {
+    /// The epoch from which the proof starts
+    pub start_epoch: u64,
+    /// The epoch at which the proof ends
+    pub end_epoch: u64,
+    /// The unchanged nodes & digests
+    pub unchanged_nodes: Vec<AzksElement>,
+    /// The inserted leaves, chunked by epoch of insertion in increasing order of epoch.
+    /// Epochs in which no leaves were inserted are omitted.
+    pub insertions: Vec<EpochInsertions>,
+}
@@ -9,14 +9,15 @@
 
 use super::*;
 use crate::test_config_sync;
+use crate::test_utils::thread_test_rng;
 #[cfg(feature = "nostd")]
 use alloc::vec;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
 // ================= Test helpers ================= //
 
 fn random_label() -> crate::NodeLabel {
-    let mut rng = thread_rng();
+    let mut rng = thread_test_rng();
     crate::NodeLabel {
         label_val: rng.gen::<[u8; 32]>(),
         label_len: 256,
@@ -49,7 +50,7 @@ fn byte_arr_from_u64_le(input_int: u64) -> [u8; 32] {
 #[test]
 fn test_get_bit_at_small() {
     let val = 0b1010u64 << 60;
-    let expected = vec![Bit::One, Bit::Zero, Bit::One, Bit::Zero];
+    let expected = [Bit::One, Bit::Zero, Bit::One, Bit::Zero];
     let label = NodeLabel::new(byte_arr_from_u64(val), 4);
     for (index, item) in expected.iter().enumerate().take(4) {
         assert!(
@@ -276,8 +277,8 @@ fn test_node_label_lcp_with_zero_length_label<TC: Configuration>() {
 // Test for get_longest_common_prefix between a label and its prefix.
 test_config_sync!(test_node_label_lcp_with_prefix_label);
 fn test_node_label_lcp_with_prefix_label<TC: Configuration>() {
-    let label_1 = NodeLabel::new(byte_arr_from_u64(01u64 << 62), 2u32);
-    let label_2 = NodeLabel::new(byte_arr_from_u64(01u64 << 62), 3u32);
+    let label_1 = NodeLabel::new(byte_arr_from_u64(1u64 << 62), 2u32);
+    let label_2 = NodeLabel::new(byte_arr_from_u64(1u64 << 62), 3u32);
     let expected = label_1;
     assert!(
         label_1.get_longest_common_prefix::<TC>(label_2) == expected,
@@ -390,6 +391,7 @@ fn test_node_label_lcp_some_leading_zero<TC: Configuration>() {
 // This test tests get_dir by manually computing the prefix and the bit
 // immediately following the prefix of that length.
 test_config_sync!(test_get_dir_large);
+#[allow(clippy::extra_unused_type_parameters)]
 fn test_get_dir_large<TC: Configuration>() {
     for i in 0..256 {
         let label_1 = random_label();
@@ -447,24 +449,24 @@ fn test_is_prefix_of<TC: Configuration>() {
     let label_3 = NodeLabel::new(byte_arr_from_u64(0b0u64), 4u32);
 
     // empty label is prefix of all labels
-    assert_eq!(TC::empty_label().is_prefix_of(&label_1), true);
-    assert_eq!(TC::empty_label().is_prefix_of(&label_2), true);
-    assert_eq!(TC::empty_label().is_prefix_of(&label_3), true);
+    assert!(TC::empty_label().is_prefix_of(&label_1));
+    assert!(TC::empty_label().is_prefix_of(&label_2));
+    assert!(TC::empty_label().is_prefix_of(&label_3));
 
     // every label is a prefix of itself
-    assert_eq!(label_1.is_prefix_of(&label_1), true);
-    assert_eq!(label_2.is_prefix_of(&label_2), true);
-    assert_eq!(label_3.is_prefix_of(&label_3), true);
+    assert!(label_1.is_prefix_of(&label_1));
+    assert!(label_2.is_prefix_of(&label_2));
+    assert!(label_3.is_prefix_of(&label_3));
 
     // valid prefixes
-    assert_eq!(label_1.is_prefix_of(&label_2), true);
+    assert!(label_1.is_prefix_of(&label_2));
 
     // invalid prefixes
-    assert_eq!(label_1.is_prefix_of(&label_3), false);
-    assert_eq!(label_2.is_prefix_of(&label_1), false);
-    assert_eq!(label_2.is_prefix_of(&label_3), false);
-    assert_eq!(label_3.is_prefix_of(&label_1), false);
-    assert_eq!(label_3.is_prefix_of(&label_2), false);
+    assert!(!label_1.is_prefix_of(&label_3));
+    assert!(!label_2.is_prefix_of(&label_1));
+    assert!(!label_2.is_prefix_of(&label_3));
+    assert!(!label_3.is_prefix_of(&label_1));
+    assert!(!label_3.is_prefix_of(&label_2));
 }
 
 // This test gets a prefix for a hard-coded random string and makes sure it is equal to a hand-computed value.
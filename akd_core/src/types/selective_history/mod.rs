@@ -0,0 +1,103 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A history proof which discloses the values of only some versions of a label.
+//!
+//! The leaf for each version of a label commits to its value with a hiding commitment,
+//! so a [SelectiveHistoryProof] can prove that a version was published at an epoch by
+//! providing only the commitment, rather than the value and commitment nonce. Versions
+//! published within the disclosed epochs reveal their values, while all other versions
+//! reveal only their commitments.
+
+use crate::{AkdValue, AzksValue, MembershipProof, NonMembershipProof};
+
+#[cfg(feature = "nostd")]
+use alloc::vec::Vec;
+
+/// What a [SelectiveUpdateProof] discloses about the value of its version
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum UpdateDisclosure {
+    /// The value is revealed, along with the nonce of its commitment
+    Revealed {
+        /// Value at this update
+        value: AkdValue,
+        /// Nonce for commitment value derived from raw AkdLabel and AkdValue
+        commitment_nonce: Vec<u8>,
+    },
+    /// Only the commitment to the value is revealed
+    Hidden {
+        /// The commitment to the value at this update
+        commitment: AzksValue,
+    },
+}
+
+/// An [crate::UpdateProof] which may hide the value of its version
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct SelectiveUpdateProof {
+    /// Epoch of this update
+    pub epoch: u64,
+    /// Version at this update
+    pub version: u64,
+    /// The value at this update, or the commitment to it
+    pub disclosure: UpdateDisclosure,
+    /// VRF proof for the label for the current version
+    pub existence_vrf_proof: Vec<u8>,
+    /// Membership proof to show that the key was included in this epoch
+    pub existence_proof: MembershipProof,
+    /// VRF proof for the label for the previous version which became stale
+    pub previous_version_vrf_proof: Option<Vec<u8>>,
+    /// Proof that previous value was set to old at this epoch
+    pub previous_version_proof: Option<MembershipProof>,
+}
+
+/// A [crate::HistoryProof] which reveals the values of only the versions
+/// published between `disclosed_start_epoch` and `disclosed_end_epoch` (inclusive)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct SelectiveHistoryProof {
+    /// The first epoch whose updates have their values revealed
+    pub disclosed_start_epoch: u64,
+    /// The last epoch whose updates have their values revealed
+    pub disclosed_end_epoch: u64,
+    /// The update proofs in the key history
+    pub update_proofs: Vec<SelectiveUpdateProof>,
+    /// VRF Proofs for the labels of the values until the next marker version
+    pub until_marker_vrf_proofs: Vec<Vec<u8>>,
+    /// Proof that the values until the next marker version did not exist at this time
+    pub non_existence_until_marker_proofs: Vec<NonMembershipProof>,
+    /// VRF proofs for the labels of future marker entries
+    pub future_marker_vrf_proofs: Vec<Vec<u8>>,
+    /// Proof that future markers did not exist
+    pub non_existence_of_future_marker_proofs: Vec<NonMembershipProof>,
+}
+
+/// The payload that is outputted as a result of successful verification of a
+/// [SelectiveHistoryProof]. The value is only present for disclosed versions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct SelectiveVerifyResult {
+    /// The epoch of this record
+    pub epoch: u64,
+    /// Version at this update
+    pub version: u64,
+    /// The plaintext value associated with the record, if it was disclosed
+    pub value: Option<AkdValue>,
+}
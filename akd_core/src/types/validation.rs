@@ -0,0 +1,202 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Validated construction of [AkdLabel]s and [AkdValue]s, which checks their bytes against
+//! the length and encoding constraints configured by an application
+
+use super::{AkdLabel, AkdValue};
+
+#[cfg(feature = "nostd")]
+use alloc::vec::Vec;
+
+/// The bytes which may appear in a label or value
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum BytePolicy {
+    /// Any byte is allowed
+    #[default]
+    Any,
+    /// Only printable ASCII characters (from `0x20` to `0x7e`) are allowed
+    PrintableAscii,
+    /// The bytes must be valid UTF-8, without control characters
+    Utf8,
+    /// Only the listed bytes are allowed
+    Only(&'static [u8]),
+}
+
+impl BytePolicy {
+    /// Returns the index and value of the first byte which is not allowed by the policy, if any
+    pub fn first_disallowed(&self, bytes: &[u8]) -> Option<(usize, u8)> {
+        match self {
+            Self::Any => None,
+            Self::PrintableAscii => bytes
+                .iter()
+                .position(|byte| !(0x20..=0x7e).contains(byte))
+                .map(|index| (index, bytes[index])),
+            Self::Utf8 => match core::str::from_utf8(bytes) {
+                Ok(text) => text
+                    .char_indices()
+                    .find(|(_, c)| c.is_control())
+                    .map(|(index, _)| (index, bytes[index])),
+                Err(err) => Some((err.valid_up_to(), bytes[err.valid_up_to()])),
+            },
+            Self::Only(allowed) => bytes
+                .iter()
+                .position(|byte| !allowed.contains(byte))
+                .map(|index| (index, bytes[index])),
+        }
+    }
+}
+
+/// The length and encoding constraints on the bytes of a label or value, which are checked
+/// by [AkdLabel::new] and [AkdValue::new]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ByteConstraints {
+    /// Whether the bytes may be empty
+    pub allow_empty: bool,
+    /// The maximum length, in bytes
+    pub max_len: Option<usize>,
+    /// The bytes which are allowed
+    pub bytes: BytePolicy,
+}
+
+impl ByteConstraints {
+    /// Constraints which allow any bytes, including none
+    pub const UNCONSTRAINED: Self = Self {
+        allow_empty: true,
+        max_len: None,
+        bytes: BytePolicy::Any,
+    };
+
+    /// Checks the bytes against the constraints
+    pub fn check(&self, bytes: &[u8]) -> Result<(), InvalidBytes> {
+        if bytes.is_empty() && !self.allow_empty {
+            return Err(InvalidBytes::Empty);
+        }
+        if let Some(max_len) = self.max_len {
+            if bytes.len() > max_len {
+                return Err(InvalidBytes::TooLong {
+                    len: bytes.len(),
+                    max_len,
+                });
+            }
+        }
+        if let Some((index, byte)) = self.bytes.first_disallowed(bytes) {
+            return Err(InvalidBytes::DisallowedByte { index, byte });
+        }
+        Ok(())
+    }
+}
+
+/// The reason that bytes do not satisfy [ByteConstraints]. Since the bytes may identify a
+/// user, they are not included.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum InvalidBytes {
+    /// The bytes are empty
+    Empty,
+    /// The bytes are longer than the maximum length
+    TooLong {
+        /// The length of the bytes
+        len: usize,
+        /// The maximum length
+        max_len: usize,
+    },
+    /// The bytes contain a byte which is not allowed by the [BytePolicy] of the constraints
+    DisallowedByte {
+        /// The index of the first disallowed byte
+        index: usize,
+        /// The value of the first disallowed byte
+        byte: u8,
+    },
+}
+
+impl core::fmt::Display for InvalidBytes {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "The bytes are empty"),
+            Self::TooLong { len, max_len } => {
+                write!(
+                    f,
+                    "The length of {len} bytes exceeds the maximum of {max_len}"
+                )
+            }
+            Self::DisallowedByte { index, byte } => {
+                write!(f, "The byte {byte:#04x} at index {index} is not allowed")
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "nostd"))]
+impl std::error::Error for InvalidBytes {}
+
+impl AkdLabel {
+    /// Creates a label from bytes which satisfy the given constraints. The conversions from
+    /// strings and byte vectors do not check any constraints.
+    pub fn new(
+        bytes: impl Into<Vec<u8>>,
+        constraints: &ByteConstraints,
+    ) -> Result<Self, InvalidBytes> {
+        let bytes = bytes.into();
+        constraints.check(&bytes)?;
+        Ok(Self(bytes))
+    }
+}
+
+impl AkdValue {
+    /// Creates a value from bytes which satisfy the given constraints. The conversions from
+    /// strings and byte vectors do not check any constraints.
+    pub fn new(
+        bytes: impl Into<Vec<u8>>,
+        constraints: &ByteConstraints,
+    ) -> Result<Self, InvalidBytes> {
+        let bytes = bytes.into();
+        constraints.check(&bytes)?;
+        Ok(Self(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_constraints() {
+        let constraints = ByteConstraints {
+            allow_empty: false,
+            max_len: Some(5),
+            bytes: BytePolicy::PrintableAscii,
+        };
+        assert_eq!(
+            Ok(AkdLabel::from("alice")),
+            AkdLabel::new("alice", &constraints)
+        );
+        assert_eq!(Err(InvalidBytes::Empty), AkdLabel::new("", &constraints));
+        assert_eq!(
+            Err(InvalidBytes::TooLong { len: 6, max_len: 5 }),
+            AkdValue::new("alice!", &constraints)
+        );
+        assert_eq!(
+            Err(InvalidBytes::DisallowedByte {
+                index: 1,
+                byte: 0x07
+            }),
+            AkdValue::new(b"a\x07".to_vec(), &constraints)
+        );
+        assert_eq!(
+            Ok(AkdValue::from([0u8, 0xff])),
+            AkdValue::new([0u8, 0xff], &ByteConstraints::UNCONSTRAINED)
+        );
+    }
+
+    #[test]
+    fn test_debug_is_redacted() {
+        let label = AkdLabel::from("alice@example.com");
+        let value = AkdValue::from("secret key");
+        assert_eq!("AkdLabel(<17 bytes>)", format!("{label:?}"));
+        assert_eq!("AkdValue(<10 bytes>)", format!("{value:?}"));
+    }
+}
@@ -7,6 +7,10 @@
 
 //! Utility functions
 
+use crate::configuration::Configuration;
+
+#[cfg(feature = "nostd")]
+use alloc::vec;
 #[cfg(feature = "nostd")]
 use alloc::vec::Vec;
 
@@ -16,6 +20,28 @@ pub fn get_marker_version_log2(version: u64) -> u64 {
     64 - (version.leading_zeros() as u64) - 1
 }
 
+/// The versions of a label whose non-existence proves that no version after `last_version`
+/// exists as of `current_epoch`, under the marker versions of the configuration: every version
+/// up until the next marker version, followed by every later marker version up to the current
+/// epoch (as a label has at most one version per epoch)
+pub fn future_versions<TC: Configuration>(
+    last_version: u64,
+    current_epoch: u64,
+) -> (Vec<u64>, Vec<u64>) {
+    let next_marker = TC::next_marker_version(last_version);
+    let until_marker = (last_version + 1..next_marker).collect();
+    let mut future_markers = vec![];
+    let mut marker = next_marker;
+    while marker <= current_epoch {
+        future_markers.push(marker);
+        match TC::next_marker_version(marker) {
+            next if next > marker => marker = next,
+            _ => break,
+        }
+    }
+    (until_marker, future_markers)
+}
+
 /// Corresponds to the I2OSP() function from RFC8017, prepending the length of
 /// a byte array to the byte array (so that it is ready for serialization and hashing)
 ///
@@ -141,7 +141,7 @@ pub(crate) fn verify_nonmembership<TC: Configuration>(
 /// This function is called to verify that a given [NodeLabel] is indeed
 /// the VRF for a given version (fresh or stale) for a [AkdLabel].
 /// Hence, it also takes as input the server's public key.
-fn verify_label<TC: Configuration>(
+pub(crate) fn verify_label<TC: Configuration>(
     vrf_public_key: &[u8],
     akd_label: &AkdLabel,
     freshness: VersionFreshness,
@@ -150,17 +150,19 @@ fn verify_label<TC: Configuration>(
     node_label: NodeLabel,
 ) -> Result<(), VerificationError> {
     let vrf_pk = crate::ecvrf::VRFPublicKey::try_from(vrf_public_key)?;
-    let hashed_label = TC::get_hash_from_label_input(akd_label, freshness, version);
+    let hashed_label =
+        TC::get_hash_from_label_input(&TC::normalize_label(akd_label), freshness, version);
 
     // VRF proof verification (returns VRF hash output)
     let proof = Proof::try_from(vrf_proof)?;
     vrf_pk.verify(&proof, &hashed_label)?;
     let output: crate::ecvrf::Output = (&proof).into();
 
-    if NodeLabel::new(output.to_truncated_bytes(), 256) != node_label {
+    let expected =
+        NodeLabel::new(output.to_truncated_bytes(), 256).get_prefix(TC::node_label_len());
+    if expected != node_label {
         return Err(VerificationError::Vrf(VrfError::Verification(
-            "Expected first 32 bytes of the proof output did NOT match the supplied label"
-                .to_string(),
+            "Expected prefix of the proof output did NOT match the supplied label".to_string(),
         )));
     }
     Ok(())
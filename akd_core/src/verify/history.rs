@@ -11,11 +11,17 @@ use super::base::{
     verify_existence, verify_existence_with_commitment, verify_existence_with_val,
     verify_nonexistence,
 };
+use super::lookup::lookup_verify;
 use super::VerificationError;
 
 use crate::configuration::Configuration;
 use crate::hash::Digest;
-use crate::{AkdLabel, HistoryProof, UpdateProof, VerifyResult, VersionFreshness};
+use crate::{
+    history_aggregate_label, AggregatedHistoryProof, AggregatedVerifyResult, AkdLabel, AzksValue,
+    CompactHistoryProof, HistoryAggregate, HistoryProof, LinkedHistoryProof, LinkedVerifyResult,
+    MembershipProof, NonMembershipProof, RedactionRecord, SelectiveHistoryProof,
+    SelectiveVerifyResult, UpdateDisclosure, UpdateProof, VerifyResult, VersionFreshness,
+};
 #[cfg(feature = "nostd")]
 use alloc::format;
 #[cfg(feature = "nostd")]
@@ -24,9 +30,10 @@ use alloc::string::ToString;
 use alloc::vec::Vec;
 
 /// Parameters for customizing how history proof verification proceeds
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Default)]
 pub enum HistoryVerificationParams {
     /// No customization to the verification procedure
+    #[default]
     Default,
     /// Allows for the encountering of missing (tombstoned) values
     /// instead of attempting to check if their hash matches the leaf node
@@ -34,12 +41,6 @@ pub enum HistoryVerificationParams {
     AllowMissingValues,
 }
 
-impl Default for HistoryVerificationParams {
-    fn default() -> Self {
-        Self::Default
-    }
-}
-
 /// Verifies a key history proof, given the corresponding sequence of hashes.
 /// Returns a vector of whether the validity of a hash could be verified.
 /// When false, the value <=> hash validity at the position could not be
@@ -64,25 +65,15 @@ pub fn key_history_verify<TC: Configuration>(
         )));
     }
 
-    // Check that the sent proofs are for a contiguous sequence of decreasing versions
-    for count in 0..num_proofs {
-        if count > 0 {
-            // Make sure this proof is for a version 1 more than the previous one.
-            if proof.update_proofs[count].version + 1 != proof.update_proofs[count - 1].version {
-                return Err(VerificationError::HistoryProof(format!(
-                    "Update proofs should be ordered consecutively and in decreasing order. 
-                    Error detected with version {} = {}, followed by version {} = {}",
-                    count,
-                    proof.update_proofs[count].version,
-                    count - 1,
-                    proof.update_proofs[count - 1].version
-                )));
-            }
-        }
-    }
+    verify_update_ordering(
+        &proof
+            .update_proofs
+            .iter()
+            .map(|update_proof| (update_proof.version, update_proof.epoch))
+            .collect::<Vec<_>>(),
+    )?;
 
     // Verify all individual update proofs
-    let mut maybe_previous_update_epoch = None;
     for update_proof in proof.update_proofs.into_iter() {
         // Get the highest version sent among the update proofs.
         last_version = if update_proof.version > last_version {
@@ -91,20 +82,10 @@ pub fn key_history_verify<TC: Configuration>(
             last_version
         };
 
-        if let Some(previous_update_epoch) = maybe_previous_update_epoch {
-            // Make sure this this epoch is more than the previous epoch you checked
-            if update_proof.epoch > previous_update_epoch {
-                return Err(VerificationError::HistoryProof(format!(
-                    "Version numbers for updates are decreasing, but their corresponding
-                    epochs are not decreasing: epoch = {}, previous epoch = {}",
-                    update_proof.epoch, previous_update_epoch
-                )));
-            }
-        }
-        maybe_previous_update_epoch = Some(update_proof.epoch);
         let result = verify_single_update_proof::<TC>(
             root_hash,
             vrf_public_key,
+            current_epoch,
             update_proof,
             &akd_label,
             params,
@@ -112,75 +93,134 @@ pub fn key_history_verify<TC: Configuration>(
         results.push(result);
     }
 
-    // Get the least and greatest marker entries for the current version
-    let next_marker = crate::utils::get_marker_version_log2(last_version) + 1;
-    let final_marker = crate::utils::get_marker_version_log2(current_epoch);
+    verify_no_future_versions::<TC>(
+        vrf_public_key,
+        root_hash,
+        current_epoch,
+        &akd_label,
+        last_version,
+        &proof.until_marker_vrf_proofs,
+        &proof.non_existence_until_marker_proofs,
+        &proof.future_marker_vrf_proofs,
+        &proof.non_existence_of_future_marker_proofs,
+    )?;
+
+    Ok(results)
+}
+
+/// Checks that the (version, epoch) pairs of the update proofs are for a contiguous
+/// sequence of decreasing versions, whose epochs are not increasing
+fn verify_update_ordering(updates: &[(u64, u64)]) -> Result<(), VerificationError> {
+    for count in 1..updates.len() {
+        // Make sure this proof is for a version 1 more than the previous one.
+        if updates[count].0 + 1 != updates[count - 1].0 {
+            return Err(VerificationError::HistoryProof(format!(
+                "Update proofs should be ordered consecutively and in decreasing order. 
+                Error detected with version {} = {}, followed by version {} = {}",
+                count,
+                updates[count].0,
+                count - 1,
+                updates[count - 1].0
+            )));
+        }
+    }
+
+    for count in 1..updates.len() {
+        // Make sure this this epoch is more than the previous epoch you checked
+        if updates[count].1 > updates[count - 1].1 {
+            return Err(VerificationError::HistoryProof(format!(
+                "Version numbers for updates are decreasing, but their corresponding
+                epochs are not decreasing: epoch = {}, previous epoch = {}",
+                updates[count].1,
+                updates[count - 1].1
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Verifies that no version after `last_version` exists, by checking the non-existence
+/// of every version up until the next marker, and of every future marker version
+#[allow(clippy::too_many_arguments)]
+pub(super) fn verify_no_future_versions<TC: Configuration>(
+    vrf_public_key: &[u8],
+    root_hash: Digest,
+    current_epoch: u64,
+    akd_label: &AkdLabel,
+    last_version: u64,
+    until_marker_vrf_proofs: &[Vec<u8>],
+    non_existence_until_marker_proofs: &[NonMembershipProof],
+    future_marker_vrf_proofs: &[Vec<u8>],
+    non_existence_of_future_marker_proofs: &[NonMembershipProof],
+) -> Result<(), VerificationError> {
+    // Get the versions up until the next marker, and the future marker versions
+    let (until_marker_versions, future_marker_versions) =
+        crate::utils::future_versions::<TC>(last_version, current_epoch);
 
     // Perform checks for expected number of until-marker proofs
-    let expected_num_until_marker_proofs = (1 << next_marker) - last_version - 1;
-    if expected_num_until_marker_proofs != proof.until_marker_vrf_proofs.len() as u64 {
+    let expected_num_until_marker_proofs = until_marker_versions.len();
+    if expected_num_until_marker_proofs != until_marker_vrf_proofs.len() {
         return Err(VerificationError::HistoryProof(format!(
             "Expected {} until-marker proofs, but got {}",
             expected_num_until_marker_proofs,
-            proof.until_marker_vrf_proofs.len()
+            until_marker_vrf_proofs.len()
         )));
     }
-    if proof.until_marker_vrf_proofs.len() != proof.non_existence_until_marker_proofs.len() {
+    if until_marker_vrf_proofs.len() != non_existence_until_marker_proofs.len() {
         return Err(VerificationError::HistoryProof(format!(
             "Expected equal number of until-marker proofs, but got ({}, {})",
-            proof.until_marker_vrf_proofs.len(),
-            proof.non_existence_until_marker_proofs.len()
+            until_marker_vrf_proofs.len(),
+            non_existence_until_marker_proofs.len()
         )));
     }
 
     // Verify the non-existence of future entries, up to the next marker
-    for (i, version) in (last_version + 1..(1 << next_marker)).enumerate() {
+    for (i, &version) in until_marker_versions.iter().enumerate() {
         verify_nonexistence::<TC>(
             vrf_public_key,
             root_hash,
-            &akd_label,
+            akd_label,
             VersionFreshness::Fresh,
             version,
-            &proof.until_marker_vrf_proofs[i],
-            &proof.non_existence_until_marker_proofs[i],
+            &until_marker_vrf_proofs[i],
+            &non_existence_until_marker_proofs[i],
         )
         .map_err(|_| {
             VerificationError::HistoryProof(format!(
                 "Non-existence of next few proof of label {:?} with version
                 {:?} at epoch {:?} does not verify",
-                &akd_label, version, current_epoch
+                akd_label, version, current_epoch
             ))
         })?;
     }
 
     // Perform checks for expected number of future-marker proofs
-    let expected_num_future_marker_proofs = final_marker + 1 - next_marker;
-    if expected_num_future_marker_proofs != proof.future_marker_vrf_proofs.len() as u64 {
+    let expected_num_future_marker_proofs = future_marker_versions.len();
+    if expected_num_future_marker_proofs != future_marker_vrf_proofs.len() {
         return Err(VerificationError::HistoryProof(format!(
             "Expected {} future-marker proofs, but got {}",
             expected_num_future_marker_proofs,
-            proof.future_marker_vrf_proofs.len()
+            future_marker_vrf_proofs.len()
         )));
     }
-    if proof.future_marker_vrf_proofs.len() != proof.non_existence_of_future_marker_proofs.len() {
+    if future_marker_vrf_proofs.len() != non_existence_of_future_marker_proofs.len() {
         return Err(VerificationError::HistoryProof(format!(
             "Expected equal number of future-marker proofs, but got ({}, {})",
-            proof.future_marker_vrf_proofs.len(),
-            proof.non_existence_of_future_marker_proofs.len()
+            future_marker_vrf_proofs.len(),
+            non_existence_of_future_marker_proofs.len()
         )));
     }
 
     // Verify the VRFs and non-membership proofs for future markers
-    for (i, pow) in (next_marker..final_marker + 1).enumerate() {
-        let version = 1 << pow;
+    for (i, &version) in future_marker_versions.iter().enumerate() {
         verify_nonexistence::<TC>(
             vrf_public_key,
             root_hash,
-            &akd_label,
+            akd_label,
             VersionFreshness::Fresh,
             version,
-            &proof.future_marker_vrf_proofs[i],
-            &proof.non_existence_of_future_marker_proofs[i],
+            &future_marker_vrf_proofs[i],
+            &non_existence_of_future_marker_proofs[i],
         )
         .map_err(|_| {
             VerificationError::HistoryProof(format!(
@@ -190,23 +230,379 @@ pub fn key_history_verify<TC: Configuration>(
         })?;
     }
 
+    Ok(())
+}
+
+/// Verifies a key history proof which was generated for the updates in the last
+/// `num_epochs` epochs, as in [key_history_verify], and additionally checks that the proof
+/// contains every update made within those epochs. This holds when the oldest update in the
+/// proof was either made at or before the start of the window, or is the first version
+/// of the label. The results include that oldest update, whose value was the current value
+/// at the start of the window.
+pub fn key_history_verify_recent_epochs<TC: Configuration>(
+    vrf_public_key: &[u8],
+    root_hash: Digest,
+    current_epoch: u64,
+    akd_label: AkdLabel,
+    proof: HistoryProof,
+    num_epochs: u64,
+    params: HistoryVerificationParams,
+) -> Result<Vec<VerifyResult>, VerificationError> {
+    let results = key_history_verify::<TC>(
+        vrf_public_key,
+        root_hash,
+        current_epoch,
+        akd_label,
+        proof,
+        params,
+    )?;
+
+    let window_start = current_epoch.saturating_sub(num_epochs);
+    // The results are non-empty and in decreasing order of version
+    let oldest = &results[results.len() - 1];
+    if oldest.version != 1 && oldest.epoch > window_start {
+        return Err(VerificationError::HistoryProof(format!(
+            "History proof is incomplete for the last {num_epochs} epochs: the oldest update \
+            (version {}) is at epoch {}, which is after the start of the window at epoch {window_start}",
+            oldest.version, oldest.epoch
+        )));
+    }
+    Ok(results)
+}
+
+/// Verifies a [CompactHistoryProof] by expanding it into the [HistoryProof] it was compacted
+/// from, and then verifying that proof as in [key_history_verify].
+pub fn compact_key_history_verify<TC: Configuration>(
+    vrf_public_key: &[u8],
+    root_hash: Digest,
+    current_epoch: u64,
+    akd_label: AkdLabel,
+    proof: CompactHistoryProof,
+    params: HistoryVerificationParams,
+) -> Result<Vec<VerifyResult>, VerificationError> {
+    key_history_verify::<TC>(
+        vrf_public_key,
+        root_hash,
+        current_epoch,
+        akd_label,
+        proof.expand()?,
+        params,
+    )
+}
+
+/// Verifies an [AggregatedHistoryProof]. The versions after the aggregate are verified as in
+/// [key_history_verify], and the aggregate is verified with a lookup of the label's aggregate
+/// label, as in [lookup_verify]. The oldest explicit version must directly follow the last
+/// aggregated version, or be the first version of the label if there is no aggregate. The
+/// versions within the aggregate are not checked (see [HistoryAggregate::extend]).
+pub fn aggregated_key_history_verify<TC: Configuration>(
+    vrf_public_key: &[u8],
+    root_hash: Digest,
+    current_epoch: u64,
+    akd_label: AkdLabel,
+    proof: AggregatedHistoryProof,
+    params: HistoryVerificationParams,
+) -> Result<AggregatedVerifyResult, VerificationError> {
+    let aggregate_label = history_aggregate_label(&TC::normalize_label(&akd_label));
+    let recent = key_history_verify::<TC>(
+        vrf_public_key,
+        root_hash,
+        current_epoch,
+        akd_label,
+        proof.recent,
+        params,
+    )?;
+
+    // The results are non-empty and in decreasing order of version
+    let oldest = &recent[recent.len() - 1];
+    let aggregate = match proof.aggregate {
+        None => {
+            if oldest.version != 1 {
+                return Err(VerificationError::HistoryProof(format!(
+                    "The history proof begins at version {}, but has no aggregate of the earlier versions",
+                    oldest.version
+                )));
+            }
+            None
+        }
+        Some(lookup_proof) => {
+            let result = lookup_verify::<TC>(
+                vrf_public_key,
+                root_hash,
+                current_epoch,
+                aggregate_label,
+                lookup_proof,
+            )?;
+            let aggregate = HistoryAggregate::from_value(&result.value).ok_or_else(|| {
+                VerificationError::HistoryProof(
+                    "The value of the aggregate label is not a history aggregate".to_string(),
+                )
+            })?;
+            if oldest.version != aggregate.last_version + 1 || oldest.epoch <= aggregate.last_epoch
+            {
+                return Err(VerificationError::HistoryProof(format!(
+                    "The history proof begins at version {} at epoch {}, which does not follow the \
+                    aggregate of version {} at epoch {}",
+                    oldest.version, oldest.epoch, aggregate.last_version, aggregate.last_epoch
+                )));
+            }
+            Some(aggregate)
+        }
+    };
+    Ok(AggregatedVerifyResult { aggregate, recent })
+}
+
+/// Verifies a [LinkedHistoryProof] for a label whose history may have been moved to other
+/// labels. The history of each label in the chain, starting with `akd_label`, is verified as
+/// in [key_history_verify]. Each history except the last must end with a link record to the
+/// next label, and the next label must have been created in the same epoch as the link. The
+/// updates of all of the labels are returned together (without the link records), ordered
+/// from the most recent update to the earliest one.
+pub fn linked_key_history_verify<TC: Configuration>(
+    vrf_public_key: &[u8],
+    root_hash: Digest,
+    current_epoch: u64,
+    akd_label: AkdLabel,
+    proof: LinkedHistoryProof,
+    params: HistoryVerificationParams,
+) -> Result<Vec<LinkedVerifyResult>, VerificationError> {
+    match proof.segments.first() {
+        Some(segment) if segment.label == *TC::normalize_label(&akd_label) => {}
+        _ => {
+            return Err(VerificationError::HistoryProof(format!(
+                "The linked history proof does not begin with the history of user {akd_label:?}"
+            )))
+        }
+    }
+
+    let num_segments = proof.segments.len();
+    let next_labels = proof
+        .segments
+        .iter()
+        .skip(1)
+        .map(|segment| Some(segment.label.clone()))
+        .chain([None])
+        .collect::<Vec<_>>();
+    let mut segment_results = Vec::with_capacity(num_segments);
+    let mut link_epoch = None;
+    for (segment, next_label) in proof.segments.into_iter().zip(next_labels) {
+        let mut results = key_history_verify::<TC>(
+            vrf_public_key,
+            root_hash,
+            current_epoch,
+            segment.label.clone(),
+            segment.proof,
+            params,
+        )?;
+
+        if let Some(link_epoch) = link_epoch {
+            // The label must have been created by the link to it
+            match results.last() {
+                Some(first) if first.version == 1 && first.epoch == link_epoch => {}
+                _ => {
+                    return Err(VerificationError::HistoryProof(format!(
+                        "The history of user {:?} does not begin with the link to it at epoch {link_epoch}",
+                        segment.label
+                    )))
+                }
+            }
+        }
+
+        // The results are ordered from the most recent update, which must be a link to the next label
+        let link_target = results[0].value.link_target();
+        if link_target != next_label {
+            return Err(VerificationError::HistoryProof(format!(
+                "The history of user {:?} ends with a link to {link_target:?}, but is followed by {next_label:?}",
+                segment.label
+            )));
+        }
+        if next_label.is_some() {
+            link_epoch = Some(results.remove(0).epoch);
+        }
+
+        segment_results.push(
+            results
+                .into_iter()
+                .map(|result| LinkedVerifyResult {
+                    label: segment.label.clone(),
+                    result,
+                })
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    Ok(segment_results.into_iter().rev().flatten().collect())
+}
+
+/// Verifies a [SelectiveHistoryProof]. Every version is checked to have been published at
+/// its epoch, as in [key_history_verify], but only the versions published within the
+/// disclosed epochs are checked against (and return) their values. All other versions
+/// are checked against the commitments to their values, which are returned as `None`.
+pub fn selective_key_history_verify<TC: Configuration>(
+    vrf_public_key: &[u8],
+    root_hash: Digest,
+    current_epoch: u64,
+    akd_label: AkdLabel,
+    proof: SelectiveHistoryProof,
+) -> Result<Vec<SelectiveVerifyResult>, VerificationError> {
+    // Make sure the update proofs are non-empty
+    if proof.update_proofs.is_empty() {
+        return Err(VerificationError::HistoryProof(format!(
+            "No update proofs included in the proof of user {akd_label:?} at epoch {current_epoch:?}!"
+        )));
+    }
+    if proof.disclosed_start_epoch > proof.disclosed_end_epoch {
+        return Err(VerificationError::HistoryProof(format!(
+            "Disclosed epochs are empty: start epoch {} is after end epoch {}",
+            proof.disclosed_start_epoch, proof.disclosed_end_epoch
+        )));
+    }
+
+    verify_update_ordering(
+        &proof
+            .update_proofs
+            .iter()
+            .map(|update_proof| (update_proof.version, update_proof.epoch))
+            .collect::<Vec<_>>(),
+    )?;
+
+    let disclosed_epochs = proof.disclosed_start_epoch..=proof.disclosed_end_epoch;
+    let last_version = proof.update_proofs[0].version;
+    let mut results = Vec::new();
+    for update_proof in proof.update_proofs.into_iter() {
+        let value = match update_proof.disclosure {
+            UpdateDisclosure::Revealed {
+                value,
+                commitment_nonce,
+            } => {
+                verify_existence_with_val::<TC>(
+                    vrf_public_key,
+                    root_hash,
+                    &akd_label,
+                    &value,
+                    update_proof.epoch,
+                    &commitment_nonce,
+                    VersionFreshness::Fresh,
+                    update_proof.version,
+                    &update_proof.existence_vrf_proof,
+                    &update_proof.existence_proof,
+                )?;
+                Some(value)
+            }
+            UpdateDisclosure::Hidden { commitment } => {
+                if disclosed_epochs.contains(&update_proof.epoch) {
+                    return Err(VerificationError::HistoryProof(format!(
+                        "Version {} was published at epoch {}, which is within the disclosed \
+                        epochs, but its value was not revealed",
+                        update_proof.version, update_proof.epoch
+                    )));
+                }
+                verify_existence_with_commitment::<TC>(
+                    vrf_public_key,
+                    root_hash,
+                    &akd_label,
+                    commitment,
+                    update_proof.epoch,
+                    VersionFreshness::Fresh,
+                    update_proof.version,
+                    &update_proof.existence_vrf_proof,
+                    &update_proof.existence_proof,
+                )?;
+                None
+            }
+        };
+
+        verify_previous_version_stale::<TC>(
+            root_hash,
+            vrf_public_key,
+            &akd_label,
+            update_proof.epoch,
+            update_proof.version,
+            update_proof.previous_version_vrf_proof.as_ref(),
+            update_proof.previous_version_proof.as_ref(),
+        )?;
+
+        results.push(SelectiveVerifyResult {
+            epoch: update_proof.epoch,
+            version: update_proof.version,
+            value,
+        });
+    }
+
+    verify_no_future_versions::<TC>(
+        vrf_public_key,
+        root_hash,
+        current_epoch,
+        &akd_label,
+        last_version,
+        &proof.until_marker_vrf_proofs,
+        &proof.non_existence_until_marker_proofs,
+        &proof.future_marker_vrf_proofs,
+        &proof.non_existence_of_future_marker_proofs,
+    )?;
+
     Ok(results)
 }
 
+/// Verifies the commitment and epoch carried by a tombstone (or redaction record) in an update
+/// proof: the commitment to the removed value must be the one in the tree, and the value must
+/// have been removed no earlier than it was published, and no later than the current epoch
+fn verify_removed_value<TC: Configuration>(
+    commitment: AzksValue,
+    removed_at: u64,
+    removal: &str,
+    proof: &UpdateProof,
+    current_epoch: u64,
+) -> Result<(), VerificationError> {
+    if TC::hash_leaf_with_commitment(commitment, proof.epoch).0 != proof.existence_proof.hash_val.0
+    {
+        return Err(VerificationError::HistoryProof(format!(
+            "The commitment of the {removal} value of version {} does not match its existence proof",
+            proof.version
+        )));
+    }
+    if removed_at < proof.epoch || removed_at > current_epoch {
+        return Err(VerificationError::HistoryProof(format!(
+            "Version {} published at epoch {} cannot have been {removal} at epoch {removed_at} as of epoch {current_epoch}",
+            proof.version, proof.epoch
+        )));
+    }
+    Ok(())
+}
+
 /// Verifies a single update proof
 fn verify_single_update_proof<TC: Configuration>(
     root_hash: Digest,
     vrf_public_key: &[u8],
+    current_epoch: u64,
     proof: UpdateProof,
     akd_label: &AkdLabel,
     params: HistoryVerificationParams,
 ) -> Result<VerifyResult, VerificationError> {
     // Verify the VRF and membership proof for the corresponding label for the version being updated to.
     match (params, &proof.value) {
-        (HistoryVerificationParams::AllowMissingValues, bytes) if bytes.0 == crate::TOMBSTONE => {
+        (HistoryVerificationParams::AllowMissingValues, value) if TC::is_tombstone(value) => {
             // A tombstone was encountered, we need to just take the
             // hash of the value at "face value" since we don't have
-            // the real value available
+            // the real value available, unless the tombstone carries
+            // the commitment to the value which it replaced
+            if let Some(metadata) = TC::tombstone_metadata(value) {
+                verify_removed_value::<TC>(
+                    metadata.commitment,
+                    metadata.tombstoned_at,
+                    "tombstoned",
+                    &proof,
+                    current_epoch,
+                )?;
+            } else if let Some(record) = RedactionRecord::from_value(value) {
+                verify_removed_value::<TC>(
+                    record.commitment,
+                    record.redacted_at,
+                    "redacted",
+                    &proof,
+                    current_epoch,
+                )?;
+            }
             verify_existence::<TC>(
                 vrf_public_key,
                 root_hash,
@@ -240,33 +636,52 @@ fn verify_single_update_proof<TC: Configuration>(
         value: proof.value,
     };
 
-    if proof.version <= 1 {
-        // There is no previous version, so we can just return here
-        return Ok(verify_result);
-    }
-
     // ***** PART 2 ***************************
     // Verify the membership proof the for stale label of the previous version
+    verify_previous_version_stale::<TC>(
+        root_hash,
+        vrf_public_key,
+        akd_label,
+        proof.epoch,
+        proof.version,
+        proof.previous_version_vrf_proof.as_ref(),
+        proof.previous_version_proof.as_ref(),
+    )?;
+
+    Ok(verify_result)
+}
 
-    let previous_version_proof = proof.previous_version_proof.as_ref().ok_or_else(|| {
+/// Verifies that the version previous to `version` was marked as stale at `epoch`
+fn verify_previous_version_stale<TC: Configuration>(
+    root_hash: Digest,
+    vrf_public_key: &[u8],
+    akd_label: &AkdLabel,
+    epoch: u64,
+    version: u64,
+    previous_version_vrf_proof: Option<&Vec<u8>>,
+    previous_version_proof: Option<&MembershipProof>,
+) -> Result<(), VerificationError> {
+    if version <= 1 {
+        // There is no previous version
+        return Ok(());
+    }
+
+    let previous_version_proof = previous_version_proof.ok_or_else(|| {
         VerificationError::HistoryProof("Missing membership proof for previous version".to_string())
     })?;
-    let previous_version_vrf_proof =
-        proof.previous_version_vrf_proof.as_ref().ok_or_else(|| {
-            VerificationError::HistoryProof("Missing VRF proof for previous version".to_string())
-        })?;
+    let previous_version_vrf_proof = previous_version_vrf_proof.ok_or_else(|| {
+        VerificationError::HistoryProof("Missing VRF proof for previous version".to_string())
+    })?;
 
     verify_existence_with_commitment::<TC>(
         vrf_public_key,
         root_hash,
         akd_label,
         TC::stale_azks_value(),
-        proof.epoch,
+        epoch,
         VersionFreshness::Stale,
-        proof.version - 1,
+        version - 1,
         previous_version_vrf_proof,
         previous_version_proof,
-    )?;
-
-    Ok(verify_result)
+    )
 }
@@ -8,11 +8,16 @@
 //! Verification of lookup proofs
 
 use super::base::{verify_existence, verify_existence_with_val, verify_nonexistence};
+use super::history::verify_no_future_versions;
 use super::VerificationError;
 
 use crate::configuration::Configuration;
 use crate::hash::Digest;
-use crate::{AkdLabel, LookupProof, VerifyResult, VersionFreshness};
+use crate::signing::{configuration_fingerprint, verify_epoch_signature, EpochSignatureVerifier};
+use crate::{
+    AkdLabel, LatestVersionProof, LookupProof, LookupResult, NonExistenceProof,
+    SignedLookupResponse, VerifyResult, VersionFreshness,
+};
 
 /// Verifies a lookup with respect to the root_hash
 pub fn lookup_verify<TC: Configuration>(
@@ -43,7 +48,7 @@ pub fn lookup_verify<TC: Configuration>(
         &proof.existence_proof,
     )?;
 
-    let marker_version = 1 << crate::utils::get_marker_version_log2(proof.version);
+    let marker_version = TC::marker_version(proof.version);
     verify_existence::<TC>(
         vrf_public_key,
         root_hash,
@@ -70,3 +75,122 @@ pub fn lookup_verify<TC: Configuration>(
         value: proof.value,
     })
 }
+
+/// Verifies a lookup with respect to the root_hash as in [lookup_verify], returning a
+/// [LookupResult] which carries the label, the root hash, and the proof along with the value
+pub fn lookup_verify_result<TC: Configuration>(
+    vrf_public_key: &[u8],
+    root_hash: Digest,
+    current_epoch: u64,
+    akd_label: AkdLabel,
+    proof: LookupProof,
+) -> Result<LookupResult, VerificationError> {
+    let result = lookup_verify::<TC>(
+        vrf_public_key,
+        root_hash,
+        current_epoch,
+        akd_label.clone(),
+        proof.clone(),
+    )?;
+    Ok(LookupResult {
+        label: akd_label,
+        value: result.value,
+        version: result.version,
+        epoch: result.epoch,
+        current_epoch,
+        root_hash,
+        proof,
+    })
+}
+
+/// Verifies a [LatestVersionProof] with respect to the root_hash: that the lookup proof
+/// verifies as in [lookup_verify], and that no version newer than the one looked up
+/// exists as of `current_epoch`
+pub fn lookup_latest_verify<TC: Configuration>(
+    vrf_public_key: &[u8],
+    root_hash: Digest,
+    current_epoch: u64,
+    akd_label: AkdLabel,
+    proof: LatestVersionProof,
+) -> Result<VerifyResult, VerificationError> {
+    let result = lookup_verify::<TC>(
+        vrf_public_key,
+        root_hash,
+        current_epoch,
+        akd_label.clone(),
+        proof.lookup_proof,
+    )?;
+
+    verify_no_future_versions::<TC>(
+        vrf_public_key,
+        root_hash,
+        current_epoch,
+        &akd_label,
+        result.version,
+        &proof.until_marker_vrf_proofs,
+        &proof.non_existence_until_marker_proofs,
+        &proof.future_marker_vrf_proofs,
+        &proof.non_existence_of_future_marker_proofs,
+    )?;
+
+    Ok(result)
+}
+
+/// Verifies a [SignedLookupResponse]: that it was produced by a directory running the same
+/// configuration, that the directory signed the root hash for the response's epoch (with the
+/// root key of `epoch_verifier`, or with a delegate which the root key certified for the
+/// epoch), and that the lookup proof verifies against that root hash
+pub fn signed_lookup_verify<TC: Configuration>(
+    vrf_public_key: &[u8],
+    epoch_verifier: &impl EpochSignatureVerifier,
+    akd_label: AkdLabel,
+    response: SignedLookupResponse,
+) -> Result<VerifyResult, VerificationError> {
+    if response.configuration_fingerprint != configuration_fingerprint::<TC>() {
+        return Err(VerificationError::LookupProof(alloc::format!(
+            "Configuration fingerprint {} does not match the expected configuration",
+            hex::encode(response.configuration_fingerprint)
+        )));
+    }
+
+    if !verify_epoch_signature::<TC>(
+        epoch_verifier,
+        response.delegation.as_ref(),
+        response.epoch,
+        &response.root_hash,
+        &response.epoch_signature,
+    ) {
+        return Err(VerificationError::LookupProof(alloc::format!(
+            "Invalid signature for the root hash of epoch {}",
+            response.epoch
+        )));
+    }
+
+    lookup_verify::<TC>(
+        vrf_public_key,
+        response.root_hash,
+        response.epoch,
+        akd_label,
+        response.proof,
+    )
+}
+
+/// Verifies a [NonExistenceProof] with respect to the root_hash, which shows that
+/// the label has never been registered in the directory
+pub fn nonexistence_verify<TC: Configuration>(
+    vrf_public_key: &[u8],
+    root_hash: Digest,
+    akd_label: AkdLabel,
+    proof: NonExistenceProof,
+) -> Result<(), VerificationError> {
+    // Versions start at 1, so a label without its first version was never registered
+    verify_nonexistence::<TC>(
+        vrf_public_key,
+        root_hash,
+        &akd_label,
+        VersionFreshness::Fresh,
+        1,
+        &proof.vrf_proof,
+        &proof.non_membership_proof,
+    )
+}
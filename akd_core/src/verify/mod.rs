@@ -10,6 +10,7 @@
 pub mod base;
 pub mod history;
 pub mod lookup;
+pub mod sharded;
 
 #[cfg(feature = "nostd")]
 use alloc::format;
@@ -29,6 +30,10 @@ pub enum VerificationError {
     LookupProof(String),
     /// Error verifying a history proof
     HistoryProof(String),
+    /// Error verifying that the root of a shard is committed to by a top-level root hash
+    ShardProof(String),
+    /// Error verifying a random spot check of an epoch
+    SpotCheck(String),
     /// Error verifying a VRF proof
     #[cfg(feature = "vrf")]
     Vrf(crate::ecvrf::VrfError),
@@ -46,6 +51,8 @@ impl core::fmt::Display for VerificationError {
             }
             VerificationError::LookupProof(err) => format!("(Lookup proof) - {err}"),
             VerificationError::HistoryProof(err) => format!("(History proof) - {err}"),
+            VerificationError::ShardProof(err) => format!("(Shard proof) - {err}"),
+            VerificationError::SpotCheck(err) => format!("(Spot check) - {err}"),
             #[cfg(feature = "vrf")]
             VerificationError::Vrf(vrf) => vrf.to_string(),
             #[cfg(feature = "protobuf")]
@@ -55,6 +62,9 @@ impl core::fmt::Display for VerificationError {
     }
 }
 
+#[cfg(not(feature = "nostd"))]
+impl std::error::Error for VerificationError {}
+
 #[cfg(feature = "vrf")]
 impl From<crate::ecvrf::VrfError> for VerificationError {
     fn from(input: crate::ecvrf::VrfError) -> Self {
@@ -82,5 +92,13 @@ impl From<protobuf::Error> for VerificationError {
 #[cfg(feature = "public_tests")]
 pub use base::{verify_membership_for_tests_only, verify_nonmembership_for_tests_only};
 
-pub use history::{key_history_verify, HistoryVerificationParams};
-pub use lookup::lookup_verify;
+pub use history::{
+    aggregated_key_history_verify, compact_key_history_verify, key_history_verify,
+    key_history_verify_recent_epochs, linked_key_history_verify, selective_key_history_verify,
+    HistoryVerificationParams,
+};
+pub use lookup::{
+    lookup_latest_verify, lookup_verify, lookup_verify_result, nonexistence_verify,
+    signed_lookup_verify,
+};
+pub use sharded::{sharded_key_history_verify, sharded_lookup_verify};
@@ -0,0 +1,81 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Verification of the proofs of sharded directories (see [crate::sharding])
+
+use super::history::{key_history_verify, HistoryVerificationParams};
+use super::lookup::lookup_verify;
+use super::VerificationError;
+
+use crate::configuration::Configuration;
+use crate::hash::Digest;
+use crate::sharding::{shard_index, ShardInclusionProof, ShardedHistoryProof, ShardedLookupProof};
+use crate::{AkdLabel, VerifyResult};
+
+#[cfg(feature = "nostd")]
+use alloc::format;
+#[cfg(feature = "nostd")]
+use alloc::vec::Vec;
+
+/// Verifies that the shard of an inclusion proof is the shard of the label, and that its
+/// root is committed to by the top-level root hash
+fn verify_label_shard<TC: Configuration>(
+    top_level_root: Digest,
+    akd_label: &AkdLabel,
+    inclusion: &ShardInclusionProof,
+) -> Result<(), VerificationError> {
+    inclusion.verify::<TC>(top_level_root)?;
+    let expected = shard_index::<TC>(akd_label, inclusion.num_shards);
+    if inclusion.shard != expected {
+        return Err(VerificationError::ShardProof(format!(
+            "The label belongs to shard {expected}, but the proof is for shard {}",
+            inclusion.shard
+        )));
+    }
+    Ok(())
+}
+
+/// Verifies a lookup of a sharded directory with respect to its top-level root hash: the
+/// root of the label's shard must be committed to by the top-level root hash, and the
+/// lookup proof must verify with respect to the root of the shard
+pub fn sharded_lookup_verify<TC: Configuration>(
+    vrf_public_key: &[u8],
+    top_level_root: Digest,
+    akd_label: AkdLabel,
+    proof: ShardedLookupProof,
+) -> Result<VerifyResult, VerificationError> {
+    verify_label_shard::<TC>(top_level_root, &akd_label, &proof.inclusion)?;
+    let shard_root = proof.inclusion.shard_root;
+    lookup_verify::<TC>(
+        vrf_public_key,
+        shard_root.root_hash,
+        shard_root.epoch,
+        akd_label,
+        proof.proof,
+    )
+}
+
+/// Verifies the key history of a sharded directory with respect to its top-level root hash,
+/// as with [sharded_lookup_verify]. The epochs of the results are epochs of the label's shard.
+pub fn sharded_key_history_verify<TC: Configuration>(
+    vrf_public_key: &[u8],
+    top_level_root: Digest,
+    akd_label: AkdLabel,
+    proof: ShardedHistoryProof,
+    params: HistoryVerificationParams,
+) -> Result<Vec<VerifyResult>, VerificationError> {
+    verify_label_shard::<TC>(top_level_root, &akd_label, &proof.inclusion)?;
+    let shard_root = proof.inclusion.shard_root;
+    key_history_verify::<TC>(
+        vrf_public_key,
+        shard_root.root_hash,
+        shard_root.epoch,
+        akd_label,
+        proof.proof,
+        params,
+    )
+}
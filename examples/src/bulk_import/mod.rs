@@ -0,0 +1,374 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! An importer which populates a directory from an export of an existing user database.
+//! The input is streamed as label/value pairs from either a CSV file (one `label,value`
+//! pair per line) or an NDJSON file (one `{"label": ..., "value": ...}` object per line),
+//! and is published in chunks of a fixed number of updates per epoch. Example command:
+//!
+//!   cargo run -p examples -- bulk-import \
+//!     --input users.csv \
+//!     --format csv \
+//!     --cursor import.cursor
+//!
+//! Every record is validated before it is published: labels and values must be valid in
+//! the selected encoding, labels must not be empty, values must not be link records, and
+//! a label may appear at most once in the input. After each chunk is published, the
+//! position reached in the input is written to the cursor file, so that an interrupted
+//! import against persistent storage can be resumed from the next chunk by re-running
+//! the same command.
+
+#[cfg(test)]
+mod tests;
+
+use akd::ecvrf::{HardCodedAkdVRF, VRFKeyStorage};
+use akd::storage::{Database, StorageManager};
+use akd::{AkdLabel, AkdValue, Configuration, Directory, LINK_VALUE_PREFIX};
+use anyhow::{anyhow, bail, Result};
+use clap::{Parser, ValueEnum};
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::mysql_demo::mysql::AsyncMySqlDatabase;
+
+type TC = akd::ExperimentalConfiguration<akd::ExampleLabel>;
+
+#[derive(Parser, Debug, Clone)]
+pub(crate) struct CliArgs {
+    /// The file containing the label/value pairs to import
+    #[clap(long = "input")]
+    input: PathBuf,
+    /// The format of the input file
+    #[clap(long = "format", value_enum, default_value = "csv")]
+    format: ImportFormat,
+    /// The encoding of the labels and values in the input file
+    #[clap(long = "encoding", value_enum, default_value = "utf8")]
+    encoding: ValueEncoding,
+    /// Whether the first line of a CSV input is a header, which is skipped
+    #[clap(long = "header")]
+    header: bool,
+    /// The number of updates which are published in each epoch
+    #[clap(long = "chunk_size", default_value = "10000")]
+    chunk_size: usize,
+    /// The file in which the progress of the import is recorded. If the file exists,
+    /// the import resumes after the last chunk which it records as published.
+    #[clap(long = "cursor")]
+    cursor: Option<PathBuf>,
+    /// Only validate the input, without publishing it
+    #[clap(long = "validate_only")]
+    validate_only: bool,
+    /// Import into an in-memory database, rather than the MySQL database of the MySQL demo
+    #[clap(long = "memory")]
+    memory_db: bool,
+}
+
+/// The format of an import file
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ImportFormat {
+    /// One `label,value` pair per line
+    Csv,
+    /// One `{"label": ..., "value": ...}` object per line
+    Ndjson,
+}
+
+/// The encoding of the labels and values in an import file
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ValueEncoding {
+    /// The UTF-8 bytes of the string are used as-is
+    Utf8,
+    /// The string is decoded from hex
+    Hex,
+}
+
+/// The options which control how an input is parsed and published
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ImportOptions {
+    pub format: ImportFormat,
+    pub encoding: ValueEncoding,
+    pub header: bool,
+    pub chunk_size: usize,
+    pub validate_only: bool,
+}
+
+/// The position reached by an import, which is recorded after each published chunk
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ImportCursor {
+    /// The number of lines of the input which have been published
+    pub line: u64,
+    /// The epoch of the directory after the last published chunk
+    pub epoch: u64,
+}
+
+/// The outcome of an import
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct ImportSummary {
+    /// The number of records read from the input, including those skipped on resume
+    pub records: u64,
+    /// The number of records which were published by this run of the import
+    pub published: u64,
+    /// The number of epochs which were published by this run of the import
+    pub epochs: u64,
+    /// The position reached by the import
+    pub cursor: ImportCursor,
+}
+
+#[derive(Deserialize)]
+struct NdjsonRecord {
+    label: String,
+    value: String,
+}
+
+pub(crate) async fn run(args: CliArgs) -> Result<()> {
+    if args.chunk_size == 0 {
+        bail!("The chunk size must be at least 1");
+    }
+    let options = ImportOptions {
+        format: args.format,
+        encoding: args.encoding,
+        header: args.header,
+        chunk_size: args.chunk_size,
+        validate_only: args.validate_only,
+    };
+
+    if args.memory_db {
+        let db = akd::storage::memory::AsyncInMemoryDatabase::new();
+        import_into(db, &args, options).await
+    } else {
+        // MySQL (the default), with the same connection settings as the MySQL demo
+        let db = AsyncMySqlDatabase::new(
+            "localhost",
+            "default",
+            Option::from("root"),
+            Option::from("example"),
+            Option::from(8001),
+            1000,
+        )
+        .await?;
+        import_into(db, &args, options).await
+    }
+}
+
+async fn import_into<S: Database + 'static>(
+    db: S,
+    args: &CliArgs,
+    options: ImportOptions,
+) -> Result<()> {
+    let storage_manager = StorageManager::new_no_cache(db);
+    let directory = Directory::<TC, _, _>::new(storage_manager, HardCodedAkdVRF {}).await?;
+
+    let resume_from = match &args.cursor {
+        Some(path) if path.exists() => Some(read_cursor(path)?),
+        _ => None,
+    };
+    if let Some(cursor) = resume_from {
+        println!(
+            "Resuming import after line {} (epoch {})",
+            cursor.line, cursor.epoch
+        );
+    }
+
+    let total_lines = BufReader::new(File::open(&args.input)?).lines().count() as u64;
+    let pb = ProgressBar::new(total_lines);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} lines {msg}")?,
+    );
+    pb.enable_steady_tick(Duration::from_millis(200));
+
+    let reader = BufReader::new(File::open(&args.input)?);
+    let summary = import(
+        &directory,
+        reader,
+        options,
+        resume_from,
+        |cursor, published| {
+            pb.set_position(cursor.line);
+            if published {
+                pb.set_message(format!("(epoch {})", cursor.epoch));
+                if let Some(path) = &args.cursor {
+                    write_cursor(path, cursor)?;
+                }
+            }
+            Ok(())
+        },
+    )
+    .await;
+    pb.finish_and_clear();
+    let summary = summary?;
+
+    if options.validate_only {
+        println!("Validated {} records", summary.records);
+    } else {
+        println!(
+            "Published {} of {} records in {} epochs, the directory is now at epoch {}",
+            summary.published, summary.records, summary.epochs, summary.cursor.epoch
+        );
+    }
+    Ok(())
+}
+
+/// Imports the records of the input into the directory, publishing one epoch for every
+/// `chunk_size` records. When `resume_from` is provided, the lines up to the cursor are
+/// only validated, since they were published by a previous run of the import.
+///
+/// The callback is invoked with the current position after each chunk is read, and
+/// with `true` once the chunk has been published. An error returned by the callback
+/// stops the import.
+pub(crate) async fn import<TC, S, V, R, F>(
+    directory: &Directory<TC, S, V>,
+    reader: R,
+    options: ImportOptions,
+    resume_from: Option<ImportCursor>,
+    mut on_progress: F,
+) -> Result<ImportSummary>
+where
+    TC: Configuration,
+    S: Database + 'static,
+    V: VRFKeyStorage,
+    R: BufRead,
+    F: FnMut(&ImportCursor, bool) -> Result<()>,
+{
+    let mut cursor = match resume_from {
+        Some(cursor) => {
+            let current_epoch = directory.get_epoch_hash().await?.epoch();
+            if !options.validate_only && current_epoch != cursor.epoch {
+                bail!(
+                    "The cursor was recorded at epoch {}, but the directory is at epoch {}. \
+                    The directory was modified since the cursor was written.",
+                    cursor.epoch,
+                    current_epoch
+                );
+            }
+            cursor
+        }
+        None => ImportCursor {
+            line: 0,
+            epoch: directory.get_epoch_hash().await?.epoch(),
+        },
+    };
+    let resume_line = cursor.line;
+    let mut summary = ImportSummary::default();
+    let mut seen: HashMap<AkdLabel, u64> = HashMap::new();
+    let mut chunk = Vec::with_capacity(options.chunk_size);
+
+    let mut lines = reader.lines();
+    let mut line_number = 0u64;
+    loop {
+        let line = lines.next().transpose()?;
+        if let Some(line) = &line {
+            line_number += 1;
+            let record =
+                if options.header && options.format == ImportFormat::Csv && line_number == 1 {
+                    None
+                } else {
+                    parse_record(line, options)
+                        .map_err(|err| anyhow!("Line {}: {}", line_number, err))?
+                };
+            if let Some((label, value)) = record {
+                if let Some(first) = seen.insert(label.clone(), line_number) {
+                    bail!(
+                        "Line {}: duplicate label, which was first seen on line {}",
+                        line_number,
+                        first
+                    );
+                }
+                summary.records += 1;
+                if line_number > resume_line && !options.validate_only {
+                    chunk.push((label, value));
+                }
+            }
+        }
+
+        let end_of_input = line.is_none();
+        if chunk.len() == options.chunk_size || (end_of_input && !chunk.is_empty()) {
+            let updates = std::mem::replace(&mut chunk, Vec::with_capacity(options.chunk_size));
+            let num_updates = updates.len() as u64;
+            let epoch_hash = directory.publish(updates).await?;
+            cursor = ImportCursor {
+                line: line_number,
+                epoch: epoch_hash.epoch(),
+            };
+            summary.published += num_updates;
+            summary.epochs += 1;
+            on_progress(&cursor, true)?;
+        } else if line_number > cursor.line {
+            on_progress(
+                &ImportCursor {
+                    line: line_number,
+                    epoch: cursor.epoch,
+                },
+                false,
+            )?;
+        }
+
+        if end_of_input {
+            break;
+        }
+    }
+
+    summary.cursor = cursor;
+    Ok(summary)
+}
+
+/// Parses a single line of the input, returning `None` for a blank line
+fn parse_record(line: &str, options: ImportOptions) -> Result<Option<(AkdLabel, AkdValue)>> {
+    let line = line.trim_end_matches('\r');
+    if line.trim().is_empty() {
+        return Ok(None);
+    }
+    let (label, value) = match options.format {
+        ImportFormat::Csv => {
+            let (label, value) = line
+                .split_once(',')
+                .ok_or_else(|| anyhow!("expected a label and a value separated by a comma"))?;
+            if value.contains(',') {
+                bail!("expected exactly two comma-separated fields");
+            }
+            (label.to_string(), value.to_string())
+        }
+        ImportFormat::Ndjson => {
+            let record: NdjsonRecord = serde_json::from_str(line)?;
+            (record.label, record.value)
+        }
+    };
+
+    let label = AkdLabel(decode(&label, options.encoding).map_err(|err| anyhow!("label {err}"))?);
+    let value = AkdValue(decode(&value, options.encoding).map_err(|err| anyhow!("value {err}"))?);
+    if label.is_empty() {
+        bail!("the label is empty");
+    }
+    if value.starts_with(LINK_VALUE_PREFIX) {
+        bail!("the value is a link record, which cannot be imported");
+    }
+    Ok(Some((label, value)))
+}
+
+fn decode(field: &str, encoding: ValueEncoding) -> Result<Vec<u8>> {
+    match encoding {
+        ValueEncoding::Utf8 => Ok(field.as_bytes().to_vec()),
+        ValueEncoding::Hex => hex::decode(field).map_err(|err| anyhow!("is not valid hex: {err}")),
+    }
+}
+
+fn read_cursor(path: &Path) -> Result<ImportCursor> {
+    serde_json::from_str(&std::fs::read_to_string(path)?)
+        .map_err(|err| anyhow!("Invalid cursor file {}: {}", path.display(), err))
+}
+
+/// Writes the cursor to a temporary file which is then renamed over the cursor file,
+/// so that the cursor file is never left partially written
+fn write_cursor(path: &Path, cursor: &ImportCursor) -> Result<()> {
+    let temp = path.with_extension("tmp");
+    std::fs::write(&temp, serde_json::to_string(cursor)?)?;
+    std::fs::rename(&temp, path)?;
+    Ok(())
+}
@@ -0,0 +1,177 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Tests for the bulk importer
+
+use super::{import, ImportCursor, ImportFormat, ImportOptions, ValueEncoding};
+use crate::test_config;
+use akd::ecvrf::HardCodedAkdVRF;
+use akd::storage::memory::AsyncInMemoryDatabase;
+use akd::storage::StorageManager;
+use akd::{AkdLabel, AkdValue, Configuration, Directory};
+
+const OPTIONS: ImportOptions = ImportOptions {
+    format: ImportFormat::Csv,
+    encoding: ValueEncoding::Utf8,
+    header: true,
+    chunk_size: 2,
+    validate_only: false,
+};
+
+async fn new_directory<TC: Configuration>() -> Directory<TC, AsyncInMemoryDatabase, HardCodedAkdVRF>
+{
+    let storage_manager = StorageManager::new_no_cache(AsyncInMemoryDatabase::new());
+    Directory::<TC, _, _>::new(storage_manager, HardCodedAkdVRF {})
+        .await
+        .unwrap()
+}
+
+test_config!(test_import_csv_in_chunks);
+async fn test_import_csv_in_chunks<TC: Configuration>() {
+    let directory = new_directory::<TC>().await;
+    let input = "label,value\nalice,a1\nbob,b1\n\ncarol,c1\n";
+
+    let mut published_cursors = vec![];
+    let summary = import(
+        &directory,
+        input.as_bytes(),
+        OPTIONS,
+        None,
+        |cursor, published| {
+            if published {
+                published_cursors.push(*cursor);
+            }
+            Ok(())
+        },
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(3, summary.records);
+    assert_eq!(3, summary.published);
+    assert_eq!(2, summary.epochs);
+    assert_eq!(
+        vec![
+            ImportCursor { line: 3, epoch: 1 },
+            ImportCursor { line: 5, epoch: 2 }
+        ],
+        published_cursors
+    );
+    let (proof, _) = directory.lookup(AkdLabel::from("carol")).await.unwrap();
+    assert_eq!(AkdValue::from("c1"), proof.value);
+}
+
+test_config!(test_import_resumes_from_cursor);
+async fn test_import_resumes_from_cursor<TC: Configuration>() {
+    let directory = new_directory::<TC>().await;
+    let input = "label,value\nalice,a1\nbob,b1\ncarol,c1\n";
+
+    // An import which is interrupted after its first chunk
+    let result = import(
+        &directory,
+        input.as_bytes(),
+        OPTIONS,
+        None,
+        |_, published| {
+            if published {
+                anyhow::bail!("interrupted");
+            }
+            Ok(())
+        },
+    )
+    .await;
+    assert!(result.is_err());
+
+    let cursor = ImportCursor { line: 3, epoch: 1 };
+    let summary = import(
+        &directory,
+        input.as_bytes(),
+        OPTIONS,
+        Some(cursor),
+        |_, _| Ok(()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(1, summary.published);
+    assert_eq!(ImportCursor { line: 4, epoch: 2 }, summary.cursor);
+
+    // Alice was published only once, by the first import
+    let history = directory
+        .key_history(&AkdLabel::from("alice"), akd::HistoryParams::default())
+        .await
+        .unwrap()
+        .0;
+    assert_eq!(1, history.update_proofs.len());
+
+    // The cursor no longer matches the directory
+    assert!(import(
+        &directory,
+        input.as_bytes(),
+        OPTIONS,
+        Some(cursor),
+        |_, _| Ok(())
+    )
+    .await
+    .is_err());
+}
+
+test_config!(test_import_validation);
+async fn test_import_validation<TC: Configuration>() {
+    let directory = new_directory::<TC>().await;
+    let validate_only = ImportOptions {
+        validate_only: true,
+        ..OPTIONS
+    };
+
+    let invalid_inputs = [
+        ("label,value\nalice,a1\nbob,b1\nalice,a2\n", OPTIONS),
+        ("label,value\nalice\n", OPTIONS),
+        ("label,value\n,a1\n", OPTIONS),
+        (
+            "616c696365,zz\n",
+            ImportOptions {
+                encoding: ValueEncoding::Hex,
+                header: false,
+                ..OPTIONS
+            },
+        ),
+        (
+            "{\"label\": \"alice\"}\n",
+            ImportOptions {
+                format: ImportFormat::Ndjson,
+                ..OPTIONS
+            },
+        ),
+    ];
+    for (input, options) in invalid_inputs {
+        let options = ImportOptions {
+            validate_only: true,
+            ..options
+        };
+        let result = import(&directory, input.as_bytes(), options, None, |_, _| Ok(())).await;
+        assert!(result.is_err(), "{input} should be rejected");
+    }
+
+    // Validation does not publish anything
+    let input = "{\"label\": \"616c696365\", \"value\": \"6131\"}\n";
+    let summary = import(
+        &directory,
+        input.as_bytes(),
+        ImportOptions {
+            format: ImportFormat::Ndjson,
+            encoding: ValueEncoding::Hex,
+            ..validate_only
+        },
+        None,
+        |_, _| Ok(()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(1, summary.records);
+    assert_eq!(0, summary.epochs);
+    assert_eq!(0, directory.get_epoch_hash().await.unwrap().epoch());
+}
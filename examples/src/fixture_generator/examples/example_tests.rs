@@ -12,7 +12,9 @@ use std::fs::File;
 use akd::{
     directory::Directory,
     ecvrf::HardCodedAkdVRF,
-    storage::{memory::AsyncInMemoryDatabase, Database, StorageManager, StorageUtil},
+    storage::{
+        memory::AsyncInMemoryDatabase, types::DbRecord, Database, StorageManager, StorageUtil,
+    },
     NamedConfiguration,
 };
 
@@ -49,11 +51,12 @@ async fn test_use_fixture<TC: NamedConfiguration>() {
 
     // assert final directory state
     let final_state = reader.read_state(epochs[1]).unwrap();
-    let records = storage_manager
+    let mut records = storage_manager
         .get_db()
         .batch_get_all_direct()
         .await
         .unwrap();
+    records.retain(|record| !matches!(record, DbRecord::OperationRecord(_)));
     assert_eq!(final_state.records.len(), records.len());
     assert!(records.iter().all(|r| final_state.records.contains(r)));
 }
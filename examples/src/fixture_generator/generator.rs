@@ -77,11 +77,11 @@ pub(crate) async fn generate<TC: NamedConfiguration, L: DomainLabel>(args: &Args
     assert!(args
         .capture_states
         .as_ref()
-        .map_or(true, |states| states.iter().max().unwrap() <= &args.epochs));
+        .is_none_or(|states| states.iter().max().unwrap() <= &args.epochs));
     assert!(args
         .capture_deltas
         .as_ref()
-        .map_or(true, |deltas| deltas.iter().max().unwrap() <= &args.epochs));
+        .is_none_or(|deltas| deltas.iter().max().unwrap() <= &args.epochs));
 
     // process users
     let mut user_map = HashMap::new();
@@ -175,12 +175,14 @@ pub(crate) async fn generate<TC: NamedConfiguration, L: DomainLabel>(args: &Args
             if states.contains(&epoch) {
                 let comment = format!("{STATE_COMMENT} {epoch}");
 
-                // Sort the records by label to make the output deterministic.
+                // Sort the records by label to make the output deterministic. The
+                // operations log is timestamped, and so is left out of the state.
                 let mut records = storage_manager
                     .get_db()
                     .batch_get_all_direct()
                     .await
                     .unwrap();
+                records.retain(|record| !matches!(record, DbRecord::OperationRecord(_)));
                 records.sort();
 
                 let state = State { epoch, records };
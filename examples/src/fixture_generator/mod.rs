@@ -20,6 +20,7 @@
 mod examples;
 mod generator;
 mod parser;
+#[allow(dead_code)]
 mod reader;
 mod writer;
 
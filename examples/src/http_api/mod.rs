@@ -0,0 +1,357 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! The models of an HTTP API which serves the lookup, history and audit proofs of a
+//! directory, as specified by the OpenAPI document `openapi.yaml` in this directory.
+//! Gateway teams can generate their clients from the document, and a server can
+//! deserialize its requests into the models below and serialize the responses of the
+//! handlers with `serde_json`.
+//!
+//! The handlers are independent of any web framework: each one takes the directory and a
+//! request model and returns either a response model or an [ApiError], which carries the
+//! HTTP status code to respond with. Binary values are lowercase hex, and proofs are
+//! encoded in the protobuf format of `akd_core::proto`, so that clients which are not
+//! written in Rust can decode them with code generated from the same specification.
+//!
+//! Note that this module is intended for demonstration purposes only and not meant to be
+//! executable as-is.
+
+#[cfg(test)]
+mod tests;
+
+use akd::ecvrf::VRFKeyStorage;
+use akd::errors::{AkdError, DirectoryError, StorageError};
+use akd::storage::Database;
+use akd::{
+    AkdLabel, Configuration, Digest, Directory, HistoryParams, ProofVersion, VerifyResult,
+    VersionedHistoryProof, VersionedLookupProof,
+};
+use akd_core::proto::specs::types::AppendOnlyProof;
+use anyhow::anyhow;
+use protobuf::Message;
+use serde::{Deserialize, Serialize};
+
+/// The OpenAPI specification of the HTTP API
+#[allow(unused)]
+pub(crate) const OPENAPI_SPEC: &str = include_str!("openapi.yaml");
+
+/// The response of `GET /v1/epoch`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct EpochResponse {
+    /// The latest published epoch
+    pub(crate) epoch: u64,
+    /// The root hash of the latest epoch, in hex
+    pub(crate) root_hash: String,
+}
+
+/// The response of `GET /v1/public-key`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct PublicKeyResponse {
+    /// The public key of the directory's VRF, in hex
+    pub(crate) vrf_public_key: String,
+}
+
+/// The request of `POST /v1/lookup`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct LookupRequest {
+    /// The label to look up, in hex
+    pub(crate) label: String,
+}
+
+/// The response of `POST /v1/lookup`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct LookupResponse {
+    /// The epoch of the proof
+    pub(crate) epoch: u64,
+    /// The root hash of the epoch, in hex
+    pub(crate) root_hash: String,
+    /// An encoded [VersionedLookupProof], in hex
+    pub(crate) proof: String,
+}
+
+/// The request of `POST /v1/history`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct HistoryRequest {
+    /// The label whose history is requested, in hex
+    pub(crate) label: String,
+}
+
+/// The response of `POST /v1/history`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct HistoryResponse {
+    /// The epoch of the proof
+    pub(crate) epoch: u64,
+    /// The root hash of the epoch, in hex
+    pub(crate) root_hash: String,
+    /// An encoded [VersionedHistoryProof], in hex
+    pub(crate) proof: String,
+}
+
+/// The query parameters of `GET /v1/audit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct AuditRequest {
+    /// The epoch which the audit starts from
+    pub(crate) start_epoch: u64,
+    /// The epoch which the audit ends at
+    pub(crate) end_epoch: u64,
+}
+
+/// The response of `GET /v1/audit`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct AuditResponse {
+    /// The epoch which the audit starts from
+    pub(crate) start_epoch: u64,
+    /// The epoch which the audit ends at
+    pub(crate) end_epoch: u64,
+    /// An encoded [AppendOnlyProof], in hex
+    pub(crate) proof: String,
+}
+
+/// The kind of a failed request, which determines its HTTP status code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ErrorCode {
+    /// The request is malformed, or asks for epochs which do not exist (400)
+    InvalidRequest,
+    /// The requested label has never been published (404)
+    NotFound,
+    /// The directory failed to serve the request (500)
+    Internal,
+}
+
+/// The body of every error response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ErrorResponse {
+    /// The kind of the failure
+    pub(crate) code: ErrorCode,
+    /// A description of the failure, for humans
+    pub(crate) message: String,
+}
+
+/// A failed request, with the HTTP status code and body to respond with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ApiError {
+    /// The HTTP status code of the response
+    pub(crate) status: u16,
+    /// The body of the response
+    pub(crate) body: ErrorResponse,
+}
+
+impl ApiError {
+    fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        let status = match code {
+            ErrorCode::InvalidRequest => 400,
+            ErrorCode::NotFound => 404,
+            ErrorCode::Internal => 500,
+        };
+        Self {
+            status,
+            body: ErrorResponse {
+                code,
+                message: message.into(),
+            },
+        }
+    }
+}
+
+impl From<AkdError> for ApiError {
+    fn from(err: AkdError) -> Self {
+        let code = match &err {
+            AkdError::Storage(StorageError::NotFound(_)) => ErrorCode::NotFound,
+            AkdError::Directory(DirectoryError::InvalidEpoch(_)) => ErrorCode::InvalidRequest,
+            _ => ErrorCode::Internal,
+        };
+        Self::new(code, err.to_string())
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HTTP {}: {}", self.status, self.body.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+fn decode_label(label: &str) -> Result<AkdLabel, ApiError> {
+    hex::decode(label).map(AkdLabel).map_err(|err| {
+        ApiError::new(
+            ErrorCode::InvalidRequest,
+            format!("The label is not valid hex: {err}"),
+        )
+    })
+}
+
+fn internal_error(err: impl std::fmt::Display) -> ApiError {
+    ApiError::new(ErrorCode::Internal, err.to_string())
+}
+
+/// Serves `GET /v1/epoch`
+#[allow(unused)]
+pub(crate) async fn get_epoch<TC, S, V>(
+    directory: &Directory<TC, S, V>,
+) -> Result<EpochResponse, ApiError>
+where
+    TC: Configuration,
+    S: Database + 'static,
+    V: VRFKeyStorage,
+{
+    let epoch_hash = directory.get_epoch_hash().await?;
+    Ok(EpochResponse {
+        epoch: epoch_hash.epoch(),
+        root_hash: hex::encode(epoch_hash.hash()),
+    })
+}
+
+/// Serves `GET /v1/public-key`
+#[allow(unused)]
+pub(crate) async fn get_public_key<TC, S, V>(
+    directory: &Directory<TC, S, V>,
+) -> Result<PublicKeyResponse, ApiError>
+where
+    TC: Configuration,
+    S: Database + 'static,
+    V: VRFKeyStorage,
+{
+    let vrf_public_key = directory.get_public_key().await?;
+    Ok(PublicKeyResponse {
+        vrf_public_key: hex::encode(vrf_public_key.as_bytes()),
+    })
+}
+
+/// Serves `POST /v1/lookup`
+#[allow(unused)]
+pub(crate) async fn lookup<TC, S, V>(
+    directory: &Directory<TC, S, V>,
+    request: LookupRequest,
+) -> Result<LookupResponse, ApiError>
+where
+    TC: Configuration,
+    S: Database + 'static,
+    V: VRFKeyStorage,
+{
+    let label = decode_label(&request.label)?;
+    let (proof, epoch_hash) = directory.lookup(label).await?;
+    let proof = VersionedLookupProof::V1(proof)
+        .encode()
+        .map_err(internal_error)?;
+    Ok(LookupResponse {
+        epoch: epoch_hash.epoch(),
+        root_hash: hex::encode(epoch_hash.hash()),
+        proof: hex::encode(proof),
+    })
+}
+
+/// Serves `POST /v1/history`, with a proof of the complete history of the label in the
+/// latest proof format
+#[allow(unused)]
+pub(crate) async fn key_history<TC, S, V>(
+    directory: &Directory<TC, S, V>,
+    request: HistoryRequest,
+) -> Result<HistoryResponse, ApiError>
+where
+    TC: Configuration,
+    S: Database + 'static,
+    V: VRFKeyStorage,
+{
+    let label = decode_label(&request.label)?;
+    let (proof, epoch_hash) = directory
+        .key_history(&label, HistoryParams::Complete)
+        .await?;
+    let proof = VersionedHistoryProof::new(proof, ProofVersion::LATEST)
+        .encode()
+        .map_err(internal_error)?;
+    Ok(HistoryResponse {
+        epoch: epoch_hash.epoch(),
+        root_hash: hex::encode(epoch_hash.hash()),
+        proof: hex::encode(proof),
+    })
+}
+
+/// Serves `GET /v1/audit`
+#[allow(unused)]
+pub(crate) async fn audit<TC, S, V>(
+    directory: &Directory<TC, S, V>,
+    request: AuditRequest,
+) -> Result<AuditResponse, ApiError>
+where
+    TC: Configuration,
+    S: Database + 'static,
+    V: VRFKeyStorage,
+{
+    let proof = directory
+        .audit(request.start_epoch, request.end_epoch)
+        .await?;
+    let proof = AppendOnlyProof::from(&proof)
+        .write_to_bytes()
+        .map_err(internal_error)?;
+    Ok(AuditResponse {
+        start_epoch: request.start_epoch,
+        end_epoch: request.end_epoch,
+        proof: hex::encode(proof),
+    })
+}
+
+/// Verifies a [LookupResponse] on the client, returning the value of `label`
+#[allow(unused)]
+pub(crate) fn verify_lookup<TC: Configuration>(
+    vrf_public_key: &[u8],
+    label: AkdLabel,
+    response: &LookupResponse,
+) -> anyhow::Result<VerifyResult> {
+    let root_hash = decode_digest(&response.root_hash)?;
+    let proof = VersionedLookupProof::decode(&hex::decode(&response.proof)?)
+        .map_err(|err| anyhow!("{err}"))?;
+    akd::client::lookup_verify::<TC>(
+        vrf_public_key,
+        root_hash,
+        response.epoch,
+        label,
+        proof.into_lookup_proof(),
+    )
+    .map_err(|err| anyhow!("{err}"))
+}
+
+/// Verifies a [HistoryResponse] on the client, returning the values of `label`, most
+/// recent first
+#[allow(unused)]
+pub(crate) fn verify_history<TC: Configuration>(
+    vrf_public_key: &[u8],
+    label: AkdLabel,
+    response: &HistoryResponse,
+) -> anyhow::Result<Vec<VerifyResult>> {
+    let root_hash = decode_digest(&response.root_hash)?;
+    let proof = VersionedHistoryProof::decode(&hex::decode(&response.proof)?)
+        .map_err(|err| anyhow!("{err}"))?
+        .into_history_proof()
+        .map_err(|err| anyhow!("{err}"))?;
+    akd::client::key_history_verify::<TC>(
+        vrf_public_key,
+        root_hash,
+        response.epoch,
+        label,
+        proof,
+        akd::HistoryVerificationParams::default(),
+    )
+    .map_err(|err| anyhow!("{err}"))
+}
+
+/// Verifies an [AuditResponse] against the root hashes of its epochs, which the auditor
+/// has obtained independently of the response (from the start epoch to the end epoch)
+#[allow(unused)]
+pub(crate) async fn verify_audit<TC: Configuration>(
+    root_hashes: Vec<Digest>,
+    response: &AuditResponse,
+) -> anyhow::Result<()> {
+    let proof = AppendOnlyProof::parse_from_bytes(&hex::decode(&response.proof)?)?;
+    let proof = akd::AppendOnlyProof::try_from(&proof).map_err(|err| anyhow!("{err}"))?;
+    Ok(akd::auditor::audit_verify::<TC>(root_hashes, proof).await?)
+}
+
+fn decode_digest(digest: &str) -> anyhow::Result<Digest> {
+    akd::hash::try_parse_digest(&hex::decode(digest)?).map_err(|err| anyhow!(err))
+}
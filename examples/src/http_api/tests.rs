@@ -0,0 +1,324 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Tests for the HTTP API models and handlers
+
+use super::*;
+use crate::test_config;
+use akd::ecvrf::HardCodedAkdVRF;
+use akd::storage::memory::AsyncInMemoryDatabase;
+use akd::storage::StorageManager;
+use akd::{AkdValue, EpochHash};
+use serde::de::DeserializeOwned;
+use std::collections::BTreeSet;
+
+type TestDirectory<TC> = Directory<TC, AsyncInMemoryDatabase, HardCodedAkdVRF>;
+
+async fn new_directory<TC: Configuration>() -> TestDirectory<TC> {
+    let storage_manager = StorageManager::new_no_cache(AsyncInMemoryDatabase::new());
+    Directory::<TC, _, _>::new(storage_manager, HardCodedAkdVRF {})
+        .await
+        .unwrap()
+}
+
+/// Sends a model through JSON, as it would be sent over the wire
+fn over_the_wire<T: Serialize + DeserializeOwned>(model: &T) -> T {
+    serde_json::from_str(&serde_json::to_string(model).unwrap()).unwrap()
+}
+
+fn spec() -> serde_yaml::Value {
+    serde_yaml::from_str(OPENAPI_SPEC).unwrap()
+}
+
+fn keys(value: &serde_yaml::Value) -> BTreeSet<String> {
+    value
+        .as_mapping()
+        .unwrap()
+        .keys()
+        .map(|key| key.as_str().unwrap().to_string())
+        .collect()
+}
+
+/// The properties of an object schema of the spec, and whether they are all required
+fn schema_properties(spec: &serde_yaml::Value, schema: &str) -> BTreeSet<String> {
+    let schema = &spec["components"]["schemas"][schema];
+    assert_eq!("object", schema["type"].as_str().unwrap());
+    let properties = keys(&schema["properties"]);
+    let required = schema["required"]
+        .as_sequence()
+        .unwrap()
+        .iter()
+        .map(|key| key.as_str().unwrap().to_string())
+        .collect::<BTreeSet<_>>();
+    assert_eq!(
+        properties, required,
+        "Every property of {schema:?} is required"
+    );
+    properties
+}
+
+fn json_keys<T: Serialize>(model: &T) -> BTreeSet<String> {
+    serde_json::to_value(model)
+        .unwrap()
+        .as_object()
+        .unwrap()
+        .keys()
+        .cloned()
+        .collect()
+}
+
+#[test]
+fn test_spec_matches_models() {
+    let spec = spec();
+    assert_eq!(
+        [
+            "/v1/audit",
+            "/v1/epoch",
+            "/v1/history",
+            "/v1/lookup",
+            "/v1/public-key"
+        ]
+        .map(String::from)
+        .into_iter()
+        .collect::<BTreeSet<_>>(),
+        keys(&spec["paths"])
+    );
+
+    let label = String::new();
+    let proof = String::new();
+    let models = [
+        (
+            "EpochResponse",
+            json_keys(&EpochResponse {
+                epoch: 0,
+                root_hash: proof.clone(),
+            }),
+        ),
+        (
+            "PublicKeyResponse",
+            json_keys(&PublicKeyResponse {
+                vrf_public_key: proof.clone(),
+            }),
+        ),
+        (
+            "LookupRequest",
+            json_keys(&LookupRequest {
+                label: label.clone(),
+            }),
+        ),
+        (
+            "LookupResponse",
+            json_keys(&LookupResponse {
+                epoch: 0,
+                root_hash: proof.clone(),
+                proof: proof.clone(),
+            }),
+        ),
+        ("HistoryRequest", json_keys(&HistoryRequest { label })),
+        (
+            "HistoryResponse",
+            json_keys(&HistoryResponse {
+                epoch: 0,
+                root_hash: proof.clone(),
+                proof: proof.clone(),
+            }),
+        ),
+        (
+            "AuditResponse",
+            json_keys(&AuditResponse {
+                start_epoch: 0,
+                end_epoch: 0,
+                proof: proof.clone(),
+            }),
+        ),
+        (
+            "ErrorResponse",
+            json_keys(&ErrorResponse {
+                code: ErrorCode::Internal,
+                message: proof,
+            }),
+        ),
+    ];
+    for (schema, model_keys) in models {
+        assert_eq!(
+            schema_properties(&spec, schema),
+            model_keys,
+            "The properties of {schema}"
+        );
+    }
+
+    // the query parameters of the audit
+    let parameters = spec["paths"]["/v1/audit"]["get"]["parameters"]
+        .as_sequence()
+        .unwrap()
+        .iter()
+        .map(|parameter| {
+            assert_eq!("query", parameter["in"].as_str().unwrap());
+            parameter["name"].as_str().unwrap().to_string()
+        })
+        .collect::<BTreeSet<_>>();
+    assert_eq!(
+        json_keys(&AuditRequest {
+            start_epoch: 0,
+            end_epoch: 0
+        }),
+        parameters
+    );
+
+    // the error codes, and the status codes which the handlers respond with
+    let codes = spec["components"]["schemas"]["ErrorResponse"]["properties"]["code"]["enum"]
+        .as_sequence()
+        .unwrap()
+        .iter()
+        .map(|code| code.as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    let expected = [
+        (ErrorCode::InvalidRequest, 400),
+        (ErrorCode::NotFound, 404),
+        (ErrorCode::Internal, 500),
+    ];
+    for (code, (error_code, status)) in codes.iter().zip(expected) {
+        assert_eq!(&serde_json::to_value(error_code).unwrap(), code);
+        assert_eq!(status, ApiError::new(error_code, "").status);
+    }
+    assert_eq!(expected.len(), codes.len());
+}
+
+test_config!(test_serve_and_verify);
+async fn test_serve_and_verify<TC: Configuration>() {
+    let directory = new_directory::<TC>().await;
+    let label = AkdLabel::from("alice");
+    let mut root_hashes = vec![get_epoch(&directory).await.unwrap().root_hash];
+    for epoch in 1..=3 {
+        let EpochHash(_, root_hash) = directory
+            .publish(vec![
+                (
+                    label.clone(),
+                    AkdValue(format!("alice {epoch}").into_bytes()),
+                ),
+                (
+                    AkdLabel(format!("user {epoch}").into_bytes()),
+                    AkdValue::from("value"),
+                ),
+            ])
+            .await
+            .unwrap();
+        root_hashes.push(hex::encode(root_hash));
+    }
+
+    let epoch = over_the_wire(&get_epoch(&directory).await.unwrap());
+    assert_eq!(3, epoch.epoch);
+    assert_eq!(root_hashes[3], epoch.root_hash);
+    let vrf_public_key = over_the_wire(&get_public_key(&directory).await.unwrap());
+    let vrf_public_key = hex::decode(vrf_public_key.vrf_public_key).unwrap();
+
+    let request = over_the_wire(&LookupRequest {
+        label: hex::encode(&label.0),
+    });
+    let response = over_the_wire(&lookup(&directory, request).await.unwrap());
+    assert_eq!(epoch.root_hash, response.root_hash);
+    let result = verify_lookup::<TC>(&vrf_public_key, label.clone(), &response).unwrap();
+    assert_eq!((3, 3), (result.epoch, result.version));
+    assert_eq!(AkdValue::from("alice 3"), result.value);
+
+    let request = over_the_wire(&HistoryRequest {
+        label: hex::encode(&label.0),
+    });
+    let response = over_the_wire(&key_history(&directory, request).await.unwrap());
+    let results = verify_history::<TC>(&vrf_public_key, label.clone(), &response).unwrap();
+    assert_eq!(
+        vec![3, 2, 1],
+        results
+            .iter()
+            .map(|result| result.epoch)
+            .collect::<Vec<_>>()
+    );
+    // a lookup proof does not verify as the proof of a different label
+    let response = over_the_wire(
+        &lookup(
+            &directory,
+            LookupRequest {
+                label: hex::encode("user 1"),
+            },
+        )
+        .await
+        .unwrap(),
+    );
+    assert!(verify_lookup::<TC>(&vrf_public_key, label, &response).is_err());
+
+    let request = AuditRequest {
+        start_epoch: 1,
+        end_epoch: 3,
+    };
+    let response = over_the_wire(&audit(&directory, request).await.unwrap());
+    let hashes = root_hashes[1..=3]
+        .iter()
+        .map(|hash| hex::decode(hash).unwrap().try_into().unwrap())
+        .collect::<Vec<Digest>>();
+    verify_audit::<TC>(hashes.clone(), &response).await.unwrap();
+    // the audit fails against a different history of root hashes
+    let mut forged = hashes;
+    forged.swap(1, 2);
+    assert!(verify_audit::<TC>(forged, &response).await.is_err());
+}
+
+test_config!(test_error_responses);
+async fn test_error_responses<TC: Configuration>() {
+    let directory = new_directory::<TC>().await;
+    directory
+        .publish(vec![(AkdLabel::from("alice"), AkdValue::from("a1"))])
+        .await
+        .unwrap();
+
+    let unknown = hex::encode("bob");
+    let err = lookup(
+        &directory,
+        LookupRequest {
+            label: unknown.clone(),
+        },
+    )
+    .await
+    .unwrap_err();
+    assert_eq!((404, ErrorCode::NotFound), (err.status, err.body.code));
+    let err = key_history(&directory, HistoryRequest { label: unknown })
+        .await
+        .unwrap_err();
+    assert_eq!((404, ErrorCode::NotFound), (err.status, err.body.code));
+
+    let err = lookup(
+        &directory,
+        LookupRequest {
+            label: "not hex".to_string(),
+        },
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        (400, ErrorCode::InvalidRequest),
+        (err.status, err.body.code)
+    );
+
+    for (start_epoch, end_epoch) in [(1, 1), (0, 2)] {
+        let err = audit(
+            &directory,
+            AuditRequest {
+                start_epoch,
+                end_epoch,
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            (400, ErrorCode::InvalidRequest),
+            (err.status, err.body.code)
+        );
+    }
+
+    // error bodies are sent as JSON
+    let body = serde_json::to_value(over_the_wire(&err.body)).unwrap();
+    assert_eq!("invalid_request", body["code"]);
+}
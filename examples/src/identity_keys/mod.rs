@@ -0,0 +1,588 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! An identity key directory for a messaging service in the style of Signal or Matrix,
+//! in which each account has a long-lived identity key and each of its devices a key of
+//! its own, signed by the identity key. Example command:
+//!
+//!   cargo run -p examples -- identity-keys
+//!
+//! The label of an account is its name, and its value is the encoding of [AccountKeys]:
+//! the identity key along with the signed keys of the account's devices. A new version is
+//! published whenever a device is added or removed, or the identity key is replaced (for
+//! instance, after a reinstall).
+//!
+//! Users compare a safety number, which is derived from the identity keys of both
+//! parties, to authenticate a conversation, and are warned when it changes. The key
+//! directory lets a [Client] do better than trust-on-first-use: when the version of a
+//! contact has advanced since it was last seen, the client verifies the contact's key
+//! history, which shows every identity key the contact has had since then, and the
+//! epochs in which they were published. A safety number change which the client did not
+//! observe directly (such as an identity key which was published and then replaced by
+//! the original one) is then still detected, and cannot be hidden by the server.
+//!
+//! The identity and device keys are Ed25519 keys; Signal uses XEdDSA signatures with
+//! Curve25519 identity keys, which does not change the structure of the directory.
+
+#[cfg(test)]
+mod tests;
+
+use akd::ecvrf::{HardCodedAkdVRF, VRFKeyStorage};
+use akd::storage::memory::AsyncInMemoryDatabase;
+use akd::storage::{Database, StorageManager};
+use akd::{
+    AkdLabel, AkdValue, Configuration, Directory, EpochHash, HistoryParams,
+    HistoryVerificationParams, VerifyResult,
+};
+use anyhow::{anyhow, bail, Result};
+use clap::Parser;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use sha2::{Digest, Sha512};
+use std::collections::HashMap;
+
+type TC = akd::ExperimentalConfiguration<akd::ExampleLabel>;
+
+/// The version of the encoding of [AccountKeys]
+const ACCOUNT_KEYS_VERSION: u8 = 1;
+/// The domain separator of the signatures of device keys
+const DEVICE_KEY_DOMAIN: &[u8] = b"AKD identity key directory device key";
+/// The number of iterations of the hash of a safety number
+const SAFETY_NUMBER_ITERATIONS: usize = 5200;
+
+#[derive(Parser, Debug, Clone)]
+pub(crate) struct CliArgs {
+    /// The number of devices of each account
+    #[clap(long = "num_devices", default_value = "2")]
+    num_devices: u32,
+}
+
+/// The key of one of the devices of an account
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DeviceKey {
+    /// The identifier of the device within the account
+    pub(crate) device_id: u32,
+    /// The public key of the device
+    pub(crate) public_key: [u8; 32],
+    /// The signature of the device key with the identity key of the account
+    pub(crate) signature: [u8; 64],
+}
+
+impl DeviceKey {
+    /// Creates the key of a device, signed with the identity key of the account
+    pub(crate) fn new(
+        account: &str,
+        identity_key: &SigningKey,
+        device_id: u32,
+        public_key: [u8; 32],
+    ) -> Self {
+        let message = Self::message(account, device_id, &public_key);
+        Self {
+            device_id,
+            public_key,
+            signature: identity_key.sign(&message).to_bytes(),
+        }
+    }
+
+    /// The message which the identity key signs, which binds the device key to the
+    /// account, so that it cannot be replayed as a device of another account with the
+    /// same identity key
+    fn message(account: &str, device_id: u32, public_key: &[u8; 32]) -> Vec<u8> {
+        [
+            DEVICE_KEY_DOMAIN,
+            &(account.len() as u32).to_be_bytes(),
+            account.as_bytes(),
+            &device_id.to_be_bytes(),
+            public_key,
+        ]
+        .concat()
+    }
+}
+
+/// The keys of an account, which are the value of the account's label in the directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AccountKeys {
+    /// The public identity key of the account
+    pub(crate) identity_key: [u8; 32],
+    /// The keys of the account's devices, in order of their identifiers
+    pub(crate) devices: Vec<DeviceKey>,
+}
+
+impl AccountKeys {
+    /// Encodes the keys into the value which is published for the account:
+    ///
+    /// ```text
+    /// version (1 byte) || identity key (32 bytes) || number of devices (u32)
+    ///     || for each device: device id (u32) || public key (32 bytes) || signature (64 bytes)
+    /// ```
+    ///
+    /// with integers in big-endian order and the devices in order of their identifiers,
+    /// so that equal keys have equal encodings.
+    pub(crate) fn to_value(&self) -> AkdValue {
+        let mut devices = self.devices.iter().collect::<Vec<_>>();
+        devices.sort_by_key(|device| device.device_id);
+
+        let mut out = vec![ACCOUNT_KEYS_VERSION];
+        out.extend_from_slice(&self.identity_key);
+        out.extend_from_slice(&(devices.len() as u32).to_be_bytes());
+        for device in devices {
+            out.extend_from_slice(&device.device_id.to_be_bytes());
+            out.extend_from_slice(&device.public_key);
+            out.extend_from_slice(&device.signature);
+        }
+        AkdValue(out)
+    }
+
+    /// Decodes the keys from the value which is published for an account
+    pub(crate) fn from_value(value: &AkdValue) -> Result<Self> {
+        let mut reader = Reader(&value.0);
+        let version = reader.take::<1>()?[0];
+        if version != ACCOUNT_KEYS_VERSION {
+            bail!("Unsupported version {version} of the account keys");
+        }
+        let identity_key = reader.take::<32>()?;
+        let num_devices = u32::from_be_bytes(reader.take::<4>()?);
+        let mut devices = Vec::new();
+        for _ in 0..num_devices {
+            let device_id = u32::from_be_bytes(reader.take::<4>()?);
+            if devices
+                .last()
+                .is_some_and(|previous: &DeviceKey| previous.device_id >= device_id)
+            {
+                bail!("The devices of the account keys are not in order");
+            }
+            devices.push(DeviceKey {
+                device_id,
+                public_key: reader.take::<32>()?,
+                signature: reader.take::<64>()?,
+            });
+        }
+        if !reader.0.is_empty() {
+            bail!("Trailing bytes after the account keys");
+        }
+        Ok(Self {
+            identity_key,
+            devices,
+        })
+    }
+
+    /// Verifies that the key of each device is signed by the identity key of the account
+    pub(crate) fn verify(&self, account: &str) -> Result<()> {
+        let identity_key = VerifyingKey::from_bytes(&self.identity_key)
+            .map_err(|err| anyhow!("Invalid identity key of {account}: {err}"))?;
+        for device in self.devices.iter() {
+            let message = DeviceKey::message(account, device.device_id, &device.public_key);
+            identity_key
+                .verify(&message, &Signature::from_bytes(&device.signature))
+                .map_err(|_| {
+                    anyhow!(
+                        "The key of device {} of {account} is not signed by its identity key",
+                        device.device_id
+                    )
+                })?;
+        }
+        Ok(())
+    }
+
+    fn device_ids(&self) -> Vec<u32> {
+        self.devices.iter().map(|device| device.device_id).collect()
+    }
+}
+
+/// Reads fixed-size fields from an encoding
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        if self.0.len() < N {
+            bail!("The account keys are truncated");
+        }
+        let (field, rest) = self.0.split_at(N);
+        self.0 = rest;
+        Ok(field.try_into()?)
+    }
+}
+
+/// The 30 digit fingerprint of an account's identity key, computed as in Signal's numeric
+/// fingerprints: an iterated SHA-512 hash of the key and the account, of which the first
+/// 30 bytes are read as six 5 byte big-endian integers, each reduced modulo 100000
+fn fingerprint(account: &str, identity_key: &[u8; 32]) -> String {
+    let mut hash = Sha512::new()
+        .chain_update(0u16.to_be_bytes())
+        .chain_update(identity_key)
+        .chain_update(account.as_bytes())
+        .finalize();
+    for _ in 0..SAFETY_NUMBER_ITERATIONS {
+        hash = Sha512::new()
+            .chain_update(hash)
+            .chain_update(identity_key)
+            .finalize();
+    }
+    hash[..30]
+        .chunks(5)
+        .map(|chunk| {
+            let value = chunk
+                .iter()
+                .fold(0u64, |value, byte| (value << 8) | u64::from(*byte));
+            format!("{:05}", value % 100000)
+        })
+        .collect()
+}
+
+/// The safety number of a conversation between two accounts, which is the same for both
+/// parties: the fingerprints of their identity keys, in sorted order
+pub(crate) fn safety_number(
+    account: &str,
+    identity_key: &[u8; 32],
+    other_account: &str,
+    other_identity_key: &[u8; 32],
+) -> String {
+    let mut fingerprints = [
+        fingerprint(account, identity_key),
+        fingerprint(other_account, other_identity_key),
+    ];
+    fingerprints.sort();
+    fingerprints.concat()
+}
+
+/// The key server, which publishes the keys of accounts to the directory
+pub(crate) struct KeyServer<TC, S: Database, V> {
+    directory: Directory<TC, S, V>,
+}
+
+impl<TC, S, V> KeyServer<TC, S, V>
+where
+    TC: Configuration,
+    S: Database + 'static,
+    V: VRFKeyStorage,
+{
+    pub(crate) fn new(directory: Directory<TC, S, V>) -> Self {
+        Self { directory }
+    }
+
+    /// Publishes the keys of an account, after checking that its device keys are signed
+    /// by its identity key
+    pub(crate) async fn publish(&self, account: &str, keys: &AccountKeys) -> Result<EpochHash> {
+        keys.verify(account)?;
+        Ok(self
+            .directory
+            .publish(vec![(AkdLabel::from(account), keys.to_value())])
+            .await?)
+    }
+
+    /// The VRF public key of the directory, which clients verify proofs with
+    pub(crate) async fn vrf_public_key(&self) -> Result<Vec<u8>> {
+        Ok(self.directory.get_public_key().await?.as_bytes().to_vec())
+    }
+}
+
+/// What a [Client] learned about a contact when refreshing its keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ContactUpdate {
+    /// The contact was seen for the first time
+    New {
+        /// The safety number of the conversation with the contact
+        safety_number: String,
+    },
+    /// Nothing has changed since the contact was last seen
+    Unchanged,
+    /// The devices of the contact have changed, but its identity key has not, so the
+    /// safety number is unchanged
+    DevicesChanged {
+        /// The devices which were added
+        added: Vec<u32>,
+        /// The devices which were removed
+        removed: Vec<u32>,
+    },
+    /// The identity key of the contact has changed since it was last seen (even if it
+    /// has since been changed back), and the user should verify the safety number again
+    SafetyNumberChanged {
+        /// The safety number which the user last saw
+        previous_safety_number: String,
+        /// The current safety number
+        safety_number: String,
+        /// The epochs in which the contact's identity key changed since it was last
+        /// seen, which includes changes which were since reverted
+        changed_in_epochs: Vec<u64>,
+    },
+}
+
+/// What a [Client] remembers about a contact
+#[derive(Debug, Clone)]
+struct Contact {
+    keys: AccountKeys,
+    version: u64,
+}
+
+/// A messaging client, which tracks the keys of its contacts
+pub(crate) struct Client {
+    account: String,
+    identity_key: SigningKey,
+    vrf_public_key: Vec<u8>,
+    contacts: HashMap<String, Contact>,
+}
+
+impl Client {
+    pub(crate) fn new(account: &str, identity_key: SigningKey, vrf_public_key: Vec<u8>) -> Self {
+        Self {
+            account: account.to_string(),
+            identity_key,
+            vrf_public_key,
+            contacts: HashMap::new(),
+        }
+    }
+
+    /// The keys of the client's account with the given devices
+    pub(crate) fn account_keys(&self, device_ids: &[u32]) -> AccountKeys {
+        let mut device_ids = device_ids.to_vec();
+        device_ids.sort();
+        device_ids.dedup();
+        AccountKeys {
+            identity_key: self.identity_key.verifying_key().to_bytes(),
+            devices: device_ids
+                .into_iter()
+                .map(|device_id| {
+                    // A placeholder for the public key of the device, which is not used
+                    let public_key = Sha512::new()
+                        .chain_update(self.identity_key.as_bytes())
+                        .chain_update(device_id.to_be_bytes())
+                        .finalize()[..32]
+                        .try_into()
+                        .expect("a 32 byte slice");
+                    DeviceKey::new(&self.account, &self.identity_key, device_id, public_key)
+                })
+                .collect(),
+        }
+    }
+
+    /// The safety number of the conversation with a contact, if it has been seen
+    pub(crate) fn safety_number(&self, contact: &str) -> Option<String> {
+        self.contacts.get(contact).map(|known| {
+            safety_number(
+                &self.account,
+                &self.identity_key.verifying_key().to_bytes(),
+                contact,
+                &known.keys.identity_key,
+            )
+        })
+    }
+
+    /// Looks up the current keys of a contact and verifies them. If the contact has been
+    /// seen before, at an older version, its key history is verified as well, to find
+    /// every identity key it has had since.
+    pub(crate) async fn refresh<TC, S, V>(
+        &mut self,
+        server: &KeyServer<TC, S, V>,
+        contact: &str,
+    ) -> Result<ContactUpdate>
+    where
+        TC: Configuration,
+        S: Database + 'static,
+        V: VRFKeyStorage,
+    {
+        let label = AkdLabel::from(contact);
+        let (proof, EpochHash(epoch, root_hash)) = server.directory.lookup(label.clone()).await?;
+        let result = akd::client::lookup_verify::<TC>(
+            &self.vrf_public_key,
+            root_hash,
+            epoch,
+            label.clone(),
+            proof,
+        )
+        .map_err(|err| anyhow!("The lookup proof of {contact} did not verify: {err}"))?;
+        let keys = AccountKeys::from_value(&result.value)?;
+        keys.verify(contact)?;
+
+        let known = match self.contacts.get(contact) {
+            None => {
+                self.contacts.insert(
+                    contact.to_string(),
+                    Contact {
+                        keys,
+                        version: result.version,
+                    },
+                );
+                return Ok(ContactUpdate::New {
+                    safety_number: self.safety_number(contact).expect("the contact was added"),
+                });
+            }
+            Some(known) if known.version == result.version => return Ok(ContactUpdate::Unchanged),
+            Some(known) => known.clone(),
+        };
+        if result.version < known.version {
+            bail!(
+                "The version of {contact} went back from {} to {}",
+                known.version,
+                result.version
+            );
+        }
+
+        let updates = self
+            .verified_history(server, contact, known.version)
+            .await?;
+        if updates.first().map(|update| update.version) != Some(result.version)
+            || updates.last().map(|update| &update.value) != Some(&known.keys.to_value())
+        {
+            bail!(
+                "The key history of {contact} does not lead from version {} to version {}",
+                known.version,
+                result.version
+            );
+        }
+
+        // Walking the history from the oldest version, find each change of identity key
+        let mut identity_key = known.keys.identity_key;
+        let mut changed_in_epochs = vec![];
+        for update in updates.iter().rev().skip(1) {
+            let keys = AccountKeys::from_value(&update.value)?;
+            if keys.identity_key != identity_key {
+                identity_key = keys.identity_key;
+                changed_in_epochs.push(update.epoch);
+            }
+        }
+
+        let previous_safety_number = self.safety_number(contact).expect("the contact is known");
+        let (previous_devices, devices) = (known.keys.device_ids(), keys.device_ids());
+        self.contacts.insert(
+            contact.to_string(),
+            Contact {
+                keys,
+                version: result.version,
+            },
+        );
+        if changed_in_epochs.is_empty() {
+            Ok(ContactUpdate::DevicesChanged {
+                added: devices
+                    .iter()
+                    .filter(|device| !previous_devices.contains(device))
+                    .copied()
+                    .collect(),
+                removed: previous_devices
+                    .iter()
+                    .filter(|device| !devices.contains(device))
+                    .copied()
+                    .collect(),
+            })
+        } else {
+            Ok(ContactUpdate::SafetyNumberChanged {
+                previous_safety_number,
+                safety_number: self.safety_number(contact).expect("the contact is known"),
+                changed_in_epochs,
+            })
+        }
+    }
+
+    /// Verifies the complete key history of a contact, and returns its updates from the
+    /// latest down to `since_version`
+    async fn verified_history<TC, S, V>(
+        &self,
+        server: &KeyServer<TC, S, V>,
+        contact: &str,
+        since_version: u64,
+    ) -> Result<Vec<VerifyResult>>
+    where
+        TC: Configuration,
+        S: Database + 'static,
+        V: VRFKeyStorage,
+    {
+        let label = AkdLabel::from(contact);
+        let (proof, EpochHash(epoch, root_hash)) = server
+            .directory
+            .key_history(&label, HistoryParams::Complete)
+            .await?;
+        let updates = akd::client::key_history_verify::<TC>(
+            &self.vrf_public_key,
+            root_hash,
+            epoch,
+            label,
+            proof,
+            HistoryVerificationParams::default(),
+        )
+        .map_err(|err| anyhow!("The key history of {contact} did not verify: {err}"))?;
+        Ok(updates
+            .into_iter()
+            .filter(|update| update.version >= since_version)
+            .collect())
+    }
+}
+
+pub(crate) async fn run(args: CliArgs) -> Result<()> {
+    let storage_manager = StorageManager::new_no_cache(AsyncInMemoryDatabase::new());
+    let directory = Directory::<TC, _, _>::new(storage_manager, HardCodedAkdVRF {}).await?;
+    let server = KeyServer::new(directory);
+    let vrf_public_key = server.vrf_public_key().await?;
+
+    let devices = (1..=args.num_devices.max(1)).collect::<Vec<_>>();
+    let mut alice = Client::new(
+        "alice",
+        SigningKey::from_bytes(&rand::random()),
+        vrf_public_key.clone(),
+    );
+    let mut bob = Client::new(
+        "bob",
+        SigningKey::from_bytes(&rand::random()),
+        vrf_public_key.clone(),
+    );
+    let EpochHash(epoch, _) = server
+        .publish("alice", &alice.account_keys(&devices))
+        .await?;
+    println!("The keys of alice were published in epoch {epoch}");
+    let EpochHash(epoch, _) = server.publish("bob", &bob.account_keys(&devices)).await?;
+    println!("The keys of bob were published in epoch {epoch}");
+
+    if let ContactUpdate::New { safety_number } = alice.refresh(&server, "bob").await? {
+        println!("alice starts a conversation with bob, with safety number {safety_number}");
+    }
+    bob.refresh(&server, "alice").await?;
+
+    // bob adds a device, which does not change the safety number
+    let mut bob_devices = devices.clone();
+    bob_devices.push(devices.len() as u32 + 1);
+    server
+        .publish("bob", &bob.account_keys(&bob_devices))
+        .await?;
+    println!(
+        "alice's view of bob: {:?}",
+        alice.refresh(&server, "bob").await?
+    );
+
+    // bob reinstalls, with a new identity key, which changes the safety number
+    bob.identity_key = SigningKey::from_bytes(&rand::random());
+    server.publish("bob", &bob.account_keys(&devices)).await?;
+    match alice.refresh(&server, "bob").await? {
+        ContactUpdate::SafetyNumberChanged {
+            previous_safety_number,
+            safety_number,
+            changed_in_epochs,
+        } => println!(
+            "bob's safety number changed from {previous_safety_number} to {safety_number} \
+             (identity key changed in epochs {changed_in_epochs:?})"
+        ),
+        update => bail!("Unexpected update of bob: {update:?}"),
+    }
+
+    // The server briefly publishes an identity key of its own for bob, and restores the
+    // real one before alice looks bob up again. The history still shows the change.
+    let mallory = Client::new(
+        "bob",
+        SigningKey::from_bytes(&rand::random()),
+        vrf_public_key,
+    );
+    server
+        .publish("bob", &mallory.account_keys(&devices))
+        .await?;
+    server.publish("bob", &bob.account_keys(&devices)).await?;
+    match alice.refresh(&server, "bob").await? {
+        ContactUpdate::SafetyNumberChanged {
+            changed_in_epochs, ..
+        } => println!(
+            "alice detected that bob's identity key was replaced in epochs {changed_in_epochs:?}, \
+             even though it is the same as before"
+        ),
+        update => bail!("A transient identity key of bob went undetected: {update:?}"),
+    }
+    Ok(())
+}
@@ -0,0 +1,213 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Tests for the identity key directory
+
+use super::*;
+use crate::test_config;
+
+type TestKeyServer<TC> = KeyServer<TC, AsyncInMemoryDatabase, HardCodedAkdVRF>;
+
+async fn new_key_server<TC: Configuration>() -> (TestKeyServer<TC>, Vec<u8>) {
+    let storage_manager = StorageManager::new_no_cache(AsyncInMemoryDatabase::new());
+    let directory = Directory::<TC, _, _>::new(storage_manager, HardCodedAkdVRF {})
+        .await
+        .unwrap();
+    let server = KeyServer::new(directory);
+    let vrf_public_key = server.vrf_public_key().await.unwrap();
+    (server, vrf_public_key)
+}
+
+fn client(account: &str, seed: u8, vrf_public_key: &[u8]) -> Client {
+    Client::new(
+        account,
+        SigningKey::from_bytes(&[seed; 32]),
+        vrf_public_key.to_vec(),
+    )
+}
+
+#[test]
+fn test_account_keys_encoding() {
+    let alice = client("alice", 1, &[]);
+    let keys = alice.account_keys(&[3, 1, 2]);
+    assert_eq!(vec![1, 2, 3], keys.device_ids());
+    assert_eq!(1 + 32 + 4 + 3 * (4 + 32 + 64), keys.to_value().0.len());
+    assert_eq!(keys, AccountKeys::from_value(&keys.to_value()).unwrap());
+    keys.verify("alice").unwrap();
+
+    // the order of the devices does not change the encoding
+    let mut reordered = keys.clone();
+    reordered.devices.reverse();
+    assert_eq!(keys.to_value(), reordered.to_value());
+
+    let value = keys.to_value().0;
+    for malformed in [
+        value[..value.len() - 1].to_vec(),
+        [&value[..], &[0]].concat(),
+        [&[2], &value[1..]].concat(),
+        vec![],
+    ] {
+        assert!(AccountKeys::from_value(&AkdValue(malformed)).is_err());
+    }
+
+    // device keys are bound to the account and signed by its identity key
+    assert!(keys.verify("bob").is_err());
+    let mut forged = keys.clone();
+    forged.devices[0].public_key[0] ^= 1;
+    assert!(forged.verify("alice").is_err());
+    let mut forged = keys;
+    forged.identity_key = client("mallory", 2, &[]).account_keys(&[]).identity_key;
+    assert!(forged.verify("alice").is_err());
+}
+
+#[test]
+fn test_safety_number() {
+    let alice = SigningKey::from_bytes(&[1; 32]).verifying_key().to_bytes();
+    let bob = SigningKey::from_bytes(&[2; 32]).verifying_key().to_bytes();
+    let mallory = SigningKey::from_bytes(&[3; 32]).verifying_key().to_bytes();
+
+    let safety_number = super::safety_number("alice", &alice, "bob", &bob);
+    assert_eq!(60, safety_number.len());
+    assert!(safety_number.chars().all(|c| c.is_ascii_digit()));
+    assert_eq!(
+        safety_number,
+        super::safety_number("bob", &bob, "alice", &alice)
+    );
+    assert_ne!(
+        safety_number,
+        super::safety_number("alice", &alice, "bob", &mallory)
+    );
+    assert_ne!(
+        safety_number,
+        super::safety_number("alice", &alice, "carol", &bob)
+    );
+}
+
+test_config!(test_contact_updates);
+async fn test_contact_updates<TC: Configuration>() {
+    let (server, vrf_public_key) = new_key_server::<TC>().await;
+    let mut alice = client("alice", 1, &vrf_public_key);
+    let mut bob = client("bob", 2, &vrf_public_key);
+    server
+        .publish("bob", &bob.account_keys(&[1, 2]))
+        .await
+        .unwrap();
+
+    let safety_number = match alice.refresh(&server, "bob").await.unwrap() {
+        ContactUpdate::New { safety_number } => safety_number,
+        update => panic!("Unexpected update {update:?}"),
+    };
+    assert_eq!(Some(safety_number.clone()), alice.safety_number("bob"));
+    assert_eq!(
+        ContactUpdate::Unchanged,
+        alice.refresh(&server, "bob").await.unwrap()
+    );
+
+    // changes of devices keep the safety number
+    server
+        .publish("bob", &bob.account_keys(&[2, 3]))
+        .await
+        .unwrap();
+    server
+        .publish("bob", &bob.account_keys(&[2, 3, 4]))
+        .await
+        .unwrap();
+    assert_eq!(
+        ContactUpdate::DevicesChanged {
+            added: vec![3, 4],
+            removed: vec![1]
+        },
+        alice.refresh(&server, "bob").await.unwrap()
+    );
+    assert_eq!(Some(safety_number.clone()), alice.safety_number("bob"));
+
+    // a new identity key changes it
+    bob.identity_key = SigningKey::from_bytes(&[3; 32]);
+    let EpochHash(epoch, _) = server
+        .publish("bob", &bob.account_keys(&[1]))
+        .await
+        .unwrap();
+    match alice.refresh(&server, "bob").await.unwrap() {
+        ContactUpdate::SafetyNumberChanged {
+            previous_safety_number,
+            safety_number: new_safety_number,
+            changed_in_epochs,
+        } => {
+            assert_eq!(safety_number, previous_safety_number);
+            assert_ne!(safety_number, new_safety_number);
+            assert_eq!(Some(new_safety_number), alice.safety_number("bob"));
+            assert_eq!(vec![epoch], changed_in_epochs);
+        }
+        update => panic!("Unexpected update {update:?}"),
+    }
+}
+
+test_config!(test_detects_transient_identity_keys);
+async fn test_detects_transient_identity_keys<TC: Configuration>() {
+    let (server, vrf_public_key) = new_key_server::<TC>().await;
+    let mut alice = client("alice", 1, &vrf_public_key);
+    let bob = client("bob", 2, &vrf_public_key);
+    let mallory = client("bob", 3, &vrf_public_key);
+    server
+        .publish("bob", &bob.account_keys(&[1]))
+        .await
+        .unwrap();
+    alice.refresh(&server, "bob").await.unwrap();
+    let safety_number = alice.safety_number("bob").unwrap();
+
+    // the server publishes a key of its own for bob, then restores bob's keys, so the
+    // latest value is the one alice has already seen
+    let EpochHash(first, _) = server
+        .publish("bob", &mallory.account_keys(&[1]))
+        .await
+        .unwrap();
+    server
+        .publish("bob", &mallory.account_keys(&[1, 2]))
+        .await
+        .unwrap();
+    let EpochHash(second, _) = server
+        .publish("bob", &bob.account_keys(&[1]))
+        .await
+        .unwrap();
+    assert_eq!(
+        ContactUpdate::SafetyNumberChanged {
+            previous_safety_number: safety_number.clone(),
+            safety_number,
+            changed_in_epochs: vec![first, second],
+        },
+        alice.refresh(&server, "bob").await.unwrap()
+    );
+}
+
+test_config!(test_rejects_invalid_keys);
+async fn test_rejects_invalid_keys<TC: Configuration>() {
+    let (server, vrf_public_key) = new_key_server::<TC>().await;
+    let mut alice = client("alice", 1, &vrf_public_key);
+    let bob = client("bob", 2, &vrf_public_key);
+
+    // the server refuses device keys which are not signed by the identity key
+    let mut forged = bob.account_keys(&[1]);
+    forged.devices[0].public_key[0] ^= 1;
+    assert!(server.publish("bob", &forged).await.is_err());
+
+    // and clients refuse them if the server publishes them anyway
+    server
+        .directory
+        .publish(vec![(AkdLabel::from("bob"), forged.to_value())])
+        .await
+        .unwrap();
+    assert!(alice.refresh(&server, "bob").await.is_err());
+    assert_eq!(None, alice.safety_number("bob"));
+
+    // as well as lookups with a different VRF key
+    let mut carol = client("carol", 3, &[0u8; 32]);
+    server
+        .publish("bob", &bob.account_keys(&[1]))
+        .await
+        .unwrap();
+    assert!(carol.refresh(&server, "bob").await.is_err());
+}
@@ -7,8 +7,15 @@
 
 //! A set of example applications and utilities for AKD
 
+mod bulk_import;
 mod fixture_generator;
+mod http_api;
+mod identity_keys;
+mod mls_adapter;
 mod mysql_demo;
+#[cfg(feature = "perf_regression")]
+mod perf_regression;
+mod public_keys;
 mod wasm_client;
 mod whatsapp_kt_auditor;
 
@@ -30,8 +37,19 @@ enum ExampleType {
     WhatsappKtAuditor(whatsapp_kt_auditor::CliArgs),
     /// MySQL Demo
     MysqlDemo(mysql_demo::CliArgs),
+    /// Bulk Import
+    BulkImport(bulk_import::CliArgs),
     /// Fixture Generator
     FixtureGenerator(fixture_generator::Args),
+    /// MLS Delivery Service Adapter
+    MlsAdapter(mls_adapter::CliArgs),
+    /// Identity Key Directory
+    IdentityKeys(identity_keys::CliArgs),
+    /// Public Key Directory
+    PublicKeys(public_keys::CliArgs),
+    /// Performance Regression Check
+    #[cfg(feature = "perf_regression")]
+    PerfRegression(perf_regression::CliArgs),
 }
 
 // MAIN //
@@ -42,7 +60,13 @@ async fn main() -> Result<()> {
     match args.example {
         ExampleType::WhatsappKtAuditor(args) => whatsapp_kt_auditor::render_cli(args).await?,
         ExampleType::MysqlDemo(args) => mysql_demo::render_cli(args).await?,
+        ExampleType::BulkImport(args) => bulk_import::run(args).await?,
         ExampleType::FixtureGenerator(args) => fixture_generator::run(args).await,
+        ExampleType::MlsAdapter(args) => mls_adapter::run(args).await?,
+        ExampleType::IdentityKeys(args) => identity_keys::run(args).await?,
+        ExampleType::PublicKeys(args) => public_keys::run(args).await?,
+        #[cfg(feature = "perf_regression")]
+        ExampleType::PerfRegression(args) => perf_regression::run(args).await?,
     }
 
     Ok(())
@@ -0,0 +1,425 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! An adapter which uses a directory as the key directory of an MLS (RFC 9420) delivery
+//! service. Example command:
+//!
+//!   cargo run -p examples -- mls-adapter
+//!
+//! MLS clients upload KeyPackages to the delivery service, which hands them out to the
+//! members adding them to groups. A KeyPackage is single-use, but the signature key of the
+//! leaf node it contains is the long-lived identity of the client, so it is the binding of
+//! each identity to its signature key which is published to the directory: the label of
+//! an identity is `mls:<cipher suite>:<identity>`, and its value is the signature key.
+//! The delivery service publishes a new version whenever a client uploads KeyPackages
+//! with a new signature key, so that each rotation is visible in the key history.
+//!
+//! When a member fetches a KeyPackage to add a client to a group, the delivery service
+//! returns a lookup proof of the client's identity along with it. Before the member adds
+//! the client, it verifies the lookup proof, and checks that the KeyPackage is signed by
+//! the signature key which the directory holds for the identity of its credential, so that
+//! a compromised delivery service cannot add a key of its own in the client's name.
+//!
+//! The KeyPackages are a simplified form of those of RFC 9420 (without capabilities,
+//! lifetimes or extensions), which are encoded and signed as in the RFC, for the
+//! `MLS_128_DHKEMX25519_AES128GCM_SHA256_Ed25519` cipher suite.
+
+#[cfg(test)]
+mod tests;
+
+use akd::ecvrf::{HardCodedAkdVRF, VRFKeyStorage};
+use akd::storage::memory::AsyncInMemoryDatabase;
+use akd::storage::{Database, StorageManager};
+use akd::{AkdLabel, AkdValue, Configuration, Directory, EpochHash, LookupProof};
+use anyhow::{anyhow, bail, Result};
+use clap::Parser;
+use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+type TC = akd::ExperimentalConfiguration<akd::ExampleLabel>;
+
+/// The MLS protocol version `mls10`
+pub(crate) const MLS_10: u16 = 1;
+/// The cipher suite `MLS_128_DHKEMX25519_AES128GCM_SHA256_Ed25519`
+pub(crate) const CIPHER_SUITE_X25519_ED25519: u16 = 1;
+/// The credential type `basic`
+const BASIC_CREDENTIAL: u16 = 1;
+
+#[derive(Parser, Debug, Clone)]
+pub(crate) struct CliArgs {
+    /// The number of clients which join the group
+    #[clap(long = "num_clients", default_value = "3")]
+    num_clients: usize,
+}
+
+/// An MLS KeyPackage, which a client uploads to the delivery service so that it can be
+/// added to groups while it is offline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct KeyPackage {
+    /// The protocol version
+    pub(crate) version: u16,
+    /// The cipher suite
+    pub(crate) cipher_suite: u16,
+    /// The HPKE public key which the Welcome message is encrypted to
+    pub(crate) init_key: Vec<u8>,
+    /// The HPKE public key of the client's leaf in the group's tree
+    pub(crate) encryption_key: Vec<u8>,
+    /// The public key with which the client signs
+    pub(crate) signature_key: Vec<u8>,
+    /// The identity of the client's basic credential
+    pub(crate) identity: Vec<u8>,
+    /// The signature of the KeyPackage with the signature key
+    pub(crate) signature: Vec<u8>,
+}
+
+impl KeyPackage {
+    /// Creates a KeyPackage for the identity, signed with its signature key
+    pub(crate) fn new(
+        identity: &[u8],
+        signing_key: &SigningKey,
+        init_key: Vec<u8>,
+        encryption_key: Vec<u8>,
+    ) -> Self {
+        let mut key_package = Self {
+            version: MLS_10,
+            cipher_suite: CIPHER_SUITE_X25519_ED25519,
+            init_key,
+            encryption_key,
+            signature_key: signing_key.verifying_key().as_bytes().to_vec(),
+            identity: identity.to_vec(),
+            signature: vec![],
+        };
+        key_package.signature = signing_key
+            .sign(&key_package.sign_content())
+            .to_bytes()
+            .to_vec();
+        key_package
+    }
+
+    /// The encoded `KeyPackageTBS`
+    fn to_be_signed(&self) -> Vec<u8> {
+        let mut tbs = vec![];
+        tbs.extend_from_slice(&self.version.to_be_bytes());
+        tbs.extend_from_slice(&self.cipher_suite.to_be_bytes());
+        write_vector(&mut tbs, &self.init_key);
+        // the leaf node
+        write_vector(&mut tbs, &self.encryption_key);
+        write_vector(&mut tbs, &self.signature_key);
+        tbs.extend_from_slice(&BASIC_CREDENTIAL.to_be_bytes());
+        write_vector(&mut tbs, &self.identity);
+        tbs
+    }
+
+    /// The `SignContent` which is signed by `SignWithLabel(., "KeyPackageTBS", .)`
+    fn sign_content(&self) -> Vec<u8> {
+        let mut content = vec![];
+        write_vector(&mut content, b"MLS 1.0 KeyPackageTBS");
+        write_vector(&mut content, &self.to_be_signed());
+        content
+    }
+
+    /// Verifies the signature of the KeyPackage with its own signature key
+    pub(crate) fn verify_signature(&self) -> Result<()> {
+        let signature_key = verifying_key(&self.signature_key)?;
+        let signature = ed25519_dalek::Signature::from_slice(&self.signature)?;
+        signature_key
+            .verify(&self.sign_content(), &signature)
+            .map_err(|_| anyhow!("Invalid KeyPackage signature"))
+    }
+
+    /// The directory label of the KeyPackage's identity
+    pub(crate) fn label(&self) -> AkdLabel {
+        identity_label(self.cipher_suite, &self.identity)
+    }
+}
+
+/// Writes a variable-length vector, with the length encoding of RFC 9420
+fn write_vector(out: &mut Vec<u8>, bytes: &[u8]) {
+    let len = bytes.len();
+    if len < 1 << 6 {
+        out.push(len as u8);
+    } else if len < 1 << 14 {
+        out.extend_from_slice(&(0x4000 | len as u16).to_be_bytes());
+    } else {
+        out.extend_from_slice(&(0x8000_0000 | len as u32).to_be_bytes());
+    }
+    out.extend_from_slice(bytes);
+}
+
+fn verifying_key(bytes: &[u8]) -> Result<VerifyingKey> {
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| anyhow!("An Ed25519 signature key must be 32 bytes"))?;
+    Ok(VerifyingKey::from_bytes(&bytes)?)
+}
+
+/// The directory label of an identity, for the signature keys of a cipher suite
+pub(crate) fn identity_label(cipher_suite: u16, identity: &[u8]) -> AkdLabel {
+    let mut label = format!("mls:{cipher_suite:04x}:").into_bytes();
+    label.extend_from_slice(identity);
+    AkdLabel(label)
+}
+
+/// A KeyPackage handed out by the delivery service, with the proof of the signature key
+/// of its identity
+#[derive(Debug, Clone)]
+pub(crate) struct KeyPackageResponse {
+    /// The KeyPackage
+    pub(crate) key_package: KeyPackage,
+    /// The lookup proof of the identity which the KeyPackage was requested for
+    pub(crate) proof: LookupProof,
+    /// The epoch and root hash which the proof verifies against
+    pub(crate) epoch_hash: EpochHash,
+}
+
+/// A delivery service which stores KeyPackages and publishes the signature keys of their
+/// identities to a directory
+pub(crate) struct DeliveryService<TC, S: Database, V> {
+    directory: Directory<TC, S, V>,
+    key_packages: Mutex<HashMap<AkdLabel, Vec<KeyPackage>>>,
+    /// The signature key which is published for each identity
+    published: Mutex<HashMap<AkdLabel, AkdValue>>,
+}
+
+impl<TC, S, V> DeliveryService<TC, S, V>
+where
+    TC: Configuration,
+    S: Database + 'static,
+    V: VRFKeyStorage,
+{
+    pub(crate) fn new(directory: Directory<TC, S, V>) -> Self {
+        Self {
+            directory,
+            key_packages: Mutex::new(HashMap::new()),
+            published: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Stores the uploaded KeyPackages, after publishing the signature keys of their
+    /// identities which have changed. Returns the published epoch, if any. Every
+    /// KeyPackage must be validly signed, and the KeyPackages of an identity must share
+    /// its signature key.
+    pub(crate) async fn upload(&self, key_packages: Vec<KeyPackage>) -> Result<Option<EpochHash>> {
+        let mut signature_keys = HashMap::<AkdLabel, AkdValue>::new();
+        for key_package in key_packages.iter() {
+            if key_package.version != MLS_10
+                || key_package.cipher_suite != CIPHER_SUITE_X25519_ED25519
+            {
+                bail!(
+                    "Unsupported KeyPackage version {} or cipher suite {}",
+                    key_package.version,
+                    key_package.cipher_suite
+                );
+            }
+            key_package.verify_signature()?;
+            let signature_key = AkdValue(key_package.signature_key.clone());
+            match signature_keys.insert(key_package.label(), signature_key.clone()) {
+                Some(other) if other != signature_key => bail!(
+                    "The KeyPackages of {} have different signature keys",
+                    String::from_utf8_lossy(&key_package.identity)
+                ),
+                _ => {}
+            }
+        }
+
+        let mut published = self.published.lock().await;
+        let updates = signature_keys
+            .into_iter()
+            .filter(|(label, signature_key)| published.get(label) != Some(signature_key))
+            .collect::<Vec<_>>();
+        let epoch_hash = if updates.is_empty() {
+            None
+        } else {
+            let epoch_hash = self.directory.publish(updates.clone()).await?;
+            published.extend(updates);
+            Some(epoch_hash)
+        };
+
+        let mut stored = self.key_packages.lock().await;
+        for key_package in key_packages {
+            let label = key_package.label();
+            let packages = stored.entry(label.clone()).or_default();
+            // KeyPackages of a previous signature key can no longer be verified
+            packages.retain(|package| package.signature_key == key_package.signature_key);
+            packages.push(key_package);
+        }
+        Ok(epoch_hash)
+    }
+
+    /// Hands out one of the KeyPackages of the identity, which is then removed, with the
+    /// lookup proof of the identity's signature key
+    pub(crate) async fn fetch(
+        &self,
+        cipher_suite: u16,
+        identity: &[u8],
+    ) -> Result<KeyPackageResponse> {
+        let label = identity_label(cipher_suite, identity);
+        let key_package = self
+            .key_packages
+            .lock()
+            .await
+            .get_mut(&label)
+            .and_then(|packages| packages.pop())
+            .ok_or_else(|| {
+                anyhow!(
+                    "No KeyPackage is available for {}",
+                    String::from_utf8_lossy(identity)
+                )
+            })?;
+        let (proof, epoch_hash) = self.directory.lookup(label).await?;
+        Ok(KeyPackageResponse {
+            key_package,
+            proof,
+            epoch_hash,
+        })
+    }
+}
+
+/// Verifies a KeyPackage which was fetched to add `identity` to a group: that the lookup
+/// proof of the identity verifies against the root hash, and that the KeyPackage is of
+/// the identity and signed with the signature key which the directory holds for it.
+/// Returns the KeyPackage, which can then be added to the group.
+///
+/// The root hash must be one which the member trusts, such as the root hash of an epoch
+/// which it has seen signed by the directory or checked by an auditor.
+pub(crate) fn verify_key_package<TC: Configuration>(
+    vrf_public_key: &[u8],
+    cipher_suite: u16,
+    identity: &[u8],
+    response: KeyPackageResponse,
+) -> Result<KeyPackage> {
+    let label = identity_label(cipher_suite, identity);
+    let result = akd::client::lookup_verify::<TC>(
+        vrf_public_key,
+        response.epoch_hash.hash(),
+        response.epoch_hash.epoch(),
+        label.clone(),
+        response.proof,
+    )
+    .map_err(|err| anyhow!("The lookup proof of the identity did not verify: {err}"))?;
+
+    let key_package = response.key_package;
+    if key_package.label() != label {
+        bail!(
+            "The KeyPackage is of {}, rather than {}",
+            String::from_utf8_lossy(&key_package.identity),
+            String::from_utf8_lossy(identity)
+        );
+    }
+    if key_package.signature_key != result.value.0 {
+        bail!(
+            "The KeyPackage's signature key is not the one published for {} (version {})",
+            String::from_utf8_lossy(identity),
+            result.version
+        );
+    }
+    key_package.verify_signature()?;
+    Ok(key_package)
+}
+
+/// A client, with its signature key and a source of HPKE keys
+struct Client {
+    identity: Vec<u8>,
+    signing_key: SigningKey,
+}
+
+impl Client {
+    fn new(identity: &str) -> Self {
+        Self {
+            identity: identity.as_bytes().to_vec(),
+            signing_key: SigningKey::from_bytes(&rand::random()),
+        }
+    }
+
+    fn key_packages(&self, count: usize) -> Vec<KeyPackage> {
+        (0..count)
+            .map(|_| {
+                // Placeholders for the HPKE keys, which are not used by the adapter
+                let init_key = rand::random::<[u8; 32]>().to_vec();
+                let encryption_key = rand::random::<[u8; 32]>().to_vec();
+                KeyPackage::new(&self.identity, &self.signing_key, init_key, encryption_key)
+            })
+            .collect()
+    }
+}
+
+pub(crate) async fn run(args: CliArgs) -> Result<()> {
+    let storage_manager = StorageManager::new_no_cache(AsyncInMemoryDatabase::new());
+    let directory = Directory::<TC, _, _>::new(storage_manager, HardCodedAkdVRF {}).await?;
+    let vrf_public_key = directory.get_public_key().await?;
+    let delivery_service = DeliveryService::new(directory);
+
+    let mut clients = (0..args.num_clients.max(1))
+        .map(|i| Client::new(&format!("client{i}@example.com")))
+        .collect::<Vec<_>>();
+    for client in clients.iter() {
+        if let Some(EpochHash(epoch, _)) = delivery_service.upload(client.key_packages(2)).await? {
+            println!(
+                "{} uploaded KeyPackages, and its signature key was published in epoch {epoch}",
+                String::from_utf8_lossy(&client.identity)
+            );
+        }
+    }
+
+    // The first client creates a group and adds the others
+    let identities = clients[1..]
+        .iter()
+        .map(|client| client.identity.clone())
+        .collect::<Vec<_>>();
+    for identity in identities {
+        let response = delivery_service
+            .fetch(CIPHER_SUITE_X25519_ED25519, &identity)
+            .await?;
+        let epoch = response.epoch_hash.epoch();
+        verify_key_package::<TC>(
+            vrf_public_key.as_bytes(),
+            CIPHER_SUITE_X25519_ED25519,
+            &identity,
+            response,
+        )?;
+        println!(
+            "Verified the KeyPackage of {} against epoch {epoch}, and added it to the group",
+            String::from_utf8_lossy(&identity)
+        );
+    }
+
+    // A client rotates its signature key, which publishes a new version of its identity
+    let rotated = clients.len() - 1;
+    clients[rotated].signing_key = SigningKey::from_bytes(&rand::random());
+    if let Some(EpochHash(epoch, _)) = delivery_service
+        .upload(clients[rotated].key_packages(1))
+        .await?
+    {
+        println!(
+            "{} rotated its signature key in epoch {epoch}",
+            String::from_utf8_lossy(&clients[rotated].identity)
+        );
+    }
+
+    // A delivery service which substitutes a KeyPackage of its own is detected
+    let identity = clients[rotated].identity.clone();
+    let mut response = delivery_service
+        .fetch(CIPHER_SUITE_X25519_ED25519, &identity)
+        .await?;
+    response.key_package = Client {
+        identity: identity.clone(),
+        signing_key: SigningKey::from_bytes(&rand::random()),
+    }
+    .key_packages(1)
+    .remove(0);
+    match verify_key_package::<TC>(
+        vrf_public_key.as_bytes(),
+        CIPHER_SUITE_X25519_ED25519,
+        &identity,
+        response,
+    ) {
+        Ok(_) => bail!("A substituted KeyPackage was accepted"),
+        Err(err) => println!("Rejected a substituted KeyPackage: {err}"),
+    }
+    Ok(())
+}
@@ -0,0 +1,246 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Tests for the MLS delivery service adapter
+
+use super::*;
+use crate::test_config;
+use akd::HistoryParams;
+
+type TestDeliveryService<TC> = DeliveryService<TC, AsyncInMemoryDatabase, HardCodedAkdVRF>;
+
+async fn new_delivery_service<TC: Configuration>() -> TestDeliveryService<TC> {
+    let storage_manager = StorageManager::new_no_cache(AsyncInMemoryDatabase::new());
+    let directory = Directory::<TC, _, _>::new(storage_manager, HardCodedAkdVRF {})
+        .await
+        .unwrap();
+    DeliveryService::new(directory)
+}
+
+fn signing_key(seed: u8) -> SigningKey {
+    SigningKey::from_bytes(&[seed; 32])
+}
+
+fn key_package(identity: &str, signing_key: &SigningKey, seed: u8) -> KeyPackage {
+    KeyPackage::new(
+        identity.as_bytes(),
+        signing_key,
+        vec![seed; 32],
+        vec![seed.wrapping_add(1); 32],
+    )
+}
+
+async fn fetch_and_verify<TC: Configuration>(
+    delivery_service: &TestDeliveryService<TC>,
+    identity: &str,
+) -> Result<KeyPackage> {
+    let vrf_public_key = delivery_service.directory.get_public_key().await?;
+    let response = delivery_service
+        .fetch(CIPHER_SUITE_X25519_ED25519, identity.as_bytes())
+        .await?;
+    verify_key_package::<TC>(
+        vrf_public_key.as_bytes(),
+        CIPHER_SUITE_X25519_ED25519,
+        identity.as_bytes(),
+        response,
+    )
+}
+
+#[test]
+fn test_key_package_signature() {
+    let key_package = key_package("alice", &signing_key(1), 0);
+    key_package.verify_signature().unwrap();
+    assert_eq!(
+        AkdLabel::from("mls:0001:alice"),
+        key_package.label(),
+        "The label of an identity is scoped to the cipher suite"
+    );
+
+    for tamper in [
+        |key_package: &mut KeyPackage| key_package.identity = b"mallory".to_vec(),
+        |key_package: &mut KeyPackage| key_package.init_key[0] ^= 1,
+        |key_package: &mut KeyPackage| key_package.cipher_suite = 2,
+        |key_package: &mut KeyPackage| key_package.signature_key[0] ^= 1,
+    ] {
+        let mut tampered = key_package.clone();
+        tamper(&mut tampered);
+        assert!(tampered.verify_signature().is_err());
+    }
+
+    // lengths of 64 bytes and more take two bytes to encode
+    let mut out = vec![];
+    write_vector(&mut out, &[0u8; 63]);
+    assert_eq!(63, out[0]);
+    out.clear();
+    write_vector(&mut out, &[0u8; 300]);
+    assert_eq!([0x41, 0x2c], out[..2]);
+}
+
+test_config!(test_add_members);
+async fn test_add_members<TC: Configuration>() {
+    let delivery_service = new_delivery_service::<TC>().await;
+    let alice = signing_key(1);
+    let bob = signing_key(2);
+
+    let EpochHash(epoch, _) = delivery_service
+        .upload(vec![
+            key_package("alice", &alice, 10),
+            key_package("alice", &alice, 11),
+            key_package("bob", &bob, 20),
+        ])
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(1, epoch);
+
+    // each KeyPackage is handed out once, with a proof of the identity's signature key
+    let mut fetched = vec![
+        fetch_and_verify(&delivery_service, "alice").await.unwrap(),
+        fetch_and_verify(&delivery_service, "alice").await.unwrap(),
+    ];
+    fetched.sort_by_key(|key_package| key_package.init_key.clone());
+    assert_eq!(
+        vec![
+            key_package("alice", &alice, 10),
+            key_package("alice", &alice, 11)
+        ],
+        fetched
+    );
+    assert!(fetch_and_verify(&delivery_service, "alice").await.is_err());
+    assert_eq!(
+        key_package("bob", &bob, 20),
+        fetch_and_verify(&delivery_service, "bob").await.unwrap()
+    );
+
+    // uploading more KeyPackages with the same signature key publishes nothing
+    assert_eq!(
+        None,
+        delivery_service
+            .upload(vec![key_package("alice", &alice, 12)])
+            .await
+            .unwrap()
+    );
+    fetch_and_verify(&delivery_service, "alice").await.unwrap();
+}
+
+test_config!(test_signature_key_rotation);
+async fn test_signature_key_rotation<TC: Configuration>() {
+    let delivery_service = new_delivery_service::<TC>().await;
+    let old_key = signing_key(1);
+    let new_key = signing_key(2);
+    delivery_service
+        .upload(vec![
+            key_package("alice", &old_key, 10),
+            key_package("alice", &old_key, 11),
+        ])
+        .await
+        .unwrap();
+    let EpochHash(epoch, _) = delivery_service
+        .upload(vec![key_package("alice", &new_key, 12)])
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(2, epoch);
+
+    // the KeyPackages of the old signature key are dropped
+    let key_package = fetch_and_verify(&delivery_service, "alice").await.unwrap();
+    assert_eq!(
+        new_key.verifying_key().as_bytes().to_vec(),
+        key_package.signature_key
+    );
+    assert!(fetch_and_verify(&delivery_service, "alice").await.is_err());
+
+    // and the rotation is visible in the history of the identity
+    let (proof, EpochHash(epoch, root_hash)) = delivery_service
+        .directory
+        .key_history(
+            &identity_label(CIPHER_SUITE_X25519_ED25519, b"alice"),
+            HistoryParams::Complete,
+        )
+        .await
+        .unwrap();
+    let vrf_public_key = delivery_service.directory.get_public_key().await.unwrap();
+    let history = akd::client::key_history_verify::<TC>(
+        vrf_public_key.as_bytes(),
+        root_hash,
+        epoch,
+        identity_label(CIPHER_SUITE_X25519_ED25519, b"alice"),
+        proof,
+        akd::HistoryVerificationParams::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        vec![
+            new_key.verifying_key().as_bytes().to_vec(),
+            old_key.verifying_key().as_bytes().to_vec()
+        ],
+        history
+            .into_iter()
+            .map(|result| result.value.0)
+            .collect::<Vec<_>>()
+    );
+}
+
+test_config!(test_rejects_substituted_key_packages);
+async fn test_rejects_substituted_key_packages<TC: Configuration>() {
+    let delivery_service = new_delivery_service::<TC>().await;
+    let alice = signing_key(1);
+    let mallory = signing_key(3);
+    delivery_service
+        .upload(vec![
+            key_package("alice", &alice, 10),
+            key_package("alice", &alice, 11),
+            key_package("mallory", &mallory, 30),
+        ])
+        .await
+        .unwrap();
+    let vrf_public_key = delivery_service.directory.get_public_key().await.unwrap();
+    let verify = |response| {
+        verify_key_package::<TC>(
+            vrf_public_key.as_bytes(),
+            CIPHER_SUITE_X25519_ED25519,
+            b"alice",
+            response,
+        )
+    };
+
+    // a KeyPackage in alice's name, signed with a key which was never published
+    let mut response = delivery_service
+        .fetch(CIPHER_SUITE_X25519_ED25519, b"alice")
+        .await
+        .unwrap();
+    response.key_package = key_package("alice", &mallory, 31);
+    assert!(verify(response).is_err());
+
+    // a KeyPackage of another identity, with its valid proof
+    let response = delivery_service
+        .fetch(CIPHER_SUITE_X25519_ED25519, b"mallory")
+        .await
+        .unwrap();
+    assert!(verify(response).is_err());
+
+    // alice's KeyPackage with a proof against a different root hash
+    let mut response = delivery_service
+        .fetch(CIPHER_SUITE_X25519_ED25519, b"alice")
+        .await
+        .unwrap();
+    response.epoch_hash = EpochHash(response.epoch_hash.epoch(), [0u8; 32]);
+    assert!(verify(response).is_err());
+
+    // uploads of KeyPackages which are not validly signed, or which disagree on the
+    // signature key of an identity, are rejected
+    let mut forged = key_package("alice", &alice, 12);
+    forged.signature_key = mallory.verifying_key().as_bytes().to_vec();
+    assert!(delivery_service.upload(vec![forged]).await.is_err());
+    assert!(delivery_service
+        .upload(vec![
+            key_package("bob", &signing_key(4), 40),
+            key_package("bob", &signing_key(5), 41)
+        ])
+        .await
+        .is_err());
+}
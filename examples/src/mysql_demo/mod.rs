@@ -26,7 +26,7 @@ use tokio::time::timeout;
 mod commands;
 mod directory_host;
 mod logs;
-mod mysql;
+pub(crate) mod mysql;
 mod mysql_storables;
 
 #[cfg(test)]
@@ -236,6 +236,7 @@ async fn process_input(
                         1u32,
                         [1u8; 32],
                         1u64,
+                        None,
                     );
                     data.push(akd::storage::types::DbRecord::ValueState(state));
                 }
@@ -248,7 +249,7 @@ async fn process_input(
                     assert_eq!(Ok(()), storage.batch_set(data).await);
                     let toc: Duration = Instant::now() - tic;
                     println!("Insert batch of {} items in {} ms", len, toc.as_millis());
-                    storage.log_metrics(log::Level::Warn).await;
+                    storage.log_metrics(tracing::Level::WARN).await;
                 } else {
                     error!("Command available with MySQL db's only");
                 }
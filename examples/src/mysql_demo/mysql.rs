@@ -14,7 +14,7 @@ use akd::storage::types::{DbRecord, KeyData, StorageType, ValueState, ValueState
 use akd::storage::{Database, Storable};
 use akd::tree_node::TreeNodeWithPreviousValue;
 use akd::NodeLabel;
-use akd::{AkdLabel, AkdValue};
+use akd::{AkdLabel, AkdValue, RetentionClass};
 use async_trait::async_trait;
 use log::{debug, error, info, warn};
 use mysql_async::prelude::*;
@@ -32,6 +32,11 @@ type MySqlError = mysql_async::Error;
 const TABLE_AZKS: &str = crate::mysql_demo::mysql_storables::TABLE_AZKS;
 const TABLE_HISTORY_TREE_NODES: &str = crate::mysql_demo::mysql_storables::TABLE_HISTORY_TREE_NODES;
 const TABLE_USER: &str = crate::mysql_demo::mysql_storables::TABLE_USER;
+const TABLE_OPERATIONS: &str = crate::mysql_demo::mysql_storables::TABLE_OPERATIONS;
+const TABLE_SOFT_DELETIONS: &str = crate::mysql_demo::mysql_storables::TABLE_SOFT_DELETIONS;
+const TABLE_ANCHOR_RECEIPTS: &str = crate::mysql_demo::mysql_storables::TABLE_ANCHOR_RECEIPTS;
+const TABLE_PUBLISH_LEASE: &str = crate::mysql_demo::mysql_storables::TABLE_PUBLISH_LEASE;
+const TABLE_AUDIT_EPOCHS: &str = crate::mysql_demo::mysql_storables::TABLE_AUDIT_EPOCHS;
 const TEMP_IDS_TABLE: &str = crate::mysql_demo::mysql_storables::TEMP_IDS_TABLE;
 
 const MAXIMUM_SQL_TIER_CONNECTION_TIMEOUT_SECS: u64 = 300;
@@ -122,7 +127,10 @@ impl<'a> AsyncMySqlDatabase {
             .db_name(Option::from(database))
             .user(user)
             .pass(password)
-            .tcp_port(dport);
+            .tcp_port(dport)
+            // an update counts the rows it matches, rather than the rows it changes, so that a
+            // conditional write which leaves its row unchanged is not mistaken for a conflict
+            .client_found_rows(true);
         let opts: Opts = builder.into();
 
         #[allow(clippy::mutex_atomic)]
@@ -326,8 +334,64 @@ impl<'a> AsyncMySqlDatabase {
             + TABLE_USER
             + "` (`username` VARBINARY(256) NOT NULL, `epoch` BIGINT UNSIGNED NOT NULL, `version` BIGINT UNSIGNED NOT NULL,"
             + " `node_label_val` VARBINARY(32) NOT NULL, `node_label_len` INT UNSIGNED NOT NULL, `data` VARBINARY(2000),"
+            + " `retention_class` TINYINT UNSIGNED NULL,"
             + " PRIMARY KEY(`username`, `epoch`))";
         tx.query_drop(command).await?;
+        // User data tables created before retention classes were introduced lack the column
+        let command =
+            "SELECT COUNT(*) FROM information_schema.COLUMNS WHERE `TABLE_SCHEMA` = DATABASE()"
+                .to_owned()
+                + " AND `TABLE_NAME` = '"
+                + TABLE_USER
+                + "' AND `COLUMN_NAME` = 'retention_class'";
+        let retention_columns: Option<u64> = tx.query_first(command).await?;
+        if retention_columns == Some(0) {
+            let command = "ALTER TABLE `".to_owned()
+                + TABLE_USER
+                + "` ADD COLUMN `retention_class` TINYINT UNSIGNED NULL";
+            tx.query_drop(command).await?;
+        }
+
+        // Operations log table
+        let command = "CREATE TABLE IF NOT EXISTS `".to_owned()
+            + TABLE_OPERATIONS
+            + "` (`sequence` BIGINT UNSIGNED NOT NULL, `kind` TINYINT UNSIGNED NOT NULL,"
+            + " `epoch` BIGINT UNSIGNED NOT NULL, `timestamp_ms` BIGINT UNSIGNED NOT NULL,"
+            + " `identity` VARCHAR(256) NOT NULL, `metadata` TEXT NOT NULL, `detail` TEXT NOT NULL,"
+            + " PRIMARY KEY(`sequence`))";
+        tx.query_drop(command).await?;
+
+        // Soft deletions table
+        let command = "CREATE TABLE IF NOT EXISTS `".to_owned()
+            + TABLE_SOFT_DELETIONS
+            + "` (`username` VARBINARY(256) NOT NULL, `epoch` BIGINT UNSIGNED NOT NULL,"
+            + " PRIMARY KEY(`username`))";
+        tx.query_drop(command).await?;
+
+        // Anchor receipts table
+        let command = "CREATE TABLE IF NOT EXISTS `".to_owned()
+            + TABLE_ANCHOR_RECEIPTS
+            + "` (`epoch` BIGINT UNSIGNED NOT NULL, `anchor` VARCHAR(256) NOT NULL,"
+            + " `root_hash` BINARY(32) NOT NULL, `timestamp_ms` BIGINT UNSIGNED NOT NULL,"
+            + " `receipt` MEDIUMBLOB NOT NULL, PRIMARY KEY(`epoch`, `anchor`))";
+        tx.query_drop(command).await?;
+
+        // Publish lease table
+        let command = "CREATE TABLE IF NOT EXISTS `".to_owned()
+            + TABLE_PUBLISH_LEASE
+            + "` (`key` SMALLINT UNSIGNED NOT NULL, `holder` VARCHAR(256) NOT NULL,"
+            + " `token` BIGINT UNSIGNED NOT NULL, `expires_at_ms` BIGINT UNSIGNED NOT NULL,"
+            + " PRIMARY KEY (`key`))";
+        tx.query_drop(command).await?;
+
+        // Audit epochs table
+        let command = "CREATE TABLE IF NOT EXISTS `".to_owned()
+            + TABLE_AUDIT_EPOCHS
+            + "` (`index` BIGINT UNSIGNED NOT NULL, `start_epoch` BIGINT UNSIGNED NOT NULL,"
+            + " `start_root_hash` BINARY(32) NOT NULL, `end_epoch` BIGINT UNSIGNED NOT NULL,"
+            + " `end_root_hash` BINARY(32) NOT NULL, `timestamp_ms` BIGINT UNSIGNED NOT NULL,"
+            + " PRIMARY KEY(`index`))";
+        tx.query_drop(command).await?;
 
         // if we got here, we're good to commit. Transaction's will auto-rollback when memory freed if commit wasn't done.
         tx.commit().await?;
@@ -348,6 +412,21 @@ impl<'a> AsyncMySqlDatabase {
         let command = "DELETE FROM `".to_owned() + TABLE_HISTORY_TREE_NODES + "`";
         tx.query_drop(command).await?;
 
+        let command = "DELETE FROM `".to_owned() + TABLE_OPERATIONS + "`";
+        tx.query_drop(command).await?;
+
+        let command = "DELETE FROM `".to_owned() + TABLE_SOFT_DELETIONS + "`";
+        tx.query_drop(command).await?;
+
+        let command = "DELETE FROM `".to_owned() + TABLE_ANCHOR_RECEIPTS + "`";
+        tx.query_drop(command).await?;
+
+        let command = "DELETE FROM `".to_owned() + TABLE_PUBLISH_LEASE + "`";
+        tx.query_drop(command).await?;
+
+        let command = "DELETE FROM `".to_owned() + TABLE_AUDIT_EPOCHS + "`";
+        tx.query_drop(command).await?;
+
         tx.commit().await?;
 
         Ok(())
@@ -367,6 +446,21 @@ impl<'a> AsyncMySqlDatabase {
         let command = "DROP TABLE IF EXISTS `".to_owned() + TABLE_HISTORY_TREE_NODES + "`";
         tx.query_drop(command).await?;
 
+        let command = "DROP TABLE IF EXISTS `".to_owned() + TABLE_OPERATIONS + "`";
+        tx.query_drop(command).await?;
+
+        let command = "DROP TABLE IF EXISTS `".to_owned() + TABLE_SOFT_DELETIONS + "`";
+        tx.query_drop(command).await?;
+
+        let command = "DROP TABLE IF EXISTS `".to_owned() + TABLE_ANCHOR_RECEIPTS + "`";
+        tx.query_drop(command).await?;
+
+        let command = "DROP TABLE IF EXISTS `".to_owned() + TABLE_PUBLISH_LEASE + "`";
+        tx.query_drop(command).await?;
+
+        let command = "DROP TABLE IF EXISTS `".to_owned() + TABLE_AUDIT_EPOCHS + "`";
+        tx.query_drop(command).await?;
+
         tx.commit().await?;
 
         Ok(())
@@ -439,6 +533,21 @@ impl<'a> AsyncMySqlDatabase {
                 DbRecord::ValueState(_) => {
                     DbRecord::set_batch_statement::<akd::storage::types::ValueState>(i)
                 }
+                DbRecord::OperationRecord(_) => {
+                    DbRecord::set_batch_statement::<akd::storage::types::OperationRecord>(i)
+                }
+                DbRecord::SoftDeletion(_) => {
+                    DbRecord::set_batch_statement::<akd::storage::types::SoftDeletion>(i)
+                }
+                DbRecord::AnchorReceipt(_) => {
+                    DbRecord::set_batch_statement::<akd::storage::types::AnchorReceipt>(i)
+                }
+                DbRecord::PublishLease(_) => {
+                    DbRecord::set_batch_statement::<akd::storage::types::PublishLease>(i)
+                }
+                DbRecord::AuditEpoch(_) => {
+                    DbRecord::set_batch_statement::<akd::storage::types::AuditEpoch>(i)
+                }
             }
         };
 
@@ -471,6 +580,173 @@ impl<'a> AsyncMySqlDatabase {
         Ok(trans)
     }
 
+    /// Writes records of any type on a transaction, in a batch for each type of record
+    async fn internal_batch_set_all(
+        &self,
+        records: Vec<DbRecord>,
+        mut trans: mysql_async::Transaction<'a>,
+    ) -> core::result::Result<mysql_async::Transaction<'a>, MySqlError> {
+        // generate batches by type
+        let mut groups = std::collections::HashMap::new();
+        for record in records {
+            match &record {
+                DbRecord::Azks(_) => groups
+                    .entry(StorageType::Azks)
+                    .or_insert_with(Vec::new)
+                    .push(record),
+                DbRecord::TreeNode(_) => groups
+                    .entry(StorageType::TreeNode)
+                    .or_insert_with(Vec::new)
+                    .push(record),
+                DbRecord::ValueState(_) => groups
+                    .entry(StorageType::ValueState)
+                    .or_insert_with(Vec::new)
+                    .push(record),
+                DbRecord::OperationRecord(_) => groups
+                    .entry(StorageType::OperationRecord)
+                    .or_insert_with(Vec::new)
+                    .push(record),
+                DbRecord::SoftDeletion(_) => groups
+                    .entry(StorageType::SoftDeletion)
+                    .or_insert_with(Vec::new)
+                    .push(record),
+                DbRecord::AnchorReceipt(_) => groups
+                    .entry(StorageType::AnchorReceipt)
+                    .or_insert_with(Vec::new)
+                    .push(record),
+                DbRecord::PublishLease(_) => groups
+                    .entry(StorageType::PublishLease)
+                    .or_insert_with(Vec::new)
+                    .push(record),
+                DbRecord::AuditEpoch(_) => groups
+                    .entry(StorageType::AuditEpoch)
+                    .or_insert_with(Vec::new)
+                    .push(record),
+            }
+        }
+        // go through each group which is narrowed to a single type
+        for (_key, mut value) in groups.into_iter() {
+            if !value.is_empty() {
+                // Sort the records to match db-layer sorting which will help with insert performance
+                value.sort_by(|a, b| match &a {
+                    DbRecord::TreeNode(node) => {
+                        if let DbRecord::TreeNode(node2) = &b {
+                            node.label.cmp(&node2.label)
+                        } else {
+                            Ordering::Equal
+                        }
+                    }
+                    DbRecord::ValueState(state) => {
+                        if let DbRecord::ValueState(state2) = &b {
+                            match state.username.0.cmp(&state2.username.0) {
+                                Ordering::Equal => state.epoch.cmp(&state2.epoch),
+                                other => other,
+                            }
+                        } else {
+                            Ordering::Equal
+                        }
+                    }
+                    _ => Ordering::Equal,
+                });
+                // execute the multi-batch insert statement(s)
+                trans = self.internal_batch_set(value, trans).await?;
+            }
+        }
+        Ok(trans)
+    }
+
+    /// Writes a record on a transaction if the version stamp of the stored record is the one
+    /// expected, returning whether it was written. The head of the tree and the publish lease
+    /// are updated conditionally on their version columns, and records which are not expected to
+    /// be stored are inserted unless they are.
+    async fn internal_set_if_version(
+        &self,
+        record: DbRecord,
+        expected_version: Option<u64>,
+        trans: &mut mysql_async::Transaction<'a>,
+    ) -> core::result::Result<bool, MySqlError> {
+        self.record_call_stats('w', "internal_set_if_version".to_string(), "".to_string())
+            .await;
+
+        let out = match (&record, expected_version) {
+            (DbRecord::Azks(azks), Some(expected)) => {
+                let statement = format!(
+                    "UPDATE `{TABLE_AZKS}` SET `epoch` = :epoch, `num_nodes` = :num_nodes WHERE `key` = :key AND `epoch` = :expected"
+                );
+                let params = params! { "key" => 1u8, "epoch" => azks.latest_epoch, "num_nodes" => azks.num_nodes, "expected" => expected };
+                trans.exec_drop(statement, params).await
+            }
+            (DbRecord::PublishLease(lease), Some(expected)) => {
+                let statement = format!(
+                    "UPDATE `{TABLE_PUBLISH_LEASE}` SET `holder` = :holder, `token` = :token, `expires_at_ms` = :expires_at_ms WHERE `key` = :key AND `token` = :expected"
+                );
+                let params = params! { "key" => 1u8, "holder" => lease.holder.clone(), "token" => lease.token, "expires_at_ms" => lease.expires_at_ms, "expected" => expected };
+                trans.exec_drop(statement, params).await
+            }
+            (_, None) => {
+                let statement = record.set_statement();
+                let statement = statement
+                    .split("ON DUPLICATE KEY UPDATE")
+                    .next()
+                    .unwrap_or_default()
+                    .replacen("INSERT INTO", "INSERT IGNORE INTO", 1);
+                let params = record.set_params().ok_or_else(|| {
+                    Error::Other("Failed to construct MySQL parameters block".into())
+                })?;
+                trans.exec_drop(statement, params).await
+            }
+            (_, Some(expected)) => {
+                // the stored record is locked until the transaction ends, so that it cannot
+                // change between the check of its version and the write
+                let stored = self.check_for_infra_error(locked_version(trans, &record).await)?;
+                if stored != Some(expected) {
+                    return Ok(false);
+                }
+                let params = record.set_params().ok_or_else(|| {
+                    Error::Other("Failed to construct MySQL parameters block".into())
+                })?;
+                trans.exec_drop(record.set_statement(), params).await
+            }
+        };
+        self.check_for_infra_error(out)?;
+
+        Ok(trans.affected_rows() >= 1)
+    }
+
+    /// Writes records, and then each of the conditional records if the version stamp of its
+    /// stored record is the one expected, all in a single transaction. Returns whether the
+    /// records were written, as the transaction is rolled back if any version does not match.
+    async fn internal_batch_set_if_versions(
+        &self,
+        records: Vec<DbRecord>,
+        conditional: Vec<(DbRecord, Option<u64>)>,
+    ) -> core::result::Result<bool, MySqlError> {
+        let mut conn = self.get_connection().await?;
+        let mut tx = conn.start_transaction(TxOpts::default()).await?;
+        tx.query_drop("SET autocommit=0").await?;
+        tx.query_drop("SET unique_checks=0").await?;
+        tx.query_drop("SET foreign_key_checks=0").await?;
+
+        tx = self.internal_batch_set_all(records, tx).await?;
+
+        tx.query_drop("SET autocommit=1").await?;
+        tx.query_drop("SET unique_checks=1").await?;
+        tx.query_drop("SET foreign_key_checks=1").await?;
+
+        for (record, expected_version) in conditional {
+            if !self
+                .internal_set_if_version(record, expected_version, &mut tx)
+                .await?
+            {
+                tx.rollback().await?;
+                return Ok(false);
+            }
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
     /// Create the test database
     #[allow(dead_code)]
     pub async fn create_test_db<T: Into<String>>(
@@ -580,6 +856,7 @@ impl<'a> AsyncMySqlDatabase {
         false
     }
 
+    #[tracing::instrument(level = "debug", skip_all, fields(db.system = "mysql", db.operation = "SELECT"))]
     async fn get_direct<St: Storable>(
         &self,
         id: &St::StorageKey,
@@ -630,9 +907,56 @@ impl<'a> AsyncMySqlDatabase {
     }
 }
 
+/// Retrieves the version stamp of the stored record with the same id as `record`, if there is
+/// one, locking it until the end of the transaction
+async fn locked_version(
+    trans: &mut mysql_async::Transaction<'_>,
+    record: &DbRecord,
+) -> core::result::Result<Option<u64>, MySqlError> {
+    match record {
+        DbRecord::Azks(azks) => locked_version_of::<akd::Azks>(trans, &azks.get_id()).await,
+        DbRecord::TreeNode(node) => {
+            locked_version_of::<TreeNodeWithPreviousValue>(trans, &node.get_id()).await
+        }
+        DbRecord::ValueState(state) => {
+            locked_version_of::<ValueState>(trans, &state.get_id()).await
+        }
+        DbRecord::OperationRecord(operation) => {
+            locked_version_of::<akd::storage::types::OperationRecord>(trans, &operation.get_id())
+                .await
+        }
+        DbRecord::SoftDeletion(deletion) => {
+            locked_version_of::<akd::storage::types::SoftDeletion>(trans, &deletion.get_id()).await
+        }
+        DbRecord::AnchorReceipt(receipt) => {
+            locked_version_of::<akd::storage::types::AnchorReceipt>(trans, &receipt.get_id()).await
+        }
+        DbRecord::PublishLease(lease) => {
+            locked_version_of::<akd::storage::types::PublishLease>(trans, &lease.get_id()).await
+        }
+        DbRecord::AuditEpoch(audit_epoch) => {
+            locked_version_of::<akd::storage::types::AuditEpoch>(trans, &audit_epoch.get_id()).await
+        }
+    }
+}
+
+async fn locked_version_of<St: Storable>(
+    trans: &mut mysql_async::Transaction<'_>,
+    id: &St::StorageKey,
+) -> core::result::Result<Option<u64>, MySqlError> {
+    let statement = DbRecord::get_specific_statement::<St>() + " FOR UPDATE";
+    let row: Option<Row> = match DbRecord::get_specific_params::<St>(id) {
+        Some(params) => trans.exec_first(statement, params).await?,
+        None => trans.query_first(statement).await?,
+    };
+    row.map(|mut row| DbRecord::from_row::<St>(&mut row).map(|record| record.version_stamp()))
+        .transpose()
+}
+
 #[async_trait]
 impl Database for AsyncMySqlDatabase {
     /// Storage a record in the data layer
+    #[tracing::instrument(level = "debug", skip_all, fields(db.system = "mysql", db.operation = "INSERT"))]
     async fn set(&self, record: DbRecord) -> core::result::Result<(), StorageError> {
         match self.internal_set(record, None).await {
             Ok(_) => Ok(()),
@@ -643,6 +967,7 @@ impl Database for AsyncMySqlDatabase {
         }
     }
 
+    #[tracing::instrument(level = "debug", skip_all, fields(db.system = "mysql", db.operation = "INSERT", num_records = records.len()))]
     async fn batch_set(
         &self,
         records: Vec<DbRecord>,
@@ -653,61 +978,16 @@ impl Database for AsyncMySqlDatabase {
             return Ok(());
         }
 
-        // generate batches by type
-        let mut groups = std::collections::HashMap::new();
-        for record in records {
-            match &record {
-                DbRecord::Azks(_) => groups
-                    .entry(StorageType::Azks)
-                    .or_insert_with(Vec::new)
-                    .push(record),
-                DbRecord::TreeNode(_) => groups
-                    .entry(StorageType::TreeNode)
-                    .or_insert_with(Vec::new)
-                    .push(record),
-                DbRecord::ValueState(_) => groups
-                    .entry(StorageType::ValueState)
-                    .or_insert_with(Vec::new)
-                    .push(record),
-            }
-        }
         // now execute each type'd batch in batch operations
         let result = async {
             let mut conn = self.get_connection().await?;
             let mut tx = conn.start_transaction(TxOpts::default()).await?;
-            // go through each group which is narrowed to a single type
-            // applying the changes on the transaction
+            // apply the changes on the transaction
             tx.query_drop("SET autocommit=0").await?;
             tx.query_drop("SET unique_checks=0").await?;
             tx.query_drop("SET foreign_key_checks=0").await?;
 
-            for (_key, mut value) in groups.into_iter() {
-                if !value.is_empty() {
-                    // Sort the records to match db-layer sorting which will help with insert performance
-                    value.sort_by(|a, b| match &a {
-                        DbRecord::TreeNode(node) => {
-                            if let DbRecord::TreeNode(node2) = &b {
-                                node.label.cmp(&node2.label)
-                            } else {
-                                Ordering::Equal
-                            }
-                        }
-                        DbRecord::ValueState(state) => {
-                            if let DbRecord::ValueState(state2) = &b {
-                                match state.username.0.cmp(&state2.username.0) {
-                                    Ordering::Equal => state.epoch.cmp(&state2.epoch),
-                                    other => other,
-                                }
-                            } else {
-                                Ordering::Equal
-                            }
-                        }
-                        _ => Ordering::Equal,
-                    });
-                    // execute the multi-batch insert statement(s)
-                    tx = self.internal_batch_set(value, tx).await?;
-                }
-            }
+            tx = self.internal_batch_set_all(records, tx).await?;
 
             tx.query_drop("SET autocommit=1").await?;
             tx.query_drop("SET unique_checks=1").await?;
@@ -725,6 +1005,42 @@ impl Database for AsyncMySqlDatabase {
         }
     }
 
+    #[tracing::instrument(level = "debug", skip_all, fields(db.system = "mysql", db.operation = "UPDATE"))]
+    async fn set_if_version(
+        &self,
+        record: DbRecord,
+        expected_version: Option<u64>,
+    ) -> core::result::Result<(), StorageError> {
+        self.batch_set_if_versions(
+            vec![],
+            vec![(record, expected_version)],
+            akd::storage::DbSetState::General,
+        )
+        .await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all, fields(db.system = "mysql", db.operation = "UPDATE", num_records = records.len() + conditional.len()))]
+    async fn batch_set_if_versions(
+        &self,
+        records: Vec<DbRecord>,
+        conditional: Vec<(DbRecord, Option<u64>)>,
+        _state: akd::storage::DbSetState,
+    ) -> core::result::Result<(), StorageError> {
+        match self
+            .internal_batch_set_if_versions(records, conditional)
+            .await
+        {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(StorageError::Conflict(
+                "A conditional write found a stored record at an unexpected version".to_string(),
+            )),
+            Err(error) => {
+                error!("MySQL error {}", error);
+                Err(StorageError::Other(format!("MySQL Error {error}")))
+            }
+        }
+    }
+
     /// Retrieve a stored record from the data layer
     async fn get<St: Storable>(
         &self,
@@ -734,6 +1050,7 @@ impl Database for AsyncMySqlDatabase {
     }
 
     /// Retrieve a batch of records by id
+    #[tracing::instrument(level = "debug", skip_all, fields(db.system = "mysql", db.operation = "SELECT", num_records = ids.len()))]
     async fn batch_get<St: Storable>(
         &self,
         ids: &[St::StorageKey],
@@ -859,6 +1176,7 @@ impl Database for AsyncMySqlDatabase {
         Ok(map)
     }
 
+    #[tracing::instrument(level = "debug", skip_all, fields(db.system = "mysql", db.operation = "SELECT"))]
     async fn get_user_data(
         &self,
         username: &AkdLabel,
@@ -872,7 +1190,7 @@ impl Database for AsyncMySqlDatabase {
         let result = async {
             let mut conn = self.get_connection().await?;
             let statement_text =
-                "SELECT `username`, `epoch`, `version`, `node_label_val`, `node_label_len`, `data` FROM `"
+                "SELECT `username`, `epoch`, `version`, `node_label_val`, `node_label_len`, `data`, `retention_class` FROM `"
                     .to_owned()
                     + TABLE_USER
                     + "` WHERE `username` = :the_user";
@@ -888,6 +1206,7 @@ impl Database for AsyncMySqlDatabase {
                         Some(node_label_val),
                         Some(node_label_len),
                         Some(data),
+                        Some(retention_class),
                     ) = (
                         row.take(0),
                         row.take(1),
@@ -895,6 +1214,7 @@ impl Database for AsyncMySqlDatabase {
                         row.take::<Vec<u8>, _>(3),
                         row.take(4),
                         row.take(5),
+                        row.take::<Option<u8>, _>(6),
                     ) {
                         // explicitly check the array length for safety
                         let r: core::result::Result<[u8; 32], _> = node_label_val.try_into();
@@ -908,6 +1228,7 @@ impl Database for AsyncMySqlDatabase {
                                 },
                                 value: AkdValue(data),
                                 username: AkdLabel(username),
+                                retention_class: retention_class.and_then(RetentionClass::from_u8),
                             });
                         }
                     }
@@ -931,6 +1252,7 @@ impl Database for AsyncMySqlDatabase {
         }
     }
 
+    #[tracing::instrument(level = "debug", skip_all, fields(db.system = "mysql", db.operation = "SELECT"))]
     async fn get_user_state(
         &self,
         username: &AkdLabel,
@@ -942,7 +1264,7 @@ impl Database for AsyncMySqlDatabase {
         let result = async {
             let mut conn = self.get_connection().await?;
             let mut statement_text =
-                "SELECT `username`, `epoch`, `version`, `node_label_val`, `node_label_len`, `data` FROM `"
+                "SELECT `username`, `epoch`, `version`, `node_label_val`, `node_label_len`, `data`, `retention_class` FROM `"
                     .to_owned()
                     + TABLE_USER
                     + "` WHERE `username` = :the_user";
@@ -978,6 +1300,7 @@ impl Database for AsyncMySqlDatabase {
                         Some(node_label_val),
                         Some(node_label_len),
                         Some(data),
+                        Some(retention_class),
                     ) = (
                         row.take(0),
                         row.take(1),
@@ -985,6 +1308,7 @@ impl Database for AsyncMySqlDatabase {
                         row.take::<Vec<_>, _>(3),
                         row.take(4),
                         row.take(5),
+                        row.take::<Option<u8>, _>(6),
                     ) {
                         // explicitly check the array length for safety
                         let r: core::result::Result<[u8; 32], _> = node_label_val.try_into();
@@ -998,6 +1322,7 @@ impl Database for AsyncMySqlDatabase {
                                 },
                                 value: AkdValue(data),
                                 username: AkdLabel(username),
+                                retention_class: retention_class.and_then(RetentionClass::from_u8),
                             });
                         }
                     }
@@ -1020,6 +1345,7 @@ impl Database for AsyncMySqlDatabase {
         }
     }
 
+    #[tracing::instrument(level = "debug", skip_all, fields(db.system = "mysql", db.operation = "SELECT", num_labels = keys.len()))]
     async fn get_user_state_versions(
         &self,
         keys: &[AkdLabel],
@@ -1208,4 +1534,12 @@ impl Database for AsyncMySqlDatabase {
             }
         }
     }
+
+    async fn close(&self) -> core::result::Result<(), StorageError> {
+        let pool = self.pool.read().await.clone();
+        pool.disconnect().await.map_err(|error| {
+            error!("MySQL error {}", error);
+            StorageError::Connection(format!("MySQL Error {error}"))
+        })
+    }
 }
@@ -12,7 +12,7 @@ use std::convert::TryInto;
 use akd::storage::types::{DbRecord, StorageType};
 use akd::storage::Storable;
 use akd::tree_node::{NodeKey, TreeNodeWithPreviousValue};
-use akd::NodeLabel;
+use akd::{NodeLabel, RetentionClass};
 use mysql_async::prelude::*;
 use mysql_async::*;
 
@@ -21,13 +21,26 @@ type MySqlError = mysql_async::Error;
 pub(crate) const TABLE_AZKS: &str = "azks";
 pub(crate) const TABLE_HISTORY_TREE_NODES: &str = "history";
 pub(crate) const TABLE_USER: &str = "users";
+pub(crate) const TABLE_OPERATIONS: &str = "operations";
+pub(crate) const TABLE_SOFT_DELETIONS: &str = "soft_deletions";
+pub(crate) const TABLE_ANCHOR_RECEIPTS: &str = "anchor_receipts";
+pub(crate) const TABLE_PUBLISH_LEASE: &str = "publish_lease";
+pub(crate) const TABLE_AUDIT_EPOCHS: &str = "audit_epochs";
 pub(crate) const TEMP_IDS_TABLE: &str = "temp_ids_table";
 
 const SELECT_AZKS_DATA: &str = "`epoch`, `num_nodes`";
 const SELECT_HISTORY_TREE_NODE_DATA: &str =
     "`label_len`, `label_val`, `last_epoch`, `least_descendant_ep`, `parent_label_len`, `parent_label_val`, `node_type`, `left_child_len`, `left_child_label_val`, `right_child_len`, `right_child_label_val`, `hash`, `p_last_epoch`, `p_least_descendant_ep`, `p_parent_label_len`, `p_parent_label_val`, `p_node_type`, `p_left_child_len`, `p_left_child_label_val`, `p_right_child_len`, `p_right_child_label_val`, `p_hash`";
 const SELECT_USER_DATA: &str =
-    "`username`, `epoch`, `version`, `node_label_val`, `node_label_len`, `data`";
+    "`username`, `epoch`, `version`, `node_label_val`, `node_label_len`, `data`, `retention_class`";
+const SELECT_OPERATION_DATA: &str =
+    "`sequence`, `kind`, `epoch`, `timestamp_ms`, `identity`, `metadata`, `detail`";
+const SELECT_SOFT_DELETION_DATA: &str = "`username`, `epoch`";
+const SELECT_ANCHOR_RECEIPT_DATA: &str =
+    "`epoch`, `anchor`, `root_hash`, `timestamp_ms`, `receipt`";
+const SELECT_PUBLISH_LEASE_DATA: &str = "`holder`, `token`, `expires_at_ms`";
+const SELECT_AUDIT_EPOCH_DATA: &str =
+    "`index`, `start_epoch`, `start_root_hash`, `end_epoch`, `end_root_hash`, `timestamp_ms`";
 
 pub(crate) trait MySqlStorable {
     fn set_statement(&self) -> String;
@@ -38,6 +51,7 @@ pub(crate) trait MySqlStorable {
 
     fn set_batch_params(items: &[DbRecord]) -> Result<mysql_async::Params>;
 
+    #[allow(dead_code)]
     fn get_statement<St: Storable>() -> String;
 
     fn get_batch_create_temp_table<St: Storable>() -> Option<String>;
@@ -113,7 +127,24 @@ impl MySqlStorable for DbRecord {
                 , `p_right_child_len` = :p_right_child_len
                 , `p_right_child_label_val` = :p_right_child_label_val
                 , `p_hash` = :p_hash"),
-            DbRecord::ValueState(_) => format!("INSERT INTO `{TABLE_USER}` ({SELECT_USER_DATA}) VALUES (:username, :epoch, :version, :node_label_val, :node_label_len, :data)"),
+            DbRecord::ValueState(_) => format!("INSERT INTO `{TABLE_USER}` ({SELECT_USER_DATA}) VALUES (:username, :epoch, :version, :node_label_val, :node_label_len, :data, :retention_class)"),
+            DbRecord::OperationRecord(_) => format!("INSERT INTO `{TABLE_OPERATIONS}` ({SELECT_OPERATION_DATA}) VALUES (:sequence, :kind, :epoch, :timestamp_ms, :identity, :metadata, :detail)"),
+            DbRecord::SoftDeletion(_) => format!("INSERT INTO `{TABLE_SOFT_DELETIONS}` ({SELECT_SOFT_DELETION_DATA}) VALUES (:username, :epoch)
+            ON DUPLICATE KEY UPDATE
+                `epoch` = :epoch"),
+            DbRecord::AnchorReceipt(_) => format!("INSERT INTO `{TABLE_ANCHOR_RECEIPTS}` ({SELECT_ANCHOR_RECEIPT_DATA}) VALUES (:epoch, :anchor, :root_hash, :timestamp_ms, :receipt)
+            ON DUPLICATE KEY UPDATE
+                `root_hash` = :root_hash
+                , `timestamp_ms` = :timestamp_ms
+                , `receipt` = :receipt"),
+            DbRecord::PublishLease(_) => format!("INSERT INTO `{TABLE_PUBLISH_LEASE}` (`key`, {SELECT_PUBLISH_LEASE_DATA})
+            VALUES (:key, :holder, :token, :expires_at_ms)
+            ON DUPLICATE KEY UPDATE
+                `holder` = :holder
+                , `token` = :token
+                , `expires_at_ms` = :expires_at_ms"),
+            // Audit epochs are never rewritten once consolidated
+            DbRecord::AuditEpoch(_) => format!("INSERT INTO `{TABLE_AUDIT_EPOCHS}` ({SELECT_AUDIT_EPOCH_DATA}) VALUES (:index, :start_epoch, :start_root_hash, :end_epoch, :end_root_hash, :timestamp_ms)"),
         }
     }
 
@@ -149,7 +180,22 @@ impl MySqlStorable for DbRecord {
                 "p_hash" => node.previous_node.clone().map(|a| a.hash.0),
             }),
             DbRecord::ValueState(state) => Some(
-                params! { "username" => state.get_id().0, "epoch" => state.epoch, "version" => state.version, "node_label_len" => state.label.label_len, "node_label_val" => state.label.label_val, "data" => state.value.0.clone() },
+                params! { "username" => state.get_id().0, "epoch" => state.epoch, "version" => state.version, "node_label_len" => state.label.label_len, "node_label_val" => state.label.label_val, "data" => state.value.0.clone(), "retention_class" => state.retention_class.map(|class| class as u8) },
+            ),
+            DbRecord::OperationRecord(operation) => Some(
+                params! { "sequence" => operation.sequence, "kind" => operation.kind as u8, "epoch" => operation.epoch, "timestamp_ms" => operation.timestamp_ms, "identity" => operation.identity.clone(), "metadata" => operation.metadata.clone(), "detail" => operation.detail.clone() },
+            ),
+            DbRecord::SoftDeletion(deletion) => Some(
+                params! { "username" => deletion.username.0.clone(), "epoch" => deletion.epoch },
+            ),
+            DbRecord::AnchorReceipt(receipt) => Some(
+                params! { "epoch" => receipt.epoch, "anchor" => receipt.anchor.clone(), "root_hash" => receipt.root_hash.to_vec(), "timestamp_ms" => receipt.timestamp_ms, "receipt" => receipt.receipt.clone() },
+            ),
+            DbRecord::PublishLease(lease) => Some(
+                params! { "key" => 1u8, "holder" => lease.holder.clone(), "token" => lease.token, "expires_at_ms" => lease.expires_at_ms },
+            ),
+            DbRecord::AuditEpoch(audit_epoch) => Some(
+                params! { "index" => audit_epoch.index, "start_epoch" => audit_epoch.start_epoch, "start_root_hash" => audit_epoch.start_root_hash.to_vec(), "end_epoch" => audit_epoch.end_epoch, "end_root_hash" => audit_epoch.end_root_hash.to_vec(), "timestamp_ms" => audit_epoch.timestamp_ms },
             ),
         }
     }
@@ -186,11 +232,29 @@ impl MySqlStorable for DbRecord {
                 }
                 StorageType::ValueState => {
                     parts = format!(
-                        "{parts}(:username{i}, :epoch{i}, :version{i}, :node_label_val{i}, :node_label_len{i}, :data{i})"
+                        "{parts}(:username{i}, :epoch{i}, :version{i}, :node_label_val{i}, :node_label_len{i}, :data{i}, :retention_class{i})"
+                    );
+                }
+                StorageType::OperationRecord => {
+                    parts = format!(
+                        "{parts}(:sequence{i}, :kind{i}, :epoch{i}, :timestamp_ms{i}, :identity{i}, :metadata{i}, :detail{i})"
+                    );
+                }
+                StorageType::SoftDeletion => {
+                    parts = format!("{parts}(:username{i}, :epoch{i})");
+                }
+                StorageType::AnchorReceipt => {
+                    parts = format!(
+                        "{parts}(:epoch{i}, :anchor{i}, :root_hash{i}, :timestamp_ms{i}, :receipt{i})"
+                    );
+                }
+                StorageType::AuditEpoch => {
+                    parts = format!(
+                        "{parts}(:index{i}, :start_epoch{i}, :start_root_hash{i}, :end_epoch{i}, :end_root_hash{i}, :timestamp_ms{i})"
                     );
                 }
                 _ => {
-                    // azks
+                    // azks and the publish lease
                 }
             }
 
@@ -239,7 +303,37 @@ impl MySqlStorable for DbRecord {
                 `data` = new.data
                 , `node_label_val` = new.node_label_val
                 , `node_label_len` = new.node_label_len
-                , `version` = new.version"
+                , `version` = new.version
+                , `retention_class` = new.retention_class"
+            ),
+            // The operations log is append-only, so existing entries are never updated
+            StorageType::OperationRecord => format!(
+                "INSERT INTO `{TABLE_OPERATIONS}` ({SELECT_OPERATION_DATA})
+            VALUES {parts}"
+            ),
+            StorageType::SoftDeletion => format!(
+                "INSERT INTO `{TABLE_SOFT_DELETIONS}` ({SELECT_SOFT_DELETION_DATA})
+            VALUES {parts} as new
+            ON DUPLICATE KEY UPDATE
+                `epoch` = new.epoch"
+            ),
+            StorageType::AnchorReceipt => format!(
+                "INSERT INTO `{TABLE_ANCHOR_RECEIPTS}` ({SELECT_ANCHOR_RECEIPT_DATA})
+            VALUES {parts} as new
+            ON DUPLICATE KEY UPDATE
+                `root_hash` = new.root_hash
+                , `timestamp_ms` = new.timestamp_ms
+                , `receipt` = new.receipt"
+            ),
+            StorageType::PublishLease => format!(
+                "INSERT INTO `{TABLE_PUBLISH_LEASE}` (`key`, {SELECT_PUBLISH_LEASE_DATA})
+            VALUES (:key, :holder, :token, :expires_at_ms) as new
+            ON DUPLICATE KEY UPDATE `holder` = new.holder, `token` = new.token, `expires_at_ms` = new.expires_at_ms"
+            ),
+            // Audit epochs are never rewritten once consolidated
+            StorageType::AuditEpoch => format!(
+                "INSERT INTO `{TABLE_AUDIT_EPOCHS}` ({SELECT_AUDIT_EPOCH_DATA})
+            VALUES {parts}"
             ),
         }
     }
@@ -367,6 +461,86 @@ impl MySqlStorable for DbRecord {
                         Value::from(state.label.label_val),
                     ),
                     (format!("data{idx}"), Value::from(state.value.0.clone())),
+                    (
+                        format!("retention_class{idx}"),
+                        Value::from(state.retention_class.map(|class| class as u8)),
+                    ),
+                ]),
+                DbRecord::OperationRecord(operation) => Ok(vec![
+                    (format!("sequence{idx}"), Value::from(operation.sequence)),
+                    (format!("kind{idx}"), Value::from(operation.kind as u8)),
+                    (format!("epoch{idx}"), Value::from(operation.epoch)),
+                    (
+                        format!("timestamp_ms{idx}"),
+                        Value::from(operation.timestamp_ms),
+                    ),
+                    (
+                        format!("identity{idx}"),
+                        Value::from(operation.identity.clone()),
+                    ),
+                    (
+                        format!("metadata{idx}"),
+                        Value::from(operation.metadata.clone()),
+                    ),
+                    (
+                        format!("detail{idx}"),
+                        Value::from(operation.detail.clone()),
+                    ),
+                ]),
+                DbRecord::SoftDeletion(deletion) => Ok(vec![
+                    (
+                        format!("username{idx}"),
+                        Value::from(deletion.username.0.clone()),
+                    ),
+                    (format!("epoch{idx}"), Value::from(deletion.epoch)),
+                ]),
+                DbRecord::AnchorReceipt(receipt) => Ok(vec![
+                    (format!("epoch{idx}"), Value::from(receipt.epoch)),
+                    (format!("anchor{idx}"), Value::from(receipt.anchor.clone())),
+                    (
+                        format!("root_hash{idx}"),
+                        Value::from(receipt.root_hash.to_vec()),
+                    ),
+                    (
+                        format!("timestamp_ms{idx}"),
+                        Value::from(receipt.timestamp_ms),
+                    ),
+                    (
+                        format!("receipt{idx}"),
+                        Value::from(receipt.receipt.clone()),
+                    ),
+                ]),
+                DbRecord::PublishLease(lease) => Ok(vec![
+                    ("key".to_string(), Value::from(1u8)),
+                    ("holder".to_string(), Value::from(lease.holder.clone())),
+                    ("token".to_string(), Value::from(lease.token)),
+                    (
+                        "expires_at_ms".to_string(),
+                        Value::from(lease.expires_at_ms),
+                    ),
+                ]),
+                DbRecord::AuditEpoch(audit_epoch) => Ok(vec![
+                    (format!("index{idx}"), Value::from(audit_epoch.index)),
+                    (
+                        format!("start_epoch{idx}"),
+                        Value::from(audit_epoch.start_epoch),
+                    ),
+                    (
+                        format!("start_root_hash{idx}"),
+                        Value::from(audit_epoch.start_root_hash.to_vec()),
+                    ),
+                    (
+                        format!("end_epoch{idx}"),
+                        Value::from(audit_epoch.end_epoch),
+                    ),
+                    (
+                        format!("end_root_hash{idx}"),
+                        Value::from(audit_epoch.end_root_hash.to_vec()),
+                    ),
+                    (
+                        format!("timestamp_ms{idx}"),
+                        Value::from(audit_epoch.timestamp_ms),
+                    ),
                 ]),
             })
             .collect::<Result<Vec<_>>>()?
@@ -383,6 +557,21 @@ impl MySqlStorable for DbRecord {
                 format!("SELECT {SELECT_HISTORY_TREE_NODE_DATA} FROM `{TABLE_HISTORY_TREE_NODES}`")
             }
             StorageType::ValueState => format!("SELECT {SELECT_USER_DATA} FROM `{TABLE_USER}`"),
+            StorageType::OperationRecord => {
+                format!("SELECT {SELECT_OPERATION_DATA} FROM `{TABLE_OPERATIONS}`")
+            }
+            StorageType::SoftDeletion => {
+                format!("SELECT {SELECT_SOFT_DELETION_DATA} FROM `{TABLE_SOFT_DELETIONS}`")
+            }
+            StorageType::AnchorReceipt => {
+                format!("SELECT {SELECT_ANCHOR_RECEIPT_DATA} FROM `{TABLE_ANCHOR_RECEIPTS}`")
+            }
+            StorageType::PublishLease => {
+                format!("SELECT {SELECT_PUBLISH_LEASE_DATA} FROM `{TABLE_PUBLISH_LEASE}`")
+            }
+            StorageType::AuditEpoch => {
+                format!("SELECT {SELECT_AUDIT_EPOCH_DATA} FROM `{TABLE_AUDIT_EPOCHS}`")
+            }
         }
     }
 
@@ -403,6 +592,35 @@ impl MySqlStorable for DbRecord {
                     )
                 )
             },
+            StorageType::OperationRecord => {
+                Some(
+                    format!(
+                        "CREATE TEMPORARY TABLE `{TEMP_IDS_TABLE}`(`sequence` BIGINT UNSIGNED NOT NULL, PRIMARY KEY(`sequence`))"
+                    )
+                )
+            },
+            StorageType::SoftDeletion => {
+                Some(
+                    format!(
+                        "CREATE TEMPORARY TABLE `{TEMP_IDS_TABLE}`(`username` VARCHAR(256) NOT NULL, PRIMARY KEY(`username`))"
+                    )
+                )
+            },
+            StorageType::AnchorReceipt => {
+                Some(
+                    format!(
+                        "CREATE TEMPORARY TABLE `{TEMP_IDS_TABLE}`(`epoch` BIGINT UNSIGNED NOT NULL, `anchor` VARCHAR(256) NOT NULL, PRIMARY KEY(`epoch`, `anchor`))"
+                    )
+                )
+            },
+            StorageType::PublishLease => None,
+            StorageType::AuditEpoch => {
+                Some(
+                    format!(
+                        "CREATE TEMPORARY TABLE `{TEMP_IDS_TABLE}`(`index` BIGINT UNSIGNED NOT NULL, PRIMARY KEY(`index`))"
+                    )
+                )
+            },
         }
     }
 
@@ -415,6 +633,19 @@ impl MySqlStorable for DbRecord {
             StorageType::ValueState => {
                 format!("INSERT INTO `{TEMP_IDS_TABLE}` (`username`, `epoch`) VALUES ")
             }
+            StorageType::OperationRecord => {
+                format!("INSERT INTO `{TEMP_IDS_TABLE}` (`sequence`) VALUES ")
+            }
+            StorageType::SoftDeletion => {
+                format!("INSERT INTO `{TEMP_IDS_TABLE}` (`username`) VALUES ")
+            }
+            StorageType::AnchorReceipt => {
+                format!("INSERT INTO `{TEMP_IDS_TABLE}` (`epoch`, `anchor`) VALUES ")
+            }
+            StorageType::PublishLease => "".to_string(),
+            StorageType::AuditEpoch => {
+                format!("INSERT INTO `{TEMP_IDS_TABLE}` (`index`) VALUES ")
+            }
         };
         if let Some(item_count) = num_items {
             for i in 0..item_count {
@@ -426,6 +657,19 @@ impl MySqlStorable for DbRecord {
                     StorageType::ValueState => {
                         format!("(:username{i}, :epoch{i})")
                     }
+                    StorageType::OperationRecord => {
+                        format!("(:sequence{i})")
+                    }
+                    StorageType::SoftDeletion => {
+                        format!("(:username{i})")
+                    }
+                    StorageType::AnchorReceipt => {
+                        format!("(:epoch{i}, :anchor{i})")
+                    }
+                    StorageType::PublishLease => String::from(""),
+                    StorageType::AuditEpoch => {
+                        format!("(:index{i})")
+                    }
                 };
                 statement = format!("{statement}{append}");
 
@@ -439,6 +683,11 @@ impl MySqlStorable for DbRecord {
                 StorageType::Azks => "",
                 StorageType::TreeNode => "(:label_len, :label_val)",
                 StorageType::ValueState => "(:username, :epoch)",
+                StorageType::OperationRecord => "(:sequence)",
+                StorageType::SoftDeletion => "(:username)",
+                StorageType::AnchorReceipt => "(:epoch, :anchor)",
+                StorageType::PublishLease => "",
+                StorageType::AuditEpoch => "(:index)",
             };
         }
         statement
@@ -488,12 +737,69 @@ impl MySqlStorable for DbRecord {
                         , a.`node_label_val`
                         , a.`node_label_len`
                         , a.`data`
+                        , a.`retention_class`
                     FROM `{TABLE_USER}` a
                     INNER JOIN {TEMP_IDS_TABLE} ids
                         ON ids.`username` = a.`username`
                         AND ids.`epoch` = a.`epoch`"
                 )
             }
+            StorageType::OperationRecord => {
+                format!(
+                    "SELECT
+                        a.`sequence`
+                        , a.`kind`
+                        , a.`epoch`
+                        , a.`timestamp_ms`
+                        , a.`identity`
+                        , a.`metadata`
+                        , a.`detail`
+                    FROM `{TABLE_OPERATIONS}` a
+                    INNER JOIN {TEMP_IDS_TABLE} ids
+                        ON ids.`sequence` = a.`sequence`"
+                )
+            }
+            StorageType::SoftDeletion => {
+                format!(
+                    "SELECT
+                        a.`username`
+                        , a.`epoch`
+                    FROM `{TABLE_SOFT_DELETIONS}` a
+                    INNER JOIN {TEMP_IDS_TABLE} ids
+                        ON ids.`username` = a.`username`"
+                )
+            }
+            StorageType::AnchorReceipt => {
+                format!(
+                    "SELECT
+                        a.`epoch`
+                        , a.`anchor`
+                        , a.`root_hash`
+                        , a.`timestamp_ms`
+                        , a.`receipt`
+                    FROM `{TABLE_ANCHOR_RECEIPTS}` a
+                    INNER JOIN {TEMP_IDS_TABLE} ids
+                        ON ids.`epoch` = a.`epoch`
+                        AND ids.`anchor` = a.`anchor`"
+                )
+            }
+            StorageType::PublishLease => {
+                format!("SELECT {SELECT_PUBLISH_LEASE_DATA} FROM `{TABLE_PUBLISH_LEASE}` LIMIT 1")
+            }
+            StorageType::AuditEpoch => {
+                format!(
+                    "SELECT
+                        a.`index`
+                        , a.`start_epoch`
+                        , a.`start_root_hash`
+                        , a.`end_epoch`
+                        , a.`end_root_hash`
+                        , a.`timestamp_ms`
+                    FROM `{TABLE_AUDIT_EPOCHS}` a
+                    INNER JOIN {TEMP_IDS_TABLE} ids
+                        ON ids.`index` = a.`index`"
+                )
+            }
         }
     }
 
@@ -508,6 +814,21 @@ impl MySqlStorable for DbRecord {
             StorageType::ValueState => format!(
                 "SELECT {SELECT_USER_DATA} FROM `{TABLE_USER}` WHERE `username` = :username AND `epoch` = :epoch"
             ),
+            StorageType::OperationRecord => format!(
+                "SELECT {SELECT_OPERATION_DATA} FROM `{TABLE_OPERATIONS}` WHERE `sequence` = :sequence"
+            ),
+            StorageType::SoftDeletion => format!(
+                "SELECT {SELECT_SOFT_DELETION_DATA} FROM `{TABLE_SOFT_DELETIONS}` WHERE `username` = :username"
+            ),
+            StorageType::AnchorReceipt => format!(
+                "SELECT {SELECT_ANCHOR_RECEIPT_DATA} FROM `{TABLE_ANCHOR_RECEIPTS}` WHERE `epoch` = :epoch AND `anchor` = :anchor"
+            ),
+            StorageType::PublishLease => {
+                format!("SELECT {SELECT_PUBLISH_LEASE_DATA} FROM `{TABLE_PUBLISH_LEASE}` LIMIT 1")
+            }
+            StorageType::AuditEpoch => format!(
+                "SELECT {SELECT_AUDIT_EPOCH_DATA} FROM `{TABLE_AUDIT_EPOCHS}` WHERE `index` = :index"
+            ),
         }
     }
 
@@ -536,6 +857,48 @@ impl MySqlStorable for DbRecord {
                     None
                 }
             }
+            StorageType::OperationRecord => {
+                let bin = St::get_full_binary_key_id(key);
+                if let Ok(back) = akd::storage::types::OperationRecord::key_from_full_binary(&bin) {
+                    Some(params! {
+                        "sequence" => back
+                    })
+                } else {
+                    None
+                }
+            }
+            StorageType::SoftDeletion => {
+                let bin = St::get_full_binary_key_id(key);
+                if let Ok(back) = akd::storage::types::SoftDeletion::key_from_full_binary(&bin) {
+                    Some(params! {
+                        "username" => back.0
+                    })
+                } else {
+                    None
+                }
+            }
+            StorageType::AnchorReceipt => {
+                let bin = St::get_full_binary_key_id(key);
+                if let Ok(back) = akd::storage::types::AnchorReceipt::key_from_full_binary(&bin) {
+                    Some(params! {
+                        "epoch" => back.0,
+                        "anchor" => back.1
+                    })
+                } else {
+                    None
+                }
+            }
+            StorageType::PublishLease => None,
+            StorageType::AuditEpoch => {
+                let bin = St::get_full_binary_key_id(key);
+                if let Ok(back) = akd::storage::types::AuditEpoch::key_from_full_binary(&bin) {
+                    Some(params! {
+                        "index" => back
+                    })
+                } else {
+                    None
+                }
+            }
         }
     }
 
@@ -580,6 +943,71 @@ impl MySqlStorable for DbRecord {
                     .collect::<Vec<_>>();
                 Some(mysql_async::Params::from(pvec))
             }
+            StorageType::OperationRecord => {
+                let pvec = keys
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, key)| {
+                        let bin = St::get_full_binary_key_id(key);
+                        // Since these are constructed from a safe key, they should never fail
+                        // so we'll leave the unwrap to simplify
+                        let back: u64 =
+                            akd::storage::types::OperationRecord::key_from_full_binary(&bin)
+                                .unwrap();
+                        (format!("sequence{idx}"), Value::from(back))
+                    })
+                    .collect::<Vec<_>>();
+                Some(mysql_async::Params::from(pvec))
+            }
+            StorageType::SoftDeletion => {
+                let pvec = keys
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, key)| {
+                        let bin = St::get_full_binary_key_id(key);
+                        // Since these are constructed from a safe key, they should never fail
+                        // so we'll leave the unwrap to simplify
+                        let back: akd::AkdLabel =
+                            akd::storage::types::SoftDeletion::key_from_full_binary(&bin).unwrap();
+                        (format!("username{idx}"), Value::from(back.0))
+                    })
+                    .collect::<Vec<_>>();
+                Some(mysql_async::Params::from(pvec))
+            }
+            StorageType::AnchorReceipt => {
+                let pvec = keys
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(idx, key)| {
+                        let bin = St::get_full_binary_key_id(key);
+                        // Since these are constructed from a safe key, they should never fail
+                        // so we'll leave the unwrap to simplify
+                        let back =
+                            akd::storage::types::AnchorReceipt::key_from_full_binary(&bin).unwrap();
+                        vec![
+                            (format!("epoch{idx}"), Value::from(back.0)),
+                            (format!("anchor{idx}"), Value::from(back.1)),
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+                Some(mysql_async::Params::from(pvec))
+            }
+            StorageType::PublishLease => None,
+            StorageType::AuditEpoch => {
+                let pvec = keys
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, key)| {
+                        let bin = St::get_full_binary_key_id(key);
+                        // Since these are constructed from a safe key, they should never fail
+                        // so we'll leave the unwrap to simplify
+                        let back: u64 =
+                            akd::storage::types::AuditEpoch::key_from_full_binary(&bin).unwrap();
+                        (format!("index{idx}"), Value::from(back))
+                    })
+                    .collect::<Vec<_>>();
+                Some(mysql_async::Params::from(pvec))
+            }
         }
     }
 
@@ -741,7 +1169,8 @@ impl MySqlStorable for DbRecord {
                 }
             }
             StorageType::ValueState => {
-                // `username`, `epoch`, `version`, `node_label_val`, `node_label_len`, `data`
+                // `username`, `epoch`, `version`, `node_label_val`, `node_label_len`, `data`,
+                // `retention_class`
                 if let (
                     Some(Ok(username)),
                     Some(Ok(epoch)),
@@ -749,6 +1178,7 @@ impl MySqlStorable for DbRecord {
                     Some(Ok(node_label_val)),
                     Some(Ok(node_label_len)),
                     Some(Ok(data)),
+                    Some(Ok(retention_class)),
                 ) = (
                     row.take_opt(0),
                     row.take_opt(1),
@@ -756,8 +1186,20 @@ impl MySqlStorable for DbRecord {
                     row.take_opt(3),
                     row.take_opt(4),
                     row.take_opt(5),
+                    row.take_opt::<Option<u8>, _>(6),
                 ) {
                     let node_label_val_vec: Vec<u8> = node_label_val;
+                    let retention_class = retention_class
+                        .map(|class| {
+                            RetentionClass::from_u8(class).ok_or_else(|| {
+                                MySqlError::from(mysql_async::ServerError {
+                                    state: "".to_string(),
+                                    code: 0,
+                                    message: format!("Unknown retention class {class}"),
+                                })
+                            })
+                        })
+                        .transpose()?;
                     let state = DbRecord::build_user_state(
                         username,
                         data,
@@ -765,10 +1207,132 @@ impl MySqlStorable for DbRecord {
                         node_label_len,
                         node_label_val_vec.try_into().map_err(|_| cast_err())?,
                         epoch,
+                        retention_class,
                     );
                     return Ok(DbRecord::ValueState(state));
                 }
             }
+            StorageType::OperationRecord => {
+                // `sequence`, `kind`, `epoch`, `timestamp_ms`, `identity`, `metadata`, `detail`
+                if let (
+                    Some(Ok(sequence)),
+                    Some(Ok(kind)),
+                    Some(Ok(epoch)),
+                    Some(Ok(timestamp_ms)),
+                    Some(Ok(identity)),
+                    Some(Ok(metadata)),
+                    Some(Ok(detail)),
+                ) = (
+                    row.take_opt(0),
+                    row.take_opt(1),
+                    row.take_opt(2),
+                    row.take_opt(3),
+                    row.take_opt(4),
+                    row.take_opt(5),
+                    row.take_opt(6),
+                ) {
+                    let operation = DbRecord::build_operation_record(
+                        sequence,
+                        kind,
+                        epoch,
+                        timestamp_ms,
+                        identity,
+                        metadata,
+                        detail,
+                    )
+                    .map_err(|message| {
+                        MySqlError::from(mysql_async::ServerError {
+                            state: "".to_string(),
+                            code: 0,
+                            message,
+                        })
+                    })?;
+                    return Ok(DbRecord::OperationRecord(operation));
+                }
+            }
+            StorageType::SoftDeletion => {
+                // `username`, `epoch`
+                if let (Some(Ok(username)), Some(Ok(epoch))) = (row.take_opt(0), row.take_opt(1)) {
+                    let deletion = akd::storage::types::SoftDeletion {
+                        username: akd::AkdLabel(username),
+                        epoch,
+                    };
+                    return Ok(DbRecord::SoftDeletion(deletion));
+                }
+            }
+            StorageType::AnchorReceipt => {
+                // `epoch`, `anchor`, `root_hash`, `timestamp_ms`, `receipt`
+                if let (
+                    Some(Ok(epoch)),
+                    Some(Ok(anchor)),
+                    Some(Ok(root_hash)),
+                    Some(Ok(timestamp_ms)),
+                    Some(Ok(receipt)),
+                ) = (
+                    row.take_opt(0),
+                    row.take_opt(1),
+                    row.take_opt::<Vec<u8>, _>(2),
+                    row.take_opt(3),
+                    row.take_opt(4),
+                ) {
+                    if let Ok(root_hash) = root_hash.try_into() {
+                        let receipt = akd::storage::types::AnchorReceipt {
+                            epoch,
+                            anchor,
+                            root_hash,
+                            timestamp_ms,
+                            receipt,
+                        };
+                        return Ok(DbRecord::AnchorReceipt(receipt));
+                    }
+                }
+            }
+            StorageType::PublishLease => {
+                // `holder`, `token`, `expires_at_ms`
+                if let (Some(Ok(holder)), Some(Ok(token)), Some(Ok(expires_at_ms))) =
+                    (row.take_opt(0), row.take_opt(1), row.take_opt(2))
+                {
+                    let lease = akd::storage::types::PublishLease {
+                        holder,
+                        token,
+                        expires_at_ms,
+                    };
+                    return Ok(DbRecord::PublishLease(lease));
+                }
+            }
+            StorageType::AuditEpoch => {
+                // `index`, `start_epoch`, `start_root_hash`, `end_epoch`, `end_root_hash`,
+                // `timestamp_ms`
+                if let (
+                    Some(Ok(index)),
+                    Some(Ok(start_epoch)),
+                    Some(Ok(start_root_hash)),
+                    Some(Ok(end_epoch)),
+                    Some(Ok(end_root_hash)),
+                    Some(Ok(timestamp_ms)),
+                ) = (
+                    row.take_opt(0),
+                    row.take_opt(1),
+                    row.take_opt::<Vec<u8>, _>(2),
+                    row.take_opt(3),
+                    row.take_opt::<Vec<u8>, _>(4),
+                    row.take_opt(5),
+                ) {
+                    if let (Ok(start_root_hash), Ok(end_root_hash)) =
+                        (start_root_hash.try_into(), end_root_hash.try_into())
+                    {
+                        let audit_epoch = akd::storage::types::AuditEpoch {
+                            index,
+                            start_epoch,
+                            start_root_hash,
+                            end_epoch,
+                            end_root_hash,
+                            timestamp_ms,
+                        };
+                        return Ok(DbRecord::AuditEpoch(audit_epoch));
+                    }
+                }
+            }
         }
         // fallback
         let err = MySqlError::Driver(mysql_async::DriverError::FromRow { row: row.clone() });
@@ -5,10 +5,8 @@
 // License, Version 2.0 found in the LICENSE-APACHE file in the root directory
 // of this source tree. You may select, at your option, one of the above-listed licenses.
 
-use crate::{
-    mysql_demo::tests::test_util::{directory_test_suite, log_init},
-    test_config_serial,
-};
+use crate::{mysql_demo::tests::test_util::log_init, test_config_serial};
+use akd::test_utils::directory_test_suite;
 use akd::{ecvrf::HardCodedAkdVRF, storage::StorageManager, Configuration};
 use log::info;
 
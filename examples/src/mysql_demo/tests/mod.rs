@@ -8,4 +8,6 @@
 mod memory_tests;
 mod mysql_db_tests;
 mod mysql_tests;
+#[cfg(feature = "soak_test")]
+mod soak_tests;
 mod test_util;
@@ -6,11 +6,10 @@
 // of this source tree. You may select, at your option, one of the above-listed licenses.
 
 use crate::mysql_demo::mysql::AsyncMySqlDatabase;
-use crate::mysql_demo::tests::test_util::{
-    directory_test_suite, log_init, test_lookups as test_lookups_util,
-};
+use crate::mysql_demo::tests::test_util::log_init;
 use crate::test_config_serial;
 use akd::storage::StorageManager;
+use akd::test_utils::{directory_test_suite, lookup_test_suite};
 use akd::{ecvrf::HardCodedAkdVRF, Configuration};
 use log::{error, info, warn};
 
@@ -54,7 +53,7 @@ async fn test_directory_operations<TC: Configuration>() {
         let storage_manager = StorageManager::new_no_cache(mysql_db.clone());
         directory_test_suite::<TC, _, HardCodedAkdVRF>(&storage_manager, 50, &vrf).await;
 
-        storage_manager.log_metrics(log::Level::Trace).await;
+        storage_manager.log_metrics(tracing::Level::TRACE).await;
 
         // clean the test infra
         if let Err(mysql_async::Error::Server(error)) = storage_manager.get_db().drop_tables().await
@@ -111,7 +110,7 @@ async fn test_directory_operations_with_caching<TC: Configuration>() {
         let storage_manager = StorageManager::new(mysql_db.clone(), None, None, None);
         directory_test_suite::<TC, _, HardCodedAkdVRF>(&storage_manager, 50, &vrf).await;
 
-        storage_manager.log_metrics(log::Level::Trace).await;
+        storage_manager.log_metrics(tracing::Level::TRACE).await;
 
         // clean the test infra
         if let Err(mysql_async::Error::Server(error)) = storage_manager.get_db().drop_tables().await
@@ -167,7 +166,7 @@ async fn test_lookups<TC: Configuration>() {
         let vrf = HardCodedAkdVRF {};
         let storage_manager = StorageManager::new(mysql_db, None, None, None);
 
-        test_lookups_util::<TC, _, HardCodedAkdVRF>(&storage_manager, &vrf, 50, 5, 100).await;
+        lookup_test_suite::<TC, _, HardCodedAkdVRF>(&storage_manager, &vrf, 50, 5, 100).await;
 
         // clean the test infra
         if let Err(mysql_async::Error::Server(error)) = storage_manager.get_db().drop_tables().await
@@ -0,0 +1,266 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A large-scale soak test against the MySQL backend, which drives a realistic number of labels
+//! through many epochs to validate release candidates. It is gated behind the `soak_test`
+//! feature and requires the MySQL test container:
+//!
+//!   cargo test -p examples --release --features soak_test soak
+//!
+//! The scale and the memory ceiling can be adjusted with the `AKD_SOAK_*` environment variables
+//! (see [SoakParams::from_env]).
+
+use crate::mysql_demo::mysql::AsyncMySqlDatabase;
+use crate::mysql_demo::tests::test_util::log_init;
+use crate::test_config_serial;
+use akd::client::{key_history_verify, lookup_verify};
+use akd::ecvrf::HardCodedAkdVRF;
+use akd::storage::StorageManager;
+use akd::test_utils::test_rng;
+use akd::{
+    AkdLabel, AkdValue, Configuration, Digest, Directory, EpochHash, HistoryParams,
+    HistoryVerificationParams,
+};
+use log::{error, info};
+use rand::Rng;
+use std::collections::HashSet;
+use std::time::Instant;
+
+/// The scale of the soak test
+#[derive(Debug)]
+struct SoakParams {
+    /// The number of distinct labels, which are registered evenly over the epochs
+    num_labels: u64,
+    num_epochs: u64,
+    /// The number of labels registered in earlier epochs which are updated in each epoch
+    updates_per_epoch: u64,
+    /// The number of lookups which are verified after each epoch
+    lookups_per_epoch: usize,
+    /// The number of epochs covered by each audit
+    audit_interval: u64,
+    /// The size limit of the storage manager's cache
+    cache_limit_bytes: usize,
+    /// The ceiling on the resident memory of the test process
+    max_rss_bytes: u64,
+}
+
+impl SoakParams {
+    fn from_env() -> Self {
+        let var = |name: &str, default: u64| match std::env::var(name) {
+            Ok(value) => value
+                .parse()
+                .unwrap_or_else(|_| panic!("{name} is not an integer: {value}")),
+            Err(_) => default,
+        };
+        Self {
+            num_labels: var("AKD_SOAK_LABELS", 1_000_000),
+            num_epochs: var("AKD_SOAK_EPOCHS", 1_000),
+            updates_per_epoch: var("AKD_SOAK_UPDATES_PER_EPOCH", 100),
+            lookups_per_epoch: var("AKD_SOAK_LOOKUPS_PER_EPOCH", 5) as usize,
+            audit_interval: var("AKD_SOAK_AUDIT_INTERVAL", 50),
+            cache_limit_bytes: var("AKD_SOAK_CACHE_MB", 1024) as usize * 1024 * 1024,
+            max_rss_bytes: var("AKD_SOAK_MAX_RSS_MB", 8192) * 1024 * 1024,
+        }
+    }
+}
+
+/// The resident memory of this process, if it can be read (on Linux)
+fn resident_memory_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes = line.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Up to `amount` distinct indices of the first `len` labels
+fn sample(rng: &mut impl Rng, len: u64, amount: usize) -> HashSet<u64> {
+    rand::seq::index::sample(rng, len as usize, amount.min(len as usize))
+        .into_iter()
+        .map(|i| i as u64)
+        .collect()
+}
+
+fn label(i: u64) -> AkdLabel {
+    AkdLabel::from(format!("soak-user-{i}").as_str())
+}
+
+fn value(i: u64, epoch: u64) -> AkdValue {
+    AkdValue::from(format!("soak-user-{i} at epoch {epoch}").as_str())
+}
+
+test_config_serial!(test_soak);
+async fn test_soak<TC: Configuration>() {
+    log_init(log::Level::Info);
+    let params = SoakParams::from_env();
+    info!("\n\n******** Starting MySQL Soak Test {params:?} ********\n\n");
+
+    assert!(
+        AsyncMySqlDatabase::test_guard(),
+        "The soak test requires the MySQL test container to be running"
+    );
+    if let Err(error) = AsyncMySqlDatabase::create_test_db(
+        "localhost",
+        Option::from("root"),
+        Option::from("example"),
+        Option::from(8001),
+    )
+    .await
+    {
+        panic!("Error creating test database: {}", error);
+    }
+    let mysql_db = AsyncMySqlDatabase::new(
+        "localhost",
+        "test_db",
+        Option::from("root"),
+        Option::from("example"),
+        Option::from(8001),
+        200,
+    )
+    .await
+    .expect("Failed to create async mysql db");
+    if let Err(error) = mysql_db.delete_data().await {
+        error!("Error cleaning mysql prior to soak test: {}", error);
+    }
+
+    let storage_manager = StorageManager::new(mysql_db, None, Some(params.cache_limit_bytes), None);
+    let dir = Directory::<TC, _, _>::new(storage_manager.clone(), HardCodedAkdVRF {})
+        .await
+        .expect("Error initializing directory");
+    let vrf_pk = dir.get_public_key().await.unwrap();
+    let mut rng = test_rng();
+
+    // the epoch of the latest update of each label, or 0 if it is not registered yet
+    let mut latest_epochs = vec![0u64; params.num_labels as usize];
+    let mut num_registered = 0u64;
+    let mut root_hashes: Vec<Digest> = vec![];
+    let mut seen_root_hashes = HashSet::new();
+    let mut last_audited_epoch = 1;
+    let labels_per_epoch = params.num_labels.div_ceil(params.num_epochs);
+    let start = Instant::now();
+
+    for epoch in 1..=params.num_epochs {
+        // updates of labels registered in earlier epochs, followed by new registrations
+        let mut indices = sample(&mut rng, num_registered, params.updates_per_epoch as usize);
+        let num_new = labels_per_epoch.min(params.num_labels - num_registered);
+        indices.extend(num_registered..num_registered + num_new);
+        num_registered += num_new;
+        let updates = indices
+            .iter()
+            .map(|&i| (label(i), value(i, epoch)))
+            .collect::<Vec<_>>();
+        for &i in &indices {
+            latest_epochs[i as usize] = epoch;
+        }
+
+        let EpochHash(published_epoch, root_hash) = dir
+            .publish(updates)
+            .await
+            .unwrap_or_else(|error| panic!("Error publishing epoch {epoch}: {error:?}"));
+
+        // the root evolves monotonically: one epoch at a time, to a root hash never seen before
+        assert_eq!(epoch, published_epoch, "Epochs were skipped or repeated");
+        assert!(
+            seen_root_hashes.insert(root_hash),
+            "The root hash of epoch {epoch} repeats an earlier root hash"
+        );
+        assert_eq!(
+            EpochHash(epoch, root_hash),
+            dir.get_epoch_hash().await.unwrap()
+        );
+        root_hashes.push(root_hash);
+
+        for i in sample(&mut rng, num_registered, params.lookups_per_epoch) {
+            let (proof, epoch_hash) = dir.lookup(label(i)).await.unwrap();
+            let result = lookup_verify::<TC>(
+                vrf_pk.as_bytes(),
+                epoch_hash.hash(),
+                epoch_hash.epoch(),
+                label(i),
+                proof,
+            )
+            .unwrap_or_else(|error| panic!("Lookup of label {i} failed to verify: {error:?}"));
+            assert_eq!(value(i, latest_epochs[i as usize]), result.value);
+        }
+
+        if epoch - last_audited_epoch >= params.audit_interval || epoch == params.num_epochs {
+            audit::<TC, _>(&dir, &root_hashes, last_audited_epoch, epoch).await;
+            last_audited_epoch = epoch;
+        }
+
+        if let Some(rss) = resident_memory_bytes() {
+            assert!(
+                rss <= params.max_rss_bytes,
+                "Resident memory of {} MiB exceeds the ceiling of {} MiB at epoch {epoch}",
+                rss / (1024 * 1024),
+                params.max_rss_bytes / (1024 * 1024)
+            );
+        }
+        if epoch % 10 == 0 {
+            info!(
+                "Published epoch {epoch} ({num_registered} labels registered) after {}s, \
+                 resident memory {} MiB",
+                start.elapsed().as_secs(),
+                resident_memory_bytes().unwrap_or_default() / (1024 * 1024)
+            );
+        }
+    }
+
+    // the full history of the most updated of a sample of labels
+    let i = sample(&mut rng, num_registered, 100)
+        .into_iter()
+        .max_by_key(|&i| latest_epochs[i as usize])
+        .unwrap();
+    let (proof, epoch_hash) = dir
+        .key_history(&label(i), HistoryParams::default())
+        .await
+        .unwrap();
+    key_history_verify::<TC>(
+        vrf_pk.as_bytes(),
+        epoch_hash.hash(),
+        epoch_hash.epoch(),
+        label(i),
+        proof,
+        HistoryVerificationParams::default(),
+    )
+    .unwrap_or_else(|error| panic!("History of label {i} failed to verify: {error:?}"));
+
+    storage_manager.log_metrics(tracing::Level::INFO).await;
+    if let Err(mysql_async::Error::Server(error)) = storage_manager.get_db().drop_tables().await {
+        error!(
+            "ERROR: Failed to clean MySQL test database with error {}",
+            error
+        );
+    }
+    info!(
+        "\n\n******** Completed MySQL Soak Test in {}s ********\n\n",
+        start.elapsed().as_secs()
+    );
+}
+
+/// Audits the epochs in the given range (inclusive), in a single multi-epoch proof
+async fn audit<TC: Configuration, S: akd::storage::Database + 'static>(
+    dir: &Directory<TC, S, HardCodedAkdVRF>,
+    root_hashes: &[Digest],
+    start_epoch: u64,
+    end_epoch: u64,
+) {
+    if start_epoch == end_epoch {
+        return;
+    }
+    let proof = dir
+        .audit(start_epoch, end_epoch)
+        .await
+        .unwrap_or_else(|error| {
+            panic!("Error auditing epochs {start_epoch}..{end_epoch}: {error:?}")
+        });
+    let hashes = root_hashes[start_epoch as usize - 1..end_epoch as usize].to_vec();
+    akd::auditor::audit_verify::<TC>(hashes, proof)
+        .await
+        .unwrap_or_else(|error| {
+            panic!("Audit of epochs {start_epoch}..{end_epoch} failed to verify: {error:?}")
+        });
+}
@@ -0,0 +1,273 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A benchmark-comparison tool which runs a standardized publish / lookup / verify
+//! workload against the in-memory database, records the results as a JSON baseline,
+//! and fails when the results regress past a configurable threshold relative to a
+//! previously recorded baseline. Example command:
+//!
+//!   cargo run -p examples --release --features perf_regression -- perf-regression \
+//!     --baseline perf_baseline.json \
+//!     --output perf_results.json \
+//!     --threshold 15
+//!
+
+#[cfg(test)]
+mod tests;
+
+use akd::ecvrf::HardCodedAkdVRF;
+use akd::storage::memory::AsyncInMemoryDatabase;
+use akd::storage::StorageManager;
+use akd::{AkdLabel, AkdValue, Directory, NamedConfiguration};
+use anyhow::{anyhow, bail, Result};
+use clap::Parser;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Instant;
+
+#[derive(Parser, Debug, Clone)]
+pub(crate) struct CliArgs {
+    /// The number of users which are published in each epoch of the workload
+    #[clap(long = "num_users", default_value = "1000")]
+    num_users: usize,
+    /// The number of epochs which are published in the workload
+    #[clap(long = "num_epochs", default_value = "5")]
+    num_epochs: u64,
+    /// The number of lookups which are generated and verified after the final epoch
+    #[clap(long = "num_lookups", default_value = "100")]
+    num_lookups: usize,
+    /// A previously recorded baseline to compare the results against
+    #[clap(long = "baseline")]
+    baseline: Option<PathBuf>,
+    /// Where to write the results of this run, which can be used as a future baseline
+    #[clap(long = "output")]
+    output: Option<PathBuf>,
+    /// The maximum allowed slowdown (in percent) of any measurement relative to the baseline
+    #[clap(long = "threshold", default_value = "10")]
+    threshold: f64,
+}
+
+/// The parameters of the standardized workload. Results are only comparable
+/// when they were recorded with the same parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct WorkloadParams {
+    pub num_users: usize,
+    pub num_epochs: u64,
+    pub num_lookups: usize,
+}
+
+/// The measurements of a single run of the workload for one configuration.
+/// All times are averages per operation, in milliseconds.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct WorkloadResult {
+    pub configuration: String,
+    pub publish_ms: f64,
+    pub lookup_ms: f64,
+    pub verify_ms: f64,
+}
+
+impl WorkloadResult {
+    fn measurements(&self) -> [(&'static str, f64); 3] {
+        [
+            ("publish", self.publish_ms),
+            ("lookup", self.lookup_ms),
+            ("verify", self.verify_ms),
+        ]
+    }
+}
+
+/// The contents of a baseline file
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Baseline {
+    pub params: WorkloadParams,
+    pub results: Vec<WorkloadResult>,
+}
+
+/// A single measurement which exceeded the regression threshold
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Regression {
+    pub configuration: String,
+    pub measurement: &'static str,
+    pub baseline_ms: f64,
+    pub current_ms: f64,
+}
+
+impl Regression {
+    fn slowdown_percent(&self) -> f64 {
+        slowdown_percent(self.baseline_ms, self.current_ms)
+            .expect("Regressions are only reported against a positive baseline")
+    }
+}
+
+/// The slowdown (in percent) of a measurement relative to its baseline, or [None] when the
+/// baseline is not positive, as there is then nothing to compare against
+fn slowdown_percent(baseline_ms: f64, current_ms: f64) -> Option<f64> {
+    (baseline_ms > 0.0).then(|| (current_ms - baseline_ms) / baseline_ms * 100.0)
+}
+
+pub(crate) async fn run(args: CliArgs) -> Result<()> {
+    let params = WorkloadParams {
+        num_users: args.num_users,
+        num_epochs: args.num_epochs,
+        num_lookups: args.num_lookups,
+    };
+
+    // NOTE(new_config): Add new configurations here
+    let current = Baseline {
+        params,
+        results: vec![
+            run_workload::<akd::WhatsAppV1Configuration>(params).await?,
+            run_workload::<akd::ExperimentalConfiguration<akd::ExampleLabel>>(params).await?,
+        ],
+    };
+
+    for result in current.results.iter() {
+        println!(
+            "{}: publish = {:.3} ms, lookup = {:.3} ms, verify = {:.3} ms",
+            result.configuration, result.publish_ms, result.lookup_ms, result.verify_ms
+        );
+    }
+
+    if let Some(output) = &args.output {
+        std::fs::write(output, serde_json::to_string_pretty(&current)?)?;
+        println!("Results written to {}", output.display());
+    }
+
+    if let Some(baseline_path) = &args.baseline {
+        let baseline: Baseline = serde_json::from_str(&std::fs::read_to_string(baseline_path)?)?;
+        let regressions = compare(&baseline, &current, args.threshold)?;
+        if !regressions.is_empty() {
+            for regression in regressions.iter() {
+                println!(
+                    "REGRESSION {} {}: {:.3} ms -> {:.3} ms ({:+.1}%)",
+                    regression.configuration,
+                    regression.measurement,
+                    regression.baseline_ms,
+                    regression.current_ms,
+                    regression.slowdown_percent()
+                );
+            }
+            bail!(
+                "{} measurement(s) regressed by more than {}% relative to {}",
+                regressions.len(),
+                args.threshold,
+                baseline_path.display()
+            );
+        }
+        println!(
+            "No regressions beyond {}% relative to {}",
+            args.threshold,
+            baseline_path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Compares the current results against a baseline, returning all of the measurements
+/// which are slower than the baseline by more than `threshold` percent. Configurations
+/// which are missing from the baseline, and measurements whose baseline is zero, are
+/// skipped.
+pub(crate) fn compare(
+    baseline: &Baseline,
+    current: &Baseline,
+    threshold: f64,
+) -> Result<Vec<Regression>> {
+    if baseline.params != current.params {
+        bail!(
+            "The baseline was recorded with different workload parameters ({:?}) than the current run ({:?})",
+            baseline.params,
+            current.params
+        );
+    }
+
+    let mut regressions = vec![];
+    for result in current.results.iter() {
+        let Some(base) = baseline
+            .results
+            .iter()
+            .find(|base| base.configuration == result.configuration)
+        else {
+            println!(
+                "No baseline found for configuration {}, skipping",
+                result.configuration
+            );
+            continue;
+        };
+
+        for ((measurement, baseline_ms), (_, current_ms)) in
+            base.measurements().into_iter().zip(result.measurements())
+        {
+            match slowdown_percent(baseline_ms, current_ms) {
+                Some(slowdown) if slowdown > threshold => regressions.push(Regression {
+                    configuration: result.configuration.clone(),
+                    measurement,
+                    baseline_ms,
+                    current_ms,
+                }),
+                Some(_) => {}
+                None => println!(
+                    "The baseline of the {measurement} measurement of configuration {} is zero, skipping",
+                    result.configuration
+                ),
+            }
+        }
+    }
+    Ok(regressions)
+}
+
+async fn run_workload<TC: NamedConfiguration>(params: WorkloadParams) -> Result<WorkloadResult> {
+    let mut rng = StdRng::seed_from_u64(42);
+    let db = AsyncInMemoryDatabase::new();
+    let storage = StorageManager::new_no_cache(db);
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<TC, _, _>::new(storage, vrf).await?;
+
+    let labels = (0..params.num_users)
+        .map(|_| AkdLabel::random(&mut rng))
+        .collect::<Vec<_>>();
+
+    let mut publish_ms = 0f64;
+    for _ in 0..params.num_epochs {
+        let updates = labels
+            .iter()
+            .map(|label| (label.clone(), AkdValue::random(&mut rng)))
+            .collect::<Vec<_>>();
+        let tic = Instant::now();
+        akd.publish(updates).await?;
+        publish_ms += tic.elapsed().as_secs_f64() * 1000.0;
+    }
+
+    let vrf_pk = akd.get_public_key().await?;
+    let mut lookup_ms = 0f64;
+    let mut verify_ms = 0f64;
+    for label in labels.iter().cycle().take(params.num_lookups) {
+        let tic = Instant::now();
+        let (proof, epoch_hash) = akd.lookup(label.clone()).await?;
+        lookup_ms += tic.elapsed().as_secs_f64() * 1000.0;
+
+        let tic = Instant::now();
+        akd::client::lookup_verify::<TC>(
+            vrf_pk.as_bytes(),
+            epoch_hash.hash(),
+            epoch_hash.epoch(),
+            label.clone(),
+            proof,
+        )
+        .map_err(|err| anyhow!("Lookup proof failed to verify: {err}"))?;
+        verify_ms += tic.elapsed().as_secs_f64() * 1000.0;
+    }
+
+    Ok(WorkloadResult {
+        configuration: TC::name().to_string(),
+        publish_ms: publish_ms / params.num_epochs.max(1) as f64,
+        lookup_ms: lookup_ms / params.num_lookups.max(1) as f64,
+        verify_ms: verify_ms / params.num_lookups.max(1) as f64,
+    })
+}
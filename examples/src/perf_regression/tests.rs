@@ -0,0 +1,68 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Tests for the comparison of workload results against a baseline
+
+use super::{compare, Baseline, WorkloadParams, WorkloadResult};
+
+const PARAMS: WorkloadParams = WorkloadParams {
+    num_users: 10,
+    num_epochs: 2,
+    num_lookups: 5,
+};
+
+fn baseline_with(publish_ms: f64, lookup_ms: f64, verify_ms: f64) -> Baseline {
+    Baseline {
+        params: PARAMS,
+        results: vec![WorkloadResult {
+            configuration: "experimental".to_string(),
+            publish_ms,
+            lookup_ms,
+            verify_ms,
+        }],
+    }
+}
+
+#[test]
+fn test_compare_within_threshold() {
+    let baseline = baseline_with(100.0, 10.0, 1.0);
+    let current = baseline_with(109.0, 5.0, 1.05);
+    assert!(compare(&baseline, &current, 10.0).unwrap().is_empty());
+}
+
+#[test]
+fn test_compare_detects_regression() {
+    let baseline = baseline_with(100.0, 10.0, 1.0);
+    let current = baseline_with(100.0, 12.0, 1.0);
+    let regressions = compare(&baseline, &current, 10.0).unwrap();
+    assert_eq!(1, regressions.len());
+    assert_eq!("lookup", regressions[0].measurement);
+    assert_eq!(20.0, regressions[0].slowdown_percent().round());
+}
+
+#[test]
+fn test_compare_skips_unknown_configuration() {
+    let baseline = baseline_with(100.0, 10.0, 1.0);
+    let mut current = baseline_with(500.0, 50.0, 5.0);
+    current.results[0].configuration = "other".to_string();
+    assert!(compare(&baseline, &current, 10.0).unwrap().is_empty());
+}
+
+#[test]
+fn test_compare_skips_zero_baseline() {
+    let baseline = baseline_with(0.0, 10.0, 1.0);
+    let current = baseline_with(100.0, 10.0, 1.0);
+    assert!(compare(&baseline, &current, 10.0).unwrap().is_empty());
+}
+
+#[test]
+fn test_compare_rejects_mismatched_params() {
+    let baseline = baseline_with(100.0, 10.0, 1.0);
+    let mut current = baseline_with(100.0, 10.0, 1.0);
+    current.params.num_users += 1;
+    assert!(compare(&baseline, &current, 10.0).is_err());
+}
@@ -0,0 +1,440 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! A directory of long-lived OpenPGP and age public keys, in the style of a keyserver,
+//! in which each key carries an explicit expiry. Example command:
+//!
+//!   cargo run -p examples -- public-keys
+//!
+//! The label of a key is `<kind>:<address>` (such as `age:alice@example.com`), and its
+//! value is the encoding of a [PublishedKey]: the key along with the times from which and
+//! until which it is valid. The directory knows nothing of expiry, which is a semantic of
+//! the application layered on top of the versions of a label:
+//! * A key is renewed by publishing a new version with the same key and a later expiry,
+//!   and revoked by publishing a version which expires immediately
+//! * A client which looks a key up rejects it once it has expired, with [verify_lookup].
+//!   Since a lookup proof shows that its version is the latest one, a server cannot hide a
+//!   revocation by serving an older version with a later expiry.
+//! * A client which verifies a signature made in the past finds the key which was valid
+//!   at the time of the signature from the key history, with [validity_periods], since a
+//!   version stops being valid when it expires or when the next version is published,
+//!   whichever comes first
+//!
+//! Times are in seconds since the Unix epoch. The keys themselves are opaque to the
+//! directory: an age recipient (`age1...`) or a binary OpenPGP transferable public key.
+
+#[cfg(test)]
+mod tests;
+
+use akd::ecvrf::{HardCodedAkdVRF, VRFKeyStorage};
+use akd::storage::memory::AsyncInMemoryDatabase;
+use akd::storage::{Database, StorageManager};
+use akd::{
+    AkdLabel, AkdValue, Configuration, Directory, EpochHash, HistoryParams, HistoryProof,
+    HistoryVerificationParams, LookupProof,
+};
+use anyhow::{anyhow, bail, Result};
+use clap::Parser;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+type TC = akd::ExperimentalConfiguration<akd::ExampleLabel>;
+
+/// The version of the encoding of [PublishedKey]
+const PUBLISHED_KEY_VERSION: u8 = 1;
+/// The number of seconds in a day
+const DAY: u64 = 24 * 60 * 60;
+
+#[derive(Parser, Debug, Clone)]
+pub(crate) struct CliArgs {
+    /// The number of days for which a published key is valid
+    #[clap(long = "validity_days", default_value = "365")]
+    validity_days: u64,
+}
+
+/// The kind of a published key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum KeyKind {
+    /// An OpenPGP transferable public key
+    OpenPgp,
+    /// An age recipient
+    Age,
+}
+
+impl KeyKind {
+    /// The prefix of the labels of keys of this kind
+    fn prefix(&self) -> &'static str {
+        match self {
+            KeyKind::OpenPgp => "pgp",
+            KeyKind::Age => "age",
+        }
+    }
+
+    /// The label under which the key of this kind of an address is published
+    pub(crate) fn label(&self, address: &str) -> AkdLabel {
+        AkdLabel::from(format!("{}:{address}", self.prefix()).as_str())
+    }
+}
+
+/// A public key along with the period in which it is valid, which is the value of the
+/// key's label in the directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PublishedKey {
+    /// The time from which the key is valid
+    pub(crate) not_before: u64,
+    /// The time at which the key expires
+    pub(crate) expires_at: u64,
+    /// The public key
+    pub(crate) key: Vec<u8>,
+}
+
+impl PublishedKey {
+    /// Encodes the key into the value which is published for it:
+    ///
+    /// ```text
+    /// version (1 byte) || not before (u64) || expires at (u64) || key (remaining bytes)
+    /// ```
+    ///
+    /// with integers in big-endian order.
+    pub(crate) fn to_value(&self) -> AkdValue {
+        let mut out = vec![PUBLISHED_KEY_VERSION];
+        out.extend_from_slice(&self.not_before.to_be_bytes());
+        out.extend_from_slice(&self.expires_at.to_be_bytes());
+        out.extend_from_slice(&self.key);
+        AkdValue(out)
+    }
+
+    /// Decodes the key from the value which is published for it
+    pub(crate) fn from_value(value: &AkdValue) -> Result<Self> {
+        let (version, rest) = value
+            .0
+            .split_first()
+            .ok_or_else(|| anyhow!("The published key is empty"))?;
+        if *version != PUBLISHED_KEY_VERSION {
+            bail!("Unsupported version {version} of the published key");
+        }
+        if rest.len() < 16 {
+            bail!("The published key is truncated");
+        }
+        let (times, key) = rest.split_at(16);
+        let published = Self {
+            not_before: u64::from_be_bytes(times[..8].try_into()?),
+            expires_at: u64::from_be_bytes(times[8..].try_into()?),
+            key: key.to_vec(),
+        };
+        if published.expires_at < published.not_before {
+            bail!("The published key expires before it is valid");
+        }
+        Ok(published)
+    }
+
+    /// Checks that the key is valid at a time
+    pub(crate) fn check_valid_at(&self, time: u64) -> Result<(), KeyRejection> {
+        if time < self.not_before {
+            Err(KeyRejection::NotYetValid {
+                not_before: self.not_before,
+            })
+        } else if time >= self.expires_at {
+            Err(KeyRejection::Expired {
+                expires_at: self.expires_at,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The reason for which a client rejects a key which has been verified to be in the
+/// directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum KeyRejection {
+    /// The key is not valid yet
+    NotYetValid {
+        /// The time from which the key is valid
+        not_before: u64,
+    },
+    /// The key has expired, or has been revoked
+    Expired {
+        /// The time at which the key expired
+        expires_at: u64,
+    },
+}
+
+impl fmt::Display for KeyRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyRejection::NotYetValid { not_before } => {
+                write!(f, "The key is not valid until {not_before}")
+            }
+            KeyRejection::Expired { expires_at } => write!(f, "The key expired at {expires_at}"),
+        }
+    }
+}
+
+impl std::error::Error for KeyRejection {}
+
+/// The period in which a version of a key was valid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ValidityPeriod {
+    /// The version of the key
+    pub(crate) version: u64,
+    /// The epoch in which the version was published
+    pub(crate) epoch: u64,
+    /// The key
+    pub(crate) key: Vec<u8>,
+    /// The time from which the version was valid
+    pub(crate) from: u64,
+    /// The time until which the version was valid, which is the earlier of its expiry and
+    /// the time from which the next version is valid
+    pub(crate) until: u64,
+}
+
+/// Verifies the lookup proof of a key, and returns the key if it is valid at the time
+/// `now`. A key which has expired or is not yet valid is rejected with a [KeyRejection].
+pub(crate) fn verify_lookup<TC: Configuration>(
+    vrf_public_key: &[u8],
+    epoch_hash: &EpochHash,
+    kind: KeyKind,
+    address: &str,
+    proof: LookupProof,
+    now: u64,
+) -> Result<PublishedKey> {
+    let EpochHash(epoch, root_hash) = *epoch_hash;
+    let result = akd::client::lookup_verify::<TC>(
+        vrf_public_key,
+        root_hash,
+        epoch,
+        kind.label(address),
+        proof,
+    )
+    .map_err(|err| anyhow!("The lookup proof of {address} did not verify: {err}"))?;
+    let published = PublishedKey::from_value(&result.value)?;
+    published.check_valid_at(now)?;
+    Ok(published)
+}
+
+/// Verifies the key history of a key, and returns the validity periods of its versions,
+/// from the latest to the oldest. Versions which were superseded before becoming valid
+/// have no validity period, and are omitted.
+pub(crate) fn validity_periods<TC: Configuration>(
+    vrf_public_key: &[u8],
+    epoch_hash: &EpochHash,
+    kind: KeyKind,
+    address: &str,
+    proof: HistoryProof,
+) -> Result<Vec<ValidityPeriod>> {
+    let EpochHash(epoch, root_hash) = *epoch_hash;
+    let updates = akd::client::key_history_verify::<TC>(
+        vrf_public_key,
+        root_hash,
+        epoch,
+        kind.label(address),
+        proof,
+        HistoryVerificationParams::default(),
+    )
+    .map_err(|err| anyhow!("The key history of {address} did not verify: {err}"))?;
+
+    let mut periods = vec![];
+    let mut next_from = u64::MAX;
+    for update in updates {
+        let published = PublishedKey::from_value(&update.value)?;
+        let until = published.expires_at.min(next_from);
+        if published.not_before < until {
+            periods.push(ValidityPeriod {
+                version: update.version,
+                epoch: update.epoch,
+                key: published.key,
+                from: published.not_before,
+                until,
+            });
+        }
+        next_from = next_from.min(published.not_before);
+    }
+    Ok(periods)
+}
+
+/// Returns the key which was valid at a time, from the validity periods of a key
+pub(crate) fn key_valid_at(periods: &[ValidityPeriod], time: u64) -> Option<&ValidityPeriod> {
+    periods
+        .iter()
+        .find(|period| period.from <= time && time < period.until)
+}
+
+/// The key server, which publishes keys to the directory
+pub(crate) struct KeyServer<TC, S: Database, V> {
+    directory: Directory<TC, S, V>,
+}
+
+impl<TC, S, V> KeyServer<TC, S, V>
+where
+    TC: Configuration,
+    S: Database + 'static,
+    V: VRFKeyStorage,
+{
+    pub(crate) fn new(directory: Directory<TC, S, V>) -> Self {
+        Self { directory }
+    }
+
+    /// Publishes a key, after checking that its validity period is not empty
+    pub(crate) async fn publish(
+        &self,
+        kind: KeyKind,
+        address: &str,
+        key: &PublishedKey,
+    ) -> Result<EpochHash> {
+        if key.expires_at < key.not_before {
+            bail!("The key of {address} expires before it is valid");
+        }
+        Ok(self
+            .directory
+            .publish(vec![(kind.label(address), key.to_value())])
+            .await?)
+    }
+
+    /// Revokes a key at the time `now`, by publishing it again with an expiry of `now`
+    pub(crate) async fn revoke(&self, kind: KeyKind, address: &str, now: u64) -> Result<EpochHash> {
+        let (proof, _) = self.directory.lookup(kind.label(address)).await?;
+        let mut published = PublishedKey::from_value(&proof.value)?;
+        published.not_before = published.not_before.min(now);
+        published.expires_at = published.expires_at.min(now);
+        self.publish(kind, address, &published).await
+    }
+
+    /// Looks up a key, returning its lookup proof along with the epoch it is for
+    pub(crate) async fn lookup(
+        &self,
+        kind: KeyKind,
+        address: &str,
+    ) -> Result<(LookupProof, EpochHash)> {
+        Ok(self.directory.lookup(kind.label(address)).await?)
+    }
+
+    /// Returns the complete key history of a key, along with the epoch it is for
+    pub(crate) async fn key_history(
+        &self,
+        kind: KeyKind,
+        address: &str,
+    ) -> Result<(HistoryProof, EpochHash)> {
+        Ok(self
+            .directory
+            .key_history(&kind.label(address), HistoryParams::Complete)
+            .await?)
+    }
+
+    /// The VRF public key of the directory, which clients verify proofs with
+    pub(crate) async fn vrf_public_key(&self) -> Result<Vec<u8>> {
+        Ok(self.directory.get_public_key().await?.as_bytes().to_vec())
+    }
+}
+
+pub(crate) async fn run(args: CliArgs) -> Result<()> {
+    let storage_manager = StorageManager::new_no_cache(AsyncInMemoryDatabase::new());
+    let directory = Directory::<TC, _, _>::new(storage_manager, HardCodedAkdVRF {}).await?;
+    let server = KeyServer::new(directory);
+    let vrf_public_key = server.vrf_public_key().await?;
+    let validity = args.validity_days.max(1) * DAY;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let address = "alice@example.com";
+
+    // alice publishes an age recipient and an OpenPGP key
+    let recipient = b"age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p".to_vec();
+    server
+        .publish(
+            KeyKind::Age,
+            address,
+            &PublishedKey {
+                not_before: now,
+                expires_at: now + validity,
+                key: recipient.clone(),
+            },
+        )
+        .await?;
+    server
+        .publish(
+            KeyKind::OpenPgp,
+            address,
+            &PublishedKey {
+                not_before: now,
+                expires_at: now + validity,
+                key: rand::random::<[u8; 32]>().to_vec(),
+            },
+        )
+        .await?;
+
+    let (proof, epoch_hash) = server.lookup(KeyKind::Age, address).await?;
+    let published = verify_lookup::<TC>(
+        &vrf_public_key,
+        &epoch_hash,
+        KeyKind::Age,
+        address,
+        proof.clone(),
+        now,
+    )?;
+    println!(
+        "The age recipient of {address} is {}, valid until {}",
+        String::from_utf8_lossy(&published.key),
+        published.expires_at
+    );
+
+    // Once the validity period has passed, the same proof is rejected
+    let later = now + validity;
+    match verify_lookup::<TC>(
+        &vrf_public_key,
+        &epoch_hash,
+        KeyKind::Age,
+        address,
+        proof,
+        later,
+    ) {
+        Err(err) => println!(
+            "After {} days, the age recipient is rejected: {err}",
+            args.validity_days
+        ),
+        Ok(_) => bail!("An expired age recipient was accepted"),
+    }
+
+    // alice renews the key before it expires, and later revokes it
+    server
+        .publish(
+            KeyKind::Age,
+            address,
+            &PublishedKey {
+                not_before: now,
+                expires_at: later + validity,
+                key: recipient,
+            },
+        )
+        .await?;
+    let (proof, epoch_hash) = server.lookup(KeyKind::Age, address).await?;
+    verify_lookup::<TC>(
+        &vrf_public_key,
+        &epoch_hash,
+        KeyKind::Age,
+        address,
+        proof,
+        later,
+    )?;
+    println!(
+        "After the renewal, the age recipient is valid after {} days",
+        args.validity_days
+    );
+    server.revoke(KeyKind::Age, address, later + DAY).await?;
+
+    let (proof, epoch_hash) = server.key_history(KeyKind::Age, address).await?;
+    let periods =
+        validity_periods::<TC>(&vrf_public_key, &epoch_hash, KeyKind::Age, address, proof)?;
+    for period in periods.iter().rev() {
+        println!(
+            "Version {} (published in epoch {}) was valid from {} until {}",
+            period.version, period.epoch, period.from, period.until
+        );
+    }
+    if key_valid_at(&periods, later + 2 * DAY).is_some() {
+        bail!("A revoked age recipient is still valid");
+    }
+    Ok(())
+}
@@ -0,0 +1,250 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This source code is dual-licensed under either the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree or the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree. You may select, at your option, one of the above-listed licenses.
+
+//! Tests for the public key directory
+
+use super::*;
+use crate::test_config;
+
+type TestKeyServer<TC> = KeyServer<TC, AsyncInMemoryDatabase, HardCodedAkdVRF>;
+
+const ADDRESS: &str = "alice@example.com";
+const NOW: u64 = 1_700_000_000;
+
+async fn new_key_server<TC: Configuration>() -> (TestKeyServer<TC>, Vec<u8>) {
+    let storage_manager = StorageManager::new_no_cache(AsyncInMemoryDatabase::new());
+    let directory = Directory::<TC, _, _>::new(storage_manager, HardCodedAkdVRF {})
+        .await
+        .unwrap();
+    let server = KeyServer::new(directory);
+    let vrf_public_key = server.vrf_public_key().await.unwrap();
+    (server, vrf_public_key)
+}
+
+fn published_key(key: &[u8], not_before: u64, expires_at: u64) -> PublishedKey {
+    PublishedKey {
+        not_before,
+        expires_at,
+        key: key.to_vec(),
+    }
+}
+
+fn rejection(result: Result<PublishedKey>) -> KeyRejection {
+    result
+        .unwrap_err()
+        .downcast::<KeyRejection>()
+        .expect("a key rejection")
+}
+
+#[test]
+fn test_published_key_encoding() {
+    let key = published_key(b"age1recipient", NOW, NOW + DAY);
+    assert_eq!(1 + 8 + 8 + 13, key.to_value().0.len());
+    assert_eq!(key, PublishedKey::from_value(&key.to_value()).unwrap());
+
+    let value = key.to_value().0;
+    for malformed in [
+        value[..16].to_vec(),
+        [&[2], &value[1..]].concat(),
+        published_key(b"key", NOW, NOW - 1).to_value().0,
+        vec![],
+    ] {
+        assert!(PublishedKey::from_value(&AkdValue(malformed)).is_err());
+    }
+
+    // the validity period includes its start, but not its end
+    assert_eq!(
+        Err(KeyRejection::NotYetValid { not_before: NOW }),
+        key.check_valid_at(NOW - 1)
+    );
+    assert_eq!(Ok(()), key.check_valid_at(NOW));
+    assert_eq!(Ok(()), key.check_valid_at(NOW + DAY - 1));
+    assert_eq!(
+        Err(KeyRejection::Expired {
+            expires_at: NOW + DAY
+        }),
+        key.check_valid_at(NOW + DAY)
+    );
+}
+
+test_config!(test_lookup_rejects_expired_keys);
+async fn test_lookup_rejects_expired_keys<TC: Configuration>() {
+    let (server, vrf_public_key) = new_key_server::<TC>().await;
+    let key = published_key(b"age1recipient", NOW, NOW + DAY);
+    server.publish(KeyKind::Age, ADDRESS, &key).await.unwrap();
+
+    let (proof, epoch_hash) = server.lookup(KeyKind::Age, ADDRESS).await.unwrap();
+    let verify = |proof: LookupProof, kind: KeyKind, now: u64| {
+        verify_lookup::<TC>(&vrf_public_key, &epoch_hash, kind, ADDRESS, proof, now)
+    };
+    assert_eq!(key, verify(proof.clone(), KeyKind::Age, NOW).unwrap());
+    assert_eq!(
+        KeyRejection::Expired {
+            expires_at: NOW + DAY
+        },
+        rejection(verify(proof.clone(), KeyKind::Age, NOW + DAY))
+    );
+    assert_eq!(
+        KeyRejection::NotYetValid { not_before: NOW },
+        rejection(verify(proof.clone(), KeyKind::Age, NOW - 1))
+    );
+
+    // the kind of the key is bound by its label
+    assert!(verify(proof, KeyKind::OpenPgp, NOW).is_err());
+}
+
+test_config!(test_renewal_and_revocation);
+async fn test_renewal_and_revocation<TC: Configuration>() {
+    let (server, vrf_public_key) = new_key_server::<TC>().await;
+    server
+        .publish(
+            KeyKind::OpenPgp,
+            ADDRESS,
+            &published_key(b"pgp", NOW, NOW + DAY),
+        )
+        .await
+        .unwrap();
+    let (stale_proof, _) = server.lookup(KeyKind::OpenPgp, ADDRESS).await.unwrap();
+
+    // a renewal extends the validity of the key
+    let renewed = published_key(b"pgp", NOW, NOW + 10 * DAY);
+    server
+        .publish(KeyKind::OpenPgp, ADDRESS, &renewed)
+        .await
+        .unwrap();
+    let (proof, epoch_hash) = server.lookup(KeyKind::OpenPgp, ADDRESS).await.unwrap();
+    assert_eq!(
+        renewed,
+        verify_lookup::<TC>(
+            &vrf_public_key,
+            &epoch_hash,
+            KeyKind::OpenPgp,
+            ADDRESS,
+            proof,
+            NOW + 2 * DAY
+        )
+        .unwrap()
+    );
+
+    // a revocation ends it, and the server cannot hide it by serving an older version
+    let EpochHash(revoked_in, _) = server
+        .revoke(KeyKind::OpenPgp, ADDRESS, NOW + 3 * DAY)
+        .await
+        .unwrap();
+    let (proof, epoch_hash) = server.lookup(KeyKind::OpenPgp, ADDRESS).await.unwrap();
+    assert_eq!(
+        KeyRejection::Expired {
+            expires_at: NOW + 3 * DAY
+        },
+        rejection(verify_lookup::<TC>(
+            &vrf_public_key,
+            &epoch_hash,
+            KeyKind::OpenPgp,
+            ADDRESS,
+            proof,
+            NOW + 4 * DAY
+        ))
+    );
+    assert!(verify_lookup::<TC>(
+        &vrf_public_key,
+        &epoch_hash,
+        KeyKind::OpenPgp,
+        ADDRESS,
+        stale_proof,
+        NOW
+    )
+    .is_err());
+
+    let (proof, epoch_hash) = server.key_history(KeyKind::OpenPgp, ADDRESS).await.unwrap();
+    let periods = validity_periods::<TC>(
+        &vrf_public_key,
+        &epoch_hash,
+        KeyKind::OpenPgp,
+        ADDRESS,
+        proof,
+    )
+    .unwrap();
+    // the renewal and the revocation supersede the earlier versions from their start
+    assert_eq!(1, periods.len());
+    assert_eq!(revoked_in, periods[0].epoch);
+    assert_eq!((NOW, NOW + 3 * DAY), (periods[0].from, periods[0].until));
+    assert_eq!(None, key_valid_at(&periods, NOW + 3 * DAY));
+    assert_eq!(
+        Some(3),
+        key_valid_at(&periods, NOW).map(|period| period.version)
+    );
+}
+
+test_config!(test_validity_periods_of_rotations);
+async fn test_validity_periods_of_rotations<TC: Configuration>() {
+    let (server, vrf_public_key) = new_key_server::<TC>().await;
+    server
+        .publish(
+            KeyKind::Age,
+            ADDRESS,
+            &published_key(b"first", NOW, NOW + 10 * DAY),
+        )
+        .await
+        .unwrap();
+    // the second key supersedes the first from the time it is valid
+    server
+        .publish(
+            KeyKind::Age,
+            ADDRESS,
+            &published_key(b"second", NOW + 5 * DAY, NOW + 20 * DAY),
+        )
+        .await
+        .unwrap();
+    // the third key is superseded by the fourth before it is ever valid
+    server
+        .publish(
+            KeyKind::Age,
+            ADDRESS,
+            &published_key(b"third", NOW + 30 * DAY, NOW + 40 * DAY),
+        )
+        .await
+        .unwrap();
+    server
+        .publish(
+            KeyKind::Age,
+            ADDRESS,
+            &published_key(b"fourth", NOW + 15 * DAY, NOW + 40 * DAY),
+        )
+        .await
+        .unwrap();
+
+    let (proof, epoch_hash) = server.key_history(KeyKind::Age, ADDRESS).await.unwrap();
+    let periods =
+        validity_periods::<TC>(&vrf_public_key, &epoch_hash, KeyKind::Age, ADDRESS, proof).unwrap();
+    assert_eq!(
+        vec![
+            (b"fourth".to_vec(), NOW + 15 * DAY, NOW + 40 * DAY),
+            (b"second".to_vec(), NOW + 5 * DAY, NOW + 15 * DAY),
+            (b"first".to_vec(), NOW, NOW + 5 * DAY),
+        ],
+        periods
+            .iter()
+            .map(|period| (period.key.clone(), period.from, period.until))
+            .collect::<Vec<_>>()
+    );
+    let key_at = |time| key_valid_at(&periods, time).map(|period| period.key.clone());
+    assert_eq!(None, key_at(NOW - 1));
+    assert_eq!(Some(b"first".to_vec()), key_at(NOW + 5 * DAY - 1));
+    assert_eq!(Some(b"second".to_vec()), key_at(NOW + 5 * DAY));
+    assert_eq!(Some(b"fourth".to_vec()), key_at(NOW + 35 * DAY));
+    assert_eq!(None, key_at(NOW + 40 * DAY));
+
+    // publishing a key which expires before it is valid is refused
+    assert!(server
+        .publish(
+            KeyKind::Age,
+            ADDRESS,
+            &published_key(b"fifth", NOW, NOW - 1)
+        )
+        .await
+        .is_err());
+}
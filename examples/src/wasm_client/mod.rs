@@ -138,7 +138,6 @@ fn lookup_verify<TC: Configuration>(
 }
 
 /// NOTE(new_config): Add a new configuration here
-
 /// Verify a lookup proof in WebAssembly for WhatsAppV1Configuration,
 /// utilizing serde serialized structure for the proof
 #[allow(unused)]